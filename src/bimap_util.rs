@@ -6,17 +6,16 @@ use std::collections::BTreeMap;
 #[cfg(not(feature = "std"))]
 use alloc::collections::btree_map::BTreeMap;
 
-use std::hash::Hash;
-
-use bimap::BiMap;
+use bimap::BiBTreeMap;
 use itertools::enumerate;
 
-/// Given two lists which are permutations of one another, creates a BiMap which maps an index in
-/// one list to an index in the other list with the same associated value.
+/// Given two lists which are permutations of one another, creates a bijection which maps an index
+/// in one list to an index in the other list with the same associated value. The bijection is
+/// ordered, rather than hashed, so that routing doesn't depend on a randomly seeded hasher.
 ///
 /// If the lists contain duplicates, then multiple permutations with this property exist, and an
 /// arbitrary one of them will be returned.
-pub fn bimap_from_lists<T: Eq + Ord + Hash>(a: Vec<T>, b: Vec<T>) -> BiMap<usize, usize> {
+pub fn bimap_from_lists<T: Ord>(a: Vec<T>, b: Vec<T>) -> BiBTreeMap<usize, usize> {
     assert_eq!(a.len(), b.len(), "Vectors differ in length");
 
     let mut b_values_to_indices = BTreeMap::new();
@@ -24,7 +23,7 @@ pub fn bimap_from_lists<T: Eq + Ord + Hash>(a: Vec<T>, b: Vec<T>) -> BiMap<usize
         b_values_to_indices.entry(value).or_insert_with(Vec::new).push(i);
     }
 
-    let mut bimap = BiMap::new();
+    let mut bimap = BiBTreeMap::new();
     for (i, value) in enumerate(a) {
         if let Some(j) = b_values_to_indices.get_mut(&value).and_then(Vec::pop) {
             bimap.insert(i, j);
@@ -62,9 +61,9 @@ mod tests {
         let first = vec!['a', 'a', 'b'];
         let second = vec!['a', 'b', 'a'];
         let bimap = bimap_from_lists(first.clone(), second.clone());
-        for i in 0..3 {
+        for (i, value) in first.iter().enumerate() {
             let j = *bimap.get_by_left(&i).unwrap();
-            assert_eq!(first[i], second[j]);
+            assert_eq!(*value, second[j]);
         }
     }
 
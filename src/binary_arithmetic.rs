@@ -1,53 +1,116 @@
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(not(feature = "std"))]
+use alloc::collections::vec_deque::VecDeque;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
 use itertools::Itertools;
 use num::BigUint;
 use num_traits::{One, Zero};
 
-use crate::expression::{BinaryExpression, Expression};
+use crate::expression::{BinaryExpression, BooleanExpression, Expression};
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
 use crate::wire_values::WireValues;
 
+/// How `binary_summation_with_strategy` should add its terms.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SummationStrategy {
+    /// Join each term into a field element, add them, and split the sum. This is the cheapest
+    /// strategy, but it requires the sum to fit in a field element.
+    JoinAndSplit,
+    /// Add the terms column by column, as in schoolbook addition; see
+    /// `binary_summation_columnwise`.
+    Columnwise,
+    /// Add the terms with a tree of carry-save adders; see `binary_summation_csa`.
+    CarrySave,
+}
+
 impl<F: Field> GadgetBuilder<F> {
     /// Add two binary expressions in a widening manner. The result will be one bit longer than the
     /// longer of the two inputs.
     pub fn binary_sum(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BinaryExpression<F> {
-        self.binary_summation(&[x.clone(), y.clone()])
+        logged!(self, "binary_sum", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.binary_summation(&[x.clone(), y.clone()])
+        })
     }
 
     /// Add two binary expressions, ignoring any overflow.
     pub fn binary_sum_ignoring_overflow(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BinaryExpression<F> {
-        self.binary_summation_ignoring_overflow(&[x.clone(), y.clone()])
+        logged!(self, "binary_sum_ignoring_overflow",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.binary_summation_ignoring_overflow(&[x.clone(), y.clone()])
+        })
     }
 
     /// Add two binary expressions while asserting that overflow does not occur.
     pub fn binary_sum_asserting_no_overflow(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BinaryExpression<F> {
-        self.binary_summation_asserting_no_overflow(&[x.clone(), y.clone()])
+        logged!(self, "binary_sum_asserting_no_overflow",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.binary_summation_asserting_no_overflow(&[x.clone(), y.clone()])
+        })
     }
 
     /// Add an arbitrary number of binary expressions. The result will be at least one bit longer than the
     /// longest input.
+    ///
+    /// If the sum could be too large to fit in a field element, this delegates to
+    /// `binary_summation_columnwise`.
     pub fn binary_summation(&mut self, terms: &[BinaryExpression<F>]) -> BinaryExpression<F> {
-        // We will non-deterministically generate the sum bits, join the binary expressions, and
-        // verify the summation on those field elements.
+        logged!(self, "binary_summation", [terms.len()], {
+            let strategy = if Self::max_sum_bits(terms) < Element::<F>::max_bits() {
+                SummationStrategy::JoinAndSplit
+            } else {
+                SummationStrategy::Columnwise
+            };
+            self.binary_summation_with_strategy(terms, strategy)
+        })
+    }
 
+    /// Add an arbitrary number of binary expressions using the given strategy, rather than letting
+    /// `binary_summation` choose one. This is mainly useful for comparing the costs of strategies.
+    /// The result has enough bits to hold any sum of the terms. Panics if the strategy is
+    /// `JoinAndSplit` but the sum could be too large to fit in a field element.
+    pub fn binary_summation_with_strategy(
+        &mut self, terms: &[BinaryExpression<F>], strategy: SummationStrategy,
+    ) -> BinaryExpression<F> {
+        logged!(self, "binary_summation_with_strategy",
+                [terms.len(), format!("{:?}", strategy)], {
+            let sum_bits = Self::max_sum_bits(terms);
+            match strategy {
+                SummationStrategy::JoinAndSplit =>
+                    self.binary_summation_join_and_split(terms, sum_bits),
+                SummationStrategy::Columnwise => self.binary_summation_columnwise(terms),
+                SummationStrategy::CarrySave => self.binary_summation_csa(terms, sum_bits),
+            }
+        })
+    }
+
+    /// The number of bits needed to hold any sum of the given terms.
+    fn max_sum_bits(terms: &[BinaryExpression<F>]) -> usize {
         let mut max_sum = BigUint::zero();
         for term in terms {
             let max_term = (BigUint::one() << term.len()) - BigUint::one();
             max_sum += max_term;
         }
-        let sum_bits = max_sum.bits() as usize;
+        max_sum.bits() as usize
+    }
 
-        // TODO: Generalize this addition function to support larger operands.
-        // We can split the bits into chunks and perform addition on joined chunks.
+    fn binary_summation_join_and_split(
+        &mut self, terms: &[BinaryExpression<F>], sum_bits: usize,
+    ) -> BinaryExpression<F> {
         assert!(sum_bits < Element::<F>::max_bits(),
-                "Binary operands are too large to fit an a field element.");
+                "Binary terms are too large for their sum to fit in a field element.");
 
+        // We will non-deterministically generate the sum bits, join the binary expressions, and
+        // verify the summation on those field elements.
         let sum_wire = self.binary_wire(sum_bits);
         let sum = BinaryExpression::from(&sum_wire);
 
@@ -55,58 +118,244 @@ impl<F: Field> GadgetBuilder<F> {
             &terms.iter().map(BinaryExpression::join).collect_vec());
         self.assert_equal(&sum_of_terms, &sum.join());
 
-        self.generator(
+        self.generator_with_outputs(
             sum_of_terms.dependencies(),
+            sum_wire.bits.iter().map(|bit| bit.wire()).collect(),
             move |values: &mut WireValues<F>| {
+                // If the terms aren't truly binary, the sum might not fit in `sum_bits` bits. In
+                // that case we assign only the low bits, so that generation still completes but
+                // the summation constraint will not be satisfied.
                 let sum_element = sum_of_terms.evaluate(values);
-                let sum_biguint = sum_element.to_biguint();
-                values.set_binary_unsigned(&sum_wire, sum_biguint);
+                let sum_mask = (BigUint::one() << sum_bits) - BigUint::one();
+                let sum_biguint = sum_element.to_biguint() & sum_mask;
+                values.set_binary_unsigned(&sum_wire, &sum_biguint);
             },
         );
 
         sum
     }
 
+    /// Add an arbitrary number of binary expressions column by column, as in schoolbook addition.
+    /// The result has enough bits to hold any sum of the terms.
+    ///
+    /// Each column's bits are added to the carry from the previous column, and only that small
+    /// sum is split, using exactly as many bits as its maximum value requires. The lowest bit of
+    /// the split is the result bit, and the remaining bits form the carry into the next column.
+    /// With `k` terms, each column sum is less than `2k`, so this works for arbitrarily wide terms
+    /// in any field with more than `2k` elements. A column whose sum is at most 1, such as a lone
+    /// bit with no carry, needs no split.
+    ///
+    /// With `k` terms of `w` bits each, this costs about `w (log2(k) + 2)` constraints, compared
+    /// to roughly `w + log2(k) + 1` for joining and splitting, so it is only worthwhile when the
+    /// sum doesn't fit in a field element.
+    pub fn binary_summation_columnwise(
+        &mut self, terms: &[BinaryExpression<F>],
+    ) -> BinaryExpression<F> {
+        logged!(self, "binary_summation_columnwise", [terms.len()], {
+            let width = terms.iter().fold(0, |x, y| x.max(y.len()));
+            let mut bits = Vec::new();
+            let mut carry = Expression::zero();
+            let mut max_carry = BigUint::zero();
+
+            let mut i = 0;
+            while i < width || !max_carry.is_zero() {
+                let column: Vec<&BooleanExpression<F>> = terms.iter()
+                    .filter_map(|term| term.bits.get(i))
+                    .collect();
+                let mut max_sum = max_carry.clone();
+                for bit in &column {
+                    max_sum += match bit.expression().as_constant() {
+                        Some(c) => c.to_biguint().clone(),
+                        None => BigUint::one(),
+                    };
+                }
+                let mut column_sum = carry;
+                for bit in column {
+                    column_sum += bit.expression();
+                }
+
+                if max_sum <= BigUint::one() {
+                    bits.push(BooleanExpression::new_unsafe(column_sum));
+                    carry = Expression::zero();
+                } else {
+                    let column_bits = max_sum.bits() as usize;
+                    assert!(column_bits < Element::<F>::max_bits(),
+                            "Too many terms for their column sums to fit in a field element.");
+                    let split = self.split_bounded(&column_sum, column_bits);
+                    bits.push(split.bits[0].clone());
+                    carry = BinaryExpression { bits: split.bits[1..].to_vec() }.join();
+                }
+                max_carry = max_sum >> 1;
+                i += 1;
+            }
+
+            BinaryExpression { bits }
+        })
+    }
+
+    /// Add an arbitrary number of binary expressions modulo `2^output_bits`, using a tree of
+    /// carry-save adders. Each term is truncated or zero-padded to `output_bits` bits, and the
+    /// result has exactly `output_bits` bits.
+    ///
+    /// Unlike `binary_summation`, this never joins the terms into a field element, so it works
+    /// regardless of the field size. However, since field additions are free in R1CS, it is only
+    /// cheaper when the field is too small for `binary_summation`'s approach. With `n >= 2` terms
+    /// and `w = output_bits`, it uses `n - 2` 3-to-2 compressors of at most `2w` constraints each,
+    /// plus a final addition of `w + 2` constraints if `w + 1` bits fit in a field element, or at
+    /// most `2w` constraints otherwise. That is at most `2w(n - 1)` constraints in total, compared
+    /// to roughly `w + log2(n) + 1` for `binary_summation`.
+    pub fn binary_summation_csa(
+        &mut self, terms: &[BinaryExpression<F>], output_bits: usize,
+    ) -> BinaryExpression<F> {
+        logged!(self, "binary_summation_csa", [terms.len(), output_bits], {
+            let mut queue: VecDeque<BinaryExpression<F>> = terms.iter()
+                .map(|term| Self::resize(term, output_bits))
+                .collect();
+
+            // Each 3-to-2 compressor replaces three terms with two terms of the same sum.
+            while queue.len() > 2 {
+                let (x, y, z) = (queue.pop_front().unwrap(), queue.pop_front().unwrap(),
+                                 queue.pop_front().unwrap());
+                let mut sum_bits = Vec::with_capacity(output_bits);
+                let mut carry_bits = vec![BooleanExpression::_false()];
+                for i in 0..output_bits {
+                    let (sum, carry) = self.full_adder(&x.bits[i], &y.bits[i], &z.bits[i]);
+                    sum_bits.push(sum);
+                    carry_bits.push(carry);
+                }
+                // The carries are shifted by one bit; the most significant carry overflows.
+                carry_bits.truncate(output_bits);
+                queue.push_back(BinaryExpression { bits: sum_bits });
+                queue.push_back(BinaryExpression { bits: carry_bits });
+            }
+
+            match (queue.pop_front(), queue.pop_front()) {
+                (None, _) => BinaryExpression::from(BigUint::zero()).padded(output_bits),
+                (Some(x), None) => x,
+                (Some(x), Some(y)) if output_bits + 1 < Element::<F>::max_bits() => {
+                    let mut sum = self.binary_sum(&x, &y);
+                    sum.truncate(output_bits);
+                    sum
+                }
+                (Some(x), Some(y)) => {
+                    // The sum doesn't fit in a field element, so use a ripple-carry adder.
+                    let mut carry = BooleanExpression::_false();
+                    let mut bits = Vec::with_capacity(output_bits);
+                    for i in 0..output_bits {
+                        let (sum, carry_out) = self.full_adder(&x.bits[i], &y.bits[i], &carry);
+                        bits.push(sum);
+                        carry = carry_out;
+                    }
+                    BinaryExpression { bits }
+                }
+            }
+        })
+    }
+
+    /// Add three bits, returning the sum bit and the carry bit. This uses two constraints, or fewer
+    /// if any inputs are constant.
+    fn full_adder(
+        &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>, z: &BooleanExpression<F>,
+    ) -> (BooleanExpression<F>, BooleanExpression<F>) {
+        let (x, y, z) = (x.expression(), y.expression(), z.expression());
+        let xy = self.product(x, y);
+        let x_xor_y = x + y - &xy * 2u128;
+        let x_xor_y_z = self.product(&x_xor_y, z);
+        let sum = &x_xor_y + z - &x_xor_y_z * 2u128;
+        // The carry is the majority of the three bits. The two products can't both be 1, since if
+        // x and y are both 1, their exclusive disjunction is 0.
+        let carry = xy + x_xor_y_z;
+        (BooleanExpression::new_unsafe(sum), BooleanExpression::new_unsafe(carry))
+    }
+
+    /// Truncate or zero-pad `x` to exactly `bits` bits.
+    fn resize(x: &BinaryExpression<F>, bits: usize) -> BinaryExpression<F> {
+        if x.len() > bits {
+            x.truncated(bits)
+        } else {
+            x.padded(bits)
+        }
+    }
+
     /// Add an arbitrary number of binary expressions, ignoring any overflow.
     pub fn binary_summation_ignoring_overflow(&mut self, terms: &[BinaryExpression<F>])
                                               -> BinaryExpression<F> {
-        let input_bits = terms.iter().fold(0, |x, y| x.max(y.len()));
-        let mut sum = self.binary_summation(terms);
-        sum.truncate(input_bits);
-        sum
+        logged!(self, "binary_summation_ignoring_overflow", [terms.len()], {
+            let input_bits = terms.iter().fold(0, |x, y| x.max(y.len()));
+            let mut sum = self.binary_summation(terms);
+            sum.truncate(input_bits);
+            sum
+        })
     }
 
     /// Add an arbitrary number of binary expressions, asserting that overflow does not occur.
     pub fn binary_summation_asserting_no_overflow(&mut self, terms: &[BinaryExpression<F>])
                                                   -> BinaryExpression<F> {
-        let input_bits = terms.iter().fold(0, |x, y| x.max(y.len()));
-        let mut sum = self.binary_summation(terms);
-        let carry = BinaryExpression { bits: sum.bits[input_bits..].to_vec() };
-        self.binary_assert_zero(&carry);
-        sum.truncate(input_bits);
-        sum
+        logged!(self, "binary_summation_asserting_no_overflow", [terms.len()], {
+            let input_bits = terms.iter().fold(0, |x, y| x.max(y.len()));
+            let mut sum = self.binary_summation(terms);
+            let carry = BinaryExpression { bits: sum.bits[input_bits..].to_vec() };
+            self.binary_assert_zero(&carry);
+            sum.truncate(input_bits);
+            sum
+        })
+    }
+
+    /// Multiply two binary expressions in a widening manner. The result will have `x.len() +
+    /// y.len()` bits, which is enough to hold any product.
+    pub fn binary_product(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "binary_product", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            let product_bits = x.len() + y.len();
+            // TODO: Generalize this to support larger operands, e.g. by multiplying chunks.
+            assert!(product_bits < Element::<F>::max_bits(),
+                    "Binary operands are too large for their product to fit in a field element.");
+            let product = self.product(&x.join(), &y.join());
+            self.split_bounded(&product, product_bits)
+        })
+    }
+
+    /// Multiply two binary expressions, ignoring any overflow. The result will have as many bits
+    /// as the longer of the two inputs.
+    pub fn binary_product_ignoring_overflow(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "binary_product_ignoring_overflow",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            let mut product = self.binary_product(x, y);
+            product.truncate(x.len().max(y.len()));
+            product
+        })
     }
 
     /// Assert that a binary expression is zero.
     pub fn binary_assert_zero(&mut self, x: &BinaryExpression<F>) {
-        // The expression may be too large to fit in a single field element, so we will join chunks
-        // and assert that each chunk is zero. The chunk size is chosen such that overflow is
-        // impossible, even if all bits are 1.
-        let bits = Element::<F>::max_bits() - 1;
-        for chunk in x.chunks(bits) {
-            self.assert_zero(&chunk.join());
-        }
+        logged!(self, "binary_assert_zero", [x.join_allowing_overflow()], {
+            // The expression may be too large to fit in a single field element, so we will join
+            // chunks and assert that each chunk is zero. The chunk size is chosen such that
+            // overflow is impossible, even if all bits are 1.
+            let bits = Element::<F>::max_bits() - 1;
+            for chunk in x.chunks(bits) {
+                self.assert_zero(&chunk.join());
+            }
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use num::BigUint;
-    use num_traits::Zero;
+    use num_traits::{One, Zero};
 
+    use crate::binary_arithmetic::SummationStrategy;
     use crate::expression::BinaryExpression;
+    use crate::field::Bn128;
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::lcg::LCG;
+    use crate::fields::small::F257;
+    use crate::wire::BinaryWire;
+    use crate::wire_values::WireValues;
 
     #[test]
     fn binary_sum() {
@@ -129,6 +378,175 @@ mod tests {
         assert_eq!(BigUint::from(21u8), sum.evaluate(&values));
     }
 
+    #[test]
+    fn binary_product() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.binary_wire(4);
+        let y = builder.binary_wire(3);
+        let product = builder.binary_product(
+            &BinaryExpression::from(&x), &BinaryExpression::from(&y));
+        let wrapped = builder.binary_product_ignoring_overflow(
+            &BinaryExpression::from(&x), &BinaryExpression::from(&y));
+        let gadget = builder.build();
+        assert_eq!(7, product.len());
+        assert_eq!(4, wrapped.len());
+
+        // 13 * 7 = 91 = 0b1011011.
+        let mut values = binary_unsigned_values!(
+            &x => &BigUint::from(13u8), &y => &BigUint::from(7u8));
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(91u8), product.evaluate(&values));
+        assert_eq!(BigUint::from(0b1011u8), wrapped.evaluate(&values));
+    }
+
+    #[test]
+    fn binary_summation_csa_matches_biguint() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let wires: Vec<BinaryWire> = (0..7).map(|_i| builder.binary_wire(32)).collect();
+        let terms: Vec<BinaryExpression<Bn128>> =
+            wires.iter().map(BinaryExpression::from).collect();
+        let sum = builder.binary_summation_csa(&terms, 35);
+        let wrapped = builder.binary_summation_csa(&terms, 32);
+        let gadget = builder.build();
+
+        let mut lcg = LCG::new();
+        for _trial in 0..3 {
+            let mut values = WireValues::new();
+            let mut expected = BigUint::zero();
+            for wire in &wires {
+                let value = BigUint::from(lcg.next_u32());
+                values.set_binary_unsigned(wire, &value);
+                expected += value;
+            }
+            assert!(gadget.execute(&mut values));
+            assert_eq!(expected, sum.evaluate(&values));
+            assert_eq!(expected % (BigUint::one() << 32), wrapped.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn binary_summation_small_field() {
+        // The sum of three 8-bit values needs 10 bits, which doesn't fit in an element of F_257.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BinaryWire> = (0..3).map(|_i| builder.binary_wire(8)).collect();
+        let terms: Vec<BinaryExpression<F257>> = wires.iter().map(BinaryExpression::from).collect();
+        let sum = builder.binary_summation(&terms);
+        let gadget = builder.build();
+        assert_eq!(10, sum.len());
+
+        let mut values = WireValues::new();
+        for (wire, value) in wires.iter().zip([255u32, 254, 200].iter()) {
+            values.set_binary_unsigned(wire, &BigUint::from(*value));
+        }
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(709u32), sum.evaluate(&values));
+    }
+
+    #[test]
+    fn binary_summation_csa_cost() {
+        let sizes = |csa: bool| {
+            let mut builder = GadgetBuilder::<Bn128>::new();
+            let terms: Vec<BinaryExpression<Bn128>> = (0..7)
+                .map(|_i| BinaryExpression::from(&builder.binary_wire(32)))
+                .collect();
+            if csa {
+                builder.binary_summation_csa(&terms, 35);
+            } else {
+                builder.binary_summation(&terms);
+            }
+            // Subtract the booleanity constraints of the input bits.
+            builder.build().size() - 7 * 32
+        };
+
+        // Field additions are free, so when the sum fits in a field element, joining and splitting
+        // is far cheaper than a carry-save adder tree. The CSA tree uses 5 compressors of up to
+        // 2 * 35 constraints, plus a final addition of 35 + 2 constraints. Full adders involving
+        // constant zero bits, from padding or from the shifted carries, are cheaper, saving 31.
+        assert_eq!(35 + 1, sizes(false));
+        assert_eq!(5 * 2 * 35 + 35 + 2 - 31, sizes(true));
+    }
+
+    #[test]
+    fn binary_summation_columnwise_wide_terms() {
+        // The sum of twenty 200-bit values is far too large for F_257, but each column sum fits.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BinaryWire> = (0..20).map(|_i| builder.binary_wire(200)).collect();
+        let terms: Vec<BinaryExpression<F257>> = wires.iter().map(BinaryExpression::from).collect();
+        let sum = builder.binary_summation(&terms);
+        let gadget = builder.build();
+        assert_eq!(205, sum.len());
+
+        let mut lcg = LCG::new();
+        for trial in 0..2 {
+            let mut values = WireValues::new();
+            let mut expected = BigUint::zero();
+            for wire in &wires {
+                // In the first trial, every bit is set, to exercise the largest carries.
+                let value = if trial == 0 {
+                    (BigUint::one() << 200) - BigUint::one()
+                } else {
+                    (0..7).fold(BigUint::zero(), |acc, _i| (acc << 32) + lcg.next_u32())
+                        % (BigUint::one() << 200)
+                };
+                values.set_binary_unsigned(wire, &value);
+                expected += value;
+            }
+            assert!(gadget.execute(&mut values));
+            assert_eq!(expected, sum.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn binary_summation_strategies_agree() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let widths = [5, 8, 3, 8, 1];
+        let wires: Vec<BinaryWire> = widths.iter().map(|&w| builder.binary_wire(w)).collect();
+        let terms: Vec<BinaryExpression<Bn128>> =
+            wires.iter().map(BinaryExpression::from).collect();
+        let sums: Vec<BinaryExpression<Bn128>> = [
+            SummationStrategy::JoinAndSplit,
+            SummationStrategy::Columnwise,
+            SummationStrategy::CarrySave,
+        ].iter()
+            .map(|&strategy| builder.binary_summation_with_strategy(&terms, strategy))
+            .collect();
+        let gadget = builder.build();
+        assert!(sums.iter().all(|sum| sum.len() == 10));
+
+        let mut lcg = LCG::new();
+        for _trial in 0..5 {
+            let mut values = WireValues::new();
+            let mut expected = BigUint::zero();
+            for (wire, &w) in wires.iter().zip(widths.iter()) {
+                let value = BigUint::from(lcg.next_u32()) % (BigUint::one() << w);
+                values.set_binary_unsigned(wire, &value);
+                expected += value;
+            }
+            assert!(gadget.execute(&mut values));
+            for sum in &sums {
+                assert_eq!(expected, sum.evaluate(&values));
+            }
+        }
+    }
+
+    #[test]
+    fn binary_sum_non_binary_inputs() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.binary_wire(4);
+        let y = builder.binary_wire(4);
+        builder.binary_sum(&BinaryExpression::from(&x), &BinaryExpression::from(&y));
+        let gadget = builder.build();
+
+        // Assign 100 to x's least significant "bit", so that the sum doesn't fit in 5 bits. The
+        // constraints should not be satisfiable, but witness generation should not panic.
+        let mut values = values!(x.bits[0].wire() => 100u8.into());
+        for i in 1..4 {
+            values.set_boolean(x.bits[i], false);
+        }
+        values.set_binary_unsigned(&y, &BigUint::zero());
+        assert!(!gadget.execute(&mut values));
+    }
+
     #[test]
     fn binary_sum_ignoring_overflow() {
         let mut builder = GadgetBuilder::<F257>::new();
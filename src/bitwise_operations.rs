@@ -6,48 +6,90 @@ use crate::field::Field;
 use crate::gadget_builder::GadgetBuilder;
 
 impl<F: Field> GadgetBuilder<F> {
-    /// The bitwise negation of a binary expression `x`, a.k.a. `~x`.
+    /// The bitwise negation of a binary expression `x`, a.k.a. `~x`. This is equivalent to
+    /// `x.not()`, which doesn't require a builder.
     pub fn bitwise_not(&mut self, x: &BinaryExpression<F>) -> BinaryExpression<F> {
-        let bits = x.bits.iter()
-            .map(|w| self.not(w))
-            .collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_not", [x.join_allowing_overflow()], {
+            x.not()
+        })
     }
 
     /// The bitwise conjunction of two binary expressions `x` and `y`, a.k.a. `x & y`.
     pub fn bitwise_and(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BinaryExpression<F> {
-        assert_eq!(x.len(), y.len());
-        let l = x.len();
-        let bits = (0..l).map(|i|
-            self.and(&x.bits[i], &y.bits[i])
-        ).collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_and", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            let l = x.len();
+            let bits = (0..l).map(|i|
+                self.and(&x.bits[i], &y.bits[i])
+            ).collect();
+            BinaryExpression { bits }
+        })
     }
 
     /// The bitwise disjunction of two binary expressions `x` and `y`, a.k.a. `x | y`.
     pub fn bitwise_or(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BinaryExpression<F> {
-        assert_eq!(x.len(), y.len());
-        let l = x.len();
-        let bits = (0..l).map(|i|
-            self.or(&x.bits[i], &y.bits[i])
-        ).collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_or", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            let l = x.len();
+            let bits = (0..l).map(|i|
+                self.or(&x.bits[i], &y.bits[i])
+            ).collect();
+            BinaryExpression { bits }
+        })
     }
 
     /// The bitwise exclusive disjunction of two binary expressions `x` and `y`, a.k.a. `x ^ y`.
-    pub fn bitwise_xor<BE1, BE2>(
+    pub fn bitwise_xor(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BinaryExpression<F> {
-        assert_eq!(x.len(), y.len());
-        let l = x.len();
-        let bits = (0..l).map(|i|
-            self.xor(&x.bits[i], &y.bits[i])
-        ).collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_xor", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            let l = x.len();
+            let bits = (0..l).map(|i|
+                self.xor(&x.bits[i], &y.bits[i])
+            ).collect();
+            BinaryExpression { bits }
+        })
+    }
+
+    /// The bitwise majority of three binary expressions, as in SHA-256's `Maj`. This requires two
+    /// products per bit; see `majority`.
+    pub fn bitwise_majority(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>, z: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_majority",
+                [x.join_allowing_overflow(), y.join_allowing_overflow(),
+                 z.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            assert_eq!(x.len(), z.len());
+            let l = x.len();
+            let bits = (0..l).map(|i|
+                self.majority(&x.bits[i], &y.bits[i], &z.bits[i])
+            ).collect();
+            BinaryExpression { bits }
+        })
+    }
+
+    /// For each bit, chooses the bit of `y` if the bit of `x` is set, otherwise the bit of `z`, as
+    /// in SHA-256's `Ch`. This requires one product per bit; see `choose`.
+    pub fn bitwise_choose(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>, z: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_choose",
+                [x.join_allowing_overflow(), y.join_allowing_overflow(),
+                 z.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            assert_eq!(x.len(), z.len());
+            let l = x.len();
+            let bits = (0..l).map(|i|
+                self.choose(&x.bits[i], &y.bits[i], &z.bits[i])
+            ).collect();
+            BinaryExpression { bits }
+        })
     }
 
     /// Rotate bits in the direction of increasing significance. This is equivalent to "left rotate"
@@ -55,13 +97,15 @@ impl<F: Field> GadgetBuilder<F> {
     pub fn bitwise_rotate_inc_significance(
         &mut self, x: &BinaryExpression<F>, n: usize,
     ) -> BinaryExpression<F> {
-        let l = x.len();
-        let bits = (0..l).map(|i| {
-            // This is equivalent to (i - n) mod l.
-            let from_idx = (l + i - n % l) % l;
-            x.bits[from_idx].clone()
-        }).collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_rotate_inc_significance", [x.join_allowing_overflow(), n], {
+            let l = x.len();
+            let bits = (0..l).map(|i| {
+                // This is equivalent to (i - n) mod l.
+                let from_idx = (l + i - n % l) % l;
+                x.bits[from_idx].clone()
+            }).collect();
+            BinaryExpression { bits }
+        })
     }
 
     /// Rotate bits in the direction of increasing significance. This is equivalent to "right
@@ -69,12 +113,14 @@ impl<F: Field> GadgetBuilder<F> {
     pub fn bitwise_rotate_dec_significance(
         &mut self, x: &BinaryExpression<F>, n: usize,
     ) -> BinaryExpression<F> {
-        let l = x.len();
-        let bits = (0..l).map(|i| {
-            let from_idx = (i + n) % l;
-            x.bits[from_idx].clone()
-        }).collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_rotate_dec_significance", [x.join_allowing_overflow(), n], {
+            let l = x.len();
+            let bits = (0..l).map(|i| {
+                let from_idx = (i + n) % l;
+                x.bits[from_idx].clone()
+            }).collect();
+            BinaryExpression { bits }
+        })
     }
 
     /// Shift bits in the direction of increasing significance, discarding bits on the most
@@ -83,15 +129,17 @@ impl<F: Field> GadgetBuilder<F> {
     pub fn bitwise_shift_inc_significance(
         &mut self, x: &BinaryExpression<F>, n: usize,
     ) -> BinaryExpression<F> {
-        let bits = (0..x.len()).map(|i| {
-            if i < n {
-                BooleanExpression::_false()
-            } else {
-                let from_idx = i - n;
-                x.bits[from_idx].clone()
-            }
-        }).collect();
-        BinaryExpression { bits }
+        logged!(self, "bitwise_shift_inc_significance", [x.join_allowing_overflow(), n], {
+            let bits = (0..x.len()).map(|i| {
+                if i < n {
+                    BooleanExpression::_false()
+                } else {
+                    let from_idx = i - n;
+                    x.bits[from_idx].clone()
+                }
+            }).collect();
+            BinaryExpression { bits }
+        })
     }
 
     /// Shift bits in the direction of decreasing significance, discarding bits on the least
@@ -100,16 +148,129 @@ impl<F: Field> GadgetBuilder<F> {
     pub fn bitwise_shift_dec_significance(
         &mut self, x: &BinaryExpression<F>, n: usize,
     ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_shift_dec_significance", [x.join_allowing_overflow(), n], {
+            let l = x.len();
+            let bits = (0..l).map(|i| {
+                if i < l - n {
+                    let from_idx = i + n;
+                    x.bits[from_idx].clone()
+                } else {
+                    BooleanExpression::_false()
+                }
+            }).collect();
+            BinaryExpression { bits }
+        })
+    }
+
+    /// Selects between two binary expressions of equal length, bit by bit: if `c` then `x` else
+    /// `y`. Costs one constraint per bit.
+    pub fn binary_selection(
+        &mut self, c: &BooleanExpression<F>, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "binary_selection",
+                [c.expression(), x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            let bits = x.bits.iter().zip(y.bits.iter()).map(|(x_i, y_i)| {
+                let selected = self.selection(c, x_i.expression(), y_i.expression());
+                // A selection between two booleans is itself boolean.
+                BooleanExpression::new_unsafe(selected)
+            }).collect();
+            BinaryExpression { bits }
+        })
+    }
+
+    /// Rotate bits in the direction of increasing significance by a variable amount `n`, which is
+    /// interpreted modulo `x.len()`. This is a logarithmic barrel shifter: for each bit of `n`, the
+    /// value is conditionally rotated by the corresponding power of two, for a cost of
+    /// `x.len() * n.len()` constraints.
+    pub fn bitwise_rotate_inc_significance_var(
+        &mut self, x: &BinaryExpression<F>, n: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_rotate_inc_significance_var",
+                [x.join_allowing_overflow(), n.join_allowing_overflow()], {
+            self.bitwise_rotate_var(x, n, Self::bitwise_rotate_inc_significance)
+        })
+    }
+
+    /// Rotate bits in the direction of decreasing significance by a variable amount `n`, which is
+    /// interpreted modulo `x.len()`. See `bitwise_rotate_inc_significance_var` for details.
+    pub fn bitwise_rotate_dec_significance_var(
+        &mut self, x: &BinaryExpression<F>, n: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_rotate_dec_significance_var",
+                [x.join_allowing_overflow(), n.join_allowing_overflow()], {
+            self.bitwise_rotate_var(x, n, Self::bitwise_rotate_dec_significance)
+        })
+    }
+
+    /// Shift bits in the direction of increasing significance by a variable amount `n`. If `n` is
+    /// at least `x.len()`, the result is zero. This is a logarithmic barrel shifter, as in
+    /// `bitwise_rotate_inc_significance_var`.
+    pub fn bitwise_shift_inc_significance_var(
+        &mut self, x: &BinaryExpression<F>, n: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_shift_inc_significance_var",
+                [x.join_allowing_overflow(), n.join_allowing_overflow()], {
+            self.bitwise_shift_var(x, n, Self::bitwise_shift_inc_significance)
+        })
+    }
+
+    /// Shift bits in the direction of decreasing significance by a variable amount `n`. If `n` is
+    /// at least `x.len()`, the result is zero. This is a logarithmic barrel shifter, as in
+    /// `bitwise_rotate_inc_significance_var`.
+    pub fn bitwise_shift_dec_significance_var(
+        &mut self, x: &BinaryExpression<F>, n: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "bitwise_shift_dec_significance_var",
+                [x.join_allowing_overflow(), n.join_allowing_overflow()], {
+            self.bitwise_shift_var(x, n, Self::bitwise_shift_dec_significance)
+        })
+    }
+
+    fn bitwise_rotate_var<R>(
+        &mut self, x: &BinaryExpression<F>, n: &BinaryExpression<F>, rotate: R,
+    ) -> BinaryExpression<F>
+        where R: Fn(&mut Self, &BinaryExpression<F>, usize) -> BinaryExpression<F> {
         let l = x.len();
-        let bits = (0..l).map(|i| {
-            if i < l - n {
-                let from_idx = i + n;
-                x.bits[from_idx].clone()
+        let mut current = x.clone();
+        if l == 0 {
+            return current;
+        }
+
+        // Bit i of n contributes a rotation of 2^i mod l.
+        let mut power = 1 % l;
+        for n_bit in n.bits.iter() {
+            if power != 0 {
+                let rotated = rotate(self, &current, power);
+                current = self.binary_selection(n_bit, &rotated, &current);
+            }
+            power = power * 2 % l;
+        }
+        current
+    }
+
+    fn bitwise_shift_var<S>(
+        &mut self, x: &BinaryExpression<F>, n: &BinaryExpression<F>, shift: S,
+    ) -> BinaryExpression<F>
+        where S: Fn(&mut Self, &BinaryExpression<F>, usize) -> BinaryExpression<F> {
+        let l = x.len();
+        let mut current = x.clone();
+
+        // Bits of n with weights below l conditionally shift by their weight. If these shifts add
+        // up to l or more, the result is already zero.
+        let mut overflow = BooleanExpression::_false();
+        for (i, n_bit) in n.bits.iter().enumerate() {
+            if i < usize::BITS as usize && 1usize << i < l {
+                let shifted = shift(self, &current, 1 << i);
+                current = self.binary_selection(n_bit, &shifted, &current);
             } else {
-                BooleanExpression::_false()
+                // Any higher bit means that n >= l, so the result must be zero.
+                overflow = self.or(&overflow, n_bit);
             }
-        }).collect();
-        BinaryExpression { bits }
+        }
+
+        let zero = BinaryExpression { bits: vec![BooleanExpression::_false(); l] };
+        self.binary_selection(&overflow, &zero, &current)
     }
 }
 
@@ -117,9 +278,11 @@ impl<F: Field> GadgetBuilder<F> {
 mod tests {
     use num::BigUint;
 
+    use crate::Bn128;
     use crate::expression::BinaryExpression;
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::lcg::LCG;
+    use crate::fields::small::F257;
 
     #[test]
     fn bitwise_not() {
@@ -171,6 +334,42 @@ mod tests {
         assert_eq!(BigUint::from(0b00111100u32), x_and_y.evaluate(&values_11111100_00111111));
     }
 
+    #[test]
+    fn bitwise_majority() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.binary_wire(8), builder.binary_wire(8), builder.binary_wire(8));
+        let majority = builder.bitwise_majority(
+            &BinaryExpression::from(&x), &BinaryExpression::from(&y), &BinaryExpression::from(&z));
+        let gadget = builder.build();
+        assert_eq!(3 * 8 + 2 * 8, gadget.size());
+
+        // Maj(11110000, 11001100, 10101010) = 11101000.
+        let mut values = binary_unsigned_values!(
+            &x => &BigUint::from(0b11110000u32),
+            &y => &BigUint::from(0b11001100u32),
+            &z => &BigUint::from(0b10101010u32));
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(0b11101000u32), majority.evaluate(&values));
+    }
+
+    #[test]
+    fn bitwise_choose() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.binary_wire(8), builder.binary_wire(8), builder.binary_wire(8));
+        let choice = builder.bitwise_choose(
+            &BinaryExpression::from(&x), &BinaryExpression::from(&y), &BinaryExpression::from(&z));
+        let gadget = builder.build();
+        assert_eq!(3 * 8 + 8, gadget.size());
+
+        // Ch(11110000, 11001100, 10101010) = 11001010.
+        let mut values = binary_unsigned_values!(
+            &x => &BigUint::from(0b11110000u32),
+            &y => &BigUint::from(0b11001100u32),
+            &z => &BigUint::from(0b10101010u32));
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(0b11001010u32), choice.evaluate(&values));
+    }
+
     #[test]
     fn bitwise_rotate_dec_significance() {
         let mut builder = GadgetBuilder::<F257>::new();
@@ -202,5 +401,79 @@ mod tests {
         assert_eq!(BigUint::from(0b01100010u32), x_rot.evaluate(&values));
     }
 
-    // TODO: Tests for shift methods
-}
\ No newline at end of file
+    // TODO: Tests for constant shift methods
+
+    /// Builds a gadget applying `op` to a 16-bit `x` and an 8-bit `n`, and checks it against
+    /// `expected` for the given amounts and for pseudorandom cases.
+    fn check_var_op<Op, Expected>(op: Op, expected: Expected, amounts: &[u8])
+        where Op: Fn(&mut GadgetBuilder<Bn128>, &BinaryExpression<Bn128>, &BinaryExpression<Bn128>)
+                     -> BinaryExpression<Bn128>,
+              Expected: Fn(u16, u8) -> u16 {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.binary_wire(16);
+        let n = builder.binary_wire(8);
+        let result = op(&mut builder, &BinaryExpression::from(&x), &BinaryExpression::from(&n));
+        let gadget = builder.build();
+
+        let mut lcg = LCG::new();
+        let mut cases: Vec<(u16, u8)> =
+            amounts.iter().map(|&n| (0b1011_0000_0000_0110, n)).collect();
+        for _i in 0..20 {
+            cases.push((lcg.next_u32() as u16, lcg.next_u32() as u8 % 24));
+        }
+
+        for (x_value, n_value) in cases {
+            let mut values = binary_unsigned_values!(
+                &x => &BigUint::from(x_value), &n => &BigUint::from(n_value));
+            assert!(gadget.execute(&mut values));
+            assert_eq!(BigUint::from(expected(x_value, n_value)), result.evaluate(&values),
+                       "x = {}, n = {}", x_value, n_value);
+        }
+    }
+
+    #[test]
+    fn bitwise_rotate_inc_significance_var() {
+        check_var_op(
+            GadgetBuilder::bitwise_rotate_inc_significance_var,
+            |x, n| x.rotate_left(n as u32),
+            &[0, 1, 15, 16, 17, 255]);
+    }
+
+    #[test]
+    fn bitwise_rotate_dec_significance_var() {
+        check_var_op(
+            GadgetBuilder::bitwise_rotate_dec_significance_var,
+            |x, n| x.rotate_right(n as u32),
+            &[0, 1, 15, 16, 17, 255]);
+    }
+
+    #[test]
+    fn bitwise_shift_inc_significance_var() {
+        check_var_op(
+            GadgetBuilder::bitwise_shift_inc_significance_var,
+            |x, n| x.checked_shl(n as u32).unwrap_or(0),
+            &[0, 1, 15, 16, 17, 32, 255]);
+    }
+
+    #[test]
+    fn bitwise_shift_dec_significance_var() {
+        check_var_op(
+            GadgetBuilder::bitwise_shift_dec_significance_var,
+            |x, n| x.checked_shr(n as u32).unwrap_or(0),
+            &[0, 1, 15, 16, 17, 32, 255]);
+    }
+
+    #[test]
+    fn bitwise_rotate_var_cost() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.binary_wire(16);
+        let n = builder.binary_wire(8);
+        builder.bitwise_rotate_inc_significance_var(
+            &BinaryExpression::from(&x), &BinaryExpression::from(&n));
+
+        // Besides the 24 booleanity constraints for the inputs, there is one selection per bit of x
+        // for each of the four low bits of n. The higher bits of n would rotate by a multiple of
+        // 16, which is the identity, so they cost nothing.
+        assert_eq!(24 + 16 * 4, builder.build().size());
+    }
+}
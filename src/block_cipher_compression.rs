@@ -0,0 +1,170 @@
+//! This module provides one-way compression functions built from block ciphers, with a choice of
+//! feedforward mode.
+
+use std::marker::PhantomData;
+
+use crate::expression::Expression;
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+use crate::gadget_traits::{BlockCipher, CompressionFunction};
+
+/// A mode for building a one-way compression function `H(h, m)` from a block cipher `E`, where `h`
+/// is the chaining value and `m` is the message block. All feedforward additions are field
+/// additions.
+///
+/// Each mode is among the constructions proven secure by Preneel, Govaerts and Vandewalle, and by
+/// Black, Rogaway and Shrimpton, in the ideal cipher model: each is collision resistant and
+/// preimage resistant, provided that `E` behaves like an ideal cipher. These proofs do not apply
+/// to ciphers with known weaknesses, such as related-key attacks, so the cipher should be designed
+/// for use as a compression function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OneWayCompressionMode {
+    /// Davies-Meyer: `H = E_m(h) + h`. The message block is used as the key, so the cipher must be
+    /// secure against related-key attacks in which the attacker controls the key. This is the
+    /// default mode, and matches `DaviesMeyer`.
+    #[default]
+    DaviesMeyer,
+    /// Miyaguchi-Preneel: `H = E_h(m) + m + h`. The chaining value is used as the key. This
+    /// matches `MiyaguchiPreneel`.
+    MiyaguchiPreneel,
+    /// Matyas-Meyer-Oseas: `H = E_h(m) + m`. The chaining value is used as the key. Unlike
+    /// Miyaguchi-Preneel, the chaining value is not fed forward, so the output depends on `h` only
+    /// through the key schedule.
+    MatyasMeyerOseas,
+}
+
+/// A one-way compression function built from a block cipher, using one of the modes in
+/// `OneWayCompressionMode`. The chaining value is the first argument to `compress`, and the message
+/// block is the second.
+pub struct BlockCipherCompression<F: Field, BC: BlockCipher<F>> {
+    cipher: BC,
+    mode: OneWayCompressionMode,
+    phantom: PhantomData<fn() -> F>,
+}
+
+impl<F: Field, BC: BlockCipher<F>> BlockCipherCompression<F, BC> {
+    /// Create a new compression function from the given block cipher, using the default mode,
+    /// Davies-Meyer.
+    pub fn new(cipher: BC) -> Self {
+        Self::with_mode(cipher, OneWayCompressionMode::default())
+    }
+
+    /// Create a new compression function from the given block cipher and mode.
+    pub fn with_mode(cipher: BC, mode: OneWayCompressionMode) -> Self {
+        BlockCipherCompression { cipher, mode, phantom: PhantomData }
+    }
+
+    /// The mode used by this compression function.
+    pub fn mode(&self) -> OneWayCompressionMode {
+        self.mode
+    }
+}
+
+impl<F: Field, BC: BlockCipher<F>> CompressionFunction<F> for BlockCipherCompression<F, BC> {
+    fn compress(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>, y: &Expression<F>)
+                -> Expression<F> {
+        let (h, m) = (x, y);
+        match self.mode {
+            OneWayCompressionMode::DaviesMeyer =>
+                self.cipher.encrypt(builder, m, h) + h,
+            OneWayCompressionMode::MiyaguchiPreneel =>
+                self.cipher.encrypt(builder, h, m) + m + h,
+            OneWayCompressionMode::MatyasMeyerOseas =>
+                self.cipher.encrypt(builder, h, m) + m,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rand"))]
+mod tests {
+    use crate::{BlockCipher, CompressionFunction, DaviesMeyer, Element, Expression, GadgetBuilder,
+                HashFunction, MerkleDamgard, MiMCBlockCipher, MiyaguchiPreneel};
+    use crate::block_cipher_compression::{BlockCipherCompression, OneWayCompressionMode};
+    use crate::fields::small::F11;
+
+    const MODES: [OneWayCompressionMode; 3] = [
+        OneWayCompressionMode::DaviesMeyer,
+        OneWayCompressionMode::MiyaguchiPreneel,
+        OneWayCompressionMode::MatyasMeyerOseas,
+    ];
+
+    fn compress(mode: OneWayCompressionMode, h: u8, m: u8) -> Element<F11> {
+        BlockCipherCompression::with_mode(MiMCBlockCipher::default(), mode)
+            .compress_evaluate(&Element::from(h), &Element::from(m))
+    }
+
+    #[test]
+    fn default_matches_davies_meyer() {
+        let default = BlockCipherCompression::new(MiMCBlockCipher::<F11>::default());
+        assert_eq!(OneWayCompressionMode::DaviesMeyer, default.mode());
+        let davies_meyer = DaviesMeyer::new(MiMCBlockCipher::<F11>::default());
+        for h in 0u8..11 {
+            for m in 0u8..11 {
+                let (h, m) = (Element::from(h), Element::from(m));
+                assert_eq!(davies_meyer.compress_evaluate(&h, &m),
+                           default.compress_evaluate(&h, &m));
+            }
+        }
+
+        // Outputs of DaviesMeyer prior to the introduction of BlockCipherCompression, which used
+        // the MiMC constants of version 0.4.
+        let legacy = BlockCipherCompression::with_mode(
+            MiMCBlockCipher::<F11>::default_v0_4(), OneWayCompressionMode::default());
+        assert_eq!(Element::from(9u8),
+                   legacy.compress_evaluate(&Element::from(2u8), &Element::from(3u8)));
+        assert_eq!(Element::from(0u8),
+                   legacy.compress_evaluate(&Element::from(7u8), &Element::from(4u8)));
+    }
+
+    #[test]
+    fn miyaguchi_preneel_mode_matches_miyaguchi_preneel() {
+        let miyaguchi_preneel = MiyaguchiPreneel::new(MiMCBlockCipher::<F11>::default());
+        let (h, m) = (Element::from(2u8), Element::from(3u8));
+        assert_eq!(miyaguchi_preneel.compress_evaluate(&h, &m),
+                   compress(OneWayCompressionMode::MiyaguchiPreneel, 2, 3));
+    }
+
+    #[test]
+    fn modes_match_cipher_composition() {
+        let mimc = MiMCBlockCipher::<F11>::default();
+        let (h, m) = (Element::from(2u8), Element::from(3u8));
+        assert_eq!(mimc.encrypt_evaluate(&m, &h) + &h,
+                   compress(OneWayCompressionMode::DaviesMeyer, 2, 3));
+        assert_eq!(mimc.encrypt_evaluate(&h, &m) + &m + &h,
+                   compress(OneWayCompressionMode::MiyaguchiPreneel, 2, 3));
+        assert_eq!(mimc.encrypt_evaluate(&h, &m) + &m,
+                   compress(OneWayCompressionMode::MatyasMeyerOseas, 2, 3));
+    }
+
+    #[test]
+    fn modes_are_distinct() {
+        let outputs: Vec<Element<F11>> = MODES.iter().map(|&mode| compress(mode, 2, 3)).collect();
+        assert!(outputs[0] != outputs[1]);
+        assert!(outputs[0] != outputs[2]);
+        assert!(outputs[1] != outputs[2]);
+    }
+
+    #[test]
+    fn merkle_damgard_with_each_mode() {
+        for &mode in MODES.iter() {
+            let mut builder = GadgetBuilder::<F11>::new();
+            let (x_wire, y_wire) = (builder.wire(), builder.wire());
+            let compress = BlockCipherCompression::with_mode(MiMCBlockCipher::default(), mode);
+            let md = MerkleDamgard::new(Element::from(5u8), compress);
+            let hash = md.hash(&mut builder, &[Expression::from(x_wire), Expression::from(y_wire)]);
+            let gadget = builder.build();
+
+            let mut values = values!(x_wire => 2u8.into(), y_wire => 3u8.into());
+            assert!(gadget.execute(&mut values));
+
+            // Compose the compression function by hand: compress(compress(compress(5, 2), 3), 2),
+            // where the final block is the length padding.
+            let compress = |h: &Element<F11>, m: u8| {
+                BlockCipherCompression::with_mode(MiMCBlockCipher::default(), mode)
+                    .compress_evaluate(h, &Element::from(m))
+            };
+            let expected = compress(&compress(&compress(&Element::from(5u8), 2), 3), 2);
+            assert_eq!(expected, hash.evaluate(&values));
+        }
+    }
+}
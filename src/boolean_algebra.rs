@@ -5,43 +5,104 @@ use crate::field::Field;
 use crate::gadget_builder::GadgetBuilder;
 
 impl<F: Field> GadgetBuilder<F> {
-    /// The negation of a boolean value.
+    /// The negation of a boolean value. This is equivalent to `x.negated()`, which doesn't require
+    /// a builder.
     pub fn not(&mut self, x: &BooleanExpression<F>) -> BooleanExpression<F> {
-        BooleanExpression::new_unsafe(Expression::one() - x.expression())
+        logged!(self, "not", [x.expression()], {
+            x.negated()
+        })
     }
 
     /// The conjunction of two boolean values.
     pub fn and(
         &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>,
     ) -> BooleanExpression<F> {
-        BooleanExpression::new_unsafe(self.product(x.expression(), y.expression()))
+        logged!(self, "and", [x.expression(), y.expression()], {
+            BooleanExpression::new_unsafe(self.product(x.expression(), y.expression()))
+        })
     }
 
     /// The disjunction of two boolean values.
     pub fn or(
         &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>,
     ) -> BooleanExpression<F> {
-        let x_exp = x.expression();
-        let y_exp = y.expression();
-        BooleanExpression::new_unsafe(
-            x_exp + y_exp - self.product(x_exp, y_exp))
+        logged!(self, "or", [x.expression(), y.expression()], {
+            let x_exp = x.expression();
+            let y_exp = y.expression();
+            BooleanExpression::new_unsafe(
+                x_exp + y_exp - self.product(x_exp, y_exp))
+        })
     }
 
     /// The exclusive disjunction of two boolean values.
     pub fn xor(
         &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>,
     ) -> BooleanExpression<F> {
-        let x_exp = x.expression();
-        let y_exp = y.expression();
-        BooleanExpression::new_unsafe(x_exp + y_exp - self.product(x_exp, y_exp) * 2u128)
+        logged!(self, "xor", [x.expression(), y.expression()], {
+            let x_exp = x.expression();
+            let y_exp = y.expression();
+            BooleanExpression::new_unsafe(x_exp + y_exp - self.product(x_exp, y_exp) * 2u128)
+        })
+    }
+
+    /// The negated exclusive disjunction of two boolean values, i.e. whether they are equal. This
+    /// is `1 - xor(x, y)`, so it requires a single product, like `xor`.
+    pub fn xnor(
+        &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>,
+    ) -> BooleanExpression<F> {
+        logged!(self, "xnor", [x.expression(), y.expression()], {
+            self.xor(x, y).negated()
+        })
+    }
+
+    /// The material implication `x -> y`, i.e. `!x | y`. This is computed as `1 - x + x y`, which
+    /// requires a single product.
+    pub fn implies(
+        &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>,
+    ) -> BooleanExpression<F> {
+        logged!(self, "implies", [x.expression(), y.expression()], {
+            let x_exp = x.expression();
+            let y_exp = y.expression();
+            BooleanExpression::new_unsafe(
+                Expression::one() - x_exp + self.product(x_exp, y_exp))
+        })
+    }
+
+    /// The majority of three boolean values, as in SHA-256's `Maj`. Rather than expanding it as
+    /// `x y + x z + y z - 2 x y z`, we compute `x y + z (x xor y)`, where `x xor y` reuses the
+    /// product `x y`. This requires two products.
+    pub fn majority(
+        &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>, z: &BooleanExpression<F>,
+    ) -> BooleanExpression<F> {
+        logged!(self, "majority", [x.expression(), y.expression(), z.expression()], {
+            let x_exp = x.expression();
+            let y_exp = y.expression();
+            let xy = self.product(x_exp, y_exp);
+            let x_xor_y = x_exp + y_exp - &xy * 2u128;
+            BooleanExpression::new_unsafe(&xy + self.product(z.expression(), &x_xor_y))
+        })
+    }
+
+    /// Returns `y` if `x` is set, otherwise `z`, as in SHA-256's `Ch`. This is computed as
+    /// `z + x (y - z)`, which requires a single product.
+    pub fn choose(
+        &mut self, x: &BooleanExpression<F>, y: &BooleanExpression<F>, z: &BooleanExpression<F>,
+    ) -> BooleanExpression<F> {
+        logged!(self, "choose", [x.expression(), y.expression(), z.expression()], {
+            BooleanExpression::new_unsafe(self.selection(x, y.expression(), z.expression()))
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::expression::BooleanExpression;
+    use crate::expression::{BooleanExpression, Expression};
+    use crate::field::Element;
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::fields::small::{F257, F7};
+    use crate::test_util::exhaustive_check;
+    use crate::wire::BooleanWire;
+    use crate::wire_values::WireValues;
 
     #[test]
     fn and() {
@@ -114,4 +175,87 @@ mod tests {
         assert!(gadget.execute(&mut values11));
         assert_eq!(false, xor.evaluate(&values11));
     }
-}
\ No newline at end of file
+
+    /// Exhaustively check `op(x, y) = z` over F7, where `x` and `y` are boolean wires and `z` is an
+    /// unconstrained wire.
+    fn check_exhaustive<O, R>(op: O, reference: R)
+        where O: Fn(&mut GadgetBuilder<F7>, &BooleanExpression<F7>, &BooleanExpression<F7>)
+                    -> BooleanExpression<F7>,
+              R: Fn(bool, bool) -> bool {
+        let mut builder = GadgetBuilder::<F7>::new();
+        let (x, y, z) = (builder.boolean_wire(), builder.boolean_wire(), builder.wire());
+        let result = op(&mut builder, &BooleanExpression::from(x), &BooleanExpression::from(y));
+        builder.assert_equal(result.expression(), &Expression::from(z));
+        let gadget = builder.build();
+
+        let to_bool = |value: &Element<F7>| match value {
+            v if v.is_zero() => Some(false),
+            v if v.is_one() => Some(true),
+            _ => None,
+        };
+        let result = exhaustive_check(&gadget, &[x.wire(), y.wire(), z], |v| {
+            match (to_bool(&v[0]), to_bool(&v[1]), to_bool(&v[2])) {
+                (Some(x), Some(y), Some(z)) => reference(x, y) == z,
+                _ => false,
+            }
+        });
+        if let Err(report) = result {
+            panic!("{}", report);
+        }
+    }
+
+    /// Check the truth table of a gate with `arity` boolean inputs, and that it requires the given
+    /// number of products, excluding the inputs' booleanity constraints.
+    fn check_truth_table<O, R>(arity: usize, products: usize, op: O, reference: R)
+        where O: Fn(&mut GadgetBuilder<F257>, &[BooleanExpression<F257>])
+                    -> BooleanExpression<F257>,
+              R: Fn(&[bool]) -> bool {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..arity).map(|_i| builder.boolean_wire()).collect();
+        let inputs: Vec<BooleanExpression<F257>> =
+            wires.iter().map(BooleanExpression::from).collect();
+        let result = op(&mut builder, &inputs);
+        let gadget = builder.build();
+        assert_eq!(arity + products, gadget.size());
+
+        for assignment in 0..1 << arity {
+            let bits: Vec<bool> = (0..arity).map(|i| assignment >> i & 1 == 1).collect();
+            let mut values = WireValues::new();
+            for (&wire, &bit) in wires.iter().zip(bits.iter()) {
+                values.set_boolean(wire, bit);
+            }
+            assert!(gadget.execute(&mut values));
+            assert_eq!(reference(&bits), result.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn xnor() {
+        check_truth_table(2, 1, |b, v| b.xnor(&v[0], &v[1]), |v| v[0] == v[1]);
+    }
+
+    #[test]
+    fn implies() {
+        check_truth_table(2, 1, |b, v| b.implies(&v[0], &v[1]), |v| !v[0] || v[1]);
+    }
+
+    #[test]
+    fn majority() {
+        check_truth_table(
+            3, 2, |b, v| b.majority(&v[0], &v[1], &v[2]),
+            |v| v.iter().filter(|&&bit| bit).count() >= 2);
+    }
+
+    #[test]
+    fn choose() {
+        check_truth_table(
+            3, 1, |b, v| b.choose(&v[0], &v[1], &v[2]), |v| if v[0] { v[1] } else { v[2] });
+    }
+
+    #[test]
+    fn exhaustive_and_or_xor() {
+        check_exhaustive(|b, x, y| b.and(x, y), |x, y| x & y);
+        check_exhaustive(|b, x, y| b.or(x, y), |x, y| x | y);
+        check_exhaustive(|b, x, y| b.xor(x, y), |x, y| x ^ y);
+    }
+}
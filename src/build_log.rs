@@ -0,0 +1,230 @@
+//! This module contains the build log which `GadgetBuilder` records when
+//! `GadgetBuilder::enable_build_log` is called. Each entry describes one call to a builder method,
+//! giving a readable trace of how a gadget was assembled.
+
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use std::fmt;
+use std::fmt::Formatter;
+use std::ops::Range;
+
+use crate::util::join;
+
+/// The maximum number of characters used to describe each input of a logged call. Longer
+/// descriptions are truncated, ending with `...`.
+pub const MAX_INPUT_CHARS: usize = 60;
+
+/// A call to a `GadgetBuilder` method, as recorded in the build log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogEntry {
+    /// The name of the method.
+    pub method: &'static str,
+    /// The Display of each input, truncated to `MAX_INPUT_CHARS` characters.
+    pub inputs: Vec<String>,
+    /// The number of logged calls which this call was made within. Calls made directly by the user
+    /// have depth 0.
+    pub depth: usize,
+    /// The indices of the constraints added by this call, including those added by nested calls.
+    pub constraints: Range<usize>,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:indent$}{}({}): constraints {}..{}", "", self.method, join(", ", &self.inputs),
+               self.constraints.start, self.constraints.end, indent = 2 * self.depth)
+    }
+}
+
+/// Describes an input of a logged call, truncating its Display to `MAX_INPUT_CHARS` characters.
+pub(crate) fn describe(input: &dyn fmt::Display) -> String {
+    let mut description = format!("{}", input);
+    if description.chars().count() > MAX_INPUT_CHARS {
+        let (cut, _c) = description.char_indices().nth(MAX_INPUT_CHARS - 3).unwrap();
+        description.truncate(cut);
+        description.push_str("...");
+    }
+    description
+}
+
+/// Runs `$body` as a builder method named `$method`, recording it in the build log of `$builder`
+/// along with the given inputs, if the log is enabled. The body runs in a closure, so a `return`
+/// within it still reaches the end of the entry. Inputs are only formatted if the log is enabled.
+macro_rules! logged {
+    ($builder:ident, $method:literal, [$($input:expr),* $(,)?], $body:block) => {{
+        let entry = $builder.log_start(
+            $method, || vec![$($crate::build_log::describe(&$input)),*]);
+        #[allow(clippy::redundant_closure_call)]
+        let result = (|| $body)();
+        $builder.log_end(entry);
+        result
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BinaryExpression, BooleanExpression, Bn128, CompressionFunction, Expression, F257,
+                GadgetBuilder, LogEntry, MAX_INPUT_CHARS, MerklePath};
+    use crate::field::Field;
+
+    /// A builder asserting that `x < y`, with or without a build log.
+    fn comparison(log: bool) -> GadgetBuilder<F257> {
+        let mut builder = GadgetBuilder::new();
+        if log {
+            builder.enable_build_log();
+        }
+        let (x, y) = (builder.wire(), builder.wire());
+        let lt = builder.lt(&Expression::from(x), &Expression::from(y));
+        builder.assert_true(&lt);
+        builder
+    }
+
+    #[test]
+    fn comparison_log() {
+        let builder = comparison(true);
+        let log = builder.build_log().to_vec();
+        let gadget = builder.build();
+        assert_eq!(Some(&log), gadget.build_log.as_ref());
+
+        // The top-level calls partition the constraints.
+        let top_level: Vec<&LogEntry> = log.iter().filter(|entry| entry.depth == 0).collect();
+        let methods: Vec<&str> = top_level.iter().map(|entry| entry.method).collect();
+        assert_eq!(vec!["lt", "assert_true"], methods);
+        assert_eq!(0, top_level[0].constraints.start);
+        assert_eq!(top_level[0].constraints.end, top_level[1].constraints.start);
+        let total: usize = top_level.iter().map(|entry| entry.constraints.len()).sum();
+        assert_eq!(gadget.size(), total);
+        assert_eq!(gadget.size(), top_level[1].constraints.end);
+
+        // Each entry's constraints lie within those of the call it was made within.
+        for (i, entry) in log.iter().enumerate().filter(|(_i, entry)| entry.depth > 0) {
+            let parent = log[..i].iter().rev()
+                .find(|other| other.depth == entry.depth - 1)
+                .unwrap();
+            assert!(parent.constraints.start <= entry.constraints.start);
+            assert!(entry.constraints.end <= parent.constraints.end);
+        }
+
+        assert!(log.iter().any(|entry| entry.method == "split"));
+        assert!(log.iter().any(|entry| entry.method == "split_allowing_ambiguity"));
+
+        // Booleanity assertions, including the one on the sum of the comparison's mask bits, each
+        // add the constraint x * (x - 1) = 0.
+        let assertions: Vec<&LogEntry> = log.iter()
+            .filter(|entry| entry.method == "assert_boolean" && !entry.constraints.is_empty())
+            .collect();
+        assert!(assertions.iter().any(|entry| entry.inputs[0].contains('+')));
+        for entry in assertions {
+            assert_eq!(1, entry.constraints.len());
+            let constraint = &gadget.constraints[entry.constraints.start];
+            assert_eq!(entry.inputs[0], format!("{}", constraint.a));
+        }
+    }
+
+    #[test]
+    fn conditional_scope_log() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.enable_build_log();
+        let (x, y) = (Expression::from(builder.wire()), Expression::from(builder.wire()));
+        let c = BooleanExpression::from(builder.boolean_wire());
+        builder.assert_zero(&x);
+        builder.conditional_scope(&c, |builder| {
+            builder.assert_equal(&x, &y);
+            builder.assert_product(&x, &y, &Expression::one());
+        });
+        builder.assert_nonzero(&y);
+        let gadget = builder.build();
+        let log = gadget.build_log.as_ref().unwrap();
+
+        // The scope has its own entry, and the top-level calls still partition the constraints.
+        let top_level: Vec<&LogEntry> = log.iter().filter(|entry| entry.depth == 0).collect();
+        let methods: Vec<&str> = top_level.iter().map(|entry| entry.method).collect();
+        let expected = ["boolean_wire", "assert_zero", "conditional_scope", "assert_nonzero"];
+        assert_eq!(expected.to_vec(), methods);
+        assert_eq!(0, top_level[0].constraints.start);
+        for pair in top_level.windows(2) {
+            assert_eq!(pair[0].constraints.end, pair[1].constraints.start);
+        }
+        assert_eq!(gadget.size(), top_level[3].constraints.end);
+
+        // The entries within the scope refer to the rewritten constraints. The rewritten equality
+        // takes one constraint, while the rewritten product takes two, since x * y is computed
+        // first.
+        let scope = &top_level[2].constraints;
+        let scope_index = log.iter().position(|entry| entry.method == "conditional_scope").unwrap();
+        let inner: Vec<&LogEntry> = log[scope_index + 1..].iter()
+            .filter(|entry| entry.depth == 1)
+            .take(2)
+            .collect();
+        let (equal, product) = (inner[0], inner[1]);
+        assert_eq!("assert_equal", equal.method);
+        assert_eq!("assert_product", product.method);
+        assert_eq!(scope.start..scope.start + 1, equal.constraints);
+        assert_eq!(equal.constraints.end..scope.end, product.constraints);
+        assert_eq!(2, product.constraints.len());
+        assert_eq!(c.expression(), &gadget.constraints[equal.constraints.start].a);
+        assert_eq!(c.expression(), &gadget.constraints[product.constraints.end - 1].a);
+    }
+
+    #[test]
+    fn extension_methods_logged() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.enable_build_log();
+        let (x, y) = (builder.binary_wire(4), builder.binary_wire(4));
+        let (x, y) = (BinaryExpression::from(&x), BinaryExpression::from(&y));
+        builder.and(&x.bits[0], &y.bits[0]);
+        let sum = builder.binary_sum(&x, &y);
+        let path = MerklePath::new(y.clone(), vec![x.join(); 4]);
+        builder.merkle_tree_root(&sum.join(), &path, &TestCompress);
+        builder.assert_permutation(&[x.join(), y.join()], &[y.join(), x.join()]);
+        let gadget = builder.build();
+        let log = gadget.build_log.as_ref().unwrap();
+
+        let top_level: Vec<&LogEntry> = log.iter().filter(|entry| entry.depth == 0).collect();
+        let methods: Vec<&str> = top_level.iter().map(|entry| entry.method).collect();
+        let expected = ["binary_wire", "binary_wire", "and", "binary_sum", "merkle_tree_root",
+            "assert_permutation"];
+        assert_eq!(expected.to_vec(), methods);
+        for pair in top_level.windows(2) {
+            assert_eq!(pair[0].constraints.end, pair[1].constraints.start);
+        }
+        assert_eq!(gadget.size(), top_level[5].constraints.end);
+
+        // Nested calls to extension methods are logged too.
+        assert!(log.iter().any(|entry| entry.method == "binary_summation" && entry.depth == 1));
+        assert!(log.iter().any(|entry| entry.method == "selection" && entry.depth == 1));
+    }
+
+    #[test]
+    fn disabled_log() {
+        let builder = comparison(false);
+        assert!(builder.build_log().is_empty());
+        let gadget = builder.build();
+        assert!(gadget.build_log.is_none());
+        assert_eq!(comparison(true).build().constraints, gadget.constraints);
+    }
+
+    #[test]
+    fn truncated_inputs() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        builder.enable_build_log();
+        let (_wires, exps) = builder.expression_wires(20);
+        let sum = Expression::sum_of_expressions(&exps);
+        builder.assert_zero(&sum);
+        let entry = &builder.build_log()[0];
+        assert_eq!("assert_zero", entry.method);
+        assert_eq!(MAX_INPUT_CHARS, entry.inputs[0].chars().count());
+        assert!(entry.inputs[0].ends_with("..."));
+        assert!(format!("{}", sum).starts_with(entry.inputs[0].trim_end_matches("...")));
+    }
+
+    struct TestCompress;
+
+    impl<F: Field> CompressionFunction<F> for TestCompress {
+        fn compress(&self, _builder: &mut GadgetBuilder<F>, x: &Expression<F>, y: &Expression<F>)
+                    -> Expression<F> {
+            x * 2 + y
+        }
+    }
+}
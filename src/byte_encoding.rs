@@ -0,0 +1,209 @@
+//! This module contains an injective encoding of byte strings as field elements, for binding
+//! identifiers such as asset names or domain tags into circuits, along with gadgets for using it.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::BigUint;
+use num_traits::ToPrimitive;
+
+use crate::expression::{BinaryExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+
+/// Encodes a byte string as field elements: its length, followed by its bytes packed
+/// little-endian into elements of `bytes_per_element` bytes each, with the last one possibly
+/// shorter.
+///
+/// The encoding is injective, so distinct byte strings, including those where one is a prefix of
+/// the other, never encode to the same sequence. Each chunk has at most `8 * bytes_per_element <=
+/// max_bits - 1` bits, so it's less than `|F|` and its element determines it. The length then
+/// determines how many chunks follow and how many bytes the last one holds, so the chunks, and
+/// hence the bytes, can be recovered; see `decode_bytes_from_elements`. Sequences of encodings
+/// are injective as well, since each encoding begins with the number of elements which follow.
+///
+/// The length must be less than `|F|`, so that its element determines it, or else this panics.
+/// This only limits small fields, e.g. `F257` can encode up to 256 bytes.
+pub fn encode_bytes_to_elements<F: Field>(bytes: &[u8]) -> Vec<Element<F>> {
+    assert!(BigUint::from(bytes.len()) < F::order(),
+            "A byte string of length {} is too long to encode in this field", bytes.len());
+    let mut elements = vec![Element::from(bytes.len())];
+    elements.extend(bytes.chunks(bytes_per_element::<F>())
+        .map(|chunk| Element::from(BigUint::from_bytes_le(chunk))));
+    elements
+}
+
+/// The inverse of `encode_bytes_to_elements`. Returns `None` if the elements are not a valid
+/// encoding, e.g. if there are too few or too many of them for the encoded length.
+pub fn decode_bytes_from_elements<F: Field>(elements: &[Element<F>]) -> Option<Vec<u8>> {
+    let (length, chunks) = elements.split_first()?;
+    let length = length.to_biguint().to_usize()?;
+    let chunk_bytes = bytes_per_element::<F>();
+    if chunks.len() != length.div_ceil(chunk_bytes) {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(length);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let width = chunk_bytes.min(length - i * chunk_bytes);
+        let mut chunk_value = chunk.to_biguint().to_bytes_le();
+        if chunk_value.len() > width {
+            return None;
+        }
+        chunk_value.resize(width, 0);
+        bytes.extend(chunk_value);
+    }
+    Some(bytes)
+}
+
+/// The number of bytes packed into each element by `encode_bytes_to_elements`.
+fn bytes_per_element<F: Field>() -> usize {
+    let bytes = (Element::<F>::max_bits() - 1) / 8;
+    assert!(bytes > 0, "The field is too small to hold a byte per element");
+    bytes
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Returns the encoding of a constant byte string, as given by `encode_bytes_to_elements`.
+    /// This requires no constraints.
+    pub fn constant_bytes(&mut self, bytes: &[u8]) -> Vec<Expression<F>> {
+        logged!(self, "constant_bytes", [bytes.len()], {
+            encode_bytes_to_elements(bytes).iter().map(Expression::from).collect()
+        })
+    }
+
+    /// Asserts that the given bytes, each an 8-bit binary expression, equal the constant byte
+    /// string `expected`. The bytes are packed as by `encode_bytes_to_elements`, so this costs one
+    /// constraint per element of the encoding, excluding its length, which is checked statically.
+    ///
+    /// Panics if the number of bytes differs from the length of `expected`, since the gadget could
+    /// never be satisfied.
+    pub fn assert_bytes_equal(&mut self, bytes: &[BinaryExpression<F>], expected: &[u8]) {
+        logged!(self, "assert_bytes_equal", [bytes.len(), expected.len()], {
+            assert_eq!(bytes.len(), expected.len(),
+                       "Expected {} bytes, but got {}", expected.len(), bytes.len());
+            for byte in bytes {
+                assert_eq!(8, byte.len(), "Each byte must have 8 bits");
+            }
+
+            let expected_chunks = &self.constant_bytes(expected)[1..];
+            let chunk_pairs = bytes.chunks(bytes_per_element::<F>()).zip(expected_chunks);
+            for (chunk, expected_chunk) in chunk_pairs {
+                let chunk_value = BinaryExpression::concat(chunk).join();
+                self.assert_equal(&chunk_value, expected_chunk);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::{BinaryExpression, BinaryWire, Bn128, decode_bytes_from_elements, Element,
+                encode_bytes_to_elements, F257, GadgetBuilder, WireValues};
+    use crate::byte_encoding::bytes_per_element;
+
+    #[test]
+    fn shared_prefixes_encode_differently() {
+        let strings: [&[u8]; 6] = [b"", b"\0", b"\0\0", b"asset", b"asset\0", b"asset-1"];
+        for (i, a) in strings.iter().enumerate() {
+            for b in &strings[i + 1..] {
+                assert!(encode_bytes_to_elements::<Bn128>(a) != encode_bytes_to_elements(b));
+                assert!(encode_bytes_to_elements::<F257>(a) != encode_bytes_to_elements(b));
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // Bn128 elements hold 253 bits, or 31 whole bytes.
+        assert_eq!(31, bytes_per_element::<Bn128>());
+        for &length in &[0usize, 1, 30, 31, 32, 62, 63, 100] {
+            let bytes: Vec<u8> = (0..length).map(|i| (i * 37 + 255) as u8).collect();
+            let encoded = encode_bytes_to_elements::<Bn128>(&bytes);
+            assert_eq!(1 + length.div_ceil(31), encoded.len());
+            assert_eq!(Some(bytes.clone()), decode_bytes_from_elements(&encoded));
+
+            let encoded = encode_bytes_to_elements::<F257>(&bytes);
+            assert_eq!(1 + length, encoded.len());
+            assert_eq!(Some(bytes), decode_bytes_from_elements(&encoded));
+        }
+    }
+
+    #[test]
+    fn longest_encodable_length() {
+        let bytes = vec![0xab; 256];
+        let encoded = encode_bytes_to_elements::<F257>(&bytes);
+        assert_eq!(Element::from(256u16), encoded[0]);
+        assert_eq!(Some(bytes), decode_bytes_from_elements(&encoded));
+    }
+
+    #[test]
+    #[should_panic(expected = "too long to encode")]
+    fn length_wraps() {
+        // A length of 257 would be encoded as 0 in F257.
+        encode_bytes_to_elements::<F257>(&[0xab; 257]);
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let encoded = encode_bytes_to_elements::<Bn128>(&[0xff; 32]);
+        assert_eq!(None, decode_bytes_from_elements::<Bn128>(&[]));
+        assert_eq!(None, decode_bytes_from_elements(&encoded[..2]));
+        let too_long = [encoded.clone(), vec![Element::one()]].concat();
+        assert_eq!(None, decode_bytes_from_elements(&too_long));
+
+        // The last chunk holds one byte, so it can't be 256.
+        let mut too_wide = encoded;
+        too_wide[2] = Element::from(256u16);
+        assert_eq!(None, decode_bytes_from_elements(&too_wide));
+    }
+
+    /// Builds a gadget asserting that as many witness bytes as `actual` has equal `expected`, and
+    /// checks whether `actual` satisfies it.
+    fn bytes_equal(expected: &[u8], actual: &[u8]) -> bool {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let byte_wires: Vec<BinaryWire> =
+            (0..actual.len()).map(|_i| builder.binary_wire(8)).collect();
+        let bytes: Vec<BinaryExpression<Bn128>> =
+            byte_wires.iter().map(BinaryExpression::from).collect();
+        builder.assert_bytes_equal(&bytes, expected);
+        let gadget = builder.build();
+        assert_eq!(expected.len().div_ceil(31), gadget.size() - 8 * actual.len());
+
+        let mut values = WireValues::new();
+        for (wire, &byte) in byte_wires.iter().zip(actual) {
+            values.set_binary_unsigned(wire, &BigUint::from(byte));
+        }
+        gadget.execute(&mut values)
+    }
+
+    #[test]
+    fn assert_bytes_equal() {
+        let expected: Vec<u8> = (0..40u8).collect();
+        assert!(bytes_equal(&expected, &expected));
+        assert!(bytes_equal(b"", b""));
+
+        let mut wrong = expected.clone();
+        wrong[35] ^= 1;
+        assert!(!bytes_equal(&expected, &wrong));
+        assert!(!bytes_equal(b"asset", b"assex"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected 5 bytes, but got 4")]
+    fn assert_bytes_equal_wrong_length() {
+        bytes_equal(b"asset", b"asse");
+    }
+
+    #[test]
+    fn constant_bytes() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let constants = builder.constant_bytes(b"domain");
+        assert_eq!(0, builder.build().size());
+        let values: Vec<Element<Bn128>> =
+            constants.iter().map(|c| c.as_constant().unwrap()).collect();
+        assert_eq!(encode_bytes_to_elements::<Bn128>(b"domain"), values);
+    }
+}
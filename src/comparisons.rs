@@ -4,127 +4,349 @@
 use alloc::vec::Vec;
 
 use itertools::enumerate;
+use num::{BigUint, One};
 
 use crate::expression::{BinaryExpression, BooleanExpression, Expression};
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
+use crate::wire::Wire;
 use crate::wire_values::WireValues;
 use crate::util::concat;
 
 impl<F: Field> GadgetBuilder<F> {
     /// Assert that `x < y`.
     pub fn assert_lt(&mut self, x: &Expression<F>, y: &Expression<F>) {
-        let lt = self.lt(x, y);
-        self.assert_true(&lt);
+        logged!(self, "assert_lt", [x, y], {
+            let lt = self.lt(x, y);
+            self.assert_true(&lt);
+        })
     }
 
     /// Assert that `x <= y`.
     pub fn assert_le(&mut self, x: &Expression<F>, y: &Expression<F>) {
-        let le = self.le(x, y);
-        self.assert_true(&le);
+        logged!(self, "assert_le", [x, y], {
+            let le = self.le(x, y);
+            self.assert_true(&le);
+        })
     }
 
     /// Assert that `x > y`.
     pub fn assert_gt(&mut self, x: &Expression<F>, y: &Expression<F>) {
-        let gt = self.gt(x, y);
-        self.assert_true(&gt);
+        logged!(self, "assert_gt", [x, y], {
+            let gt = self.gt(x, y);
+            self.assert_true(&gt);
+        })
     }
 
     /// Assert that `x >= y`.
     pub fn assert_ge(&mut self, x: &Expression<F>, y: &Expression<F>) {
-        let ge = self.ge(x, y);
-        self.assert_true(&ge);
+        logged!(self, "assert_ge", [x, y], {
+            let ge = self.ge(x, y);
+            self.assert_true(&ge);
+        })
     }
 
     /// Assert that `x < y`.
     pub fn assert_lt_binary(&mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>) {
-        let lt = self.lt_binary(x, y);
-        self.assert_true(&lt);
+        logged!(self, "assert_lt_binary",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            let lt = self.lt_binary(x, y);
+            self.assert_true(&lt);
+        })
     }
 
     /// Assert that `x <= y`.
     pub fn assert_le_binary(&mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>) {
-        let le = self.le_binary(x, y);
-        self.assert_true(&le);
+        logged!(self, "assert_le_binary",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            let le = self.le_binary(x, y);
+            self.assert_true(&le);
+        })
     }
 
     /// Assert that `x > y`.
     pub fn assert_gt_binary(&mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>) {
-        let gt = self.gt_binary(x, y);
-        self.assert_true(&gt);
+        logged!(self, "assert_gt_binary",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            let gt = self.gt_binary(x, y);
+            self.assert_true(&gt);
+        })
     }
 
     /// Assert that `x >= y`.
     pub fn assert_ge_binary(&mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>)
     {
-        let ge = self.ge_binary(x, y);
-        self.assert_true(&ge);
+        logged!(self, "assert_ge_binary",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            let ge = self.ge_binary(x, y);
+            self.assert_true(&ge);
+        })
+    }
+
+    /// Assert that `x < y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn assert_lt_bounded(&mut self, x: &Expression<F>, y: &Expression<F>, bits: usize) {
+        logged!(self, "assert_lt_bounded", [x, y, bits], {
+            self.assert_cmp_bounded(x, y, bits, true, true);
+        })
+    }
+
+    /// Assert that `x <= y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn assert_le_bounded(&mut self, x: &Expression<F>, y: &Expression<F>, bits: usize) {
+        logged!(self, "assert_le_bounded", [x, y, bits], {
+            self.assert_cmp_bounded(x, y, bits, true, false);
+        })
+    }
+
+    /// Assert that `x > y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn assert_gt_bounded(&mut self, x: &Expression<F>, y: &Expression<F>, bits: usize) {
+        logged!(self, "assert_gt_bounded", [x, y, bits], {
+            self.assert_cmp_bounded(x, y, bits, false, true);
+        })
+    }
+
+    /// Assert that `x >= y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn assert_ge_bounded(&mut self, x: &Expression<F>, y: &Expression<F>, bits: usize) {
+        logged!(self, "assert_ge_bounded", [x, y, bits], {
+            self.assert_cmp_bounded(x, y, bits, false, false);
+        })
     }
 
     /// Returns `x < y`.
     pub fn lt(&mut self, x: &Expression<F>, y: &Expression<F>) -> BooleanExpression<F> {
-        self.cmp(x, y, true, true)
+        logged!(self, "lt", [x, y], {
+            self.cmp(x, y, true, true)
+        })
     }
 
     /// Returns `x <= y`.
     pub fn le(&mut self, x: &Expression<F>, y: &Expression<F>) -> BooleanExpression<F> {
-        self.cmp(x, y, true, false)
+        logged!(self, "le", [x, y], {
+            self.cmp(x, y, true, false)
+        })
     }
 
     /// Returns `x > y`.
     pub fn gt(&mut self, x: &Expression<F>, y: &Expression<F>) -> BooleanExpression<F> {
-        self.cmp(x, y, false, true)
+        logged!(self, "gt", [x, y], {
+            self.cmp(x, y, false, true)
+        })
     }
 
     /// Returns `x >= y`.
     pub fn ge(&mut self, x: &Expression<F>, y: &Expression<F>) -> BooleanExpression<F> {
-        self.cmp(x, y, false, false)
+        logged!(self, "ge", [x, y], {
+            self.cmp(x, y, false, false)
+        })
     }
 
     /// Returns `x < y`.
     pub fn lt_binary(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BooleanExpression<F> {
-        self.cmp_binary(x, y, true, true)
+        logged!(self, "lt_binary", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.cmp_binary(x, y, true, true)
+        })
     }
 
     /// Returns `x <= y`.
     pub fn le_binary(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BooleanExpression<F> {
-        self.cmp_binary(x, y, true, false)
+        logged!(self, "le_binary", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.cmp_binary(x, y, true, false)
+        })
     }
 
     /// Returns `x > y`.
     pub fn gt_binary(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BooleanExpression<F> {
-        self.cmp_binary(x, y, false, true)
+        logged!(self, "gt_binary", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.cmp_binary(x, y, false, true)
+        })
     }
 
     /// Returns `x >= y`.
     pub fn ge_binary(
         &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
     ) -> BooleanExpression<F> {
-        self.cmp_binary(x, y, false, false)
+        logged!(self, "ge_binary", [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            self.cmp_binary(x, y, false, false)
+        })
+    }
+
+    /// Returns `x < y`, where both `x` and `y` must fit in `bits` bits. Given the bound,
+    /// `y - x - 1` lies in `[-2^bits, 2^bits)`, so adding `2^bits` gives a value in
+    /// `[0, 2^(bits + 1))` which can't wrap around the field, and whose top bit is set iff `x < y`.
+    /// Without the bound, an operand close to `|F|` would behave like a negative number, so the
+    /// result could be wrong.
+    ///
+    /// The bound is enforced by range checking each operand with `split_bounded`, unless it is
+    /// already known to fit in `bits` bits (see `GadgetBuilder::known_bound`), as it is after an
+    /// earlier `split_bounded`. With both operands known to fit, this requires only `bits + 2`
+    /// constraints, whereas `lt` must split both operands into `max_bits` bits.
+    pub fn lt_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize,
+    ) -> BooleanExpression<F> {
+        logged!(self, "lt_bounded", [x, y, bits], {
+            self.cmp_bounded(x, y, bits, true, true)
+        })
+    }
+
+    /// Returns `x <= y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn le_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize,
+    ) -> BooleanExpression<F> {
+        logged!(self, "le_bounded", [x, y, bits], {
+            self.cmp_bounded(x, y, bits, true, false)
+        })
+    }
+
+    /// Returns `x > y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn gt_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize,
+    ) -> BooleanExpression<F> {
+        logged!(self, "gt_bounded", [x, y, bits], {
+            self.cmp_bounded(x, y, bits, false, true)
+        })
+    }
+
+    /// Returns `x >= y`, where both must fit in `bits` bits. See `lt_bounded`.
+    pub fn ge_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize,
+    ) -> BooleanExpression<F> {
+        logged!(self, "ge_bounded", [x, y, bits], {
+            self.cmp_bounded(x, y, bits, false, false)
+        })
+    }
+
+    /// Returns `|x - y|`, treating `x` and `y` as integers in `[0, |F|)`. Both operands are split
+    /// once into their canonical binary encodings, which a single comparison then uses to select
+    /// the sign. When `x = y`, both candidate differences are zero, so either selection is correct.
+    pub fn abs_difference(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
+        logged!(self, "abs_difference", [x, y], {
+            let x_bin = self.split_unless_constant(x, true);
+            let y_bin = self.split_unless_constant(y, true);
+            let lt = self.cmp_binary(&x_bin, &y_bin, true, true);
+            self.selection(&lt, &(y - x), &(x - y))
+        })
+    }
+
+    /// Returns `|x - y|`, as a binary expression with the same width as `x` and `y`. Since the
+    /// difference is smaller than both operands' upper bound, it always fits in that width.
+    pub fn abs_difference_binary(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryExpression<F> {
+        logged!(self, "abs_difference_binary",
+                [x.join_allowing_overflow(), y.join_allowing_overflow()], {
+            assert_eq!(x.len(), y.len());
+            let (x_exp, y_exp) = (x.join(), y.join());
+            let lt = self.cmp_binary(x, y, true, true);
+            let difference = self.selection(&lt, &(&y_exp - &x_exp), &(&x_exp - &y_exp));
+            self.split_bounded(&difference, x.len())
+        })
+    }
+
+    /// Assert that `|x - y| <= tolerance`, treating `x` and `y` as integers in `[0, |F|)`. The
+    /// absolute difference is always less than `|F|`, so it cannot exceed the comparison's width.
+    pub fn assert_within(&mut self, x: &Expression<F>, y: &Expression<F>, tolerance: &Element<F>) {
+        logged!(self, "assert_within", [x, y, tolerance], {
+            let difference = self.abs_difference(x, y);
+            self.assert_le(&difference, &Expression::from(tolerance));
+        })
     }
 
     fn cmp(
         &mut self, x: &Expression<F>, y: &Expression<F>, less: bool, strict: bool,
     ) -> BooleanExpression<F> {
+        if let (Some(x_value), Some(y_value)) = (x.as_constant(), y.as_constant()) {
+            let result = match (less, strict) {
+                (true, true) => x_value < y_value,
+                (true, false) => x_value <= y_value,
+                (false, true) => x_value > y_value,
+                (false, false) => x_value >= y_value,
+            };
+            return BooleanExpression::from(result);
+        }
+
         let (x_bin, y_bin) = if less {
             // We're asserting x <[=] y. We don't need x's canonical encoding, because the
             // non-canonical encoding would give x_bin > |F| and thus x_bin > y_bin, rendering the
             // instance unsatisfiable.
             // TODO: This only holds for assertions, not evaluations.
-            (self.split_allowing_ambiguity(x), self.split(y))
+            (self.split_unless_constant(x, false), self.split_unless_constant(y, true))
         } else {
             // Similarly, here we're asserting y <[=] x, so we don't need y's canonical encoding.
-            (self.split(x), self.split_allowing_ambiguity(y))
+            (self.split_unless_constant(x, true), self.split_unless_constant(y, false))
         };
         self.cmp_binary(&x_bin, &y_bin, less, strict)
     }
 
+    /// Returns the difference whose sign determines a bounded comparison: `larger - smaller` for
+    /// non-strict comparisons, or `larger - smaller - 1` for strict ones. It is nonnegative iff the
+    /// comparison holds, and lies in `[-2^bits, 2^bits)`.
+    fn cmp_bounded_difference(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize, less: bool, strict: bool,
+    ) -> Expression<F> {
+        assert!(bits + 1 < Element::<F>::max_bits(),
+                "Bounded comparisons require fewer than {} bits", Element::<F>::max_bits() - 1);
+        self.range_check_operand(x, bits);
+        self.range_check_operand(y, bits);
+
+        let (smaller, larger) = if less { (x, y) } else { (y, x) };
+        let difference = larger - smaller;
+        if strict {
+            difference - Expression::one()
+        } else {
+            difference
+        }
+    }
+
+    fn cmp_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize, less: bool, strict: bool,
+    ) -> BooleanExpression<F> {
+        if let (Some(_), Some(_)) = (x.as_constant(), y.as_constant()) {
+            return self.cmp(x, y, less, strict);
+        }
+        let difference = self.cmp_bounded_difference(x, y, bits, less, strict);
+        let offset = Element::from(BigUint::one() << bits);
+        let shifted_bits = self.split_bounded(&(difference + Expression::from(offset)), bits + 1);
+        shifted_bits.bits[bits].clone()
+    }
+
+    fn assert_cmp_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, bits: usize, less: bool, strict: bool,
+    ) {
+        // A negative difference would wrap around to at least |F| - 2^bits, which doesn't fit in
+        // bits bits, so it suffices to range check the difference.
+        let difference = self.cmp_bounded_difference(x, y, bits, less, strict);
+        self.split_bounded(&difference, bits);
+    }
+
+    /// Enforce that an operand of a bounded comparison fits in `bits` bits. Constants are checked
+    /// while building, and expressions with a known bound need no further constraints.
+    fn range_check_operand(&mut self, x: &Expression<F>, bits: usize) {
+        if let Some(c) = x.as_constant() {
+            assert!(c.bits() <= bits, "Constant {} does not fit in {} bits", c, bits);
+            return;
+        }
+        if !matches!(self.known_bound(x), Some(known_bits) if known_bits <= bits) {
+            self.split_bounded(x, bits);
+        }
+    }
+
+    /// Split `x` into `max_bits` bits. If `x` is a constant, its bits are already known, so no
+    /// constraints are needed. Otherwise, `canonical` determines whether the canonical encoding is
+    /// enforced, as in `split`, or whether ambiguity is allowed, as in `split_allowing_ambiguity`.
+    fn split_unless_constant(&mut self, x: &Expression<F>, canonical: bool) -> BinaryExpression<F> {
+        match x.as_constant() {
+            Some(c) => BinaryExpression::from(c.to_biguint().clone())
+                .padded(Element::<F>::max_bits()),
+            None if canonical => self.split(x),
+            None => self.split_allowing_ambiguity(x),
+        }
+    }
+
     // TODO: Consider identifying the first differing chunk with a single field element rather than
     // a bitmask. This will mean doing random access later.
     fn cmp_binary(
@@ -147,11 +369,8 @@ impl<F: Field> GadgetBuilder<F> {
 
         // Create a mask bit for each chunk index. masks[i] must equal 1 iff i is the first index
         // where the chunks differ, otherwise 0. If no chunks differ, all masks must equal 0.
-        let mask = self.wires(chunks);
-        // Each mask bit wire must equal 0 or 1.
-        for &m in &mask {
-            self.assert_boolean(&Expression::from(m));
-        }
+        // Each mask bit wire must equal 0 or 1, which binary_wire enforces.
+        let mask: Vec<Wire> = self.binary_wire(chunks).bits.iter().map(|bit| bit.wire()).collect();
         // The sum of all masks must equal 0 or 1, so that at most one mask can equal 1.
         let diff_exists = self.assert_boolean(&Expression::sum_of_wires(&mask));
 
@@ -159,8 +378,9 @@ impl<F: Field> GadgetBuilder<F> {
             let x_chunks = x_chunks.clone();
             let y_chunks = y_chunks.clone();
             let mask = mask.clone();
-            self.generator(
+            self.generator_with_outputs(
                 concat(&[x_bits.dependencies(), y_bits.dependencies()]),
+                mask.clone(),
                 move |values: &mut WireValues<F>| {
                     let mut seen_diff: bool = false;
                     for (i, &mask_bit) in enumerate(&mask).rev() {
@@ -211,8 +431,10 @@ impl<F: Field> GadgetBuilder<F> {
         //     2^bits + x - y
         // And check the most significant bit, i.e., the one with index `bits`.
         // x >= y iff that bit is set. The other cases are similar.
-        // TODO: If `bits` is very large, base might not fit in a field element. Need to generalize
-        // this to work with arbitrary bit widths, or at least an assertion to fail gracefully.
+        // z has bits + 1 bits, which must not be enough to overflow the field size.
+        assert!(bits + 1 < Element::<F>::max_bits(),
+                "Comparison chunks of {} bits are too large for a field with {}-bit elements",
+                bits, Element::<F>::max_bits());
         let base = Expression::from(
             (Element::one() << bits) - Element::from(strict));
         let z = base + if less { -diff } else { diff };
@@ -221,15 +443,19 @@ impl<F: Field> GadgetBuilder<F> {
 
     /// The number of constraints used by `cmp_binary`, given a certain chunk size.
     fn cmp_constraints(operand_bits: usize, chunk_bits: usize) -> usize {
-        let chunks = (operand_bits + chunk_bits - 1) / chunk_bits;
+        let chunks = operand_bits.div_ceil(chunk_bits);
         3 * chunks + 2 + chunk_bits
     }
 
     /// The optimal number of bits per chunk for the comparison algorithm used in `cmp_binary`.
+    /// Chunks can have at most `max_bits - 2` bits, since `cmp_subtractive` splits a value with one
+    /// more bit than the chunk size, and that split must not overflow the field size.
     fn cmp_chunk_bits(operand_bits: usize) -> usize {
+        let max_chunk_bits = Element::<F>::max_bits().saturating_sub(2);
+        assert!(max_chunk_bits >= 1, "The field is too small to support comparisons");
         let mut best_chunk_bits = 1;
         let mut best_constraints = Self::cmp_constraints(operand_bits, 1);
-        for chunk_bits in 2..Element::<F>::max_bits() {
+        for chunk_bits in 2..=max_chunk_bits {
             let constraints = Self::cmp_constraints(operand_bits, chunk_bits);
             if constraints < best_constraints {
                 best_chunk_bits = chunk_bits;
@@ -242,12 +468,117 @@ impl<F: Field> GadgetBuilder<F> {
 
 #[cfg(test)]
 mod tests {
+    use num::BigUint;
+
     use crate::Bn128;
-    use crate::expression::Expression;
+    use crate::expression::{BinaryExpression, Expression};
     use crate::field::Element;
     use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::{F11, F7};
     use crate::test_util::assert_eq_false;
     use crate::test_util::assert_eq_true;
+    use crate::test_util::exhaustive_check;
+    use crate::wire_values::WireValues;
+
+    #[test]
+    fn bounded_comparisons() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let lt = builder.lt_bounded(&x_exp, &y_exp, 64);
+        let le = builder.le_bounded(&x_exp, &y_exp, 64);
+        let gt = builder.gt_bounded(&x_exp, &y_exp, 64);
+        let ge = builder.ge_bounded(&x_exp, &y_exp, 64);
+        let gadget = builder.build();
+
+        let max = u64::MAX;
+        let boundary_values = [0, 1, 2, 1 << 32, 1 << 63, max - 1, max];
+        for &x_value in &boundary_values {
+            for &y_value in &boundary_values {
+                let mut values = values!(x => Element::from(x_value), y => Element::from(y_value));
+                assert!(gadget.execute(&mut values));
+                assert_eq!(x_value < y_value, lt.evaluate(&values));
+                assert_eq!(x_value <= y_value, le.evaluate(&values));
+                assert_eq!(x_value > y_value, gt.evaluate(&values));
+                assert_eq!(x_value >= y_value, ge.evaluate(&values));
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_comparison_cost() {
+        // Each operand is range checked, with 64 constraints for its bits and one for their sum.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.lt_bounded(&Expression::from(x), &Expression::from(y), 64);
+        assert_eq!(66 + 2 * 65, builder.build().size());
+
+        // Operands which are already known to fit need no range checks.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        builder.mark_bounded(&x_exp, 64);
+        builder.mark_bounded(&y_exp, 64);
+        builder.lt_bounded(&x_exp, &y_exp, 64);
+        let bounded_size = builder.build().size();
+        assert_eq!(66, bounded_size);
+
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.lt(&Expression::from(x), &Expression::from(y));
+        assert!(4 * bounded_size < builder.build().size());
+    }
+
+    #[test]
+    fn assert_bounded_comparisons() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        builder.assert_lt_bounded(&x_exp, &y_exp, 64);
+        let gadget = builder.build();
+        assert_eq!(65 + 2 * 65, gadget.size());
+
+        let max = u64::MAX;
+        assert!(gadget.execute(&mut values!(x => Element::from(max - 1), y => Element::from(max))));
+        assert!(gadget.execute(&mut values!(x => Element::from(0u64), y => Element::from(1u64))));
+        assert!(!gadget.execute(&mut values!(x => Element::from(max), y => Element::from(max))));
+        assert!(!gadget.execute(&mut values!(x => Element::from(max), y => Element::from(0u64))));
+    }
+
+    #[test]
+    fn bounded_comparison_known_bound() {
+        // Operands which have been range checked aren't checked again.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        builder.split_bounded(&x_exp, 32);
+        builder.split_bounded(&y_exp, 64);
+        builder.assert_le_bounded(&x_exp, &y_exp, 64);
+        // One generator for each split.
+        assert_eq!(3, builder.build().witness_generators.len());
+    }
+
+    #[test]
+    fn bounded_comparison_operand_too_large() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let lt = builder.lt_bounded(&x_exp, &y_exp, 64);
+        builder.assert_lt_bounded(&x_exp, &y_exp, 64);
+        let gadget = builder.build();
+
+        assert!(gadget.execute(&mut values!(x => Element::from(4u8), y => Element::from(5u8))));
+
+        // As an integer, p - 1 is not less than 5, but without range checks, it would act like -1.
+        // The comparison's own bits do claim that x < y, so only the range check on x catches it.
+        let mut values = values!(x => -Element::one(), y => Element::from(5u8));
+        assert!(!gadget.execute(&mut values));
+        assert_eq_true(&lt, &values);
+        let mut values = values!(x => Element::from(1u128 << 64), y => Element::from(1u8));
+        assert!(!gadget.execute(&mut values));
+        let mut values = values!(x => Element::from(1u8), y => Element::from(1u128 << 64));
+        assert!(!gadget.execute(&mut values));
+    }
 
     #[test]
     fn comparisons() {
@@ -296,4 +627,179 @@ mod tests {
         assert_eq_false(&gt, &values_large_lt);
         assert_eq_false(&ge, &values_large_lt);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn comparisons_with_constant() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        let x_exp = Expression::from(x);
+        let c = Expression::from(42u8);
+        let lt = builder.lt(&x_exp, &c);
+        let le = builder.le(&x_exp, &c);
+        let gt = builder.gt(&c, &x_exp);
+        let ge = builder.ge(&c, &x_exp);
+        let gadget = builder.build();
+
+        let mut values_41 = values!(x => 41u8.into());
+        assert!(gadget.execute(&mut values_41));
+        assert_eq_true(&lt, &values_41);
+        assert_eq_true(&le, &values_41);
+        assert_eq_true(&gt, &values_41);
+        assert_eq_true(&ge, &values_41);
+
+        let mut values_42 = values!(x => 42u8.into());
+        assert!(gadget.execute(&mut values_42));
+        assert_eq_false(&lt, &values_42);
+        assert_eq_true(&le, &values_42);
+        assert_eq_false(&gt, &values_42);
+        assert_eq_true(&ge, &values_42);
+
+        let mut values_large = values!(x => Element::from(1u128 << 80));
+        assert!(gadget.execute(&mut values_large));
+        assert_eq_false(&lt, &values_large);
+        assert_eq_false(&le, &values_large);
+        assert_eq_false(&gt, &values_large);
+        assert_eq_false(&ge, &values_large);
+    }
+
+    #[test]
+    fn comparison_with_constant_is_cheaper() {
+        let mut variable_builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (variable_builder.wire(), variable_builder.wire());
+        variable_builder.lt(&Expression::from(x), &Expression::from(y));
+        let variable_size = variable_builder.build().size();
+
+        let mut constant_builder = GadgetBuilder::<Bn128>::new();
+        let x = constant_builder.wire();
+        constant_builder.lt(&Expression::from(x), &Expression::from(1_000_000u32));
+        let constant_size = constant_builder.build().size();
+
+        // Comparing against a constant avoids splitting it and range checking its bits, which is
+        // the bulk of the cost.
+        assert!(constant_size * 3 < variable_size * 2,
+                "{} constraints with a constant, {} without", constant_size, variable_size);
+    }
+
+    #[test]
+    fn comparison_booleanity_constraints() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.binary_wire(64), builder.binary_wire(64));
+        let before = builder.booleanity_constraint_count();
+        assert_eq!(128, before);
+        builder.lt_binary(&BinaryExpression::from(&x), &BinaryExpression::from(&y));
+        let added = builder.booleanity_constraint_count() - before;
+
+        // One booleanity constraint per mask bit, one for the sum of the mask bits, and one per
+        // bit of the final subtractive comparison. The operand bits are not asserted again.
+        let chunk_bits = GadgetBuilder::<Bn128>::cmp_chunk_bits(64);
+        let chunks = 64usize.div_ceil(chunk_bits);
+        assert_eq!(chunks + 1 + (chunk_bits + 1), added);
+    }
+
+    #[test]
+    fn comparisons_of_constants() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let lt = builder.lt(&Expression::from(3u8), &Expression::from(5u8));
+        let ge = builder.ge(&Expression::from(3u8), &Expression::from(5u8));
+        let gadget = builder.build();
+        assert_eq!(0, gadget.size());
+
+        let values = WireValues::new();
+        assert_eq_true(&lt, &values);
+        assert_eq_false(&ge, &values);
+    }
+
+    #[test]
+    fn comparisons_tiny_field() {
+        // In F7, elements have 3 bits, so comparison chunks must be limited to a single bit.
+        let mut builder = GadgetBuilder::<F7>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let x_exp = Expression::from(x);
+        let y_exp = Expression::from(y);
+        let lt = builder.lt(&x_exp, &y_exp);
+        let ge = builder.ge(&x_exp, &y_exp);
+        let gadget = builder.build();
+
+        for x_value in 0u8..7 {
+            for y_value in 0u8..7 {
+                let mut values = values!(x => x_value.into(), y => y_value.into());
+                assert!(gadget.execute(&mut values));
+                assert_eq!(x_value < y_value, lt.evaluate(&values));
+                assert_eq!(x_value >= y_value, ge.evaluate(&values));
+            }
+        }
+    }
+
+    #[test]
+    fn exhaustive_assert_lt_le() {
+        let mut builder = GadgetBuilder::<F7>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.assert_lt(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+        if let Err(report) = exhaustive_check(&gadget, &[x, y], |v| v[0] < v[1]) {
+            panic!("{}", report);
+        }
+
+        let mut builder = GadgetBuilder::<F11>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.assert_le(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+        if let Err(report) = exhaustive_check(&gadget, &[x, y], |v| v[0] <= v[1]) {
+            panic!("{}", report);
+        }
+    }
+
+    #[test]
+    fn abs_difference() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let xy = builder.abs_difference(&x_exp, &y_exp);
+        let yx = builder.abs_difference(&y_exp, &x_exp);
+        let gadget = builder.build();
+
+        for (a, b, expected) in [(42u8, 63u8, 21u8), (63, 42, 21), (7, 7, 0), (0, 255, 255)] {
+            let mut values = values!(x => a.into(), y => b.into());
+            assert!(gadget.execute(&mut values));
+            assert_eq!(Element::from(expected), xy.evaluate(&values));
+            assert_eq!(Element::from(expected), yx.evaluate(&values));
+        }
+
+        // The difference between the extremes of the field.
+        let mut values = values!(x => Element::zero(), y => Element::largest_element());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::largest_element(), xy.evaluate(&values));
+        assert_eq!(Element::largest_element(), yx.evaluate(&values));
+    }
+
+    #[test]
+    fn abs_difference_binary() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.binary_wire(8), builder.binary_wire(8));
+        let (x_exp, y_exp) = (BinaryExpression::from(&x), BinaryExpression::from(&y));
+        let difference = builder.abs_difference_binary(&x_exp, &y_exp);
+        let gadget = builder.build();
+        assert_eq!(8, difference.len());
+
+        for (a, b) in [(200u8, 13u8), (13, 200), (0, 255), (99, 99)] {
+            let mut values = binary_unsigned_values!(
+                &x => &BigUint::from(a), &y => &BigUint::from(b));
+            assert!(gadget.execute(&mut values));
+            assert_eq!(BigUint::from(a.abs_diff(b)), difference.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn assert_within() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.assert_within(&Expression::from(x), &Expression::from(y), &Element::from(10u8));
+        let gadget = builder.build();
+
+        for (a, b, within) in [(50u8, 60u8, true), (60, 50, true), (50, 50, true),
+                               (50, 61, false), (61, 50, false)] {
+            let mut values = values!(x => a.into(), y => b.into());
+            assert_eq!(within, gadget.execute(&mut values), "{} vs {}", a, b);
+        }
+    }
+}
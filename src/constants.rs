@@ -0,0 +1,102 @@
+//! This module derives the default constants used by primitives such as MiMC, Poseidon and Rescue.
+//! Deriving them requires the `rand` feature; without it, constants must be given explicitly.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[cfg(feature = "rand")]
+use rand::{RngCore, SeedableRng};
+#[cfg(feature = "rand")]
+use rand_chacha::ChaCha20Rng;
+
+use crate::field::{Element, Field};
+
+/// Derives `n` pseudorandom constants for the primitive identified by `domain_tag`.
+///
+/// The ChaCha20 seed is derived from both the tag and the order of `F`, so distinct primitives, or
+/// one primitive instantiated over distinct fields, never share a stream of constants. Callers
+/// should use a tag which names the construction, such as `"MiMC"`.
+#[cfg(feature = "rand")]
+pub fn derive_constants<F: Field>(domain_tag: &str, n: usize) -> Vec<Element<F>> {
+    let mut rng = ChaCha20Rng::from_seed(derive_seed::<F>(domain_tag));
+    Element::random_vec(&mut rng, n)
+}
+
+/// Absorbs an unambiguous encoding of the tag and field order into a 32-byte seed, using ChaCha20
+/// as a compression function: each 32-byte block is XORed into the seed, which is then replaced
+/// by the first 32 bytes of the stream it keys.
+#[cfg(feature = "rand")]
+fn derive_seed<F: Field>(domain_tag: &str) -> [u8; 32] {
+    let order = F::order().to_bytes_le();
+    let mut encoding = Vec::new();
+    encoding.extend_from_slice(&(domain_tag.len() as u64).to_le_bytes());
+    encoding.extend_from_slice(domain_tag.as_bytes());
+    encoding.extend_from_slice(&(order.len() as u64).to_le_bytes());
+    encoding.extend_from_slice(&order);
+
+    let mut seed = [0u8; 32];
+    for block in encoding.chunks(32) {
+        for (s, b) in seed.iter_mut().zip(block) {
+            *s ^= b;
+        }
+        ChaCha20Rng::from_seed(seed).fill_bytes(&mut seed);
+    }
+    seed
+}
+
+/// Derives `sets * width` constants with `derive_constants`, grouped into `sets` vectors of `width`
+/// constants each, as used by the round functions of Poseidon and Rescue.
+#[cfg(feature = "rand")]
+pub(crate) fn derive_round_constants<F: Field>(
+    domain_tag: &str, sets: usize, width: usize,
+) -> Vec<Vec<Element<F>>> {
+    derive_constants(domain_tag, sets * width)
+        .chunks(width)
+        .map(|constants| constants.to_vec())
+        .collect()
+}
+
+/// Without the `rand` feature, round constants can't be derived, so they must be given explicitly.
+#[cfg(not(feature = "rand"))]
+pub(crate) fn derive_round_constants<F: Field>(
+    domain_tag: &str, _sets: usize, _width: usize,
+) -> Vec<Vec<Element<F>>> {
+    panic!("Deriving {} round constants requires the rand feature; set them explicitly instead",
+           domain_tag)
+}
+
+#[cfg(all(test, feature = "rand"))]
+mod tests {
+    use crate::constants::derive_constants;
+    use crate::field::{Bls12_381, Bn128};
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(derive_constants::<Bn128>("MiMC", 10), derive_constants::<Bn128>("MiMC", 10));
+    }
+
+    #[test]
+    fn prefix_of_longer_stream() {
+        let long = derive_constants::<Bn128>("MiMC", 10);
+        assert_eq!(&long[..4], &derive_constants::<Bn128>("MiMC", 4)[..]);
+    }
+
+    #[test]
+    fn domain_tags_separate_streams() {
+        let mimc = derive_constants::<Bn128>("MiMC", 4);
+        let poseidon = derive_constants::<Bn128>("Poseidon", 4);
+        let rescue = derive_constants::<Bn128>("Rescue", 4);
+        assert_ne!(mimc, poseidon);
+        assert_ne!(mimc, rescue);
+        assert_ne!(poseidon, rescue);
+    }
+
+    #[test]
+    fn fields_separate_streams() {
+        let bn128: Vec<_> = derive_constants::<Bn128>("MiMC", 4).iter()
+            .map(|c| c.to_biguint().clone()).collect();
+        let bls12_381: Vec<_> = derive_constants::<Bls12_381>("MiMC", 4).iter()
+            .map(|c| c.to_biguint().clone()).collect();
+        assert_ne!(bn128, bls12_381);
+    }
+}
@@ -1,19 +1,97 @@
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::collections::btree_map::BTreeMap;
+
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
+use std::hash::{Hash, Hasher};
 
 use crate::expression::Expression;
-use crate::field::Field;
+use crate::field::{Element, Field};
+use crate::wire::Wire;
 use crate::wire_values::WireValues;
 
 /// An rank-1 constraint of the form a * b = c, where a, b, and c are linear combinations of wires.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Constraint<F: Field> {
     pub a: Expression<F>,
     pub b: Expression<F>,
     pub c: Expression<F>,
 }
 
+impl<F: Field> Clone for Constraint<F> {
+    fn clone(&self) -> Self {
+        Constraint { a: self.a.clone(), b: self.b.clone(), c: self.c.clone() }
+    }
+}
+
 impl<F: Field> Constraint<F> {
+    /// The left operand of the product, `a` in `a * b = c`.
+    pub fn a(&self) -> &Expression<F> {
+        &self.a
+    }
+
+    /// The right operand of the product, `b` in `a * b = c`.
+    pub fn b(&self) -> &Expression<F> {
+        &self.b
+    }
+
+    /// The expected product, `c` in `a * b = c`.
+    pub fn c(&self) -> &Expression<F> {
+        &self.c
+    }
+
+    /// The total number of terms across `a`, `b` and `c`, including constant terms.
+    pub fn terms_total(&self) -> usize {
+        self.a.num_terms() + self.b.num_terms() + self.c.num_terms()
+    }
+
+    /// This constraint with each wire in `assignments` replaced by its assigned constant value.
+    pub fn substitute(&self, assignments: &BTreeMap<Wire, Element<F>>) -> Self {
+        Constraint {
+            a: self.a.substitute(assignments),
+            b: self.b.substitute(assignments),
+            c: self.c.substitute(assignments),
+        }
+    }
+
+    /// This constraint with each wire in `replacements` replaced by the given expression.
+    pub fn substitute_expressions(&self, replacements: &BTreeMap<Wire, Expression<F>>) -> Self {
+        Constraint {
+            a: self.a.substitute_expressions(replacements),
+            b: self.b.substitute_expressions(replacements),
+            c: self.c.substitute_expressions(replacements),
+        }
+    }
+
+    /// If this constraint determines the value of a single wire, because one side of `a * b = c`
+    /// is constant and the other is linear in that wire alone, returns the wire and its value.
+    pub(crate) fn solve_single_unknown(&self) -> Option<(Wire, Element<F>)> {
+        let (a, b, c) = (self.a.as_constant(), self.b.as_constant(), self.c.as_constant());
+        match (a, b, c) {
+            (Some(a), Some(b), _) => Self::solve_linear(&self.c, &(a * b)),
+            (Some(a), _, _) | (_, Some(a), _) if a.is_zero() =>
+                Self::solve_linear(&self.c, &Element::zero()),
+            (Some(a), None, Some(c)) if a.is_nonzero() =>
+                Self::solve_linear(&self.b, &(c / a)),
+            (None, Some(b), Some(c)) if b.is_nonzero() =>
+                Self::solve_linear(&self.a, &(c / b)),
+            _ => None,
+        }
+    }
+
+    /// If `x` has exactly one non-constant term, returns the value of its wire for which `x`
+    /// equals `value`.
+    fn solve_linear(x: &Expression<F>, value: &Element<F>) -> Option<(Wire, Element<F>)> {
+        if x.l0_norm() != 1 {
+            return None;
+        }
+        let (&wire, coefficient) = x.iter().find(|(&wire, _)| wire != Wire::ONE)?;
+        Some((wire, (value - x.constant_term()) / coefficient))
+    }
+
     pub fn evaluate(&self, wire_values: &WireValues<F>) -> bool {
         let a_value = self.a.evaluate(wire_values);
         let b_value = self.b.evaluate(wire_values);
@@ -22,20 +100,67 @@ impl<F: Field> Constraint<F> {
     }
 }
 
+impl<F: Field> PartialEq for Constraint<F> {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.a, &self.b, &self.c) == (&other.a, &other.b, &other.c)
+    }
+}
+
+impl<F: Field> Eq for Constraint<F> {}
+
+impl<F: Field> Hash for Constraint<F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.a.hash(state);
+        self.b.hash(state);
+        self.c.hash(state);
+    }
+}
+
+impl<F: Field> Ord for Constraint<F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.a, &self.b, &self.c).cmp(&(&other.a, &other.b, &other.c))
+    }
+}
+
+impl<F: Field> PartialOrd for Constraint<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Displays the constraint as `(<a>) * (<b>) = (<c>)`.
 impl<F: Field> fmt::Display for Constraint<F> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let a_str = if self.a.num_terms() >= 2 {
-            format!("({})", self.a)
-        } else {
-            format!("{}", self.a)
-        };
+        write!(f, "({}) * ({}) = ({})", self.a, self.b, self.c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Constraint, Expression, GadgetBuilder};
+    use crate::fields::small::F257;
 
-        let b_str = if self.b.num_terms() >= 2 {
-            format!("({})", self.b)
-        } else {
-            format!("{}", self.b)
+    #[test]
+    fn display() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let constraint = Constraint::<F257> {
+            a: Expression::from(x) * 2 + Expression::one(),
+            b: Expression::from(y),
+            c: Expression::from(z) - Expression::from(3u8),
         };
+        assert_eq!("(w1 * 2 + 1) * (w2) = (w3 + 254)", format!("{}", constraint));
+    }
 
-        write!(f, "{} * {} = {}", a_str, b_str, self.c)
+    #[test]
+    fn terms_total() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let constraint = Constraint::<F257> {
+            a: Expression::from(x) + Expression::from(y) + Expression::one(),
+            b: Expression::from(z),
+            c: Expression::zero(),
+        };
+        assert_eq!(4, constraint.terms_total());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,393 @@
+//! This module extends GadgetBuilder with methods for proving that a witness integer is coprime to
+//! a public modulus, including moduli which are much larger than the field.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::{BigInt, BigUint, Integer, One, Signed, Zero};
+
+use crate::expression::Expression;
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::wire_values::WireValues;
+
+/// The number of bits in each limb of a multi-limb integer, as accepted by
+/// `assert_coprime_to_constant`. Limbs are little-endian, so an integer `u` is represented by limbs
+/// `u_i` with `u = sum(u_i * 2^(LIMB_BITS * i))`.
+pub const LIMB_BITS: usize = 64;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Assert that the integer represented by `u_limbs` is coprime to the constant `n`, i.e. that
+    /// `gcd(u, n) = 1`, or equivalently that `u` is invertible modulo `n`. The limbs are
+    /// little-endian with `LIMB_BITS` bits each, and are range checked unless already known to be
+    /// bounded. `n` may be much larger than the field.
+    ///
+    /// The prover supplies a Bézout pair `(a, b)` with `a * u - b * n = 1`, computed with the
+    /// extended Euclidean algorithm. This integer identity is verified limb by limb, with a range
+    /// checked carry between each column, which requires the field to have a few more than
+    /// `2 * LIMB_BITS` bits.
+    pub fn assert_coprime_to_constant(&mut self, u_limbs: &[Expression<F>], n: &BigUint) {
+        logged!(self, "assert_coprime_to_constant", [u_limbs.len(), n], {
+            assert!(!u_limbs.is_empty(), "Expected at least one limb");
+            assert!(n > &BigUint::one(), "Expected a modulus greater than 1");
+
+            let n_limbs = to_limbs(n);
+            let (k, l) = (u_limbs.len(), n_limbs.len());
+
+            for limb in u_limbs {
+                self.assert_limb_bounded(limb, LIMB_BITS);
+            }
+
+            let outputs = {
+                let u_limbs = u_limbs.to_vec();
+                let n = n.clone();
+                let dependencies = u_limbs.iter().flat_map(Expression::dependencies).collect();
+                self.hint(dependencies, l + k, move |values: &WireValues<F>| {
+                    let u_limb_values: Vec<BigUint> = u_limbs.iter()
+                        .map(|limb| limb.evaluate(values).to_biguint().clone())
+                        .collect();
+                    let u = from_limbs(&u_limb_values);
+
+                    // If u is not invertible, any witness will fail to satisfy the constraints.
+                    let (a, b) = match modular_inverse(&u, &n) {
+                        Some(a) => {
+                            let b = (&a * &u - BigUint::one()) / &n;
+                            (a, b)
+                        }
+                        None => (BigUint::zero(), BigUint::zero()),
+                    };
+                    padded_limbs(&a, l).into_iter().chain(padded_limbs(&b, k))
+                        .map(Element::from)
+                        .collect()
+                })
+            };
+
+            let wires = outputs.wires();
+            let a_limbs: Vec<Expression<F>> = wires[..l].iter().map(Expression::from).collect();
+            let b_limbs: Vec<Expression<F>> = wires[l..].iter().map(Expression::from).collect();
+            for limb in a_limbs.iter().chain(b_limbs.iter()) {
+                self.assert_limb_bounded(limb, LIMB_BITS);
+            }
+
+            self.assert_limb_identity(&a_limbs, u_limbs, &b_limbs, n, &[Expression::one()]);
+        })
+    }
+
+    /// Assert that `a * u = b * n + c` over the integers, where `n` is a constant and the other
+    /// operands are little-endian limbs which the caller has range checked to `LIMB_BITS` bits.
+    ///
+    /// The identity is verified column by column, with a range checked carry between each column,
+    /// which requires the field to have a few more than `2 * LIMB_BITS` bits.
+    pub(crate) fn assert_limb_identity(
+        &mut self,
+        a: &[Expression<F>],
+        u: &[Expression<F>],
+        b: &[Expression<F>],
+        n: &BigUint,
+        c: &[Expression<F>],
+    ) {
+        let n_limbs = to_limbs(n);
+        let columns = (a.len() + u.len()).max(b.len() + n_limbs.len()).max(c.len() + 1) - 1;
+
+        // Each column sums at most this many products on each side, and carries are bounded
+        // accordingly. The offset makes every carry non-negative, so it can be range checked.
+        let count = a.len().min(u.len()).max(b.len().min(n_limbs.len()));
+        let count_bits = bits_usize(count);
+        let carry_bits = LIMB_BITS + count_bits + 3;
+        let carry_offset = BigInt::one() << (carry_bits - 1);
+        assert!(2 * LIMB_BITS + count_bits + 5 < Element::<F>::max_bits(),
+                "The field is too small for limb arithmetic");
+
+        let offset_carries: Vec<Expression<F>> = if columns > 1 {
+            let (a, u, b, c) = (a.to_vec(), u.to_vec(), b.to_vec(), c.to_vec());
+            let n_limbs = n_limbs.clone();
+            let carry_offset = carry_offset.clone();
+            let dependencies = a.iter().chain(&u).chain(&b).chain(&c)
+                .flat_map(Expression::dependencies)
+                .collect();
+            let outputs = self.hint(dependencies, columns - 1, move |values: &WireValues<F>| {
+                let limb_values = |limbs: &[Expression<F>]| -> Vec<BigUint> {
+                    limbs.iter().map(|limb| limb.evaluate(values).to_biguint().clone()).collect()
+                };
+                let (a, u, b, c) = (limb_values(&a), limb_values(&u), limb_values(&b),
+                                    limb_values(&c));
+                let mut carry = BigInt::zero();
+                let mut outputs = Vec::new();
+                for t in 0..columns - 1 {
+                    let column = column_difference(&a, &u, &b, &n_limbs, &c, t);
+                    carry = (column + carry) >> LIMB_BITS;
+                    outputs.push(to_element(&(&carry + &carry_offset)));
+                }
+                outputs
+            });
+            outputs.wires().iter().map(Expression::from).collect()
+        } else {
+            Vec::new()
+        };
+        for carry in &offset_carries {
+            self.assert_limb_bounded(carry, carry_bits);
+        }
+
+        // The carry into column t, with c_0 = c_columns = 0.
+        let offset = Expression::from(Element::from(carry_offset.to_biguint().unwrap()));
+        let carries: Vec<Expression<F>> = (0..=columns)
+            .map(|t| if t == 0 || t == columns {
+                Expression::zero()
+            } else {
+                &offset_carries[t - 1] - &offset
+            })
+            .collect();
+
+        let shift = Element::from(BigUint::one() << LIMB_BITS);
+        for t in 0..columns {
+            // (a * u - b * n - c)_t + c_t = c_{t + 1} * 2^LIMB_BITS
+            let mut terms = Vec::new();
+            for (i, a_i) in a.iter().enumerate() {
+                if t >= i && t - i < u.len() {
+                    terms.push(self.product(a_i, &u[t - i]));
+                }
+            }
+            for (i, b_i) in b.iter().enumerate() {
+                if t >= i && t - i < n_limbs.len() {
+                    terms.push(b_i * -Element::from(n_limbs[t - i].clone()));
+                }
+            }
+            if let Some(c_t) = c.get(t) {
+                terms.push(-c_t);
+            }
+            terms.push(carries[t].clone());
+            let lhs = Expression::sum_of_expressions(&terms);
+            self.assert_equal(&lhs, &(&carries[t + 1] * &shift));
+        }
+    }
+
+    /// Assert that the field element `u`, interpreted as an integer in `[0, |F|)`, is coprime to
+    /// the constant `n`, which must be smaller than the field.
+    ///
+    /// If `u` is known to be small enough, via `mark_bounded` or a prior range check, that
+    /// `a * u` cannot overflow the field for any `a < 2^bits(n)`, the Bézout identity is checked
+    /// with a single product constraint. Otherwise `u` is split into limbs, and this is equivalent
+    /// to `assert_coprime_to_constant`.
+    pub fn assert_element_coprime_to_constant(&mut self, u: &Expression<F>, n: &BigUint) {
+        logged!(self, "assert_element_coprime_to_constant", [u, n], {
+            assert!(n > &BigUint::one(), "Expected a modulus greater than 1");
+            assert!(n < &F::order(), "The modulus must be smaller than the field");
+            let n_bits = n.bits() as usize;
+
+            let u_bits = match self.known_bound(u) {
+                Some(u_bits) if n_bits + u_bits + 1 < Element::<F>::max_bits() => u_bits,
+                _ => {
+                    let u_limbs: Vec<Expression<F>> = self.split(u).chunks(LIMB_BITS).iter()
+                        .map(|chunk| {
+                            let limb = chunk.join();
+                            self.mark_bounded(&limb, chunk.len());
+                            limb
+                        })
+                        .collect();
+                    self.assert_coprime_to_constant(&u_limbs, n);
+                    return;
+                }
+            };
+
+            // Verify a * u = b * n + 1, where a < 2^bits(n) and b < 2^bits(u). Neither side can
+            // overflow the field, so this holds over the integers.
+            let outputs = {
+                let u = u.clone();
+                let n = n.clone();
+                self.hint(u.dependencies(), 2, move |values: &WireValues<F>| {
+                    let u_value = u.evaluate(values).to_biguint().clone();
+                    match modular_inverse(&u_value, &n) {
+                        Some(a) => {
+                            let b = (&a * &u_value - BigUint::one()) / &n;
+                            vec![Element::from(a), Element::from(b)]
+                        }
+                        None => vec![Element::zero(), Element::zero()],
+                    }
+                })
+            };
+            let a = Expression::from(outputs.wire(0));
+            let b = Expression::from(outputs.wire(1));
+            self.split_bounded(&a, n_bits);
+            self.split_bounded(&b, u_bits);
+            self.assert_product(&a, u, &(b * Element::from(n.clone()) + Expression::one()));
+        })
+    }
+
+    /// Assert that `x < 2^bits`, unless that is already known.
+    fn assert_limb_bounded(&mut self, x: &Expression<F>, bits: usize) {
+        if self.known_bound(x).is_none_or(|known_bits| known_bits > bits) {
+            self.split_bounded(x, bits);
+        }
+    }
+}
+
+/// The coefficient of `2^(LIMB_BITS * t)` in `a * u - b * n - c`, computed limb-wise.
+fn column_difference(
+    a: &[BigUint], u: &[BigUint], b: &[BigUint], n: &[BigUint], c: &[BigUint], t: usize,
+) -> BigInt {
+    let column_sum = |x: &[BigUint], y: &[BigUint]| -> BigInt {
+        let mut sum = BigUint::zero();
+        for (i, x_i) in x.iter().enumerate() {
+            if t >= i && t - i < y.len() {
+                sum += x_i * &y[t - i];
+            }
+        }
+        BigInt::from(sum)
+    };
+    let mut difference = column_sum(a, u) - column_sum(b, n);
+    if let Some(c_t) = c.get(t) {
+        difference -= BigInt::from(c_t.clone());
+    }
+    difference
+}
+
+/// The inverse of `x` modulo `n`, if it exists, found with the extended Euclidean algorithm.
+pub(crate) fn modular_inverse(x: &BigUint, n: &BigUint) -> Option<BigUint> {
+    let n = BigInt::from(n.clone());
+    let (mut r_prev, mut r) = (n.clone(), BigInt::from(x.clone()) % &n);
+    let (mut s_prev, mut s) = (BigInt::zero(), BigInt::one());
+    while !r.is_zero() {
+        let q = r_prev.div_floor(&r);
+        let r_next = &r_prev - &q * &r;
+        r_prev = core::mem::replace(&mut r, r_next);
+        let s_next = &s_prev - &q * &s;
+        s_prev = core::mem::replace(&mut s, s_next);
+    }
+    if r_prev.is_one() {
+        s_prev.mod_floor(&n).to_biguint()
+    } else {
+        None
+    }
+}
+
+fn to_limbs(x: &BigUint) -> Vec<BigUint> {
+    x.to_u64_digits().into_iter().map(BigUint::from).collect()
+}
+
+fn padded_limbs(x: &BigUint, len: usize) -> Vec<BigUint> {
+    let mut limbs = to_limbs(x);
+    limbs.resize(len, BigUint::zero());
+    limbs
+}
+
+fn from_limbs(limbs: &[BigUint]) -> BigUint {
+    limbs.iter().rev().fold(BigUint::zero(), |acc, limb| (acc << LIMB_BITS) + limb)
+}
+
+/// Maps an integer to a field element, wrapping negative values.
+fn to_element<F: Field>(x: &BigInt) -> Element<F> {
+    let order = BigInt::from(F::order());
+    let reduced = x.mod_floor(&order);
+    debug_assert!(!reduced.is_negative());
+    let (_sign, magnitude) = reduced.into_parts();
+    Element::from(magnitude)
+}
+
+fn bits_usize(x: usize) -> usize {
+    (usize::BITS - x.leading_zeros()) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use num::{BigUint, One};
+
+    use crate::{Bn128, Element, Expression, GadgetBuilder, Wire, WireValues};
+    use crate::coprimality::{LIMB_BITS, modular_inverse};
+
+    /// A 128-bit modulus standing in for an RSA modulus: the product of two 64-bit primes.
+    fn modulus() -> BigUint {
+        BigUint::from(18446744073709551557u64) * BigUint::from(18446744073709551533u64)
+    }
+
+    fn limb_values(u: &BigUint, wires: &[Wire]) -> WireValues<Bn128> {
+        let mut values = WireValues::new();
+        let digits = u.to_u64_digits();
+        for (i, &wire) in wires.iter().enumerate() {
+            values.set(wire, Element::from(digits.get(i).cloned().unwrap_or(0)));
+        }
+        values
+    }
+
+    #[test]
+    fn modular_inverse_small() {
+        let n = BigUint::from(15u8);
+        assert_eq!(Some(BigUint::from(13u8)), modular_inverse(&BigUint::from(7u8), &n));
+        assert_eq!(None, modular_inverse(&BigUint::from(6u8), &n));
+        assert_eq!(None, modular_inverse(&BigUint::from(0u8), &n));
+    }
+
+    #[test]
+    fn coprime_limbs() {
+        let n = modulus();
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let u_wires = builder.wires(2);
+        let u_limbs: Vec<Expression<Bn128>> = u_wires.iter().map(Expression::from).collect();
+        builder.assert_coprime_to_constant(&u_limbs, &n);
+        let gadget = builder.build();
+
+        let coprime = ((BigUint::one() << LIMB_BITS) * 12345u32) + 678u32;
+        assert!(gadget.execute(&mut limb_values(&coprime, &u_wires)));
+
+        // Multiples of either prime factor share a factor with n.
+        let multiple = BigUint::from(18446744073709551557u64) * 3u8;
+        assert!(!gadget.execute(&mut limb_values(&multiple, &u_wires)));
+        assert!(!gadget.execute(&mut limb_values(&BigUint::from(0u8), &u_wires)));
+    }
+
+    #[test]
+    fn coprime_limbs_tampered_hint() {
+        let n = modulus();
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let u_wires = builder.wires(2);
+        let u_limbs: Vec<Expression<Bn128>> = u_wires.iter().map(Expression::from).collect();
+        builder.assert_coprime_to_constant(&u_limbs, &n);
+        let gadget = builder.build();
+
+        let u = BigUint::from(1234567u32);
+        let mut values = limb_values(&u, &u_wires);
+        assert!(gadget.execute(&mut values));
+
+        // Perturbing any hinted limb or carry, or any product, should violate some constraint. We
+        // skip bits, which booleanity constraints cover.
+        let witness = values.as_map().clone();
+        for (&wire, value) in witness.iter() {
+            if u_wires.contains(&wire) || wire == Wire::ONE || value.bits() <= 1 {
+                continue;
+            }
+            let mut tampered = WireValues::new();
+            for (&w, v) in witness.iter() {
+                if w == Wire::ONE {
+                    continue;
+                }
+                tampered.set(w, if w == wire { v + Element::one() } else { v.clone() });
+            }
+            assert_ne!(value, tampered.get(wire));
+            let satisfied = gadget.constraints.iter().all(|c| c.evaluate(&tampered));
+            assert!(!satisfied, "Tampering with {} went undetected", wire);
+        }
+    }
+
+    #[test]
+    fn coprime_element() {
+        let n = BigUint::from(1000000007u32) * 998244353u32;
+
+        // Without a known bound, u is split into limbs.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let u = builder.wire();
+        builder.assert_element_coprime_to_constant(&Expression::from(u), &n);
+        let gadget = builder.build();
+        assert!(gadget.execute(&mut values!(u => 12345u32.into())));
+        assert!(!gadget.execute(&mut values!(u => 998244353u32.into())));
+
+        // With a known bound, a single product constraint suffices.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let u = builder.wire();
+        builder.split_bounded(&Expression::from(u), 64);
+        builder.assert_element_coprime_to_constant(&Expression::from(u), &n);
+        let gadget = builder.build();
+        // Range checks of u (64 bits), a (60 bits) and b (64 bits), plus the product.
+        assert_eq!(65 + 61 + 65 + 1, gadget.size());
+        assert!(gadget.execute(&mut values!(u => 12345u32.into())));
+        assert!(!gadget.execute(&mut values!(u => 2000000014u32.into())));
+    }
+}
@@ -0,0 +1,177 @@
+//! This module extends GadgetBuilder with methods for counting how many of several boolean
+//! conditions hold, and comparing that count against a threshold.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::{BooleanExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// The number of the given bits which are set. This requires no constraints.
+    pub fn count_true(&mut self, bits: &[BooleanExpression<F>]) -> Expression<F> {
+        logged!(self, "count_true", [bits.len()], {
+            Expression::sum_of_expressions(
+                &bits.iter().map(|bit| bit.expression().clone()).collect::<Vec<_>>())
+        })
+    }
+
+    /// Returns whether at least `k` of the given bits are set.
+    pub fn at_least_k_true(
+        &mut self, bits: &[BooleanExpression<F>], k: usize,
+    ) -> BooleanExpression<F> {
+        logged!(self, "at_least_k_true", [bits.len(), k], {
+            let n = bits.len();
+            if k == 0 {
+                return BooleanExpression::_true();
+            }
+            if k > n {
+                return BooleanExpression::_false();
+            }
+
+            // The count is at most n < 2^w, and k <= n, so 2^w + count - k lies in [1, 2^(w+1)).
+            // Its bit w is set iff count >= k.
+            let w = count_bits(n);
+            let count = self.count_true(bits);
+            let z = count + Expression::from(Element::<F>::one() << w) - Expression::from(k);
+            self.split_bounded(&z, w + 1).bits[w].clone()
+        })
+    }
+
+    /// Assert that at least `k` of the given bits are set. Panics if `k` exceeds the number of
+    /// bits, since the assertion could never hold.
+    pub fn assert_at_least_k_true(&mut self, bits: &[BooleanExpression<F>], k: usize) {
+        logged!(self, "assert_at_least_k_true", [bits.len(), k], {
+            let n = bits.len();
+            assert!(k <= n, "Cannot require {} of {} bits to be set", k, n);
+            if k == 0 {
+                return;
+            }
+
+            // count - k is at most n - k < 2^w if count >= k. Otherwise it wraps around to a value
+            // near |F|, which doesn't fit in w bits.
+            let count = self.count_true(bits);
+            self.split_bounded(&(count - Expression::from(k)), count_bits(n));
+        })
+    }
+
+    /// Assert that exactly `k` of the given bits are set. This requires a single constraint.
+    pub fn assert_exactly_k_true(&mut self, bits: &[BooleanExpression<F>], k: usize) {
+        logged!(self, "assert_exactly_k_true", [bits.len(), k], {
+            let count = self.count_true(bits);
+            self.assert_equal(&count, &Expression::from(k));
+        })
+    }
+}
+
+/// The number of bits needed to represent any count in `[0, n]`.
+fn count_bits(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::BooleanExpression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::test_util::{assert_eq_false, assert_eq_true};
+    use crate::wire::BooleanWire;
+    use crate::wire_values::WireValues;
+
+    const N: usize = 10;
+    const K: usize = 6;
+
+    /// Sets the first `count` wires, and clears the rest.
+    fn values_with_count(wires: &[BooleanWire], count: usize) -> WireValues<F257> {
+        let mut values = WireValues::new();
+        for (i, &wire) in wires.iter().enumerate() {
+            values.set_boolean(wire, i < count);
+        }
+        values
+    }
+
+    #[test]
+    fn count_true() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..N).map(|_i| builder.boolean_wire()).collect();
+        let bits: Vec<BooleanExpression<F257>> =
+            wires.iter().map(BooleanExpression::from).collect();
+        let count = builder.count_true(&bits);
+        let gadget = builder.build();
+
+        // Only the booleanity constraints are needed.
+        assert_eq!(N, gadget.size());
+        let mut values = values_with_count(&wires, K);
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(K), count.evaluate(&values));
+    }
+
+    #[test]
+    fn assert_at_least_k_true() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..N).map(|_i| builder.boolean_wire()).collect();
+        let bits: Vec<BooleanExpression<F257>> =
+            wires.iter().map(BooleanExpression::from).collect();
+        builder.assert_at_least_k_true(&bits, K);
+        let gadget = builder.build();
+
+        assert!(!gadget.execute(&mut values_with_count(&wires, K - 1)));
+        assert!(gadget.execute(&mut values_with_count(&wires, K)));
+        assert!(gadget.execute(&mut values_with_count(&wires, K + 1)));
+        assert!(gadget.execute(&mut values_with_count(&wires, N)));
+    }
+
+    #[test]
+    fn assert_exactly_k_true() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..N).map(|_i| builder.boolean_wire()).collect();
+        let bits: Vec<BooleanExpression<F257>> =
+            wires.iter().map(BooleanExpression::from).collect();
+        builder.assert_exactly_k_true(&bits, K);
+        let gadget = builder.build();
+
+        assert!(!gadget.execute(&mut values_with_count(&wires, K - 1)));
+        assert!(gadget.execute(&mut values_with_count(&wires, K)));
+        assert!(!gadget.execute(&mut values_with_count(&wires, K + 1)));
+    }
+
+    #[test]
+    fn at_least_k_true() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..N).map(|_i| builder.boolean_wire()).collect();
+        let bits: Vec<BooleanExpression<F257>> =
+            wires.iter().map(BooleanExpression::from).collect();
+        let at_least = builder.at_least_k_true(&bits, K);
+        let gadget = builder.build();
+
+        for count in 0..=N {
+            let mut values = values_with_count(&wires, count);
+            assert!(gadget.execute(&mut values));
+            if count >= K {
+                assert_eq_true(&at_least, &values);
+            } else {
+                assert_eq_false(&at_least, &values);
+            }
+        }
+    }
+
+    #[test]
+    fn at_least_k_true_trivial_thresholds() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..N).map(|_i| builder.boolean_wire()).collect();
+        let bits: Vec<BooleanExpression<F257>> =
+            wires.iter().map(BooleanExpression::from).collect();
+        let zero = builder.at_least_k_true(&bits, 0);
+        let too_many = builder.at_least_k_true(&bits, N + 1);
+        let gadget = builder.build();
+
+        for &count in &[0, N] {
+            let mut values = values_with_count(&wires, count);
+            assert!(gadget.execute(&mut values));
+            assert_eq_true(&zero, &values);
+            assert_eq_false(&too_many, &values);
+        }
+    }
+}
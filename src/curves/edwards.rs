@@ -1,8 +1,11 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 
-use crate::{Element, Evaluable, Expression, Field, GadgetBuilder, Group, GroupExpression, WireValues};
+use crate::{BooleanExpression, ComponentCountError, Element, Evaluable, Expression, Field,
+            GadgetBuilder, Group, GroupExpression, HashFunction, PointEncoding, Wire, WireValues};
+use crate::group::bind_components;
 
 /// Trait used to represent Edwards Curves and Twisted Edwards Curves. Note that the `a`
 /// parameter can be set to 1 to represent the less-general non-twisted Edwards Curves.
@@ -12,8 +15,8 @@ pub trait EdwardsCurve<F: Field> {
 }
 
 pub struct EdwardsGroup<F: Field, C: EdwardsCurve<F>> {
-    phantom_f: PhantomData<*const F>,
-    phantom_c: PhantomData<*const C>,
+    phantom_f: PhantomData<fn() -> F>,
+    phantom_c: PhantomData<fn() -> C>,
 }
 
 
@@ -22,14 +25,18 @@ impl<F: Field, C: EdwardsCurve<F>> Group<F> for EdwardsGroup<F, C> {
     type GroupElement = EdwardsPoint<F, C>;
     type GroupExpression = EdwardsExpression<F, C>;
 
+    /// The twisted Edwards addition law is complete if `a` is a square and `d` is not, as for
+    /// Jubjub. Curves which don't meet this condition shouldn't be used with this group.
+    const HAS_COMPLETE_ADDITION: bool = true;
+
     fn identity_element() -> Self::GroupElement {
         EdwardsPoint::new(Element::zero(), Element::one())
     }
 
     /// Adds two points on an `EdwardsCurve` using the standard algorithm for Twisted Edwards
-    /// Curves.
+    /// Curves. The cross terms `x1 y2 + y1 x2` are derived from `(x1 + y1) (x2 + y2)`, which shares
+    /// the products `x1 x2` and `y1 y2` with the rest of the formula, so this uses 6 constraints.
     // TODO: Add special case for variable + constant addition.
-    // TODO: This uses 7 constraints, but we can get this down to 6, as described in the Zcash spec.
     fn add_expressions(
         builder: &mut GadgetBuilder<F>,
         lhs: &Self::GroupExpression,
@@ -39,13 +46,15 @@ impl<F: Field, C: EdwardsCurve<F>> Group<F> for EdwardsGroup<F, C> {
         let d = C::d();
         let EdwardsExpression { x: x1, y: y1, .. } = lhs;
         let EdwardsExpression { x: x2, y: y2, .. } = rhs;
-        let x1y2 = builder.product(&x1, &y2);
-        let x2y1 = builder.product(&y1, &x2);
-        let x1x2 = builder.product(&x1, &x2);
-        let x1x2y1y2 = builder.product(&x1y2, &x2y1);
-        let y1y2 = builder.product(&y1, &y2);
+        let x1x2 = builder.product(x1, x2);
+        let y1y2 = builder.product(y1, y2);
+        let sums_product = builder.product(&(x1 + y1), &(x2 + y2));
+        let x1x2y1y2 = builder.product(&x1x2, &y1y2);
+        // quotient_unsafe asserts the cross-multiplied form x3 * denominator = numerator, using a
+        // single constraint and no inverse. This is sound since, on a complete curve, the
+        // denominators 1 +- d x1 x2 y1 y2 are nonzero for any points on the curve.
         let x3 = builder.quotient_unsafe(
-            &(x1y2 + x2y1),
+            &(sums_product - &x1x2 - &y1y2),
             &(&x1x2y1y2 * &d + Expression::one()));
         let y3 = builder.quotient_unsafe(
             &(y1y2 - &x1x2 * &a),
@@ -53,6 +62,22 @@ impl<F: Field, C: EdwardsCurve<F>> Group<F> for EdwardsGroup<F, C> {
         EdwardsExpression::new_unsafe(x3, y3)
     }
 
+    /// Adds two points directly, with the same formula as `add_expressions`. Doubling also uses
+    /// this, via the default `double_element`.
+    fn add_elements(lhs: &Self::GroupElement, rhs: &Self::GroupElement) -> Self::GroupElement {
+        let d = C::d();
+        let EdwardsPoint { x: x1, y: y1, .. } = lhs;
+        let EdwardsPoint { x: x2, y: y2, .. } = rhs;
+        let x1x2 = x1 * x2;
+        let y1y2 = y1 * y2;
+        let dx1x2y1y2 = &d * &x1x2 * &y1y2;
+        EdwardsPoint {
+            x: (x1 * y2 + x2 * y1) / (Element::one() + &dx1x2y1y2),
+            y: (y1y2 - C::a() * x1x2) / (Element::one() - dx1x2y1y2),
+            phantom: PhantomData,
+        }
+    }
+
     // TODO: improve constraint count
     /// Naive implementation of the doubling algorithm for twisted Edwards curves.
     ///
@@ -65,9 +90,9 @@ impl<F: Field, C: EdwardsCurve<F>> Group<F> for EdwardsGroup<F, C> {
         let EdwardsExpression { x, y, .. } = point;
         let a = C::a();
 
-        let xy = builder.product(&x, &y);
-        let xx = builder.product(&x, &x);
-        let yy = builder.product(&y, &y);
+        let xy = builder.product(x, y);
+        let xx = builder.product(x, x);
+        let yy = builder.product(y, y);
         let x_2 = builder.quotient_unsafe(&(&xy * Element::from(2u8)), &(&xx * &a + &yy));
         let y_2 = builder.quotient_unsafe(&(&yy - &xx * &a),
                                    &(-&xx * &a - &yy + Expression::from(2u8)));
@@ -75,16 +100,126 @@ impl<F: Field, C: EdwardsCurve<F>> Group<F> for EdwardsGroup<F, C> {
         EdwardsExpression::new_unsafe(x_2, y_2)
     }
 
+    /// Edwards points support both encodings. In the compressed encoding `[y, sign]`, `sign` is
+    /// the least significant bit of the canonical encoding of `x`. Given `y`, the curve equation
+    /// determines `x` up to negation, and `x` and `-x` have opposite parities unless `x = 0`, so
+    /// `sign` determines the point. Binding the compressed encoding requires a canonical split of
+    /// `x`.
+    fn bind_as_public(
+        builder: &mut GadgetBuilder<F>,
+        point: &Self::GroupExpression,
+        encoding: PointEncoding,
+    ) -> Vec<Wire> {
+        match encoding {
+            PointEncoding::Coordinates => bind_components(builder, &point.to_components()),
+            PointEncoding::Compressed => {
+                let x_bits = builder.split(&point.x);
+                bind_components(builder, &[point.y.clone(), x_bits.bits[0].expression().clone()])
+            }
+        }
+    }
+
+    fn encode_point(element: &Self::GroupElement, encoding: PointEncoding) -> Vec<Element<F>> {
+        match encoding {
+            PointEncoding::Coordinates => vec![element.x.clone(), element.y.clone()],
+            PointEncoding::Compressed =>
+                vec![element.y.clone(), Element::from(element.x.to_biguint().bit(0))],
+        }
+    }
+
     // TODO: implement Daira's algorithm from https://github.com/zcash/zcash/issues/3924
     // TODO: optimize for fixed-base multiplication using windowing, given a constant expression
 }
 
+/// The number of candidate points considered by `try_and_increment`. Each candidate succeeds with
+/// probability roughly 1/2, so mapping an input fails with probability roughly `2^-32`.
+const TRY_AND_INCREMENT_ATTEMPTS: usize = 32;
+
+impl<F: Field, C: EdwardsCurve<F>> EdwardsGroup<F, C> {
+    /// Map `input` to a point on the curve using the try-and-increment method: for counters
+    /// `i = 0, 1, ...`, take `x = hash(input, i)` and use the first `x` for which the curve
+    /// equation can be solved for `y`. The result is not multiplied by the cofactor.
+    ///
+    /// The point is determined up to the sign of `y`, which the prover is free to choose. Adding
+    /// the point `(0, -1)`, which has order 2, maps `(x, y)` to `(-x, -y)`, so the other choice is
+    /// `(x, -y) = -((x, y) + (0, -1))`. Clearing the cofactor removes the order 2 component, so
+    /// the two choices become negations of one another, which share a `y` coordinate.
+    ///
+    /// To prevent the prover from skipping a valid candidate, each rejected candidate comes with a
+    /// proof that its `y^2` is not a square, in the form of a square root of `n y^2` for a fixed
+    /// non-square `n`. This requires `d` to be a non-square, so that `y^2` is always defined. A
+    /// candidate with `y = 0` could be rejected by a malicious prover, but such a candidate arises
+    /// with negligible probability.
+    ///
+    /// If none of the candidates succeed, the gadget will not be satisfiable.
+    pub fn try_and_increment<HF: HashFunction<F>>(
+        builder: &mut GadgetBuilder<F>,
+        input: &Expression<F>,
+        hash: &HF,
+    ) -> EdwardsExpression<F, C> {
+        let nonresidue = Element::smallest_quadratic_nonresidue();
+
+        // The sum of the selected candidate's coordinates, and a flag which is 1 until some
+        // candidate has been selected.
+        let mut x = Expression::zero();
+        let mut y = Expression::zero();
+        let mut none_selected = Expression::one();
+
+        for i in 0..TRY_AND_INCREMENT_ATTEMPTS {
+            let x_i = hash.hash(builder, &[input.clone(), Expression::from(i as u64)]);
+
+            // From a x^2 + y^2 = 1 + d x^2 y^2, we have y^2 = (1 - a x^2) / (1 - d x^2).
+            let x_i_squared = builder.product(&x_i, &x_i);
+            let numerator = Expression::one() - &x_i_squared * C::a();
+            let denominator = Expression::one() - &x_i_squared * C::d();
+
+            // If y^2 is a square, the prover supplies r = y. Otherwise it supplies r = sqrt(n y^2).
+            let is_square_wire = builder.boolean_wire();
+            let is_square = BooleanExpression::from(is_square_wire);
+            let r = builder.wire();
+            let is_square_numerator = builder.product(is_square.expression(), &numerator);
+            let r_squared = builder.product(&Expression::from(r), &Expression::from(r));
+            let expected = &numerator * &nonresidue
+                + is_square_numerator * (Element::one() - &nonresidue);
+            builder.assert_product(&r_squared, &denominator, &expected);
+
+            {
+                let numerator = numerator.clone();
+                let denominator = denominator.clone();
+                let nonresidue = nonresidue.clone();
+                builder.generator_with_outputs(
+                    [numerator.dependencies(), denominator.dependencies()].concat(),
+                    vec![is_square_wire.wire(), r],
+                    move |values: &mut WireValues<F>| {
+                        let y_squared = numerator.evaluate(values)
+                            / denominator.evaluate(values);
+                        let (is_square_value, r_value) = match y_squared.square_root() {
+                            Some(y) => (true, y),
+                            None => (false, (&y_squared * &nonresidue).square_root().unwrap()),
+                        };
+                        values.set_boolean(is_square_wire, is_square_value);
+                        values.set(r, r_value);
+                    },
+                );
+            }
+
+            let selected = builder.product(&none_selected, is_square.expression());
+            x += builder.product(&selected, &x_i);
+            y += builder.product(&selected, &Expression::from(r));
+            none_selected -= selected;
+        }
+
+        builder.assert_zero(&none_selected);
+        EdwardsExpression::new_unsafe(x, y)
+    }
+}
+
 /// An embedded Edwards curve point defined over the same base field as
 /// the constraint system, with affine coordinates as elements.
 pub struct EdwardsPoint<F: Field, C: EdwardsCurve<F>> {
     pub x: Element<F>,
     pub y: Element<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
 }
 
 impl<F: Field, C: EdwardsCurve<F>> Clone for EdwardsPoint<F, C> {
@@ -122,7 +257,7 @@ impl<F: Field, C: EdwardsCurve<F>> EdwardsPoint<F, C> {
 pub struct EdwardsExpression<F: Field, C: EdwardsCurve<F>> {
     pub x: Expression<F>,
     pub y: Expression<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
 }
 
 impl<F: Field, C: EdwardsCurve<F>> EdwardsExpression<F, C> {
@@ -135,14 +270,31 @@ impl<F: Field, C: EdwardsCurve<F>> EdwardsExpression<F, C> {
         x: Expression<F>,
         y: Expression<F>,
     ) -> EdwardsExpression<F, C> {
-        let x_squared = builder.product(&x, &x);
-        let y_squared = builder.product(&y, &y);
-        let x_squared_y_squared = builder.product(&x_squared, &y_squared);
-        builder.assert_equal(&(&x_squared * C::a() + &y_squared),
-                             &(&x_squared_y_squared * C::d() + Expression::one()));
+        let (lhs, rhs) = Self::curve_equation_sides(builder, &x, &y);
+        builder.assert_equal(&lhs, &rhs);
         EdwardsExpression::new_unsafe(x, y)
     }
 
+    /// Returns a boolean which is on iff this point satisfies the curve equation
+    /// `a * x^2 + y^2 = 1 + d * x^2 * y^2`. Unlike `new`, this does not constrain the point to be
+    /// on the curve, so it can be used on points created with `new_unsafe`.
+    pub fn is_on_curve(&self, builder: &mut GadgetBuilder<F>) -> BooleanExpression<F> {
+        let (lhs, rhs) = Self::curve_equation_sides(builder, &self.x, &self.y);
+        builder.equal(&lhs, &rhs)
+    }
+
+    /// The two sides of the curve equation, `a * x^2 + y^2` and `1 + d * x^2 * y^2`.
+    fn curve_equation_sides(
+        builder: &mut GadgetBuilder<F>,
+        x: &Expression<F>,
+        y: &Expression<F>,
+    ) -> (Expression<F>, Expression<F>) {
+        let x_squared = builder.product(x, x);
+        let y_squared = builder.product(y, y);
+        let x_squared_y_squared = builder.product(&x_squared, &y_squared);
+        (&x_squared * C::a() + &y_squared, &x_squared_y_squared * C::d() + Expression::one())
+    }
+
     /// Creates an `EdwardsExpression` from two arbitrary coordinates of type `Expression`.
     /// This method is unsafe and should only be used when the coordinates are proven
     /// to exist on the curve.
@@ -152,15 +304,34 @@ impl<F: Field, C: EdwardsCurve<F>> EdwardsExpression<F, C> {
 }
 
 impl<F: Field, C: EdwardsCurve<F>> GroupExpression<F> for EdwardsExpression<F, C> {
+    const COMPONENTS: usize = 2;
+
     fn compressed(&self) -> &Expression<F> { &self.y }
     fn to_components(&self) -> Vec<Expression<F>> { vec![self.x.clone(), self.y.clone()] }
 
     /// Given two group components of type `Expression`, creates an `EdwardsExpression`. Used
     /// in the generic implementation of scalar multiplication for groups.
-    fn from_components_unsafe(mut components: Vec<Expression<F>>) -> Self {
-        let x = components.remove(0);
-        let y = components.remove(0);
-        Self::new_unsafe(x, y)
+    fn from_components_unsafe(components: Vec<Expression<F>>) -> Self {
+        match Self::try_from(components.as_slice()) {
+            Ok(expression) => expression,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Creates an `EdwardsExpression` from its `[x, y]` coordinates, without checking that they are on
+/// the curve, as with `new_unsafe`. Fails if there are not exactly two coordinates.
+impl<F: Field, C: EdwardsCurve<F>> TryFrom<&[Expression<F>]> for EdwardsExpression<F, C> {
+    type Error = ComponentCountError;
+
+    fn try_from(components: &[Expression<F>]) -> Result<Self, Self::Error> {
+        match components {
+            [x, y] => Ok(Self::new_unsafe(x.clone(), y.clone())),
+            _ => Err(ComponentCountError {
+                expected: <Self as GroupExpression<F>>::COMPONENTS,
+                actual: components.len(),
+            }),
+        }
     }
 }
 
@@ -196,11 +367,17 @@ impl<F: Field, C: EdwardsCurve<F>> Evaluable<F, EdwardsPoint<F, C>> for EdwardsE
 
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
     use std::str::FromStr;
 
-    use crate::{EdwardsExpression, Expression, GadgetBuilder, Group, WireValues, EdwardsGroup};
+    use crate::{ComponentCountError, EdwardsExpression, EdwardsPoint, Expression, GadgetBuilder,
+                Group, GroupExpression, WireValues, EdwardsGroup};
     use crate::field::{Bls12_381, Element};
     use crate::{JubJub};
+    #[cfg(feature = "rand")]
+    use crate::test_util::deterministic_elements;
+
+    type JubJubGroup = EdwardsGroup<Bls12_381, JubJub>;
 
     #[test]
     fn point_on_curve() {
@@ -215,7 +392,7 @@ mod tests {
         let y_exp = Expression::from(y);
 
         let mut builder = GadgetBuilder::<Bls12_381>::new();
-        let p = EdwardsExpression::<Bls12_381, JubJub>::new(
+        let _p = EdwardsExpression::<Bls12_381, JubJub>::new(
             &mut builder, x_exp, y_exp);
 
         let gadget = builder.build();
@@ -235,7 +412,7 @@ mod tests {
         let y_exp = Expression::from(y);
 
         let mut builder = GadgetBuilder::<Bls12_381>::new();
-        let p
+        let _p
             = EdwardsExpression::<Bls12_381, JubJub>::new(
             &mut builder,
             x_exp,
@@ -257,7 +434,7 @@ mod tests {
             "44412834903739585386157632289020980010620626017712148233229312325549216099227"
         ).unwrap();
 
-        EdwardsExpression::<Bls12_381, JubJub>::from((x, y));
+        let _ = EdwardsExpression::<Bls12_381, JubJub>::from((x, y));
     }
 
     #[test]
@@ -284,6 +461,31 @@ mod tests {
         assert_eq!(p3.y.evaluate(&values), Element::one());
     }
 
+    #[test]
+    fn add_elements_matches_gadget() {
+        let x = Element::<Bls12_381>::from_str(
+            "11076627216317271660298050606127911965867021807910416450833192264015104452986"
+        ).unwrap();
+        let y = Element::<Bls12_381>::from_str(
+            "44412834903739585386157632289020980010620626017712148233229312325549216099227"
+        ).unwrap();
+        let p = EdwardsPoint::<Bls12_381, JubJub>::new(x, y);
+        let p_exp = EdwardsExpression::from(&p);
+
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let double = JubJubGroup::add_expressions(&mut builder, &p_exp, &p_exp);
+        let triple = JubJubGroup::add_expressions(&mut builder, &p_exp, &double);
+        let mut values = WireValues::new();
+        assert!(builder.build().execute(&mut values));
+
+        let double_element = JubJubGroup::double_element(&p);
+        let triple_element = JubJubGroup::add_elements(&p, &double_element);
+        assert_eq!(double.x.evaluate(&values), double_element.x);
+        assert_eq!(double.y.evaluate(&values), double_element.y);
+        assert_eq!(triple.x.evaluate(&values), triple_element.x);
+        assert_eq!(triple.y.evaluate(&values), triple_element.y);
+    }
+
     #[test]
     fn mul_scalar() {
         let x1 = Element::<Bls12_381>::from_str(
@@ -303,7 +505,7 @@ mod tests {
             = EdwardsExpression::<Bls12_381, JubJub>::from((x1, y1));
 
         let mut builder = GadgetBuilder::<Bls12_381>::new();
-        let p3 = EdwardsGroup::<Bls12_381, JubJub>::mul_scalar_expression(
+        let _p3 = EdwardsGroup::<Bls12_381, JubJub>::mul_scalar_expression(
             &mut builder,
             &p1,
             &scalar,
@@ -314,4 +516,159 @@ mod tests {
 
         // TODO: include assertion
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn is_on_curve() {
+        let x = Element::from_str(
+            "11076627216317271660298050606127911965867021807910416450833192264015104452986"
+        ).unwrap();
+        let y = Element::from_str(
+            "44412834903739585386157632289020980010620626017712148233229312325549216099227"
+        ).unwrap();
+
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let (x_wire, y_wire) = (builder.wire(), builder.wire());
+        let p = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+            Expression::from(x_wire), Expression::from(y_wire));
+        let on_curve = p.is_on_curve(&mut builder);
+        let gadget = builder.build();
+
+        // x^2, y^2 and x^2 y^2 should each be computed once, followed by a single equality check.
+        let mut equality_builder = GadgetBuilder::<Bls12_381>::new();
+        let (a, b) = (equality_builder.wire(), equality_builder.wire());
+        equality_builder.equal(&Expression::from(a), &Expression::from(b));
+        assert_eq!(3 + equality_builder.build().size(), gadget.size());
+
+        let mut values = values!(x_wire => x.clone(), y_wire => y.clone());
+        assert!(gadget.execute(&mut values));
+        assert!(on_curve.evaluate(&values));
+
+        let mut values = values!(x_wire => x, y_wire => y + Element::one());
+        assert!(gadget.execute(&mut values));
+        assert!(!on_curve.evaluate(&values));
+    }
+
+    #[test]
+    fn is_identity() {
+        let x = Element::from_str(
+            "11076627216317271660298050606127911965867021807910416450833192264015104452986"
+        ).unwrap();
+        let y = Element::from_str(
+            "44412834903739585386157632289020980010620626017712148233229312325549216099227"
+        ).unwrap();
+
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let (x_wire, y_wire) = (builder.wire(), builder.wire());
+        let p = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+            Expression::from(x_wire), Expression::from(y_wire));
+        let identity = JubJubGroup::is_identity(&mut builder, &p);
+        let gadget = builder.build();
+
+        let mut values = values!(x_wire => Element::zero(), y_wire => Element::one());
+        assert!(gadget.execute(&mut values));
+        assert!(identity.evaluate(&values));
+
+        let mut values = values!(x_wire => x, y_wire => y);
+        assert!(gadget.execute(&mut values));
+        assert!(!identity.evaluate(&values));
+
+        // A point sharing one coordinate with the identity is still not the identity.
+        let mut values = values!(x_wire => Element::zero(), y_wire => -Element::one());
+        assert!(gadget.execute(&mut values));
+        assert!(!identity.evaluate(&values));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn mul_scalar_ladder() {
+        let x = Element::<Bls12_381>::from_str(
+            "11076627216317271660298050606127911965867021807910416450833192264015104452986"
+        ).unwrap();
+        let y = Element::<Bls12_381>::from_str(
+            "44412834903739585386157632289020980010620626017712148233229312325549216099227"
+        ).unwrap();
+
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let (x_wire, y_wire, scalar_wire) = (builder.wire(), builder.wire(), builder.wire());
+        let p = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+            Expression::from(x_wire), Expression::from(y_wire));
+        let scalar = Expression::from(scalar_wire);
+        let double_and_add = JubJubGroup::mul_scalar_expression(&mut builder, &p, &scalar);
+        let ladder = JubJubGroup::mul_scalar_expression_ladder(&mut builder, &p, &scalar);
+        let gadget = builder.build();
+
+        for scalar in deterministic_elements(3) {
+            let mut values = values!(
+                x_wire => x.clone(), y_wire => y.clone(), scalar_wire => scalar);
+            assert!(gadget.execute(&mut values));
+            assert_eq!(double_and_add.x.evaluate(&values), ladder.x.evaluate(&values));
+            assert_eq!(double_and_add.y.evaluate(&values), ladder.y.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn mul_scalar_ladder_cost() {
+        fn cost(ladder: bool) -> usize {
+            let mut builder = GadgetBuilder::<Bls12_381>::new();
+            let p = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+                Expression::from(builder.wire()), Expression::from(builder.wire()));
+            let scalar = Expression::from(builder.wire());
+            if ladder {
+                JubJubGroup::mul_scalar_expression_ladder(&mut builder, &p, &scalar);
+            } else {
+                JubJubGroup::mul_scalar_expression(&mut builder, &p, &scalar);
+            }
+            builder.build().size()
+        }
+
+        // Per bit, double-and-add uses a selection per coordinate (2 constraints), an addition (6)
+        // and a doubling (5). The ladder replaces the selections with two conditional swaps (4).
+        // Affine Edwards addition and doubling have no products in common, so nothing can be
+        // shared between them, and the ladder ends up about 2 constraints per bit more expensive.
+        // It is still useful when every bit should be processed identically. Splitting the
+        // 255-bit scalar adds 256 constraints to each. Double-and-add ends up slightly below its
+        // estimate, since operations involving the constant identity are partly free.
+        assert_eq!(13 * 255 + 256 - 6, cost(false));
+        assert_eq!(15 * 255 + 256, cost(true));
+    }
+
+    #[test]
+    fn operation_costs() {
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let wires = builder.wires(4);
+        let p1 = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+            Expression::from(wires[0]), Expression::from(wires[1]));
+        let p2 = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+            Expression::from(wires[2]), Expression::from(wires[3]));
+        JubJubGroup::add_expressions(&mut builder, &p1, &p2);
+        // Four products, including (x1 + y1) (x2 + y2) for the cross terms, and two quotients.
+        // Computing x1 y2 and y1 x2 directly would take 7.
+        assert_eq!(6, builder.build().size());
+
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let wires = builder.wires(2);
+        let p = EdwardsExpression::<Bls12_381, JubJub>::new_unsafe(
+            Expression::from(wires[0]), Expression::from(wires[1]));
+        JubJubGroup::double_expression(&mut builder, &p);
+        assert_eq!(5, builder.build().size());
+    }
+
+    #[test]
+    fn try_from_components() {
+        let components = vec![Expression::<Bls12_381>::zero(), Expression::one()];
+        let p = EdwardsExpression::<Bls12_381, JubJub>::try_from(components.as_slice()).unwrap();
+        assert_eq!(components, p.to_components());
+
+        let too_many = vec![Expression::zero(), Expression::one(), Expression::one()];
+        let result = EdwardsExpression::<Bls12_381, JubJub>::try_from(too_many.as_slice());
+        assert_eq!(Some(ComponentCountError { expected: 2, actual: 3 }), result.err());
+        let result = EdwardsExpression::<Bls12_381, JubJub>::try_from(&components[..1]);
+        assert_eq!(Some(ComponentCountError { expected: 2, actual: 1 }), result.err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected 2 components, but got 1")]
+    fn from_components_unsafe_wrong_count() {
+        EdwardsExpression::<Bls12_381, JubJub>::from_components_unsafe(vec![Expression::zero()]);
+    }
+}
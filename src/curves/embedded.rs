@@ -0,0 +1,37 @@
+use crate::field::Field;
+
+/// A curve whose points are defined over the field which constraints are expressed in, so that its
+/// arithmetic can be performed natively in a gadget. For example, `JubJub` is defined over the
+/// scalar field of BLS12-381, so it is embedded in `Bls12_381`.
+///
+/// Curve traits such as `EdwardsCurve` are generic in the field, but a given curve is only
+/// implemented for its host field, so using a curve with the wrong field is a compile error:
+///
+/// ```compile_fail
+/// use r1cs::{Bn128, EdwardsExpression, JubJub};
+///
+/// // JubJub is defined over the scalar field of BLS12-381, not BN128.
+/// let point: Option<EdwardsExpression<Bn128, JubJub>> = None;
+/// ```
+///
+/// This trait names the host field, so generic code can require a particular one, or derive the
+/// field from the curve rather than taking both as parameters:
+///
+/// ```
+/// use r1cs::{Bls12_381, EmbeddedCurve, JubJub};
+///
+/// fn requires_bls12_381<C: EmbeddedCurve<HostField = Bls12_381>>() {}
+/// requires_bls12_381::<JubJub>();
+/// ```
+///
+/// ```compile_fail
+/// use r1cs::{Bn128, EmbeddedCurve, JubJub};
+///
+/// fn requires_bn128<C: EmbeddedCurve<HostField = Bn128>>() {}
+/// requires_bn128::<JubJub>();
+/// ```
+pub trait EmbeddedCurve {
+    /// The field which this curve's points are defined over, and which gadgets using it must be
+    /// built over.
+    type HostField: Field;
+}
@@ -1,10 +1,28 @@
 use std::str::FromStr;
 
-use crate::{Bls12_381, CyclicGenerator, EdwardsCurve, EdwardsGroup, EdwardsPoint, Element, CyclicSubgroup};
+use num::BigUint;
 
+use crate::{Bls12_381, CyclicGroup, EdwardsCurve, EdwardsExpression, EdwardsGroup, EdwardsPoint,
+            Element, EmbeddedCurve, Expression, GadgetBuilder, Group, HashFunction, HashToCurve,
+            PrimeOrderGroup};
+
+/// The Jubjub curve, a twisted Edwards curve defined over the scalar field of BLS12-381, as used
+/// in Zcash Sapling.
 pub struct JubJub;
 
-pub type JubJubPrimeSubgroup = CyclicSubgroup<Bls12_381, EdwardsGroup<Bls12_381, JubJub>, JubJub>;
+impl JubJub {
+    /// The order of the prime-order subgroup generated by `generator_element`. The full group of
+    /// points has order `8` times this.
+    pub fn subgroup_order() -> BigUint {
+        BigUint::from_str(
+            "6554484396890773809930967563523245729705921265872317281365359162392183254199"
+        ).unwrap()
+    }
+}
+
+impl EmbeddedCurve for JubJub {
+    type HostField = Bls12_381;
+}
 
 impl EdwardsCurve<Bls12_381> for JubJub {
     fn a() -> Element<Bls12_381> {
@@ -18,23 +36,177 @@ impl EdwardsCurve<Bls12_381> for JubJub {
     }
 }
 
-impl CyclicGenerator<Bls12_381, EdwardsGroup<Bls12_381, JubJub>> for JubJub {
+impl CyclicGroup<Bls12_381> for EdwardsGroup<Bls12_381, JubJub> {
+    /// A generator of the prime-order subgroup. This is `8 * (u, 11)`, where `(u, 11)` is the
+    /// generator of the full group used by the Sapling implementation of Jubjub, so multiplying by
+    /// the cofactor of 8 maps it into the prime-order subgroup.
     fn generator_element() -> EdwardsPoint<Bls12_381, JubJub> {
         let x = Element::from_str(
-            "11076627216317271660298050606127911965867021807910416450833192264015104452986"
+            "28336281903124990867587793011069573392383982287722241916350956173377953689573"
         ).unwrap();
         let y = Element::from_str(
-            "44412834903739585386157632289020980010620626017712148233229312325549216099227"
+            "39385640392217313770878525135509063452020585410343666726093009378539878503883"
         ).unwrap();
 
         EdwardsPoint::new(x, y)
     }
 }
 
+impl PrimeOrderGroup<Bls12_381> for EdwardsGroup<Bls12_381, JubJub> {
+    fn generator_order() -> BigUint {
+        JubJub::subgroup_order()
+    }
+}
+
+impl HashToCurve<Bls12_381> for EdwardsGroup<Bls12_381, JubJub> {
+    /// Maps `input` to a point using `try_and_increment`, then clears the cofactor.
+    fn hash_to_curve<HF: HashFunction<Bls12_381>>(
+        builder: &mut GadgetBuilder<Bls12_381>,
+        input: &Expression<Bls12_381>,
+        hash: &HF,
+    ) -> EdwardsExpression<Bls12_381, JubJub> {
+        let point = Self::try_and_increment(builder, input, hash);
+        Self::clear_cofactor(builder, &point)
+    }
+
+    /// Multiplies by the cofactor of 8 using three doublings.
+    fn clear_cofactor(
+        builder: &mut GadgetBuilder<Bls12_381>,
+        expression: &EdwardsExpression<Bls12_381, JubJub>,
+    ) -> EdwardsExpression<Bls12_381, JubJub> {
+        let mut point = expression.clone();
+        for _i in 0..3 {
+            point = Self::double_expression(builder, &point);
+        }
+        point
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::{Bls12_381, CyclicGroup, EdwardsExpression, EdwardsGroup, EdwardsPoint, Element,
+                Expression, Gadget, GadgetBuilder, Group, HashFunction, HashToCurve, JubJub,
+                PointEncoding, Wire, WireValues};
+
+    type JubJubGroup = EdwardsGroup<Bls12_381, JubJub>;
+
+    #[test]
+    fn generator_has_subgroup_order() {
+        let generator = JubJubGroup::generator_element();
+        let order = Element::from(JubJub::subgroup_order());
+        let product = JubJubGroup::mul_scalar_element(&generator, &order);
+        assert_eq!(Element::zero(), product.x);
+        assert_eq!(Element::one(), product.y);
+    }
+
+    #[test]
+    fn generator_is_not_identity() {
+        let generator = JubJubGroup::generator_element();
+        assert_ne!(Element::<Bls12_381>::zero(), generator.x);
+    }
+
     #[test]
-    fn subgroup_check() {
-        //TODO: verify that generator is valid and generates a subgroup of prime order with appropriate cofactor
+    fn hash_to_curve_lands_in_subgroup() {
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let input = builder.wire();
+        let point = JubJubGroup::hash_to_curve(&mut builder, &Expression::from(input), &TestHash);
+        let gadget = builder.build();
+
+        let mut values = values!(input => 42u8.into());
+        assert!(gadget.execute(&mut values));
+        let point = EdwardsPoint::<Bls12_381, JubJub>::new(
+            point.x.evaluate(&values), point.y.evaluate(&values));
+        assert_ne!(Element::zero(), point.x);
+
+        let order = Element::from(JubJub::subgroup_order());
+        let product = JubJubGroup::mul_scalar_element(&point, &order);
+        assert_eq!(Element::zero(), product.x);
+        assert_eq!(Element::one(), product.y);
+    }
+
+    #[test]
+    fn y_sign_choices_after_cofactor_clearing() {
+        // try_and_increment lets the prover choose the sign of y. The two choices differ by
+        // negation and the point (0, -1) of order 2, so clearing the cofactor negates one of them.
+        let point = JubJubGroup::generator_element();
+        let other = EdwardsPoint::<Bls12_381, JubJub>::new(point.x.clone(), -&point.y);
+        let order_2 = EdwardsPoint::<Bls12_381, JubJub>::new(Element::zero(), -Element::one());
+        let sum = JubJubGroup::add_elements(&point, &order_2);
+        assert_eq!((-&point.x, -&point.y), (sum.x, sum.y));
+
+        let cofactor = Element::from(8u8);
+        let cleared = JubJubGroup::mul_scalar_element(&point, &cofactor);
+        let other_cleared = JubJubGroup::mul_scalar_element(&other, &cofactor);
+        assert_eq!(-&cleared.x, other_cleared.x);
+        assert_eq!(cleared.y, other_cleared.y);
+    }
+
+    /// A gadget binding a point, given by two wires, in some encoding.
+    struct Binding {
+        gadget: Gadget<Bls12_381>,
+        point_wires: [Wire; 2],
+        public_wires: Vec<Wire>,
+    }
+
+    impl Binding {
+        fn new(encoding: PointEncoding) -> Self {
+            let mut builder = GadgetBuilder::<Bls12_381>::new();
+            let (x, y) = (builder.wire(), builder.wire());
+            let point = EdwardsExpression::new_unsafe(Expression::from(x), Expression::from(y));
+            let public_wires = JubJubGroup::bind_as_public(&mut builder, &point, encoding);
+            Binding { gadget: builder.build(), point_wires: [x, y], public_wires }
+        }
+
+        /// Whether the gadget is satisfied by the given point and public values.
+        fn accepts(
+            &self, point: &EdwardsPoint<Bls12_381, JubJub>, public_values: &[Element<Bls12_381>],
+        ) -> bool {
+            let mut values = WireValues::new();
+            values.set_each(&self.point_wires, &[point.x.clone(), point.y.clone()]);
+            values.set_each(&self.public_wires, public_values);
+            self.gadget.execute(&mut values)
+        }
+    }
+
+    #[test]
+    fn bind_as_public_coordinates() {
+        let binding = Binding::new(PointEncoding::Coordinates);
+        let point = JubJubGroup::generator_element();
+        let encoded = JubJubGroup::encode_point(&point, PointEncoding::Coordinates);
+        assert_eq!(vec![point.x.clone(), point.y.clone()], encoded);
+        assert!(binding.accepts(&point, &encoded));
+        assert!(!binding.accepts(&point, &[point.y.clone(), point.x.clone()]));
+    }
+
+    #[test]
+    fn bind_as_public_compressed() {
+        let binding = Binding::new(PointEncoding::Compressed);
+        let point = JubJubGroup::generator_element();
+        let negated = EdwardsPoint::<Bls12_381, JubJub>::new(-&point.x, point.y.clone());
+
+        for p in &[point.clone(), negated.clone(), JubJubGroup::identity_element()] {
+            let encoded = JubJubGroup::encode_point(p, PointEncoding::Compressed);
+            assert_eq!(p.y, encoded[0]);
+            assert!(binding.accepts(p, &encoded));
+        }
+
+        // The point and its negation share a y coordinate, so only the sign bit distinguishes
+        // them.
+        let encoded = JubJubGroup::encode_point(&point, PointEncoding::Compressed);
+        let encoded_negated = JubJubGroup::encode_point(&negated, PointEncoding::Compressed);
+        assert_eq!(encoded[0], encoded_negated[0]);
+        assert_ne!(encoded[1], encoded_negated[1]);
+        assert!(!binding.accepts(&point, &encoded_negated));
+        assert!(!binding.accepts(&negated, &encoded));
+    }
+
+    // A dummy hash function which returns a linear combination of its inputs.
+    struct TestHash;
+
+    impl HashFunction<Bls12_381> for TestHash {
+        fn hash(&self, _builder: &mut GadgetBuilder<Bls12_381>, blocks: &[Expression<Bls12_381>])
+                -> Expression<Bls12_381> {
+            &blocks[0] * Element::from(3u8) + &blocks[1] * Element::from(5u8)
+        }
     }
 }
@@ -1,9 +1,11 @@
 pub use edwards::*;
+pub use embedded::*;
 pub use jubjub::*;
 pub use montgomery::*;
 pub use weierstrass::*;
 
 mod edwards;
+mod embedded;
 mod jubjub;
 mod montgomery;
 mod weierstrass;
@@ -13,7 +13,7 @@ pub trait MontgomeryCurve<F: Field> {
 pub struct MontgomeryPoint<F: Field, C: MontgomeryCurve<F>> {
     pub x: Element<F>,
     pub y: Element<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
 }
 
 /// An embedded Montgomery curve point defined over the same base field
@@ -22,5 +22,5 @@ pub struct MontgomeryPoint<F: Field, C: MontgomeryCurve<F>> {
 pub struct MontgomeryExpression<F: Field, C: MontgomeryCurve<F>> {
     pub x: Expression<F>,
     pub y: Expression<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
 }
@@ -1,6 +1,9 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 use std::marker::PhantomData;
 
-use crate::{Element, Expression, Field};
+use crate::{Element, Evaluable, Expression, Field, GadgetBuilder, Group, GroupExpression,
+            WireValues};
 
 /// A short Weierstrass curve.
 pub trait WeierstrassCurve<F: Field> {
@@ -8,12 +11,92 @@ pub trait WeierstrassCurve<F: Field> {
     fn b() -> Element<F>;
 }
 
+/// The group of points on a short Weierstrass curve, in affine coordinates.
+///
+/// Affine coordinates can't represent the point at infinity, so `identity_element` panics, and
+/// generic algorithms which start from the identity, such as `mul_scalar_expression`, can't be
+/// used with this group. Moreover, the chord-and-tangent addition formula is incomplete: it fails
+/// for equal or inverse inputs, so `add_expressions` should only be used on inputs known to be
+/// distinct, and `add_expressions_safe` asserts that they are.
+pub struct WeierstrassGroup<F: Field, C: WeierstrassCurve<F>> {
+    phantom_f: PhantomData<fn() -> F>,
+    phantom_c: PhantomData<fn() -> C>,
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> Group<F> for WeierstrassGroup<F, C> {
+    type GroupElement = WeierstrassPoint<F, C>;
+    type GroupExpression = WeierstrassExpression<F, C>;
+
+    const HAS_COMPLETE_ADDITION: bool = false;
+
+    fn identity_element() -> Self::GroupElement {
+        panic!("The point at infinity has no affine coordinates")
+    }
+
+    /// Adds two points with the chord rule, using 3 constraints. If the points are equal, the
+    /// slope is unconstrained, so a malicious prover can choose any result; see
+    /// `add_expressions_incomplete_asserting_distinct`.
+    fn add_expressions(
+        builder: &mut GadgetBuilder<F>,
+        lhs: &Self::GroupExpression,
+        rhs: &Self::GroupExpression,
+    ) -> Self::GroupExpression {
+        let WeierstrassExpression { x: x1, y: y1, .. } = lhs;
+        let WeierstrassExpression { x: x2, y: y2, .. } = rhs;
+        let lambda = builder.quotient_unsafe(&(y2 - y1), &(x2 - x1));
+        Self::apply_slope(builder, &lambda, lhs, x2)
+    }
+
+    /// Doubles a point with the tangent rule, using 3 constraints. This requires `y != 0`, which
+    /// holds for any point of odd order.
+    fn double_expression(
+        builder: &mut GadgetBuilder<F>,
+        expression: &Self::GroupExpression,
+    ) -> Self::GroupExpression {
+        let WeierstrassExpression { x, y, .. } = expression;
+        let x_squared = builder.product(x, x);
+        let lambda = builder.quotient_unsafe(
+            &(x_squared * Element::from(3u8) + Expression::from(C::a())),
+            &(y * Element::from(2u8)));
+        Self::apply_slope(builder, &lambda, expression, x)
+    }
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> WeierstrassGroup<F, C> {
+    /// Given the slope `lambda` of the line through `p = (x1, y1)` and a second point with x
+    /// coordinate `x2`, returns the sum `(lambda^2 - x1 - x2, lambda (x1 - x3) - y1)`.
+    fn apply_slope(
+        builder: &mut GadgetBuilder<F>,
+        lambda: &Expression<F>,
+        p: &WeierstrassExpression<F, C>,
+        x2: &Expression<F>,
+    ) -> WeierstrassExpression<F, C> {
+        let x3 = builder.product(lambda, lambda) - &p.x - x2;
+        let y3 = builder.product(lambda, &(&p.x - &x3)) - &p.y;
+        WeierstrassExpression::new_unsafe(x3, y3)
+    }
+}
+
 /// An embedded Weierstrass curve point defined over the same base field as
 /// the constraint system, with affine coordinates as elements.
 pub struct WeierstrassPoint<F: Field, C: WeierstrassCurve<F>> {
     pub x: Element<F>,
     pub y: Element<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> WeierstrassPoint<F, C> {
+    pub fn new(x: Element<F>, y: Element<F>) -> WeierstrassPoint<F, C> {
+        assert!(&y * &y == &x * &x * &x + C::a() * &x + C::b(),
+                "Point must be contained on the curve.");
+        WeierstrassPoint { x, y, phantom: PhantomData }
+    }
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> Clone for WeierstrassPoint<F, C> {
+    fn clone(&self) -> Self {
+        WeierstrassPoint { x: self.x.clone(), y: self.y.clone(), phantom: PhantomData }
+    }
 }
 
 /// An embedded Weierstrass curve point defined over the same base field
@@ -22,7 +105,51 @@ pub struct WeierstrassPoint<F: Field, C: WeierstrassCurve<F>> {
 pub struct WeierstrassExpression<F: Field, C: WeierstrassCurve<F>> {
     pub x: Expression<F>,
     pub y: Expression<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> WeierstrassExpression<F, C> {
+    /// Creates a `WeierstrassExpression` from two arbitrary coordinates. This method is unsafe and
+    /// should only be used when the coordinates are proven to be on the curve.
+    pub fn new_unsafe(x: Expression<F>, y: Expression<F>) -> WeierstrassExpression<F, C> {
+        WeierstrassExpression { x, y, phantom: PhantomData }
+    }
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> Clone for WeierstrassExpression<F, C> {
+    fn clone(&self) -> Self {
+        WeierstrassExpression::new_unsafe(self.x.clone(), self.y.clone())
+    }
+}
+
+impl<F: Field, C: WeierstrassCurve<F>> GroupExpression<F> for WeierstrassExpression<F, C> {
+    const COMPONENTS: usize = 2;
+
+    fn compressed(&self) -> &Expression<F> { &self.x }
+    fn to_components(&self) -> Vec<Expression<F>> { vec![self.x.clone(), self.y.clone()] }
+
+    fn from_components_unsafe(components: Vec<Expression<F>>) -> Self {
+        assert_eq!(components.len(), Self::COMPONENTS, "Wrong number of group element components");
+        WeierstrassExpression::new_unsafe(components[0].clone(), components[1].clone())
+    }
+}
+
+impl<F, C> From<&WeierstrassPoint<F, C>> for WeierstrassExpression<F, C>
+    where F: Field, C: WeierstrassCurve<F> {
+    fn from(point: &WeierstrassPoint<F, C>) -> Self {
+        WeierstrassExpression::new_unsafe(Expression::from(&point.x), Expression::from(&point.y))
+    }
+}
+
+impl<F, C> Evaluable<F, WeierstrassPoint<F, C>> for WeierstrassExpression<F, C>
+    where F: Field, C: WeierstrassCurve<F> {
+    fn evaluate(&self, wire_values: &WireValues<F>) -> WeierstrassPoint<F, C> {
+        WeierstrassPoint {
+            x: self.x.evaluate(wire_values),
+            y: self.y.evaluate(wire_values),
+            phantom: PhantomData,
+        }
+    }
 }
 
 /// An embedded Weierstrass curve point defined over the same base field
@@ -32,5 +159,143 @@ pub struct ProjWeierstrassExpression<F: Field, C: WeierstrassCurve<F>> {
     pub x: Expression<F>,
     pub y: Expression<F>,
     pub z: Expression<F>,
-    phantom: PhantomData<*const C>,
+    phantom: PhantomData<fn() -> C>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Element, Evaluable, Expression, GadgetBuilder, Gadget, Group, WeierstrassCurve,
+                WeierstrassExpression, WeierstrassGroup, WeierstrassPoint, Wire, WireValues};
+    use crate::fields::small::F257;
+
+    /// The curve `y^2 = x^3 + 7` over F_257, which has 258 points.
+    struct ToyCurve;
+
+    impl WeierstrassCurve<F257> for ToyCurve {
+        fn a() -> Element<F257> {
+            Element::zero()
+        }
+
+        fn b() -> Element<F257> {
+            Element::from(7u8)
+        }
+    }
+
+    type ToyGroup = WeierstrassGroup<F257, ToyCurve>;
+    type ToyExpression = WeierstrassExpression<F257, ToyCurve>;
+
+    fn point(x: u8, y: u8) -> WeierstrassPoint<F257, ToyCurve> {
+        WeierstrassPoint::new(Element::from(x), Element::from(y))
+    }
+
+    /// The sum of two points with the given slope, computed outside of any gadget.
+    fn apply_slope(
+        lambda: &Element<F257>, p: &WeierstrassPoint<F257, ToyCurve>, x2: &Element<F257>,
+    ) -> WeierstrassPoint<F257, ToyCurve> {
+        let x3 = lambda * lambda - &p.x - x2;
+        let y3 = lambda * (&p.x - &x3) - &p.y;
+        WeierstrassPoint::new(x3, y3)
+    }
+
+    /// Allocates wires for two points, returning them as expressions along with their wires.
+    fn point_wires(builder: &mut GadgetBuilder<F257>) -> ([Wire; 4], ToyExpression, ToyExpression) {
+        let (x1, y1, x2, y2) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        let p = ToyExpression::new_unsafe(Expression::from(x1), Expression::from(y1));
+        let q = ToyExpression::new_unsafe(Expression::from(x2), Expression::from(y2));
+        ([x1, y1, x2, y2], p, q)
+    }
+
+    fn point_values(
+        wires: &[Wire; 4], p: &WeierstrassPoint<F257, ToyCurve>,
+        q: &WeierstrassPoint<F257, ToyCurve>,
+    ) -> WireValues<F257> {
+        values!(wires[0] => p.x.clone(), wires[1] => p.y.clone(),
+                wires[2] => q.x.clone(), wires[3] => q.y.clone())
+    }
+
+    #[test]
+    fn add_and_double() {
+        let (p, q) = (point(1, 120), point(2, 23));
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (wires, p_exp, q_exp) = point_wires(&mut builder);
+        let sum = ToyGroup::add_expressions_safe(&mut builder, &p_exp, &q_exp);
+        let double = ToyGroup::double_expression(&mut builder, &p_exp);
+        let gadget = builder.build();
+
+        let mut values = point_values(&wires, &p, &q);
+        assert!(gadget.execute(&mut values));
+        let lambda = (&q.y - &p.y) / (&q.x - &p.x);
+        let expected_sum = apply_slope(&lambda, &p, &q.x);
+        let tangent = (&p.x * &p.x * Element::from(3u8)) / (&p.y * Element::from(2u8));
+        let expected_double = apply_slope(&tangent, &p, &p.x);
+        let (sum, double) = (sum.evaluate(&values), double.evaluate(&values));
+        assert_eq!((expected_sum.x, expected_sum.y), (sum.x, sum.y));
+        assert_eq!((expected_double.x, expected_double.y), (double.x, double.y));
+    }
+
+    /// Fills in the slope and products of the chord rule for `p + q`, using the given slope rather
+    /// than a computed one, and checks the resulting witness against the gadget's constraints.
+    /// Any other wires, such as an inverse proving `x1 != x2`, are assigned `extra`.
+    fn forged_sum_satisfies(
+        gadget: &Gadget<F257>, wires: &[Wire; 4], p: &WeierstrassPoint<F257, ToyCurve>,
+        q: &WeierstrassPoint<F257, ToyCurve>, lambda: &Element<F257>, extra: &Element<F257>,
+    ) -> bool {
+        let mut values = point_values(wires, p, q);
+        let x3 = lambda * lambda - &p.x - &q.x;
+        let generated: Vec<Wire> = gadget.wires().into_iter()
+            .filter(|wire| !wires.contains(wire))
+            .collect();
+        // The last three wires are the slope, its square and the product giving y3.
+        let n = generated.len();
+        for &wire in &generated[..n - 3] {
+            values.set(wire, extra.clone());
+        }
+        values.set(generated[n - 3], lambda.clone());
+        values.set(generated[n - 2], lambda * lambda);
+        values.set(generated[n - 1], lambda * (&p.x - &x3));
+        gadget.constraints.iter().all(|c| c.evaluate(&values))
+    }
+
+    #[test]
+    fn incomplete_addition_misuse_is_caught() {
+        let p = point(1, 120);
+        let neg_p = point(1, 137);
+        let lambda = Element::from(5u8);
+
+        // Without the assertion, a prover can add p to itself with any slope, e.g. 5, yielding a
+        // wrong result.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (wires, p_exp, q_exp) = point_wires(&mut builder);
+        ToyGroup::add_expressions(&mut builder, &p_exp, &q_exp);
+        let gadget = builder.build();
+        assert!(forged_sum_satisfies(&gadget, &wires, &p, &p, &lambda, &Element::zero()));
+
+        // With it, adding equal or inverse points is unsatisfiable, whatever the witness.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (wires, p_exp, q_exp) = point_wires(&mut builder);
+        ToyGroup::add_expressions_safe(&mut builder, &p_exp, &q_exp);
+        let gadget = builder.build();
+        for q in &[&p, &neg_p] {
+            for extra in 0..257u32 {
+                assert!(!forged_sum_satisfies(
+                    &gadget, &wires, &p, q, &lambda, &Element::from(extra)));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "This group has no complete addition formula")]
+    fn complete_addition_unavailable() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (_wires, p_exp, q_exp) = point_wires(&mut builder);
+        ToyGroup::add_expressions_complete(&mut builder, &p_exp, &q_exp);
+    }
+
+    #[test]
+    #[should_panic(expected = "The point at infinity has no affine coordinates")]
+    fn generic_scalar_multiplication_unavailable() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (_wires, p_exp, _q_exp) = point_wires(&mut builder);
+        ToyGroup::mul_scalar_expression(&mut builder, &p_exp, &Expression::from(3u8));
+    }
 }
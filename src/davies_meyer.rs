@@ -11,7 +11,7 @@ use crate::gadget_traits::{BlockCipher, CompressionFunction};
 /// cipher.
 pub struct DaviesMeyer<F: Field, BC: BlockCipher<F>> {
     cipher: BC,
-    phantom: PhantomData<*const F>,
+    phantom: PhantomData<fn() -> F>,
 }
 
 impl<F: Field, BC: BlockCipher<F>> DaviesMeyer<F, BC> {
@@ -35,7 +35,7 @@ mod tests {
     use crate::field::{Element, Field};
     use crate::gadget_builder::GadgetBuilder;
     use crate::gadget_traits::{BlockCipher, CompressionFunction};
-    use crate::test_util::F7;
+    use crate::fields::small::F7;
 
     #[test]
     fn davies_meyer() {
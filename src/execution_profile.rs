@@ -0,0 +1,129 @@
+//! This module adds a profiling mode to gadget execution, which records how long each witness
+//! generator takes. It is only available with the `std` feature, since it relies on `std::time`.
+
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use crate::field::Field;
+use crate::gadget::Gadget;
+use crate::wire_values::WireValues;
+
+/// Statistics about a single run of a witness generator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GeneratorProfile {
+    /// The index of the generator in `Gadget::witness_generators`.
+    pub index: usize,
+    /// The time spent running the generator.
+    pub elapsed: Duration,
+    /// The number of wires which the generator assigned values to.
+    pub wires_set: usize,
+}
+
+/// Statistics about witness generation, as recorded by `Gadget::execute_profiled`.
+#[derive(Clone, Debug)]
+pub struct ExecutionProfile {
+    /// One entry per generator, ordered by generator index.
+    pub generators: Vec<GeneratorProfile>,
+    /// The total time spent on witness generation, including the overhead of scheduling
+    /// generators. Constraint evaluation is not included.
+    pub elapsed: Duration,
+}
+
+impl ExecutionProfile {
+    /// The total time spent inside generators.
+    pub fn generator_elapsed(&self) -> Duration {
+        self.generators.iter().map(|g| g.elapsed).sum()
+    }
+
+    /// The total number of wires assigned by generators.
+    pub fn wires_set(&self) -> usize {
+        self.generators.iter().map(|g| g.wires_set).sum()
+    }
+
+    /// The `k` generators which took the longest, slowest first.
+    pub fn top_n(&self, k: usize) -> Vec<&GeneratorProfile> {
+        let mut sorted: Vec<&GeneratorProfile> = self.generators.iter().collect();
+        sorted.sort_by(|a, b| b.elapsed.cmp(&a.elapsed).then(a.index.cmp(&b.index)));
+        sorted.truncate(k);
+        sorted
+    }
+}
+
+impl<F: Field> Gadget<F> {
+    /// Like `execute`, but also returns a profile of witness generation. `execute` itself does no
+    /// profiling, so it is unaffected by this.
+    pub fn execute_profiled(&self, wire_values: &mut WireValues<F>) -> (bool, ExecutionProfile) {
+        let mut generators: Vec<Option<GeneratorProfile>> =
+            vec![None; self.witness_generators.len()];
+        let start = Instant::now();
+        let mut generation_end = start;
+        let result = self.execute_with(wire_values, |index, generator, values| {
+            let wires_before = values.as_map().len();
+            let generator_start = Instant::now();
+            generator.generate(values);
+            generation_end = Instant::now();
+            generators[index] = Some(GeneratorProfile {
+                index,
+                elapsed: generation_end - generator_start,
+                wires_set: values.as_map().len() - wires_before,
+            });
+            Ok::<(), Infallible>(())
+        });
+
+        // execute_with panics if any generator doesn't run, so every entry is populated.
+        let generators = generators.into_iter().map(Option::unwrap).collect();
+        let satisfied = match result {
+            Ok(satisfied) => satisfied,
+            Err(never) => match never {},
+        };
+        let profile = ExecutionProfile { generators, elapsed: generation_end - start };
+        (satisfied, profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::Expression;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::wire_values::WireValues;
+
+    #[test]
+    fn profile_sorting() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires = builder.wires(16);
+        let inputs: Vec<Expression<F257>> = wires.iter().map(Expression::from).collect();
+        let outputs = builder.sort_ascending(&inputs);
+        let gadget = builder.build();
+
+        let mut values = WireValues::new();
+        for (i, &wire) in wires.iter().enumerate() {
+            values.set(wire, ((i * 7) % 16).into());
+        }
+        let mut unprofiled_values = values.clone();
+        let inputs_set = values.as_map().len();
+
+        let (satisfied, profile) = gadget.execute_profiled(&mut values);
+        assert!(satisfied);
+        assert_eq!(gadget.witness_generators.len(), profile.generators.len());
+        for (i, generator) in profile.generators.iter().enumerate() {
+            assert_eq!(i, generator.index);
+        }
+        assert_eq!(values.as_map().len() - inputs_set, profile.wires_set());
+        assert!(profile.generator_elapsed() <= profile.elapsed);
+
+        let top = profile.top_n(3);
+        assert_eq!(3, top.len());
+        assert!(top[0].elapsed >= top[1].elapsed && top[1].elapsed >= top[2].elapsed);
+        assert!(top.iter().all(|g| g.elapsed >= profile.generators.iter()
+            .filter(|other| top.iter().all(|t| t.index != other.index))
+            .map(|other| other.elapsed).max().unwrap()));
+
+        // The unprofiled path should produce the same witness.
+        assert!(gadget.execute(&mut unprofiled_values));
+        assert_eq!(values.as_map(), unprofiled_values.as_map());
+        for output in outputs {
+            assert_eq!(output.evaluate(&values), output.evaluate(&unprofiled_values));
+        }
+    }
+}
@@ -11,8 +11,10 @@ use alloc::collections::btree_map::BTreeMap;
 #[cfg(not(feature = "std"))]
 use alloc::collections::btree_set::BTreeSet;
 
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::{Formatter, Debug};
+use std::hash::{Hash, Hasher};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 use itertools::Itertools;
@@ -26,7 +28,19 @@ use crate::wire_values::WireValues;
 use crate::util::join;
 
 /// A linear combination of wires.
-#[derive(Debug, Eq, PartialEq)]
+///
+/// Expressions can be divided by constants, but not by other expressions, since that requires
+/// constraints, along with a choice of what happens when the divisor is zero. Use one of
+/// `GadgetBuilder::quotient`, which requires a non-zero divisor, `quotient_or_zero` or
+/// `checked_quotient` instead:
+///
+/// ```compile_fail
+/// use r1cs::{Bn128, Expression};
+///
+/// let x = Expression::<Bn128>::one();
+/// let _quotient = &x / &x;
+/// ```
+#[derive(Debug)]
 pub struct Expression<F: Field> {
     /// The coefficient of each wire. Wires with a coefficient of zero are omitted.
     coefficients: BTreeMap<Wire, Element<F>>,
@@ -41,6 +55,13 @@ impl<F: Field> Expression<F> {
         Expression { coefficients: nonzero_coefficients }
     }
 
+    /// Creates an expression without filtering out zero coefficients, which is otherwise an
+    /// invariant. This is only for testing code which checks the invariant.
+    #[cfg(test)]
+    pub(crate) fn new_unfiltered(coefficients: BTreeMap<Wire, Element<F>>) -> Self {
+        Expression { coefficients }
+    }
+
     pub fn coefficients(&self) -> &BTreeMap<Wire, Element<F>> {
         &self.coefficients
     }
@@ -66,6 +87,44 @@ impl<F: Field> Expression<F> {
         Expression::new(merged_coefficients)
     }
 
+    /// The linear combination `sum(c_i * e_i) + constant` of the given `(c_i, e_i)` terms. This
+    /// merges all terms in a single pass, which is cheaper than building the same expression with
+    /// operators, since each operator call copies its operands.
+    pub fn linear_combination(
+        terms: &[(Element<F>, &Expression<F>)], constant: Element<F>,
+    ) -> Self {
+        let mut result = Expression::from(constant);
+        for (coefficient, expression) in terms {
+            result.scaled_add(coefficient, expression);
+        }
+        result
+    }
+
+    /// Add `coefficient * other` to this expression in place.
+    pub fn scaled_add(&mut self, coefficient: &Element<F>, other: &Expression<F>) {
+        if coefficient.is_zero() {
+            return;
+        }
+        for (&wire, other_coefficient) in &other.coefficients {
+            let entry = self.coefficients.entry(wire).or_insert_with(Element::zero);
+            if coefficient.is_one() {
+                *entry += other_coefficient;
+            } else {
+                *entry += coefficient * other_coefficient;
+            }
+            if entry.is_zero() {
+                self.coefficients.remove(&wire);
+            }
+        }
+    }
+
+    /// Negate this expression in place.
+    pub fn negate_in_place(&mut self) {
+        for coefficient in self.coefficients.values_mut() {
+            *coefficient = -&*coefficient;
+        }
+    }
+
     pub fn zero() -> Self {
         Expression { coefficients: BTreeMap::new() }
     }
@@ -83,10 +142,41 @@ impl<F: Field> Expression<F> {
         self.coefficients.len()
     }
 
-    /// Return Some(c) if this is a constant c, otherwise None.
+    /// An iterator over each wire and its (nonzero) coefficient, in wire order.
+    pub fn iter(&self) -> impl Iterator<Item=(&Wire, &Element<F>)> {
+        self.coefficients.iter()
+    }
+
+    /// The largest coefficient, comparing coefficients by their canonical representatives in
+    /// `[0, |F|)`. Note that small negative coefficients such as `-1` are thus considered large.
+    /// Returns zero if this expression is zero.
+    pub fn max_coefficient(&self) -> Element<F> {
+        self.coefficients.values().max().cloned().unwrap_or_else(Element::zero)
+    }
+
+    /// The constant term of this expression, i.e. the coefficient of `Wire::ONE`.
+    pub fn constant_term(&self) -> Element<F> {
+        self.coefficients.get(&Wire::ONE).cloned().unwrap_or_else(Element::zero)
+    }
+
+    /// This expression with its constant term removed.
+    pub fn without_constant(&self) -> Self {
+        let mut coefficients = self.coefficients.clone();
+        coefficients.remove(&Wire::ONE);
+        Expression { coefficients }
+    }
+
+    /// The number of non-constant terms, i.e. the number of wires other than `Wire::ONE` with a
+    /// nonzero coefficient.
+    pub fn l0_norm(&self) -> usize {
+        self.num_terms() - usize::from(self.coefficients.contains_key(&Wire::ONE))
+    }
+
+    /// Return Some(c) if this is a constant c, otherwise None. Note that this includes the zero
+    /// expression, which has no terms.
     pub fn as_constant(&self) -> Option<Element<F>> {
-        if self.num_terms() == 1 {
-            self.coefficients.get(&Wire::ONE).cloned()
+        if self.coefficients.keys().all(|&wire| wire == Wire::ONE) {
+            Some(self.constant_term())
         } else {
             None
         }
@@ -97,9 +187,59 @@ impl<F: Field> Expression<F> {
         self.coefficients.keys().copied().collect()
     }
 
+    /// This expression with each wire in `assignments` replaced by its assigned constant value.
+    pub fn substitute(&self, assignments: &BTreeMap<Wire, Element<F>>) -> Self {
+        let mut coefficients = BTreeMap::new();
+        let mut constant = self.constant_term();
+        for (&wire, coefficient) in &self.coefficients {
+            match assignments.get(&wire) {
+                _ if wire == Wire::ONE => (),
+                Some(value) => constant += coefficient * value,
+                None => { coefficients.insert(wire, coefficient.clone()); }
+            }
+        }
+        coefficients.insert(Wire::ONE, constant);
+        Expression::new(coefficients)
+    }
+
+    /// This expression with each wire in `replacements` replaced by the given expression.
+    pub fn substitute_expressions(&self, replacements: &BTreeMap<Wire, Expression<F>>) -> Self {
+        let mut result = Expression::zero();
+        for (&wire, coefficient) in &self.coefficients {
+            match replacements.get(&wire) {
+                Some(replacement) => result.scaled_add(coefficient, replacement),
+                None => result.scaled_add(coefficient, &Expression::from(wire)),
+            }
+        }
+        result
+    }
+
     pub fn evaluate(&self, wire_values: &WireValues<F>) -> Element<F> {
-        self.coefficients.iter().fold(Element::zero(),
-            |sum, (wire, coefficient)| sum + (wire_values.get(*wire) * coefficient))
+        let mut acc = BigUint::zero();
+        self.evaluate_into(wire_values, &mut acc);
+        Element::from(acc)
+    }
+
+    /// Add the value of this expression to `acc`, leaving `acc` reduced modulo `|F|`.
+    ///
+    /// Rather than reducing after every term, this accumulates unreduced products, and reduces
+    /// only once every `max_bits` terms and at the end. Between reductions the accumulator stays
+    /// below `(max_bits + 1) * |F|^2`, so it is only a few bits longer than a single product.
+    pub fn evaluate_into(&self, wire_values: &WireValues<F>, acc: &mut BigUint) {
+        let order = F::order();
+        let reduction_interval = order.bits() as usize;
+        for (i, (wire, coefficient)) in self.coefficients.iter().enumerate() {
+            let value = wire_values.get(*wire).to_biguint();
+            if coefficient.is_one() {
+                *acc += value;
+            } else {
+                *acc += value * coefficient.to_biguint();
+            }
+            if (i + 1) % reduction_interval == 0 {
+                *acc %= &order;
+            }
+        }
+        *acc %= &order;
     }
 }
 
@@ -109,6 +249,32 @@ impl<F: Field> Clone for Expression<F> {
     }
 }
 
+impl<F: Field> PartialEq for Expression<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.coefficients == other.coefficients
+    }
+}
+
+impl<F: Field> Eq for Expression<F> {}
+
+impl<F: Field> Hash for Expression<F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.coefficients.hash(state)
+    }
+}
+
+impl<F: Field> Ord for Expression<F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.coefficients.cmp(&other.coefficients)
+    }
+}
+
+impl<F: Field> PartialOrd for Expression<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl<F: Field> From<Wire> for Expression<F> {
     fn from(wire: Wire) -> Self {
         Expression::new(
@@ -181,15 +347,16 @@ impl<F: Field> Neg for &Expression<F> {
     type Output = Expression<F>;
 
     fn neg(self) -> Expression<F> {
-        self * -Element::one()
+        -self.clone()
     }
 }
 
 impl<F: Field> Neg for Expression<F> {
     type Output = Expression<F>;
 
-    fn neg(self) -> Expression<F> {
-        -&self
+    fn neg(mut self) -> Expression<F> {
+        self.negate_in_place();
+        self
     }
 }
 
@@ -197,15 +364,16 @@ impl<F: Field> Add<Expression<F>> for Expression<F> {
     type Output = Expression<F>;
 
     fn add(self, rhs: Expression<F>) -> Expression<F> {
-        &self + &rhs
+        self + &rhs
     }
 }
 
 impl<F: Field> Add<&Expression<F>> for Expression<F> {
     type Output = Expression<F>;
 
-    fn add(self, rhs: &Expression<F>) -> Expression<F> {
-        &self + rhs
+    fn add(mut self, rhs: &Expression<F>) -> Expression<F> {
+        self += rhs;
+        self
     }
 }
 
@@ -213,7 +381,7 @@ impl<F: Field> Add<Expression<F>> for &Expression<F> {
     type Output = Expression<F>;
 
     fn add(self, rhs: Expression<F>) -> Expression<F> {
-        self + &rhs
+        rhs + self
     }
 }
 
@@ -221,12 +389,7 @@ impl<F: Field> Add<&Expression<F>> for &Expression<F> {
     type Output = Expression<F>;
 
     fn add(self, rhs: &Expression<F>) -> Expression<F> {
-        // TODO: Use Expression::sum_of_expressions
-        let mut merged_coefficients = self.coefficients.clone();
-        for (wire, coefficient) in rhs.coefficients.clone() {
-            *merged_coefficients.entry(wire).or_insert_with(Element::zero) += coefficient
-        }
-        Expression::new(merged_coefficients)
+        self.clone() + rhs
     }
 }
 
@@ -238,8 +401,7 @@ impl<F: Field> AddAssign for Expression<F> {
 
 impl<F: Field> AddAssign<&Expression<F>> for Expression<F> {
     fn add_assign(&mut self, rhs: &Expression<F>) {
-        // TODO: Merge coefficients instead.
-        *self = self.clone() + rhs;
+        self.scaled_add(&Element::one(), rhs);
     }
 }
 
@@ -247,15 +409,16 @@ impl<F: Field> Sub<Expression<F>> for Expression<F> {
     type Output = Expression<F>;
 
     fn sub(self, rhs: Expression<F>) -> Self::Output {
-        &self - &rhs
+        self - &rhs
     }
 }
 
 impl<F: Field> Sub<&Expression<F>> for Expression<F> {
     type Output = Expression<F>;
 
-    fn sub(self, rhs: &Expression<F>) -> Self::Output {
-        &self - rhs
+    fn sub(mut self, rhs: &Expression<F>) -> Self::Output {
+        self -= rhs;
+        self
     }
 }
 
@@ -263,7 +426,7 @@ impl<F: Field> Sub<Expression<F>> for &Expression<F> {
     type Output = Expression<F>;
 
     fn sub(self, rhs: Expression<F>) -> Self::Output {
-        self - &rhs
+        -rhs + self
     }
 }
 
@@ -271,7 +434,7 @@ impl<F: Field> Sub<&Expression<F>> for &Expression<F> {
     type Output = Expression<F>;
 
     fn sub(self, rhs: &Expression<F>) -> Self::Output {
-        self + -rhs
+        self.clone() - rhs
     }
 }
 
@@ -283,7 +446,7 @@ impl<F: Field> SubAssign for Expression<F> {
 
 impl<F: Field> SubAssign<&Expression<F>> for Expression<F> {
     fn sub_assign(&mut self, rhs: &Expression<F>) {
-        *self = &*self - rhs;
+        self.scaled_add(&-Element::one(), rhs);
     }
 }
 
@@ -482,6 +645,12 @@ impl<F: Field> BooleanExpression<F> {
         &self.expression
     }
 
+    /// The negation of this boolean value, i.e. `1 - self`. No constraints are needed for this, so
+    /// unlike most boolean operations, it does not require a `GadgetBuilder`.
+    pub fn negated(&self) -> Self {
+        BooleanExpression::new_unsafe(Expression::one() - &self.expression)
+    }
+
     pub fn dependencies(&self) -> Vec<Wire> {
         self.expression.dependencies()
     }
@@ -530,6 +699,11 @@ impl<F: Field> Debug for BooleanExpression<F> {
 /// The sequence of bits is often interpreted as an unsigned integer for the purpose of binary
 /// arithmetic. When doing so, our convention is to treat the left-most bit as the least
 /// significant, and the right-most bit as the most significant.
+///
+/// Many external formats, such as SHA inputs, are big-endian byte strings instead. When converting
+/// from such formats, use `from_be_bytes` or `from_le_bytes` rather than reordering bits by hand.
+/// For constant gadget inputs, prefer `from_biguint_padded` over `From<BigUint>`, since the latter
+/// produces only as many bits as the value needs.
 #[derive(Debug)]
 pub struct BinaryExpression<F: Field> {
     /// The sequence of bits, ordered from least significant to most significant.
@@ -547,6 +721,29 @@ impl<F: Field> BinaryExpression<F> {
         BinaryExpression { bits: Vec::new() }
     }
 
+    /// A constant expression with exactly `bits` bits, encoding `value` least significant bit
+    /// first. Panics if `value` doesn't fit in `bits` bits.
+    pub fn from_biguint_padded(value: &BigUint, bits: usize) -> Self {
+        assert!(value.bits() <= bits as u64, "Value does not fit in {} bits", bits);
+        let bits = (0..bits)
+            .map(|i| BooleanExpression::from(value.bit(i as u64)))
+            .collect();
+        BinaryExpression { bits }
+    }
+
+    /// A constant expression encoding the given big-endian bytes, i.e. with the first byte being
+    /// the most significant. The result has exactly `8 * bytes.len()` bits, in our usual least
+    /// significant first order, so the most significant bit of the first byte comes last.
+    pub fn from_be_bytes(bytes: &[u8]) -> Self {
+        Self::from_biguint_padded(&BigUint::from_bytes_be(bytes), bytes.len() * 8)
+    }
+
+    /// A constant expression encoding the given little-endian bytes, i.e. with the first byte being
+    /// the least significant. The result has exactly `8 * bytes.len()` bits.
+    pub fn from_le_bytes(bytes: &[u8]) -> Self {
+        Self::from_biguint_padded(&BigUint::from_bytes_le(bytes), bytes.len() * 8)
+    }
+
     /// Truncate the bits in this expression, discarding the more significant bits while keeping the
     /// less significant bits.
     pub fn truncate(&mut self, l: usize) {
@@ -587,6 +784,13 @@ impl<F: Field> BinaryExpression<F> {
         self.bits.push(bit);
     }
 
+    /// The bitwise negation of this expression, a.k.a. `~self`. Like `BooleanExpression::negated`,
+    /// this requires no constraints.
+    pub fn not(&self) -> Self {
+        let bits = self.bits.iter().map(BooleanExpression::negated).collect();
+        BinaryExpression { bits }
+    }
+
     /// Join these bits into the field element they encode. This method requires that
     /// `2^self.len() < |F|`, otherwise the result might not fit in a single field element.
     pub fn join(&self) -> Expression<F> {
@@ -615,6 +819,23 @@ impl<F: Field> BinaryExpression<F> {
             |sum, (i, bit)| if bit.evaluate(values) { sum + (BigUint::one() << i) } else { sum } )
     }
 
+    /// Evaluate this expression as big-endian bytes, with the most significant byte first. The
+    /// result has exactly `ceil(self.len() / 8)` bytes, including any leading zeros.
+    pub fn to_be_bytes_value(&self, values: &WireValues<F>) -> Vec<u8> {
+        let mut bytes = self.to_le_bytes_value(values);
+        bytes.reverse();
+        bytes
+    }
+
+    /// Evaluate this expression as little-endian bytes, with the least significant byte first. The
+    /// result has exactly `ceil(self.len() / 8)` bytes, including any trailing zeros.
+    pub fn to_le_bytes_value(&self, values: &WireValues<F>) -> Vec<u8> {
+        let mut bytes = self.evaluate(values).to_bytes_le();
+        // BigUint encodes zero as a single zero byte, so we may need to truncate as well as pad.
+        bytes.resize(self.len().div_ceil(8), 0);
+        bytes
+    }
+
     pub fn concat(expressions: &[BinaryExpression<F>]) -> Self {
         let bits = expressions.iter().map(|exp| exp.bits.clone()).concat();
         BinaryExpression { bits }
@@ -643,6 +864,8 @@ impl<F: Field> From<BinaryWire> for BinaryExpression<F> {
     }
 }
 
+/// Converts a constant to a `BinaryExpression` with as many bits as the value needs, so zero
+/// becomes an empty expression. Use `BinaryExpression::from_biguint_padded` for a fixed width.
 impl<F: Field> From<BigUint> for BinaryExpression<F> {
     fn from(value: BigUint) -> Self {
         let n = value.bits();
@@ -692,8 +915,171 @@ impl<F: Field> From<u8> for BinaryExpression<F> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{BinaryExpression, GadgetBuilder};
-    use crate::test_util::F257;
+    use num::BigUint;
+
+    use crate::{BinaryExpression, BooleanExpression, Element, Expression, GadgetBuilder, Wire,
+                WireValues};
+    use crate::field::{Bn128, Field};
+    use crate::lcg::LCG;
+    use crate::fields::small::F257;
+
+    /// The original implementation of `Expression::evaluate`, which reduces after every term.
+    fn evaluate_naive<F: Field>(exp: &Expression<F>, values: &WireValues<F>) -> Element<F> {
+        exp.iter().fold(Element::zero(),
+            |sum, (wire, coefficient)| sum + (values.get(*wire) * coefficient))
+    }
+
+    /// A random expression with `n` terms, along with random values for its wires. Some
+    /// coefficients are one, and some values are the largest element, to exercise edge cases.
+    fn random_expression<F: Field>(lcg: &mut LCG, n: u32) -> (Expression<F>, WireValues<F>) {
+        let mut exp = Expression::zero();
+        let mut values = WireValues::new();
+        for index in 1..=n {
+            let wire = Wire { index };
+            let coefficient = match lcg.next_u32() % 4 {
+                0 => Element::one(),
+                1 => Element::largest_element(),
+                _ => lcg.next_element(),
+            };
+            let value = match lcg.next_u32() % 4 {
+                0 => Element::largest_element(),
+                _ => lcg.next_element(),
+            };
+            exp += Expression::from(wire) * coefficient;
+            values.set(wire, value);
+        }
+        (exp + Expression::from(lcg.next_element()), values)
+    }
+
+    #[test]
+    fn evaluate_matches_naive() {
+        let mut lcg = LCG::new();
+        for &n in &[0, 1, 2, 8, 9, 10, 100, 300] {
+            let (exp, values) = random_expression::<F257>(&mut lcg, n);
+            assert_eq!(evaluate_naive(&exp, &values), exp.evaluate(&values));
+            let (exp, values) = random_expression::<Bn128>(&mut lcg, n);
+            assert_eq!(evaluate_naive(&exp, &values), exp.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn evaluate_into_accumulates() {
+        let mut lcg = LCG::new();
+        let (exp, values) = random_expression::<F257>(&mut lcg, 20);
+        let mut acc = BigUint::from(1000u32);
+        exp.evaluate_into(&values, &mut acc);
+        let expected = evaluate_naive(&exp, &values) + Element::from(1000u32 % 257);
+        assert_eq!(expected.to_biguint(), &acc);
+    }
+
+    /// Checks that `evaluate` is at least twice as fast as the original implementation. It is
+    /// about three times as fast in release builds. Run with
+    /// `cargo test --release -- --ignored evaluate_speedup`.
+    #[test]
+    #[ignore]
+    fn evaluate_speedup() {
+        use std::time::Instant;
+
+        const ITERATIONS: u32 = 100;
+        let (exp, values) = random_expression::<Bn128>(&mut LCG::new(), 1000);
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            evaluate_naive(&exp, &values);
+        }
+        let naive = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            exp.evaluate(&values);
+        }
+        let optimized = start.elapsed();
+
+        assert!(optimized * 2 < naive, "naive {:?}, optimized {:?}", naive, optimized);
+    }
+
+    #[test]
+    fn coefficient_accessors() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let exp = Expression::from(x) * Element::from(5u8)
+            + Expression::from(y) * Element::from(3u8)
+            + Expression::from(10u8);
+
+        let terms: Vec<(Wire, Element<F257>)> =
+            exp.iter().map(|(&wire, coefficient)| (wire, coefficient.clone())).collect();
+        assert_eq!(vec![(x, Element::from(5u8)), (y, Element::from(3u8)),
+                        (Wire::ONE, Element::from(10u8))], terms);
+        assert_eq!(Element::from(10u8), exp.max_coefficient());
+        assert_eq!(Element::from(10u8), exp.constant_term());
+        assert_eq!(2, exp.l0_norm());
+
+        let non_constant = exp.without_constant();
+        assert_eq!(2, non_constant.num_terms());
+        assert_eq!(Element::zero(), non_constant.constant_term());
+        assert_eq!(Element::from(5u8), non_constant.max_coefficient());
+        assert_eq!(2, non_constant.l0_norm());
+
+        // Coefficients are compared by their canonical representatives, so of -5, -3 and -10, the
+        // largest is -3.
+        assert_eq!(-Element::<F257>::from(3u8), (-&exp).max_coefficient());
+    }
+
+    #[test]
+    fn coefficient_accessors_zero() {
+        let zero = Expression::<F257>::zero();
+        assert_eq!(0, zero.iter().count());
+        assert_eq!(Element::zero(), zero.max_coefficient());
+        assert_eq!(Element::zero(), zero.constant_term());
+        assert_eq!(0, zero.without_constant().num_terms());
+        assert_eq!(0, zero.l0_norm());
+    }
+
+    #[test]
+    fn coefficient_accessors_constant() {
+        let constant = Expression::<F257>::from(42u8);
+        assert_eq!(vec![(&Wire::ONE, &Element::from(42u8))], constant.iter().collect::<Vec<_>>());
+        assert_eq!(Element::from(42u8), constant.max_coefficient());
+        assert_eq!(Element::from(42u8), constant.constant_term());
+        assert_eq!(Expression::zero(), constant.without_constant());
+        assert_eq!(1, constant.num_terms());
+        assert_eq!(0, constant.l0_norm());
+    }
+
+    #[test]
+    fn boolean_negated() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.boolean_wire();
+        let not_x = BooleanExpression::from(x).negated();
+        let gadget = builder.build();
+        assert_eq!(1, gadget.size());
+
+        let mut values_false = boolean_values!(x => false);
+        assert!(gadget.execute(&mut values_false));
+        assert_eq!(true, not_x.evaluate(&values_false));
+
+        let mut values_true = boolean_values!(x => true);
+        assert!(gadget.execute(&mut values_true));
+        assert_eq!(false, not_x.evaluate(&values_true));
+
+        let values = WireValues::<F257>::new();
+        assert_eq!(false, BooleanExpression::_true().negated().evaluate(&values));
+        assert_eq!(true, BooleanExpression::_false().negated().evaluate(&values));
+    }
+
+    #[test]
+    fn binary_not() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.binary_wire(8);
+        let not_x = BinaryExpression::from(&x).not();
+        let gadget = builder.build();
+        assert_eq!(8, gadget.size());
+
+        // ~00010011 = 11101100.
+        let mut values = binary_unsigned_values!(&x => &BigUint::from(0b00010011u32));
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(0b11101100u32), not_x.evaluate(&values));
+    }
 
     #[test]
     fn join_fermat_prime_field() {
@@ -715,4 +1101,109 @@ mod tests {
         let exp = BinaryExpression::<F257>::from(&wire);
         exp.join();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn binary_from_biguint_padded() {
+        let values = WireValues::new();
+        let zero = BinaryExpression::<F257>::from_biguint_padded(&BigUint::from(0u8), 8);
+        assert_eq!(8, zero.len());
+        assert_eq!(BigUint::from(0u8), zero.evaluate(&values));
+        assert_eq!(0, BinaryExpression::<F257>::from(BigUint::from(0u8)).len());
+
+        let six = BinaryExpression::<F257>::from_biguint_padded(&BigUint::from(6u8), 5);
+        let six_bits: Vec<bool> = six.bits.iter().map(|b| b.evaluate(&values)).collect();
+        assert_eq!(vec![false, true, true, false, false], six_bits);
+    }
+
+    #[test]
+    #[should_panic]
+    fn binary_from_biguint_padded_too_large() {
+        BinaryExpression::<F257>::from_biguint_padded(&BigUint::from(8u8), 3);
+    }
+
+    #[test]
+    fn binary_byte_order_round_trip() {
+        let value = BigUint::from(0x0001_0203_0405u64);
+        let values = WireValues::new();
+
+        let be = value.to_bytes_be();
+        let from_be = BinaryExpression::<F257>::from_be_bytes(&be);
+        assert_eq!(be.len() * 8, from_be.len());
+        assert_eq!(value, from_be.evaluate(&values));
+        assert_eq!(be, from_be.to_be_bytes_value(&values));
+
+        let le = value.to_bytes_le();
+        let from_le = BinaryExpression::<F257>::from_le_bytes(&le);
+        assert_eq!(value, from_le.evaluate(&values));
+        assert_eq!(le, from_le.to_le_bytes_value(&values));
+
+        // Leading zero bytes are preserved.
+        let padded = BinaryExpression::<F257>::from_be_bytes(&[0, 0, 1]);
+        assert_eq!(24, padded.len());
+        assert_eq!(vec![0, 0, 1], padded.to_be_bytes_value(&values));
+        assert_eq!(vec![1, 0, 0], padded.to_le_bytes_value(&values));
+    }
+
+    #[test]
+    fn binary_be_bytes_sha_block() {
+        // A 512-bit SHA-style block: "abc", then a 1 bit, zeros, and the 64-bit message length.
+        let mut block = vec![0u8; 64];
+        block[..3].copy_from_slice(b"abc");
+        block[3] = 0x80;
+        block[63] = 24;
+
+        // Build the bit vector manually. SHA reads each byte most significant bit first, so in
+        // message order, bit j of the block is bit 7 - j % 8 of byte j / 8. Our bits are least
+        // significant first, which is the reverse of message order.
+        let message_order: Vec<bool> = (0..512)
+            .map(|j| (block[j / 8] >> (7 - j % 8)) & 1 == 1)
+            .collect();
+        let expected: Vec<bool> = message_order.into_iter().rev().collect();
+
+        let values = WireValues::new();
+        let exp = BinaryExpression::<F257>::from_be_bytes(&block);
+        let bits: Vec<bool> = exp.bits.iter().map(|b| b.evaluate(&values)).collect();
+        assert_eq!(expected, bits);
+
+        // Setting a wire from the same bytes should give the same bits.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wire = builder.binary_wire(512);
+        let mut values = WireValues::<F257>::new();
+        values.set_binary_be_bytes(&wire, &block);
+        let wire_bits: Vec<bool> = wire.bits.iter().map(|&b| values.get_boolean(b)).collect();
+        assert_eq!(expected, wire_bits);
+        assert_eq!(block, BinaryExpression::<F257>::from(&wire).to_be_bytes_value(&values));
+    }
+
+    #[test]
+    fn linear_combination_matches_operators() {
+        let mut lcg = LCG::new();
+        let wires: Vec<Expression<F257>> =
+            (1..5).map(|i| Expression::from(Wire { index: i })).collect();
+        for _ in 0..20 {
+            // Build random expressions with small support, so that terms often overlap and cancel.
+            let operands: Vec<Expression<F257>> = (0..3)
+                .map(|_| wires.iter().fold(Expression::zero(), |sum, w| {
+                    sum + w * Element::from(lcg.next_u32() % 3)
+                }))
+                .collect();
+            let coefficients: Vec<Element<F257>> = (0..3).map(|_| lcg.next_element()).collect();
+            let constant: Element<F257> = lcg.next_element();
+
+            let expected = operands.iter().zip(coefficients.iter())
+                .fold(Expression::from(&constant), |sum, (e, c)| sum + e * c);
+            let terms: Vec<(Element<F257>, &Expression<F257>)> =
+                coefficients.iter().cloned().zip(operands.iter()).collect();
+            assert_eq!(expected, Expression::linear_combination(&terms, constant));
+
+            let mut sum = operands[0].clone();
+            sum.scaled_add(&coefficients[1], &operands[1]);
+            assert_eq!(&operands[0] + &operands[1] * &coefficients[1], sum);
+
+            let mut negated = operands[2].clone();
+            negated.negate_in_place();
+            assert_eq!(&operands[2] * -Element::one(), negated);
+            assert_eq!(Expression::zero(), negated + &operands[2]);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! This module provides a fluent API for building chains of binary arithmetic and bitwise
+//! operations, such as those found in hash message schedules.
+//!
+//! ```ignore
+//! let mut ctx = builder.ctx();
+//! let z = ctx.add(&x, &y).rotr(7).xor(&w).finish();
+//! ```
+//!
+//! This is purely a convenience layer; each method delegates to the corresponding `GadgetBuilder`
+//! method, so the resulting constraints are identical to those of the verbose style.
+
+use crate::expression::BinaryExpression;
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+
+/// A session for building binary expressions fluently. Each chain of operations is started with
+/// `chain`, or with one of the binary operations `add`, `xor` or `and`.
+pub struct ExprCtx<'a, F: Field> {
+    builder: &'a mut GadgetBuilder<F>,
+}
+
+/// A chain of operations in progress, holding the binary expression computed so far.
+pub struct BinaryChain<'a, F: Field> {
+    builder: &'a mut GadgetBuilder<F>,
+    current: BinaryExpression<F>,
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Start a session for building binary expressions fluently.
+    pub fn ctx(&mut self) -> ExprCtx<'_, F> {
+        ExprCtx { builder: self }
+    }
+}
+
+impl<'a, F: Field> ExprCtx<'a, F> {
+    /// Start a chain of operations with the given binary expression.
+    pub fn chain(&mut self, x: &BinaryExpression<F>) -> BinaryChain<'_, F> {
+        BinaryChain { builder: self.builder, current: x.clone() }
+    }
+
+    /// Start a chain of operations with `x + y`, ignoring overflow.
+    pub fn add(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryChain<'_, F> {
+        self.chain(x).add(y)
+    }
+
+    /// Start a chain of operations with `x ^ y`.
+    pub fn xor(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryChain<'_, F> {
+        self.chain(x).xor(y)
+    }
+
+    /// Start a chain of operations with `x & y`.
+    pub fn and(
+        &mut self, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryChain<'_, F> {
+        self.chain(x).and(y)
+    }
+}
+
+// These method names mirror the operations they perform, rather than the `std::ops` traits, since
+// each operation needs access to the builder.
+#[allow(clippy::should_implement_trait)]
+impl<'a, F: Field> BinaryChain<'a, F> {
+    /// Add `y` to the current expression, modulo `2^n` where `n` is the current width.
+    pub fn add(self, y: &BinaryExpression<F>) -> Self {
+        self.binary_op("add", y, GadgetBuilder::binary_sum_ignoring_overflow)
+    }
+
+    /// The bitwise exclusive disjunction of the current expression and `y`.
+    pub fn xor(self, y: &BinaryExpression<F>) -> Self {
+        self.binary_op("xor", y, GadgetBuilder::bitwise_xor)
+    }
+
+    /// The bitwise conjunction of the current expression and `y`.
+    pub fn and(self, y: &BinaryExpression<F>) -> Self {
+        self.binary_op("and", y, GadgetBuilder::bitwise_and)
+    }
+
+    /// Rotate the current expression by `n` bits in the direction of decreasing significance.
+    pub fn rotr(self, n: usize) -> Self {
+        self.unary_op(|builder, x| builder.bitwise_rotate_dec_significance(x, n))
+    }
+
+    /// Shift the current expression by `n` bits in the direction of decreasing significance.
+    pub fn shr(self, n: usize) -> Self {
+        assert!(n <= self.current.len(),
+                "shr: cannot shift a {}-bit expression by {} bits", self.current.len(), n);
+        self.unary_op(|builder, x| builder.bitwise_shift_dec_significance(x, n))
+    }
+
+    /// The bitwise negation of the current expression.
+    pub fn not(self) -> Self {
+        self.unary_op(|_builder, x| x.not())
+    }
+
+    /// The width, in bits, of the current expression.
+    pub fn len(&self) -> usize {
+        self.current.len()
+    }
+
+    /// Whether the current expression has a width of zero.
+    pub fn is_empty(&self) -> bool {
+        self.current.len() == 0
+    }
+
+    /// End the chain, returning the expression which was built.
+    pub fn finish(self) -> BinaryExpression<F> {
+        self.current
+    }
+
+    fn unary_op<O>(self, op: O) -> Self
+        where O: FnOnce(&mut GadgetBuilder<F>, &BinaryExpression<F>) -> BinaryExpression<F> {
+        let BinaryChain { builder, current } = self;
+        let current = op(builder, &current);
+        BinaryChain { builder, current }
+    }
+
+    fn binary_op<O>(self, name: &str, y: &BinaryExpression<F>, op: O) -> Self
+        where O: FnOnce(&mut GadgetBuilder<F>, &BinaryExpression<F>, &BinaryExpression<F>)
+            -> BinaryExpression<F> {
+        assert_eq!(self.current.len(), y.len(),
+                   "{}: mismatched widths, {} bits and {} bits", name, self.current.len(), y.len());
+        self.unary_op(|builder, x| op(builder, x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::expression::BinaryExpression;
+    use crate::field::Bn128;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::wire_values::WireValues;
+
+    #[test]
+    fn bitwise_and_fluent() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.binary_wire(8);
+        let y = builder.binary_wire(8);
+        let x_and_y = builder.ctx()
+            .and(&BinaryExpression::from(&x), &BinaryExpression::from(&y))
+            .finish();
+        let gadget = builder.build();
+
+        // 0 & 0 = 0.
+        let mut values_0_0 = binary_unsigned_values!(
+            &x => &BigUint::from(0u32),
+            &y => &BigUint::from(0u32));
+        assert!(gadget.execute(&mut values_0_0));
+        assert_eq!(BigUint::from(0u32), x_and_y.evaluate(&values_0_0));
+
+        // 255 & 0 = 0.
+        let mut values_255_0 = binary_unsigned_values!(
+            &x => &BigUint::from(0b11111111u32),
+            &y => &BigUint::from(0u32));
+        assert!(gadget.execute(&mut values_255_0));
+        assert_eq!(BigUint::from(0u32), x_and_y.evaluate(&values_255_0));
+
+        // 255 & 255 = 255.
+        let mut values_255_255 = binary_unsigned_values!(
+            &x => &BigUint::from(0b11111111u32),
+            &y => &BigUint::from(0b11111111u32));
+        assert!(gadget.execute(&mut values_255_255));
+        assert_eq!(BigUint::from(0b11111111u32), x_and_y.evaluate(&values_255_255));
+
+        // 11111100 & 00111111 = 00111100.
+        let mut values_11111100_00111111 = binary_unsigned_values!(
+            &x => &BigUint::from(0b11111100u32),
+            &y => &BigUint::from(0b00111111u32));
+        assert!(gadget.execute(&mut values_11111100_00111111));
+        assert_eq!(BigUint::from(0b00111100u32), x_and_y.evaluate(&values_11111100_00111111));
+    }
+
+    /// A miniature, SHA-like message schedule step over 8-bit words:
+    /// `w4 = ~(w0 + (rotr(w1, 3) ^ shr(w1, 1)) + w2)`.
+    #[test]
+    fn message_schedule_fluent_matches_verbose() {
+        let inputs = [0b10110011u32, 0b01011100, 0b11100001];
+
+        let mut fluent_builder = GadgetBuilder::<Bn128>::new();
+        let fluent_wires = [
+            fluent_builder.binary_wire(8),
+            fluent_builder.binary_wire(8),
+            fluent_builder.binary_wire(8),
+        ];
+        let w: Vec<BinaryExpression<Bn128>> =
+            fluent_wires.iter().map(BinaryExpression::from).collect();
+        let mut ctx = fluent_builder.ctx();
+        let shifted = ctx.chain(&w[1]).shr(1).finish();
+        let s0 = ctx.chain(&w[1]).rotr(3).xor(&shifted).finish();
+        let fluent_w4 = ctx.add(&w[0], &s0).add(&w[2]).not().finish();
+        let fluent_gadget = fluent_builder.build();
+
+        let mut verbose_builder = GadgetBuilder::<Bn128>::new();
+        let verbose_wires = [
+            verbose_builder.binary_wire(8),
+            verbose_builder.binary_wire(8),
+            verbose_builder.binary_wire(8),
+        ];
+        let w: Vec<BinaryExpression<Bn128>> =
+            verbose_wires.iter().map(BinaryExpression::from).collect();
+        let shifted = verbose_builder.bitwise_shift_dec_significance(&w[1], 1);
+        let rotated = verbose_builder.bitwise_rotate_dec_significance(&w[1], 3);
+        let s0 = verbose_builder.bitwise_xor(&rotated, &shifted);
+        let sum = verbose_builder.binary_sum_ignoring_overflow(&w[0], &s0);
+        let sum = verbose_builder.binary_sum_ignoring_overflow(&sum, &w[2]);
+        let verbose_w4 = verbose_builder.bitwise_not(&sum);
+        let verbose_gadget = verbose_builder.build();
+
+        assert_eq!(verbose_gadget.size(), fluent_gadget.size());
+
+        let mut fluent_values = WireValues::new();
+        let mut verbose_values = WireValues::new();
+        for i in 0..3 {
+            fluent_values.set_binary_unsigned(&fluent_wires[i], &BigUint::from(inputs[i]));
+            verbose_values.set_binary_unsigned(&verbose_wires[i], &BigUint::from(inputs[i]));
+        }
+        assert!(fluent_gadget.execute(&mut fluent_values));
+        assert!(verbose_gadget.execute(&mut verbose_values));
+
+        let rotr = |x: u32, n: u32| ((x >> n) | (x << (8 - n))) & 0xFF;
+        let s0 = rotr(inputs[1], 3) ^ (inputs[1] >> 1);
+        let expected = !(inputs[0] + s0 + inputs[2]) & 0xFF;
+        assert_eq!(BigUint::from(expected), fluent_w4.evaluate(&fluent_values));
+        assert_eq!(BigUint::from(expected), verbose_w4.evaluate(&verbose_values));
+    }
+
+    #[test]
+    #[should_panic(expected = "xor: mismatched widths, 8 bits and 4 bits")]
+    fn mismatched_widths() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = BinaryExpression::from(&builder.binary_wire(8));
+        let y = BinaryExpression::from(&builder.binary_wire(4));
+        builder.ctx().chain(&x).rotr(1).xor(&y);
+    }
+}
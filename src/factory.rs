@@ -0,0 +1,58 @@
+//! This module defines factories for gadgets which are built many times with identical structure,
+//! such as one per request, along with a thread-safe cache which builds such a gadget only once.
+
+#[cfg(feature = "std")]
+use std::sync::{Arc, OnceLock};
+
+use crate::field::Field;
+use crate::gadget::Gadget;
+#[cfg(feature = "std")]
+use crate::wire_values::WireValues;
+
+/// A source of gadgets whose structure is fixed by the factory's parameters.
+pub trait GadgetFactory<F: Field> {
+    /// Handles to the wires of each gadget, used to populate a witness, such as
+    /// `StatementHandles`.
+    type IoHandles: Clone;
+
+    /// Builds a gadget along with handles to its wires. Every call should build a structurally
+    /// identical gadget, with the same wire numbering, so that callers may cache the result.
+    fn build_gadget(&self) -> (Gadget<F>, Self::IoHandles);
+}
+
+/// Wraps a `GadgetFactory`, building its gadget on first use and sharing it afterwards.
+///
+/// This is `Sync` whenever the wrapped factory and its handles are, so a single cache can serve
+/// several threads. The first caller builds the gadget, while any concurrent callers wait for it.
+#[cfg(feature = "std")]
+pub struct CachedFactory<F: Field, T: GadgetFactory<F>> {
+    factory: T,
+    cache: OnceLock<(Arc<Gadget<F>>, T::IoHandles)>,
+}
+
+#[cfg(feature = "std")]
+impl<F: Field, T: GadgetFactory<F>> CachedFactory<F, T> {
+    pub fn new(factory: T) -> Self {
+        CachedFactory { factory, cache: OnceLock::new() }
+    }
+
+    /// The wrapped factory.
+    pub fn factory(&self) -> &T {
+        &self.factory
+    }
+
+    /// Returns the shared gadget and its handles, building them if this is the first call, along
+    /// with a fresh `WireValues` for the caller to populate and pass to `execute`.
+    pub fn instance(&self) -> (Arc<Gadget<F>>, T::IoHandles, WireValues<F>) {
+        let (gadget, handles) = self.cache.get_or_init(|| {
+            let (gadget, handles) = self.factory.build_gadget();
+            (Arc::new(gadget), handles)
+        });
+        (gadget.clone(), handles.clone(), WireValues::new())
+    }
+
+    /// Whether the gadget has been built yet.
+    pub fn is_built(&self) -> bool {
+        self.cache.get().is_some()
+    }
+}
@@ -0,0 +1,101 @@
+//! This module provides out-of-circuit number theoretic transforms, for use in witness generation
+//! and other polynomial arithmetic. No constraints are involved.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::field::{Element, FieldExt};
+
+/// Evaluate the polynomial with the given coefficients at each `n`th root of unity, where `n` is
+/// the number of coefficients. The output is ordered `[p(1), p(w), ..., p(w^(n - 1))]`.
+///
+/// `n` must be a power of two which divides `2^s`, where `s` is the two-adicity of `F`.
+pub fn ntt<F: FieldExt>(coefficients: &[Element<F>]) -> Vec<Element<F>> {
+    let roots = Element::<F>::roots_of_unity(coefficients.len());
+    ntt_with_roots(coefficients, &roots)
+}
+
+/// The inverse of `ntt`. Interpolate the polynomial which evaluates to the given values at each
+/// `n`th root of unity, returning its coefficients.
+pub fn intt<F: FieldExt>(evaluations: &[Element<F>]) -> Vec<Element<F>> {
+    let n = evaluations.len();
+    let roots = Element::<F>::roots_of_unity(n);
+
+    // The inverse transform is the forward transform with inverted roots, scaled by 1/n. The
+    // inverse of w^i is w^(n - i).
+    let inverse_roots: Vec<Element<F>> = (0..n).map(|i| roots[(n - i) % n].clone()).collect();
+    let n_inverse = Element::from(n).multiplicative_inverse();
+    ntt_with_roots(evaluations, &inverse_roots).into_iter()
+        .map(|x| x * &n_inverse)
+        .collect()
+}
+
+/// An iterative radix-2 Cooley-Tukey transform. `roots` must contain `[1, w, ..., w^(n - 1)]`.
+fn ntt_with_roots<F: FieldExt>(input: &[Element<F>], roots: &[Element<F>]) -> Vec<Element<F>> {
+    let n = input.len();
+    assert_eq!(n, roots.len());
+    let log_n = n.trailing_zeros();
+
+    // Start with the inputs in bit-reversed order, so that the outputs will be in natural order.
+    let mut values: Vec<Element<F>> = (0..n)
+        .map(|i| input[reverse_bits(i, log_n)].clone())
+        .collect();
+
+    let mut half_size = 1;
+    while half_size < n {
+        let size = half_size * 2;
+        let root_stride = n / size;
+        for start in (0..n).step_by(size) {
+            for j in 0..half_size {
+                let twiddle = &roots[j * root_stride];
+                let even = values[start + j].clone();
+                let odd = &values[start + j + half_size] * twiddle;
+                values[start + j] = &even + &odd;
+                values[start + j + half_size] = even - odd;
+            }
+        }
+        half_size = size;
+    }
+    values
+}
+
+/// Reverse the `bits` least significant bits of `i`.
+fn reverse_bits(i: usize, bits: u32) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        i.reverse_bits() >> (usize::BITS - bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::Bn128;
+    use crate::fft::{intt, ntt};
+    use crate::field::Element;
+
+    #[test]
+    fn ntt_intt_round_trip_8() {
+        type F = Bn128;
+        let coefficients: Vec<Element<F>> = (0u8..8).map(|i| Element::from(i * 3 + 1)).collect();
+        let evaluations = ntt(&coefficients);
+        assert_eq!(coefficients, intt(&evaluations));
+    }
+
+    #[test]
+    fn ntt_evaluates_polynomial() {
+        type F = Bn128;
+        // p(x) = 1 + 2x + 3x^2 + 4x^3.
+        let coefficients: Vec<Element<F>> = (1u8..=4).map(Element::from).collect();
+        let evaluations = ntt(&coefficients);
+        let roots = Element::<F>::roots_of_unity(4);
+        for (x, y) in roots.iter().zip(evaluations.iter()) {
+            let expected = coefficients.iter().rev()
+                .fold(Element::zero(), |acc, c| acc * x + c);
+            assert_eq!(&expected, y);
+        }
+    }
+}
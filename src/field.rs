@@ -1,3 +1,6 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
 use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
@@ -7,9 +10,15 @@ use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Shl, Sub, Su
 use std::str::FromStr;
 
 use num::bigint::ParseBigIntError;
+#[cfg(feature = "rand")]
+use num::bigint::RandBigInt;
 use num::BigUint;
 use num_traits::One;
 use num_traits::Zero;
+#[cfg(feature = "rand")]
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "rand")]
+use rand_chacha::ChaCha20Rng;
 
 /// A prime order field.
 pub trait Field: 'static {
@@ -17,28 +26,109 @@ pub trait Field: 'static {
     fn order() -> BigUint;
 }
 
-/// The BN128 curve.
+/// The scalar field of the BN128 curve, i.e. the order of its groups. This is the field which
+/// SNARKs over BN128 express constraints in. For the field which BN128's points are defined over,
+/// see `Bn128Base`.
 #[derive(Debug)]
 pub struct Bn128 {}
 
 impl Field for Bn128 {
     fn order() -> BigUint {
-        BigUint::from_str(
-            "21888242871839275222246405745257275088548364400416034343698204186575808495617"
-        ).unwrap()
+        // Parsing the order from decimal on every call is slow, so these are the little-endian
+        // 32-bit digits of
+        // 21888242871839275222246405745257275088548364400416034343698204186575808495617.
+        BigUint::from_slice(&[0xf0000001, 0x43e1f593, 0x79b97091, 0x2833e848,
+                              0x8181585d, 0xb85045b6, 0xe131a029, 0x30644e72])
     }
 }
 
-/// The BLS12-381 curve.
+/// The scalar field of the BLS12-381 curve, i.e. the order of its groups. This is the field which
+/// SNARKs over BLS12-381 express constraints in, and the base field of the embedded `JubJub` curve.
+/// For the field which BLS12-381's points are defined over, see `Bls12_381Base`.
 #[derive(Debug)]
 pub struct Bls12_381 {}
 
 impl Field for Bls12_381 {
     fn order() -> BigUint {
-        BigUint::from_str(
-            "52435875175126190479447740508185965837690552500527637822603658699938581184513"
+        // The little-endian 32-bit digits of
+        // 52435875175126190479447740508185965837690552500527637822603658699938581184513.
+        BigUint::from_slice(&[0x00000001, 0xffffffff, 0xfffe5bfe, 0x53bda402,
+                              0x09a1d805, 0x3339d808, 0x299d7d48, 0x73eda753])
+    }
+}
+
+/// The base field of the BN128 curve, which its points are defined over. This is distinct from
+/// `Bn128`, the scalar field.
+#[derive(Debug)]
+pub struct Bn128Base {}
+
+impl Field for Bn128Base {
+    fn order() -> BigUint {
+        // The little-endian 32-bit digits of
+        // 21888242871839275222246405745257275088696311157297823662689037894645226208583.
+        BigUint::from_slice(&[0xd87cfd47, 0x3c208c16, 0x6871ca8d, 0x97816a91,
+                              0x8181585d, 0xb85045b6, 0xe131a029, 0x30644e72])
+    }
+}
+
+/// The 381-bit base field of the BLS12-381 curve, which its points are defined over. This is
+/// distinct from `Bls12_381`, the scalar field.
+#[derive(Debug)]
+pub struct Bls12_381Base {}
+
+impl Field for Bls12_381Base {
+    fn order() -> BigUint {
+        // The little-endian 32-bit digits of 40024095552216673934177898257359041565568828199390078
+        // 85332058136124031650490837864442687629129015664037894272559787.
+        BigUint::from_slice(&[0xffffaaab, 0xb9feffff, 0xb153ffff, 0x1eabfffe,
+                              0xf6b0f624, 0x6730d2a0, 0xf38512bf, 0x64774b84,
+                              0x434bacd7, 0x4b1ba7b6, 0x397fe69a, 0x1a0111ea])
+    }
+}
+
+/// A prime field with additional structure which is useful for FFTs and related polynomial
+/// arithmetic.
+pub trait FieldExt: Field + Sized {
+    /// The largest `s` such that `2^s` divides `|F| - 1`.
+    fn two_adicity() -> u32;
+
+    /// A primitive `2^s`th root of unity, where `s` is the two-adicity of this field.
+    fn two_adic_root_of_unity() -> Element<Self>;
+
+    /// A generator of the multiplicative group of this field.
+    fn multiplicative_generator() -> Element<Self>;
+}
+
+impl FieldExt for Bn128 {
+    fn two_adicity() -> u32 {
+        28
+    }
+
+    fn two_adic_root_of_unity() -> Element<Self> {
+        Element::from_str(
+            "19103219067921713944291392827692070036145651957329286315305642004821462161904"
+        ).unwrap()
+    }
+
+    fn multiplicative_generator() -> Element<Self> {
+        Element::from(5u8)
+    }
+}
+
+impl FieldExt for Bls12_381 {
+    fn two_adicity() -> u32 {
+        32
+    }
+
+    fn two_adic_root_of_unity() -> Element<Self> {
+        Element::from_str(
+            "10238227357739495823651030575849232062558860180284477541189508159991286009131"
         ).unwrap()
     }
+
+    fn multiplicative_generator() -> Element<Self> {
+        Element::from(7u8)
+    }
 }
 
 /// An element of a prime field.
@@ -47,8 +137,9 @@ pub struct Element<F: Field> {
     n: BigUint,
     /// F needs to be present in a struct field, otherwise the compiler will complain that it is
     /// unused. In reality it is used, but only at compile time. For example, some functions take an
-    /// `Element<F>` and call `F::order()`.
-    phantom: PhantomData<*const F>,
+    /// `Element<F>` and call `F::order()`. We use `fn() -> F` rather than `*const F` so that
+    /// elements, and the expressions and gadgets built from them, are `Send` and `Sync`.
+    phantom: PhantomData<fn() -> F>,
 }
 
 impl<F: Field> Element<F> {
@@ -64,6 +155,28 @@ impl<F: Field> Element<F> {
         Self::from(F::order() - BigUint::one())
     }
 
+    /// Returns a uniformly random element drawn from the given source of randomness.
+    #[cfg(feature = "rand")]
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::from(rng.gen_biguint_below(&F::order()))
+    }
+
+    /// Returns `n` uniformly random elements drawn from the given source of randomness.
+    #[cfg(feature = "rand")]
+    pub fn random_vec<R: Rng + ?Sized>(rng: &mut R, n: usize) -> Vec<Self> {
+        (0..n).map(|_i| Self::random(rng)).collect()
+    }
+
+    /// Returns the `index`th element of a deterministic stream identified by `seed`. Each index is
+    /// drawn from its own ChaCha20 stream, so any element can be computed without generating the
+    /// ones before it.
+    #[cfg(feature = "rand")]
+    pub fn random_from_seed(seed: u64, index: usize) -> Self {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        rng.set_stream(index as u64);
+        Self::random(&mut rng)
+    }
+
     pub fn to_biguint(&self) -> &BigUint {
         &self.n
     }
@@ -82,10 +195,10 @@ impl<F: Field> Element<F> {
 
     pub fn multiplicative_inverse(&self) -> Self {
         assert!(!self.is_zero(), "Zero does not have a multiplicative inverse");
-        // From Fermat's little theorem.
+        // From Fermat's little theorem, x^(p - 2) = x^-1.
         // TODO: Use a faster method, like the one described in "Fast Modular Reciprocals".
         // Or just wait for https://github.com/rust-num/num-bigint/issues/60
-        self.exponentiation(&-Self::from(2u8))
+        self.pow_biguint(&(F::order() - BigUint::from(2u8)))
     }
 
     /// Like `multiplicative_inverse`, except that zero is mapped to itself rather than causing a
@@ -98,8 +211,48 @@ impl<F: Field> Element<F> {
         }
     }
 
+    /// Raise this element to the given power. Since the power is itself a field element, this is
+    /// limited to powers less than `|F|`; use `pow_biguint` for larger powers.
     pub fn exponentiation(&self, power: &Self) -> Self {
-        Self::from(self.to_biguint().modpow(power.to_biguint(), &F::order()))
+        self.pow_biguint(power.to_biguint())
+    }
+
+    /// Raise this element to an arbitrary integer power. The power is used as is, with no
+    /// reduction modulo `|F| - 1` or otherwise.
+    pub fn pow_biguint(&self, power: &BigUint) -> Self {
+        Self::from(self.to_biguint().modpow(power, &F::order()))
+    }
+
+    /// Raise this element to a (small) integer power. This uses square-and-multiply with field
+    /// multiplications, so the power is never converted to a `BigUint`.
+    pub fn pow_u64(&self, power: u64) -> Self {
+        match power {
+            0 => Self::one(),
+            1 => self.clone(),
+            2 => self.square(),
+            3 => self.cube(),
+            _ => {
+                // Scan the bits of the power from most to least significant.
+                let mut result = self.clone();
+                for i in (0..63 - power.leading_zeros()).rev() {
+                    result = result.square();
+                    if (power >> i) & 1 == 1 {
+                        result *= self;
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    /// Returns `self^2`.
+    pub fn square(&self) -> Self {
+        self * self
+    }
+
+    /// Returns `self^3`.
+    pub fn cube(&self) -> Self {
+        &self.square() * self
     }
 
     pub fn integer_division(&self, rhs: &Self) -> Self {
@@ -146,6 +299,92 @@ impl<F: Field> Element<F> {
     pub fn bit(&self, i: usize) -> bool {
         ((self.to_biguint() >> i) & BigUint::one()).is_one()
     }
+
+    /// Whether this element is a square in `F`. Zero is considered a square.
+    pub fn is_quadratic_residue(&self) -> bool {
+        // This is Euler's criterion.
+        if self.is_zero() {
+            return true;
+        }
+        let power = (F::order() - BigUint::one()) >> 1;
+        self.pow_biguint(&power).is_one()
+    }
+
+    /// The smallest element which is not a square in `F`. `F` must have an odd order.
+    pub fn smallest_quadratic_nonresidue() -> Self {
+        let mut candidate = Self::from(2u8);
+        while candidate.is_quadratic_residue() {
+            candidate += Self::one();
+        }
+        candidate
+    }
+
+    /// Returns some `r` such that `r^2 = self`, or `None` if this element is not a square. The
+    /// other square root, if distinct, is `-r`.
+    pub fn square_root(&self) -> Option<Self> {
+        if !self.is_quadratic_residue() {
+            return None;
+        }
+        if self.is_zero() {
+            return Some(Self::zero());
+        }
+
+        // This is the Tonelli-Shanks algorithm. We write |F| - 1 = q 2^s with q odd.
+        let mut q = F::order() - BigUint::one();
+        let mut s = 0;
+        while (&q & BigUint::one()).is_zero() {
+            q >>= 1;
+            s += 1;
+        }
+
+        let mut m = s;
+        let mut c = Self::smallest_quadratic_nonresidue().pow_biguint(&q);
+        let mut t = self.pow_biguint(&q);
+        let mut r = self.pow_biguint(&((&q + BigUint::one()) >> 1));
+        while !t.is_one() {
+            // Find the least i such that t^(2^i) = 1.
+            let mut i = 0;
+            let mut t_pow = t.clone();
+            while !t_pow.is_one() {
+                t_pow = &t_pow * &t_pow;
+                i += 1;
+            }
+
+            let mut b = c;
+            for _j in 0..(m - i - 1) {
+                b = &b * &b;
+            }
+            m = i;
+            c = &b * &b;
+            t = &t * &c;
+            r = &r * &b;
+        }
+        Some(r)
+    }
+}
+
+impl<F: FieldExt> Element<F> {
+    /// Return all `n`th roots of unity, in the order `[1, w, w^2, ..., w^(n - 1)]` where `w` is a
+    /// primitive `n`th root of unity. `n` must divide `2^s`, where `s` is the two-adicity of `F`.
+    pub fn roots_of_unity(n: usize) -> Vec<Self> {
+        assert!(n.is_power_of_two(), "n must be a power of two");
+        let log_n = n.trailing_zeros();
+        assert!(log_n <= F::two_adicity(), "n must divide the order of the two-adic subgroup");
+
+        let mut w = F::two_adic_root_of_unity();
+        for _i in log_n..F::two_adicity() {
+            w = &w * &w;
+        }
+
+        let mut roots = Vec::with_capacity(n);
+        let mut current = Self::one();
+        for _i in 0..n {
+            let next = &current * &w;
+            roots.push(current);
+            current = next;
+        }
+        roots
+    }
 }
 
 impl<F: Field> From<BigUint> for Element<F> {
@@ -285,7 +524,12 @@ impl<F: Field> Add<&Element<F>> for &Element<F> {
     type Output = Element<F>;
 
     fn add(self, rhs: &Element<F>) -> Element<F> {
-        Element::from((self.to_biguint() + rhs.to_biguint()) % F::order())
+        // Both summands are less than |F|, so subtracting |F| at most once reduces the sum, which
+        // is much cheaper than a division.
+        let order = F::order();
+        let sum = self.to_biguint() + rhs.to_biguint();
+        let n = if sum >= order { sum - order } else { sum };
+        Element { n, phantom: PhantomData }
     }
 }
 
@@ -502,12 +746,36 @@ impl<F: Field> fmt::Display for Element<F> {
 
 #[cfg(test)]
 mod tests {
+    use std::fmt::Debug;
     use std::iter;
 
     use itertools::assert_equal;
+    use num::BigUint;
+    use num_traits::{One, Zero};
+    #[cfg(feature = "rand")]
+    use rand::SeedableRng;
+    #[cfg(feature = "rand")]
+    use rand_chacha::ChaCha20Rng;
 
-    use crate::field::Element;
-    use crate::test_util::{F257, F7};
+    use crate::field::{Bls12_381, Bls12_381Base, Bn128, Bn128Base, Element, Field, FieldExt};
+    use crate::fields::small::{F257, F7};
+
+    #[test]
+    fn large_field_orders() {
+        assert_eq!(
+            Bn128::order().to_string(),
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617");
+        assert_eq!(
+            Bls12_381::order().to_string(),
+            "52435875175126190479447740508185965837690552500527637822603658699938581184513");
+        assert_eq!(
+            Bn128Base::order().to_string(),
+            "21888242871839275222246405745257275088696311157297823662689037894645226208583");
+        assert_eq!(
+            Bls12_381Base::order().to_string(),
+            "4002409555221667393417789825735904156556882819939007885332058136124031650490837864442\
+             687629129015664037894272559787");
+    }
 
     #[test]
     fn addition() {
@@ -575,7 +843,7 @@ mod tests {
         let x = Element::<F257>::zero();
         let n: usize = 20;
         assert_equal(
-            iter::repeat(false).take(n),
+            iter::repeat_n(false, n),
             (0..n).map(|i| x.bit(i)));
     }
 
@@ -594,6 +862,167 @@ mod tests {
         assert_eq!(false, x.bit(9));
     }
 
+    #[test]
+    fn pow_u64() {
+        type F = F257;
+        assert_eq!(Element::<F>::one(), Element::<F>::from(3u8).pow_u64(0));
+        assert_eq!(Element::<F>::from(243u8), Element::<F>::from(3u8).pow_u64(5));
+        assert_eq!(Element::<F>::one(), Element::<F>::from(3u8).pow_u64(256));
+    }
+
+    /// Pseudorandom elements and exponents from a linear congruential generator. Exponents span
+    /// twice as many bits as the field, so many exceed `|F|`.
+    fn pseudorandom_pairs<F: Field>(count: usize) -> Vec<(Element<F>, BigUint)> {
+        let mut state = 42u64;
+        let mut next_biguint = |bits: usize| {
+            let mut n = BigUint::zero();
+            for _i in 0..bits.div_ceil(64) {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                n = (n << 64) + BigUint::from(state);
+            }
+            n
+        };
+        let bits = Element::<F>::max_bits();
+        (0..count)
+            .map(|_i| (Element::from(next_biguint(bits) % F::order()), next_biguint(2 * bits)))
+            .collect()
+    }
+
+    #[test]
+    fn pow_biguint_matches_modpow() {
+        fn check<F: Field + Debug>() {
+            for (x, e) in pseudorandom_pairs::<F>(20) {
+                let expected = Element::<F>::from(x.to_biguint().modpow(&e, &F::order()));
+                assert_eq!(expected, x.pow_biguint(&e));
+
+                let e_reduced = Element::<F>::from(&e % F::order());
+                assert_eq!(x.pow_biguint(e_reduced.to_biguint()), x.exponentiation(&e_reduced));
+
+                let small = e.to_u64_digits().first().cloned().unwrap_or(0) >> 48;
+                assert_eq!(x.pow_biguint(&BigUint::from(small)), x.pow_u64(small));
+            }
+        }
+        check::<F257>();
+        check::<Bn128>();
+    }
+
+    #[test]
+    fn pow_small_exponents() {
+        fn check<F: Field + Debug>() {
+            for (x, _e) in pseudorandom_pairs::<F>(5) {
+                assert_eq!(Element::one(), x.pow_biguint(&BigUint::zero()));
+                assert_eq!(x, x.pow_biguint(&BigUint::one()));
+                assert_eq!(Element::one(), x.pow_u64(0));
+                assert_eq!(x, x.pow_u64(1));
+                assert_eq!(&x * &x, x.square());
+                assert_eq!(&x * &x * &x, x.cube());
+                assert_eq!(x.square(), x.pow_u64(2));
+                assert_eq!(x.cube(), x.pow_u64(3));
+                assert_eq!(x.cube().square(), x.pow_u64(6));
+            }
+            assert_eq!(Element::<F>::one(), Element::<F>::zero().pow_u64(0));
+            assert_eq!(Element::<F>::zero(), Element::<F>::zero().pow_u64(7));
+        }
+        check::<F257>();
+        check::<Bn128>();
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random_from_seed() {
+        let x = Element::<Bn128>::random_from_seed(1, 0);
+        assert_eq!(x, Element::random_from_seed(1, 0));
+        assert!(x != Element::random_from_seed(1, 1));
+        assert!(x != Element::random_from_seed(2, 0));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random_vec() {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let elements = Element::<Bn128>::random_vec(&mut rng, 3);
+        assert_eq!(3, elements.len());
+        assert!(elements[0] != elements[1] && elements[1] != elements[2]);
+
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        assert_eq!(elements, Element::random_vec(&mut rng, 3));
+    }
+
+    #[test]
+    fn multiplicative_inverse_random() {
+        fn check<F: Field + Debug>() {
+            for (x, _e) in pseudorandom_pairs::<F>(20) {
+                if x.is_nonzero() {
+                    assert_eq!(Element::<F>::one(), &x * &x.multiplicative_inverse());
+                }
+            }
+        }
+        check::<F257>();
+        check::<Bn128>();
+    }
+
+    #[test]
+    fn field_orders_match_curve_parameters() {
+        // BN128 is parameterized by u, with base field order 36u^4 + 36u^3 + 24u^2 + 6u + 1 and
+        // scalar field order 36u^4 + 36u^3 + 18u^2 + 6u + 1.
+        let u = BigUint::from(4965661367192848881u64);
+        let bn_common = BigUint::from(36u8) * u.pow(4) + BigUint::from(36u8) * u.pow(3)
+            + BigUint::from(6u8) * &u + 1u8;
+        assert_eq!(&bn_common + BigUint::from(24u8) * u.pow(2), Bn128Base::order());
+        assert_eq!(&bn_common + BigUint::from(18u8) * u.pow(2), Bn128::order());
+
+        // BLS12-381 is parameterized by x = -0xd201000000010000, with scalar field order
+        // r = x^4 - x^2 + 1 and base field order (x - 1)^2 r / 3 + x.
+        let x = BigUint::from(0xd201000000010000u64);
+        let r = x.pow(4) - x.pow(2) + 1u8;
+        assert_eq!(r, Bls12_381::order());
+        assert_eq!((&x + 1u8).pow(2) * r / 3u8 - x, Bls12_381Base::order());
+        assert_eq!(381, Bls12_381Base::order().bits());
+        assert_eq!(254, Bn128Base::order().bits());
+    }
+
+    #[test]
+    fn two_adic_root_of_unity_order() {
+        fn check<F: FieldExt + Debug>() {
+            let w = F::two_adic_root_of_unity();
+            let half_order = 1u64 << (F::two_adicity() - 1);
+            assert_eq!(-Element::<F>::one(), w.pow_u64(half_order));
+            assert_eq!(Element::<F>::one(), w.pow_u64(half_order * 2));
+        }
+        check::<Bn128>();
+        check::<Bls12_381>();
+    }
+
+    #[test]
+    fn multiplicative_generator_is_nonresidue() {
+        fn check<F: FieldExt + Debug>() {
+            let g = F::multiplicative_generator();
+            let half = Element::<F>::from(Element::<F>::largest_element().to_biguint() >> 1);
+            assert_eq!(-Element::<F>::one(), g.exponentiation(&half));
+        }
+        check::<Bn128>();
+        check::<Bls12_381>();
+    }
+
+    #[test]
+    fn roots_of_unity() {
+        type F = Bn128;
+        let roots = Element::<F>::roots_of_unity(8);
+        assert_eq!(8, roots.len());
+        assert_eq!(Element::<F>::one(), roots[0]);
+        assert_eq!(-Element::<F>::one(), roots[4]);
+        for root in roots.iter() {
+            assert_eq!(Element::<F>::one(), root.pow_u64(8));
+        }
+        assert_eq!(Element::<F>::one(), &roots[7] * &roots[1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn roots_of_unity_too_large() {
+        Element::<Bn128>::roots_of_unity(1 << 29);
+    }
+
     #[test]
     fn order_of_elements() {
         type F = F257;
@@ -601,4 +1030,29 @@ mod tests {
             assert!(Element::<F>::from(i) < Element::<F>::from(i + 1));
         }
     }
+
+    #[test]
+    fn square_roots() {
+        type F = F257;
+        let mut squares = 0;
+        for i in 0u16..257 {
+            let x = Element::<F>::from(i);
+            match x.square_root() {
+                Some(r) => {
+                    assert_eq!(x, &r * &r);
+                    squares += 1;
+                }
+                None => assert!(!x.is_quadratic_residue()),
+            }
+        }
+        // Zero, plus half of the 256 nonzero elements.
+        assert_eq!(129, squares);
+        assert_eq!(Element::<F>::from(3u8), Element::smallest_quadratic_nonresidue());
+
+        // BLS12-381 has a two-adicity of 32, which exercises the Tonelli-Shanks loop.
+        let x = Element::<Bls12_381>::from(123456789u32);
+        let x_squared = &x * &x;
+        let r = x_squared.square_root().unwrap();
+        assert!(r == x || r == -x);
+    }
 }
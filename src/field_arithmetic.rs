@@ -1,6 +1,12 @@
 //! This module extends GadgetBuilder with native field arithmetic methods.
 
-use crate::expression::{BooleanExpression, Expression};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::BigUint;
+use num_traits::One;
+
+use crate::expression::{BinaryExpression, BooleanExpression, Expression};
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
 use crate::wire_values::WireValues;
@@ -9,97 +15,221 @@ use crate::util::concat;
 impl<F: Field> GadgetBuilder<F> {
     /// The product of two `Expression`s `x` and `y`, i.e. `x * y`.
     pub fn product(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
-        if let Some(c) = x.as_constant() {
-            return y * c;
-        }
-        if let Some(c) = y.as_constant() {
-            return x * c;
-        }
+        logged!(self, "product", [x, y], {
+            if let Some(c) = x.as_constant() {
+                return y * c;
+            }
+            if let Some(c) = y.as_constant() {
+                return x * c;
+            }
 
-        let product = self.wire();
-        let product_exp = Expression::from(product);
-        self.assert_product(x, y, &product_exp);
-
-        {
-            let x = x.clone();
-            let y = y.clone();
-            self.generator(
-                concat(&[x.dependencies(), y.dependencies()]),
-                move |values: &mut WireValues<F>| {
-                    let product_value = x.evaluate(values) * y.evaluate(values);
-                    values.set(product, product_value);
-                },
-            );
-        }
+            let product = self.wire();
+            let product_exp = Expression::from(product);
+            self.assert_product(x, y, &product_exp);
+
+            {
+                let x = x.clone();
+                let y = y.clone();
+                self.generator_with_outputs(
+                    concat(&[x.dependencies(), y.dependencies()]),
+                    vec![product],
+                    move |values: &mut WireValues<F>| {
+                        let product_value = x.evaluate(values) * y.evaluate(values);
+                        values.set(product, product_value);
+                    },
+                );
+            }
+
+            product_exp
+        })
+    }
+
+    /// The partial products of `terms`, i.e. `p_i = t_0 * ... * t_i` for each `i`. Each step costs
+    /// one product constraint, except that constant factors are free, so the first step is free.
+    pub fn grand_product(&mut self, terms: &[Expression<F>]) -> Vec<Expression<F>> {
+        logged!(self, "grand_product", [terms.len()], {
+            self.grand_product_from(&Expression::one(), terms)
+        })
+    }
+
+    /// Like `grand_product`, but starting from the accumulator `start`, i.e.
+    /// `p_i = start * t_0 * ... * t_i`. If `start` isn't constant, the first step also costs a
+    /// constraint.
+    pub fn grand_product_from(
+        &mut self, start: &Expression<F>, terms: &[Expression<F>],
+    ) -> Vec<Expression<F>> {
+        logged!(self, "grand_product_from", [start, terms.len()], {
+            let mut accumulator = start.clone();
+            terms.iter()
+                .map(|term| {
+                    accumulator = self.product(&accumulator, term);
+                    accumulator.clone()
+                })
+                .collect()
+        })
+    }
 
-        product_exp
+    /// The product of all of `terms`, or one if there are none. This is the last element of
+    /// `grand_product`, for callers which don't need the intermediate products.
+    pub fn grand_product_final(&mut self, terms: &[Expression<F>]) -> Expression<F> {
+        logged!(self, "grand_product_final", [terms.len()], {
+            self.grand_product(terms).pop().unwrap_or_else(Expression::one)
+        })
     }
 
-    /// `x^p` for a constant `p`.
+    /// `x^p` for a constant `p`. If `x` is also constant, this requires no constraints.
     pub fn exponentiation(&mut self, x: &Expression<F>, p: &Element<F>) -> Expression<F> {
-        // This is exponentiation by squaring. For each 1 bit of p, multiply by the associated
-        // square power.
-        let mut product_exp = Expression::one();
-        let mut last_square = Expression::zero();
-
-        for i in 0..p.bits() {
-            let square = if i == 0 {
-                x.clone()
-            } else {
-                self.product(&last_square, &last_square)
-            };
+        logged!(self, "exponentiation", [x, p], {
+            if let Some(c) = x.as_constant() {
+                return Expression::from(c.exponentiation(p));
+            }
+
+            // This is exponentiation by squaring. For each 1 bit of p, multiply by the associated
+            // square power.
+            let mut product_exp = Expression::one();
+            let mut last_square = Expression::zero();
+
+            for i in 0..p.bits() {
+                let square = if i == 0 {
+                    x.clone()
+                } else {
+                    self.product(&last_square, &last_square)
+                };
 
-            if p.bit(i) {
-                product_exp = self.product(&product_exp, &square);
+                if p.bit(i) {
+                    product_exp = self.product(&product_exp, &square);
+                }
+
+                last_square = square;
             }
+            product_exp
+        })
+    }
 
-            last_square = square;
-        }
-        product_exp
+    /// `x^e`, where the exponent `e` is given in binary. This is square-and-multiply, so each bit
+    /// costs a squaring, a selection between the square and one, and a product with the
+    /// accumulator. If `x` is constant, this uses `constant_base_exponentiation` instead.
+    pub fn exponentiation_variable(
+        &mut self, x: &Expression<F>, exponent_bits: &BinaryExpression<F>,
+    ) -> Expression<F> {
+        logged!(self, "exponentiation_variable", [x, exponent_bits.join_allowing_overflow()], {
+            if let Some(c) = x.as_constant() {
+                return self.constant_base_exponentiation(&c, exponent_bits);
+            }
+
+            let mut product_exp = Expression::one();
+            let mut square = x.clone();
+            for (i, bit) in exponent_bits.bits.iter().enumerate() {
+                if i > 0 {
+                    square = self.product(&square, &square);
+                }
+                let factor = self.selection(bit, &square, &Expression::one());
+                product_exp = self.product(&product_exp, &factor);
+            }
+            product_exp
+        })
+    }
+
+    /// `base^e` for a constant `base`, where the exponent `e` is given in binary. The powers
+    /// `base^(2^i)` are computed while building, so selecting between each power and one is linear
+    /// in the exponent bit, and each bit costs only a product with the accumulator. The first
+    /// product is with one, so it's free, as are bits whose power is one.
+    pub fn constant_base_exponentiation(
+        &mut self, base: &Element<F>, exponent_bits: &BinaryExpression<F>,
+    ) -> Expression<F> {
+        logged!(self, "constant_base_exponentiation",
+                [base, exponent_bits.join_allowing_overflow()], {
+            let mut product_exp = Expression::one();
+            let mut power = base.clone();
+            for (i, bit) in exponent_bits.bits.iter().enumerate() {
+                if i > 0 {
+                    power = &power * &power;
+                }
+                // bit ? power : 1, i.e. 1 + bit * (power - 1).
+                let factor = Expression::one() + bit.expression() * (&power - Element::one());
+                product_exp = self.product(&product_exp, &factor);
+            }
+            product_exp
+        })
     }
 
     /// Returns `1 / x`, assuming `x` is non-zero. If `x` is zero, the gadget will not be
     /// satisfiable.
+    ///
+    /// If `x` is constant, the inverse is computed directly, with no constraints. If it is the
+    /// constant zero, this doesn't panic; instead, for consistency with non-constant zeros, it adds
+    /// a constraint which can never be satisfied, and returns zero.
     pub fn inverse(&mut self, x: &Expression<F>) -> Expression<F> {
-        let x_inv = self.wire();
-        self.assert_product(x, &Expression::from(x_inv), &Expression::one());
-
-        let x = x.clone();
-        self.generator(
-            x.dependencies(),
-            move |values: &mut WireValues<F>| {
-                let x_value = x.evaluate(values);
-                let inverse_value = x_value.multiplicative_inverse();
-                values.set(x_inv, inverse_value);
-            },
-        );
+        logged!(self, "inverse", [x], {
+            if let Some(c) = x.as_constant() {
+                if c.is_zero() {
+                    self.assert_unsatisfiable();
+                    return Expression::zero();
+                }
+                return Expression::from(c.multiplicative_inverse());
+            }
 
-        x_inv.into()
+            let x_inv = {
+                let x = x.clone();
+                self.hint(
+                    x.dependencies(), 1,
+                    move |values: &WireValues<F>| vec![x.evaluate(values).multiplicative_inverse()],
+                ).wire(0)
+            };
+            self.assert_product(x, &Expression::from(x_inv), &Expression::one());
+            x_inv.into()
+        })
     }
 
     /// Like `inverse`, except that zero is mapped to itself rather than being prohibited.
     pub fn inverse_or_zero(&mut self, x: &Expression<F>) -> Expression<F> {
-        let x_inv_or_zero = self.wire();
-        let nonzero = self.nonzero(x);
-        self.assert_product(x, &Expression::from(x_inv_or_zero), nonzero.expression());
+        logged!(self, "inverse_or_zero", [x], {
+            if let Some(c) = x.as_constant() {
+                return Expression::from(c.multiplicative_inverse_or_zero());
+            }
+            self.nonzero_with_inverse(x).1
+        })
+    }
 
-        let x = x.clone();
-        self.generator(
-            x.dependencies(),
-            move |values: &mut WireValues<F>| {
-                let x_value = x.evaluate(values);
-                values.set(x_inv_or_zero, x_value.multiplicative_inverse_or_zero());
-            },
-        );
+    /// Returns `x / y`, and requires `y` to be non-zero: this adds the constraint `y != 0` to the
+    /// gadget, so no witness with `y = 0` satisfies it. Moreover, since the inverse of `y` is
+    /// computed by a witness generator, executing the gadget with `y = 0` panics. Use
+    /// `quotient_or_zero` or `checked_quotient` if `y` may legitimately be zero.
+    ///
+    /// If `y` is constant, `x`'s coefficients are divided directly, with no constraints. As with
+    /// `inverse`, a constant zero `y` results in an unsatisfiable gadget rather than a panic.
+    pub fn quotient(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
+        logged!(self, "quotient", [x, y], {
+            let y_inv = self.inverse(y);
+            self.product(x, &y_inv)
+        })
+    }
 
-        x_inv_or_zero.into()
+    /// Returns `x / y`, or zero if `y` is zero. Unlike `quotient`, this places no restriction on
+    /// `y`. It costs four constraints, or none if `y` is constant.
+    pub fn quotient_or_zero(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
+        logged!(self, "quotient_or_zero", [x, y], {
+            self.checked_quotient(x, y).0
+        })
     }
 
-    /// Returns `x / y`, assuming `y` is non-zero. If `y` is zero, the gadget will not be
-    /// satisfiable.
-    pub fn quotient(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
-        let y_inv = self.inverse(y);
-        self.product(x, &y_inv)
+    /// Returns `x / y` along with a flag indicating whether it is defined, i.e. whether `y` is
+    /// non-zero. If it isn't, the quotient is zero. This costs the same as `quotient_or_zero`.
+    pub fn checked_quotient(
+        &mut self, x: &Expression<F>, y: &Expression<F>,
+    ) -> (Expression<F>, BooleanExpression<F>) {
+        logged!(self, "checked_quotient", [x, y], {
+            if let Some(c) = y.as_constant() {
+                let defined = if c.is_nonzero() {
+                    BooleanExpression::_true()
+                } else {
+                    BooleanExpression::_false()
+                };
+                return (x * c.multiplicative_inverse_or_zero(), defined);
+            }
+            let (defined, y_inv) = self.nonzero_with_inverse(y);
+            (self.product(x, &y_inv), defined)
+        })
     }
 
     /// Returns `x / y`, assuming `y` is non-zero. This is equivalent to `quotient` except that it
@@ -108,65 +238,274 @@ impl<F: Field> GadgetBuilder<F> {
     ///
     /// This method uses a single constraint, whereas `quotient` uses two, so this method may be
     /// preferable in cases where `0 / 0` cannot possibly arise.
+    ///
+    /// If `y` is constant, this is equivalent to `quotient`, which requires no constraints then.
     pub fn quotient_unsafe(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
-        let q = self.wire();
-        let x = x.clone();
-        let y = y.clone();
-        self.generator(
-            [x.dependencies(), y.dependencies()].concat(),
-            move |values: &mut WireValues<F>| {
-                let x_value = x.evaluate(values);
-                let y_value = y.evaluate(values);
-                assert!(y_value.is_nonzero(), "Division by zero");
-                let q_value = x_value / y_value;
-                values.set(q, q_value)
-            }
-        );
-        Expression::from(q)
+        logged!(self, "quotient_unsafe", [x, y], {
+            if y.as_constant().is_some() {
+                return self.quotient(x, y);
+            }
+
+            let q = self.wire();
+            let q_exp = Expression::from(q);
+            self.assert_product(y, &q_exp, x);
+
+            {
+                let x = x.clone();
+                let y = y.clone();
+                self.generator_with_outputs(
+                    [x.dependencies(), y.dependencies()].concat(),
+                    vec![q],
+                    move |values: &mut WireValues<F>| {
+                        let x_value = x.evaluate(values);
+                        let y_value = y.evaluate(values);
+                        assert!(y_value.is_nonzero(), "Division by zero");
+                        let q_value = x_value / y_value;
+                        values.set(q, q_value)
+                    }
+                );
+            }
+
+            q_exp
+        })
     }
 
     /// Returns `x mod y`, assuming `y` is non-zero. If `y` is zero, the gadget will not be
-    /// satisfiable.
+    /// satisfiable. If `x` and `y` are both constant, this requires no constraints. See `div_rem`,
+    /// including for the bound on `x`.
     pub fn modulus(&mut self, x: &Expression<F>, y: &Expression<F>) -> Expression<F> {
-        // We will non-deterministically compute a quotient q and remainder r such that:
-        //     y * q = x - r
-        //     r < y
-
-        let q = self.wire();
-        let r = self.wire();
-        self.assert_product(y, &Expression::from(q), &(x - Expression::from(r)));
-        self.assert_lt(&Expression::from(r), y);
-
-        {
-            let x = x.clone();
-            let y = y.clone();
-            self.generator(
-                concat(&[x.dependencies(), y.dependencies()]),
-                move |values: &mut WireValues<F>| {
-                    let x_value = x.evaluate(values);
-                    let y_value = y.evaluate(values);
-                    values.set(q, x_value.integer_division(&y_value));
-                    values.set(r, x_value.integer_modulus(&y_value));
-                },
-            );
-        }
+        logged!(self, "modulus", [x, y], {
+            self.div_rem(x, y).1
+        })
+    }
 
-        r.into()
+    /// Returns the quotient and remainder `(q, r)` of the integer division of `x` by `y`, so that
+    /// `x = q * y + r` and `r < y`. If `y` is zero, the gadget will not be satisfiable. If `x` and
+    /// `y` are both constant, this requires no constraints.
+    ///
+    /// This assumes that `x` fits in `max_bits - 2` bits, so that `q` does too. If it doesn't, the
+    /// gadget may be unsatisfiable, but it never yields a wrong quotient or remainder. If a tighter
+    /// bound on `x` is known (see `GadgetBuilder::mark_bounded`), `q` is range checked to it.
+    pub fn div_rem(
+        &mut self, x: &Expression<F>, y: &Expression<F>,
+    ) -> (Expression<F>, Expression<F>) {
+        logged!(self, "div_rem", [x, y], {
+            let max_q_bits = Element::<F>::max_bits() - 2;
+            let q_bits = self.known_bound(x).map_or(max_q_bits, |x_bits| x_bits.min(max_q_bits));
+            self.div_rem_bounded(x, y, q_bits)
+        })
+    }
+
+    /// Like `div_rem`, but assumes that the quotient fits in `q_bits` bits, which makes the range
+    /// check on `q` cheaper. If it doesn't fit, the gadget is unsatisfiable.
+    ///
+    /// Panics if `q_bits` is not less than `Element::<F>::max_bits()`.
+    ///
+    /// # Soundness
+    ///
+    /// The quotient and remainder are computed non-deterministically, and checked with
+    ///     y * q = x - r
+    ///     r < y
+    /// On their own, these don't determine `q`, since `y * q` is computed mod `|F|`: for any
+    /// `r < y`, `q = (x - r) / y` satisfies both. Bounding `q` by `x` doesn't suffice either. For
+    /// example, with `y = (|F| + 3) / 2` and `x = y`, the pair `q = 2, r = y - 3` is accepted,
+    /// since `2 * y = |F| + 3`.
+    ///
+    /// The equation holds over the integers if `q * y + r < |F|`, in which case `(q, r)` is the
+    /// unique pair satisfying it, so we constrain `q` and `y` to have at most `max_bits - 1` bits
+    /// between them. With `q < 2^a` and `y < 2^b` for `a + b = max_bits - 1`,
+    ///     q * y + r < (q + 1) * y < 2^(max_bits - 1) <= |F|.
+    /// An honest `q * y` is at most `x`, which fits in `max_bits - 2` bits, so their bit lengths
+    /// sum to at most `max_bits - 1`, and honest witnesses satisfy this constraint.
+    ///
+    /// When `y` is a constant with `b` bits, this is just a range check of `q` to
+    /// `max_bits - 1 - b` bits. Otherwise `y` is split, and for each bit `q_j` of `q`, we assert
+    /// that `q_j = 1` implies `y < 2^(max_bits - 2 - j)`, which costs two constraints per bit of
+    /// `q`.
+    pub fn div_rem_bounded(
+        &mut self, x: &Expression<F>, y: &Expression<F>, q_bits: usize,
+    ) -> (Expression<F>, Expression<F>) {
+        let max_bits = Element::<F>::max_bits();
+        assert!(q_bits < max_bits,
+                "div_rem_bounded: q_bits must be less than {}, but got {}", max_bits, q_bits);
+
+        logged!(self, "div_rem_bounded", [x, y, q_bits], {
+            if let (Some(x_value), Some(y_value)) = (x.as_constant(), y.as_constant()) {
+                if y_value.is_zero() {
+                    self.assert_unsatisfiable();
+                    return (Expression::zero(), Expression::zero());
+                }
+                return (Expression::from(x_value.integer_division(&y_value)),
+                        Expression::from(x_value.integer_modulus(&y_value)));
+            }
+
+            let (q, r) = {
+                let x = x.clone();
+                let y = y.clone();
+                let hint = self.hint(
+                    concat(&[x.dependencies(), y.dependencies()]), 2,
+                    move |values: &WireValues<F>| {
+                        let x_value = x.evaluate(values);
+                        let y_value = y.evaluate(values);
+                        vec![x_value.integer_division(&y_value), x_value.integer_modulus(&y_value)]
+                    },
+                );
+                (Expression::from(hint.wire(0)), Expression::from(hint.wire(1)))
+            };
+            self.assert_product(y, &q, &(x - &r));
+
+            match y.as_constant() {
+                // With a constant modulus, r only needs as many bits as y. Once r is range checked
+                // to that width, a bounded comparison suffices.
+                Some(y_value) if y_value.bits() + 1 < max_bits => {
+                    let y_bits = y_value.bits();
+                    self.split_bounded(&q, q_bits.min(max_bits - 1 - y_bits));
+                    self.split_bounded(&r, y_bits);
+                    self.assert_lt_bounded(&r, y, y_bits);
+                }
+                _ => {
+                    let y_bin = self.split(y);
+                    let q_bin = self.split_bounded(&q, q_bits);
+                    let r_bin = self.split_allowing_ambiguity(&r);
+                    self.assert_lt_binary(&r_bin, &y_bin);
+
+                    // y_at_least[i] is true iff y >= 2^i, i.e. any bit of y at index i or above is
+                    // set.
+                    let lowest = (max_bits - 1).saturating_sub(q_bits);
+                    let mut y_at_least = vec![BooleanExpression::_false(); max_bits + 1];
+                    for i in (lowest..max_bits).rev() {
+                        y_at_least[i] = self.or(&y_at_least[i + 1], &y_bin.bits[i]);
+                    }
+                    for (j, q_bit) in q_bin.bits.iter().enumerate() {
+                        if q_bit.expression().as_constant().is_none() {
+                            self.assert_product(q_bit.expression(),
+                                                y_at_least[max_bits - 2 - j].expression(),
+                                                &Expression::zero());
+                        }
+                    }
+                }
+            }
+
+            (q, r)
+        })
+    }
+
+    /// Adds the constraint `0 * 0 = 1`, which can never be satisfied. This is used when an
+    /// operation's inputs are constants which are known to be invalid, such as an inverse of zero,
+    /// so that the failure surfaces when the gadget is executed, as it would for non-constant
+    /// inputs, rather than as a panic while building.
+    fn assert_unsatisfiable(&mut self) {
+        self.assert_product(&Expression::zero(), &Expression::zero(), &Expression::one());
     }
 
     /// Returns whether `x` divides `y`, i.e. `x | y`.
     pub fn divides(&mut self, x: &Expression<F>, y: &Expression<F>) -> BooleanExpression<F> {
-        let m = self.modulus(y, x);
-        self.zero(&m)
+        logged!(self, "divides", [x, y], {
+            let m = self.modulus(y, x);
+            self.zero(&m)
+        })
+    }
+
+    /// Returns `x mod m` for a small constant `m`, assuming that `x` fits in `x_bits` bits. If a
+    /// tighter bound on `x` is known (see `GadgetBuilder::mark_bounded`), it is used instead.
+    ///
+    /// This is cheaper than `modulus`, since only the quotient and remainder are split, each into
+    /// as few bits as the bounds allow. If `m = 2`, `x` is split and its least significant bit is
+    /// returned. The bound on `x` is not trusted for soundness: if `x` does not fit, the gadget is
+    /// unsatisfiable rather than producing a wrong remainder.
+    pub fn modulus_constant(&mut self, x: &Expression<F>, m: u64, x_bits: usize) -> Expression<F> {
+        logged!(self, "modulus_constant", [x, m, x_bits], {
+            assert!(m > 0, "Modulus must be non-zero");
+            if let Some(x_value) = x.as_constant() {
+                return Expression::from(x_value.integer_modulus(&Element::from(m)));
+            }
+            if m == 1 {
+                return Expression::zero();
+            }
+
+            if m == 2 {
+                return self.parity(x, x_bits).expression().clone();
+            }
+            let x_bits = self.known_bound(x).map_or(x_bits, |known_bits| known_bits.min(x_bits));
+
+            // We will non-deterministically compute a quotient q and remainder r such that:
+            //     x = q * m + r
+            //     q < 2^q_bits, where q_bits is enough for any x < 2^x_bits
+            //     r < m
+            // Since q * m + r < 2^q_bits * m <= |F|, the first equation holds over the integers, so
+            // r is the true remainder.
+            let m_biguint = BigUint::from(m);
+            let q_max = ((BigUint::one() << x_bits) - BigUint::one()) / &m_biguint;
+            let q_bits = q_max.bits() as usize;
+            let r_bits = (64 - (m - 1).leading_zeros()) as usize;
+            assert!((BigUint::one() << q_bits) * &m_biguint <= F::order(),
+                    "x_bits is too large for the quotient and remainder to fit in a field element");
+
+            let (q, r) = {
+                let x = x.clone();
+                let m = Element::from(m);
+                let hint = self.hint(
+                    x.dependencies(), 2,
+                    move |values: &WireValues<F>| {
+                        let x_value = x.evaluate(values);
+                        vec![x_value.integer_division(&m), x_value.integer_modulus(&m)]
+                    },
+                );
+                (Expression::from(hint.wire(0)), Expression::from(hint.wire(1)))
+            };
+            self.assert_equal(x, &(&q * Element::from(m) + &r));
+            self.split_bounded(&q, q_bits);
+            self.split_bounded(&r, r_bits);
+            if !m.is_power_of_two() {
+                self.assert_lt_bounded(&r, &Expression::from(m), r_bits);
+            }
+            r
+        })
+    }
+
+    /// Returns whether the small constant `m` divides `x`, assuming that `x` fits in `x_bits` bits.
+    /// See `modulus_constant`.
+    pub fn divides_constant(
+        &mut self, x: &Expression<F>, m: u64, x_bits: usize,
+    ) -> BooleanExpression<F> {
+        logged!(self, "divides_constant", [x, m, x_bits], {
+            if m == 2 && x.as_constant().is_none() {
+                return self.parity(x, x_bits).negated();
+            }
+            let r = self.modulus_constant(x, m, x_bits);
+            self.zero(&r)
+        })
+    }
+
+    /// Returns the least significant bit of `x`, assuming that `x` fits in `x_bits` bits.
+    fn parity(&mut self, x: &Expression<F>, x_bits: usize) -> BooleanExpression<F> {
+        // split_bounded uses a tighter known bound, if any, and pads the result.
+        self.split_bounded(x, x_bits.max(1)).bits[0].clone()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::expression::Expression;
-    use crate::field::Element;
+    use std::convert::Infallible;
+    use std::fmt::Debug;
+
+    use num::BigUint;
+    use num_traits::One;
+    #[cfg(feature = "rand")]
+    use num_traits::Zero;
+
+    use crate::Bn128;
+    use crate::expression::{BinaryExpression, Expression};
+    use crate::field::{Element, Field};
+    use crate::gadget::Gadget;
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::{assert_eq_false, assert_eq_true, F257};
+    use crate::lcg::LCG;
+    use crate::fields::small::F257;
+    #[cfg(feature = "rand")]
+    use crate::test_util::deterministic_elements;
+    use crate::test_util::{assert_eq_false, assert_eq_true};
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
 
     #[test]
     fn exp() {
@@ -186,6 +525,127 @@ mod tests {
         assert_eq!(Element::from(27u8), x_exp_3.evaluate(&values));
     }
 
+    /// Builds `base^e` for a 16-bit exponent `e`, with `constant_base_exponentiation`, and checks
+    /// it against `Element::pow_biguint` for random exponents and for zero. Returns the number of
+    /// constraints, excluding those on the exponent bits.
+    fn check_constant_base_exponentiation<F: Field + Debug>(base: Element<F>) -> usize {
+        let mut builder = GadgetBuilder::<F>::new();
+        let exponent = builder.binary_wire(16);
+        let power = builder.constant_base_exponentiation(
+            &base, &BinaryExpression::from(&exponent));
+        let gadget = builder.build();
+
+        let mut lcg = LCG::new();
+        let exponents = (0..10).map(|_i| lcg.next_u32() & 0xffff).chain([0, 0xffff]);
+        for e in exponents {
+            let mut values = binary_unsigned_values!(exponent => BigUint::from(e));
+            assert!(gadget.execute(&mut values));
+            assert_eq!(base.pow_biguint(&BigUint::from(e)), power.evaluate(&values));
+        }
+        gadget.size() - 16
+    }
+
+    #[test]
+    fn constant_base_exponentiation() {
+        // Each bit after the first costs one product, except that 3 has order 2^8 in F257, so
+        // 3^(2^i) = 1 for i >= 8, and those bits are free.
+        assert_eq!(7, check_constant_base_exponentiation(Element::<F257>::from(3u8)));
+        assert_eq!(15, check_constant_base_exponentiation(Element::<Bn128>::from(5u8)));
+        assert_eq!(15, check_constant_base_exponentiation(LCG::new().next_element::<Bn128>()));
+    }
+
+    #[test]
+    fn constant_base_exponentiation_constant_exponent() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let base = Element::from(3u8);
+        let zero = builder.constant_base_exponentiation(&base, &BinaryExpression::from(0u8));
+        let empty = builder.constant_base_exponentiation(&base, &BinaryExpression { bits: vec![] });
+        let power = builder.constant_base_exponentiation(&base, &BinaryExpression::from(200u8));
+        assert_eq!(0, builder.build().size());
+        assert_eq!(Some(Element::one()), zero.as_constant());
+        assert_eq!(Some(Element::one()), empty.as_constant());
+        assert_eq!(Some(base.exponentiation(&Element::from(200u8))), power.as_constant());
+    }
+
+    #[test]
+    fn exponentiation_variable() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let exponent = builder.binary_wire(16);
+        let power = builder.exponentiation_variable(
+            &Expression::from(x), &BinaryExpression::from(&exponent));
+        let gadget = builder.build();
+
+        let mut lcg = LCG::new();
+        for _i in 0..10 {
+            let (x_value, e) = (lcg.next_element::<F257>(), lcg.next_u32() & 0xffff);
+            let mut values = values!(x => x_value.clone());
+            values.set_binary_unsigned(&exponent, &BigUint::from(e));
+            assert!(gadget.execute(&mut values));
+            assert_eq!(x_value.pow_biguint(&BigUint::from(e)), power.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn constant_base_exponentiation_savings() {
+        let size = |constant_base: bool| {
+            let mut builder = GadgetBuilder::<Bn128>::new();
+            let exponent = BinaryExpression::from(&builder.binary_wire(16));
+            if constant_base {
+                builder.constant_base_exponentiation(&Element::from(5u8), &exponent);
+            } else {
+                let x = Expression::from(builder.wire());
+                builder.exponentiation_variable(&x, &exponent);
+            }
+            builder.build().size() - 16
+        };
+        // The variable base also needs 15 squarings and 16 selections.
+        assert_eq!(15, size(true));
+        assert_eq!(15 + 15 + 16, size(false));
+    }
+
+    #[test]
+    fn grand_product() {
+        let mut lcg = LCG::new();
+        for n in 1..8 {
+            let mut builder = GadgetBuilder::<F257>::new();
+            let (wires, terms) = builder.expression_wires(n);
+            let start = builder.wire();
+            let partials = builder.grand_product(&terms);
+            let with_start = builder.grand_product_from(&Expression::from(start), &terms);
+            let gadget = builder.build();
+            assert_eq!(n - 1 + n, gadget.size());
+
+            let mut values = values!(start => 3u8.into());
+            let elements: Vec<Element<F257>> = (0..n).map(|_i| lcg.next_element()).collect();
+            values.set_each(&wires, &elements);
+            assert!(gadget.execute(&mut values));
+
+            let mut expected = Element::one();
+            for i in 0..n {
+                expected *= &elements[i];
+                assert_eq!(expected, partials[i].evaluate(&values));
+                assert_eq!(&expected * Element::from(3u8), with_start[i].evaluate(&values));
+            }
+        }
+    }
+
+    #[test]
+    fn grand_product_edge_cases() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let x_exp = Expression::from(x);
+        let terms = [x_exp.clone()];
+        let start = Expression::from(5u8);
+        assert!(builder.grand_product(&[]).is_empty());
+        assert_eq!(Expression::one(), builder.grand_product_final(&[]));
+        assert_eq!(vec![x_exp.clone()], builder.grand_product(&terms));
+        assert_eq!(x_exp, builder.grand_product_final(&terms));
+        assert_eq!(vec![&x_exp * Element::from(5u8)],
+                   builder.grand_product_from(&start, &terms));
+        assert_eq!(0, builder.build().size());
+    }
+
     #[test]
     #[should_panic]
     fn invert_zero() {
@@ -198,6 +658,346 @@ mod tests {
         gadget.execute(&mut values);
     }
 
+    #[test]
+    fn constant_folding() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b) = (Element::<F257>::from(100u8), Element::<F257>::from(7u8));
+        let (a_exp, b_exp) = (Expression::from(&a), Expression::from(&b));
+        let exp = builder.exponentiation(&a_exp, &Element::from(200u8));
+        let inv = builder.inverse(&a_exp);
+        let inv_or_zero = builder.inverse_or_zero(&a_exp);
+        let zero_inv_or_zero = builder.inverse_or_zero(&Expression::zero());
+        let quot = builder.quotient(&a_exp, &b_exp);
+        let quot_unsafe = builder.quotient_unsafe(&a_exp, &b_exp);
+        let modulus = builder.modulus(&a_exp, &b_exp);
+        let gadget = builder.build();
+        assert_eq!(0, gadget.size());
+        assert!(gadget.witness_generators.is_empty());
+
+        assert_eq!(Some(a.exponentiation(&Element::from(200u8))), exp.as_constant());
+        assert_eq!(Some(a.multiplicative_inverse()), inv.as_constant());
+        assert_eq!(inv.as_constant(), inv_or_zero.as_constant());
+        assert_eq!(Some(Element::zero()), zero_inv_or_zero.as_constant());
+        assert_eq!(Some(&a / &b), quot.as_constant());
+        assert_eq!(quot.as_constant(), quot_unsafe.as_constant());
+        assert_eq!(Some(Element::from(2u8)), modulus.as_constant());
+    }
+
+    #[test]
+    fn quotient_unsafe() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let quot = builder.quotient_unsafe(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+        assert_eq!(1, gadget.size());
+
+        let mut values = values!(x => 12u8.into(), y => 4u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(3u8), quot.evaluate(&values));
+
+        // The quotient must actually be constrained.
+        let constraint = &gadget.constraints[0];
+        assert_eq!(Expression::from(y), constraint.a);
+        assert_eq!(quot, constraint.b);
+        assert_eq!(Expression::from(x), constraint.c);
+    }
+
+    #[test]
+    #[should_panic(expected = "Zero does not have a multiplicative inverse")]
+    fn quotient_zero_divisor_panics() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.quotient(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+
+        let mut values = values!(x => 12u8.into(), y => 0u8.into());
+        gadget.execute(&mut values);
+    }
+
+    #[test]
+    fn quotient_semantics() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let quotient = builder.quotient(&x_exp, &y_exp);
+        let gadget = builder.build();
+        let mut values = values!(x => 12u8.into(), y => 4u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(3u8), quotient.evaluate(&values));
+
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let or_zero = builder.quotient_or_zero(&x_exp, &y_exp);
+        let (checked, defined) = builder.checked_quotient(&x_exp, &y_exp);
+        let gadget = builder.build();
+        assert_eq!(8, gadget.size());
+
+        let mut values = values!(x => 12u8.into(), y => 4u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(3u8), or_zero.evaluate(&values));
+        assert_eq!(Element::from(3u8), checked.evaluate(&values));
+        assert!(defined.evaluate(&values));
+
+        let mut values = values!(x => 12u8.into(), y => 0u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::zero(), or_zero.evaluate(&values));
+        assert_eq!(Element::zero(), checked.evaluate(&values));
+        assert!(!defined.evaluate(&values));
+    }
+
+    #[test]
+    fn checked_quotient_constant_divisor() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = Expression::from(builder.wire());
+        let (by_four, four_defined) = builder.checked_quotient(&x, &Expression::from(4u8));
+        let (by_zero, zero_defined) = builder.checked_quotient(&x, &Expression::zero());
+        assert_eq!(0, builder.build().size());
+        assert_eq!(Some(Element::one()), four_defined.expression().as_constant());
+        assert_eq!(Some(Element::zero()), zero_defined.expression().as_constant());
+        assert_eq!(&x / Element::from(4u8), by_four);
+        assert_eq!(Expression::zero(), by_zero);
+    }
+
+    #[test]
+    fn constant_divisor() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let dividend = Expression::from(x) * Element::from(3u8) + Expression::from(6u8);
+        let quot = builder.quotient(&dividend, &Expression::from(3u8));
+        let gadget = builder.build();
+        assert_eq!(0, gadget.size());
+
+        let mut values = values!(x => 5u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(7u8), quot.evaluate(&values));
+    }
+
+    #[test]
+    fn constant_zero_inverse_unsatisfiable() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.inverse(&Expression::zero());
+        let gadget = builder.build();
+        assert!(!gadget.execute(&mut WireValues::new()));
+
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.quotient(&Expression::one(), &Expression::zero());
+        builder.modulus(&Expression::one(), &Expression::zero());
+        let gadget = builder.build();
+        assert_eq!(2, gadget.size());
+        assert!(!gadget.execute(&mut WireValues::new()));
+    }
+
+    #[test]
+    fn modulus_constant_divisor() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        let r = builder.modulus(&Expression::from(x), &Expression::from(1000u32));
+        let gadget = builder.build();
+        // One constraint for the quotient, a 243-bit split of q, which leaves room for the 10 bits
+        // of the modulus, and a 10-bit split for each of r and 999 - r.
+        assert_eq!(267, gadget.size());
+
+        for &x_value in &[0u64, 999, 1000, 123_456_789, u64::MAX] {
+            let mut values = values!(x => Element::from(x_value));
+            assert!(gadget.execute(&mut values));
+            assert_eq!(Element::from(x_value % 1000), r.evaluate(&values));
+        }
+    }
+
+    /// Executes `gadget` with `values`, except that generators whose outputs are all already set
+    /// are skipped. This lets a test supply a forged quotient and remainder, while the remaining
+    /// wires, such as their bits, are derived from them.
+    fn execute_forged(gadget: &Gadget<Bn128>, values: &mut WireValues<Bn128>) -> bool {
+        gadget.execute_with(values, |_index, generator, values| {
+            let outputs = generator.outputs();
+            if outputs.is_empty() || !values.contains_all(outputs) {
+                generator.generate(values);
+            }
+            Ok::<(), Infallible>(())
+        }).unwrap()
+    }
+
+    /// Builds `div_rem` for a variable `x` and `y`, and checks whether the forged quotient and
+    /// remainder are accepted.
+    fn accepts_forged_div_rem(
+        x_value: &Element<Bn128>, y_value: &Element<Bn128>, q_value: &Element<Bn128>,
+        r_value: &Element<Bn128>,
+    ) -> bool {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (q, r) = builder.div_rem(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+
+        let mut values = values!(x => x_value.clone(), y => y_value.clone());
+        for (exp, value) in &[(q, q_value), (r, r_value)] {
+            values.set(exp.dependencies()[0], (*value).clone());
+        }
+        execute_forged(&gadget, &mut values)
+    }
+
+    #[test]
+    fn div_rem() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (q, r) = builder.div_rem(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+
+        let max = (BigUint::one() << (Element::<Bn128>::max_bits() - 2)) - BigUint::one();
+        let big = BigUint::from(0x9e3779b97f4a7c15u64);
+        let big = &big * &big * &big;
+        let cases = [(0u8.into(), 7u8.into()), (6u8.into(), 7u8.into()), (7u8.into(), 7u8.into()),
+                     (big.clone(), 12345u16.into()), (big.clone(), big.clone() - 1u8),
+                     (max.clone(), 3u8.into()), (max.clone(), 1u8.into()),
+                     (max.clone(), max.clone()), (5u8.into(), max.clone() + 1u8),
+                     (123u8.into(), Element::<Bn128>::largest_element().to_biguint().clone())];
+        for (x_value, y_value) in cases.iter() {
+            let mut values = values!(x => Element::from(x_value.clone()),
+                                     y => Element::from(y_value.clone()));
+            assert!(gadget.execute(&mut values));
+            assert_eq!(Element::from(x_value / y_value), q.evaluate(&values));
+            assert_eq!(Element::from(x_value % y_value), r.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn div_rem_rejects_wraparound() {
+        // 2 * y = |F| + 3, so x = y would be 2 * y + (y - 3) if products were allowed to wrap.
+        // This x exceeds the bound which div_rem assumes, but that mustn't yield a wrong result.
+        let order = Bn128::order();
+        let y = Element::from((&order + 3u8) / 2u8);
+        assert!(!accepts_forged_div_rem(&y, &y, &Element::from(2u8), &(&y - Element::from(3u8))));
+
+        // 2 * y = |F| + 1, so x = 10 would be 2 * y + 9, although y > x.
+        let y = Element::from((&order + 1u8) / 2u8);
+        let x = Element::from(10u8);
+        assert!(accepts_forged_div_rem(&x, &y, &Element::zero(), &x));
+        assert!(!accepts_forged_div_rem(&x, &y, &Element::from(2u8), &Element::from(9u8)));
+    }
+
+    #[test]
+    fn div_rem_constant_rejects_wraparound() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        let (q, r) = builder.div_rem(&Expression::from(x), &Expression::from(1000u32));
+        let gadget = builder.build();
+
+        // Claim that 5 mod 1000 is 6, with q = -1 / 1000 so that 1000 * q = 5 - 6.
+        let q_value = -Element::one() / Element::from(1000u32);
+        let mut values = values!(x => 5u8.into());
+        values.set(q.dependencies()[0], q_value);
+        values.set(r.dependencies()[0], 6u8.into());
+        assert!(!execute_forged(&gadget, &mut values));
+    }
+
+    #[test]
+    fn div_rem_bounded() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (q, r) = builder.div_rem_bounded(&Expression::from(x), &Expression::from(y), 8);
+        let gadget = builder.build();
+
+        let mut values = values!(x => 1000u16.into(), y => 7u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(142u8), q.evaluate(&values));
+        assert_eq!(Element::from(6u8), r.evaluate(&values));
+
+        // 1000 / 3 doesn't fit in 8 bits.
+        assert!(!gadget.execute(&mut values!(x => 1000u16.into(), y => 3u8.into())));
+    }
+
+    #[test]
+    #[should_panic(expected = "q_bits must be less than")]
+    fn div_rem_bounded_q_bits_too_large() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let q_bits = Element::<Bn128>::max_bits();
+        builder.div_rem_bounded(&Expression::from(x), &Expression::from(y), q_bits);
+    }
+
+    /// Checks `modulus_constant` against `BigUint` arithmetic, for pseudorandom 40-bit inputs.
+    #[cfg(feature = "rand")]
+    fn check_modulus_constant(m: u64) {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        let r = builder.modulus_constant(&Expression::from(x), m, 40);
+        let divides = builder.divides_constant(&Expression::from(x), m, 40);
+        let gadget = builder.build();
+
+        let mask = (BigUint::one() << 40u32) - BigUint::one();
+        let mut x_values: Vec<BigUint> = deterministic_elements::<Bn128>(10).iter()
+            .map(|e| e.to_biguint() & &mask)
+            .collect();
+        x_values.extend(vec![BigUint::zero(), BigUint::from(m), mask]);
+        for x_value in x_values {
+            let mut values = values!(x => Element::from(x_value.clone()));
+            assert!(gadget.execute(&mut values));
+            let expected = &x_value % m;
+            assert_eq!(Element::from(expected.clone()), r.evaluate(&values));
+            assert_eq!(expected.is_zero(), divides.evaluate(&values));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn modulus_constant_3() {
+        check_modulus_constant(3);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn modulus_constant_10() {
+        check_modulus_constant(10);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn modulus_constant_parity() {
+        check_modulus_constant(2);
+
+        // Parity is just the least significant bit of a 40-bit split.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        builder.modulus_constant(&Expression::from(x), 2, 40);
+        assert_eq!(41, builder.build().size());
+    }
+
+    #[test]
+    fn modulus_constant_rejects_out_of_range_remainder() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        let r = builder.modulus_constant(&Expression::from(x), 3, 16);
+        let gadget = builder.build();
+
+        let mut values = values!(x => 1000u16.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(1u8), r.evaluate(&values));
+
+        // Claim that 1003 = 334 * 3 + 4, keeping the honest quotient. Wire::ONE is set by
+        // WireValues::new.
+        let mut forged = WireValues::new();
+        for (&wire, value) in values.as_map() {
+            if wire == x {
+                forged.set(wire, 1003u16.into());
+            } else if Expression::from(wire) == r {
+                forged.set(wire, 4u8.into());
+            } else if wire != Wire::ONE {
+                forged.set(wire, value.clone());
+            }
+        }
+        assert!(!gadget.constraints.iter().all(|c| c.evaluate(&forged)));
+    }
+
+    #[test]
+    fn modulus_constant_unsatisfiable_beyond_bound() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        builder.modulus_constant(&Expression::from(x), 3, 8);
+        let gadget = builder.build();
+        assert!(gadget.execute(&mut values!(x => 255u8.into())));
+        assert!(!gadget.execute(&mut values!(x => 1000u16.into())));
+    }
+
     #[test]
     fn divides() {
         let mut builder = GadgetBuilder::<F257>::new();
@@ -0,0 +1,4 @@
+//! This module groups fields by purpose. The crate's main fields, such as `Bn128`, are exported at
+//! the crate root.
+
+pub mod small;
@@ -0,0 +1,119 @@
+//! This module contains small prime fields, which are useful for unit testing gadgets. Since their
+//! elements can be enumerated, gadgets can be checked against every possible input. Its contents
+//! are also exported at the crate root.
+//!
+//! For example, a downstream test of the `lt` comparison gadget over `F13` might look like this:
+//!
+//! ```
+//! use r1cs::*;
+//! use r1cs::fields::small::F13;
+//!
+//! let mut builder = GadgetBuilder::<F13>::new();
+//! let (x, y) = (builder.wire(), builder.wire());
+//! let lt = builder.lt(&Expression::from(x), &Expression::from(y));
+//! let gadget = builder.build();
+//!
+//! for x_value in Element::<F13>::all_elements() {
+//!     for y_value in Element::<F13>::all_elements() {
+//!         let expected = x_value.to_biguint() < y_value.to_biguint();
+//!         let mut values = values!(x => x_value.clone(), y => y_value);
+//!         assert!(gadget.execute(&mut values));
+//!         assert_eq!(expected, lt.evaluate(&values));
+//!     }
+//! }
+//! ```
+
+use num::{BigUint, ToPrimitive};
+
+use crate::field::{Element, Field};
+
+/// The largest order of a `SmallField`, so that its elements can be enumerated in reasonable time.
+pub const MAX_SMALL_FIELD_ORDER: u64 = 1 << 20;
+
+/// A marker trait for fields small enough to enumerate, with order at most
+/// `MAX_SMALL_FIELD_ORDER`. This enables `Element::all_elements`, as well as exhaustive checks.
+pub trait SmallField: Field {}
+
+impl<F: SmallField> Element<F> {
+    /// Every element of the field, in ascending order.
+    pub fn all_elements() -> impl Iterator<Item = Element<F>> {
+        let order = F::order().to_u64()
+            .filter(|&order| order <= MAX_SMALL_FIELD_ORDER)
+            .expect("Field is too large to enumerate");
+        (0..order).map(Element::from)
+    }
+}
+
+/// The field of order 7.
+#[derive(Debug)]
+pub struct F7 {}
+
+impl Field for F7 {
+    fn order() -> BigUint {
+        BigUint::from(7u8)
+    }
+}
+
+impl SmallField for F7 {}
+
+/// The field of order 11.
+#[derive(Debug)]
+pub struct F11 {}
+
+impl Field for F11 {
+    fn order() -> BigUint {
+        BigUint::from(11u8)
+    }
+}
+
+impl SmallField for F11 {}
+
+/// The field of order 13.
+#[derive(Debug)]
+pub struct F13 {}
+
+impl Field for F13 {
+    fn order() -> BigUint {
+        BigUint::from(13u8)
+    }
+}
+
+impl SmallField for F13 {}
+
+/// The field of order 257, which is the smallest prime above a byte's range.
+#[derive(Debug)]
+pub struct F257 {}
+
+impl Field for F257 {
+    fn order() -> BigUint {
+        BigUint::from(257u16)
+    }
+}
+
+impl SmallField for F257 {}
+
+/// The field of order 65537, which is the smallest prime above a 16-bit range.
+#[derive(Debug)]
+pub struct F65537 {}
+
+impl Field for F65537 {
+    fn order() -> BigUint {
+        BigUint::from(65537u32)
+    }
+}
+
+impl SmallField for F65537 {}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Element;
+    use crate::fields::small::{F65537, F7};
+
+    #[test]
+    fn all_elements() {
+        let elements: Vec<Element<F7>> = Element::all_elements().collect();
+        let expected: Vec<Element<F7>> = (0u8..7).map(Element::from).collect();
+        assert_eq!(expected, elements);
+        assert_eq!(65537, Element::<F65537>::all_elements().count());
+    }
+}
@@ -1,10 +1,26 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "std"))]
+use alloc::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
+
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::fmt;
+use std::fmt::Formatter;
+use std::hash::{Hash, Hasher};
 
+use crate::build_log::LogEntry;
 use crate::constraint::Constraint;
-use crate::field::Field;
-use crate::wire_values::WireValues;
-use crate::witness_generator::WitnessGenerator;
+use crate::expression::Expression;
+use crate::field::{Element, Field};
+use crate::util::{FnvHasher, join};
+use crate::wire::{Wire, WireKind};
+use crate::wire_values::{TypedWireValues, WireValues};
+use crate::witness_generator::{Retry, WitnessGenerator};
 
 /// An R1CS gadget.
 pub struct Gadget<F: Field> {
@@ -12,6 +28,26 @@ pub struct Gadget<F: Field> {
     pub constraints: Vec<Constraint<F>>,
     /// The set of generators used to generate a complete witness from inputs.
     pub witness_generators: Vec<WitnessGenerator<F>>,
+    /// The kinds of wires allocated by typed builder methods such as
+    /// `GadgetBuilder::boolean_wire`, used by `typed_values` to check values as they're set.
+    pub wire_kinds: BTreeMap<Wire, WireKind>,
+    /// The log recorded by the builder, if `GadgetBuilder::enable_build_log` was called. Gadgets
+    /// derived from this one by transformations such as `propagate_constants` have no log, since
+    /// their constraints no longer match it.
+    pub build_log: Option<Vec<LogEntry>>,
+}
+
+impl<F: Field> Clone for Gadget<F> {
+    /// Clones the constraints. Witness generators are reference counted, so they are shared rather
+    /// than copied.
+    fn clone(&self) -> Self {
+        Gadget {
+            constraints: self.constraints.clone(),
+            witness_generators: self.witness_generators.clone(),
+            wire_kinds: self.wire_kinds.clone(),
+            build_log: self.build_log.clone(),
+        }
+    }
 }
 
 impl<F: Field> Gadget<F> {
@@ -20,9 +56,489 @@ impl<F: Field> Gadget<F> {
         self.constraints.len()
     }
 
-    /// Execute the gadget, and return whether all constraints were satisfied.
+    /// The constraints of this gadget, in the order they were added.
+    pub fn constraints(&self) -> &[Constraint<F>] {
+        &self.constraints
+    }
+
+    /// Consumes this gadget, returning its constraints and witness generators. Wire kinds and the
+    /// build log are discarded.
+    pub fn into_parts(self) -> (Vec<Constraint<F>>, Vec<WitnessGenerator<F>>) {
+        (self.constraints, self.witness_generators)
+    }
+
+    /// A copy of this gadget without witness generators, for verifiers which only check complete
+    /// assignments, e.g. with `check`. Executing it requires a value for every wire.
+    pub fn clone_constraints_only(&self) -> Gadget<F> {
+        Gadget {
+            constraints: self.constraints.clone(),
+            witness_generators: Vec::new(),
+            wire_kinds: self.wire_kinds.clone(),
+            build_log: self.build_log.clone(),
+        }
+    }
+
+    /// Whether every constraint is satisfied by the given values, without running any generators.
+    /// Panics if a wire in some constraint has no value.
+    pub fn check(&self, wire_values: &WireValues<F>) -> bool {
+        self.constraints.iter().all(|constraint| constraint.evaluate(wire_values))
+    }
+
+    /// The kind of the given wire, if it was allocated by a typed builder method such as
+    /// `GadgetBuilder::boolean_wire`.
+    pub fn wire_kind(&self, wire: Wire) -> Option<WireKind> {
+        self.wire_kinds.get(&wire).copied()
+    }
+
+    /// Returns an empty assignment which checks each value against the kind of its wire as it's
+    /// set, so that a mistake like setting a boolean wire to 2 is reported where it's made, rather
+    /// than as an unsatisfied constraint during `execute`.
+    pub fn typed_values(&self) -> TypedWireValues<'_, F> {
+        TypedWireValues::new(self)
+    }
+
+    /// All wires referenced by this gadget's constraints, excluding the constant `Wire::ONE`, in
+    /// ascending order of index. The order doesn't depend on the order of constraints, so exporters
+    /// can use this to assign variable IDs reproducibly, and to validate sets of public wires.
+    pub fn wires(&self) -> Vec<Wire> {
+        let mut wires = BTreeSet::new();
+        for constraint in &self.constraints {
+            for expression in &[&constraint.a, &constraint.b, &constraint.c] {
+                wires.extend(expression.dependencies());
+            }
+        }
+        wires.remove(&Wire::ONE);
+        wires.into_iter().collect()
+    }
+
+    /// One more than the largest wire index referenced by this gadget's constraints, i.e. the
+    /// first variable ID which is free if wire indices are used directly as variable IDs, as in
+    /// zkinterface's `free_variable_id`. Since helpers often allocate wires which never appear in
+    /// constraints, indices can be sparse, so this is generally not `wires().len() + 1`; see
+    /// `compact_wires` for a way to close such gaps.
+    pub fn free_variable_id(&self) -> u64 {
+        let max_index = self.wires().last().map_or(Wire::ONE.index, |wire| wire.index);
+        u64::from(max_index) + 1
+    }
+
+    /// Wires which some witness generator declares as an output, but which appear in no
+    /// constraint, in ascending order of index. A prover could assign such wires arbitrary values,
+    /// which is almost always a soundness bug. Only declared outputs are checked; see
+    /// `WitnessGenerator::with_outputs`.
+    pub fn unconstrained_hint_wires(&self) -> Vec<Wire> {
+        let counts = self.constraint_counts();
+        self.generated_wires().into_iter()
+            .filter(|wire| !counts.contains_key(wire))
+            .collect()
+    }
+
+    /// Wires which some witness generator declares as an output, and which appear in exactly one
+    /// constraint, in ascending order of index. This is a weaker smell than
+    /// `unconstrained_hint_wires`: a wire fully determined by a single constraint, such as the
+    /// output of `product`, is fine, but a wire which a single constraint only partially restricts
+    /// may leave the prover some freedom.
+    pub fn wires_with_single_constraint(&self) -> Vec<Wire> {
+        let counts = self.constraint_counts();
+        self.generated_wires().into_iter()
+            .filter(|wire| counts.get(wire) == Some(&1))
+            .collect()
+    }
+
+    /// The declared outputs of all witness generators.
+    fn generated_wires(&self) -> BTreeSet<Wire> {
+        self.witness_generators.iter()
+            .flat_map(|generator| generator.outputs().iter().cloned())
+            .collect()
+    }
+
+    /// Whether every witness generator declares its outputs, so that `generated_wires` includes
+    /// every wire which a generator might set.
+    fn declares_all_outputs(&self) -> bool {
+        self.witness_generators.iter().all(|generator| !generator.outputs().is_empty())
+    }
+
+    /// For each wire, the number of constraints which it appears in.
+    fn constraint_counts(&self) -> BTreeMap<Wire, usize> {
+        let mut counts = BTreeMap::new();
+        for constraint in &self.constraints {
+            let mut wires = BTreeSet::new();
+            for expression in &[&constraint.a, &constraint.b, &constraint.c] {
+                wires.extend(expression.dependencies());
+            }
+            for wire in wires {
+                *counts.entry(wire).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Renumbers the wires referenced by constraints densely, so that `Wire::ONE` keeps index 0
+    /// and the remaining wires take indices `1..=wires().len()`, in their original order. Returns
+    /// the compacted gadget along with a map from old wires to new ones, which can be used with
+    /// `WireValues::remap` to translate a witness of this gadget.
+    ///
+    /// Since the relative order of wires is preserved, `variable_order` of the compacted gadget,
+    /// given the remapped set of public wires, corresponds exactly to `variable_order` of this one.
+    ///
+    /// Witness generators can't be renumbered, so the compacted gadget has none. A complete witness
+    /// should be generated with this gadget, then remapped.
+    pub fn compact_wires(&self) -> (Gadget<F>, BTreeMap<Wire, Wire>) {
+        self.renumber_wires(self.wires())
+    }
+
+    /// Like `compact_wires`, but wires are numbered in order of their first appearance in the
+    /// constraints, scanning `a`, `b` and `c` of each constraint in turn. The result doesn't depend
+    /// on the order in which wires were allocated, only on the order and shape of constraints, so
+    /// two gadgets built the same way are identical after this renumbering.
+    ///
+    /// Within a single expression, terms are still scanned in order of wire index, so wires which
+    /// first appear together in one expression keep their relative order.
+    pub fn canonicalize_wires(&self) -> (Gadget<F>, BTreeMap<Wire, Wire>) {
+        let mut seen = BTreeSet::new();
+        seen.insert(Wire::ONE);
+        let mut order = Vec::new();
+        for constraint in &self.constraints {
+            for expression in &[&constraint.a, &constraint.b, &constraint.c] {
+                for wire in expression.dependencies() {
+                    if seen.insert(wire) {
+                        order.push(wire);
+                    }
+                }
+            }
+        }
+        self.renumber_wires(order)
+    }
+
+    /// Renumbers wires so that `Wire::ONE` keeps index 0, and the wires of `order`, which must
+    /// include every other wire referenced by constraints, take indices `1..=order.len()`.
+    fn renumber_wires(&self, order: Vec<Wire>) -> (Gadget<F>, BTreeMap<Wire, Wire>) {
+        let mut map = BTreeMap::new();
+        map.insert(Wire::ONE, Wire::ONE);
+        for (i, wire) in order.into_iter().enumerate() {
+            map.insert(wire, Wire { index: i as u32 + 1 });
+        }
+
+        let remap = |exp: &Expression<F>| Expression::new(
+            exp.iter().map(|(wire, coefficient)| (map[wire], coefficient.clone())).collect());
+        let constraints = self.constraints.iter()
+            .map(|constraint| Constraint {
+                a: remap(&constraint.a),
+                b: remap(&constraint.b),
+                c: remap(&constraint.c),
+            })
+            .collect();
+        let wire_kinds = self.wire_kinds.iter()
+            .filter_map(|(wire, &kind)| map.get(wire).map(|&new_wire| (new_wire, kind)))
+            .collect();
+        (Gadget { constraints, witness_generators: Vec::new(), wire_kinds, build_log: None }, map)
+    }
+
+    /// Finds wires which the constraints force to be constant, substitutes their values throughout,
+    /// and removes constraints which become trivially satisfied. Returns the folded gadget, along
+    /// with the value of each constant wire.
+    ///
+    /// A wire is found to be constant when, after substituting the constants found so far, some
+    /// constraint has a single unknown wire which appears linearly, such as `(w - 3) * 1 = 7` or
+    /// `2 * 5 = w + 1`. This repeats until no more constants are found, so chains of constraints
+    /// fold completely. Constraints which become constant but are violated are kept, so an
+    /// unsatisfiable gadget stays unsatisfiable.
+    ///
+    /// Witness generators are kept unchanged. They still set the constant wires, which the folded
+    /// constraints simply no longer reference.
+    pub fn propagate_constants(&self) -> (Gadget<F>, BTreeMap<Wire, Element<F>>) {
+        let mut assignments = BTreeMap::new();
+        loop {
+            let mut made_progress = false;
+            for constraint in &self.constraints {
+                let constraint = constraint.substitute(&assignments);
+                if let Some((wire, value)) = constraint.solve_single_unknown() {
+                    assignments.insert(wire, value);
+                    made_progress = true;
+                }
+            }
+            if !made_progress {
+                break;
+            }
+        }
+
+        let constraints = self.constraints.iter()
+            .map(|constraint| constraint.substitute(&assignments))
+            .filter(|constraint| match (constraint.a.as_constant(), constraint.b.as_constant(),
+                                        constraint.c.as_constant()) {
+                (Some(a), Some(b), Some(c)) => a * b != c,
+                _ => true,
+            })
+            .collect();
+        let folded = Gadget {
+            constraints,
+            witness_generators: self.witness_generators.clone(),
+            wire_kinds: self.wire_kinds.clone(),
+            build_log: None,
+        };
+        (folded, assignments)
+    }
+
+    /// Merges constraints which multiply the same pair of expressions, in either order and up to
+    /// constant factors. Returns the merged gadget, along with a report of what was merged.
+    ///
+    /// Two constraints `a * b = c_1` and `a * b = c_2` imply the linear equality `c_1 = c_2`, so
+    /// the later one is redundant once that equality is enforced. If `c_1` and `c_2` are
+    /// identical, the later constraint is simply dropped. Otherwise, the highest-indexed wire of
+    /// `c_2 - c_1`, which is typically the later product's output, is solved for and replaced
+    /// throughout by an expression in the remaining wires, and the later constraint is dropped.
+    /// Since substitutions can make further products identical, this repeats until nothing more
+    /// is merged. If `c_1` and `c_2` differ by a nonzero constant, no witness satisfies both, and
+    /// both are kept so that the gadget stays unsatisfiable.
+    ///
+    /// Every witness of this gadget satisfies the merged gadget. Conversely, a witness of the
+    /// merged gadget satisfies this one once each substituted wire is assigned the value of its
+    /// replacement. Witness generators are kept unchanged, so they still set substituted wires,
+    /// which the merged constraints simply no longer reference. Substituted wires no longer appear
+    /// in the R1CS instance, so callers should check that none of them are public.
+    pub fn merge_duplicate_products(&self) -> (Gadget<F>, MergeReport<F>) {
+        let mut report = MergeReport {
+            duplicates_removed: 0,
+            substituted_wires: BTreeMap::new(),
+            conflicts: 0,
+        };
+        let mut constraints = self.constraints.clone();
+        loop {
+            // The normalized output of the first constraint seen with each normalized pair of
+            // operands. These may mention wires substituted since, so they're substituted on use.
+            let mut outputs = BTreeMap::new();
+            let mut merged = Vec::new();
+            let mut made_progress = false;
+            report.conflicts = 0;
+            for constraint in &constraints {
+                let constraint = constraint.substitute_expressions(&report.substituted_wires);
+                let (operands, output) = normalize_product(&constraint);
+                let first_output = match outputs.get(&operands) {
+                    Some(first_output) =>
+                        Expression::substitute_expressions(first_output, &report.substituted_wires),
+                    None => {
+                        outputs.insert(operands, output);
+                        merged.push(constraint);
+                        continue;
+                    }
+                };
+
+                let difference = &output - &first_output;
+                match difference.as_constant() {
+                    Some(constant) if constant.is_zero() => {
+                        report.duplicates_removed += 1;
+                        made_progress = true;
+                    }
+                    Some(_constant) => {
+                        report.conflicts += 1;
+                        merged.push(constraint);
+                    }
+                    None => {
+                        report.substitute(&difference);
+                        made_progress = true;
+                    }
+                }
+            }
+
+            constraints = merged.iter()
+                .map(|constraint| constraint.substitute_expressions(&report.substituted_wires))
+                .collect();
+            if !made_progress {
+                break;
+            }
+        }
+
+        let merged = Gadget {
+            constraints,
+            witness_generators: self.witness_generators.clone(),
+            wire_kinds: self.wire_kinds.clone(),
+            build_log: None,
+        };
+        (merged, report)
+    }
+
+    /// A hash of this gadget's constraints after `canonicalize_wires`, ignoring the order of
+    /// constraints. Gadgets which are equivalent up to renumbering have equal fingerprints, so
+    /// differing fingerprints quickly rule out equivalence.
+    pub fn fingerprint(&self) -> u64 {
+        let (canonical, _map) = self.canonicalize_wires();
+        let mut constraints = canonical.constraints;
+        constraints.sort();
+        let mut hasher = FnvHasher::default();
+        constraints.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether `other` has the same constraints as this gadget, up to the order of constraints and
+    /// renumbering of wires as by `canonicalize_wires`. Witness generators are ignored.
+    ///
+    /// Like `diff`, this is a structural comparison, not a semantic one: gadgets which accept the
+    /// same witnesses, but encode it with different constraints, are not considered equivalent.
+    pub fn is_equivalent_up_to_renumbering(&self, other: &Gadget<F>) -> bool {
+        self.size() == other.size()
+            && self.fingerprint() == other.fingerprint()
+            && self.diff(other).is_empty()
+    }
+
+    /// Compares the constraints of this gadget with those of `other`, after renumbering the wires
+    /// of both with `canonicalize_wires`. This is useful when refactoring a gadget, to check that
+    /// its constraints are unchanged, or changed only in expected ways.
+    ///
+    /// This is not a semantic equivalence check. Constraints are compared syntactically, so an
+    /// equivalent gadget with differently shaped constraints, or with constraints which introduce
+    /// wires in a different order, is reported as changed.
+    pub fn diff(&self, other: &Gadget<F>) -> GadgetDiff<F> {
+        let mut ours = self.canonicalize_wires().0.constraints;
+        let mut theirs = other.canonicalize_wires().0.constraints;
+        ours.sort();
+        theirs.sort();
+
+        // Walk both sorted lists, matching up equal constraints.
+        let mut only_in_self = Vec::new();
+        let mut only_in_other = Vec::new();
+        let (mut ours, mut theirs) = (ours.into_iter().peekable(), theirs.into_iter().peekable());
+        loop {
+            let ordering = match (ours.peek(), theirs.peek()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match ordering {
+                Ordering::Less => only_in_self.extend(ours.next()),
+                Ordering::Greater => only_in_other.extend(theirs.next()),
+                Ordering::Equal => {
+                    ours.next();
+                    theirs.next();
+                }
+            }
+        }
+
+        let mut changed_wires = BTreeSet::new();
+        for constraint in only_in_self.iter().chain(only_in_other.iter()) {
+            for expression in &[&constraint.a, &constraint.b, &constraint.c] {
+                changed_wires.extend(expression.dependencies());
+            }
+        }
+        changed_wires.remove(&Wire::ONE);
+
+        GadgetDiff {
+            only_in_self,
+            only_in_other,
+            size_delta: other.size() as isize - self.size() as isize,
+            changed_wires: changed_wires.into_iter().collect(),
+        }
+    }
+
+    /// A canonical ordering of the variables of this gadget's R1CS instance, suitable for mapping
+    /// wires to column indices: `Wire::ONE`, followed by the public wires, followed by the private
+    /// wires, with each group in ascending order of index. Only wires which appear in constraints
+    /// are included, so any public wires which don't appear in constraints are omitted.
+    pub fn variable_order(&self, public_wires: &BTreeSet<Wire>) -> Vec<Wire> {
+        let (public, private): (Vec<Wire>, Vec<Wire>) = self.wires().into_iter()
+            .partition(|wire| public_wires.contains(wire));
+        let mut order = Vec::with_capacity(1 + public.len() + private.len());
+        order.push(Wire::ONE);
+        order.extend(public);
+        order.extend(private);
+        order
+    }
+
+    /// The index of `wire` in `variable_order`, or `None` if it doesn't appear in any constraint.
+    pub fn index_of(&self, wire: Wire, public_wires: &BTreeSet<Wire>) -> Option<usize> {
+        self.variable_order(public_wires).iter().position(|&w| w == wire)
+    }
+
+    /// The value of each variable, in the order given by `variable_order`. Fails if any of those
+    /// variables has no value, which typically means the gadget hasn't been executed.
+    pub fn assignment_vector(
+        &self, values: &WireValues<F>, public_wires: &BTreeSet<Wire>,
+    ) -> Result<Vec<Element<F>>, MissingWireError> {
+        self.variable_order(public_wires).into_iter()
+            .map(|wire| if values.contains(wire) {
+                Ok(values.get(wire).clone())
+            } else {
+                Err(MissingWireError { wire })
+            })
+            .collect()
+    }
+
+    /// The wires which some constraint or witness generator depends on, but which no generator
+    /// declares as an output and `values` doesn't contain, in ascending order of index. These are
+    /// the inputs which the caller must supply before executing the gadget.
+    ///
+    /// Only declared outputs are considered generated (see `WitnessGenerator::with_outputs`), so a
+    /// wire set by a generator which doesn't declare its outputs will be reported as missing. For
+    /// this reason, `execute` and `try_execute` only check for missing inputs if every generator
+    /// declares its outputs.
+    pub fn missing_inputs(&self, values: &WireValues<F>) -> Vec<Wire> {
+        let generated = self.generated_wires();
+        let mut dependencies: BTreeSet<Wire> = self.wires().into_iter().collect();
+        for generator in &self.witness_generators {
+            dependencies.extend(generator.inputs().iter().cloned());
+        }
+        dependencies.into_iter()
+            .filter(|&wire| wire != Wire::ONE && !generated.contains(&wire)
+                && !values.contains(wire))
+            .collect()
+    }
+
+    /// Execute the gadget, and return whether all constraints were satisfied. If every generator
+    /// declares its outputs, panics, listing every missing input, if `missing_inputs` is non-empty.
     pub fn execute(&self, wire_values: &mut WireValues<F>) -> bool {
-        let mut pending_generators: Vec<&WitnessGenerator<F>> = self.witness_generators.iter().collect();
+        self.try_execute(wire_values).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `execute`, but fails with the complete list of missing inputs, rather than panicking,
+    /// if `missing_inputs` is non-empty. In that case, no generators are run.
+    ///
+    /// Gadgets with a generator which doesn't declare its outputs, such as one added with
+    /// `GadgetBuilder::generator`, skip this check, since any wire could be one of its outputs.
+    pub fn try_execute(&self, wire_values: &mut WireValues<F>) -> Result<bool, MissingInputsError> {
+        if self.declares_all_outputs() {
+            let missing = self.missing_inputs(wire_values);
+            if !missing.is_empty() {
+                return Err(MissingInputsError { wires: missing });
+            }
+        }
+
+        let result = self.execute_with(wire_values, |_index, generator, values| {
+            generator.generate(values);
+            Ok::<(), Infallible>(())
+        });
+        match result {
+            Ok(satisfied) => Ok(satisfied),
+            Err(never) => match never {},
+        }
+    }
+
+    /// Execute the gadget, and return whether all constraints were satisfied. Unlike `execute`,
+    /// this supports retryable generators: whenever a generator returns `Retry`, any values it set
+    /// are rolled back and it is run again, up to `max_retries` times. Fails if a generator
+    /// exhausts its retries.
+    pub fn execute_with_backtracking(
+        &self, wire_values: &mut WireValues<F>, max_retries: usize,
+    ) -> Result<bool, RetryLimitError> {
+        self.execute_with(wire_values, |index, generator, values| {
+            for attempt in 0..=max_retries {
+                let snapshot = values.snapshot();
+                match generator.try_generate(values, attempt) {
+                    Ok(()) => return Ok(()),
+                    Err(Retry) => values.rollback(snapshot),
+                }
+            }
+            Err(RetryLimitError { generator: index, attempts: max_retries + 1 })
+        })
+    }
+
+    /// Execute the gadget, using `run` to run each generator once its inputs are available. `run`
+    /// is also passed the generator's index in `witness_generators`. If `run` fails, execution
+    /// stops and the error is returned.
+    pub(crate) fn execute_with<R, E>(
+        &self, wire_values: &mut WireValues<F>, mut run: R,
+    ) -> Result<bool, E>
+        where R: FnMut(usize, &WitnessGenerator<F>, &mut WireValues<F>) -> Result<(), E> {
+        let mut pending_generators: Vec<(usize, &WitnessGenerator<F>)> =
+            self.witness_generators.iter().enumerate().collect();
 
         // TODO: This repeatedly enumerates all generators, whether or not any of their dependencies
         // have been generated. A better approach would be to create a map from wires to generators
@@ -30,15 +546,21 @@ impl<F: Field> Gadget<F> {
         // check for generators which are now ready to run, and place them in a queue.
         loop {
             let mut made_progress = false;
-            pending_generators.retain(|generator| {
-                if wire_values.contains_all(generator.inputs()) {
-                    generator.generate(wire_values);
+            let mut error = None;
+            pending_generators.retain(|&(index, generator)| {
+                if error.is_none() && wire_values.contains_all(generator.inputs()) {
+                    if let Err(e) = run(index, generator, wire_values) {
+                        error = Some(e);
+                    }
                     made_progress = true;
                     false
                 } else {
                     true
                 }
             });
+            if let Some(e) = error {
+                return Err(e);
+            }
 
             if !made_progress {
                 break;
@@ -47,16 +569,677 @@ impl<F: Field> Gadget<F> {
 
         assert_eq!(pending_generators.len(), 0, "Some generators never received inputs");
 
-        self.constraints.iter().all(|constraint| constraint.evaluate(wire_values))
+        Ok(self.check(wire_values))
+    }
+}
+
+/// The differences between the constraints of two gadgets, as found by `Gadget::diff`. Wires are
+/// numbered as by `Gadget::canonicalize_wires`, separately for each gadget.
+#[derive(Clone, Debug)]
+pub struct GadgetDiff<F: Field> {
+    /// Constraints of the first gadget with no match in the second, in sorted order.
+    pub only_in_self: Vec<Constraint<F>>,
+    /// Constraints of the second gadget with no match in the first, in sorted order.
+    pub only_in_other: Vec<Constraint<F>>,
+    /// The size of the second gadget minus the size of the first.
+    pub size_delta: isize,
+    /// Wires which appear in some unmatched constraint, so the set of constraints they appear in
+    /// differs between the two gadgets, in ascending order of index.
+    pub changed_wires: Vec<Wire>,
+}
+
+impl<F: Field> GadgetDiff<F> {
+    /// Whether the two gadgets have the same constraints, up to order and renumbering.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty()
+    }
+}
+
+/// Displays each unmatched constraint on its own line, prefixed with `-` if it is only in the first
+/// gadget or `+` if it is only in the second, like a unified diff.
+impl<F: Field> fmt::Display for GadgetDiff<F> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let lines: Vec<String> = self.only_in_self.iter().map(|c| format!("- {}", c))
+            .chain(self.only_in_other.iter().map(|c| format!("+ {}", c)))
+            .collect();
+        write!(f, "{}", join("\n", &lines))
+    }
+}
+
+/// The operands of `constraint`, each scaled so that its first coefficient is one, in ascending
+/// order, along with its output divided by the same factors. Products of the same pair of
+/// expressions, up to order and constant factors, have equal normalized operands.
+fn normalize_product<F: Field>(
+    constraint: &Constraint<F>,
+) -> ((Expression<F>, Expression<F>), Expression<F>) {
+    let (a, a_scale) = normalize_scale(&constraint.a);
+    let (b, b_scale) = normalize_scale(&constraint.b);
+    let output = &constraint.c / &(a_scale * b_scale);
+    let operands = if a <= b { (a, b) } else { (b, a) };
+    (operands, output)
+}
+
+/// `x` divided by its first coefficient, along with that coefficient. Zero is left unchanged.
+fn normalize_scale<F: Field>(x: &Expression<F>) -> (Expression<F>, Element<F>) {
+    match x.coefficients().values().next() {
+        Some(scale) => (x / scale, scale.clone()),
+        None => (x.clone(), Element::one()),
+    }
+}
+
+/// What `Gadget::merge_duplicate_products` merged.
+#[derive(Clone, Debug)]
+pub struct MergeReport<F: Field> {
+    /// The number of constraints dropped because another constraint multiplied the same operands
+    /// to the same output.
+    pub duplicates_removed: usize,
+    /// The wires which were eliminated, each mapped to the expression in the remaining wires which
+    /// replaced it. Each substitution dropped one constraint.
+    pub substituted_wires: BTreeMap<Wire, Expression<F>>,
+    /// The number of constraints which multiplied the same operands as an earlier constraint, to
+    /// an output differing from it by a nonzero constant. These make the gadget unsatisfiable, so
+    /// they were kept.
+    pub conflicts: usize,
+}
+
+impl<F: Field> MergeReport<F> {
+    /// The total number of constraints which were dropped.
+    pub fn constraints_removed(&self) -> usize {
+        self.duplicates_removed + self.substituted_wires.len()
+    }
+
+    /// Given a non-constant `difference` which must be zero, solves it for its highest-indexed
+    /// wire, and records the substitution, applying it to earlier substitutions as well.
+    fn substitute(&mut self, difference: &Expression<F>) {
+        // Wire::ONE is ordered last, so we skip any constant term.
+        let (&wire, coefficient) = difference.coefficients().iter().rev()
+            .find(|(&wire, _coefficient)| wire != Wire::ONE)
+            .expect("Expected a non-constant difference");
+        let replacement = Expression::from(wire) - difference / coefficient;
+        let mut substitution = BTreeMap::new();
+        substitution.insert(wire, replacement);
+        for existing in self.substituted_wires.values_mut() {
+            *existing = existing.substitute_expressions(&substitution);
+        }
+        self.substituted_wires.extend(substitution);
+    }
+}
+
+/// An error indicating that a wire has no value in a `WireValues`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MissingWireError {
+    /// The wire which has no value.
+    pub wire: Wire,
+}
+
+impl fmt::Display for MissingWireError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "No value for {}", self.wire)
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for MissingWireError {}
+
+/// An error indicating that some inputs of a gadget have no value, as found by
+/// `Gadget::missing_inputs`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MissingInputsError {
+    /// The wires which have no value, in ascending order of index.
+    pub wires: Vec<Wire>,
+}
+
+impl fmt::Display for MissingInputsError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let wires: Vec<String> = self.wires.iter().map(|wire| format!("{}", wire)).collect();
+        write!(f, "No values for inputs {}", join(", ", &wires))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MissingInputsError {}
+
+/// An error indicating that a retryable generator still failed after its final attempt.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryLimitError {
+    /// The index of the generator in `witness_generators`.
+    pub generator: usize,
+    /// The number of attempts which were made.
+    pub attempts: usize,
+}
+
+impl fmt::Display for RetryLimitError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Generator {} failed after {} attempts", self.generator, self.attempts)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RetryLimitError {}
+
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "std")]
+    use std::collections::BTreeSet;
+    #[cfg(not(feature = "std"))]
+    use alloc::collections::btree_set::BTreeSet;
+
     use crate::expression::Expression;
+    use crate::field::Element;
+    use crate::gadget::{Gadget, MergeReport, MissingInputsError, MissingWireError,
+                        RetryLimitError};
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::gadget_traits::MultiPermutation;
+    use crate::lcg::LCG;
+    use crate::poseidon::{NumberOfRounds, PoseidonBuilder, PoseidonSbox};
+    use crate::fields::small::F257;
+    use crate::test_util::multiply_add;
+    use crate::wire::Wire;
     use crate::wire_values::WireValues;
+    use crate::witness_generator::Retry;
+
+    #[test]
+    fn unconstrained_hint_wires() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        // A planted output which no constraint verifies.
+        builder.generator_with_outputs(vec![x], vec![y], move |values: &mut WireValues<F257>| {
+            let value = values.get(x).clone();
+            values.set(y, value);
+        });
+        let square = builder.product(&Expression::from(x), &Expression::from(x));
+        let gadget = builder.build();
+
+        assert_eq!(vec![y], gadget.unconstrained_hint_wires());
+        assert_eq!(square.dependencies(), gadget.wires_with_single_constraint());
+    }
+
+    #[test]
+    fn comparisons_and_splitting_have_no_unconstrained_hint_wires() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x, y) = (Expression::from(x), Expression::from(y));
+        builder.lt(&x, &y);
+        builder.ge(&x, &y);
+        builder.split(&x);
+        builder.split_bounded(&y, 8);
+        builder.split_allowing_ambiguity(&y);
+        builder.equal(&x, &y);
+        let gadget = builder.build();
+
+        assert!(gadget.witness_generators.iter().all(|generator| !generator.outputs().is_empty()));
+        assert_eq!(Vec::<Wire>::new(), gadget.unconstrained_hint_wires());
+    }
+
+    #[test]
+    fn missing_inputs() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.lt(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+
+        let mut values = values!(x => 3u8.into());
+        assert_eq!(vec![y], gadget.missing_inputs(&values));
+        assert_eq!(Err(MissingInputsError { wires: vec![y] }), gadget.try_execute(&mut values));
+        // No generators were run, so only the constant one and x have values.
+        assert_eq!(2, values.as_map().len());
+
+        values.set(y, 5u8.into());
+        assert_eq!(Vec::<Wire>::new(), gadget.missing_inputs(&values));
+        assert_eq!(Ok(true), gadget.try_execute(&mut values));
+        assert_eq!(Vec::<Wire>::new(), gadget.missing_inputs(&values));
+    }
+
+    #[test]
+    fn execute_undeclared_outputs() {
+        // The inverse example from the README, whose generator doesn't declare its output.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let x_inv = builder.wire();
+        builder.assert_product(&Expression::from(x), &Expression::from(x_inv), &Expression::one());
+        builder.generator(vec![x], move |values: &mut WireValues<F257>| {
+            let x_inv_value = values.get(x).multiplicative_inverse();
+            values.set(x_inv, x_inv_value);
+        });
+        let gadget = builder.build();
+
+        // x_inv is reported as missing, but execution doesn't rely on that.
+        let mut values = values!(x => 3u8.into());
+        assert_eq!(vec![x_inv], gadget.missing_inputs(&values));
+        assert_eq!(Ok(true), gadget.try_execute(&mut values));
+        assert!(gadget.execute(&mut values!(x => 3u8.into())));
+        assert_eq!(Element::from(86u8), *values.get(x_inv));
+    }
+
+    #[test]
+    #[should_panic(expected = "No values for inputs")]
+    fn execute_missing_inputs() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        builder.product(&Expression::from(x), &Expression::from(y));
+        builder.assert_equal(&Expression::from(x), &Expression::from(z));
+        builder.build().execute(&mut values!(x => 3u8.into()));
+    }
+
+    #[test]
+    fn propagate_constants_chain() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (w1, w2, w3) = (builder.wire(), builder.wire(), builder.wire());
+        builder.assert_equal(&Expression::from(w1), &Expression::from(7u8));
+        builder.assert_equal(
+            &Expression::from(w2), &(Expression::from(w1) + Expression::from(3u8)));
+        builder.assert_product(&Expression::from(w2), &Expression::from(w2), &Expression::from(w3));
+        let gadget = builder.build();
+
+        let (folded, assignments) = gadget.propagate_constants();
+        assert_eq!(Some(&Element::from(7u8)), assignments.get(&w1));
+        assert_eq!(Some(&Element::from(10u8)), assignments.get(&w2));
+        assert_eq!(Some(&Element::from(100u8)), assignments.get(&w3));
+        assert_eq!(0, folded.size());
+    }
+
+    #[test]
+    fn propagate_constants_mixed() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, w) = (builder.wire(), builder.wire(), builder.wire());
+        builder.assert_equal(&Expression::from(w), &Expression::from(7u8));
+        builder.assert_equal(&(Expression::from(x) + Expression::from(w)), &Expression::from(y));
+        let z = builder.product(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+
+        let (folded, assignments) = gadget.propagate_constants();
+        assert_eq!(vec![w], assignments.keys().cloned().collect::<Vec<Wire>>());
+        assert_eq!(2, folded.size());
+        // The product constraint doesn't involve w, so it is untouched.
+        assert_eq!(gadget.constraints[2], folded.constraints[1]);
+        assert_eq!(Expression::from(x) + Expression::from(7u8), folded.constraints[0].a);
+
+        // Satisfiability is preserved, with the same witness.
+        let mut values = values!(x => 3u8.into(), y => 10u8.into(), w => 7u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(Element::from(30u8), z.evaluate(&values));
+        assert!(folded.constraints.iter().all(|c| c.evaluate(&values)));
+        // The folded gadget no longer needs a value for w.
+        let mut values = values!(x => 3u8.into(), y => 10u8.into());
+        assert!(folded.execute(&mut values));
+        assert!(!values.contains(w));
+        assert_eq!(Element::from(30u8), z.evaluate(&values));
+        let mut values = values!(x => 3u8.into(), y => 11u8.into());
+        assert!(!folded.execute(&mut values));
+    }
+
+    #[test]
+    fn propagate_constants_conflict() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let w = builder.wire();
+        builder.assert_equal(&Expression::from(w), &Expression::from(7u8));
+        builder.assert_equal(&Expression::from(w), &Expression::from(8u8));
+        let (folded, assignments) = builder.build().propagate_constants();
+        assert_eq!(1, assignments.len());
+        // The violated constraint is kept.
+        assert_eq!(1, folded.size());
+    }
+
+    /// Checks that `merged` accepts a witness exactly when `gadget` accepts it with each
+    /// substituted wire assigned the value of its replacement. The witnesses are generated from
+    /// the given inputs, then perturbed at each wire of `merged` in turn.
+    fn assert_merge_preserves_witnesses(
+        gadget: &Gadget<F257>, inputs: &[Wire], trials: usize,
+    ) -> MergeReport<F257> {
+        let (merged, report) = gadget.merge_duplicate_products();
+        let accepts = |gadget: &Gadget<F257>, values: &WireValues<F257>| {
+            gadget.constraints.iter().all(|c| c.evaluate(values))
+        };
+        let completed = |values: &WireValues<F257>| {
+            let mut completed = WireValues::new();
+            for (&wire, value) in values.as_map() {
+                if wire != Wire::ONE && !report.substituted_wires.contains_key(&wire) {
+                    completed.set(wire, value.clone());
+                }
+            }
+            for (&wire, replacement) in &report.substituted_wires {
+                completed.set(wire, replacement.evaluate(values));
+            }
+            completed
+        };
+
+        let mut lcg = LCG::new();
+        for _trial in 0..trials {
+            let mut values = WireValues::new();
+            for &input in inputs {
+                values.set(input, lcg.next_element());
+            }
+            assert!(gadget.execute(&mut values));
+            assert!(accepts(&merged, &values));
+
+            for perturbed in merged.wires() {
+                let mut forged = WireValues::new();
+                for (&wire, value) in values.as_map() {
+                    if wire != Wire::ONE && wire != perturbed {
+                        forged.set(wire, value.clone());
+                    }
+                }
+                forged.set(perturbed, values.get(perturbed) + lcg.next_element::<F257>());
+                assert_eq!(accepts(gadget, &completed(&forged)), accepts(&merged, &forged));
+            }
+        }
+        report
+    }
+
+    #[test]
+    fn merge_duplicate_products_shared_product() {
+        // Two independent sub-gadgets compute x * y, with the operands in opposite orders.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let xy = builder.product(&x_exp, &y_exp);
+        let x2y = builder.product(&xy, &x_exp);
+        let yx = builder.product(&y_exp, &x_exp);
+        let xy2 = builder.product(&yx, &y_exp);
+        let gadget = builder.build();
+
+        let (merged, report) = gadget.merge_duplicate_products();
+        assert_eq!(3, merged.size());
+        assert_eq!(1, report.constraints_removed());
+        let yx_wire = yx.dependencies()[0];
+        assert_eq!(Some(&xy), report.substituted_wires.get(&yx_wire));
+
+        let mut values = values!(x => 3u8.into(), y => 5u8.into());
+        assert!(gadget.execute(&mut values));
+        assert!(merged.constraints.iter().all(|c| c.evaluate(&values)));
+        assert_eq!(Element::from(45u8), x2y.evaluate(&values));
+        assert_eq!(Element::from(75u8), xy2.evaluate(&values));
+        assert_merge_preserves_witnesses(&gadget, &[x, y], 3);
+    }
+
+    #[test]
+    fn merge_duplicate_products_offset_outputs() {
+        // The outputs differ by a constant as well as a wire, so the constant term must not be
+        // chosen for substitution.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z, w) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        builder.assert_product(&x_exp, &y_exp, &Expression::from(z));
+        builder.assert_product(&x_exp, &y_exp, &(Expression::from(w) + Expression::from(3u8)));
+        let (merged, report) = builder.build().merge_duplicate_products();
+        assert_eq!(1, merged.size());
+        assert_eq!(Some(&(Expression::from(z) - Expression::from(3u8))),
+                   report.substituted_wires.get(&w));
+    }
+
+    #[test]
+    fn merge_duplicate_products_exact_and_scaled() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let (x_exp, y_exp, z_exp) = (Expression::from(x), Expression::from(y), Expression::from(z));
+        builder.assert_product(&x_exp, &y_exp, &z_exp);
+        builder.assert_product(&x_exp, &y_exp, &z_exp);
+        let two = Element::from(2u8);
+        builder.assert_product(&(&y_exp * &two), &x_exp, &(&z_exp * &two));
+        let (merged, report) = builder.build().merge_duplicate_products();
+        assert_eq!(1, merged.size());
+        assert_eq!(2, report.duplicates_removed);
+        assert!(report.substituted_wires.is_empty());
+    }
+
+    #[test]
+    fn merge_duplicate_products_conflict() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let (x_exp, y_exp, z_exp) = (Expression::from(x), Expression::from(y), Expression::from(z));
+        builder.assert_product(&x_exp, &y_exp, &z_exp);
+        builder.assert_product(&x_exp, &y_exp, &(z_exp + Expression::one()));
+        let (merged, report) = builder.build().merge_duplicate_products();
+        assert_eq!(2, merged.size());
+        assert_eq!(1, report.conflicts);
+        assert_eq!(0, report.constraints_removed());
+    }
+
+    #[test]
+    fn merge_duplicate_products_sorting() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (input_wires, inputs) = builder.expression_wires(4);
+        builder.sort_ascending(&inputs);
+        assert_merge_preserves_witnesses(&builder.build(), &input_wires, 3);
+    }
+
+    #[test]
+    fn merge_duplicate_products_poseidon() {
+        let mut lcg = LCG::new();
+        let round_constants = (0..10)
+            .map(|_r| (0..3).map(|_i| lcg.next_element()).collect())
+            .collect();
+        let poseidon = PoseidonBuilder::<F257>::new(3)
+            .sbox(PoseidonSbox::Exponentiation3)
+            .num_rounds(NumberOfRounds::new(4, 6))
+            .round_constants(round_constants)
+            .build();
+
+        // Permuting the same inputs twice duplicates every product, so the second permutation
+        // should be merged into the first entirely.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (input_wires, inputs) = builder.expression_wires(3);
+        let first = poseidon.permute(&mut builder, &inputs);
+        let second = poseidon.permute(&mut builder, &inputs);
+        let gadget = builder.build();
+        let report = assert_merge_preserves_witnesses(&gadget, &input_wires, 2);
+        assert_eq!(gadget.size() / 2, report.constraints_removed());
+
+        let (merged, _report) = gadget.merge_duplicate_products();
+        let mut values = values!(input_wires[0] => 1u8.into(), input_wires[1] => 2u8.into(),
+                                 input_wires[2] => 3u8.into());
+        assert!(gadget.execute(&mut values));
+        assert!(merged.constraints.iter().all(|c| c.evaluate(&values)));
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.evaluate(&values), b.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn wires() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, _unused, z) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        builder.assert_equal(&(Expression::from(z) + Expression::one()), &Expression::from(x));
+        builder.assert_product(&Expression::from(y), &Expression::from(x), &Expression::zero());
+        let gadget = builder.build();
+        assert_eq!(vec![x, y, z], gadget.wires());
+    }
+
+    #[test]
+    fn variable_order() {
+        let (gadget, [x, y, z, w]) = multiply_add::<F257>();
+        let public_wires: BTreeSet<Wire> = [z, w].iter().cloned().collect();
+        let order = gadget.variable_order(&public_wires);
+        assert_eq!(Wire::ONE, order[0]);
+        assert_eq!(&[w, z], &order[1..3]);
+        assert_eq!(&[x, y], &order[3..5]);
+        assert_eq!(6, order.len());
+
+        assert_eq!(Some(0), gadget.index_of(Wire::ONE, &public_wires));
+        assert_eq!(Some(1), gadget.index_of(w, &public_wires));
+        assert_eq!(None, gadget.index_of(Wire { index: 3 }, &public_wires));
+
+        // Rebuilding the same gadget yields the same order.
+        let (rebuilt, _wires) = multiply_add::<F257>();
+        assert_eq!(order, rebuilt.variable_order(&public_wires));
+    }
+
+    #[test]
+    fn assignment_vector() {
+        let (gadget, [x, y, z, w]) = multiply_add::<F257>();
+        let public_wires: BTreeSet<Wire> = [z].iter().cloned().collect();
+
+        let mut values = values!(x => 3u8.into(), y => 4u8.into(), w => 5u8.into());
+        assert_eq!(Err(MissingWireError { wire: z }),
+                   gadget.assignment_vector(&values, &public_wires));
+        values.set(z, 17u8.into());
+        assert!(gadget.execute(&mut values));
+        let assignment = gadget.assignment_vector(&values, &public_wires).unwrap();
+        assert_eq!(Element::one(), assignment[0]);
+        assert_eq!(Element::from(17u8), assignment[1]);
+
+        // Evaluating each constraint as dot products with the assignment vector agrees with
+        // evaluating it directly.
+        let dot = |exp: &Expression<F257>| exp.iter().fold(Element::zero(), |sum, (&wire, c)| {
+            sum + &assignment[gadget.index_of(wire, &public_wires).unwrap()] * c
+        });
+        for constraint in &gadget.constraints {
+            assert_eq!(dot(&constraint.a) * dot(&constraint.b), dot(&constraint.c));
+        }
+    }
+
+    #[test]
+    fn free_variable_id_sparse() {
+        // The unused wire leaves a gap, so counting wires would underestimate the free ID.
+        let (gadget, [_x, _y, z, _w]) = multiply_add::<F257>();
+        assert_eq!(5, gadget.wires().len());
+        assert_eq!(u64::from(z.index) + 1, gadget.free_variable_id());
+        assert_eq!(7, gadget.free_variable_id());
+    }
+
+    #[test]
+    fn compact_wires() {
+        let (gadget, [x, y, z, w]) = multiply_add::<F257>();
+        let (compacted, map) = gadget.compact_wires();
+        assert_eq!(6, compacted.free_variable_id());
+        assert_eq!(gadget.size(), compacted.size());
+        assert_eq!(Wire::ONE, map[&Wire::ONE]);
+        assert_eq!(Wire { index: 1 }, map[&x]);
+        assert_eq!(Wire { index: 3 }, map[&w]);
+
+        let mut values =
+            values!(x => 3u8.into(), y => 4u8.into(), z => 17u8.into(), w => 5u8.into());
+        assert!(gadget.execute(&mut values));
+        let mut remapped = values.remap(&map);
+        assert!(compacted.execute(&mut remapped));
+
+        let public_wires: BTreeSet<Wire> = [z].iter().cloned().collect();
+        let remapped_public: BTreeSet<Wire> = public_wires.iter().map(|w| map[w]).collect();
+        assert_eq!(gadget.assignment_vector(&values, &public_wires),
+                   compacted.assignment_vector(&remapped, &remapped_public));
+
+        // A witness which is wrong before remapping is still wrong after.
+        let mut bad_values =
+            values!(x => 3u8.into(), y => 4u8.into(), z => 18u8.into(), w => 5u8.into());
+        assert!(!gadget.execute(&mut bad_values));
+        assert!(!compacted.execute(&mut bad_values.remap(&map)));
+    }
+
+    /// Like `multiply_add`, but with wires allocated in a different order, and more unused wires.
+    fn multiply_add_reordered() -> Gadget<F257> {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (w, z, _unused) = (builder.wire(), builder.wire(), builder.wire());
+        let (y, x) = (builder.wire(), builder.wire());
+        builder.wires(3);
+        let xy = builder.product(&Expression::from(x), &Expression::from(y));
+        builder.assert_equal(&Expression::from(z), &(xy + Expression::from(w)));
+        builder.build()
+    }
+
+    #[test]
+    fn diff_with_self() {
+        let (gadget, _wires) = multiply_add::<F257>();
+        let diff = gadget.diff(&gadget);
+        assert!(diff.is_empty());
+        assert_eq!(0, diff.size_delta);
+        assert!(diff.changed_wires.is_empty());
+        assert_eq!("", format!("{}", diff));
+        assert!(gadget.is_equivalent_up_to_renumbering(&gadget));
+    }
+
+    #[test]
+    fn diff_added_constraint() {
+        let (gadget, _wires) = multiply_add::<F257>();
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, _unused, w) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        let xy = builder.product(&Expression::from(x), &Expression::from(y));
+        let z = builder.wire();
+        builder.assert_equal(&Expression::from(z), &(xy + Expression::from(w)));
+        builder.assert_equal(&Expression::from(w), &Expression::from(5u8));
+        let extended = builder.build();
+
+        let diff = gadget.diff(&extended);
+        assert!(!diff.is_empty());
+        assert!(diff.only_in_self.is_empty());
+        assert_eq!(1, diff.only_in_other.len());
+        assert_eq!(1, diff.size_delta);
+        // Canonically, w is the last of x, y, xy, z and w to appear.
+        assert_eq!(vec![Wire { index: 5 }], diff.changed_wires);
+        assert_eq!("+ (w5) * (1) = (5)", format!("{}", diff));
+        assert!(!gadget.is_equivalent_up_to_renumbering(&extended));
+
+        let reverse = extended.diff(&gadget);
+        assert_eq!(1, reverse.only_in_self.len());
+        assert_eq!(-1, reverse.size_delta);
+    }
+
+    #[test]
+    fn diff_up_to_renumbering() {
+        let (gadget, _wires) = multiply_add::<F257>();
+        let reordered = multiply_add_reordered();
+        assert!(gadget.compact_wires().0.diff(&gadget).is_empty());
+
+        // The compacted gadgets differ, since compaction preserves allocation order.
+        let compacted = gadget.compact_wires().0;
+        let reordered_compacted = reordered.compact_wires().0;
+        assert!(compacted.constraints != reordered_compacted.constraints);
+
+        assert_eq!(gadget.fingerprint(), reordered.fingerprint());
+        assert!(gadget.diff(&reordered).is_empty());
+        assert!(gadget.is_equivalent_up_to_renumbering(&reordered));
+    }
+
+    #[test]
+    fn diff_changed_coefficient() {
+        let (gadget, _wires) = multiply_add::<F257>();
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, w) = (builder.wire(), builder.wire(), builder.wire());
+        let xy = builder.product(&Expression::from(x), &Expression::from(y));
+        let z = builder.wire();
+        let sum = xy + Expression::from(w) * Element::from(2u8);
+        builder.assert_equal(&Expression::from(z), &sum);
+        let changed = builder.build();
+
+        assert!(gadget.fingerprint() != changed.fingerprint());
+        let diff = gadget.diff(&changed);
+        assert_eq!((1, 1, 0), (diff.only_in_self.len(), diff.only_in_other.len(), diff.size_delta));
+        assert!(!gadget.is_equivalent_up_to_renumbering(&changed));
+    }
+
+    #[test]
+    fn constraints_accessor() {
+        let (gadget, _wires) = multiply_add::<F257>();
+        assert_eq!(gadget.size(), gadget.constraints().len());
+        let size = gadget.size();
+        let (constraints, generators) = gadget.into_parts();
+        assert_eq!(size, constraints.len());
+        assert_eq!(1, generators.len());
+    }
+
+    #[test]
+    fn clone_constraints_only() {
+        let (gadget, [x, y, z, w]) = multiply_add::<F257>();
+        let verifier = gadget.clone_constraints_only();
+        assert!(verifier.witness_generators.is_empty());
+        assert_eq!(gadget.constraints(), verifier.constraints());
+
+        let mut values =
+            values!(x => 3u8.into(), y => 4u8.into(), z => 17u8.into(), w => 5u8.into());
+        assert!(gadget.execute(&mut values));
+        assert!(verifier.check(&values));
+        assert!(verifier.execute(&mut values.clone()));
+
+        // The same witness, but with z off by one. Wire::ONE is set by WireValues::new.
+        let mut forged = WireValues::new();
+        for (&wire, value) in values.as_map() {
+            if wire == z {
+                forged.set(wire, value + Element::one());
+            } else if wire != Wire::ONE {
+                forged.set(wire, value.clone());
+            }
+        }
+        assert!(!verifier.check(&forged));
+    }
 
     #[test]
     fn constraint_not_satisfied() {
@@ -93,4 +1276,44 @@ mod tests {
         let mut values = WireValues::new();
         gadget.execute(&mut values);
     }
+
+    #[test]
+    fn execute_with_backtracking() {
+        // A retryable generator sets y = x + attempt, and requests a retry until y is a perfect
+        // square, i.e. it searches for the smallest square above x.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, root) = (builder.wire(), builder.wire(), builder.wire());
+        builder.retryable_generator(vec![x], move |values, attempt| {
+            let y_value = values.get(x) + Element::from(attempt);
+            values.set(y, y_value.clone());
+            let y_int = y_value.to_biguint();
+            let root_int = y_int.sqrt();
+            if &root_int * &root_int != *y_int {
+                return Err(Retry);
+            }
+            values.set(root, Element::from(root_int));
+            Ok(())
+        });
+        builder.assert_product(&Expression::from(root), &Expression::from(root),
+                               &Expression::from(y));
+        let gadget = builder.build();
+
+        let mut values = values!(x => 10u8.into());
+        assert_eq!(Err(RetryLimitError { generator: 0, attempts: 6 }),
+                   gadget.execute_with_backtracking(&mut values, 5));
+        // The failed attempts were rolled back.
+        assert!(!values.contains(y));
+
+        assert_eq!(Ok(true), gadget.execute_with_backtracking(&mut values, 6));
+        assert_eq!(&Element::from(16u8), values.get(y));
+    }
+
+    #[test]
+    #[should_panic(expected = "requires execute_with_backtracking")]
+    fn execute_retryable_without_backtracking() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        builder.retryable_generator(vec![x], |_values, _attempt| Err(Retry));
+        builder.build().execute(&mut values!(x => 10u8.into()));
+    }
 }
@@ -1,18 +1,59 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use std::fmt;
+use std::fmt::Formatter;
+use std::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "std"))]
+use alloc::collections::btree_map::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::collections::btree_set::BTreeSet;
 
+use crate::build_log::LogEntry;
 use crate::constraint::Constraint;
 use crate::expression::{BooleanExpression, Expression};
 use crate::field::{Element, Field};
 use crate::gadget::Gadget;
-use crate::wire::{BinaryWire, BooleanWire, Wire};
+use crate::wire::{BinaryWire, BooleanWire, Wire, WireKind};
 use crate::wire_values::WireValues;
-use crate::witness_generator::WitnessGenerator;
+use crate::witness_generator::{Retry, WitnessGenerator};
+use crate::util::{concat, expressions_from_wires, join};
 
 pub struct GadgetBuilder<F: Field> {
     next_wire_index: u32,
     constraints: Vec<Constraint<F>>,
     witness_generators: Vec<WitnessGenerator<F>>,
+    /// Expressions which are known to be boolean, either because they have been asserted to be
+    /// boolean, or because they were marked as such with `mark_boolean`.
+    known_booleans: BTreeSet<Expression<F>>,
+    /// For each expression with a known bound, the number of bits it is known to fit in.
+    known_bounds: BTreeMap<Expression<F>, usize>,
+    /// Expressions which are known to be nonzero, either because they have been asserted to be
+    /// nonzero, or because they were marked as such with `mark_nonzero`.
+    known_nonzero: BTreeSet<Expression<F>>,
+    /// The number of booleanity constraints added by `assert_boolean`.
+    booleanity_constraints: usize,
+    /// Outputs of `hint` which have not yet appeared in any constraint.
+    unconstrained_hints: BTreeSet<Wire>,
+    /// Whether `try_build` should reject all unconstrained generator outputs, not just hints.
+    strict: bool,
+    /// The kinds of wires allocated by typed methods such as `boolean_wire`.
+    wire_kinds: BTreeMap<Wire, WireKind>,
+    /// The maximum number of terms in any one side of a constraint, if a budget was set.
+    term_budget: Option<usize>,
+    /// Whether `try_build` should fail if any constraint exceeded the term budget.
+    term_budget_strict: bool,
+    /// A warning for each side of a constraint which exceeded the term budget.
+    warnings: Vec<TermBudgetWarning>,
+    /// The number of terms in the widest side of any constraint added so far.
+    widest_expression_terms: usize,
+    /// The build log, if it was enabled with `enable_build_log`.
+    build_log: Option<Vec<LogEntry>>,
+    /// The number of logged calls in progress.
+    build_log_depth: usize,
 }
 
 /// A utility for building `Gadget`s. See the readme for examples.
@@ -24,6 +65,19 @@ impl<F: Field> GadgetBuilder<F> {
             next_wire_index: 1,
             constraints: Vec::new(),
             witness_generators: Vec::new(),
+            known_booleans: BTreeSet::new(),
+            known_bounds: BTreeMap::new(),
+            known_nonzero: BTreeSet::new(),
+            booleanity_constraints: 0,
+            unconstrained_hints: BTreeSet::new(),
+            strict: false,
+            wire_kinds: BTreeMap::new(),
+            term_budget: None,
+            term_budget_strict: false,
+            warnings: Vec::new(),
+            widest_expression_terms: 0,
+            build_log: None,
+            build_log_depth: 0,
         }
     }
 
@@ -36,9 +90,12 @@ impl<F: Field> GadgetBuilder<F> {
 
     /// Add a wire to the gadget, whose value is constrained to equal 0 or 1.
     pub fn boolean_wire(&mut self) -> BooleanWire {
-        let w = self.wire();
-        self.assert_boolean(&Expression::from(w));
-        BooleanWire::new_unsafe(w)
+        logged!(self, "boolean_wire", [], {
+            let w = self.wire();
+            self.assert_boolean(&Expression::from(w));
+            self.wire_kinds.insert(w, WireKind::Boolean);
+            BooleanWire::new_unsafe(w)
+        })
     }
 
     /// Add `n` wires to the gadget. They will start with no generator and no associated
@@ -47,30 +104,130 @@ impl<F: Field> GadgetBuilder<F> {
         (0..n).map(|_i| self.wire()).collect()
     }
 
+    /// Add `n` wires to the gadget, returning them along with an expression for each.
+    pub fn expression_wires(&mut self, n: usize) -> (Vec<Wire>, Vec<Expression<F>>) {
+        let wires = self.wires(n);
+        let expressions = expressions_from_wires(&wires);
+        (wires, expressions)
+    }
+
+    /// Add `n` wires to the gadget, returning the range of their indices. The indices are
+    /// guaranteed to be contiguous, which is convenient for mapping an external variable numbering
+    /// onto wires: external variable `i` can be mapped to `Wire { index: range.start + i }`.
+    ///
+    /// There is intentionally no way to request a specific wire index, since the builder (and any
+    /// gadgets added before or after) may already be using it. External numberings should instead
+    /// be offset by the start of an allocated range.
+    pub fn allocate_wires(&mut self, n: usize) -> Range<u32> {
+        let start = self.next_wire_index;
+        self.next_wire_index += n as u32;
+        start..self.next_wire_index
+    }
+
     /// Add a binary wire comprised of `n` bits to the gadget.
     pub fn binary_wire(&mut self, n: usize) -> BinaryWire {
-        BinaryWire { bits: (0..n).map(|_i| self.boolean_wire()).collect() }
+        logged!(self, "binary_wire", [n], {
+            let bits: Vec<BooleanWire> = (0..n).map(|_i| self.boolean_wire()).collect();
+            for (index, bit) in bits.iter().enumerate() {
+                self.wire_kinds.insert(bit.wire, WireKind::BinaryBit { index, bits: n });
+            }
+            BinaryWire { bits }
+        })
     }
 
     /// Add a generator function for setting certain wire values.
     pub fn generator<T>(&mut self, dependencies: Vec<Wire>, generate: T)
-        where T: Fn(&mut WireValues<F>) + 'static {
+        where T: Fn(&mut WireValues<F>) + Send + Sync + 'static {
         self.witness_generators.push(WitnessGenerator::new(dependencies, generate));
     }
 
+    /// Add a generator function for setting certain wire values, declaring the wires it sets. This
+    /// lets `Gadget::unconstrained_hint_wires` and strict builds check that each output is
+    /// constrained.
+    pub fn generator_with_outputs<T>(
+        &mut self, dependencies: Vec<Wire>, outputs: Vec<Wire>, generate: T,
+    ) where T: Fn(&mut WireValues<F>) + Send + Sync + 'static {
+        self.witness_generators.push(
+            WitnessGenerator::new(dependencies, generate).with_outputs(outputs));
+    }
+
+    /// Add a generator which may fail by returning `Retry`, in which case any values it set are
+    /// rolled back and it is run again. It is passed the number of previous attempts. Gadgets with
+    /// such generators must be executed with `Gadget::execute_with_backtracking`.
+    pub fn retryable_generator<T>(&mut self, dependencies: Vec<Wire>, generate: T)
+        where T: Fn(&mut WireValues<F>, usize) -> Result<(), Retry> + Send + Sync + 'static {
+        self.witness_generators.push(WitnessGenerator::new_retryable(dependencies, generate));
+    }
+
+    /// Add `n_outputs` wires whose values are supplied by the prover, computed from the values of
+    /// `dependencies` by `compute`. Nothing about the outputs is enforced; the caller must verify
+    /// them with constraints.
+    ///
+    /// The outputs are tracked until each of them appears in some constraint. `build` panics, and
+    /// `try_build` returns an error, if any outputs are never constrained, since an unconstrained
+    /// hint is almost always a soundness bug.
+    pub fn hint<T>(&mut self, dependencies: Vec<Wire>, n_outputs: usize, compute: T) -> HintHandle
+        where T: Fn(&WireValues<F>) -> Vec<Element<F>> + Send + Sync + 'static {
+        let outputs = self.wires(n_outputs);
+        self.unconstrained_hints.extend(outputs.iter().cloned());
+
+        let handle = HintHandle { wires: outputs.clone() };
+        self.generator_with_outputs(
+            dependencies,
+            outputs.clone(),
+            move |values: &mut WireValues<F>| {
+                let output_values = compute(values);
+                assert_eq!(output_values.len(), outputs.len(),
+                           "Hint computed {} values, but {} were expected",
+                           output_values.len(), outputs.len());
+                for (&wire, value) in outputs.iter().zip(output_values) {
+                    values.set(wire, value);
+                }
+            },
+        );
+        handle
+    }
+
     /// x == y
     pub fn equal(&mut self, x: &Expression<F>, y: &Expression<F>) -> BooleanExpression<F> {
-        self.zero(&(x - y))
+        logged!(self, "equal", [x, y], {
+            self.zero(&(x - y))
+        })
     }
 
     /// x == 0
     pub fn zero(&mut self, x: &Expression<F>) -> BooleanExpression<F> {
-        let nonzero = self.nonzero(x);
-        self.not(&nonzero)
+        logged!(self, "zero", [x], {
+            self.nonzero(x).negated()
+        })
     }
 
     /// x != 0
     pub fn nonzero(&mut self, x: &Expression<F>) -> BooleanExpression<F> {
+        logged!(self, "nonzero", [x], {
+            self.nonzero_with_inverse_unconstrained(x).0
+        })
+    }
+
+    /// Returns `x != 0`, along with `1 / x` if `x != 0`, or zero otherwise. This is equivalent to
+    /// calling `nonzero` and `inverse_or_zero`, but shares their constraints, so it needs three
+    /// constraints rather than five.
+    pub fn nonzero_with_inverse(
+        &mut self, x: &Expression<F>,
+    ) -> (BooleanExpression<F>, Expression<F>) {
+        logged!(self, "nonzero_with_inverse", [x], {
+            let (nonzero, m) = self.nonzero_with_inverse_unconstrained(x);
+            // If x = 0, then y = 0, so this forces m = 0.
+            self.assert_product(nonzero.negated().expression(), &m, &Expression::zero());
+            (nonzero, m)
+        })
+    }
+
+    /// Returns `y = (x != 0)` and `m`, where `m = 1 / x` if `x != 0`. If `x = 0`, `m` is
+    /// unconstrained, although the generator sets it to zero.
+    fn nonzero_with_inverse_unconstrained(
+        &mut self, x: &Expression<F>,
+    ) -> (BooleanExpression<F>, Expression<F>) {
         // See the Pinocchio paper for an explanation.
         let (y, m) = (self.wire(), self.wire());
         let (y_exp, m_exp) = (Expression::from(y), Expression::from(m));
@@ -78,8 +235,9 @@ impl<F: Field> GadgetBuilder<F> {
         self.assert_product(&(Expression::one() - &y_exp), x, &Expression::zero());
 
         let x = x.clone();
-        self.generator(
+        self.generator_with_outputs(
             x.dependencies(),
+            vec![y, m],
             move |values: &mut WireValues<F>| {
                 let x_value = x.evaluate(values);
                 let y_value = if x_value.is_nonzero() {
@@ -87,91 +245,633 @@ impl<F: Field> GadgetBuilder<F> {
                 } else {
                     Element::zero()
                 };
-                let m_value: Element<F> = if x_value.is_nonzero() {
-                    &y_value / x_value
-                } else {
-                    // The value of m doesn't matter if x = 0.
-                    Element::one()
-                };
-                values.set(m, m_value);
+                values.set(m, x_value.multiplicative_inverse_or_zero());
                 values.set(y, y_value);
             },
         );
 
         // y can only be 0 or 1 based on the constraints above.
-        BooleanExpression::new_unsafe(y_exp)
+        (BooleanExpression::new_unsafe(y_exp), m_exp)
     }
 
     /// if c { x } else { y }. Assumes c is binary.
     pub fn selection(
         &mut self, c: &BooleanExpression<F>, x: &Expression<F>, y: &Expression<F>,
     ) -> Expression<F> {
-        y + self.product(c.expression(), &(x - y))
+        logged!(self, "selection", [c.expression(), x, y], {
+            y + self.product(c.expression(), &(x - y))
+        })
     }
 
     /// Assert that x * y = z;
     pub fn assert_product(&mut self, x: &Expression<F>, y: &Expression<F>, z: &Expression<F>) {
-        self.constraints.push(Constraint {
-            a: x.clone(),
-            b: y.clone(),
-            c: z.clone(),
-        });
+        logged!(self, "assert_product", [x, y, z], {
+            if !self.unconstrained_hints.is_empty() {
+                for exp in [x, y, z] {
+                    for wire in exp.dependencies() {
+                        self.unconstrained_hints.remove(&wire);
+                    }
+                }
+            }
+            for (operand, exp) in [('a', x), ('b', y), ('c', z)] {
+                let terms = exp.num_terms();
+                self.widest_expression_terms = self.widest_expression_terms.max(terms);
+                if let Some(budget) = self.term_budget {
+                    if terms > budget {
+                        let constraint = self.constraints.len();
+                        self.warnings.push(
+                            TermBudgetWarning { constraint, operand, terms, budget });
+                    }
+                }
+            }
+            self.constraints.push(Constraint {
+                a: x.clone(),
+                b: y.clone(),
+                c: z.clone(),
+            });
+        })
+    }
+
+    /// Add a constraint of the form `a * b = c` directly from sparse lists of `(wire, coefficient)`
+    /// terms. This is intended for constraints generated by external tools; most gadgets should use
+    /// expression-based methods such as `assert_product` instead. If a wire appears multiple times
+    /// within a list, its coefficients are summed.
+    pub fn constraint_from_triples(
+        &mut self,
+        a: &[(Wire, Element<F>)],
+        b: &[(Wire, Element<F>)],
+        c: &[(Wire, Element<F>)],
+    ) {
+        logged!(self, "constraint_from_triples", [a.len(), b.len(), c.len()], {
+            let a = expression_from_terms(a);
+            let b = expression_from_terms(b);
+            let c = expression_from_terms(c);
+            self.assert_product(&a, &b, &c);
+        })
     }
 
-    /// Assert that the given quantity is in [0, 1], and return it as a `BooleanExpression`.
+    /// Assert that the given quantity is in [0, 1], and return it as a `BooleanExpression`. If `x`
+    /// is already known to be boolean, no constraint is added, so asserting booleanity of the same
+    /// expression from several code paths costs a single constraint.
     pub fn assert_boolean(&mut self, x: &Expression<F>) -> BooleanExpression<F> {
-        self.assert_product(x, &(x - Expression::one()), &Expression::zero());
-        BooleanExpression::new_unsafe(x.clone())
+        logged!(self, "assert_boolean", [x], {
+            if !self.is_known_boolean(x) {
+                self.assert_product(x, &(x - Expression::one()), &Expression::zero());
+                self.mark_boolean(x);
+                self.booleanity_constraints += 1;
+            }
+            BooleanExpression::new_unsafe(x.clone())
+        })
+    }
+
+    /// The number of booleanity constraints which have been added by `assert_boolean`, including
+    /// those added by `boolean_wire` and `binary_wire`. Redundant assertions are not counted, since
+    /// they add no constraints.
+    pub fn booleanity_constraint_count(&self) -> usize {
+        self.booleanity_constraints
+    }
+
+    /// Record that `x` is known to be boolean, so that later calls to `assert_boolean` can skip
+    /// adding a constraint. This does not add any constraints itself; the caller is responsible
+    /// for ensuring that `x` is actually constrained to be boolean.
+    pub fn mark_boolean(&mut self, x: &Expression<F>) {
+        self.known_booleans.insert(x.clone());
+    }
+
+    /// Whether `x` is known to be boolean, either because it is the constant 0 or 1, or because it
+    /// or its negation `1 - x` was previously asserted or marked to be boolean.
+    pub fn is_known_boolean(&self, x: &Expression<F>) -> bool {
+        match x.as_constant() {
+            Some(c) => c.is_zero() || c.is_one(),
+            None => self.known_booleans.contains(x)
+                || self.known_booleans.contains(&(Expression::one() - x)),
+        }
+    }
+
+    /// Record that `x` is known to fit in `bits` bits, i.e. that `x < 2^bits`. As with
+    /// `mark_boolean`, this adds no constraints, so the caller is responsible for ensuring that the
+    /// bound is actually enforced. If a tighter bound is already known, it is kept.
+    pub fn mark_bounded(&mut self, x: &Expression<F>, bits: usize) {
+        let bound = self.known_bounds.entry(x.clone()).or_insert(bits);
+        *bound = (*bound).min(bits);
+    }
+
+    /// The smallest number of bits which `x` is known to fit in, if any bound is known. Known
+    /// booleans are considered to fit in one bit.
+    pub fn known_bound(&self, x: &Expression<F>) -> Option<usize> {
+        if let Some(c) = x.as_constant() {
+            Some(c.bits())
+        } else if self.is_known_boolean(x) {
+            Some(1)
+        } else {
+            self.known_bounds.get(x).cloned()
+        }
+    }
+
+    /// Record that `x` is known to be nonzero. As with `mark_boolean`, this adds no constraints, so
+    /// the caller is responsible for ensuring that `x` is actually constrained to be nonzero.
+    pub fn mark_nonzero(&mut self, x: &Expression<F>) {
+        self.known_nonzero.insert(x.clone());
+    }
+
+    /// Whether `x` is known to be nonzero, either because it is a nonzero constant, or because it
+    /// or its negation was previously asserted or marked to be nonzero.
+    pub fn is_known_nonzero(&self, x: &Expression<F>) -> bool {
+        match x.as_constant() {
+            Some(c) => c.is_nonzero(),
+            None => self.known_nonzero.contains(x) || self.known_nonzero.contains(&-x),
+        }
     }
 
     /// Assert that x == y.
     pub fn assert_equal(&mut self, x: &Expression<F>, y: &Expression<F>) {
-        self.assert_product(x, &Expression::one(), y);
+        logged!(self, "assert_equal", [x, y], {
+            self.assert_product(x, &Expression::one(), y);
+        })
+    }
+
+    /// Assert that x == y if `condition` is set. Otherwise, no constraint is placed on x or y.
+    pub fn assert_equal_if(
+        &mut self, condition: &BooleanExpression<F>, x: &Expression<F>, y: &Expression<F>,
+    ) {
+        logged!(self, "assert_equal_if", [condition.expression(), x, y], {
+            self.assert_product(condition.expression(), &(x - y), &Expression::zero());
+        })
     }
 
     /// Assert that x != y.
     pub fn assert_nonequal(&mut self, x: &Expression<F>, y: &Expression<F>) {
-        let difference = x - y;
-        self.assert_nonzero(&difference);
+        logged!(self, "assert_nonequal", [x, y], {
+            let difference = x - y;
+            self.assert_nonzero(&difference);
+        })
+    }
+
+    /// Assert that the given expressions are pairwise distinct. The differences of all pairs are
+    /// multiplied together, and the product is asserted to be nonzero, which costs `n (n - 1) / 2`
+    /// constraints, as many as asserting each pair nonequal. Unlike with `assert_nonequal`, whose
+    /// generator panics given equal values, a witness with duplicates just fails to satisfy the
+    /// gadget.
+    pub fn assert_all_distinct(&mut self, xs: &[Expression<F>]) {
+        logged!(self, "assert_all_distinct", [xs.len()], {
+            let differences: Vec<Expression<F>> = (0..xs.len())
+                .flat_map(|i| (i + 1..xs.len()).map(move |j| &xs[i] - &xs[j]))
+                .collect();
+            let product = self.grand_product_final(&differences);
+            if product.as_constant().is_some() {
+                // This is free if the product is nonzero, and unsatisfiable otherwise.
+                self.inverse(&product);
+                return;
+            }
+
+            let product_inv = {
+                let product = product.clone();
+                self.hint(
+                    product.dependencies(), 1,
+                    move |values: &WireValues<F>| {
+                        vec![product.evaluate(values).multiplicative_inverse_or_zero()]
+                    },
+                ).wire(0)
+            };
+            self.assert_product(&product, &Expression::from(product_inv), &Expression::one());
+        })
     }
 
     /// Assert that x == 0.
     pub fn assert_zero(&mut self, x: &Expression<F>) {
-        self.assert_equal(x, &Expression::zero());
+        logged!(self, "assert_zero", [x], {
+            self.assert_equal(x, &Expression::zero());
+        })
     }
 
     /// Assert that x != 0.
     pub fn assert_nonzero(&mut self, x: &Expression<F>) {
-        // A field element is non-zero iff it has a multiplicative inverse.
-        // We don't care what the inverse is, but calling inverse(x) will require that it exists.
-        self.inverse(x);
+        logged!(self, "assert_nonzero", [x], {
+            // A field element is non-zero iff it has a multiplicative inverse.
+            // We don't care what the inverse is, but calling inverse(x) will require that it
+            // exists.
+            self.inverse(x);
+            self.mark_nonzero(x);
+        })
     }
 
     /// Assert that x == 1.
     pub fn assert_true(&mut self, x: &BooleanExpression<F>) {
-        self.assert_equal(x.expression(), &Expression::one());
+        logged!(self, "assert_true", [x.expression()], {
+            self.assert_equal(x.expression(), &Expression::one());
+        })
     }
 
     /// Assert that x == 0.
     pub fn assert_false(&mut self, x: &BooleanExpression<F>) {
-        self.assert_equal(x.expression(), &Expression::zero());
+        logged!(self, "assert_false", [x.expression()], {
+            self.assert_equal(x.expression(), &Expression::zero());
+        })
+    }
+
+    /// Run `f`, enforcing the constraints it adds only if `condition` is set. Each constraint
+    /// `a * b = c` added within the scope is rewritten as `condition * (a * b - c) = 0`. If `a` or
+    /// `b` is constant, `a * b` is linear, so this takes a single constraint. Otherwise, `a * b` is
+    /// first computed as a new product, so the rewrite takes two constraints.
+    ///
+    /// Witness generators added within the scope still run unconditionally, so they must be total:
+    /// they must not panic, even on inputs which violate the scope's constraints. For example,
+    /// `inverse` panics given zero, so it should be replaced with `inverse_or_zero` in a scope.
+    ///
+    /// Facts recorded within the scope, such as known booleans and bounds, only hold if
+    /// `condition` is set, so they are forgotten when the scope ends.
+    pub fn conditional_scope<T>(&mut self, condition: &BooleanExpression<F>, f: T)
+        where T: FnOnce(&mut GadgetBuilder<F>) {
+        self.conditional_scope_constraints(condition, f);
     }
 
-    /// Builds the gadget.
+    /// Like `conditional_scope`, but returns the scope's constraints, as they were before they
+    /// were made conditional.
+    fn conditional_scope_constraints<T>(
+        &mut self, condition: &BooleanExpression<F>, f: T,
+    ) -> Vec<Constraint<F>> where T: FnOnce(&mut GadgetBuilder<F>) {
+        logged!(self, "conditional_scope", [condition.expression()], {
+            let known_booleans = self.known_booleans.clone();
+            let known_bounds = self.known_bounds.clone();
+            let known_nonzero = self.known_nonzero.clone();
+            let start = self.constraints.len();
+            let first_scoped_entry = self.build_log().len();
+            f(self);
+            let scoped = self.constraints.split_off(start);
+            self.known_booleans = known_booleans;
+            self.known_bounds = known_bounds;
+            self.known_nonzero = known_nonzero;
+
+            // The rewrite isn't logged itself. Instead, the entries made within the scope are
+            // remapped to the rewritten constraints, with rewritten_starts[i] being the index of
+            // the first constraint which the i'th scoped constraint was rewritten to.
+            let build_log = self.build_log.take();
+            let mut rewritten_starts = Vec::with_capacity(scoped.len() + 1);
+            for Constraint { a, b, c } in &scoped {
+                rewritten_starts.push(self.constraints.len());
+                // product requires no constraint if either factor is constant.
+                let ab = self.product(a, b);
+                self.assert_product(condition.expression(), &(ab - c), &Expression::zero());
+            }
+            rewritten_starts.push(self.constraints.len());
+            self.build_log = build_log;
+
+            if let Some(log) = self.build_log.as_mut() {
+                for entry in &mut log[first_scoped_entry..] {
+                    let Range { start: entry_start, end: entry_end } = entry.constraints;
+                    entry.constraints = rewritten_starts[entry_start - start]
+                        ..rewritten_starts[entry_end - start];
+                }
+            }
+            scoped
+        })
+    }
+
+    /// Assert that the constraints added by `a` or those added by `b` are satisfied. Returns a
+    /// selector which is set iff `a`'s constraints are enforced; otherwise, `b`'s are. The
+    /// selector's generator sets it iff `a`'s constraints are satisfied by the witness.
+    ///
+    /// Each branch is run in a `conditional_scope`, so the same caveats apply: generators in both
+    /// branches always run, and must be total. In particular, the inputs of both branches must be
+    /// assigned, even though only one branch needs to be satisfied.
+    pub fn assert_or<A, B>(&mut self, a: A, b: B) -> BooleanExpression<F>
+        where A: FnOnce(&mut GadgetBuilder<F>), B: FnOnce(&mut GadgetBuilder<F>) {
+        logged!(self, "assert_or", [], {
+            let selector = self.boolean_wire();
+            let selector_exp = BooleanExpression::from(selector);
+            let a_constraints = self.conditional_scope_constraints(&selector_exp, a);
+            self.conditional_scope(&selector_exp.negated(), b);
+
+            let dependencies = a_constraints.iter()
+                .flat_map(|constraint| {
+                    let Constraint { a, b, c } = constraint;
+                    concat(&[a.dependencies(), b.dependencies(), c.dependencies()])
+                })
+                .collect();
+            self.generator_with_outputs(
+                dependencies,
+                vec![selector.wire()],
+                move |values: &mut WireValues<F>| {
+                    let satisfied = a_constraints.iter()
+                        .all(|constraint| constraint.evaluate(values));
+                    values.set_boolean(selector, satisfied);
+                },
+            );
+            selector_exp
+        })
+    }
+
+    /// Enable or disable strict builds. In a strict build, `try_build` checks every declared
+    /// generator output, not just outputs of `hint`, and returns an error if any of them appear in
+    /// no constraint. See `Gadget::unconstrained_hint_wires`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Set the maximum number of terms which each side of a constraint may have. Very wide
+    /// expressions are legal, but they are expensive for provers, and usually indicate a bug such
+    /// as an expression which grows with each iteration of a loop. Any constraint added from now
+    /// on which exceeds the budget is recorded in `warnings`. There is no budget by default.
+    pub fn set_term_budget(&mut self, max_terms: usize) {
+        self.term_budget = Some(max_terms);
+    }
+
+    /// Enable or disable strict term budgets. With a strict budget, `try_build` returns an error if
+    /// any constraint exceeded the budget set by `set_term_budget`.
+    pub fn set_term_budget_strict(&mut self, strict: bool) {
+        self.term_budget_strict = strict;
+    }
+
+    /// The warnings recorded so far, one for each side of a constraint which exceeded the budget
+    /// set by `set_term_budget`.
+    pub fn warnings(&self) -> &[TermBudgetWarning] {
+        &self.warnings
+    }
+
+    /// The number of terms in the widest side of any constraint added so far, whether or not a
+    /// term budget is set.
+    pub fn widest_expression_terms(&self) -> usize {
+        self.widest_expression_terms
+    }
+
+    /// Start recording a build log, with an entry for each call to a built-in gadget method made
+    /// from now on. Methods which only allocate wires or add generators, such as `wire` and
+    /// `hint`, aren't logged. See `build_log`. The built gadget includes the log, as
+    /// `Gadget::build_log`.
+    pub fn enable_build_log(&mut self) {
+        if self.build_log.is_none() {
+            self.build_log = Some(Vec::new());
+        }
+    }
+
+    /// The build log recorded so far, or an empty log if it was never enabled. Entries appear in
+    /// the order calls were made, so each call precedes those nested within it, and the ranges of
+    /// calls with depth 0 partition the constraints added while the log was enabled.
+    ///
+    /// Constraints added within a `conditional_scope` are rewritten when the scope ends. The scope
+    /// has its own entry, and the ranges of calls made within it are remapped to the rewritten
+    /// constraints, so they may be wider than the constraints originally added.
+    pub fn build_log(&self) -> &[LogEntry] {
+        self.build_log.as_deref().unwrap_or(&[])
+    }
+
+    /// Begin a build log entry for a call to `method`, if the log is enabled, returning the
+    /// entry's index. `inputs` is only called if the log is enabled.
+    pub(crate) fn log_start<T>(&mut self, method: &'static str, inputs: T) -> Option<usize>
+        where T: FnOnce() -> Vec<String> {
+        let log = self.build_log.as_mut()?;
+        let start = self.constraints.len();
+        log.push(LogEntry {
+            method,
+            inputs: inputs(),
+            depth: self.build_log_depth,
+            constraints: start..start,
+        });
+        self.build_log_depth += 1;
+        Some(log.len() - 1)
+    }
+
+    /// End the build log entry returned by `log_start`, recording the constraints added since.
+    pub(crate) fn log_end(&mut self, entry: Option<usize>) {
+        if let (Some(index), Some(log)) = (entry, self.build_log.as_mut()) {
+            log[index].constraints.end = self.constraints.len();
+            self.build_log_depth -= 1;
+        }
+    }
+
+    /// Builds the gadget. Panics if any outputs of `hint` were never constrained.
     pub fn build(self) -> Gadget<F> {
-        Gadget {
+        match self.try_build() {
+            Ok(gadget) => gadget,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Check this builder's constraints and generators for signs of malformed gadgets, such as
+    /// references to wires this builder never allocated. Issues for which `is_error` is true are
+    /// always bugs; the rest are suspicious but can be legitimate, such as a generator reading an
+    /// input wire which nothing constrains.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let allocated = |wire: &Wire| wire.index < self.next_wire_index;
+        let mut issues = Vec::new();
+        let mut constrained = BTreeSet::new();
+
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            for expression in [&constraint.a, &constraint.b, &constraint.c] {
+                for (&wire, coefficient) in expression.iter() {
+                    if !allocated(&wire) {
+                        issues.push(ValidationIssue::UnallocatedWire { constraint: i, wire });
+                    }
+                    if coefficient.is_zero() {
+                        issues.push(ValidationIssue::ZeroCoefficient { constraint: i, wire });
+                    }
+                    constrained.insert(wire);
+                }
+            }
+            if constraint.a.l0_norm() == 0 && constraint.b.l0_norm() == 0 {
+                issues.push(ValidationIssue::ConstantProduct { constraint: i });
+            }
+        }
+
+        for (i, generator) in self.witness_generators.iter().enumerate() {
+            for &wire in generator.inputs() {
+                if !allocated(&wire) {
+                    issues.push(ValidationIssue::UnallocatedDependency { generator: i, wire });
+                } else if !constrained.contains(&wire) && wire != Wire::ONE {
+                    issues.push(ValidationIssue::UnconstrainedDependency { generator: i, wire });
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Builds the gadget, or returns an error if any outputs of `hint` were never constrained. In a
+    /// strict build (see `set_strict`), this also applies to all declared generator outputs. With a
+    /// strict term budget (see `set_term_budget_strict`), this also returns an error if any
+    /// constraint exceeded the budget.
+    ///
+    /// In debug builds, this also panics if `validate` reports any errors.
+    pub fn try_build(self) -> Result<Gadget<F>, BuildError> {
+        #[cfg(debug_assertions)]
+        {
+            let errors: Vec<String> = self.validate().iter()
+                .filter(|issue| issue.is_error())
+                .map(|issue| format!("{}", issue))
+                .collect();
+            assert!(errors.is_empty(), "Invalid gadget: {}", join("; ", &errors));
+        }
+
+        let mut unconstrained = self.unconstrained_hints;
+        let gadget = Gadget {
             constraints: self.constraints,
             witness_generators: self.witness_generators,
+            wire_kinds: self.wire_kinds,
+            build_log: self.build_log,
+        };
+        if self.strict {
+            unconstrained.extend(gadget.unconstrained_hint_wires());
+        }
+        if !unconstrained.is_empty() {
+            let wires = unconstrained.into_iter().collect();
+            return Err(BuildError::UnconstrainedHints(UnconstrainedHintError { wires }));
+        }
+        if self.term_budget_strict && !self.warnings.is_empty() {
+            return Err(BuildError::TermBudgetExceeded(self.warnings));
+        }
+        Ok(gadget)
+    }
+}
+
+/// The output wires of a hint added with `GadgetBuilder::hint`.
+#[derive(Clone, Debug)]
+pub struct HintHandle {
+    wires: Vec<Wire>,
+}
+
+impl HintHandle {
+    /// All output wires of the hint, in the order their values are computed.
+    pub fn wires(&self) -> &[Wire] {
+        &self.wires
+    }
+
+    /// The `i`th output wire of the hint.
+    pub fn wire(&self, i: usize) -> Wire {
+        self.wires[i]
+    }
+}
+
+/// An error indicating that some outputs of `GadgetBuilder::hint` do not appear in any constraint,
+/// so a prover could assign them arbitrary values.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnconstrainedHintError {
+    /// The unconstrained hint outputs.
+    pub wires: Vec<Wire>,
+}
+
+impl fmt::Display for UnconstrainedHintError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let wires: Vec<String> = self.wires.iter().map(|wire| format!("{}", wire)).collect();
+        write!(f, "Hinted wires never constrained: {}", join(", ", &wires))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnconstrainedHintError {}
+
+/// An error returned by `GadgetBuilder::try_build`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BuildError {
+    /// Some outputs of `hint`, or in a strict build any generator outputs, were never constrained.
+    UnconstrainedHints(UnconstrainedHintError),
+    /// Some constraints exceeded a strict term budget.
+    TermBudgetExceeded(Vec<TermBudgetWarning>),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            BuildError::UnconstrainedHints(error) => write!(f, "{}", error),
+            BuildError::TermBudgetExceeded(warnings) => {
+                let warnings: Vec<String> = warnings.iter()
+                    .map(|warning| format!("{}", warning))
+                    .collect();
+                write!(f, "Term budget exceeded: {}", join("; ", &warnings))
+            }
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+/// A warning that one side of a constraint has more terms than the budget set with
+/// `GadgetBuilder::set_term_budget`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TermBudgetWarning {
+    /// The index of the constraint, in the order constraints were added.
+    pub constraint: usize,
+    /// The side of the constraint `a * b = c` which is too wide: `'a'`, `'b'` or `'c'`.
+    pub operand: char,
+    /// The number of terms on that side, including any constant term.
+    pub terms: usize,
+    /// The budget which was exceeded.
+    pub budget: usize,
+}
+
+impl fmt::Display for TermBudgetWarning {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Constraint {} has {} terms in {}, exceeding the budget of {}",
+               self.constraint, self.terms, self.operand, self.budget)
+    }
+}
+
+/// A problem found by `GadgetBuilder::validate`. Constraints and generators are identified by their
+/// index, in the order they were added.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValidationIssue {
+    /// A constraint refers to a wire which the builder never allocated.
+    UnallocatedWire { constraint: usize, wire: Wire },
+    /// A constraint contains a term with a zero coefficient, which should have been filtered out.
+    ZeroCoefficient { constraint: usize, wire: Wire },
+    /// Both multiplicands of a constraint are constants, so the product could have been folded
+    /// into a linear constraint.
+    ConstantProduct { constraint: usize },
+    /// A generator depends on a wire which the builder never allocated, so it can never run.
+    UnallocatedDependency { generator: usize, wire: Wire },
+    /// A generator depends on a wire which appears in no constraint.
+    UnconstrainedDependency { generator: usize, wire: Wire },
+}
+
+impl ValidationIssue {
+    /// Whether this issue always indicates a bug, as opposed to a warning.
+    pub fn is_error(&self) -> bool {
+        match self {
+            ValidationIssue::UnallocatedWire { .. } => true,
+            ValidationIssue::ZeroCoefficient { .. } => true,
+            ValidationIssue::ConstantProduct { .. } => false,
+            ValidationIssue::UnallocatedDependency { .. } => true,
+            ValidationIssue::UnconstrainedDependency { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::UnallocatedWire { constraint, wire } =>
+                write!(f, "Constraint {} refers to unallocated wire {}", constraint, wire),
+            ValidationIssue::ZeroCoefficient { constraint, wire } =>
+                write!(f, "Constraint {} has a zero coefficient for {}", constraint, wire),
+            ValidationIssue::ConstantProduct { constraint } =>
+                write!(f, "Constraint {} is a product of constants", constraint),
+            ValidationIssue::UnallocatedDependency { generator, wire } =>
+                write!(f, "Generator {} depends on unallocated wire {}", generator, wire),
+            ValidationIssue::UnconstrainedDependency { generator, wire } =>
+                write!(f, "Generator {} depends on unconstrained wire {}", generator, wire),
+        }
+    }
+}
+
+/// Builds an expression from a list of `(wire, coefficient)` terms, summing any duplicates.
+fn expression_from_terms<F: Field>(terms: &[(Wire, Element<F>)]) -> Expression<F> {
+    let mut coefficients = BTreeMap::new();
+    for (wire, coefficient) in terms {
+        *coefficients.entry(*wire).or_insert_with(Element::zero) += coefficient;
+    }
+    Expression::new(coefficients)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::expression::{BooleanExpression, Expression};
+    use crate::expression::{BinaryExpression, BooleanExpression, Expression};
     use crate::field::Element;
-    use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::{assert_eq_false, assert_eq_true, F257};
+    use crate::gadget_builder::{BuildError, GadgetBuilder, TermBudgetWarning,
+                                UnconstrainedHintError, ValidationIssue};
+    use crate::fields::small::F257;
+    use crate::test_util::{assert_eq_false, assert_eq_true};
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
 
     #[test]
     fn assert_binary_0_1() {
@@ -201,6 +901,62 @@ mod tests {
         assert!(!gadget.execute(&mut values2));
     }
 
+    #[test]
+    fn assert_boolean_twice() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x_wire = builder.wire();
+        let x = Expression::from(x_wire);
+        builder.assert_boolean(&x);
+        builder.assert_boolean(&x);
+        assert_eq!(1, builder.booleanity_constraint_count());
+        let gadget = builder.build();
+        assert_eq!(1, gadget.size());
+
+        // The remaining constraint must still reject non-boolean values.
+        let mut values2 = values!(x_wire => 2u8.into());
+        assert!(!gadget.execute(&mut values2));
+    }
+
+    #[test]
+    fn assert_boolean_negation() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.boolean_wire();
+        assert_eq!(1, builder.booleanity_constraint_count());
+        let not_x = Expression::one() - Expression::from(x.wire());
+        assert!(builder.is_known_boolean(&not_x));
+        builder.assert_boolean(&not_x);
+        assert_eq!(1, builder.booleanity_constraint_count());
+        assert_eq!(1, builder.build().size());
+    }
+
+    #[test]
+    fn assert_boolean_unmarked() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let y = builder.wire();
+        builder.assert_boolean(&Expression::from(x));
+        assert!(!builder.is_known_boolean(&Expression::from(y)));
+        assert!(!builder.is_known_boolean(&(Expression::from(x) * 2u128)));
+        builder.assert_boolean(&Expression::from(y));
+        let gadget = builder.build();
+        assert_eq!(2, gadget.size());
+
+        // The constraint on y must not have been skipped.
+        let mut values = values!(x => 1u8.into(), y => 2u8.into());
+        assert!(!gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn boolean_wire_is_known_boolean() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.boolean_wire();
+        assert!(builder.is_known_boolean(&Expression::from(x.wire())));
+        assert!(builder.is_known_boolean(&Expression::one()));
+        assert_eq!(Some(1), builder.known_bound(&Expression::from(x.wire())));
+        builder.assert_boolean(&Expression::from(x.wire()));
+        assert_eq!(1, builder.build().size());
+    }
+
     #[test]
     fn selection() {
         let mut builder = GadgetBuilder::<F257>::new();
@@ -241,4 +997,378 @@ mod tests {
         assert!(gadget.execute(&mut values_7_13));
         assert_eq_false(&equal, &values_7_13);
     }
+
+    #[test]
+    fn nonzero_with_inverse() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let (nonzero, inverse) = builder.nonzero_with_inverse(&Expression::from(x));
+        let gadget = builder.build();
+        assert_eq!(3, gadget.size());
+
+        let mut values_0 = values!(x => 0u8.into());
+        assert!(gadget.execute(&mut values_0));
+        assert_eq_false(&nonzero, &values_0);
+        assert_eq!(Element::zero(), inverse.evaluate(&values_0));
+
+        let mut values_5 = values!(x => 5u8.into());
+        assert!(gadget.execute(&mut values_5));
+        assert_eq_true(&nonzero, &values_5);
+        assert_eq!(Element::from(5u8).multiplicative_inverse(), inverse.evaluate(&values_5));
+    }
+
+    #[test]
+    fn nonzero_with_inverse_fewer_constraints() {
+        let mut separate_builder = GadgetBuilder::<F257>::new();
+        let x = Expression::from(separate_builder.wire());
+        separate_builder.nonzero(&x);
+        separate_builder.inverse_or_zero(&x);
+
+        let mut combined_builder = GadgetBuilder::<F257>::new();
+        let x = Expression::from(combined_builder.wire());
+        combined_builder.nonzero_with_inverse(&x);
+
+        assert!(combined_builder.build().size() < separate_builder.build().size());
+    }
+
+    #[test]
+    fn assert_all_distinct() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (wires, exps) = builder.expression_wires(4);
+        builder.assert_all_distinct(&exps);
+        let gadget = builder.build();
+        assert_eq!(6, gadget.size());
+
+        let values = |xs: [u8; 4]| {
+            let mut values = WireValues::new();
+            for (&wire, x) in wires.iter().zip(xs) {
+                values.set(wire, Element::from(x));
+            }
+            values
+        };
+        assert!(gadget.execute(&mut values([1, 2, 3, 0])));
+        assert!(!gadget.execute(&mut values([1, 2, 3, 1])));
+        assert!(!gadget.execute(&mut values([7, 7, 7, 7])));
+    }
+
+    #[test]
+    fn assert_all_distinct_constants() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.assert_all_distinct(&[Expression::from(1u8), Expression::from(2u8)]);
+        assert_eq!(0, builder.build().size());
+
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.assert_all_distinct(&[Expression::from(1u8), Expression::from(1u8)]);
+        let gadget = builder.build();
+        assert!(!gadget.execute(&mut WireValues::new()));
+    }
+
+    #[test]
+    fn nonzero_with_inverse_rejects_nonzero_inverse_of_zero() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let (_nonzero, inverse) = builder.nonzero_with_inverse(&Expression::from(x));
+        let gadget = builder.build();
+
+        // Claim a nonzero inverse for x = 0. Wire::ONE is set by WireValues::new.
+        let mut values = values!(x => 0u8.into());
+        assert!(gadget.execute(&mut values));
+        let mut forged = WireValues::new();
+        for (&wire, value) in values.as_map() {
+            if Expression::from(wire) == inverse {
+                forged.set(wire, Element::one());
+            } else if wire != Wire::ONE {
+                forged.set(wire, value.clone());
+            }
+        }
+        assert!(!gadget.constraints.iter().all(|c| c.evaluate(&forged)));
+    }
+
+    #[test]
+    fn allocate_wires_contiguous() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let w = builder.wire();
+        let range = builder.allocate_wires(3);
+        assert_eq!(w.index + 1..w.index + 4, range);
+        assert_eq!(w.index + 4, builder.wire().index);
+    }
+
+    #[test]
+    fn constraints_from_triples() {
+        // Build x / y, i.e. the constraints y * y_inv = 1 and x * y_inv = q, from raw triples.
+        let mut manual_builder = GadgetBuilder::<F257>::new();
+        let range = manual_builder.allocate_wires(4);
+        let [x, y, y_inv, q] = [0, 1, 2, 3].map(|i| Wire { index: range.start + i });
+        let one = Element::one;
+        manual_builder.constraint_from_triples(
+            &[(y, one())], &[(y_inv, one())], &[(Wire::ONE, one())]);
+        // Duplicate terms should be summed, so this is just x.
+        manual_builder.constraint_from_triples(
+            &[(x, Element::from(2u8)), (x, -one())], &[(y_inv, one())], &[(q, one())]);
+        let manual_gadget = manual_builder.build();
+
+        let mut values = values!(x => 6u8.into(), y => 3u8.into(),
+            y_inv => Element::from(3u8).multiplicative_inverse(), q => 2u8.into());
+        assert!(manual_gadget.execute(&mut values));
+
+        // Compare to the same constraints built with expression-based methods.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.quotient(&Expression::from(x), &Expression::from(y));
+        let gadget = builder.build();
+
+        assert_eq!(gadget.size(), manual_gadget.size());
+        for (expected, actual) in gadget.constraints.iter().zip(manual_gadget.constraints.iter()) {
+            assert_eq!(expected.a(), actual.a());
+            assert_eq!(expected.b(), actual.b());
+            assert_eq!(expected.c(), actual.c());
+        }
+    }
+
+    #[test]
+    fn hint_constrained() {
+        // Hint the square root of x, and verify it by squaring.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let hint = builder.hint(vec![x], 1, move |values: &WireValues<F257>| {
+            vec![values.get(x).square_root().unwrap()]
+        });
+        let root = Expression::from(hint.wire(0));
+        builder.assert_product(&root, &root, &Expression::from(x));
+        let gadget = builder.try_build().unwrap();
+
+        let mut values = values!(x => 9u8.into());
+        assert!(gadget.execute(&mut values));
+        let root_value = values.get(hint.wire(0)).clone();
+        assert_eq!(Element::from(9u8), &root_value * &root_value);
+    }
+
+    #[test]
+    fn hint_unconstrained() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let hint = builder.hint(vec![x], 2, move |values: &WireValues<F257>| {
+            vec![values.get(x).clone(), values.get(x).clone()]
+        });
+        // Only the first output is constrained.
+        builder.assert_equal(&Expression::from(hint.wire(0)), &Expression::from(x));
+
+        let error = builder.try_build().err().unwrap();
+        assert_eq!(BuildError::UnconstrainedHints(
+            UnconstrainedHintError { wires: vec![hint.wire(1)] }), error);
+    }
+
+    #[test]
+    #[should_panic(expected = "Hinted wires never constrained")]
+    fn hint_unconstrained_build() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        builder.hint(vec![x], 1, move |values: &WireValues<F257>| vec![values.get(x).clone()]);
+        builder.build();
+    }
+
+    #[test]
+    fn conditional_scope() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let condition = builder.boolean_wire();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let (x_exp, y_exp, z_exp) = (Expression::from(x), Expression::from(y), Expression::from(z));
+        builder.conditional_scope(&BooleanExpression::from(condition), |builder| {
+            // Nonlinear, so rewritten as two constraints.
+            builder.assert_product(&x_exp, &y_exp, &z_exp);
+            // Linear, so rewritten as one constraint.
+            builder.assert_equal(&x_exp, &Expression::from(3u8));
+        });
+        let gadget = builder.build();
+        assert_eq!(1 + 2 + 1, gadget.size());
+
+        let values = |c: bool, x_value: u8, z_value: u8| {
+            let mut values = values!(x => x_value.into(), y => 5u8.into(), z => z_value.into());
+            values.set_boolean(condition, c);
+            values
+        };
+        assert!(gadget.execute(&mut values(true, 3, 15)));
+        assert!(!gadget.execute(&mut values(true, 3, 16)));
+        assert!(!gadget.execute(&mut values(true, 4, 20)));
+        assert!(gadget.execute(&mut values(false, 4, 16)));
+    }
+
+    #[test]
+    fn conditional_scope_forgets_facts() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let condition = builder.boolean_wire();
+        let x = Expression::from(builder.wire());
+        builder.conditional_scope(&BooleanExpression::from(condition), |builder| {
+            builder.assert_boolean(&x);
+            assert!(builder.is_known_boolean(&x));
+        });
+        assert!(!builder.is_known_boolean(&x));
+    }
+
+    #[test]
+    fn assert_or() {
+        // Assert that x^2 = 9 or y^3 = 8.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+        let selector = builder.assert_or(
+            |builder| builder.assert_product(&x_exp, &x_exp, &Expression::from(9u8)),
+            |builder| {
+                let y_squared = builder.product(&y_exp, &y_exp);
+                builder.assert_product(&y_squared, &y_exp, &Expression::from(8u8));
+            });
+        let gadget = builder.build();
+
+        // One constraint for the selector's booleanity, and two for each nonlinear constraint.
+        assert_eq!(1 + 2 + 2 * 2, gadget.size());
+
+        let mut values = values!(x => 3u8.into(), y => 5u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq_true(&selector, &values);
+
+        let mut values = values!(x => 4u8.into(), y => 2u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq_false(&selector, &values);
+
+        let mut values = values!(x => 3u8.into(), y => 2u8.into());
+        assert!(gadget.execute(&mut values));
+
+        // Neither holds.
+        assert!(!gadget.execute(&mut values!(x => 4u8.into(), y => 5u8.into())));
+    }
+
+    #[test]
+    fn strict_build() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.set_strict(true);
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.generator_with_outputs(vec![x], vec![y], move |values: &mut WireValues<F257>| {
+            let value = values.get(x).clone();
+            values.set(y, value);
+        });
+        builder.le(&Expression::from(x), &Expression::from(7u8));
+        let error = builder.try_build().err().unwrap();
+        assert_eq!(BuildError::UnconstrainedHints(UnconstrainedHintError { wires: vec![y] }),
+                   error);
+    }
+
+    #[test]
+    fn strict_build_clean() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.set_strict(true);
+        let x = builder.wire();
+        builder.le(&Expression::from(x), &Expression::from(7u8));
+        builder.inverse(&Expression::from(x));
+        assert!(builder.try_build().is_ok());
+    }
+
+    #[test]
+    fn term_budget_warning() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.set_term_budget(1000);
+        let (_wires, exps) = builder.expression_wires(5000);
+        // Accumulating into one expression, rather than constraining partial sums, makes a single
+        // constraint with every term.
+        let mut sum = Expression::zero();
+        for exp in &exps {
+            sum += exp;
+        }
+        let total = builder.wire();
+        builder.assert_equal(&sum, &Expression::from(total));
+        assert_eq!(
+            &[TermBudgetWarning { constraint: 0, operand: 'a', terms: 5000, budget: 1000 }],
+            builder.warnings());
+        assert_eq!(5000, builder.widest_expression_terms());
+        assert_eq!("Constraint 0 has 5000 terms in a, exceeding the budget of 1000",
+                   format!("{}", builder.warnings()[0]));
+        assert!(builder.try_build().is_ok());
+    }
+
+    #[test]
+    fn term_budget_clean() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.set_term_budget(8);
+        let x = builder.binary_wire(4);
+        let y = builder.binary_wire(4);
+        builder.binary_sum(&BinaryExpression::from(&x), &BinaryExpression::from(&y));
+        assert!(builder.warnings().is_empty());
+        assert!(builder.widest_expression_terms() <= 8);
+    }
+
+    #[test]
+    fn term_budget_strict() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.set_term_budget(2);
+        builder.set_term_budget_strict(true);
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let sum = Expression::from(x) + Expression::from(y) + Expression::from(z);
+        builder.assert_zero(&sum);
+        let warning = TermBudgetWarning { constraint: 0, operand: 'a', terms: 3, budget: 2 };
+        assert_eq!(BuildError::TermBudgetExceeded(vec![warning]),
+                   builder.try_build().err().unwrap());
+    }
+
+    #[test]
+    fn validate_clean() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.quotient(&Expression::from(x), &Expression::from(y));
+        assert_eq!(Vec::<ValidationIssue>::new(), builder.validate());
+    }
+
+    #[test]
+    fn validate_unallocated_wire() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let unallocated = Wire { index: 7 };
+        builder.constraint_from_triples(
+            &[(x, Element::one())], &[(unallocated, Element::one())], &[]);
+        assert_eq!(vec![ValidationIssue::UnallocatedWire { constraint: 0, wire: unallocated }],
+                   builder.validate());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Constraint 0 refers to unallocated wire w7")]
+    fn validate_unallocated_wire_build() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.assert_zero(&Expression::from(Wire { index: 7 }));
+        builder.build();
+    }
+
+    #[test]
+    fn validate_zero_coefficient() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let zero_one = Expression::new_unfiltered(
+            [(x, Element::one()), (Wire::ONE, Element::zero())].iter().cloned().collect());
+        builder.assert_zero(&zero_one);
+        assert_eq!(vec![ValidationIssue::ZeroCoefficient { constraint: 0, wire: Wire::ONE }],
+                   builder.validate());
+    }
+
+    #[test]
+    fn validate_constant_product() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        builder.assert_product(
+            &Expression::from(2u8), &Expression::from(3u8), &Expression::from(x));
+        let issues = builder.validate();
+        assert_eq!(vec![ValidationIssue::ConstantProduct { constraint: 0 }], issues);
+        assert!(!issues[0].is_error());
+        builder.build();
+    }
+
+    #[test]
+    fn validate_generator_dependencies() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        builder.assert_boolean(&Expression::from(x));
+        let unallocated = Wire { index: 9 };
+        builder.generator(vec![x, y, unallocated], |_values| {});
+        assert_eq!(vec![
+            ValidationIssue::UnconstrainedDependency { generator: 0, wire: y },
+            ValidationIssue::UnallocatedDependency { generator: 0, wire: unallocated },
+        ], builder.validate());
+    }
 }
@@ -2,8 +2,9 @@
 use alloc::vec::Vec;
 
 use itertools::Itertools;
+use num::BigUint;
 
-use crate::{Element, Expression, Field, GadgetBuilder, WireValues};
+use crate::{BinaryExpression, Element, Expression, Field, GadgetBuilder, WireValues};
 
 /// A symmetric-key block cipher.
 pub trait BlockCipher<F: Field> {
@@ -55,6 +56,33 @@ pub trait CompressionFunction<F: Field> {
     }
 }
 
+/// A function which compresses two binary digests into one, and is intended to be one-way. Unlike
+/// `CompressionFunction`, digests may be wider than a single field element, which suits binary
+/// hashes such as SHA-256.
+pub trait BinaryCompressionFunction<F: Field> {
+    /// The number of bits in each digest, including the inputs and output of `compress`.
+    fn digest_bits(&self) -> usize;
+
+    /// Compress two digests of `digest_bits` bits each into one.
+    fn compress(
+        &self, builder: &mut GadgetBuilder<F>, x: &BinaryExpression<F>, y: &BinaryExpression<F>,
+    ) -> BinaryExpression<F>;
+
+    /// Like `compress`, but actually evaluates the compression function rather than just adding it
+    /// to a `GadgetBuilder`.
+    fn compress_evaluate(&self, x: &BigUint, y: &BigUint) -> BigUint {
+        let bits = self.digest_bits();
+        let mut builder = GadgetBuilder::new();
+        let compressed = self.compress(
+            &mut builder,
+            &BinaryExpression::from_biguint_padded(x, bits),
+            &BinaryExpression::from_biguint_padded(y, bits));
+        let mut values = WireValues::new();
+        builder.build().execute(&mut values);
+        compressed.evaluate(&values)
+    }
+}
+
 /// A permutation of single field elements.
 pub trait Permutation<F: Field> {
     /// Permute the given field element.
@@ -95,6 +123,10 @@ pub trait MultiPermutation<F: Field> {
 
     /// Like `permute`, but actually evaluates the permutation rather than just adding it to a
     /// `GadgetBuilder`.
+    ///
+    /// The default implementation builds and executes a `permute` gadget. Implementations can
+    /// instead operate on the elements directly, which is much faster, as long as the results are
+    /// identical.
     fn permute_evaluate(&self, inputs: &[Element<F>]) -> Vec<Element<F>> {
         let mut builder = GadgetBuilder::new();
         let input_expressions = inputs.iter().map(Expression::from).collect_vec();
@@ -134,4 +166,38 @@ pub trait HashFunction<F: Field> {
         builder.build().execute(&mut values);
         hash.evaluate(&values)
     }
-}
\ No newline at end of file
+}
+
+/// A function which hashes a sequence of field elements, outputting any number of field elements,
+/// for protocols which derive several values, such as a key and a nonce, from one input.
+pub trait WideHashFunction<F: Field> {
+    /// Hash the given blocks, outputting `output_len` elements.
+    fn hash_wide(
+        &self, builder: &mut GadgetBuilder<F>, blocks: &[Expression<F>], output_len: usize,
+    ) -> Vec<Expression<F>>;
+
+    /// Like `hash_wide`, but actually evaluates the hash function rather than just adding it to a
+    /// `GadgetBuilder`.
+    fn hash_wide_evaluate(&self, blocks: &[Element<F>], output_len: usize) -> Vec<Element<F>> {
+        let mut builder = GadgetBuilder::new();
+        let block_expressions = blocks.iter().map(Expression::from).collect_vec();
+        let hash = self.hash_wide(&mut builder, &block_expressions, output_len);
+        let mut values = WireValues::new();
+        builder.build().execute(&mut values);
+        hash.iter().map(|exp| exp.evaluate(&values)).collect()
+    }
+}
+
+/// Adapts a `WideHashFunction` into a `HashFunction`, whose output is the first element of the
+/// wide output.
+pub struct FirstOutputHash<WH>(pub WH);
+
+impl<F: Field, WH: WideHashFunction<F>> HashFunction<F> for FirstOutputHash<WH> {
+    fn hash(&self, builder: &mut GadgetBuilder<F>, blocks: &[Expression<F>]) -> Expression<F> {
+        self.0.hash_wide(builder, blocks, 1).remove(0)
+    }
+
+    fn hash_evaluate(&self, blocks: &[Element<F>]) -> Element<F> {
+        self.0.hash_wide_evaluate(blocks, 1).remove(0)
+    }
+}
@@ -0,0 +1,394 @@
+//! This module extends GadgetBuilder with GLV-style scalar multiplication, which uses an
+//! efficiently computable endomorphism to split a scalar into two half-length scalars.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::{BigInt, BigUint, Integer, One, Signed};
+
+use crate::expression::{BinaryExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::group::{mul_two_scalars, Group};
+use crate::wire_values::WireValues;
+use crate::Evaluable;
+
+/// An efficiently computable endomorphism `φ` of a group, which acts on a subgroup of prime order
+/// `n` as multiplication by a constant `λ`, i.e. `φ(P) = λ P` for every `P` in the subgroup.
+///
+/// Such an endomorphism lets a scalar `k` be decomposed as `k = k1 + λ k2 (mod n)` with `k1` and
+/// `k2` roughly half as long as `n`, so that `k P = k1 P + k2 φ(P)` can be computed with half as
+/// many doublings.
+pub trait Endomorphism<F: Field, G: Group<F>> {
+    /// The prime order `n` of the subgroup on which `φ` acts as multiplication by `λ`.
+    fn subgroup_order(&self) -> BigUint;
+
+    /// The eigenvalue `λ`.
+    fn eigenvalue(&self) -> BigUint;
+
+    /// Two short, linearly independent vectors `(a, b)` with `a + λ b = 0 (mod n)`, which form a
+    /// basis of the lattice of all such vectors. These are typically found with the extended
+    /// Euclidean algorithm, as described in the GLV paper.
+    fn lattice_basis(&self) -> [(BigInt, BigInt); 2];
+
+    /// Apply `φ` to a point in constraints.
+    fn apply_expression(
+        &self, builder: &mut GadgetBuilder<F>, point: &G::GroupExpression,
+    ) -> G::GroupExpression;
+
+    /// Like `apply_expression`, but actually evaluates `φ` rather than just adding it to a
+    /// `GadgetBuilder`.
+    fn apply_element(&self, point: &G::GroupElement) -> G::GroupElement {
+        let mut builder = GadgetBuilder::new();
+        let image = self.apply_expression(&mut builder, &G::GroupExpression::from(point));
+        let mut values = WireValues::new();
+        builder.build().execute(&mut values);
+        image.evaluate(&values)
+    }
+
+    /// The number of bits in each half of a decomposition returned by `decompose_scalar`.
+    fn decomposition_bits(&self) -> usize {
+        let bound = decomposition_bound(&self.lattice_basis());
+        (bound << 1u8).bits() as usize
+    }
+
+    /// Decompose `k` as `k1 + λ k2 (mod n)`, returning `(k1, k2)`. Both are non-negative and fit
+    /// in `decomposition_bits` bits.
+    fn decompose_scalar(&self, k: &BigUint) -> (BigUint, BigUint) {
+        decompose(k, &self.subgroup_order(), &self.eigenvalue(), &self.lattice_basis())
+    }
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Multiply `point` by `scalar`, using the given endomorphism to decompose the scalar into two
+    /// half-length scalars if there is one. Otherwise, this falls back to the generic
+    /// `Group::mul_scalar_expression`.
+    ///
+    /// `point` must lie in the subgroup on which the endomorphism acts as multiplication by `λ`.
+    /// As with `mul_scalar_expression`, the scalar is split with `split_allowing_ambiguity`.
+    ///
+    /// The decomposition is checked with native field arithmetic, which requires the subgroup
+    /// order to be somewhat smaller than the field; this panics if it is too large. Subgroups
+    /// nearly as large as the field would need limb arithmetic, as in `assert_coprime_to_constant`.
+    pub fn mul_scalar_glv<G: Group<F>>(
+        &mut self,
+        point: &G::GroupExpression,
+        scalar: &Expression<F>,
+        endomorphism: Option<&dyn Endomorphism<F, G>>,
+    ) -> G::GroupExpression {
+        logged!(self, "mul_scalar_glv", [scalar], {
+            let endomorphism = match endomorphism {
+                Some(endomorphism) => endomorphism,
+                None => return G::mul_scalar_expression(self, point, scalar),
+            };
+            let (k1, k2) = self.decompose_scalar_glv(scalar, endomorphism);
+
+            // We use Shamir's trick with the table [0, P, φ(P), P + φ(P)].
+            let image = endomorphism.apply_expression(self, point);
+            mul_two_scalars::<F, G>(self, point, &k1, &image, &k2)
+        })
+    }
+
+    /// Decompose `scalar` as `k1 + λ k2 (mod n)`, returning the bits of `k1` and `k2`.
+    fn decompose_scalar_glv<G: Group<F>>(
+        &mut self, scalar: &Expression<F>, endomorphism: &dyn Endomorphism<F, G>,
+    ) -> (BinaryExpression<F>, BinaryExpression<F>) {
+        let (k1, k2, _quotient) = self.decompose_scalar_glv_with_quotient(scalar, endomorphism);
+        (k1, k2)
+    }
+
+    /// Like `decompose_scalar_glv`, but also returns the bits of the offset quotient, for tests.
+    fn decompose_scalar_glv_with_quotient<G: Group<F>>(
+        &mut self, scalar: &Expression<F>, endomorphism: &dyn Endomorphism<F, G>,
+    ) -> (BinaryExpression<F>, BinaryExpression<F>, BinaryExpression<F>) {
+        let n = endomorphism.subgroup_order();
+        let lambda = endomorphism.eigenvalue();
+        let basis = endomorphism.lattice_basis();
+        let half_bits = endomorphism.decomposition_bits();
+
+        // We reduce k and λ k2 modulo n with per-bit weights, so that every term stays below n:
+        // k = sum(k_i (2^i mod n)) and λ k2 = sum(k2_i (λ 2^i mod n)).
+        let scalar_bits = self.split_allowing_ambiguity(scalar);
+        let k_weights: Vec<BigUint> = (0..scalar_bits.len())
+            .map(|i| (BigUint::one() << i) % &n)
+            .collect();
+        let k2_weights: Vec<BigUint> = (0..half_bits).map(|i| (&lambda << i) % &n).collect();
+
+        // The difference k1 + λ k2 - k lies in [-k_max, l_max], so it equals q n for some q in
+        // [-q_offset, q_max]. We range check q + q_offset.
+        let k_max: BigUint = k_weights.iter().sum();
+        let l_max = (BigUint::one() << half_bits) - 1u8 + k2_weights.iter().sum::<BigUint>();
+        let q_offset = Integer::div_ceil(&k_max, &n);
+        let q_max = &l_max / &n;
+        let quotient_bits = (&q_offset + &q_max).bits() as usize;
+
+        // Once q is range checked, the difference k1 + λ k2 - k - q n lies in a range of this
+        // width. If it is less than |F|, the difference can't wrap around to zero.
+        let width = &k_max + &l_max + ((BigUint::one() << quotient_bits) - 1u8) * &n;
+        assert!(width < F::order(),
+                "The subgroup order is too large to check the decomposition natively");
+
+        let outputs = {
+            let scalar = scalar.clone();
+            let (n, lambda, q_offset) = (n.clone(), lambda.clone(), q_offset.clone());
+            let (k_weights, k2_weights) = (k_weights.clone(), k2_weights.clone());
+            self.hint(
+                scalar.dependencies(), 2 * half_bits + quotient_bits,
+                move |values: &WireValues<F>| {
+                    let k = scalar.evaluate(values).to_biguint().clone();
+                    let (k1, k2) = decompose(&k, &n, &lambda, &basis);
+                    let k_reduced: BigUint = weighted_bits(&k, &k_weights);
+                    let l = BigInt::from(k1.clone()) + BigInt::from(weighted_bits(&k2, &k2_weights))
+                        - BigInt::from(k_reduced);
+                    let q = l / BigInt::from(n.clone()) + BigInt::from(q_offset.clone());
+                    let q = q.to_biguint().unwrap();
+
+                    let mut outputs = Vec::new();
+                    for (value, bits) in &[(k1, half_bits), (k2, half_bits), (q, quotient_bits)] {
+                        outputs.extend((0..*bits).map(|i| Element::from(value.bit(i as u64))));
+                    }
+                    outputs
+                })
+        };
+
+        let wires = outputs.wires();
+        let to_binary = |builder: &mut GadgetBuilder<F>, range: core::ops::Range<usize>| {
+            let bits = wires[range].iter()
+                .map(|&wire| builder.assert_boolean(&Expression::from(wire)))
+                .collect();
+            BinaryExpression { bits }
+        };
+        let k1 = to_binary(self, 0..half_bits);
+        let k2 = to_binary(self, half_bits..2 * half_bits);
+        let quotient = to_binary(self, 2 * half_bits..wires.len());
+
+        let difference = k1.join_allowing_overflow()
+            + weighted_sum(&k2, &k2_weights)
+            - weighted_sum(&scalar_bits, &k_weights);
+        let q = quotient.join_allowing_overflow() - Expression::from(Element::from(q_offset));
+        self.assert_equal(&difference, &(q * Element::from(n)));
+        (k1, k2, quotient)
+    }
+}
+
+/// The sum of `weights[i]` over the set bits `i` of `x`.
+fn weighted_bits(x: &BigUint, weights: &[BigUint]) -> BigUint {
+    weights.iter().enumerate()
+        .filter(|(i, _weight)| x.bit(*i as u64))
+        .map(|(_i, weight)| weight)
+        .sum()
+}
+
+/// The sum of `weights[i] * x[i]`.
+fn weighted_sum<F: Field>(x: &BinaryExpression<F>, weights: &[BigUint]) -> Expression<F> {
+    let terms: Vec<Expression<F>> = x.bits.iter().zip(weights)
+        .map(|(bit, weight)| bit.expression() * Element::from(weight.clone()))
+        .collect();
+    Expression::sum_of_expressions(&terms)
+}
+
+/// A bound `B` such that rounding off `k` in the lattice basis leaves a remainder `(k1, k2)` with
+/// `|k1|, |k2| <= B`. Since each rounding error is at most 1/2, `B` is half of the largest sum of
+/// corresponding components.
+fn decomposition_bound(basis: &[(BigInt, BigInt); 2]) -> BigUint {
+    let [(a1, b1), (a2, b2)] = basis;
+    let half_sum = |x: &BigInt, y: &BigInt| {
+        Integer::div_ceil(&(x.abs() + y.abs()), &BigInt::from(2))
+    };
+    half_sum(a1, a2).max(half_sum(b1, b2)).to_biguint().unwrap()
+}
+
+/// Decompose `k` as `k1 + λ k2 (mod n)` with `0 <= k1, k2 <= 2 B`, where `B` is the
+/// `decomposition_bound`.
+fn decompose(
+    k: &BigUint, n: &BigUint, lambda: &BigUint, basis: &[(BigInt, BigInt); 2],
+) -> (BigUint, BigUint) {
+    let [(a1, b1), (a2, b2)] = basis;
+    let bound = BigInt::from(decomposition_bound(basis));
+    let n_int = BigInt::from(n.clone());
+    let lambda_int = BigInt::from(lambda.clone());
+
+    // We find a signed decomposition of t = k - B (1 + λ), with parts in [-B, B], and then add B
+    // to each part. We write (t, 0) = β1 (a1, b1) + β2 (a2, b2) over the rationals, round β1 and
+    // β2 to c1 and c2, and subtract the nearest lattice vector c1 (a1, b1) + c2 (a2, b2).
+    let t = (BigInt::from(k.clone()) - &bound * (BigInt::one() + &lambda_int)).mod_floor(&n_int);
+    let determinant = a1 * b2 - a2 * b1;
+    let c1 = round_div(&(&t * b2), &determinant);
+    let c2 = round_div(&-(&t * b1), &determinant);
+    let k1 = &t - &c1 * a1 - &c2 * a2 + &bound;
+    let k2 = -(&c1 * b1) - &c2 * b2 + &bound;
+
+    let k1 = k1.to_biguint().expect("Invalid lattice basis");
+    let k2 = k2.to_biguint().expect("Invalid lattice basis");
+    assert_eq!((&k1 + lambda * &k2) % n, k % n, "Invalid lattice basis");
+    (k1, k2)
+}
+
+/// `x / y`, rounded to the nearest integer.
+fn round_div(x: &BigInt, y: &BigInt) -> BigInt {
+    let (x, y) = if y.is_negative() { (-x, -y) } else { (x.clone(), y.clone()) };
+    let numerator: BigInt = x * 2u8 + &y;
+    numerator.div_floor(&(y * 2u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use num::{BigInt, BigUint, ToPrimitive};
+
+    use crate::curves::{EdwardsCurve, EdwardsExpression, EdwardsGroup, EdwardsPoint};
+    use crate::expression::Expression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::glv::Endomorphism;
+    use crate::group::Group;
+    use crate::fields::small::F257;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+    use crate::Evaluable;
+
+    /// The curve `3 x^2 + y^2 = 1 - 3 x^2 y^2` over F257. Since `d = -a`, it has the endomorphism
+    /// `(x, y) -> (i x, 1 / y)`, where `i = 16` is a square root of -1.
+    struct ToyCurve;
+
+    impl EdwardsCurve<F257> for ToyCurve {
+        fn a() -> Element<F257> {
+            Element::from(3u8)
+        }
+
+        fn d() -> Element<F257> {
+            -Element::from(3u8)
+        }
+    }
+
+    type ToyGroup = EdwardsGroup<F257, ToyCurve>;
+
+    /// The endomorphism of `ToyCurve`, acting on the subgroup of order 13 as multiplication by 8.
+    struct ToyEndomorphism;
+
+    impl Endomorphism<F257, ToyGroup> for ToyEndomorphism {
+        fn subgroup_order(&self) -> BigUint {
+            BigUint::from(13u8)
+        }
+
+        fn eigenvalue(&self) -> BigUint {
+            BigUint::from(8u8)
+        }
+
+        fn lattice_basis(&self) -> [(BigInt, BigInt); 2] {
+            [(BigInt::from(2), BigInt::from(3)), (BigInt::from(3), BigInt::from(-2))]
+        }
+
+        fn apply_expression(
+            &self, builder: &mut GadgetBuilder<F257>, point: &EdwardsExpression<F257, ToyCurve>,
+        ) -> EdwardsExpression<F257, ToyCurve> {
+            let y_inv = builder.inverse(&point.y);
+            EdwardsExpression::new_unsafe(&point.x * Element::from(16u8), y_inv)
+        }
+    }
+
+    /// A point of order 13.
+    fn generator() -> EdwardsPoint<F257, ToyCurve> {
+        EdwardsPoint::new(Element::from(9u8), Element::from(97u8))
+    }
+
+    #[test]
+    fn endomorphism_eigenvalue() {
+        let point = generator();
+        let image = ToyEndomorphism.apply_element(&point);
+        let expected = ToyGroup::mul_scalar_element(&point, &Element::from(8u8));
+        assert_eq!((expected.x, expected.y), (image.x, image.y));
+    }
+
+    #[test]
+    fn decompose_scalar() {
+        assert_eq!(3, ToyEndomorphism.decomposition_bits());
+        for k in 0u32..257 {
+            let (k1, k2) = ToyEndomorphism.decompose_scalar(&BigUint::from(k));
+            assert!(k1 < BigUint::from(8u8) && k2 < BigUint::from(8u8));
+            assert_eq!((k1 + k2 * 8u8) % 13u8, BigUint::from(k % 13));
+        }
+    }
+
+    #[test]
+    fn mul_scalar_glv() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, k) = (builder.wire(), builder.wire(), builder.wire());
+        let point = EdwardsExpression::new_unsafe(Expression::from(x), Expression::from(y));
+        let product = builder.mul_scalar_glv::<ToyGroup>(
+            &point, &Expression::from(k), Some(&ToyEndomorphism));
+        let gadget = builder.build();
+
+        let mut generic_builder = GadgetBuilder::<F257>::new();
+        let generic_k = generic_builder.wire();
+        ToyGroup::mul_scalar_expression(&mut generic_builder, &point, &Expression::from(generic_k));
+        assert!(gadget.size() < generic_builder.build().size());
+
+        for multiple in &[1u8, 2, 5] {
+            let point = ToyGroup::mul_scalar_element(&generator(), &Element::from(*multiple));
+            for k_value in Element::<F257>::all_elements() {
+                let mut values = values!(
+                    x => point.x.clone(), y => point.y.clone(), k => k_value.clone());
+                assert!(gadget.execute(&mut values));
+                let expected = ToyGroup::mul_scalar_element(&point, &k_value);
+                let actual = product.evaluate(&values);
+                assert_eq!((expected.x, expected.y), (actual.x, actual.y));
+            }
+        }
+    }
+
+    #[test]
+    fn mul_scalar_glv_fallback() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, k) = (builder.wire(), builder.wire(), builder.wire());
+        let point = EdwardsExpression::new_unsafe(Expression::from(x), Expression::from(y));
+        let product = builder.mul_scalar_glv::<ToyGroup>(&point, &Expression::from(k), None);
+        let gadget = builder.build();
+
+        let point = generator();
+        let mut values = values!(x => point.x.clone(), y => point.y.clone(), k => 7u8.into());
+        assert!(gadget.execute(&mut values));
+        let expected = ToyGroup::mul_scalar_element(&point, &Element::from(7u8));
+        let actual = product.evaluate(&values);
+        assert_eq!((expected.x, expected.y), (actual.x, actual.y));
+    }
+
+    #[test]
+    fn decomposition_constraints() {
+        // For a range of scalars, try every assignment of the decomposition and quotient bits, and
+        // check that those which satisfy the constraints are valid decompositions.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let k = builder.wire();
+        let (k1, k2, quotient) =
+            builder.decompose_scalar_glv_with_quotient(&Expression::from(k), &ToyEndomorphism);
+        let gadget = builder.build();
+        let bit_wires: Vec<Wire> = [&k1, &k2, &quotient].iter()
+            .flat_map(|bits| bits.dependencies())
+            .collect();
+        assert_eq!(9, bit_wires.len());
+
+        for k_value in (0u32..26).chain(250..257) {
+            let mut honest = values!(k => Element::from(k_value));
+            assert!(gadget.execute(&mut honest));
+
+            let mut satisfying = 0;
+            for assignment in 0u32..1 << bit_wires.len() {
+                let mut values = WireValues::new();
+                for (&wire, value) in honest.as_map() {
+                    if wire != Wire::ONE && !bit_wires.contains(&wire) {
+                        values.set(wire, value.clone());
+                    }
+                }
+                for (i, &wire) in bit_wires.iter().enumerate() {
+                    values.set(wire, Element::from(assignment >> i & 1));
+                }
+                if gadget.constraints.iter().all(|constraint| constraint.evaluate(&values)) {
+                    satisfying += 1;
+                    let k1 = k1.evaluate(&values).to_u32().unwrap();
+                    let k2 = k2.evaluate(&values).to_u32().unwrap();
+                    assert_eq!(k_value % 13, (k1 + 8 * k2) % 13);
+                }
+            }
+            assert!(satisfying > 0);
+        }
+    }
+}
@@ -1,8 +1,13 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
-use std::marker::PhantomData;
 
-use crate::{BooleanExpression, Element, Evaluable, Expression, Field, GadgetBuilder, WireValues};
+use std::fmt;
+use std::fmt::Formatter;
+
+use num::BigUint;
+
+use crate::{BinaryExpression, BooleanExpression, Element, Evaluable, Expression, Field,
+            GadgetBuilder, HashFunction, Wire, WireValues};
 
 pub trait Group<F: Field> where Self::GroupExpression: for<'a> From<&'a Self::GroupElement>,
                                 Self::GroupExpression: Evaluable<F, Self::GroupElement>,
@@ -11,6 +16,11 @@ pub trait Group<F: Field> where Self::GroupExpression: for<'a> From<&'a Self::Gr
     type GroupElement;
     type GroupExpression;
 
+    /// Whether `add_expressions` is complete, i.e. correct for every pair of inputs, including
+    /// equal inputs, inverses and the identity. Generic algorithms such as `mul_scalar_expression`
+    /// use this to pick an `AdditionMode`.
+    const HAS_COMPLETE_ADDITION: bool;
+
     fn identity_element() -> Self::GroupElement;
 
     fn identity_expression() -> Self::GroupExpression {
@@ -23,6 +33,56 @@ pub trait Group<F: Field> where Self::GroupExpression: for<'a> From<&'a Self::Gr
         rhs: &Self::GroupExpression,
     ) -> Self::GroupExpression;
 
+    /// The safest mode supported by this group: `Complete` if it has a complete addition formula,
+    /// otherwise `IncompleteAssertingDistinct`.
+    fn addition_mode() -> AdditionMode {
+        if Self::HAS_COMPLETE_ADDITION {
+            AdditionMode::Complete
+        } else {
+            AdditionMode::IncompleteAssertingDistinct
+        }
+    }
+
+    /// Adds two elements with a formula which is correct for all inputs. Panics if this group has
+    /// no complete addition formula.
+    fn add_expressions_complete(
+        builder: &mut GadgetBuilder<F>,
+        lhs: &Self::GroupExpression,
+        rhs: &Self::GroupExpression,
+    ) -> Self::GroupExpression {
+        assert!(Self::HAS_COMPLETE_ADDITION,
+                "This group has no complete addition formula; use \
+                 add_expressions_incomplete_asserting_distinct");
+        Self::add_expressions(builder, lhs, rhs)
+    }
+
+    /// Adds two elements with `add_expressions`, after asserting that their first components
+    /// differ. For curve points in affine coordinates, this asserts `x1 != x2`, which rules out
+    /// equal and inverse inputs, where incomplete formulas such as the chord rule fail. The
+    /// caller must also ensure that neither input is the identity.
+    fn add_expressions_incomplete_asserting_distinct(
+        builder: &mut GadgetBuilder<F>,
+        lhs: &Self::GroupExpression,
+        rhs: &Self::GroupExpression,
+    ) -> Self::GroupExpression {
+        builder.assert_nonequal(&lhs.to_components()[0], &rhs.to_components()[0]);
+        Self::add_expressions(builder, lhs, rhs)
+    }
+
+    /// Adds two elements using `addition_mode`, so that any inputs which the addition formula
+    /// doesn't handle make the gadget unsatisfiable rather than producing a wrong result.
+    fn add_expressions_safe(
+        builder: &mut GadgetBuilder<F>,
+        lhs: &Self::GroupExpression,
+        rhs: &Self::GroupExpression,
+    ) -> Self::GroupExpression {
+        match Self::addition_mode() {
+            AdditionMode::Complete => Self::add_expressions_complete(builder, lhs, rhs),
+            AdditionMode::IncompleteAssertingDistinct =>
+                Self::add_expressions_incomplete_asserting_distinct(builder, lhs, rhs),
+        }
+    }
+
     fn add_elements(
         lhs: &Self::GroupElement,
         rhs: &Self::GroupElement,
@@ -50,24 +110,52 @@ pub trait Group<F: Field> where Self::GroupExpression: for<'a> From<&'a Self::Gr
 
     /// Performs scalar multiplication in constraints by first splitting up a scalar into
     /// a binary representation, and then performing the naive double-or-add algorithm. This
-    /// implementation is generic for all groups.
+    /// implementation is generic for all groups. Additions use `add_expressions_safe`.
     fn mul_scalar_expression(
         builder: &mut GadgetBuilder<F>,
         expression: &Self::GroupExpression,
         scalar: &Expression<F>,
     ) -> Self::GroupExpression {
-        let scalar_binary = builder.split_allowing_ambiguity(&scalar);
+        let scalar_binary = builder.split_allowing_ambiguity(scalar);
 
         let mut sum = Self::identity_expression();
         let mut current = expression.clone();
         for bit in scalar_binary.bits {
             let boolean_product = Self::mul_boolean_expression(builder, &current, &bit);
-            sum = Self::add_expressions(builder, &sum, &boolean_product);
+            sum = Self::add_expressions_safe(builder, &sum, &boolean_product);
             current = Self::double_expression(builder, &current);
         }
         sum
     }
 
+    /// Performs scalar multiplication in constraints using a Montgomery ladder. Like
+    /// `mul_scalar_expression`, this is generic for all groups, but every bit is processed with
+    /// the same sequence of operations: a conditional swap, an addition and a doubling, followed
+    /// by another conditional swap. This uniformity can be useful when the circuit structure
+    /// should not depend on how the scalar is used, although it costs an extra swap per bit.
+    fn mul_scalar_expression_ladder(
+        builder: &mut GadgetBuilder<F>,
+        expression: &Self::GroupExpression,
+        scalar: &Expression<F>,
+    ) -> Self::GroupExpression {
+        let scalar_binary = builder.split_allowing_ambiguity(scalar);
+
+        // We maintain the invariant r1 = r0 + expression, processing bits from most significant
+        // to least significant.
+        let mut r0 = Self::identity_expression();
+        let mut r1 = expression.clone();
+        for bit in scalar_binary.bits.iter().rev() {
+            // If the bit is set, we double r1 rather than r0, so swap them first.
+            let (to_double, other) = conditional_swap(builder, bit, &r0, &r1);
+            let sum = Self::add_expressions_safe(builder, &to_double, &other);
+            let double = Self::double_expression(builder, &to_double);
+            let (new_r0, new_r1) = conditional_swap(builder, bit, &double, &sum);
+            r0 = new_r0;
+            r1 = new_r1;
+        }
+        r0
+    }
+
     /// Like `mul_scalar_expression`, but actually evaluates the compression function rather than just adding it
     /// to a `GadgetBuilder`.
     fn mul_scalar_element(
@@ -93,80 +181,233 @@ pub trait Group<F: Field> where Self::GroupExpression: for<'a> From<&'a Self::Gr
         boolean: &BooleanExpression<F>,
     ) -> Self::GroupExpression {
         let coordinates = expression.to_components();
+        assert_eq!(coordinates.len(), Self::GroupExpression::COMPONENTS,
+                   "Wrong number of group element components");
 
         let mut r = Vec::new();
         let ic = Self::identity_expression().to_components();
 
         for (i, x) in coordinates.iter().enumerate() {
-            r.push(builder.selection(boolean, &x, &ic[i]));
+            r.push(builder.selection(boolean, x, &ic[i]));
         }
 
         Self::GroupExpression::from_components_unsafe(r)
     }
-}
 
-/// A trait that defines a generator `g` for a cyclic group in which every element
-/// is defined as `g^a` for some scalar `a`.
-pub trait CyclicGroup<F: Field>: Group<F> {
-    fn generator_element() -> Self::GroupElement;
+    /// Returns a boolean which is on iff the given element is the identity, by comparing each
+    /// component against the corresponding component of the identity.
+    fn is_identity(
+        builder: &mut GadgetBuilder<F>,
+        expression: &Self::GroupExpression,
+    ) -> BooleanExpression<F> {
+        let coordinates = expression.to_components();
+        let ic = Self::identity_expression().to_components();
 
-    fn generator_expression() -> Self::GroupExpression {
-        Self::GroupExpression::from(&Self::generator_element())
+        let mut result = BooleanExpression::_true();
+        for (i, x) in coordinates.iter().enumerate() {
+            let component_equal = builder.equal(x, &ic[i]);
+            result = builder.and(&result, &component_equal);
+        }
+        result
     }
-}
 
-pub trait CyclicGenerator<F: Field, G: Group<F>> {
-    fn generator_element() -> G::GroupElement;
+    /// Allocates wires holding `point` in the given encoding, constrains them to match it, and
+    /// returns them in the order documented by `PointEncoding`. The wires are meant to be public
+    /// inputs: the caller should set them to the values given by `encode_point`, and include them
+    /// in the set of public wires passed to exporters such as `Gadget::assignment_vector`.
+    ///
+    /// The default implementation supports only `PointEncoding::Coordinates`.
+    fn bind_as_public(
+        builder: &mut GadgetBuilder<F>,
+        point: &Self::GroupExpression,
+        encoding: PointEncoding,
+    ) -> Vec<Wire> {
+        match encoding {
+            PointEncoding::Coordinates => bind_components(builder, &point.to_components()),
+            PointEncoding::Compressed => panic!("This group has no compressed encoding"),
+        }
+    }
 
-    fn generator_expression() -> G::GroupExpression {
-        G::GroupExpression::from(&Self::generator_element())
+    /// The values of the wires which `bind_as_public` returns for `element`, in the same order.
+    ///
+    /// The default implementation supports only `PointEncoding::Coordinates`.
+    fn encode_point(element: &Self::GroupElement, encoding: PointEncoding) -> Vec<Element<F>> {
+        match encoding {
+            PointEncoding::Coordinates => {
+                let expression = Self::GroupExpression::from(element);
+                expression.to_components().iter()
+                    .map(|component| component.as_constant().unwrap())
+                    .collect()
+            }
+            PointEncoding::Compressed => panic!("This group has no compressed encoding"),
+        }
     }
 }
 
-pub struct CyclicSubgroup<F: Field, G: Group<F>, C: CyclicGenerator<F, G>> {
-    phantom_f: PhantomData<*const F>,
-    phantom_g: PhantomData<*const G>,
-    phantom_c: PhantomData<*const C>,
+/// How `Group::add_expressions_safe` adds two elements.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AdditionMode {
+    /// A complete formula, which is correct for all inputs, as with twisted Edwards curves whose
+    /// `a` is a square and whose `d` is not.
+    Complete,
+    /// An incomplete formula, guarded by an assertion that the inputs' first components differ.
+    /// See `Group::add_expressions_incomplete_asserting_distinct`.
+    IncompleteAssertingDistinct,
 }
 
-impl<F: Field, G: Group<F>, C: CyclicGenerator<F, G>> Group<F> for CyclicSubgroup<F, G, C> {
-    type GroupElement = G::GroupElement;
-    type GroupExpression = G::GroupExpression;
+/// How a group element is mapped to public inputs by `Group::bind_as_public`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PointEncoding {
+    /// One wire per component, in the order of `GroupExpression::to_components`. For curve points
+    /// in affine coordinates, this is `[x, y]`.
+    Coordinates,
+    /// A compressed encoding, for groups which support one. For Edwards curves, this is
+    /// `[y, sign]`, where `sign` is the least significant bit of the canonical encoding of `x`.
+    Compressed,
+}
 
-    fn identity_element() -> Self::GroupElement {
-        G::identity_element()
-    }
+/// Allocates a wire for each of the given components, and asserts that it equals the component.
+pub(crate) fn bind_components<F: Field>(
+    builder: &mut GadgetBuilder<F>,
+    components: &[Expression<F>],
+) -> Vec<Wire> {
+    components.iter()
+        .map(|component| {
+            let wire = builder.wire();
+            builder.assert_equal(component, &Expression::from(wire));
+            wire
+        })
+        .collect()
+}
 
-    fn add_expressions(
-        builder: &mut GadgetBuilder<F>,
-        lhs: &Self::GroupExpression,
-        rhs: &Self::GroupExpression
-    ) -> Self::GroupExpression {
-        G::add_expressions(builder, lhs, rhs)
+/// Returns `(y, x)` if `swap` is set, otherwise `(x, y)`. This costs one product per component.
+fn conditional_swap<F: Field, GE: GroupExpression<F>>(
+    builder: &mut GadgetBuilder<F>,
+    swap: &BooleanExpression<F>,
+    x: &GE,
+    y: &GE,
+) -> (GE, GE) {
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    for (x_i, y_i) in x.to_components().iter().zip(y.to_components().iter()) {
+        let delta = builder.product(swap.expression(), &(y_i - x_i));
+        first.push(x_i + &delta);
+        second.push(y_i - &delta);
     }
+    (GE::from_components_unsafe(first), GE::from_components_unsafe(second))
+}
 
-    fn double_expression(
-        builder: &mut GadgetBuilder<F>,
-        expression: &Self::GroupExpression
-    ) -> Self::GroupExpression {
-        G::double_expression(builder, expression)
-    }
+/// Select `x` if `c` is set, otherwise `y`, component by component.
+pub(crate) fn select<F: Field, G: Group<F>>(
+    builder: &mut GadgetBuilder<F>,
+    c: &BooleanExpression<F>,
+    x: &G::GroupExpression,
+    y: &G::GroupExpression,
+) -> G::GroupExpression {
+    let components = x.to_components().iter().zip(y.to_components().iter())
+        .map(|(x_i, y_i)| builder.selection(c, x_i, y_i))
+        .collect();
+    G::GroupExpression::from_components_unsafe(components)
 }
 
-impl<F: Field, G: Group<F>, C: CyclicGenerator<F, G>> CyclicGroup<F> for CyclicSubgroup<F, G, C> {
-    fn generator_element() -> Self::GroupElement {
-        C::generator_element()
+/// Computes `k1 P + k2 Q` with Shamir's trick, processing the bits of `k1` and `k2` together from
+/// most significant to least significant, and adding a point from the table `[0, P, Q, P + Q]`.
+/// The scalars must have the same, non-zero number of bits.
+pub(crate) fn mul_two_scalars<F: Field, G: Group<F>>(
+    builder: &mut GadgetBuilder<F>,
+    p: &G::GroupExpression,
+    k1: &BinaryExpression<F>,
+    q: &G::GroupExpression,
+    k2: &BinaryExpression<F>,
+) -> G::GroupExpression {
+    assert_eq!(k1.len(), k2.len(), "Expected scalars with the same number of bits");
+    assert!(!k1.bits.is_empty(), "Expected at least one bit");
+
+    let both = G::add_expressions_safe(builder, p, q);
+    let identity = G::identity_expression();
+    let lookup = |builder: &mut GadgetBuilder<F>, i: usize| {
+        let low = select::<F, G>(builder, &k1.bits[i], p, &identity);
+        let high = select::<F, G>(builder, &k1.bits[i], &both, q);
+        select::<F, G>(builder, &k2.bits[i], &high, &low)
+    };
+
+    let bits = k1.len();
+    let mut sum = lookup(builder, bits - 1);
+    for i in (0..bits - 1).rev() {
+        sum = G::double_expression(builder, &sum);
+        let term = lookup(builder, i);
+        sum = G::add_expressions_safe(builder, &sum, &term);
     }
+    sum
+}
+
+/// A trait that defines a generator `g` for a cyclic group in which every element
+/// is defined as `g^a` for some scalar `a`. For groups which are not themselves cyclic, such as
+/// the points of an elliptic curve with a cofactor, `g` generates a cyclic subgroup.
+pub trait CyclicGroup<F: Field>: Group<F> {
+    fn generator_element() -> Self::GroupElement;
 
     fn generator_expression() -> Self::GroupExpression {
-        C::generator_expression()
+        Self::GroupExpression::from(&Self::generator_element())
     }
 }
 
+/// A cyclic group whose generator has a known prime order, such as the prime-order subgroup of an
+/// embedded curve. Scalars are then integers modulo this order.
+pub trait PrimeOrderGroup<F: Field>: CyclicGroup<F> {
+    /// The order of the generator.
+    fn generator_order() -> BigUint;
+}
+
+/// A cyclic group with a map from field elements into the subgroup generated by its generator,
+/// whose outputs have no known discrete logarithm with respect to the generator.
+pub trait HashToCurve<F: Field>: CyclicGroup<F> {
+    /// Deterministically map `input` to an element of the subgroup generated by the generator,
+    /// using the given hash function to derive candidate points.
+    fn hash_to_curve<HF: HashFunction<F>>(
+        builder: &mut GadgetBuilder<F>,
+        input: &Expression<F>,
+        hash: &HF,
+    ) -> Self::GroupExpression;
+
+    /// Multiply an element by the cofactor of the subgroup generated by the generator, i.e. the
+    /// order of the full group divided by the order of the subgroup.
+    fn clear_cofactor(
+        builder: &mut GadgetBuilder<F>,
+        expression: &Self::GroupExpression,
+    ) -> Self::GroupExpression;
+}
+
 /// Applies a (not necessarily injective) map, defined from a group to the field,
 /// to an expression corresponding to an element in the group.
 pub trait GroupExpression<F: Field> {
+    /// The number of field components in each group element, e.g. 2 for affine curve points.
+    const COMPONENTS: usize;
+
     fn compressed(&self) -> &Expression<F>;
+
+    /// The components of this element. There are always exactly `COMPONENTS` of them.
     fn to_components(&self) -> Vec<Expression<F>>;
+
+    /// Creates an element from its components, without checking that they represent a valid
+    /// element. Panics if there are not exactly `COMPONENTS` components.
     fn from_components_unsafe(components: Vec<Expression<F>>) -> Self;
-}
\ No newline at end of file
+}
+
+/// An error indicating that the wrong number of components was given for a group element.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ComponentCountError {
+    /// The number of components in each group element.
+    pub expected: usize,
+    /// The number of components which were given.
+    pub actual: usize,
+}
+
+impl fmt::Display for ComponentCountError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Expected {} components, but got {}", self.expected, self.actual)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ComponentCountError {}
\ No newline at end of file
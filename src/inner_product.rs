@@ -0,0 +1,206 @@
+//! This module extends GadgetBuilder with methods for asserting inner product relations, optionally
+//! combined with the opening of a vector commitment, as used in Bulletproofs-style protocols.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::Expression;
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+use crate::group::{Group, GroupExpression};
+
+/// The generators of a Pedersen vector commitment to a pair of vectors `a` and `b`, as used by
+/// `assert_inner_product_with_generators`. `GE` is the type of group expressions.
+pub struct InnerProductGenerators<GE> {
+    /// One generator per entry of `a`.
+    pub a: Vec<GE>,
+    /// One generator per entry of `b`.
+    pub b: Vec<GE>,
+    /// The generator which is multiplied by the blinding factor.
+    pub blinding: GE,
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Assert that `<a, b> = c`, i.e. that the sum of `a[i] * b[i]` is `c`. This costs one
+    /// constraint per pair of entries.
+    pub fn assert_inner_product(
+        &mut self, a: &[Expression<F>], b: &[Expression<F>], c: &Expression<F>,
+    ) {
+        logged!(self, "assert_inner_product", [a.len(), b.len(), c], {
+            assert_eq!(a.len(), b.len(), "Vectors must have the same length");
+            let (a_last, b_last) = match (a.last(), b.last()) {
+                (Some(a_last), Some(b_last)) => (a_last, b_last),
+                _ => {
+                    self.assert_equal(c, &Expression::zero());
+                    return;
+                }
+            };
+
+            // The last product is asserted directly against the remainder of c, rather than being
+            // computed and summed, which saves a constraint.
+            let n = a.len();
+            let products: Vec<Expression<F>> = a[..n - 1].iter().zip(&b[..n - 1])
+                .map(|(a_i, b_i)| self.product(a_i, b_i))
+                .collect();
+            let remainder = c - Expression::sum_of_expressions(&products);
+            self.assert_product(a_last, b_last, &remainder);
+        })
+    }
+
+    /// Assert that `<a, b> = c`, and that `commitment` is a Pedersen vector commitment to `a` and
+    /// `b` with the given blinding factor, i.e.
+    ///
+    /// `commitment = sum(a[i] * generators.a[i]) + sum(b[i] * generators.b[i])
+    ///     + blinding * generators.blinding`.
+    ///
+    /// Together, these prove that the committed vectors have inner product `c`. For the
+    /// commitment to be binding, no discrete log relation between the generators should be known.
+    pub fn assert_inner_product_with_generators<G: Group<F>>(
+        &mut self,
+        a: &[Expression<F>],
+        b: &[Expression<F>],
+        c: &Expression<F>,
+        generators: &InnerProductGenerators<G::GroupExpression>,
+        blinding: &Expression<F>,
+        commitment: &G::GroupExpression,
+    ) {
+        logged!(self, "assert_inner_product_with_generators", [a.len(), b.len(), c, blinding], {
+            assert_eq!(a.len(), generators.a.len(), "Expected one generator per entry of a");
+            assert_eq!(b.len(), generators.b.len(), "Expected one generator per entry of b");
+            self.assert_inner_product(a, b, c);
+
+            let mut sum = G::mul_scalar_expression(self, &generators.blinding, blinding);
+            let terms = a.iter().zip(&generators.a).chain(b.iter().zip(&generators.b));
+            for (scalar, generator) in terms {
+                let term = G::mul_scalar_expression(self, generator, scalar);
+                sum = G::add_expressions_safe(self, &sum, &term);
+            }
+
+            for (x, y) in sum.to_components().iter().zip(commitment.to_components().iter()) {
+                self.assert_equal(x, y);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "rand")]
+    use crate::curves::{EdwardsExpression, EdwardsGroup, EdwardsPoint, JubJub};
+    use crate::expression::Expression;
+    #[cfg(feature = "rand")]
+    use crate::field::Bls12_381;
+    use crate::field::{Bn128, Element};
+    use crate::gadget_builder::GadgetBuilder;
+    #[cfg(feature = "rand")]
+    use crate::group::{CyclicGroup, Group};
+    #[cfg(feature = "rand")]
+    use crate::inner_product::InnerProductGenerators;
+    #[cfg(feature = "rand")]
+    use crate::test_util::deterministic_elements;
+    #[cfg(feature = "rand")]
+    use crate::wire_values::WireValues;
+
+    #[cfg(feature = "rand")]
+    type JubJubGroup = EdwardsGroup<Bls12_381, JubJub>;
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn assert_inner_product() {
+        const N: usize = 8;
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (a_wires, b_wires, c_wire) = (builder.wires(N), builder.wires(N), builder.wire());
+        let a: Vec<Expression<Bn128>> = a_wires.iter().map(Expression::from).collect();
+        let b: Vec<Expression<Bn128>> = b_wires.iter().map(Expression::from).collect();
+        builder.assert_inner_product(&a, &b, &Expression::from(c_wire));
+        let gadget = builder.build();
+        assert_eq!(N, gadget.size());
+
+        let elements = deterministic_elements(2 * N);
+        let mut values = WireValues::new();
+        let mut c = Element::zero();
+        for i in 0..N {
+            let (a_i, b_i) = (elements[2 * i].clone(), elements[2 * i + 1].clone());
+            c += &a_i * &b_i;
+            values.set(a_wires[i], a_i);
+            values.set(b_wires[i], b_i);
+        }
+
+        let mut wrong_values = values.clone();
+        wrong_values.set(c_wire, &c + Element::one());
+        assert!(!gadget.execute(&mut wrong_values));
+
+        values.set(c_wire, c);
+        assert!(gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn assert_inner_product_empty() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let c_wire = builder.wire();
+        builder.assert_inner_product(&[], &[], &Expression::from(c_wire));
+        let gadget = builder.build();
+
+        assert!(gadget.execute(&mut values!(c_wire => Element::zero())));
+        assert!(!gadget.execute(&mut values!(c_wire => Element::one())));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn assert_inner_product_with_generators() {
+        // Scalar multiplication is expensive, so we use short vectors here.
+        const N: usize = 2;
+        let generator = JubJubGroup::generator_element();
+        let generators: Vec<EdwardsPoint<Bls12_381, JubJub>> = (2..2 * N as u64 + 3)
+            .map(|k| JubJubGroup::mul_scalar_element(&generator, &Element::from(k)))
+            .collect();
+        let (a_generators, rest) = generators.split_at(N);
+        let (b_generators, blinding_generator) = rest.split_at(N);
+        let blinding_generator = &blinding_generator[0];
+
+        let mut elements = deterministic_elements::<Bls12_381>(2 * N + 1);
+        let blinding = elements.pop().unwrap();
+        let b = elements.split_off(N);
+        let a = elements;
+        let c = a.iter().zip(&b).fold(Element::zero(), |sum, (a_i, b_i)| sum + a_i * b_i);
+
+        let mut commitment = JubJubGroup::mul_scalar_element(blinding_generator, &blinding);
+        for (scalar, point) in a.iter().zip(a_generators).chain(b.iter().zip(b_generators)) {
+            let term = JubJubGroup::mul_scalar_element(point, scalar);
+            commitment = JubJubGroup::add_elements(&commitment, &term);
+        }
+
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let (a_wires, b_wires) = (builder.wires(N), builder.wires(N));
+        let (c_wire, blinding_wire) = (builder.wire(), builder.wire());
+        let to_expressions = |points: &[EdwardsPoint<Bls12_381, JubJub>]| points.iter()
+            .map(EdwardsExpression::from).collect::<Vec<_>>();
+        builder.assert_inner_product_with_generators::<JubJubGroup>(
+            &a_wires.iter().map(Expression::from).collect::<Vec<_>>(),
+            &b_wires.iter().map(Expression::from).collect::<Vec<_>>(),
+            &Expression::from(c_wire),
+            &InnerProductGenerators {
+                a: to_expressions(a_generators),
+                b: to_expressions(b_generators),
+                blinding: EdwardsExpression::from(blinding_generator),
+            },
+            &Expression::from(blinding_wire),
+            &EdwardsExpression::from(&commitment));
+        let gadget = builder.build();
+
+        let values_with = |c: &Element<Bls12_381>, blinding: &Element<Bls12_381>| {
+            let mut values = WireValues::new();
+            for i in 0..N {
+                values.set(a_wires[i], a[i].clone());
+                values.set(b_wires[i], b[i].clone());
+            }
+            values.set(c_wire, c.clone());
+            values.set(blinding_wire, blinding.clone());
+            values
+        };
+
+        assert!(gadget.execute(&mut values_with(&c, &blinding)));
+        assert!(!gadget.execute(&mut values_with(&c, &(&blinding + Element::one()))));
+        assert!(!gadget.execute(&mut values_with(&(&c + Element::one()), &blinding)));
+    }
+}
@@ -1,5 +1,5 @@
-/// This module provides a linear congruential generator for (not cryptographically secure) random
-/// data.
+//! This module provides a linear congruential generator for (not cryptographically secure) random
+//! data.
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
@@ -5,13 +5,15 @@
 //!
 //! See the [readme](https://github.com/mir-protocol/r1cs) for more information and examples.
 
-#![cfg_attr(not(feature = "std"), no_std)]
+// Tests always link std, since the test harness needs it, even when the std feature is disabled.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(any(test, feature = "test-utils"), allow(clippy::bool_assert_comparison))]
 
 #[cfg(feature = "std")]
 #[macro_use]
 extern crate std;
 
-#[cfg(not(feature = "std"))]
+#[cfg(not(any(feature = "std", test)))]
 #[macro_use]
 extern crate core as std;
 
@@ -21,65 +23,131 @@ extern crate alloc;
 
 pub use num;
 
+pub use binary_arithmetic::*;
+pub use block_cipher_compression::*;
+pub use build_log::*;
+pub use byte_encoding::*;
+#[cfg(feature = "rand")]
+pub use constants::*;
 pub use constraint::*;
+pub use coprimality::*;
 pub use curves::*;
 pub use davies_meyer::*;
+#[cfg(feature = "std")]
+pub use execution_profile::*;
 pub use expression::*;
+pub use expression_context::*;
+pub use factory::*;
+pub use fft::*;
 pub use field::*;
+pub use fields::small::*;
 pub use gadget::*;
 pub use gadget_builder::*;
 pub use gadget_traits::*;
+pub use glv::*;
 pub use group::*;
+pub use inner_product::*;
 pub use lcg::*;
 pub use matrices::*;
 pub use merkle_damgard::*;
 pub use merkle_trees::*;
 pub use mimc::*;
 pub use miyaguchi_preneel::*;
+pub use nullifier_set::*;
+#[cfg(feature = "std")]
+pub use params::*;
 pub use permutations::*;
 pub use poseidon::*;
+pub use poseidon2::*;
+pub use public_inputs::*;
+#[cfg(feature = "std")]
+pub use r1cs_circom::*;
+pub use ratio::*;
 pub use rescue::*;
+pub use selection::*;
+pub use signature::*;
 pub use sponge::*;
+pub use statements::*;
+pub use transcript::*;
+pub use uint::*;
+pub use util::{elements_from_biguints, evaluate_all, expressions_from_elements,
+               expressions_from_wires, ElementRangeError};
+pub use verify_permutation::apply_fixed_permutation;
+pub use vrf::*;
 pub use wire::*;
 pub use wire_values::*;
 pub use witness_generator::*;
 
 #[macro_use]
 mod wire_values;
+#[macro_use]
+mod build_log;
 
 mod bimap_util;
 mod binary_arithmetic;
 mod bitwise_operations;
+mod block_cipher_compression;
 mod boolean_algebra;
+mod byte_encoding;
 mod comparisons;
+mod constants;
 mod constraint;
+mod coprimality;
+mod counting;
 mod curves;
 mod davies_meyer;
+#[cfg(feature = "std")]
+mod execution_profile;
 mod expression;
+mod expression_context;
+mod factory;
+mod fft;
 mod field;
 mod field_arithmetic;
 mod gadget;
 mod gadget_builder;
 mod gadget_traits;
+mod glv;
 mod group;
+mod inner_product;
 mod lcg;
 mod matrices;
 mod merkle_damgard;
 mod merkle_trees;
+mod modular_arithmetic;
 mod mimc;
 mod miyaguchi_preneel;
+mod nullifier_set;
+mod one_hot;
+#[cfg(feature = "std")]
+mod params;
 mod permutations;
+mod polynomials;
 mod poseidon;
+mod poseidon2;
+mod public_inputs;
+#[cfg(feature = "std")]
+mod r1cs_circom;
 mod random_access;
+mod random_linear_combination;
+mod ratio;
 mod rescue;
+mod sbox;
+mod secret_sharing;
+mod selection;
 mod signature;
 mod sorting;
 mod splitting;
 mod sponge;
+mod statements;
+mod transcript;
+mod uint;
 mod util;
 mod verify_permutation;
+mod vrf;
 mod wire;
 mod witness_generator;
 
-#[cfg(test)]
-mod test_util;
+pub mod fields;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_util;
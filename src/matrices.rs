@@ -3,6 +3,9 @@ use alloc::vec::Vec;
 
 use std::ops::Mul;
 
+use num::BigUint;
+use num_traits::Zero;
+
 use crate::{Element, Expression, Field};
 
 /// A matrix of prime field elements.
@@ -20,6 +23,43 @@ impl<F: Field> ElementMatrix<F> {
         }
         ElementMatrix { rows }
     }
+
+    /// The inverse of this matrix, computed by Gauss-Jordan elimination. Panics if the matrix is
+    /// not square, or is singular.
+    pub fn inverse(&self) -> Self {
+        let n = self.rows.len();
+        assert_eq!(self.rows[0].len(), n, "Only square matrices can be inverted");
+
+        // Augment each row with the corresponding row of the identity matrix.
+        let mut rows: Vec<Vec<Element<F>>> = self.rows.iter().enumerate()
+            .map(|(i, row)| {
+                let mut augmented = row.clone();
+                augmented.extend((0..n).map(|j| Element::from(i == j)));
+                augmented
+            })
+            .collect();
+
+        for col in 0..n {
+            let pivot = (col..n).find(|&i| rows[i][col].is_nonzero())
+                .expect("Matrix is singular");
+            rows.swap(col, pivot);
+            let pivot_inverse = rows[col][col].multiplicative_inverse();
+            for entry in rows[col].iter_mut() {
+                *entry *= &pivot_inverse;
+            }
+            for i in 0..n {
+                let factor = rows[i][col].clone();
+                if i != col && factor.is_nonzero() {
+                    let pivot_row = rows[col].clone();
+                    for (entry, pivot_entry) in rows[i].iter_mut().zip(pivot_row) {
+                        *entry -= &factor * pivot_entry;
+                    }
+                }
+            }
+        }
+
+        ElementMatrix { rows: rows.into_iter().map(|row| row[n..].to_vec()).collect() }
+    }
 }
 
 impl<F: Field> Clone for ElementMatrix<F> {
@@ -32,9 +72,17 @@ impl<F: Field> Mul<&[Element<F>]> for &ElementMatrix<F> {
     type Output = Vec<Element<F>>;
 
     fn mul(self, rhs: &[Element<F>]) -> Self::Output {
-        self.rows.iter().zip(rhs.iter())
-            .map(|(row, val)| row.iter().fold(
-                Element::zero(), |sum, row_i| sum + val * row_i))
+        assert_eq!(self.rows[0].len(), rhs.len(), "Vector length must match the number of columns");
+        let order = F::order();
+        self.rows.iter()
+            .map(|row| {
+                // Sum the products as integers, reducing only once per row.
+                let sum = row.iter().zip(rhs.iter())
+                    .fold(BigUint::zero(), |sum, (row_i, val)| {
+                        sum + row_i.to_biguint() * val.to_biguint()
+                    });
+                Element::from(sum % &order)
+            })
             .collect()
     }
 }
@@ -43,9 +91,15 @@ impl<F: Field> Mul<&[Expression<F>]> for &ElementMatrix<F> {
     type Output = Vec<Expression<F>>;
 
     fn mul(self, rhs: &[Expression<F>]) -> Self::Output {
-        self.rows.iter().zip(rhs.iter())
-            .map(|(row, val)| row.iter().fold(
-                Expression::zero(), |sum, row_i| sum + val * row_i))
+        assert_eq!(self.rows[0].len(), rhs.len(), "Vector length must match the number of columns");
+        self.rows.iter()
+            .map(|row| {
+                let mut sum = Expression::zero();
+                for (row_i, val) in row.iter().zip(rhs.iter()) {
+                    sum.scaled_add(row_i, val);
+                }
+                sum
+            })
             .collect()
     }
 }
@@ -77,8 +131,24 @@ impl<F: Field> MdsMatrix<F> {
         MdsMatrix { matrix: ElementMatrix::new(rows) }
     }
 
+    /// Creates a `width`x`width` Cauchy matrix, with entries `1 / (x_i + y_j)` where `x_i = i` and
+    /// `y_j = width + j`. Since the `x_i` are distinct, the `y_j` are distinct, and no `x_i + y_j`
+    /// is zero, every square submatrix is invertible, so the matrix is MDS. This requires
+    /// `|F| > 3 * width`.
+    pub fn cauchy(width: usize) -> Self {
+        assert!(width > 0, "Expected a non-zero width");
+        assert!(F::order() > BigUint::from(3 * width), "The field is too small for this width");
+        let rows = (0..width)
+            .map(|i| (0..width)
+                .map(|j| Element::from(i + width + j).multiplicative_inverse())
+                .collect())
+            .collect();
+        MdsMatrix { matrix: ElementMatrix::new(rows) }
+    }
+
+    /// The inverse of this matrix. MDS matrices are always invertible.
     pub fn inverse(&self) -> Self {
-        unimplemented!("TODO: Implement inverse")
+        MdsMatrix { matrix: self.matrix.inverse() }
     }
 }
 
@@ -122,8 +192,72 @@ impl<F: Field> Mul<&[Expression<F>]> for MdsMatrix<F> {
 
 #[cfg(test)]
 mod tests {
+    use crate::{Element, ElementMatrix, Expression, MdsMatrix, Wire};
+    use crate::fields::small::F257;
+
     #[test]
     fn matrix_vector_multiplication() {
-        // TODO
+        let matrix = ElementMatrix::<F257>::new(vec![
+            vec![1u8.into(), 2u8.into(), 3u8.into()],
+            vec![4u8.into(), 5u8.into(), 6u8.into()],
+        ]);
+        let vector: Vec<Element<F257>> = vec![7u8.into(), 8u8.into(), 9u8.into()];
+        // [1*7 + 2*8 + 3*9, 4*7 + 5*8 + 6*9] = [50, 122]
+        assert_eq!(vec![Element::from(50u8), Element::from(122u8)], &matrix * vector.as_slice());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn cauchy() {
+        let mds = MdsMatrix::<F257>::cauchy(2);
+        // The entries are [[1/2, 1/3], [1/3, 1/4]]. Check each column by multiplying by a unit
+        // vector, scaled by 12 to clear denominators.
+        let twelve = Element::<F257>::from(12u8);
+        assert_eq!(vec![Element::from(6u8), Element::from(4u8)],
+                   &mds * [twelve.clone(), Element::zero()].as_slice());
+        assert_eq!(vec![Element::from(4u8), Element::from(3u8)],
+                   &mds * [Element::zero(), twelve].as_slice());
+    }
+
+    #[test]
+    fn mds_expression_multiplication() {
+        let mds = MdsMatrix::<F257>::cauchy(3);
+        let inputs: Vec<Expression<F257>> = vec![
+            Expression::from(Wire { index: 1 }) * Element::from(2u8) + Expression::one(),
+            Expression::from(Wire { index: 2 }) - Expression::from(Wire { index: 1 }),
+            Expression::from(Wire { index: 3 }),
+        ];
+
+        // The result should be identical to one built with operators.
+        let expected: Vec<Expression<F257>> = mds.matrix.rows.iter()
+            .map(|row| row.iter().zip(inputs.iter()).fold(
+                Expression::zero(), |sum, (row_i, val)| sum + val * row_i))
+            .collect();
+        assert_eq!(expected, &mds * inputs.as_slice());
+    }
+
+    #[test]
+    fn inverse() {
+        let matrix = ElementMatrix::<F257>::new(vec![
+            vec![0u8.into(), 2u8.into(), 3u8.into()],
+            vec![4u8.into(), 5u8.into(), 6u8.into()],
+            vec![7u8.into(), 8u8.into(), 10u8.into()],
+        ]);
+        let vector: Vec<Element<F257>> = vec![11u8.into(), 12u8.into(), 13u8.into()];
+        let product = &matrix * vector.as_slice();
+        assert_eq!(vector, &matrix.inverse() * product.as_slice());
+
+        let mds = MdsMatrix::<F257>::cauchy(4);
+        let vector: Vec<Element<F257>> = vec![1u8.into(), 2u8.into(), 3u8.into(), 4u8.into()];
+        let product = &mds * vector.as_slice();
+        assert_eq!(vector, &mds.inverse() * product.as_slice());
+    }
+
+    #[test]
+    #[should_panic(expected = "Matrix is singular")]
+    fn inverse_singular() {
+        ElementMatrix::<F257>::new(vec![
+            vec![1u8.into(), 2u8.into()],
+            vec![2u8.into(), 4u8.into()],
+        ]).inverse();
+    }
+}
@@ -1,6 +1,9 @@
 //! This module extends GadgetBuilder with an implementation of the Merkle-Damgård construction.
 
-use crate::{CompressionFunction, HashFunction};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::{CompressionFunction, HashFunction, WideHashFunction};
 use crate::expression::Expression;
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
@@ -39,13 +42,30 @@ impl<F: Field, C: CompressionFunction<F>> HashFunction<F> for MerkleDamgard<F, C
     }
 }
 
+/// Hashes as with `HashFunction::hash`, then applies an output transformation, which compresses
+/// the final state with a counter, so output `i` is `compress(hash, i)`. Every output goes through
+/// a compression, so no output reveals the final state, or any other output.
+impl<F: Field, C: CompressionFunction<F>> WideHashFunction<F> for MerkleDamgard<F, C> {
+    fn hash_wide(
+        &self, builder: &mut GadgetBuilder<F>, blocks: &[Expression<F>], output_len: usize,
+    ) -> Vec<Expression<F>> {
+        let final_state = self.hash(builder, blocks);
+        (0..output_len)
+            .map(|i| self.compress.compress(builder, &final_state, &Expression::from(i)))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{CompressionFunction, HashFunction, MerkleDamgard};
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use crate::{CompressionFunction, FirstOutputHash, HashFunction, MerkleDamgard,
+                WideHashFunction};
     use crate::expression::Expression;
     use crate::field::{Element, Field};
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F7;
+    use crate::fields::small::{F257, F7};
 
     #[test]
     fn merkle_damgard() {
@@ -78,4 +98,35 @@ mod tests {
         // after 2 (length): 3*2 + 2*3 = 5
         assert_eq!(Element::from(5u8), hash.evaluate(&values));
     }
+
+    #[test]
+    fn wide_output() {
+        // The same trivial compression function as above.
+        struct TestCompress;
+
+        impl<F: Field> CompressionFunction<F> for TestCompress {
+            fn compress(
+                &self, _builder: &mut GadgetBuilder<F>, x: &Expression<F>, y: &Expression<F>,
+            ) -> Expression<F> {
+                x * 2 + y * 3
+            }
+        }
+
+        let md = MerkleDamgard::new(Element::from(2u8), TestCompress);
+        let blocks = [Element::<F257>::from(3u8), Element::from(4u8)];
+        // initial value: 2
+        // after 3: 2*2 + 3*3 = 13
+        // after 4: 13*2 + 4*3 = 38
+        // after 2 (length): 38*2 + 2*3 = 82
+        assert_eq!(Element::from(82u8), md.hash_evaluate(&blocks));
+
+        // Output i is 82*2 + i*3.
+        let outputs = md.hash_wide_evaluate(&blocks, 4);
+        let expected = [164u8, 167, 170, 173].iter().map(|&x| Element::from(x)).collect::<Vec<_>>();
+        assert_eq!(expected, outputs);
+        assert_eq!(&md.hash_wide_evaluate(&blocks, 2)[..], &outputs[..2]);
+
+        // The adapter's hash is the first output, not the final state.
+        assert_eq!(Element::from(164u8), FirstOutputHash(md).hash_evaluate(&blocks));
+    }
 }
@@ -1,10 +1,13 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
+use std::fmt;
+use std::fmt::Formatter;
+
 use crate::expression::{BinaryExpression, BooleanExpression, Expression};
-use crate::field::Field;
+use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
-use crate::gadget_traits::CompressionFunction;
+use crate::gadget_traits::{BinaryCompressionFunction, CompressionFunction};
 
 /// The path from a leaf to the root of a binary Merkle tree.
 #[derive(Debug)]
@@ -18,9 +21,61 @@ pub struct MerklePath<F: Field> {
 }
 
 impl<F: Field> MerklePath<F> {
+    /// Creates a path from its prefix and siblings. Panics if they have different lengths; see
+    /// `try_new`.
     pub fn new(prefix: BinaryExpression<F>, siblings: Vec<Expression<F>>) -> Self {
-        assert_eq!(prefix.len(), siblings.len());
-        MerklePath { prefix, siblings }
+        Self::try_new(prefix, siblings).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but returns an error rather than panicking if the prefix doesn't have one bit
+    /// per sibling.
+    pub fn try_new(
+        prefix: BinaryExpression<F>, siblings: Vec<Expression<F>>,
+    ) -> Result<Self, MerklePathLengthError> {
+        if prefix.len() != siblings.len() {
+            let (prefix_bits, siblings) = (prefix.len(), siblings.len());
+            return Err(MerklePathLengthError { prefix_bits, siblings });
+        }
+        Ok(MerklePath { prefix, siblings })
+    }
+
+    /// Creates a path for the leaf at position `index`, deriving the prefix by splitting `index`
+    /// into one bit per sibling. This requires `2^depth < |F|`. If `index` is not less than
+    /// `2^depth`, the gadget will not be satisfiable.
+    pub fn from_index(
+        builder: &mut GadgetBuilder<F>, index: &Expression<F>, siblings: Vec<Expression<F>>,
+    ) -> Self {
+        let prefix = builder.split_bounded(index, siblings.len());
+        MerklePath::new(prefix, siblings)
+    }
+
+    /// The number of layers above the leaf, i.e. the number of siblings.
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// The position of the leaf among all leaves, recovered from the prefix. This is a linear
+    /// combination of the prefix bits, so it requires no constraints, but it does require
+    /// `2^depth < |F|`.
+    pub fn index(&self) -> Expression<F> {
+        self.prefix.join()
+    }
+
+    /// Extends this path to the given depth, for a canonical sparse tree in which every subtree
+    /// without leaves has a fixed root. `empty_hashes[i]` must be the root of an empty subtree of
+    /// height `i`, so `empty_hashes[0]` is the empty leaf, and there must be at least `depth` of
+    /// them.
+    ///
+    /// The original path proves membership in the leftmost subtree of its height, so each added
+    /// layer has a prefix bit of zero and an empty sibling. The root of the extended path is the
+    /// root of the sparse tree of height `depth` whose only non-empty subtree is the original one.
+    pub fn padded_to(&self, depth: usize, empty_hashes: &[Element<F>]) -> Self {
+        assert!(depth >= self.depth(), "Cannot pad a path of depth {} to depth {}",
+                self.depth(), depth);
+        assert!(empty_hashes.len() >= depth, "Expected at least {} empty subtree hashes", depth);
+        let mut siblings = self.siblings.clone();
+        siblings.extend(empty_hashes[self.depth()..depth].iter().map(Expression::from));
+        MerklePath { prefix: self.prefix.padded(depth), siblings }
     }
 }
 
@@ -33,6 +88,63 @@ impl<F: Field> Clone for MerklePath<F> {
     }
 }
 
+/// The path from a leaf to the root of a binary Merkle tree whose nodes are binary digests, for use
+/// with a `BinaryCompressionFunction`.
+#[derive(Debug)]
+pub struct BinaryMerklePath<F: Field> {
+    /// The sequence of "turns" when traversing up the tree, as in `MerklePath`.
+    prefix: BinaryExpression<F>,
+    /// The sequence of sibling digests which are encountered along the path up the tree.
+    siblings: Vec<BinaryExpression<F>>,
+}
+
+impl<F: Field> BinaryMerklePath<F> {
+    pub fn new(prefix: BinaryExpression<F>, siblings: Vec<BinaryExpression<F>>) -> Self {
+        assert_eq!(prefix.len(), siblings.len());
+        BinaryMerklePath { prefix, siblings }
+    }
+}
+
+impl<F: Field> Clone for BinaryMerklePath<F> {
+    fn clone(&self) -> Self {
+        BinaryMerklePath {
+            prefix: self.prefix.clone(),
+            siblings: self.siblings.clone(),
+        }
+    }
+}
+
+/// The path from a leaf to the root of a Merkle tree in which each node has `arity` children, for
+/// use with `GadgetBuilder::merkle_tree_root_arity`.
+#[derive(Debug)]
+pub struct AryMerklePath<F: Field> {
+    /// For each layer, the position of the target node among its parent's children, in binary.
+    positions: Vec<BinaryExpression<F>>,
+    /// For each layer, the parent's other children, in order, i.e. every child except the target.
+    siblings: Vec<Vec<Expression<F>>>,
+}
+
+impl<F: Field> AryMerklePath<F> {
+    pub fn new(positions: Vec<BinaryExpression<F>>, siblings: Vec<Vec<Expression<F>>>) -> Self {
+        assert_eq!(positions.len(), siblings.len());
+        AryMerklePath { positions, siblings }
+    }
+
+    /// The number of layers above the leaf.
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+}
+
+impl<F: Field> Clone for AryMerklePath<F> {
+    fn clone(&self) -> Self {
+        AryMerklePath {
+            positions: self.positions.clone(),
+            siblings: self.siblings.clone(),
+        }
+    }
+}
+
 impl<F: Field> GadgetBuilder<F> {
     /// Update an intermediate hash value in a Merkle tree, given the sibling at the current layer.
     fn merkle_tree_step<CF>(
@@ -54,36 +166,302 @@ impl<F: Field> GadgetBuilder<F> {
         path: &MerklePath<F>,
         compress: &CF,
     ) -> Expression<F> where CF: CompressionFunction<F> {
-        let mut current = leaf.clone();
-        for (prefix_bit, sibling) in path.prefix.bits.iter().zip(path.siblings.iter()) {
-            current = self.merkle_tree_step(
-                &current, sibling, prefix_bit, compress);
-        }
-        current
+        logged!(self, "merkle_tree_root", [leaf, path.depth()], {
+            let mut current = leaf.clone();
+            for (prefix_bit, sibling) in path.prefix.bits.iter().zip(path.siblings.iter()) {
+                current = self.merkle_tree_step(
+                    &current, sibling, prefix_bit, compress);
+            }
+            current
+        })
     }
 
-    pub fn assert_merkle_tree_membership<E1, E2, MP, CF>(
+    /// Compute a Merkle root given a leaf value and a path whose first `depth` layers are used,
+    /// so that trees of any depth up to `path.depth()` can be verified by the same circuit. Layers
+    /// at or above `depth` are skipped, along with their prefix bits and siblings, which may have
+    /// arbitrary values. If `depth > path.depth()`, the gadget will not be satisfiable.
+    ///
+    /// Whether each layer is active is derived from a one-hot encoding of `depth`, which costs
+    /// `path.depth() + 1` boolean wires. Each layer is then compressed as usual, and the active
+    /// flag selects between the compressed value and the node passed through unchanged, which
+    /// costs one more constraint per layer.
+    pub fn merkle_tree_root_variable_depth<CF>(
+        &mut self,
+        leaf: &Expression<F>,
+        path: &MerklePath<F>,
+        depth: &Expression<F>,
+        compress: &CF,
+    ) -> Expression<F> where CF: CompressionFunction<F> {
+        logged!(self, "merkle_tree_root_variable_depth", [leaf, path.depth(), depth], {
+            let depth_one_hot = self.one_hot_from_index(depth, path.depth() + 1);
+            // Layer i is active iff depth > i, i.e. the set bit comes after i.
+            let mut active = Expression::one();
+            let mut current = leaf.clone();
+            let layers = path.prefix.bits.iter().zip(&path.siblings).enumerate();
+            for (i, (prefix_bit, sibling)) in layers {
+                active -= depth_one_hot[i].expression();
+                let active = BooleanExpression::new_unsafe(active.clone());
+                let parent = self.merkle_tree_step(&current, sibling, prefix_bit, compress);
+                current = self.selection(&active, &parent, &current);
+            }
+            current
+        })
+    }
+
+    /// Like `merkle_tree_root`, but also returns the leaf's index, as given by `MerklePath::index`.
+    /// This is useful for binding the leaf's position to other values, such as nullifiers.
+    pub fn merkle_tree_root_with_index<CF>(
+        &mut self,
+        leaf: &Expression<F>,
+        path: &MerklePath<F>,
+        compress: &CF,
+    ) -> (Expression<F>, Expression<F>) where CF: CompressionFunction<F> {
+        logged!(self, "merkle_tree_root_with_index", [leaf, path.depth()], {
+            (self.merkle_tree_root(leaf, path, compress), path.index())
+        })
+    }
+
+    /// Compresses any number of inputs into one with a two-input compression function, by folding
+    /// them as a binary tree. Each layer compresses adjacent pairs from left to right, and carries
+    /// an unpaired last input up to the next layer unchanged, so `[a, b, c, d, e]` becomes
+    /// `c(c(c(a, b), c(c, d)), e)`. This costs `n - 1` compressions. Panics if `inputs` is empty.
+    pub fn compress_multi<CF>(&mut self, inputs: &[Expression<F>], compress: &CF) -> Expression<F>
+        where CF: CompressionFunction<F> {
+        logged!(self, "compress_multi", [inputs.len()], {
+            assert!(!inputs.is_empty(), "Expected at least one input");
+            let mut layer = inputs.to_vec();
+            while layer.len() > 1 {
+                layer = layer.chunks(2)
+                    .map(|pair| match pair {
+                        [left, right] => compress.compress(self, left, right),
+                        [single] => single.clone(),
+                        _ => unreachable!(),
+                    })
+                    .collect();
+            }
+            layer.pop().unwrap()
+        })
+    }
+
+    /// Compute the root of a Merkle tree in which each node has `arity` children, given a leaf
+    /// value and its path. Each parent is the `compress_multi` of its children.
+    ///
+    /// At each layer, the position is converted to a one-hot vector, which places the current node
+    /// among the siblings at a cost of about `2 * arity` constraints. Positions must have enough
+    /// bits to represent `arity - 1`; if they have more, positions of `arity` or more are rejected.
+    pub fn merkle_tree_root_arity<CF>(
+        &mut self,
+        leaf: &Expression<F>,
+        path: &AryMerklePath<F>,
+        arity: usize,
+        compress: &CF,
+    ) -> Expression<F> where CF: CompressionFunction<F> {
+        logged!(self, "merkle_tree_root_arity", [leaf, path.depth(), arity], {
+            assert!(arity >= 2, "Expected an arity of at least 2");
+            let mut current = leaf.clone();
+            for (position, siblings) in path.positions.iter().zip(path.siblings.iter()) {
+                assert_eq!(siblings.len(), arity - 1, "Expected {} siblings per layer", arity - 1);
+                assert!(position.len() < usize::BITS as usize && 1 << position.len() >= arity,
+                        "Positions must have enough bits to represent {}", arity - 1);
+                let one_hot = self.one_hot_from_binary(position);
+                for out_of_range in &one_hot[arity..] {
+                    self.assert_false(out_of_range);
+                }
+                let children = self.place_child(&current, siblings, &one_hot[..arity]);
+                current = self.compress_multi(&children, compress);
+            }
+            current
+        })
+    }
+
+    /// Assert that `leaf` is a member of the Merkle tree with root `purported_root`, in which each
+    /// node has `arity` children.
+    pub fn assert_merkle_tree_membership_arity<CF>(
         &mut self,
         leaf: &Expression<F>,
         purported_root: &Expression<F>,
+        path: &AryMerklePath<F>,
+        arity: usize,
+        compress: &CF,
+    ) where CF: CompressionFunction<F> {
+        logged!(self, "assert_merkle_tree_membership_arity",
+                [leaf, purported_root, path.depth(), arity], {
+            let computed_root = self.merkle_tree_root_arity(leaf, path, arity, compress);
+            self.assert_equal(purported_root, &computed_root)
+        })
+    }
+
+    /// Inserts `node` among `siblings` at the position whose bit is set in `one_hot`, returning
+    /// the full list of children. Child `j` is the node if the position is `j`, otherwise sibling
+    /// `j` if the position is after `j`, otherwise sibling `j - 1`.
+    fn place_child(
+        &mut self,
+        node: &Expression<F>,
+        siblings: &[Expression<F>],
+        one_hot: &[BooleanExpression<F>],
+    ) -> Vec<Expression<F>> {
+        let arity = one_hot.len();
+        // The sum of the one-hot bits before `j`, i.e. whether the position is before `j`.
+        let mut before = Expression::zero();
+        (0..arity)
+            .map(|j| {
+                // For the first and last children, these coincide, so their difference is zero and
+                // the second product is free.
+                let sibling = &siblings[j.min(arity - 2)];
+                let previous = &siblings[j.saturating_sub(1)];
+                let child = sibling + self.product(one_hot[j].expression(), &(node - sibling))
+                    + self.product(&before, &(previous - sibling));
+                before += one_hot[j].expression();
+                child
+            })
+            .collect()
+    }
+
+    /// Assert that `leaf` is a member of the Merkle tree with root `purported_root`.
+    pub fn assert_merkle_tree_membership<CF>(
+        &mut self,
+        leaf: &Expression<F>,
+        purported_root: &Expression<F>,
+        path: &MerklePath<F>,
+        compress: &CF,
+    ) where CF: CompressionFunction<F> {
+        logged!(self, "assert_merkle_tree_membership", [leaf, purported_root, path.depth()], {
+            let computed_root = self.merkle_tree_root(leaf, path, compress);
+            self.assert_equal(purported_root, &computed_root)
+        })
+    }
+
+    /// Assert that `leaf` is a member of the Merkle tree with root `purported_root`, but only if
+    /// `active` is set. If it is not set, the leaf, root and path may have arbitrary values.
+    ///
+    /// The leaf, siblings and prefix bits are multiplied by `active` before hashing, so when it is
+    /// not set, the inputs to the compression function are fixed constants, independent of the
+    /// witness. The first level compresses two zeros, and each level above compresses the previous
+    /// level's output with zero. Thus this works even with compression functions that constrain
+    /// their inputs, provided they accept these constant inputs.
+    pub fn assert_merkle_membership_if<CF>(
+        &mut self,
+        active: &BooleanExpression<F>,
+        leaf: &Expression<F>,
+        purported_root: &Expression<F>,
         path: &MerklePath<F>,
         compress: &CF,
     ) where CF: CompressionFunction<F> {
-        let computed_root = self.merkle_tree_root(leaf, path, compress);
-        self.assert_equal(purported_root, &computed_root)
+        logged!(self, "assert_merkle_membership_if",
+                [active.expression(), leaf, purported_root, path.depth()], {
+            let active_exp = active.expression();
+            let gated_leaf = self.product(active_exp, leaf);
+            let gated_siblings = path.siblings.iter()
+                .map(|sibling| self.product(active_exp, sibling))
+                .collect();
+            let gated_prefix = BinaryExpression {
+                bits: path.prefix.bits.iter().map(|bit| self.and(active, bit)).collect(),
+            };
+            let gated_path = MerklePath::new(gated_prefix, gated_siblings);
+            let computed_root = self.merkle_tree_root(&gated_leaf, &gated_path, compress);
+            self.assert_equal_if(active, purported_root, &computed_root)
+        })
+    }
+
+    /// Like `merkle_tree_step`, but for binary digests.
+    fn merkle_tree_step_binary<CF>(
+        &mut self,
+        node: &BinaryExpression<F>,
+        sibling: &BinaryExpression<F>,
+        prefix_bit: &BooleanExpression<F>,
+        compress: &CF,
+    ) -> BinaryExpression<F> where CF: BinaryCompressionFunction<F> {
+        let left = self.binary_selection(prefix_bit, sibling, node);
+        // Each bit of the right child is whichever of the two bits wasn't selected for the left.
+        let right_bits = sibling.bits.iter().zip(&node.bits).zip(&left.bits)
+            .map(|((s, n), l)| BooleanExpression::new_unsafe(
+                s.expression() + n.expression() - l.expression()))
+            .collect();
+        compress.compress(self, &left, &BinaryExpression { bits: right_bits })
+    }
+
+    /// Compute a Merkle root given a leaf digest and its Merkle path, where nodes are binary
+    /// digests of `compress.digest_bits()` bits. Selecting the order of each pair of children costs
+    /// one constraint per bit, in addition to the cost of `compress`.
+    pub fn merkle_tree_root_binary<CF>(
+        &mut self,
+        leaf: &BinaryExpression<F>,
+        path: &BinaryMerklePath<F>,
+        compress: &CF,
+    ) -> BinaryExpression<F> where CF: BinaryCompressionFunction<F> {
+        logged!(self, "merkle_tree_root_binary",
+                [leaf.join_allowing_overflow(), path.siblings.len()], {
+            let digest_bits = compress.digest_bits();
+            assert_eq!(leaf.len(), digest_bits, "Leaf must have {} bits", digest_bits);
+            let mut current = leaf.clone();
+            for (prefix_bit, sibling) in path.prefix.bits.iter().zip(path.siblings.iter()) {
+                assert_eq!(sibling.len(), digest_bits, "Siblings must have {} bits", digest_bits);
+                current = self.merkle_tree_step_binary(&current, sibling, prefix_bit, compress);
+            }
+            current
+        })
+    }
+
+    /// Assert that `leaf` is a member of the Merkle tree with root `purported_root`, where nodes
+    /// are binary digests. The roots are compared bit by bit, so this works for digests of any
+    /// width.
+    pub fn assert_merkle_tree_membership_binary<CF>(
+        &mut self,
+        leaf: &BinaryExpression<F>,
+        purported_root: &BinaryExpression<F>,
+        path: &BinaryMerklePath<F>,
+        compress: &CF,
+    ) where CF: BinaryCompressionFunction<F> {
+        logged!(self, "assert_merkle_tree_membership_binary",
+                [leaf.join_allowing_overflow(), purported_root.join_allowing_overflow(),
+                 path.siblings.len()], {
+            let computed_root = self.merkle_tree_root_binary(leaf, path, compress);
+            assert_eq!(purported_root.len(), computed_root.len());
+            let bit_pairs = purported_root.bits.iter().zip(&computed_root.bits);
+            for (purported_bit, computed_bit) in bit_pairs {
+                self.assert_equal(purported_bit.expression(), computed_bit.expression());
+            }
+        })
     }
 }
 
+/// An error indicating that a Merkle path's prefix doesn't have one bit per sibling.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MerklePathLengthError {
+    /// The number of bits in the prefix.
+    pub prefix_bits: usize,
+    /// The number of siblings.
+    pub siblings: usize,
+}
+
+impl fmt::Display for MerklePathLengthError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Merkle path has {} prefix bits, but {} siblings",
+               self.prefix_bits, self.siblings)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MerklePathLengthError {}
+
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
     use num::BigUint;
 
     use crate::expression::{BinaryExpression, BooleanExpression, Expression};
     use crate::field::{Element, Field};
     use crate::gadget_builder::GadgetBuilder;
-    use crate::gadget_traits::CompressionFunction;
-    use crate::merkle_trees::MerklePath;
-    use crate::test_util::{F257, F7};
+    use crate::gadget_traits::{BinaryCompressionFunction, CompressionFunction};
+    use crate::merkle_trees::{AryMerklePath, BinaryMerklePath, MerklePath, MerklePathLengthError};
+    #[cfg(feature = "rand")]
+    use crate::poseidon::poseidon_compression;
+    use crate::fields::small::{F257, F7};
+    #[cfg(feature = "rand")]
+    use crate::Bn128;
+    use crate::wire::{BinaryWire, Wire};
+    use crate::wire_values::WireValues;
 
     #[test]
     fn merkle_step() {
@@ -107,6 +485,113 @@ mod tests {
         assert_eq!(Element::from(11u8), parent_hash.evaluate(&values_4_3));
     }
 
+    /// Folds elements as `compress_multi` does, but out of circuit, with `TestCompress`.
+    fn compress_multi_evaluate(inputs: &[Element<F257>]) -> Element<F257> {
+        if inputs.len() == 1 {
+            return inputs[0].clone();
+        }
+        let layer: Vec<Element<F257>> = inputs.chunks(2)
+            .map(|pair| match pair {
+                [left, right] => TestCompress.compress_evaluate(left, right),
+                _ => pair[0].clone(),
+            })
+            .collect();
+        compress_multi_evaluate(&layer)
+    }
+
+    #[test]
+    fn compress_multi() {
+        for n in 1..8u8 {
+            let mut builder = GadgetBuilder::<F257>::new();
+            let (wires, inputs) = builder.expression_wires(n as usize);
+            let compressed = builder.compress_multi(&inputs, &TestCompress);
+            let gadget = builder.build();
+
+            let elements: Vec<Element<F257>> = (0..n).map(|i| Element::from(i * 7 + 1)).collect();
+            let mut values = WireValues::new();
+            values.set_each(&wires, &elements);
+            assert!(gadget.execute(&mut values));
+            assert_eq!(compress_multi_evaluate(&elements), compressed.evaluate(&values));
+        }
+
+        // Five inputs are folded as c(c(c(a, b), c(c, d)), e).
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (_wires, inputs) = builder.expression_wires(5);
+        let ab = TestCompress.compress(&mut builder, &inputs[0], &inputs[1]);
+        let cd = TestCompress.compress(&mut builder, &inputs[2], &inputs[3]);
+        let abcd = TestCompress.compress(&mut builder, &ab, &cd);
+        let expected = TestCompress.compress(&mut builder, &abcd, &inputs[4]);
+        assert_eq!(expected, builder.compress_multi(&inputs, &TestCompress));
+    }
+
+    #[test]
+    fn merkle_root_arity_4() {
+        let leaf_positions = [2u8, 0, 3];
+        let mut node = Element::<F257>::from(5u8);
+        for (layer, &position) in leaf_positions.iter().enumerate() {
+            let mut children: Vec<Element<F257>> = (0..3u8)
+                .map(|i| Element::from(10 * layer as u8 + i))
+                .collect();
+            children.insert(position as usize, node);
+            node = compress_multi_evaluate(&children);
+        }
+
+        // A membership proof in an arity-4 tree of depth 3, with 2-bit positions.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let leaf = builder.wire();
+        let root = builder.wire();
+        let positions: Vec<BinaryWire> = (0..3).map(|_i| builder.binary_wire(2)).collect();
+        let siblings: Vec<Vec<Wire>> = (0..3).map(|_i| builder.wires(3)).collect();
+        let path = AryMerklePath::new(
+            positions.iter().map(BinaryExpression::from).collect(),
+            siblings.iter()
+                .map(|layer| layer.iter().map(|&wire| Expression::from(wire)).collect())
+                .collect());
+        assert_eq!(3, path.depth());
+        builder.assert_merkle_tree_membership_arity(
+            &Expression::from(leaf), &Expression::from(root), &path, 4, &TestCompress);
+        let gadget = builder.build();
+
+        let assign = move |position_values: &[u8], root_value: Element<F257>| {
+            let mut values = values!(leaf => 5u8.into(), root => root_value);
+            for (layer, (position, wires)) in positions.iter().zip(&siblings).enumerate() {
+                values.set_binary_unsigned(position, &BigUint::from(position_values[layer]));
+                for (i, &wire) in wires.iter().enumerate() {
+                    values.set(wire, Element::from((10 * layer + i) as u8));
+                }
+            }
+            values
+        };
+        assert!(gadget.execute(&mut assign(&leaf_positions, node.clone())));
+        // TestCompress folds four children to 4a + 2b + 2c + d, so moving the leaf from position
+        // 2 to 1 would go unnoticed, but moving it to 3 changes the root.
+        assert!(!gadget.execute(&mut assign(&[3, 0, 3], node.clone())));
+        assert!(!gadget.execute(&mut assign(&leaf_positions, node + Element::one())));
+    }
+
+    #[test]
+    fn merkle_root_arity_3_position_out_of_range() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let position = builder.binary_wire(2);
+        let (sibling_1, sibling_2) = (builder.wire(), builder.wire());
+        let path = AryMerklePath::new(
+            vec![BinaryExpression::from(&position)],
+            vec![vec![sibling_1.into(), sibling_2.into()]]);
+        let root = builder.merkle_tree_root_arity(&Expression::one(), &path, 3, &TestCompress);
+        let gadget = builder.build();
+
+        let assign = |position_value: u8| {
+            let mut values = values!(sibling_1 => 3u8.into(), sibling_2 => 4u8.into());
+            values.set_binary_unsigned(&position, &BigUint::from(position_value));
+            values
+        };
+        let mut values = assign(1);
+        assert!(gadget.execute(&mut values));
+        // The children are [3, 1, 4], which fold to c(c(3, 1), 4) = 2 * 7 + 4.
+        assert_eq!(Element::from(18u8), root.evaluate(&values));
+        assert!(!gadget.execute(&mut assign(3)));
+    }
+
     #[test]
     fn merkle_root() {
         let mut builder = GadgetBuilder::<F257>::new();
@@ -154,13 +639,350 @@ mod tests {
             );
         values.set_binary_unsigned(&prefix_wire, &BigUint::from(0b00000000u8));
         assert!(gadget.execute(&mut values));
-        // The leaf is 1; the first parent hash is 2*1 + 1 = 3; 2*3 + 1 == 0; 2*0 + 1 = 1; 2*1 + 1 = 3; 2*3 + 1 == 0; 2*0 + 1 = 1; 2*1 + 1 = 3;
-        // the root is 2*3 + 1 == 0.
+        // The leaf is 1; the first parent hash is 2*1 + 1 = 3; 2*3 + 1 == 0; 2*0 + 1 = 1; 2*1 + 1 =
+        // 3; 2*3 + 1 == 0; 2*0 + 1 = 1; 2*1 + 1 = 3; the root is 2*3 + 1 == 0.
         assert_eq!(
             Element::from(0u8),
             root_hash.evaluate(&values));
     }
 
+    #[test]
+    #[cfg(feature = "rand")]
+    fn merkle_root_poseidon_bn128() {
+        let compress = poseidon_compression::<Bn128>();
+
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let leaf = builder.wire();
+        let prefix_wire = builder.binary_wire(2);
+        let (sibling_1, sibling_2) = (builder.wire(), builder.wire());
+        let path = MerklePath::new(
+            BinaryExpression::from(&prefix_wire),
+            vec![sibling_1.into(), sibling_2.into()]);
+        let root_hash = builder.merkle_tree_root(&Expression::from(leaf), &path, &compress);
+        let gadget = builder.build();
+
+        let mut values = values!(
+            leaf => 5u8.into(), sibling_1 => 6u8.into(), sibling_2 => 7u8.into());
+        values.set_binary_unsigned(&prefix_wire, &BigUint::from(0b10u8));
+        assert!(gadget.execute(&mut values));
+
+        // The first prefix bit is 0, so the leaf is a left child; the second is 1, so its parent is
+        // a right child.
+        let parent = compress.compress_evaluate(&Element::from(5u8), &Element::from(6u8));
+        let root = compress.compress_evaluate(&Element::from(7u8), &parent);
+        assert_eq!(root, root_hash.evaluate(&values));
+    }
+
+    #[test]
+    fn merkle_membership_if() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let active = builder.boolean_wire();
+        let (leaf, root) = (builder.wire(), builder.wire());
+        let prefix_wire = builder.binary_wire(2);
+        let siblings = builder.wires(2);
+        let path = MerklePath::new(
+            BinaryExpression::from(&prefix_wire),
+            siblings.iter().map(Expression::from).collect());
+        builder.assert_merkle_membership_if(
+            &BooleanExpression::from(active), &Expression::from(leaf), &Expression::from(root),
+            &path, &TestCompress);
+        let gadget = builder.build();
+
+        // The leaf is 1, a left child; its parent is 2*1 + 3 = 5, a right child; the root is
+        // 2*4 + 5 = 13.
+        for &(root_value, expected) in &[(13u8, true), (14u8, false)] {
+            let mut values = mixed_values!(
+                leaf => 1u8.into(), root => root_value.into(),
+                siblings[0] => 3u8.into(), siblings[1] => 4u8.into(),
+                bool active => true,
+                binary &prefix_wire => BigUint::from(0b10u8));
+            assert_eq!(expected, gadget.execute(&mut values));
+        }
+
+        // When inactive, any witness is accepted.
+        let mut values = mixed_values!(
+            leaf => 123u8.into(), root => 45u8.into(),
+            siblings[0] => 67u8.into(), siblings[1] => 89u8.into(),
+            bool active => false,
+            binary prefix_wire => &BigUint::from(0b11u8),
+        );
+        assert!(gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn merkle_membership_if_inactive_inputs_are_constant() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let active = builder.boolean_wire();
+        let (leaf, root) = (builder.wire(), builder.wire());
+        let prefix_wire = builder.binary_wire(2);
+        let siblings = builder.wires(2);
+        let path = MerklePath::new(
+            BinaryExpression::from(&prefix_wire),
+            siblings.iter().map(Expression::from).collect());
+        let compress = RecordingCompress(RefCell::new(Vec::new()));
+        builder.assert_merkle_membership_if(
+            &BooleanExpression::from(active), &Expression::from(leaf), &Expression::from(root),
+            &path, &compress);
+        let gadget = builder.build();
+
+        // The compression function sees (0, 0), then (2*0 + 0 + 1, 0), whatever the witness.
+        let inputs = compress.0.into_inner();
+        let expected = vec![(Element::zero(), Element::zero()), (Element::one(), Element::zero())];
+        for &(prefix, sibling) in &[(0b00u8, 0u8), (0b11, 67), (0b01, 89)] {
+            let mut values = mixed_values!(
+                leaf => 123u8.into(), root => 45u8.into(),
+                siblings[0] => sibling.into(), siblings[1] => 5u8.into(),
+                bool active => false,
+                binary prefix_wire => &BigUint::from(prefix),
+            );
+            assert!(gadget.execute(&mut values));
+            let evaluated: Vec<(Element<F257>, Element<F257>)> = inputs.iter()
+                .map(|(x, y)| (x.evaluate(&values), y.evaluate(&values)))
+                .collect();
+            assert_eq!(expected, evaluated);
+        }
+    }
+
+    #[test]
+    fn merkle_root_binary() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let leaf = builder.binary_wire(16);
+        let prefix_wire = builder.binary_wire(2);
+        let siblings = [builder.binary_wire(16), builder.binary_wire(16)];
+        let path = BinaryMerklePath::new(
+            BinaryExpression::from(&prefix_wire),
+            siblings.iter().map(BinaryExpression::from).collect());
+        let root = builder.merkle_tree_root_binary(
+            &BinaryExpression::from(&leaf), &path, &TestBinaryCompress);
+        let gadget = builder.build();
+
+        let mut values = binary_unsigned_values!(
+            &leaf => &BigUint::from(0x1234u16),
+            &siblings[0] => &BigUint::from(0xABCDu16),
+            &siblings[1] => &BigUint::from(0x0F0Fu16),
+            &prefix_wire => &BigUint::from(0b10u8));
+        assert!(gadget.execute(&mut values));
+        // The leaf is a left child, so its parent is rotl(0x1234, 3) ^ 0xABCD = 0x91A0 ^ 0xABCD =
+        // 0x3A6D. The parent is a right child, so the root is rotl(0x0F0F, 3) ^ 0x3A6D =
+        // 0x7878 ^ 0x3A6D = 0x4215.
+        assert_eq!(BigUint::from(0x4215u16), root.evaluate(&values));
+
+        let parent = BinaryCompressionFunction::<F257>::compress_evaluate(
+            &TestBinaryCompress, &BigUint::from(0x1234u16), &BigUint::from(0xABCDu16));
+        assert_eq!(BigUint::from(0x3A6Du16), parent);
+    }
+
+    #[test]
+    fn merkle_membership_binary() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (leaf, root) = (builder.binary_wire(16), builder.binary_wire(16));
+        let prefix_wire = builder.binary_wire(2);
+        let siblings = [builder.binary_wire(16), builder.binary_wire(16)];
+        let path = BinaryMerklePath::new(
+            BinaryExpression::from(&prefix_wire),
+            siblings.iter().map(BinaryExpression::from).collect());
+        builder.assert_merkle_tree_membership_binary(
+            &BinaryExpression::from(&leaf), &BinaryExpression::from(&root), &path,
+            &TestBinaryCompress);
+        let gadget = builder.build();
+
+        for &(leaf_value, prefix, expected) in &[
+            (0x1234u16, 0b10u8, true),
+            (0x1235u16, 0b10u8, false),
+            (0x1234u16, 0b01u8, false),
+        ] {
+            let mut values = binary_unsigned_values!(
+                &leaf => &BigUint::from(leaf_value),
+                &root => &BigUint::from(0x4215u16),
+                &siblings[0] => &BigUint::from(0xABCDu16),
+                &siblings[1] => &BigUint::from(0x0F0Fu16),
+                &prefix_wire => &BigUint::from(prefix));
+            assert_eq!(expected, gadget.execute(&mut values));
+        }
+    }
+
+    /// A toy binary compression function over 16-bit digests, which returns `rotl(x, 3) ^ y`.
+    struct TestBinaryCompress;
+
+    impl<F: Field> BinaryCompressionFunction<F> for TestBinaryCompress {
+        fn digest_bits(&self) -> usize {
+            16
+        }
+
+        fn compress(
+            &self, builder: &mut GadgetBuilder<F>, x: &BinaryExpression<F>,
+            y: &BinaryExpression<F>,
+        ) -> BinaryExpression<F> {
+            let rotated = builder.bitwise_rotate_inc_significance(x, 3);
+            builder.bitwise_xor(&rotated, y)
+        }
+    }
+
+    /// The layers of a depth 4 tree over `TestCompress`, from the 16 leaves up to the root, with
+    /// leaf `i` set to `i + 1`.
+    fn test_tree() -> Vec<Vec<Element<F257>>> {
+        let mut layers = vec![(1u8..=16).map(Element::from).collect::<Vec<_>>()];
+        while layers.last().unwrap().len() > 1 {
+            let parents = layers.last().unwrap().chunks(2)
+                .map(|pair| TestCompress.compress_evaluate(&pair[0], &pair[1]))
+                .collect();
+            layers.push(parents);
+        }
+        layers
+    }
+
+    /// Wire values proving membership of the leaf at `index`, with `index_value` assigned to the
+    /// index wire.
+    fn indexed_membership_values(
+        wires: [Wire; 3], sibling_wires: &[Wire], index: usize, index_value: usize,
+    ) -> WireValues<F257> {
+        let tree = test_tree();
+        let [index_wire, leaf, root] = wires;
+        let siblings: Vec<Element<F257>> = (0..4)
+            .map(|layer| tree[layer][(index >> layer) ^ 1].clone())
+            .collect();
+        let mut values = values!(
+            index_wire => index_value.into(), leaf => tree[0][index].clone(),
+            root => tree[4][0].clone());
+        values.set_each(sibling_wires, &siblings);
+        values
+    }
+
+    #[test]
+    fn merkle_membership_from_index() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (index, leaf, root) = (builder.wire(), builder.wire(), builder.wire());
+        let siblings = builder.wires(4);
+        let path = MerklePath::from_index(
+            &mut builder, &Expression::from(index),
+            siblings.iter().map(Expression::from).collect());
+        assert_eq!(4, path.depth());
+        let (computed_root, recovered_index) =
+            builder.merkle_tree_root_with_index(&Expression::from(leaf), &path, &TestCompress);
+        builder.assert_equal(&Expression::from(root), &computed_root);
+        let gadget = builder.build();
+
+        let wires = [index, leaf, root];
+        for &index in &[0, 6, 11, 15] {
+            let mut values = indexed_membership_values(wires, &siblings, index, index);
+            assert!(gadget.execute(&mut values));
+            assert_eq!(Element::from(index), recovered_index.evaluate(&values));
+        }
+
+        // A valid path for leaf 11 doesn't work with a different index.
+        let mut values = indexed_membership_values(wires, &siblings, 11, 10);
+        assert!(!gadget.execute(&mut values));
+
+        // 16 + 11 has the same low four bits as 11, but doesn't fit in four bits.
+        let mut values = indexed_membership_values(wires, &siblings, 11, 16 + 11);
+        assert!(!gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn try_new_length_mismatch() {
+        let prefix = BinaryExpression::<F257>::from(1u8).padded(2);
+        let error = MerklePath::try_new(prefix.clone(), vec![Expression::one()]).unwrap_err();
+        assert_eq!(MerklePathLengthError { prefix_bits: 2, siblings: 1 }, error);
+        assert!(MerklePath::try_new(prefix, vec![Expression::one(); 2]).is_ok());
+    }
+
+    /// The roots of empty subtrees of height 0 through 4 over `TestCompress`, with empty leaves
+    /// of 1.
+    fn empty_hashes() -> Vec<Element<F257>> {
+        let mut hashes = vec![Element::one()];
+        for _i in 0..4 {
+            let last = hashes.last().unwrap();
+            hashes.push(TestCompress.compress_evaluate(last, last));
+        }
+        hashes
+    }
+
+    #[test]
+    fn padded_to() {
+        // A sparse tree of height 4, whose first four leaves are 5, 6, 7 and 8.
+        let mut layer: Vec<Element<F257>> = (5u8..=8).map(Element::from).collect();
+        layer.extend(vec![Element::one(); 12]);
+        while layer.len() > 1 {
+            layer = layer.chunks(2)
+                .map(|pair| TestCompress.compress_evaluate(&pair[0], &pair[1]))
+                .collect();
+        }
+
+        // A path for the leaf 7 within the subtree of the first four leaves.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let path = MerklePath::new(
+            BinaryExpression::from(2u8).padded(2),
+            vec![Expression::from(8u8), TestCompress.compress(
+                &mut builder, &Expression::from(5u8), &Expression::from(6u8))]);
+        let padded = path.padded_to(4, &empty_hashes());
+        assert_eq!(4, padded.depth());
+        assert_eq!(path.index(), padded.index());
+        let root = builder.merkle_tree_root(&Expression::from(7u8), &padded, &TestCompress);
+        assert_eq!(Some(layer[0].clone()), root.as_constant());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected at least 4 empty subtree hashes")]
+    fn padded_to_too_few_empty_hashes() {
+        let path = MerklePath::<F257>::new(BinaryExpression::from(0u8).padded(1),
+                                           vec![Expression::one()]);
+        path.padded_to(4, &empty_hashes()[..3]);
+    }
+
+    /// Wire values proving membership of the leaf at `index` in the subtree of `test_tree` with
+    /// height `depth` containing it, claiming `depth_value` as the depth. Siblings and prefix bits
+    /// above `depth` are set to junk.
+    fn variable_depth_values(
+        wires: [Wire; 3], prefix: &BinaryWire, sibling_wires: &[Wire], index: usize, depth: usize,
+        depth_value: u8,
+    ) -> WireValues<F257> {
+        let tree = test_tree();
+        let [leaf, root, depth_wire] = wires;
+        let siblings: Vec<Element<F257>> = (0..4)
+            .map(|layer| if layer < depth {
+                tree[layer][(index >> layer) ^ 1].clone()
+            } else {
+                Element::from(99u8 + layer as u8)
+            })
+            .collect();
+        let mut values = values!(
+            leaf => tree[0][index].clone(), root => tree[depth][index >> depth].clone(),
+            depth_wire => depth_value.into());
+        let low_bits = (1 << depth) - 1;
+        values.set_binary_unsigned(prefix, &BigUint::from(index & low_bits | 0b1010 & !low_bits));
+        values.set_each(sibling_wires, &siblings);
+        values
+    }
+
+    #[test]
+    fn merkle_root_variable_depth() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (leaf, root, depth) = (builder.wire(), builder.wire(), builder.wire());
+        let prefix = builder.binary_wire(4);
+        let siblings = builder.wires(4);
+        let path = MerklePath::new(
+            BinaryExpression::from(&prefix), siblings.iter().map(Expression::from).collect());
+        let computed_root = builder.merkle_tree_root_variable_depth(
+            &Expression::from(leaf), &path, &Expression::from(depth), &TestCompress);
+        builder.assert_equal(&Expression::from(root), &computed_root);
+        let gadget = builder.build();
+
+        let wires = [leaf, root, depth];
+        for &(index, depth) in &[(6, 4), (11, 4), (2, 2), (13, 2), (5, 0)] {
+            let mut values = variable_depth_values(
+                wires, &prefix, &siblings, index, depth, depth as u8);
+            assert!(gadget.execute(&mut values));
+        }
+
+        // A depth 2 proof, with the root of the depth 2 subtree, can't claim any other depth,
+        // which would apply the junk in the inactive layers, or skip a real layer.
+        for &claimed_depth in &[1u8, 3, 4, 5] {
+            let mut values = variable_depth_values(
+                wires, &prefix, &siblings, 2, 2, claimed_depth);
+            assert!(!gadget.execute(&mut values));
+        }
+    }
+
     // A dummy compression function which returns 2x + y.
     struct TestCompress;
 
@@ -170,4 +992,16 @@ mod tests {
             x * 2 + y
         }
     }
-}
\ No newline at end of file
+
+    /// A dummy compression function which returns 2x + y + 1, recording the inputs of each
+    /// compression.
+    struct RecordingCompress<F: Field>(RefCell<Vec<(Expression<F>, Expression<F>)>>);
+
+    impl<F: Field> CompressionFunction<F> for RecordingCompress<F> {
+        fn compress(&self, _builder: &mut GadgetBuilder<F>, x: &Expression<F>, y: &Expression<F>)
+                    -> Expression<F> {
+            self.0.borrow_mut().push((x.clone(), y.clone()));
+            x * 2 + y + Expression::one()
+        }
+    }
+}
@@ -3,6 +3,8 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
+#[cfg(feature = "rand")]
+use crate::constants::derive_constants;
 use crate::expression::Expression;
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
@@ -22,17 +24,16 @@ impl<F: Field> MiMCBlockCipher<F> {
     ///
     /// The number of rounds will be `round_constants.len() + 1`, since the first round has no
     /// random constant.
-    fn new(round_constants: &[Element<F>]) -> Self {
+    pub fn new(round_constants: &[Element<F>]) -> Self {
         let round_permutation = MonomialPermutation::new(Element::from(3u8));
         let round_constants = round_constants.to_vec();
         MiMCBlockCipher { round_permutation, round_constants }
     }
-}
 
-impl<F: Field> Default for MiMCBlockCipher<F> {
-    /// Configures MiMC with the number of rounds recommended in the paper. Uses a simple LCG
-    /// (seeded with 0) as the source of randomness for these constants.
-    fn default() -> Self {
+    /// Configures MiMC as `default` did in versions up to 0.4, with round constants from a simple
+    /// LCG (seeded with 0). This exists only for compatibility with gadgets built by those
+    /// versions; the LCG constants are not domain separated from other primitives.
+    pub fn default_v0_4() -> Self {
         let mut round_constants = Vec::new();
         let mut lcg = LCG::new();
         for _r in 0..mimc_recommended_rounds::<F>() {
@@ -40,6 +41,41 @@ impl<F: Field> Default for MiMCBlockCipher<F> {
         }
         MiMCBlockCipher::new(&round_constants)
     }
+
+    /// Encrypts the given input directly, without building a gadget. This gives the same result
+    /// as `encrypt_evaluate`, which delegates to it.
+    pub fn encrypt_element(&self, key: &Element<F>, input: &Element<F>) -> Element<F> {
+        let mut current = self.round_permutation.permute_evaluate(&(input + key));
+        for round_constant in self.round_constants.iter() {
+            current += key + round_constant;
+            current = self.round_permutation.permute_evaluate(&current);
+        }
+        current + key
+    }
+
+    /// Decrypts the given output directly, without building a gadget. This gives the same result
+    /// as `decrypt_evaluate`, which delegates to it.
+    pub fn decrypt_element(&self, key: &Element<F>, output: &Element<F>) -> Element<F> {
+        let mut current = output - key;
+        for round_constant in self.round_constants.iter().rev() {
+            current = self.round_permutation.inverse_evaluate(&current);
+            current -= key + round_constant;
+        }
+        self.round_permutation.inverse_evaluate(&current) - key
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<F: Field> Default for MiMCBlockCipher<F> {
+    /// Configures MiMC with the number of rounds recommended in the paper. The round constants are
+    /// derived by `derive_constants` with the tag `"MiMC"`.
+    ///
+    /// This is a breaking change from versions up to 0.4, whose constants came from an LCG; use
+    /// `default_v0_4` to reproduce them.
+    fn default() -> Self {
+        let round_constants = derive_constants("MiMC", mimc_recommended_rounds::<F>());
+        MiMCBlockCipher::new(&round_constants)
+    }
 }
 
 impl<F: Field> BlockCipher<F> for MiMCBlockCipher<F> {
@@ -65,6 +101,10 @@ impl<F: Field> BlockCipher<F> for MiMCBlockCipher<F> {
         current + key
     }
 
+    fn encrypt_evaluate(&self, key: &Element<F>, input: &Element<F>) -> Element<F> {
+        self.encrypt_element(key, input)
+    }
+
     fn decrypt(&self, builder: &mut GadgetBuilder<F>, key: &Expression<F>, output: &Expression<F>)
                -> Expression<F> {
         let mut current = output.clone();
@@ -84,6 +124,10 @@ impl<F: Field> BlockCipher<F> for MiMCBlockCipher<F> {
         current = self.round_permutation.inverse(builder, &current);
         current - key
     }
+
+    fn decrypt_evaluate(&self, key: &Element<F>, output: &Element<F>) -> Element<F> {
+        self.decrypt_element(key, output)
+    }
 }
 
 /// The MiMC permutation, which is equivalent to MiMC encryption with a key of zero.
@@ -99,9 +143,17 @@ impl<F: Field> Permutation<F> for MiMCPermutation<F> {
         self.cipher.encrypt(builder, &Expression::zero(), x)
     }
 
+    fn permute_evaluate(&self, x: &Element<F>) -> Element<F> {
+        self.cipher.encrypt_element(&Element::zero(), x)
+    }
+
     fn inverse(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>) -> Expression<F> {
         self.cipher.decrypt(builder, &Expression::zero(), x)
     }
+
+    fn inverse_evaluate(&self, x: &Element<F>) -> Element<F> {
+        self.cipher.decrypt_element(&Element::zero(), x)
+    }
 }
 
 /// The recommended number of rounds to use in MiMC, based on the paper.
@@ -112,14 +164,18 @@ fn mimc_recommended_rounds<F: Field>() -> usize {
 
 #[cfg(test)]
 mod tests {
+    use std::fmt::Debug;
+
     use crate::expression::Expression;
-    use crate::field::Element;
+    use crate::field::{Element, Field};
     use crate::gadget_builder::GadgetBuilder;
     use crate::gadget_traits::BlockCipher;
     use crate::mimc::MiMCBlockCipher;
-    use crate::test_util::{F11, F7};
+    use crate::fields::small::{F11, F257, F7};
+    use crate::wire_values::WireValues;
 
     #[test]
+    #[cfg(feature = "rand")]
     fn mimc_encrypt_and_decrypt() {
         let mut builder = GadgetBuilder::<F11>::new();
         let key_wire = builder.wire();
@@ -154,10 +210,52 @@ mod tests {
         assert_eq!(Element::from(2u8), mimc_output.evaluate(&values));
     }
 
+    /// `default_v0_4` must reproduce the outputs of `default` in versions up to 0.4.
+    #[test]
+    fn mimc_default_v0_4_outputs() {
+        fn encrypt<F: Field>(mimc: &MiMCBlockCipher<F>) -> Element<F> {
+            let mut builder = GadgetBuilder::new();
+            let output = mimc.encrypt(&mut builder, &Expression::from(2u8), &Expression::from(3u8));
+            let mut values = WireValues::new();
+            assert!(builder.build().execute(&mut values));
+            output.evaluate(&values)
+        }
+
+        assert_eq!(Element::from(10u8), encrypt(&MiMCBlockCipher::<F11>::default_v0_4()));
+        assert_eq!(Element::from(238u8), encrypt(&MiMCBlockCipher::<F257>::default_v0_4()));
+        #[cfg(feature = "rand")]
+        assert_ne!(Element::from(238u8), encrypt(&MiMCBlockCipher::<F257>::default()));
+    }
+
+    /// `encrypt_element` and `decrypt_element` must agree with the gadgets which `encrypt_evaluate`
+    /// and `decrypt_evaluate` used to build.
+    #[test]
+    fn element_evaluation_matches_gadget() {
+        fn check<F: Field + Debug>(mimc: &MiMCBlockCipher<F>, key: Element<F>, x: Element<F>) {
+            let mut builder = GadgetBuilder::new();
+            let (key_exp, x_exp) = (Expression::from(&key), Expression::from(&x));
+            let encrypted = mimc.encrypt(&mut builder, &key_exp, &x_exp);
+            let decrypted = mimc.decrypt(&mut builder, &key_exp, &x_exp);
+            let mut values = WireValues::new();
+            assert!(builder.build().execute(&mut values));
+            assert_eq!(encrypted.evaluate(&values), mimc.encrypt_evaluate(&key, &x));
+            assert_eq!(decrypted.evaluate(&values), mimc.decrypt_evaluate(&key, &x));
+        }
+
+        let mimc = MiMCBlockCipher::<F11>::default_v0_4();
+        for key in 0u8..11 {
+            for x in 0u8..11 {
+                check(&mimc, Element::from(key), Element::from(x));
+            }
+        }
+        // Cubing isn't a permutation of Bn128, so MiMC can't be used there.
+        check(&MiMCBlockCipher::<F257>::default_v0_4(), Element::from(42u8), Element::from(200u8));
+    }
+
     /// MiMC is incompatible with F_7, because cubing is not a permutation in this field.
     #[test]
     #[should_panic]
     fn mimc_f7_incompatible() {
-        MiMCBlockCipher::<F7>::default();
+        MiMCBlockCipher::<F7>::default_v0_4();
     }
 }
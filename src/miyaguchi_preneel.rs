@@ -9,7 +9,7 @@ use crate::gadget_traits::{BlockCipher, CompressionFunction};
 /// block cipher.
 pub struct MiyaguchiPreneel<F: Field, BC: BlockCipher<F>> {
     cipher: BC,
-    phantom: PhantomData<*const F>,
+    phantom: PhantomData<fn() -> F>,
 }
 
 impl<F: Field, BC: BlockCipher<F>> MiyaguchiPreneel<F, BC> {
@@ -33,7 +33,7 @@ mod tests {
     use crate::gadget_builder::GadgetBuilder;
     use crate::gadget_traits::{BlockCipher, CompressionFunction};
     use crate::miyaguchi_preneel::MiyaguchiPreneel;
-    use crate::test_util::F7;
+    use crate::fields::small::F7;
 
     #[test]
     fn miyaguchi_preneel() {
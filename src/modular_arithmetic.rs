@@ -0,0 +1,156 @@
+//! This module extends GadgetBuilder with congruences modulo constants other than the field order,
+//! such as the order of an embedded curve's subgroup, which may be as large as the field or larger.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::{BigUint, One, Zero};
+
+use crate::coprimality::LIMB_BITS;
+use crate::expression::{BinaryExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::wire_values::WireValues;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Assert that `x * y = z (mod n)`, where the operands are unsigned integers given in binary,
+    /// which may be longer than the field, and `n > 1` is a constant.
+    ///
+    /// The prover supplies a quotient `q` with `x * y = q * n + z`, which is verified over the
+    /// integers with limb arithmetic, as in `assert_coprime_to_constant`. Such a quotient exists
+    /// only if `z <= x * y`, so the prover should supply a `z` which is reduced modulo `n`, but
+    /// this method does not check that it is.
+    pub fn assert_product_mod_constant(
+        &mut self,
+        x: &BinaryExpression<F>,
+        y: &BinaryExpression<F>,
+        z: &BinaryExpression<F>,
+        n: &BigUint,
+    ) {
+        logged!(self, "assert_product_mod_constant",
+                [x.join_allowing_overflow(), y.join_allowing_overflow(),
+                 z.join_allowing_overflow(), n], {
+            assert!(n > &BigUint::one(), "Expected a modulus greater than 1");
+
+            // Since x * y < 2^(|x| + |y|) and n >= 2^(bits(n) - 1), this bounds the quotient.
+            let q_bits = (x.len() + y.len() + 1).saturating_sub(n.bits() as usize).max(1);
+            let q_limb_count = q_bits.div_ceil(LIMB_BITS);
+
+            let outputs = {
+                let (x, y, z, n) = (x.clone(), y.clone(), z.clone(), n.clone());
+                let mut dependencies = x.dependencies();
+                dependencies.extend(y.dependencies());
+                dependencies.extend(z.dependencies());
+                self.hint(dependencies, q_limb_count, move |values: &WireValues<F>| {
+                    let product = x.evaluate(values) * y.evaluate(values);
+                    let z = z.evaluate(values);
+                    // If z exceeds the product, no quotient can satisfy the constraints.
+                    let q = if z <= product { (product - z) / &n } else { BigUint::zero() };
+                    let mut limbs = q.to_u64_digits();
+                    limbs.resize(q_limb_count, 0);
+                    limbs.into_iter().map(Element::from).collect()
+                })
+            };
+
+            let q_limbs: Vec<Expression<F>> = outputs.wires().iter().enumerate()
+                .map(|(i, &wire)| {
+                    let limb = Expression::from(wire);
+                    self.split_bounded(&limb, LIMB_BITS.min(q_bits - i * LIMB_BITS));
+                    limb
+                })
+                .collect();
+            let x_limbs = self.binary_limbs(x);
+            let y_limbs = self.binary_limbs(y);
+            let z_limbs = self.binary_limbs(z);
+            self.assert_limb_identity(&x_limbs, &y_limbs, &q_limbs, n, &z_limbs);
+        })
+    }
+
+    /// Assert that `x = y (mod n)`, where the operands are unsigned integers given in binary,
+    /// which may be longer than the field, and `n > 1` is a constant.
+    ///
+    /// This is equivalent to `assert_product_mod_constant` with `y = 1`, so the prover should
+    /// supply a `y` which is reduced modulo `n`. Combined with a range check of `y`, this asserts
+    /// that `y = x mod n`.
+    pub fn assert_congruent_mod_constant(
+        &mut self,
+        x: &BinaryExpression<F>,
+        y: &BinaryExpression<F>,
+        n: &BigUint,
+    ) {
+        logged!(self, "assert_congruent_mod_constant",
+                [x.join_allowing_overflow(), y.join_allowing_overflow(), n], {
+            let one = BinaryExpression::from(BigUint::one());
+            self.assert_product_mod_constant(x, &one, y, n);
+        })
+    }
+
+    /// Joins each `LIMB_BITS`-bit chunk of `x` into a limb, which is known to be bounded.
+    fn binary_limbs(&mut self, x: &BinaryExpression<F>) -> Vec<Expression<F>> {
+        x.chunks(LIMB_BITS).iter()
+            .map(|chunk| {
+                let limb = chunk.join();
+                self.mark_bounded(&limb, chunk.len());
+                limb
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::{BinaryExpression, Bn128, Element, Expression, GadgetBuilder, WireValues};
+
+    /// A 128-bit modulus: the product of two 64-bit primes.
+    fn modulus() -> BigUint {
+        BigUint::from(18446744073709551557u64) * BigUint::from(18446744073709551533u64)
+    }
+
+    #[test]
+    fn product_mod_constant() {
+        let n = modulus();
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.binary_wire(128);
+        let y = builder.binary_wire(128);
+        let z = builder.binary_wire(128);
+        builder.assert_product_mod_constant(
+            &BinaryExpression::from(&x), &BinaryExpression::from(&y), &BinaryExpression::from(&z),
+            &n);
+        let gadget = builder.build();
+
+        let x_value = &n - 12345u32;
+        let y_value = &n - 67890u32;
+        let z_value = &x_value * &y_value % &n;
+        let execute = |z_value: &BigUint| {
+            let mut values = WireValues::new();
+            values.set_binary_unsigned(&x, &x_value);
+            values.set_binary_unsigned(&y, &y_value);
+            values.set_binary_unsigned(&z, z_value);
+            gadget.execute(&mut values)
+        };
+        assert!(execute(&z_value));
+        assert!(!execute(&(&z_value + 1u8)));
+        assert!(!execute(&(&z_value - 1u8)));
+    }
+
+    #[test]
+    fn congruent_mod_constant() {
+        let n = modulus();
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.wire();
+        let y = builder.wire();
+        let x_bits = builder.split(&Expression::from(x));
+        let y_bits = builder.split_bounded(&Expression::from(y), 128);
+        builder.assert_congruent_mod_constant(&x_bits, &y_bits, &n);
+        let gadget = builder.build();
+
+        let x_value = BigUint::from(3u8).pow(150);
+        let reduced = &x_value % &n;
+        assert!(gadget.execute(&mut values!(
+            x => Element::from(x_value.clone()), y => Element::from(reduced.clone()))));
+        assert!(!gadget.execute(&mut values!(
+            x => Element::from(x_value), y => Element::from(reduced + 1u8))));
+    }
+}
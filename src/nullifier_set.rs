@@ -0,0 +1,245 @@
+//! This module extends GadgetBuilder with gadgets for nullifier sets stored as sparse Merkle trees.
+//! Each nullifier is the position of a leaf, which is zero if the nullifier is absent from the
+//! set, or one if it is present.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::{BinaryExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::gadget_traits::CompressionFunction;
+use crate::merkle_trees::MerklePath;
+
+/// The siblings along the path from a leaf of a sparse Merkle set up to its root. The leaf's
+/// position isn't included, since it's the key stored there.
+///
+/// The siblings are witness data which depend on the state of the whole set, so a path is only
+/// valid for the root it was taken from.
+#[derive(Debug)]
+pub struct SparseMerklePathData<F: Field> {
+    /// The sibling of each node along the path, starting with the leaf's sibling.
+    siblings: Vec<Expression<F>>,
+}
+
+impl<F: Field> SparseMerklePathData<F> {
+    pub fn new(siblings: Vec<Expression<F>>) -> Self {
+        SparseMerklePathData { siblings }
+    }
+
+    /// The height of the tree, i.e. the number of siblings.
+    pub fn height(&self) -> usize {
+        self.siblings.len()
+    }
+}
+
+impl<F: Field> Clone for SparseMerklePathData<F> {
+    fn clone(&self) -> Self {
+        SparseMerklePathData {
+            siblings: self.siblings.clone(),
+        }
+    }
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Asserts that `key` is absent from the sparse Merkle set with root `root`, and returns the
+    /// root of the set after inserting it. The key's leaf must be empty given `path`, and the new
+    /// root is computed with the same siblings, with the leaf occupied.
+    ///
+    /// Keys must fit in `path.height()` bits, or else the gadget will not be satisfiable. A tree of
+    /// height `max_bits` accepts any field element as a key.
+    pub fn sparse_merkle_insert<CF>(
+        &mut self,
+        root: &Expression<F>,
+        key: &Expression<F>,
+        path: &SparseMerklePathData<F>,
+        compress: &CF,
+    ) -> Expression<F> where CF: CompressionFunction<F> {
+        logged!(self, "sparse_merkle_insert", [root, key], {
+            let prefix = self.sparse_merkle_key_bits(key, path.height());
+            let merkle_path = MerklePath::new(prefix, path.siblings.clone());
+            self.assert_merkle_tree_membership(&Expression::zero(), root, &merkle_path, compress);
+            self.merkle_tree_root(&Expression::one(), &merkle_path, compress)
+        })
+    }
+
+    /// Asserts that the given nullifiers are pairwise distinct and absent from the sparse Merkle
+    /// set with root `old_root`, and returns the root of the set after inserting each of them in
+    /// order. Each nullifier costs two Merkle root computations and a split of its bits, and the
+    /// distinctness check costs `k (k - 1) / 2` constraints for `k` nullifiers.
+    ///
+    /// The insertions are chained, so `paths[i]` must be taken from the set after inserting the
+    /// nullifiers before `i`, not from the set with root `old_root`. Nullifiers must fit in the
+    /// height of the paths, as with `sparse_merkle_insert`.
+    pub fn batch_nullifier_update<CF>(
+        &mut self,
+        old_root: &Expression<F>,
+        nullifiers: &[Expression<F>],
+        paths: &[SparseMerklePathData<F>],
+        compress: &CF,
+    ) -> Expression<F> where CF: CompressionFunction<F> {
+        logged!(self, "batch_nullifier_update", [old_root, nullifiers.len(), paths.len()], {
+            assert_eq!(nullifiers.len(), paths.len(), "Expected one path per nullifier");
+            self.assert_all_distinct(nullifiers);
+            nullifiers.iter().zip(paths).fold(old_root.clone(), |root, (nullifier, path)| {
+                self.sparse_merkle_insert(&root, nullifier, path, compress)
+            })
+        })
+    }
+
+    /// Splits `key` into `height` bits, giving the position of its leaf. A key can only have
+    /// `max_bits` bits if its binary representation is checked to be canonical.
+    fn sparse_merkle_key_bits(
+        &mut self, key: &Expression<F>, height: usize,
+    ) -> BinaryExpression<F> {
+        let max_bits = Element::<F>::max_bits();
+        assert!(height <= max_bits,
+                "Sparse Merkle sets can have a height of at most {}", max_bits);
+        if height == max_bits {
+            self.split(key)
+        } else {
+            self.split_bounded(key, height)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::Expression;
+    use crate::field::{Element, Field};
+    use crate::gadget::Gadget;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::gadget_traits::CompressionFunction;
+    use crate::nullifier_set::SparseMerklePathData;
+    use crate::fields::small::F257;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+
+    const HEIGHT: usize = 6;
+
+    /// A batch update of three nullifiers, along with its input wires and new root.
+    struct Batch {
+        gadget: Gadget<F257>,
+        old_root: Wire,
+        nullifiers: Vec<Wire>,
+        siblings: Vec<Vec<Wire>>,
+        new_root: Expression<F257>,
+    }
+
+    fn batch() -> Batch {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let old_root = builder.wire();
+        let (nullifiers, nullifier_exps) = builder.expression_wires(3);
+        let mut siblings = Vec::new();
+        let mut paths = Vec::new();
+        for _i in 0..3 {
+            let (wires, exps) = builder.expression_wires(HEIGHT);
+            siblings.push(wires);
+            paths.push(SparseMerklePathData::new(exps));
+        }
+        let new_root = builder.batch_nullifier_update(
+            &Expression::from(old_root), &nullifier_exps, &paths, &TestCompress);
+        let gadget = builder.build();
+        Batch { gadget, old_root, nullifiers, siblings, new_root }
+    }
+
+    /// The layers of the tree with the given leaves, from the leaves up to the root.
+    fn layers(leaves: &[Element<F257>]) -> Vec<Vec<Element<F257>>> {
+        let mut layers = vec![leaves.to_vec()];
+        while layers.last().unwrap().len() > 1 {
+            let parents = layers.last().unwrap().chunks(2)
+                .map(|pair| TestCompress.compress_evaluate(&pair[0], &pair[1]))
+                .collect();
+            layers.push(parents);
+        }
+        layers
+    }
+
+    fn root(leaves: &[Element<F257>]) -> Element<F257> {
+        layers(leaves).pop().unwrap()[0].clone()
+    }
+
+    fn siblings(leaves: &[Element<F257>], index: usize) -> Vec<Element<F257>> {
+        let layers = layers(leaves);
+        (0..HEIGHT).map(|i| layers[i][(index >> i) ^ 1].clone()).collect()
+    }
+
+    /// A set with nullifiers 3 and 40 already present.
+    fn initial_leaves() -> Vec<Element<F257>> {
+        let mut leaves = vec![Element::zero(); 1 << HEIGHT];
+        leaves[3] = Element::one();
+        leaves[40] = Element::one();
+        leaves
+    }
+
+    /// Assigns the given nullifiers, with paths taken from the given sets of leaves.
+    fn values(
+        batch: &Batch, nullifiers: &[usize; 3], path_leaves: &[Vec<Element<F257>>],
+    ) -> WireValues<F257> {
+        let mut values = values!(batch.old_root => root(&initial_leaves()));
+        for i in 0..3 {
+            values.set(batch.nullifiers[i], Element::from(nullifiers[i]));
+            values.set_each(&batch.siblings[i], &siblings(&path_leaves[i], nullifiers[i]));
+        }
+        values
+    }
+
+    /// Assigns the given nullifiers, with the paths of an honest prover, each taken from the set
+    /// after the previous insertions. Returns the values along with the final leaves.
+    fn honest_values(
+        batch: &Batch, nullifiers: &[usize; 3],
+    ) -> (WireValues<F257>, Vec<Element<F257>>) {
+        let mut leaves = initial_leaves();
+        let mut path_leaves = Vec::new();
+        for &nullifier in nullifiers {
+            path_leaves.push(leaves.clone());
+            leaves[nullifier] = Element::one();
+        }
+        (values(batch, nullifiers, &path_leaves), leaves)
+    }
+
+    #[test]
+    fn insert_fresh_nullifiers() {
+        let batch = batch();
+        let (mut values, leaves) = honest_values(&batch, &[5, 9, 62]);
+        assert!(batch.gadget.execute(&mut values));
+        assert_eq!(root(&leaves), batch.new_root.evaluate(&values));
+        assert!(root(&leaves) != root(&initial_leaves()));
+    }
+
+    #[test]
+    fn duplicate_nullifier() {
+        let batch = batch();
+        let (mut values, _leaves) = honest_values(&batch, &[5, 9, 5]);
+        assert!(!batch.gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn nullifier_already_present() {
+        let batch = batch();
+        let (mut values, _leaves) = honest_values(&batch, &[5, 40, 62]);
+        assert!(!batch.gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn stale_path() {
+        // The second path is valid for the initial set, rather than the set after inserting 5.
+        // Leaves 5 and 9 are in the same subtree of height 4, so the second path's sibling at
+        // height 3 is the subtree containing leaf 5, which is stale.
+        let batch = batch();
+        let (_values, mut after_both) = honest_values(&batch, &[5, 9, 62]);
+        after_both[62] = Element::zero();
+        let path_leaves = vec![initial_leaves(), initial_leaves(), after_both];
+        assert!(!batch.gadget.execute(&mut values(&batch, &[5, 9, 62], &path_leaves)));
+    }
+
+    // A dummy compression function which returns 2x + y.
+    struct TestCompress;
+
+    impl<F: Field> CompressionFunction<F> for TestCompress {
+        fn compress(&self, _builder: &mut GadgetBuilder<F>, x: &Expression<F>, y: &Expression<F>)
+                    -> Expression<F> {
+            x * 2 + y
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! This module extends GadgetBuilder with methods for converting between indices and one-hot
+//! vectors, i.e. vectors of booleans with exactly one bit set.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::BigUint;
+use num_traits::ToPrimitive;
+
+use crate::expression::{BinaryExpression, BooleanExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::wire_values::WireValues;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Returns a one-hot vector of length `n`, whose `index`th bit is set. If `index >= n`, the
+    /// gadget will be unsatisfiable.
+    pub fn one_hot_from_index(
+        &mut self, index: &Expression<F>, n: usize,
+    ) -> Vec<BooleanExpression<F>> {
+        logged!(self, "one_hot_from_index", [index, n], {
+            let bit_wires: Vec<_> = (0..n).map(|_i| self.boolean_wire()).collect();
+            let bits: Vec<BooleanExpression<F>> =
+                bit_wires.iter().map(BooleanExpression::from).collect();
+            let computed_index = self.index_from_one_hot(&bits);
+            self.assert_equal(index, &computed_index);
+
+            let index = index.clone();
+            self.generator_with_outputs(
+                index.dependencies(),
+                bit_wires.iter().map(|bit| bit.wire()).collect(),
+                move |values: &mut WireValues<F>| {
+                    // If the index is out of range, we set no bits, so the sum constraint will
+                    // fail.
+                    let index_value = index.evaluate(values);
+                    let index_usize = index_value.to_biguint().to_usize();
+                    for (i, &bit) in bit_wires.iter().enumerate() {
+                        values.set_boolean(bit, index_usize == Some(i));
+                    }
+                },
+            );
+
+            bits
+        })
+    }
+
+    /// Returns the index of the set bit in a one-hot vector. This asserts that exactly one of the
+    /// given bits is set, but requires no other constraints.
+    pub fn index_from_one_hot(&mut self, bits: &[BooleanExpression<F>]) -> Expression<F> {
+        logged!(self, "index_from_one_hot", [bits.len()], {
+            let sum = Expression::sum_of_expressions(
+                &bits.iter().map(|bit| bit.expression().clone()).collect::<Vec<_>>());
+            self.assert_equal(&sum, &Expression::one());
+
+            Expression::sum_of_expressions(
+                &bits.iter().enumerate()
+                    .map(|(i, bit)| bit.expression() * Element::from(BigUint::from(i)))
+                    .collect::<Vec<_>>())
+        })
+    }
+
+    /// Returns a one-hot vector of length `2^k`, where `k` is the length of `index`, whose bit at
+    /// the position given by `index` is set. This costs fewer than `2^k` product constraints.
+    pub fn one_hot_from_binary(
+        &mut self, index: &BinaryExpression<F>,
+    ) -> Vec<BooleanExpression<F>> {
+        logged!(self, "one_hot_from_binary", [index.join_allowing_overflow()], {
+            // We decode one index bit at a time, starting with the least significant. After
+            // decoding `j` bits, `result[i]` is set iff the low `j` bits of the index equal `i`.
+            // Each bit splits every existing entry into two, at the cost of one product per entry.
+            let mut result = vec![BooleanExpression::_true()];
+            for bit in &index.bits {
+                let mut high = Vec::with_capacity(result.len());
+                let mut low = Vec::with_capacity(result.len());
+                for entry in &result {
+                    let entry_and_bit = self.and(entry, bit);
+                    low.push(BooleanExpression::new_unsafe(
+                        entry.expression() - entry_and_bit.expression()));
+                    high.push(entry_and_bit);
+                }
+                low.extend(high);
+                result = low;
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::{BinaryExpression, Expression};
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::wire_values::WireValues;
+
+    #[test]
+    fn one_hot_from_index() {
+        for &n in &[5, 8] {
+            let mut builder = GadgetBuilder::<F257>::new();
+            let index_wire = builder.wire();
+            let index = Expression::from(index_wire);
+            let bits = builder.one_hot_from_index(&index, n);
+            let decoded = builder.index_from_one_hot(&bits);
+            let gadget = builder.build();
+
+            for i in 0..n {
+                let mut values = values!(index_wire => i.into());
+                assert!(gadget.execute(&mut values));
+                for (j, bit) in bits.iter().enumerate() {
+                    assert_eq!(i == j, bit.evaluate(&values));
+                }
+                assert_eq!(Element::from(i), decoded.evaluate(&values));
+            }
+
+            let mut values = values!(index_wire => n.into());
+            assert!(!gadget.execute(&mut values));
+        }
+    }
+
+    #[test]
+    fn index_from_one_hot_rejects_multiple_bits() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let bit_wires: Vec<_> = (0..4).map(|_i| builder.boolean_wire()).collect();
+        let bits: Vec<_> = bit_wires.iter().map(|w| w.into()).collect();
+        builder.index_from_one_hot(&bits);
+        let gadget = builder.build();
+
+        let mut values = WireValues::new();
+        for (i, &bit) in bit_wires.iter().enumerate() {
+            values.set_boolean(bit, i == 1 || i == 2);
+        }
+        assert!(!gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn one_hot_from_binary() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let index_wire = builder.binary_wire(3);
+        let index = BinaryExpression::from(&index_wire);
+        let binary_bits = builder.one_hot_from_binary(&index);
+        let index_bits = builder.one_hot_from_index(&index.join(), 8);
+        let gadget = builder.build();
+        assert_eq!(8, binary_bits.len());
+
+        for i in 0..8u32 {
+            let mut values = WireValues::new();
+            values.set_binary_unsigned(&index_wire, &i.into());
+            assert!(gadget.execute(&mut values));
+            for j in 0..8 {
+                assert_eq!(i as usize == j, binary_bits[j].evaluate(&values));
+                assert_eq!(index_bits[j].evaluate(&values), binary_bits[j].evaluate(&values));
+            }
+        }
+    }
+
+    #[test]
+    fn one_hot_from_binary_cost() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let index = BinaryExpression::from(&builder.binary_wire(3));
+        builder.one_hot_from_binary(&index);
+
+        // Three booleanity constraints, plus 1 + 2 + 4 products, the first of which is free
+        // since it involves a constant.
+        assert_eq!(3 + 2 + 4, builder.build().size());
+    }
+}
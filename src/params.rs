@@ -0,0 +1,453 @@
+//! This module loads hash parameter sets, such as round constants and MDS matrices, from JSON
+//! files, so that parameters published alongside other implementations can be reproduced exactly.
+//!
+//! Every file is a JSON object with a `modulus` field, which must equal the order of the field the
+//! parameters are loaded into. Field elements and the modulus are given as strings, either in
+//! decimal or in hexadecimal with a `0x` prefix; small JSON integers are also accepted. Each field
+//! element must be less than the modulus.
+//!
+//! Poseidon files have the following fields:
+//! - `width`: the size of the permutation, in field elements.
+//! - `full_rounds` and `partial_rounds`: the number of each kind of round. `full_rounds` must be
+//!   even.
+//! - `sbox` (optional): one of `"x^3"`, `"x^5"` or `"inverse"`. If omitted, `PoseidonBuilder`
+//!   chooses an S-box for the field.
+//! - `round_constants`: one array of `width` elements per round.
+//! - `mds_matrix`: `width` rows of `width` elements.
+//!
+//! Rescue files have the following fields:
+//! - `width`: the size of the permutation, in field elements.
+//! - `alpha`: the degree of the permutation monomial.
+//! - `rounds`: the number of rounds.
+//! - `round_constants`: two arrays of `width` elements per round.
+//! - `mds_matrix`: `width` rows of `width` elements.
+//!
+//! MiMC files have a single other field, `round_constants`, an array of elements with one entry per
+//! round after the first.
+//!
+//! For example, the following is a Poseidon parameter set for a width of 2 in the field of order
+//! 11, with two full rounds and no partial rounds:
+//!
+//! ```json
+//! {
+//!     "modulus": "0xb",
+//!     "width": 2,
+//!     "full_rounds": 2,
+//!     "partial_rounds": 0,
+//!     "sbox": "x^3",
+//!     "round_constants": [["1", "2"], ["3", "4"]],
+//!     "mds_matrix": [["2", "1"], ["1", "2"]]
+//! }
+//! ```
+
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::Read;
+
+use num::BigUint;
+use serde_json::{Map, Value};
+
+use crate::field::{Element, Field};
+use crate::matrices::MdsMatrix;
+use crate::mimc::MiMCBlockCipher;
+use crate::poseidon::{NumberOfRounds, Poseidon, PoseidonBuilder, PoseidonSbox};
+use crate::rescue::{Rescue, RescueBuilder};
+
+/// A Poseidon parameter set, as loaded by `load_poseidon_params`.
+pub struct PoseidonParams<F: Field> {
+    /// The size of the permutation, in field elements.
+    pub width: usize,
+    /// The number of full and partial rounds.
+    pub num_rounds: NumberOfRounds,
+    /// The S-box, if one was specified.
+    pub sbox: Option<PoseidonSbox>,
+    /// The constants to add in each round, one per state element.
+    pub round_constants: Vec<Vec<Element<F>>>,
+    /// The MDS matrix to apply in the mix layer.
+    pub mds_matrix: MdsMatrix<F>,
+}
+
+impl<F: Field> PoseidonParams<F> {
+    /// A `PoseidonBuilder` configured with these parameters.
+    pub fn builder(&self) -> PoseidonBuilder<F> {
+        let mut builder = PoseidonBuilder::new(self.width);
+        builder.num_rounds(self.num_rounds)
+            .round_constants(self.round_constants.clone())
+            .mds_matrix(self.mds_matrix.clone());
+        if let Some(sbox) = self.sbox {
+            builder.sbox(sbox);
+        }
+        builder
+    }
+
+    /// The Poseidon permutation with these parameters.
+    pub fn build(&self) -> Poseidon<F> {
+        self.builder().build()
+    }
+}
+
+/// A Rescue parameter set, as loaded by `load_rescue_params`.
+pub struct RescueParams<F: Field> {
+    /// The size of the permutation, in field elements.
+    pub width: usize,
+    /// The degree of the permutation monomial.
+    pub alpha: Element<F>,
+    /// The number of rounds.
+    pub num_rounds: usize,
+    /// The constants to add after each MDS layer, two sets per round.
+    pub round_constants: Vec<Vec<Element<F>>>,
+    /// The MDS matrix to apply after each permutation layer.
+    pub mds_matrix: MdsMatrix<F>,
+}
+
+impl<F: Field> RescueParams<F> {
+    /// A `RescueBuilder` configured with these parameters.
+    pub fn builder(&self) -> RescueBuilder<F> {
+        let mut builder = RescueBuilder::new(self.width);
+        builder.alpha(self.alpha.clone())
+            .num_rounds(self.num_rounds)
+            .round_constants(self.round_constants.clone())
+            .mds_matrix(self.mds_matrix.clone());
+        builder
+    }
+
+    /// The Rescue permutation with these parameters.
+    pub fn build(&self) -> Rescue<F> {
+        self.builder().build()
+    }
+}
+
+/// A MiMC parameter set, as loaded by `load_mimc_params`.
+pub struct MiMCParams<F: Field> {
+    /// The constants to add in each round after the first.
+    pub round_constants: Vec<Element<F>>,
+}
+
+impl<F: Field> MiMCParams<F> {
+    /// The MiMC block cipher with these parameters.
+    pub fn cipher(&self) -> MiMCBlockCipher<F> {
+        MiMCBlockCipher::new(&self.round_constants)
+    }
+}
+
+/// An error encountered while loading a parameter file. Fields are identified by their path within
+/// the file, such as `round_constants[3][1]`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParamError {
+    /// The file could not be read, or is not valid JSON.
+    Json(String),
+    /// A required field is absent.
+    Missing { field: String },
+    /// A field has the wrong JSON type.
+    WrongType { field: String, expected: &'static str },
+    /// A field has the right type, but an unrecognized or invalid value.
+    InvalidValue { field: String, value: String },
+    /// The file's modulus is not the order of the field being loaded into.
+    ModulusMismatch { expected: BigUint, found: BigUint },
+    /// An array has the wrong number of entries.
+    WrongCount { field: String, expected: usize, found: usize },
+    /// A field element is not less than the modulus.
+    OutOfRange { field: String, value: BigUint, modulus: BigUint },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ParamError::Json(message) =>
+                write!(f, "Invalid parameter file: {}", message),
+            ParamError::Missing { field } =>
+                write!(f, "Missing field {}", field),
+            ParamError::WrongType { field, expected } =>
+                write!(f, "Expected {} to be {}", field, expected),
+            ParamError::InvalidValue { field, value } =>
+                write!(f, "Invalid value for {}: {}", field, value),
+            ParamError::ModulusMismatch { expected, found } =>
+                write!(f, "Modulus {} does not match the field order {}", found, expected),
+            ParamError::WrongCount { field, expected, found } =>
+                write!(f, "Expected {} entries in {}, found {}", expected, field, found),
+            ParamError::OutOfRange { field, value, modulus } =>
+                write!(f, "Value {} of {} is not less than the modulus {}", value, field, modulus),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+/// Load a Poseidon parameter set in the format described in the module documentation.
+pub fn load_poseidon_params<F: Field, R: Read>(reader: R) -> Result<PoseidonParams<F>, ParamError> {
+    let object = read_object::<F, R>(reader)?;
+    let width = parse_usize(get(&object, "width")?, "width")?;
+    let full = parse_usize(get(&object, "full_rounds")?, "full_rounds")?;
+    let partial = parse_usize(get(&object, "partial_rounds")?, "partial_rounds")?;
+    if full % 2 != 0 {
+        return Err(ParamError::InvalidValue {
+            field: "full_rounds".into(),
+            value: format!("{} is odd", full),
+        });
+    }
+
+    let sbox = match object.get("sbox") {
+        None => None,
+        Some(value) => Some(match parse_str(value, "sbox")? {
+            "x^3" => PoseidonSbox::Exponentiation3,
+            "x^5" => PoseidonSbox::Exponentiation5,
+            "inverse" => PoseidonSbox::Inverse,
+            other => return Err(ParamError::InvalidValue {
+                field: "sbox".into(),
+                value: other.into(),
+            }),
+        }),
+    };
+
+    let round_constants = parse_matrix(
+        get(&object, "round_constants")?, "round_constants", full + partial, width)?;
+    let mds_matrix = MdsMatrix::new(
+        parse_matrix(get(&object, "mds_matrix")?, "mds_matrix", width, width)?);
+
+    Ok(PoseidonParams {
+        width,
+        num_rounds: NumberOfRounds::new(full, partial),
+        sbox,
+        round_constants,
+        mds_matrix,
+    })
+}
+
+/// Load a Rescue parameter set in the format described in the module documentation.
+pub fn load_rescue_params<F: Field, R: Read>(reader: R) -> Result<RescueParams<F>, ParamError> {
+    let object = read_object::<F, R>(reader)?;
+    let width = parse_usize(get(&object, "width")?, "width")?;
+    let alpha = parse_element(get(&object, "alpha")?, "alpha")?;
+    let num_rounds = parse_usize(get(&object, "rounds")?, "rounds")?;
+    let round_constants = parse_matrix(
+        get(&object, "round_constants")?, "round_constants", 2 * num_rounds, width)?;
+    let mds_matrix = MdsMatrix::new(
+        parse_matrix(get(&object, "mds_matrix")?, "mds_matrix", width, width)?);
+    Ok(RescueParams { width, alpha, num_rounds, round_constants, mds_matrix })
+}
+
+/// Load a MiMC parameter set in the format described in the module documentation.
+pub fn load_mimc_params<F: Field, R: Read>(reader: R) -> Result<MiMCParams<F>, ParamError> {
+    let object = read_object::<F, R>(reader)?;
+    let constants = get(&object, "round_constants")?;
+    let round_constants = parse_array(constants, "round_constants")?.iter().enumerate()
+        .map(|(i, value)| parse_element(value, &format!("round_constants[{}]", i)))
+        .collect::<Result<_, _>>()?;
+    Ok(MiMCParams { round_constants })
+}
+
+/// Read a JSON object from the given reader, and check that its modulus matches `F`.
+fn read_object<F: Field, R: Read>(reader: R) -> Result<Map<String, Value>, ParamError> {
+    let value: Value = serde_json::from_reader(reader)
+        .map_err(|e| ParamError::Json(e.to_string()))?;
+    let object = match value {
+        Value::Object(object) => object,
+        _ => return Err(ParamError::WrongType { field: "file".into(), expected: "an object" }),
+    };
+
+    let modulus = parse_biguint(get(&object, "modulus")?, "modulus")?;
+    if modulus != F::order() {
+        return Err(ParamError::ModulusMismatch { expected: F::order(), found: modulus });
+    }
+    Ok(object)
+}
+
+fn get<'a>(object: &'a Map<String, Value>, field: &str) -> Result<&'a Value, ParamError> {
+    object.get(field).ok_or_else(|| ParamError::Missing { field: field.into() })
+}
+
+fn parse_str<'a>(value: &'a Value, field: &str) -> Result<&'a str, ParamError> {
+    value.as_str()
+        .ok_or_else(|| ParamError::WrongType { field: field.into(), expected: "a string" })
+}
+
+fn parse_array<'a>(value: &'a Value, field: &str) -> Result<&'a Vec<Value>, ParamError> {
+    value.as_array()
+        .ok_or_else(|| ParamError::WrongType { field: field.into(), expected: "an array" })
+}
+
+fn parse_usize(value: &Value, field: &str) -> Result<usize, ParamError> {
+    value.as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| ParamError::WrongType {
+            field: field.into(),
+            expected: "a non-negative integer",
+        })
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal string, or a non-negative JSON integer.
+fn parse_biguint(value: &Value, field: &str) -> Result<BigUint, ParamError> {
+    if let Some(n) = value.as_u64() {
+        return Ok(BigUint::from(n));
+    }
+    let s = value.as_str().ok_or_else(|| ParamError::WrongType {
+        field: field.into(),
+        expected: "a decimal or hexadecimal string",
+    })?;
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => BigUint::parse_bytes(hex.as_bytes(), 16),
+        None => BigUint::parse_bytes(s.as_bytes(), 10),
+    };
+    parsed.ok_or_else(|| ParamError::InvalidValue { field: field.into(), value: s.into() })
+}
+
+fn parse_element<F: Field>(value: &Value, field: &str) -> Result<Element<F>, ParamError> {
+    let n = parse_biguint(value, field)?;
+    if n >= F::order() {
+        return Err(ParamError::OutOfRange { field: field.into(), value: n, modulus: F::order() });
+    }
+    Ok(Element::from(n))
+}
+
+/// Parse an array of `rows` arrays, each containing `columns` field elements.
+fn parse_matrix<F: Field>(
+    value: &Value, field: &str, rows: usize, columns: usize,
+) -> Result<Vec<Vec<Element<F>>>, ParamError> {
+    let array = parse_array(value, field)?;
+    check_count(array, field, rows)?;
+    array.iter().enumerate()
+        .map(|(i, row)| {
+            let row_field = format!("{}[{}]", field, i);
+            let row = parse_array(row, &row_field)?;
+            check_count(row, &row_field, columns)?;
+            row.iter().enumerate()
+                .map(|(j, value)| parse_element(value, &format!("{}[{}]", row_field, j)))
+                .collect()
+        })
+        .collect()
+}
+
+fn check_count(array: &[Value], field: &str, expected: usize) -> Result<(), ParamError> {
+    if array.len() == expected {
+        Ok(())
+    } else {
+        Err(ParamError::WrongCount { field: field.into(), expected, found: array.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use num::BigUint;
+
+    use crate::{BlockCipher, Element, MdsMatrix, MiMCBlockCipher, MultiPermutation,
+                NumberOfRounds, PoseidonBuilder, RescueBuilder};
+    use crate::lcg::LCG;
+    use crate::params::{load_mimc_params, load_poseidon_params, load_rescue_params, ParamError};
+    use crate::PoseidonSbox::Exponentiation3;
+    use crate::fields::small::{F11, F7};
+
+    /// The parameters of the `poseidon_x3_f11` test, including the round constants which were
+    /// the default in version 0.4.
+    const POSEIDON_F11: &str = r#"{
+        "modulus": "11",
+        "width": 4,
+        "full_rounds": 4,
+        "partial_rounds": 6,
+        "sbox": "x^3",
+        "round_constants": [
+            ["2", "9", "4", "3"], ["6", "8", "7", "0xa"], ["1", "5", "0", "2"],
+            ["9", "4", "3", "6"], ["8", "7", "0xa", "1"], ["5", "0", "2", "9"],
+            ["4", "3", "6", "8"], ["7", "0xa", "1", "5"], ["0", "2", "9", "4"],
+            ["3", "6", "8", "7"]
+        ],
+        "mds_matrix": [
+            ["2", "3", "1", "1"], ["1", "2", "3", "1"], ["1", "1", "2", "3"], ["3", "1", "1", "2"]
+        ]
+    }"#;
+
+    fn mds_matrix_f11() -> MdsMatrix<F11> {
+        MdsMatrix::new(vec![
+            vec![2u8.into(), 3u8.into(), 1u8.into(), 1u8.into()],
+            vec![1u8.into(), 2u8.into(), 3u8.into(), 1u8.into()],
+            vec![1u8.into(), 1u8.into(), 2u8.into(), 3u8.into()],
+            vec![3u8.into(), 1u8.into(), 1u8.into(), 2u8.into()],
+        ])
+    }
+
+    #[test]
+    fn load_poseidon_f11() {
+        let params = load_poseidon_params::<F11, _>(POSEIDON_F11.as_bytes()).unwrap();
+        assert_eq!(NumberOfRounds::new(4, 6), params.num_rounds);
+        assert_eq!(Some(Exponentiation3), params.sbox);
+
+        // Those constants were generated by a simple LCG (seeded with 0).
+        let mut lcg = LCG::new();
+        let round_constants = (0..10)
+            .map(|_round| (0..4).map(|_i| lcg.next_element()).collect())
+            .collect();
+
+        let loaded = params.build();
+        let expected = PoseidonBuilder::new(4)
+            .sbox(Exponentiation3)
+            .num_rounds(NumberOfRounds::new(4, 6))
+            .mds_matrix(mds_matrix_f11())
+            .round_constants(round_constants)
+            .build();
+        assert_eq!(expected.round_constants(), loaded.round_constants());
+
+        let inputs = (0u8..4).map(Element::from).collect_vec();
+        assert_eq!(expected.permute_evaluate(&inputs), loaded.permute_evaluate(&inputs));
+    }
+
+    #[test]
+    fn load_poseidon_wrong_modulus() {
+        let result = load_poseidon_params::<F7, _>(POSEIDON_F11.as_bytes());
+        let error = ParamError::ModulusMismatch {
+            expected: BigUint::from(7u8),
+            found: BigUint::from(11u8),
+        };
+        assert_eq!(Some(error), result.err());
+    }
+
+    #[test]
+    fn load_poseidon_missing_constant() {
+        let file = POSEIDON_F11.replace(r#"["3", "6", "8", "7"]"#, r#"["3", "6", "8"]"#);
+        let error = load_poseidon_params::<F11, _>(file.as_bytes()).err().unwrap();
+        assert_eq!(ParamError::WrongCount {
+            field: "round_constants[9]".into(),
+            expected: 4,
+            found: 3,
+        }, error);
+        assert_eq!("Expected 4 entries in round_constants[9], found 3", error.to_string());
+    }
+
+    #[test]
+    fn load_poseidon_out_of_range() {
+        let file = POSEIDON_F11.replace(r#"["0", "2", "9", "4"]"#, r#"["0", "2", "0xb", "4"]"#);
+        let error = load_poseidon_params::<F11, _>(file.as_bytes()).err().unwrap();
+        assert_eq!("Value 11 of round_constants[8][2] is not less than the modulus 11",
+                   error.to_string());
+    }
+
+    #[test]
+    fn load_rescue_and_mimc() {
+        let rescue_file = r#"{
+            "modulus": "11",
+            "width": 2,
+            "alpha": "3",
+            "rounds": 1,
+            "round_constants": [["1", "2"], ["3", "4"]],
+            "mds_matrix": [["2", "1"], ["1", "2"]]
+        }"#;
+        let params = load_rescue_params::<F11, _>(rescue_file.as_bytes()).unwrap();
+        let constants: Vec<Vec<Element<F11>>> = vec![
+            vec![1u8.into(), 2u8.into()], vec![3u8.into(), 4u8.into()]];
+        let expected = RescueBuilder::new(2)
+            .alpha(3u8.into())
+            .num_rounds(1)
+            .round_constants(constants)
+            .mds_matrix(MdsMatrix::new(vec![
+                vec![2u8.into(), 1u8.into()], vec![1u8.into(), 2u8.into()]]))
+            .build();
+        let inputs = vec![Element::from(5u8), Element::from(6u8)];
+        assert_eq!(expected.permute_evaluate(&inputs), params.build().permute_evaluate(&inputs));
+
+        let mimc_file = r#"{ "modulus": 11, "round_constants": ["5", "0x7", 2] }"#;
+        let params = load_mimc_params::<F11, _>(mimc_file.as_bytes()).unwrap();
+        let expected = MiMCBlockCipher::new(&[5u8.into(), 7u8.into(), 2u8.into()]);
+        let (key, input) = (Element::from(3u8), Element::from(4u8));
+        assert_eq!(expected.encrypt_evaluate(&key, &input),
+                   params.cipher().encrypt_evaluate(&key, &input));
+    }
+}
@@ -1,4 +1,4 @@
-use num::{BigUint, Integer};
+use num::{BigUint, Integer, ToPrimitive};
 use num_traits::One;
 
 use crate::{Element, Expression, Field, GadgetBuilder, Permutation, WireValues};
@@ -11,9 +11,17 @@ impl<F: Field> Permutation<F> for InversePermutation {
         builder.inverse_or_zero(x)
     }
 
+    fn permute_evaluate(&self, x: &Element<F>) -> Element<F> {
+        x.multiplicative_inverse_or_zero()
+    }
+
     fn inverse(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>) -> Expression<F> {
         builder.inverse_or_zero(x)
     }
+
+    fn inverse_evaluate(&self, x: &Element<F>) -> Element<F> {
+        x.multiplicative_inverse_or_zero()
+    }
 }
 
 /// The permutation `x^n`.
@@ -32,6 +40,23 @@ impl<F: Field> MonomialPermutation<F> {
                 "x^{} is not a permutation of F", n);
         MonomialPermutation { n }
     }
+
+    /// The exponent `m` such that `x^n^m = x` for all `x`, i.e. the exponent which inverts this
+    /// permutation.
+    fn inverse_exponent(&self) -> Element<F> {
+        // By Fermat's little theorem, x^p = x mod p, so if n divides p, then x^(p / n)^n = x mod p.
+        // Further, since x^(p - 1) = 1 mod p, x^((p + (p - 1)*k) / n)^n = x mod p for any positive
+        // k, provided that n divides p + (p - 1)*k. Thus we start with p, and repeatedly add
+        // p - 1 until we find an exponent divisible by n.
+        //TODO: find a solution that isn't O(p)
+        let mut exponent_times_n = F::order();
+        loop {
+            exponent_times_n += F::order() - BigUint::one();
+            if exponent_times_n.is_multiple_of(self.n.to_biguint()) {
+                return Element::from(exponent_times_n / self.n.to_biguint());
+            }
+        }
+    }
 }
 
 impl<F: Field> Permutation<F> for MonomialPermutation<F> {
@@ -39,28 +64,25 @@ impl<F: Field> Permutation<F> for MonomialPermutation<F> {
         builder.exponentiation(x, &self.n)
     }
 
+    fn permute_evaluate(&self, x: &Element<F>) -> Element<F> {
+        // Small exponents, such as those of typical S-boxes, are much faster with `pow_u64`.
+        match self.n.to_biguint().to_u64() {
+            Some(n) => x.pow_u64(n),
+            None => x.exponentiation(&self.n),
+        }
+    }
+
     fn inverse(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>) -> Expression<F> {
         let root_wire = builder.wire();
         let root = Expression::from(root_wire);
         let exponentiation = builder.exponentiation(&root, &self.n);
         builder.assert_equal(&exponentiation, x);
 
-        // By Fermat's little theorem, x^p = x mod p, so if n divides p, then x^(p / n)^n = x mod p.
-        // Further, since x^(p - 1) = 1 mod p, x^((p + (p - 1)*k) / n)^n = x mod p for any positive k,
-        // provided that n divides p + (p - 1)*k. Thus we start with p, and repeatedly add
-        // p - 1 until we find an exponent divisible by n.
-        //TODO: find a solution that isn't O(p)
-        let mut exponent_times_n = F::order();
-        let exponent = loop {
-            exponent_times_n += F::order() - BigUint::one();
-            if exponent_times_n.is_multiple_of(self.n.to_biguint()) {
-                break Element::from(exponent_times_n / self.n.to_biguint());
-            }
-        };
-
+        let exponent = self.inverse_exponent();
         let x = x.clone();
-        builder.generator(
+        builder.generator_with_outputs(
             x.dependencies(),
+            vec![root_wire],
             move |values: &mut WireValues<F>| {
                 let root_value = x.evaluate(values).exponentiation(&exponent);
                 values.set(root_wire, root_value);
@@ -68,12 +90,16 @@ impl<F: Field> Permutation<F> for MonomialPermutation<F> {
 
         root
     }
+
+    fn inverse_evaluate(&self, x: &Element<F>) -> Element<F> {
+        x.exponentiation(&self.inverse_exponent())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{Element, Expression, GadgetBuilder, MonomialPermutation, Permutation};
-    use crate::test_util::{F11, F7};
+    use crate::fields::small::{F11, F7};
 
     #[test]
     fn cube_and_cube_root() {
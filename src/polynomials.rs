@@ -0,0 +1,197 @@
+//! This module extends GadgetBuilder with methods for evaluating polynomials.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::Expression;
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Evaluate the polynomial with the given coefficients at `x`, where `coefficients[i]` is the
+    /// coefficient of `x^i`. This uses Horner's method, which requires `n - 1` products for `n`
+    /// coefficients. If there are no coefficients, the result is zero.
+    pub fn evaluate_polynomial(
+        &mut self, coefficients: &[Expression<F>], x: &Expression<F>,
+    ) -> Expression<F> {
+        logged!(self, "evaluate_polynomial", [coefficients.len(), x], {
+            let mut coefficients = coefficients.iter().rev();
+            let mut result = match coefficients.next() {
+                Some(leading) => leading.clone(),
+                None => return Expression::zero(),
+            };
+            for coefficient in coefficients {
+                result = self.product(&result, x) + coefficient;
+            }
+            result
+        })
+    }
+
+    /// Evaluate, at `x`, the unique polynomial of degree less than `points.len()` which passes
+    /// through each of the given points. The x-coordinates of the points are constants, which must
+    /// be distinct, while their y-coordinates may be arbitrary expressions.
+    ///
+    /// The denominators of the Lagrange basis polynomials depend only on the x-coordinates, so they
+    /// are computed out-of-circuit. The numerators are computed using prefix and suffix products,
+    /// so the total cost is roughly `4n` constraints for `n` points.
+    pub fn evaluate_lagrange(
+        &mut self, points: &[(Element<F>, Expression<F>)], x: &Expression<F>,
+    ) -> Expression<F> {
+        logged!(self, "evaluate_lagrange", [points.len(), x], {
+            let n = points.len();
+            match n {
+                0 => return Expression::zero(),
+                1 => return points[0].1.clone(),
+                _ => (),
+            }
+
+            let differences: Vec<Expression<F>> = points.iter()
+                .map(|(x_i, _y_i)| x - Expression::from(x_i))
+                .collect();
+
+            // prefix_products[i] is the product of differences[..i], and suffix_products[i] is the
+            // product of differences[i + 1..].
+            let mut prefix_products = vec![Expression::one()];
+            for difference in &differences[..n - 1] {
+                let prefix = self.product(prefix_products.last().unwrap(), difference);
+                prefix_products.push(prefix);
+            }
+            let mut suffix_products = vec![Expression::one()];
+            for difference in differences[1..].iter().rev() {
+                let suffix = self.product(suffix_products.last().unwrap(), difference);
+                suffix_products.push(suffix);
+            }
+            suffix_products.reverse();
+
+            let mut terms = Vec::with_capacity(n);
+            for (i, (x_i, y_i)) in points.iter().enumerate() {
+                let mut denominator = Element::one();
+                for (j, (x_j, _y_j)) in points.iter().enumerate() {
+                    if i != j {
+                        assert!(x_i != x_j, "Interpolation points must be distinct");
+                        denominator *= x_i - x_j;
+                    }
+                }
+
+                let numerator = self.product(&prefix_products[i], &suffix_products[i]);
+                terms.push(self.product(&numerator, y_i) / denominator);
+            }
+            Expression::sum_of_expressions(&terms)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use itertools::Itertools;
+
+    use crate::expression::Expression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+
+    #[test]
+    fn horner_matches_power_sum() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let coefficient_wires = builder.wires(6);
+        let coefficients = coefficient_wires.iter().map(Expression::from).collect_vec();
+        let x_exp = Expression::from(x);
+        let horner = builder.evaluate_polynomial(&coefficients, &x_exp);
+        let mut power_sum = Expression::zero();
+        for (i, coefficient) in coefficients.iter().enumerate() {
+            let power = builder.exponentiation(&x_exp, &Element::from(i));
+            power_sum += builder.product(coefficient, &power);
+        }
+        let gadget = builder.build();
+
+        let coefficient_values = [3u8, 0, 250, 7, 1, 100];
+        let mut values = values!(x => 11u8.into());
+        for (&wire, &value) in coefficient_wires.iter().zip(coefficient_values.iter()) {
+            values.set(wire, value.into());
+        }
+        assert!(gadget.execute(&mut values));
+
+        let x_value = Element::<F257>::from(11u8);
+        let expected = coefficient_values.iter().rev()
+            .fold(Element::zero(), |acc, &c| acc * &x_value + Element::from(c));
+        assert_eq!(expected, horner.evaluate(&values));
+        assert_eq!(expected, power_sum.evaluate(&values));
+    }
+
+    #[test]
+    fn evaluate_polynomial_degenerate() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = Expression::from(builder.wire());
+        let empty = builder.evaluate_polynomial(&[], &x);
+        let constant = builder.evaluate_polynomial(&[Expression::from(5u8)], &x);
+        assert_eq!(0, builder.build().size());
+
+        let values = WireValues::new();
+        assert_eq!(Element::zero(), empty.evaluate(&values));
+        assert_eq!(Element::from(5u8), constant.evaluate(&values));
+    }
+
+    #[test]
+    fn lagrange_4_points() {
+        type F = F257;
+        let xs: Vec<Element<F>> = [1u8, 2, 5, 9].iter().map(|&x| Element::from(x)).collect();
+
+        let mut builder = GadgetBuilder::<F>::new();
+        let y_wires = builder.wires(4);
+        let x = builder.wire();
+        let points = xs.iter().cloned().zip(y_wires.iter().map(Expression::from)).collect_vec();
+        let result = builder.evaluate_lagrange(&points, &Expression::from(x));
+        let gadget = builder.build();
+
+        // Interpolate through points of p(x) = 4 + 3x + 2x^2 + x^3, and evaluate at x = 20.
+        let p = |x: &Element<F>| Element::from(4u8) + x * 3u128 + x * x * 2u128 + x * x * x;
+        let mut values = values!(x => 20u8.into());
+        for (&wire, x_i) in y_wires.iter().zip(xs.iter()) {
+            values.set(wire, p(x_i));
+        }
+        assert!(gadget.execute(&mut values));
+        assert_eq!(p(&Element::from(20u8)), result.evaluate(&values));
+    }
+
+    #[test]
+    fn lagrange_single_point() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let result = builder.evaluate_lagrange(
+            &[(Element::from(3u8), Expression::from(y))], &Expression::from(x));
+        assert_eq!(0, builder.build().size());
+
+        let values = values!(x => 10u8.into(), y => 7u8.into());
+        assert_eq!(Element::from(7u8), result.evaluate(&values));
+    }
+
+    #[test]
+    fn corrupted_intermediate_unsatisfiable() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let coefficients = (1u8..=4).map(Expression::from).collect_vec();
+        let result = builder.evaluate_polynomial(&coefficients, &Expression::from(x));
+        let gadget = builder.build();
+
+        let mut values = values!(x => 2u8.into());
+        assert!(gadget.execute(&mut values));
+
+        // Copy the generated witness, except for the final product wire, which we corrupt.
+        let corrupted_wire = result.dependencies()[0];
+        let mut corrupted = WireValues::new();
+        for (&wire, value) in values.as_map() {
+            if wire == corrupted_wire {
+                corrupted.set(wire, value + Element::one());
+            } else if wire != Wire::ONE {
+                corrupted.set(wire, value.clone());
+            }
+        }
+        assert!(!gadget.constraints.iter().all(|c| c.evaluate(&corrupted)));
+    }
+}
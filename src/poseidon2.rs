@@ -0,0 +1,539 @@
+//! This module contains an implementation of Poseidon2, a variant of Poseidon with cheaper linear
+//! layers, as described in "Poseidon2: A Faster Version of the Poseidon Hash Function".
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::{Element, ElementMatrix, Expression, Field, GadgetBuilder, MultiPermutation,
+            NumberOfRounds, PoseidonSbox};
+#[cfg(feature = "rand")]
+use crate::Sponge;
+use crate::constants::derive_round_constants;
+use crate::poseidon::{DEFAULT_SECURITY_BITS, default_sbox, secure_num_rounds_padded};
+
+/// The Poseidon2 permutation.
+///
+/// The state is first multiplied by the external matrix. Each full round then adds a constant to
+/// every state element, applies the S-box to every element, and multiplies by the external matrix.
+/// Each partial round adds a constant to the first element only, applies the S-box to it, and
+/// multiplies by the internal matrix, which is the all-ones matrix plus a diagonal matrix. Half of
+/// the full rounds come before the partial rounds, and half after.
+pub struct Poseidon2<F: Field> {
+    /// The size of the permutation, in field elements.
+    width: usize,
+    /// The number of full and partial rounds to use.
+    num_rounds: NumberOfRounds,
+    /// The S-box to apply in the sub words layer.
+    sbox: PoseidonSbox,
+    /// The constants to add in each full round, one per state element.
+    external_round_constants: Vec<Vec<Element<F>>>,
+    /// The constant to add to the first state element in each partial round.
+    internal_round_constants: Vec<Element<F>>,
+    /// The matrix applied at the start, and at the end of each full round.
+    external_matrix: ElementMatrix<F>,
+    /// The matrix applied at the end of each partial round.
+    internal_matrix: ElementMatrix<F>,
+}
+
+/// Builds a `Poseidon2` instance.
+pub struct Poseidon2Builder<F: Field> {
+    /// The size of the permutation, in field elements.
+    width: usize,
+    /// The number full and partial of rounds to use.
+    num_rounds: Option<NumberOfRounds>,
+    /// The S-box to apply in the sub words layer.
+    sbox: Option<PoseidonSbox>,
+    /// The desired (classical) security level, in bits.
+    security_bits: Option<usize>,
+    /// The constants to add in each full round, one per state element.
+    external_round_constants: Option<Vec<Vec<Element<F>>>>,
+    /// The constant to add to the first state element in each partial round.
+    internal_round_constants: Option<Vec<Element<F>>>,
+    /// The diagonal of the internal matrix, minus one.
+    internal_diagonal: Option<Vec<Element<F>>>,
+}
+
+impl<F: Field> Poseidon2Builder<F> {
+    pub fn new(width: usize) -> Self {
+        Poseidon2Builder {
+            width,
+            num_rounds: None,
+            sbox: None,
+            security_bits: None,
+            external_round_constants: None,
+            internal_round_constants: None,
+            internal_diagonal: None,
+        }
+    }
+
+    pub fn sbox(&mut self, sbox: PoseidonSbox) -> &mut Self {
+        self.sbox = Some(sbox);
+        self
+    }
+
+    pub fn num_rounds(&mut self, num_rounds: NumberOfRounds) -> &mut Self {
+        self.num_rounds = Some(num_rounds);
+        self
+    }
+
+    pub fn security_bits(&mut self, security_bits: usize) -> &mut Self {
+        self.security_bits = Some(security_bits);
+        self
+    }
+
+    /// Sets the constants of the full rounds, which must contain one vector of `width` constants
+    /// per full round.
+    pub fn external_round_constants(&mut self, constants: Vec<Vec<Element<F>>>) -> &mut Self {
+        self.external_round_constants = Some(constants);
+        self
+    }
+
+    /// Sets the constants of the partial rounds, which must contain one constant per partial round.
+    pub fn internal_round_constants(&mut self, constants: Vec<Element<F>>) -> &mut Self {
+        self.internal_round_constants = Some(constants);
+        self
+    }
+
+    /// Sets the diagonal `d` of the internal matrix, which has entries `1 + d_i` on its diagonal
+    /// and 1 elsewhere. The default is only defined for widths of 2 and 3; for larger widths, the
+    /// diagonal must be chosen so that the internal matrix meets the conditions of the paper.
+    pub fn internal_diagonal(&mut self, diagonal: Vec<Element<F>>) -> &mut Self {
+        self.internal_diagonal = Some(diagonal);
+        self
+    }
+
+    pub fn build(&self) -> Poseidon2<F> {
+        let width = self.width;
+        let sbox = self.sbox.unwrap_or_else(default_sbox::<F>);
+
+        if self.num_rounds.is_some() && self.security_bits.is_some() {
+            panic!("Cannot specify both the number of rounds and the desired security level");
+        }
+
+        // Poseidon2 has the same security analysis as Poseidon, so it uses the same numbers of
+        // rounds.
+        let num_rounds = self.num_rounds.unwrap_or_else(
+            || secure_num_rounds_padded::<F>(sbox, width,
+                                             self.security_bits.unwrap_or(DEFAULT_SECURITY_BITS)));
+        assert!(num_rounds.full().is_multiple_of(2), "asymmetric permutation configuration");
+
+        // If round constants are not specified, derive them as Poseidon does, with Poseidon2 tags.
+        // This requires the rand feature.
+        let external_round_constants = self.external_round_constants.clone().unwrap_or_else(
+            || derive_round_constants("Poseidon2 external", num_rounds.full(), width));
+        let internal_round_constants = self.internal_round_constants.clone().unwrap_or_else(
+            || derive_round_constants("Poseidon2 internal", num_rounds.partial(), 1)
+                .into_iter().flatten().collect());
+        assert_eq!(external_round_constants.len(), num_rounds.full(),
+                   "Expected one set of constants per full round");
+        for constants in external_round_constants.iter() {
+            assert_eq!(constants.len(), width, "Expected one round constant per state element");
+        }
+        assert_eq!(internal_round_constants.len(), num_rounds.partial(),
+                   "Expected one constant per partial round");
+
+        let internal_diagonal = self.internal_diagonal.clone()
+            .unwrap_or_else(|| default_internal_diagonal(width));
+        assert_eq!(internal_diagonal.len(), width, "Expected one diagonal entry per state element");
+
+        Poseidon2 {
+            width,
+            num_rounds,
+            sbox,
+            external_round_constants,
+            internal_round_constants,
+            external_matrix: external_matrix(width),
+            internal_matrix: internal_matrix(&internal_diagonal),
+        }
+    }
+}
+
+impl<F: Field> Poseidon2<F> {
+    /// The size of the permutation, in field elements.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of full and partial rounds.
+    pub fn num_rounds(&self) -> NumberOfRounds {
+        self.num_rounds
+    }
+
+    /// The S-box applied in the sub words layer.
+    pub fn sbox(&self) -> PoseidonSbox {
+        self.sbox
+    }
+
+    /// Applies the permutation to the given elements directly. The external matrix is applied
+    /// first. Full rounds then apply the S-box to every element and mix with the external matrix,
+    /// while partial rounds apply it to the first element and mix with the internal matrix. This
+    /// backs `permute_evaluate`.
+    pub fn permute_elements(&self, inputs: &[Element<F>]) -> Vec<Element<F>> {
+        assert_eq!(inputs.len(), self.width);
+        let sbox = self.sbox.to_permutation();
+
+        let mut current = &self.external_matrix * inputs;
+        for round in 0..self.num_rounds.full() + self.num_rounds.partial() {
+            let index = self.index_among_kind(round);
+            if self.is_full_round(round) {
+                let constants = &self.external_round_constants[index];
+                current = current.iter().zip(constants)
+                    .map(|(x, constant)| sbox.permute_evaluate(&(x + constant)))
+                    .collect();
+                current = &self.external_matrix * current.as_slice();
+            } else {
+                let constant = &self.internal_round_constants[index];
+                current[0] = sbox.permute_evaluate(&(&current[0] + constant));
+                current = &self.internal_matrix * current.as_slice();
+            }
+        }
+
+        current
+    }
+
+    /// Applies the inverse permutation to the given elements directly, without building a gadget.
+    pub fn inverse_elements(&self, outputs: &[Element<F>]) -> Vec<Element<F>> {
+        assert_eq!(outputs.len(), self.width);
+        let sbox = self.sbox.to_permutation();
+        let inverse_external_matrix = self.external_matrix.inverse();
+        let inverse_internal_matrix = self.internal_matrix.inverse();
+
+        let mut current = outputs.to_vec();
+        for round in (0..self.num_rounds.full() + self.num_rounds.partial()).rev() {
+            let index = self.index_among_kind(round);
+            if self.is_full_round(round) {
+                current = &inverse_external_matrix * current.as_slice();
+                let constants = &self.external_round_constants[index];
+                current = current.iter().zip(constants)
+                    .map(|(x, constant)| sbox.inverse_evaluate(x) - constant)
+                    .collect();
+            } else {
+                current = &inverse_internal_matrix * current.as_slice();
+                let constant = &self.internal_round_constants[index];
+                current[0] = sbox.inverse_evaluate(&current[0]) - constant;
+            }
+        }
+
+        &inverse_external_matrix * current.as_slice()
+    }
+
+    /// Whether the given round, counting from zero, is a full round.
+    fn is_full_round(&self, round: usize) -> bool {
+        let full_rounds_per_side = self.num_rounds.full() / 2;
+        round < full_rounds_per_side || round >= full_rounds_per_side + self.num_rounds.partial()
+    }
+
+    /// The index of the given round among rounds of the same kind.
+    fn index_among_kind(&self, round: usize) -> usize {
+        let full_rounds_per_side = self.num_rounds.full() / 2;
+        if round < full_rounds_per_side {
+            round
+        } else if self.is_full_round(round) {
+            round - self.num_rounds.partial()
+        } else {
+            round - full_rounds_per_side
+        }
+    }
+}
+
+impl<F: Field> MultiPermutation<F> for Poseidon2<F> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn permute(&self, builder: &mut GadgetBuilder<F>, inputs: &[Expression<F>])
+               -> Vec<Expression<F>> {
+        assert_eq!(inputs.len(), self.width);
+        let sbox = self.sbox.to_permutation();
+
+        let mut current = &self.external_matrix * inputs;
+        for round in 0..self.num_rounds.full() + self.num_rounds.partial() {
+            let index = self.index_among_kind(round);
+            if self.is_full_round(round) {
+                let constants = &self.external_round_constants[index];
+                current = current.iter().zip(constants)
+                    .map(|(exp, constant)| {
+                        sbox.permute(builder, &(exp + Expression::from(constant)))
+                    })
+                    .collect();
+                current = &self.external_matrix * current.as_slice();
+            } else {
+                let constant = Expression::from(&self.internal_round_constants[index]);
+                current[0] = sbox.permute(builder, &(&current[0] + constant));
+                current = &self.internal_matrix * current.as_slice();
+            }
+        }
+
+        current
+    }
+
+    fn permute_evaluate(&self, inputs: &[Element<F>]) -> Vec<Element<F>> {
+        self.permute_elements(inputs)
+    }
+
+    fn inverse(&self, builder: &mut GadgetBuilder<F>, outputs: &[Expression<F>])
+               -> Vec<Expression<F>> {
+        assert_eq!(outputs.len(), self.width);
+        let sbox = self.sbox.to_permutation();
+        let inverse_external_matrix = self.external_matrix.inverse();
+        let inverse_internal_matrix = self.internal_matrix.inverse();
+
+        let mut current = outputs.to_vec();
+        for round in (0..self.num_rounds.full() + self.num_rounds.partial()).rev() {
+            let index = self.index_among_kind(round);
+            if self.is_full_round(round) {
+                current = &inverse_external_matrix * current.as_slice();
+                let constants = &self.external_round_constants[index];
+                current = current.iter().zip(constants)
+                    .map(|(exp, constant)| sbox.inverse(builder, exp) - Expression::from(constant))
+                    .collect();
+            } else {
+                current = &inverse_internal_matrix * current.as_slice();
+                let constant = Expression::from(&self.internal_round_constants[index]);
+                current[0] = sbox.inverse(builder, &current[0]) - constant;
+            }
+        }
+
+        &inverse_external_matrix * current.as_slice()
+    }
+
+    fn inverse_evaluate(&self, outputs: &[Element<F>]) -> Vec<Element<F>> {
+        self.inverse_elements(outputs)
+    }
+}
+
+/// A compression function built from a width-3 Poseidon2 instance with default parameters, used as
+/// a sponge with a rate of two elements and a capacity of one, like `poseidon_compression`.
+#[cfg(feature = "rand")]
+pub fn poseidon2_compression<F: Field>() -> Sponge<F, Poseidon2<F>> {
+    Sponge::new(Poseidon2Builder::new(3).build(), 2, 1)
+}
+
+/// The external matrix from the paper. For widths 2 and 3, this is the circulant matrix
+/// `circ(2, 1)` or `circ(2, 1, 1)`. For multiples of 4, it consists of 4x4 blocks, each of which
+/// is the matrix `M_4` from the paper, doubled along the diagonal.
+fn external_matrix<F: Field>(width: usize) -> ElementMatrix<F> {
+    const M4: [[u8; 4]; 4] = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+    let entry = |i: usize, j: usize| -> u8 {
+        match width {
+            2 | 3 => if i == j { 2 } else { 1 },
+            _ => {
+                let block_entry = M4[i % 4][j % 4];
+                if i / 4 == j / 4 { 2 * block_entry } else { block_entry }
+            }
+        }
+    };
+    assert!(width == 2 || width == 3 || (width > 0 && width.is_multiple_of(4)),
+            "Poseidon2 requires a width of 2, 3 or a multiple of 4");
+    ElementMatrix::new((0..width)
+        .map(|i| (0..width).map(|j| Element::from(entry(i, j))).collect())
+        .collect())
+}
+
+/// The internal matrix with the given diagonal, i.e. the all-ones matrix plus `diag(diagonal)`.
+fn internal_matrix<F: Field>(diagonal: &[Element<F>]) -> ElementMatrix<F> {
+    ElementMatrix::new((0..diagonal.len())
+        .map(|i| (0..diagonal.len())
+            .map(|j| if i == j { &diagonal[i] + Element::one() } else { Element::one() })
+            .collect())
+        .collect())
+}
+
+/// The diagonals given in the paper for widths 2 and 3, which give the internal matrices
+/// `[[2, 1], [1, 3]]` and `[[2, 1, 1], [1, 2, 1], [1, 1, 3]]`.
+fn default_internal_diagonal<F: Field>(width: usize) -> Vec<Element<F>> {
+    match width {
+        2 => vec![Element::one(), Element::from(2u8)],
+        3 => vec![Element::one(), Element::one(), Element::from(2u8)],
+        _ => panic!("Width {} requires an explicit internal diagonal", width),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::{Bn128, Element, Field, GadgetBuilder, MultiPermutation, NumberOfRounds,
+                Poseidon2Builder, PoseidonBuilder};
+    #[cfg(feature = "rand")]
+    use crate::{CompressionFunction, poseidon2_compression};
+    use crate::lcg::LCG;
+    use crate::poseidon2::{external_matrix, Poseidon2};
+    use crate::PoseidonSbox::Exponentiation5;
+    use crate::fields::small::F257;
+
+    /// Generates round constants with the Grain LFSR, as the reference implementation's parameter
+    /// script does for a prime field, an `x^alpha` S-box and the given sizes. Each full round takes
+    /// `width` constants from the stream, and each partial round takes one.
+    fn grain_round_constants(
+        width: usize, num_rounds: NumberOfRounds,
+    ) -> (Vec<Vec<Element<Bn128>>>, Vec<Element<Bn128>>) {
+        let field_bits = Element::<Bn128>::max_bits();
+        let mut state: Vec<bool> = Vec::new();
+        for &(value, bits) in &[(1, 2), (0, 4), (field_bits, 12), (width, 12),
+                                (num_rounds.full(), 10), (num_rounds.partial(), 10)] {
+            state.extend((0..bits).rev().map(|i| (value >> i) & 1 == 1));
+        }
+        state.extend(vec![true; 30]);
+
+        let mut next_bit = move || {
+            let bit = state[62] ^ state[51] ^ state[38] ^ state[23] ^ state[13] ^ state[0];
+            state.remove(0);
+            state.push(bit);
+            bit
+        };
+        for _i in 0..160 {
+            next_bit();
+        }
+        let mut next_element = move || loop {
+            let mut value = BigUint::from(0u8);
+            for _i in 0..field_bits {
+                // Bits are drawn in pairs, and the second is kept only if the first is set.
+                let bit = loop {
+                    let (keep, bit) = (next_bit(), next_bit());
+                    if keep {
+                        break bit;
+                    }
+                };
+                value = (value << 1usize) | BigUint::from(bit as u8);
+            }
+            if value < Bn128::order() {
+                break Element::from(value);
+            }
+        };
+
+        let (mut external, mut internal) = (Vec::new(), Vec::new());
+        let full_rounds_per_side = num_rounds.full() / 2;
+        let partial_rounds = full_rounds_per_side..full_rounds_per_side + num_rounds.partial();
+        for round in 0..num_rounds.full() + num_rounds.partial() {
+            if !partial_rounds.contains(&round) {
+                external.push((0..width).map(|_i| next_element()).collect());
+            } else {
+                internal.push(next_element());
+            }
+        }
+        (external, internal)
+    }
+
+    fn bn128_width_3() -> Poseidon2<Bn128> {
+        let num_rounds = NumberOfRounds::new(8, 56);
+        let (external, internal) = grain_round_constants(3, num_rounds);
+        Poseidon2Builder::new(3)
+            .sbox(Exponentiation5)
+            .num_rounds(num_rounds)
+            .external_round_constants(external)
+            .internal_round_constants(internal)
+            .build()
+    }
+
+    fn element(hex: &str) -> Element<Bn128> {
+        Element::from(BigUint::parse_bytes(hex.as_bytes(), 16).unwrap())
+    }
+
+    #[test]
+    fn poseidon2_bn128_test_vector() {
+        // The test vector of the reference implementation for BN254 with a width of 3.
+        let poseidon2 = bn128_width_3();
+        let inputs = [Element::zero(), Element::one(), Element::from(2u8)];
+        assert_eq!(
+            vec![element("0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033"),
+                 element("303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570"),
+                 element("1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8")],
+            poseidon2.permute_evaluate(&inputs));
+    }
+
+    #[test]
+    fn poseidon2_inverse_bn128() {
+        let poseidon2 = bn128_width_3();
+        let mut lcg = LCG::new();
+        let inputs: Vec<Element<Bn128>> = (0..3).map(|_i| lcg.next_element()).collect();
+        let outputs = poseidon2.permute_evaluate(&inputs);
+        assert_eq!(inputs, poseidon2.inverse_evaluate(&outputs));
+    }
+
+    #[test]
+    fn poseidon2_inverse_small_widths() {
+        let mut lcg = LCG::new();
+        for &width in &[2, 4, 8] {
+            let num_rounds = NumberOfRounds::new(4, 3);
+            let mut builder = Poseidon2Builder::<F257>::new(width);
+            builder.sbox(Exponentiation5)
+                .num_rounds(num_rounds)
+                .external_round_constants((0..4)
+                    .map(|_i| (0..width).map(|_j| lcg.next_element()).collect())
+                    .collect())
+                .internal_round_constants((0..3).map(|_i| lcg.next_element()).collect());
+            if width > 3 {
+                builder.internal_diagonal((0..width).map(|i| Element::from(i as u8 + 2)).collect());
+            }
+            let poseidon2 = builder.build();
+
+            let inputs: Vec<Element<F257>> = (0..width).map(|_i| lcg.next_element()).collect();
+            let outputs = poseidon2.permute_evaluate(&inputs);
+            assert_eq!(inputs, poseidon2.inverse_evaluate(&outputs));
+        }
+    }
+
+    #[test]
+    fn external_matrix_width_8() {
+        let matrix = external_matrix::<F257>(8);
+        // The first column is 2 * (5, 4, 1, 1), followed by (5, 4, 1, 1).
+        let mut unit = vec![Element::zero(); 8];
+        unit[0] = Element::one();
+        let expected: Vec<Element<F257>> = [10u8, 8, 2, 2, 5, 4, 1, 1].iter()
+            .map(|&x| Element::from(x))
+            .collect();
+        assert_eq!(expected, &matrix * unit.as_slice());
+    }
+
+    #[test]
+    fn linear_layers_compared_to_poseidon() {
+        // With the same numbers of rounds, both variants need the same S-boxes, so they have the
+        // same number of constraints. Poseidon2's linear layers only have small entries, while
+        // the Cauchy matrix used by Poseidon has arbitrary ones.
+        let num_rounds = NumberOfRounds::new(8, 56);
+        let mut lcg = LCG::new();
+        let poseidon = PoseidonBuilder::<Bn128>::new(3)
+            .sbox(Exponentiation5)
+            .num_rounds(num_rounds)
+            .round_constants((0..64)
+                .map(|_i| (0..3).map(|_j| lcg.next_element()).collect())
+                .collect())
+            .build();
+        let poseidon2 = bn128_width_3();
+
+        let size_and_coefficients = |permutation: &dyn MultiPermutation<Bn128>| {
+            let mut builder = GadgetBuilder::new();
+            let (_wires, inputs) = builder.expression_wires(3);
+            let outputs = permutation.permute(&mut builder, &inputs);
+            let largest_coefficient = outputs.iter()
+                .flat_map(|output| output.coefficients().values().cloned().collect::<Vec<_>>())
+                .map(|coefficient| coefficient.to_biguint().clone())
+                .max()
+                .unwrap();
+            (builder.build().size(), largest_coefficient)
+        };
+        let (poseidon_size, poseidon_largest) = size_and_coefficients(&poseidon);
+        let (poseidon2_size, poseidon2_largest) = size_and_coefficients(&poseidon2);
+        assert_eq!(poseidon_size, poseidon2_size);
+        // Each output is the final linear layer applied to the last S-box outputs.
+        assert_eq!(BigUint::from(2u8), poseidon2_largest);
+        assert!(poseidon_largest > BigUint::from(u64::MAX));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn poseidon2_compression_bn128() {
+        let compression = poseidon2_compression::<Bn128>();
+        assert_eq!(NumberOfRounds::new(8, 55), compression.permutation().num_rounds());
+        let x = compression.compress_evaluate(&Element::one(), &Element::from(2u8));
+        let y = compression.compress_evaluate(&Element::from(2u8), &Element::one());
+        assert!(x != y && !x.is_zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "Poseidon2 requires a width of 2, 3 or a multiple of 4")]
+    fn unsupported_width() {
+        external_matrix::<F257>(5);
+    }
+}
@@ -0,0 +1,257 @@
+//! This module extends GadgetBuilder with methods for packing many small public inputs, such as
+//! booleans, bytes or indices, into as few field elements as possible. Verifier costs often scale
+//! with the number of public inputs, so this can be much cheaper than exposing each value.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use num::BigUint;
+use num_traits::{One, Zero};
+
+use crate::expression::{BinaryExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::group::bind_components;
+use crate::wire::Wire;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Packs the given values, each a binary expression paired with its declared width in bits,
+    /// into `ceil(total_width / (max_bits - 1))` field elements, and returns wires holding them.
+    ///
+    /// The values are concatenated least significant first, each padded to its width, and the
+    /// concatenation is cut into chunks of `max_bits - 1` bits, so each chunk fits in a field
+    /// element. Packing costs one constraint per element.
+    ///
+    /// As with `Group::bind_as_public`, the wires are meant to be public inputs: the caller should
+    /// set them to the values given by `pack_public_values`, and include them in the set of public
+    /// wires passed to exporters such as `Gadget::assignment_vector`.
+    pub fn pack_public_inputs(
+        &mut self, values_bits: &[(BinaryExpression<F>, usize)],
+    ) -> Vec<Wire> {
+        logged!(self, "pack_public_inputs", [values_bits.len()], {
+            let pieces: Vec<BinaryExpression<F>> = values_bits.iter()
+                .map(|(value, width)| {
+                    assert!(value.len() <= *width,
+                            "Value has {} bits, exceeding its declared width of {}",
+                            value.len(), width);
+                    value.padded(*width)
+                })
+                .collect();
+            let chunks: Vec<Expression<F>> = BinaryExpression::concat(&pieces)
+                .chunks(packing_bits::<F>()).iter()
+                .map(BinaryExpression::join)
+                .collect();
+            bind_components(self, &chunks)
+        })
+    }
+
+    /// The inverse of `pack_public_inputs`: given the packed wires and the declared widths of the
+    /// values they hold, returns each value in binary.
+    ///
+    /// Each packed element is split into exactly as many bits as `pack_public_inputs` put in it,
+    /// which asserts that every value is within its width. Any element holding more bits than the
+    /// widths account for makes the gadget unsatisfiable.
+    pub fn unpack_public_inputs(
+        &mut self, packed: &[Wire], widths: &[usize],
+    ) -> Vec<BinaryExpression<F>> {
+        logged!(self, "unpack_public_inputs", [packed.len(), widths.len()], {
+            let chunk_widths = packed_chunk_widths::<F>(widths.iter().sum());
+            assert_eq!(packed.len(), chunk_widths.len(),
+                       "Expected {} packed elements for the given widths", chunk_widths.len());
+            let chunks: Vec<BinaryExpression<F>> = packed.iter().zip(chunk_widths)
+                .map(|(&wire, bits)| self.split_bounded(&Expression::from(wire), bits))
+                .collect();
+            let all_bits = BinaryExpression::concat(&chunks);
+
+            let mut offset = 0;
+            widths.iter()
+                .map(|&width| {
+                    let bits = all_bits.bits[offset..offset + width].to_vec();
+                    offset += width;
+                    BinaryExpression { bits }
+                })
+                .collect()
+        })
+    }
+}
+
+/// Packs plain integers, each paired with its declared width in bits, into field elements, exactly
+/// as `GadgetBuilder::pack_public_inputs` does in-circuit. Verifiers can use this to compute the
+/// public inputs of a proof. Panics if a value doesn't fit in its width.
+pub fn pack_public_values<F: Field>(values: &[(BigUint, usize)]) -> Vec<Element<F>> {
+    let mut total = BigUint::zero();
+    let mut total_width = 0;
+    for (value, width) in values {
+        assert!(value.bits() <= *width as u64, "Value does not fit in {} bits", width);
+        total |= value << total_width;
+        total_width += width;
+    }
+
+    let chunk_bits = packing_bits::<F>();
+    let mask = (BigUint::one() << chunk_bits) - BigUint::one();
+    (0..total_width.div_ceil(chunk_bits))
+        .map(|i| Element::from((&total >> (i * chunk_bits)) & &mask))
+        .collect()
+}
+
+/// The inverse of `pack_public_values`: given packed field elements and the declared widths of
+/// the values they hold, returns each value as an integer.
+pub fn unpack_public_values<F: Field>(packed: &[Element<F>], widths: &[usize]) -> Vec<BigUint> {
+    let chunk_bits = packing_bits::<F>();
+    let total = packed.iter().enumerate()
+        .fold(BigUint::zero(), |total, (i, element)| {
+            total | (element.to_biguint() << (i * chunk_bits))
+        });
+
+    let mut offset = 0;
+    widths.iter()
+        .map(|&width| {
+            let value = (&total >> offset) & ((BigUint::one() << width) - BigUint::one());
+            offset += width;
+            value
+        })
+        .collect()
+}
+
+/// The number of bits packed into each field element. Any `max_bits - 1` bit value is less than
+/// `|F|`, so it can be joined without overflow.
+fn packing_bits<F: Field>() -> usize {
+    Element::<F>::max_bits() - 1
+}
+
+/// The number of bits packed into each element, for a total of `total_bits` bits. Every element
+/// holds `packing_bits` bits, except that the last may hold fewer.
+fn packed_chunk_widths<F: Field>(total_bits: usize) -> Vec<usize> {
+    let chunk_bits = packing_bits::<F>();
+    (0..total_bits.div_ceil(chunk_bits))
+        .map(|i| chunk_bits.min(total_bits - i * chunk_bits))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::{BinaryExpression, BinaryWire, BooleanExpression, BooleanWire, Bn128, Element,
+                GadgetBuilder, pack_public_values, unpack_public_values, WireValues};
+
+    /// The widths of 40 booleans followed by three 32-bit words.
+    fn widths() -> Vec<usize> {
+        let mut widths = vec![1; 40];
+        widths.extend(vec![32; 3]);
+        widths
+    }
+
+    fn plain_values() -> Vec<BigUint> {
+        let mut values: Vec<BigUint> = (0..40u8).map(|i| BigUint::from(i % 3 == 0)).collect();
+        values.extend(vec![BigUint::from(0xdeadbeefu32), BigUint::from(7u8),
+                           BigUint::from(u32::MAX)]);
+        values
+    }
+
+    #[test]
+    fn pack_booleans_and_words() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let booleans: Vec<BooleanWire> = (0..40).map(|_i| builder.boolean_wire()).collect();
+        let words: Vec<BinaryWire> = (0..3).map(|_i| builder.binary_wire(32)).collect();
+        let mut values_bits: Vec<(BinaryExpression<Bn128>, usize)> = booleans.iter()
+            .map(|&b| (BinaryExpression { bits: vec![BooleanExpression::from(b)] }, 1))
+            .collect();
+        values_bits.extend(words.iter().map(|w| (BinaryExpression::from(w), 32)));
+        let packed = builder.pack_public_inputs(&values_bits);
+        let unpacked = builder.unpack_public_inputs(&packed, &widths());
+        let gadget = builder.build();
+        assert_eq!(1, packed.len());
+
+        let plain = plain_values();
+        let with_packed = |packed_values: &[Element<Bn128>]| {
+            let mut values = WireValues::new();
+            for (i, &b) in booleans.iter().enumerate() {
+                values.set_boolean(b, plain[i] == BigUint::from(1u8));
+            }
+            for (i, w) in words.iter().enumerate() {
+                values.set_binary_unsigned(w, &plain[40 + i]);
+            }
+            values.set_each(&packed, packed_values);
+            values
+        };
+
+        let packed_values = pack_public_values::<Bn128>(
+            &plain.iter().cloned().zip(widths()).collect::<Vec<_>>());
+        assert_eq!(1, packed_values.len());
+        let mut values = with_packed(&packed_values);
+        assert!(gadget.execute(&mut values));
+        let unpacked_values: Vec<BigUint> = unpacked.iter().map(|x| x.evaluate(&values)).collect();
+        assert_eq!(plain, unpacked_values);
+        assert_eq!(plain, unpack_public_values(&packed_values, &widths()));
+
+        let wrong = &packed_values[0] + Element::one();
+        assert!(!gadget.execute(&mut with_packed(&[wrong])));
+    }
+
+    #[test]
+    fn pack_several_elements() {
+        // 9 words of 32 bits exceed the 253 bits which fit in a Bn128 element.
+        let widths = vec![32; 9];
+        let plain: Vec<BigUint> = (0..9u32).map(|i| BigUint::from(0x9e3779b9u32 ^ i)).collect();
+        let packed_values = pack_public_values::<Bn128>(
+            &plain.iter().cloned().zip(widths.iter().cloned()).collect::<Vec<_>>());
+        assert_eq!(2, packed_values.len());
+        assert_eq!(plain, unpack_public_values(&packed_values, &widths));
+
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let words: Vec<BinaryWire> = (0..9).map(|_i| builder.binary_wire(32)).collect();
+        let values_bits: Vec<(BinaryExpression<Bn128>, usize)> = words.iter()
+            .map(|w| (BinaryExpression::from(w), 32))
+            .collect();
+        let packed = builder.pack_public_inputs(&values_bits);
+        let gadget = builder.build();
+
+        let mut values = WireValues::new();
+        for (w, value) in words.iter().zip(&plain) {
+            values.set_binary_unsigned(w, value);
+        }
+        values.set_each(&packed, &packed_values);
+        assert!(gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn unpack_width_violation() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let packed = builder.wire();
+        let unpacked = builder.unpack_public_inputs(&[packed], &[8, 8]);
+        let gadget = builder.build();
+
+        let mut values = values!(packed => Element::from(0x1234u16));
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(0x34u8), unpacked[0].evaluate(&values));
+        assert_eq!(BigUint::from(0x12u8), unpacked[1].evaluate(&values));
+
+        // 2^16 doesn't fit in the 16 bits declared, so no witness satisfies the gadget.
+        assert!(!gadget.execute(&mut values!(packed => Element::from(1u32 << 16))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Value has 9 bits, exceeding its declared width of 8")]
+    fn pack_width_violation() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = builder.binary_wire(9);
+        builder.pack_public_inputs(&[(BinaryExpression::from(&x), 8)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Value does not fit in 8 bits")]
+    fn pack_values_width_violation() {
+        pack_public_values::<Bn128>(&[(BigUint::from(256u16), 8)]);
+    }
+
+    #[test]
+    fn pack_constant() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let packed = builder.pack_public_inputs(&[(BinaryExpression::from(5u8), 4)]);
+        let gadget = builder.build();
+        let expected = pack_public_values::<Bn128>(&[(BigUint::from(5u8), 4)]);
+        assert_eq!(vec![Element::from(5u8)], expected);
+        assert!(gadget.execute(&mut values!(packed[0] => expected[0].clone())));
+    }
+}
@@ -0,0 +1,314 @@
+//! This module exports gadgets and witnesses in the binary `.r1cs` and `.wtns` formats used by
+//! circom and snarkjs. It is only available with the `std` feature, since it relies on `std::io`.
+//!
+//! Both formats consist of a magic string, a version and a list of sections, each with a type and
+//! a byte length. Field elements are encoded in little-endian order, padded to a whole number of
+//! 64-bit limbs; for a 254-bit field such as `Bn128`, that is 32 bytes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Seek, SeekFrom, Write};
+
+use num::BigUint;
+
+use crate::field::{Element, Field};
+use crate::gadget::Gadget;
+use crate::expression::Expression;
+use crate::wire::Wire;
+use crate::wire_values::WireValues;
+
+const R1CS_MAGIC: &[u8; 4] = b"r1cs";
+const R1CS_VERSION: u32 = 1;
+const R1CS_HEADER_SECTION: u32 = 1;
+const R1CS_CONSTRAINTS_SECTION: u32 = 2;
+const R1CS_WIRE_TO_LABEL_SECTION: u32 = 3;
+
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const WTNS_VERSION: u32 = 2;
+const WTNS_HEADER_SECTION: u32 = 1;
+const WTNS_VALUES_SECTION: u32 = 2;
+
+/// Writes the gadget's constraints in circom's `.r1cs` format.
+///
+/// Variables are numbered according to `Gadget::variable_order`, so the public wires come right
+/// after the constant one, as snarkjs expects. They are all recorded as public inputs; circom's
+/// distinction between public outputs and public inputs, and between private inputs and other
+/// private signals, has no counterpart here. Each variable is labelled with its wire index.
+pub fn write_circom_r1cs<F: Field, W: Write + Seek>(
+    gadget: &Gadget<F>, public_wires: &BTreeSet<Wire>, writer: &mut W,
+) -> io::Result<()> {
+    let order = gadget.variable_order(public_wires);
+    let ids: BTreeMap<Wire, u32> = order.iter().enumerate()
+        .map(|(i, &wire)| (wire, i as u32))
+        .collect();
+    let public_count = order.iter().filter(|wire| public_wires.contains(wire)).count();
+
+    writer.write_all(R1CS_MAGIC)?;
+    write_u32(writer, R1CS_VERSION)?;
+    write_u32(writer, 3)?;
+
+    write_section(writer, R1CS_HEADER_SECTION, |w| {
+        write_field_header::<F, W>(w)?;
+        write_u32(w, order.len() as u32)?;
+        write_u32(w, 0)?; // Public outputs
+        write_u32(w, public_count as u32)?;
+        write_u32(w, 0)?; // Private inputs
+        write_u64(w, gadget.free_variable_id())?;
+        write_u32(w, gadget.size() as u32)
+    })?;
+
+    write_section(writer, R1CS_CONSTRAINTS_SECTION, |w| {
+        for constraint in gadget.constraints() {
+            for expression in &[&constraint.a, &constraint.b, &constraint.c] {
+                write_linear_combination(w, expression, &ids)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    write_section(writer, R1CS_WIRE_TO_LABEL_SECTION, |w| {
+        for wire in &order {
+            write_u64(w, u64::from(wire.index))?;
+        }
+        Ok(())
+    })
+}
+
+/// Writes a witness in circom's `.wtns` format, with values in the same order as the variables
+/// written by `write_circom_r1cs`. Fails if any of those variables has no value.
+pub fn write_circom_wtns<F: Field, W: Write + Seek>(
+    gadget: &Gadget<F>, values: &WireValues<F>, public_wires: &BTreeSet<Wire>, writer: &mut W,
+) -> io::Result<()> {
+    let assignment = gadget.assignment_vector(values, public_wires)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+
+    writer.write_all(WTNS_MAGIC)?;
+    write_u32(writer, WTNS_VERSION)?;
+    write_u32(writer, 2)?;
+
+    write_section(writer, WTNS_HEADER_SECTION, |w| {
+        write_field_header::<F, W>(w)?;
+        write_u32(w, assignment.len() as u32)
+    })?;
+
+    write_section(writer, WTNS_VALUES_SECTION, |w| {
+        for value in &assignment {
+            write_biguint(w, value.to_biguint(), field_bytes::<F>())?;
+        }
+        Ok(())
+    })
+}
+
+/// The number of bytes used to encode each field element: the size of the field's order, rounded
+/// up to a whole number of 64-bit limbs.
+fn field_bytes<F: Field>() -> usize {
+    Element::<F>::max_bits().div_ceil(64) * 8
+}
+
+/// Writes a section with the given type, followed by its length, followed by its contents as
+/// written by `body`. The length is written as a placeholder, then filled in afterward.
+fn write_section<W: Write + Seek, B>(writer: &mut W, section_type: u32, body: B) -> io::Result<()>
+    where B: FnOnce(&mut W) -> io::Result<()> {
+    write_u32(writer, section_type)?;
+    let length_position = writer.stream_position()?;
+    write_u64(writer, 0)?;
+    body(writer)?;
+    let end_position = writer.stream_position()?;
+
+    writer.seek(SeekFrom::Start(length_position))?;
+    write_u64(writer, end_position - length_position - 8)?;
+    writer.seek(SeekFrom::Start(end_position))?;
+    Ok(())
+}
+
+/// Writes the element size in bytes, followed by the field's order.
+fn write_field_header<F: Field, W: Write>(writer: &mut W) -> io::Result<()> {
+    write_u32(writer, field_bytes::<F>() as u32)?;
+    write_biguint(writer, &F::order(), field_bytes::<F>())
+}
+
+fn write_linear_combination<F: Field, W: Write>(
+    writer: &mut W, expression: &Expression<F>, ids: &BTreeMap<Wire, u32>,
+) -> io::Result<()> {
+    let mut terms: Vec<(u32, &Element<F>)> = expression.iter()
+        .map(|(wire, coefficient)| (ids[wire], coefficient))
+        .collect();
+    terms.sort_by_key(|&(id, _coefficient)| id);
+
+    write_u32(writer, terms.len() as u32)?;
+    for (id, coefficient) in terms {
+        write_u32(writer, id)?;
+        write_biguint(writer, coefficient.to_biguint(), field_bytes::<F>())?;
+    }
+    Ok(())
+}
+
+fn write_biguint<W: Write>(writer: &mut W, n: &BigUint, len: usize) -> io::Result<()> {
+    let mut bytes = n.to_bytes_le();
+    assert!(bytes.len() <= len, "Value doesn't fit in {} bytes", len);
+    bytes.resize(len, 0);
+    writer.write_all(&bytes)
+}
+
+fn write_u32<W: Write>(writer: &mut W, n: u32) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+fn write_u64<W: Write>(writer: &mut W, n: u64) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::convert::TryInto;
+    use std::io::Cursor;
+
+    use num::BigUint;
+
+    use crate::expression::Expression;
+    use crate::field::{Bn128, Element, Field};
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::r1cs_circom::{write_circom_r1cs, write_circom_wtns};
+    use crate::wire::Wire;
+
+    /// A minimal reader for the files we write, which splits them into sections.
+    struct Reader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn bytes(&mut self, len: usize) -> &'a [u8] {
+            let bytes = &self.data[self.position..self.position + len];
+            self.position += len;
+            bytes
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_le_bytes(self.bytes(4).try_into().unwrap())
+        }
+
+        fn u64(&mut self) -> u64 {
+            u64::from_le_bytes(self.bytes(8).try_into().unwrap())
+        }
+
+        fn biguint(&mut self, len: usize) -> BigUint {
+            BigUint::from_bytes_le(self.bytes(len))
+        }
+
+        /// Checks the magic string and version, then returns each section's type and contents.
+        fn sections(&mut self, magic: &[u8], version: u32) -> BTreeMap<u32, Reader<'a>> {
+            assert_eq!(magic, self.bytes(4));
+            assert_eq!(version, self.u32());
+            let count = self.u32();
+            let mut sections = BTreeMap::new();
+            for _i in 0..count {
+                let section_type = self.u32();
+                let len = self.u64() as usize;
+                sections.insert(section_type, Reader { data: self.bytes(len), position: 0 });
+            }
+            assert_eq!(self.data.len(), self.position);
+            sections
+        }
+
+        fn is_done(&self) -> bool {
+            self.position == self.data.len()
+        }
+    }
+
+    #[test]
+    fn r1cs_header_and_constraints() {
+        // Assert that z = x * y - w, with an unused wire, so that the sections encode a negative
+        // coefficient and a gap in the wire indices.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y, _unused, w) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        let xy = builder.product(&Expression::from(x), &Expression::from(y));
+        let z = builder.wire();
+        builder.assert_equal(&Expression::from(z), &(xy - Expression::from(w)));
+        let gadget = builder.build();
+        let public_wires: BTreeSet<Wire> = [z].iter().cloned().collect();
+        let mut cursor = Cursor::new(Vec::new());
+        write_circom_r1cs(&gadget, &public_wires, &mut cursor).unwrap();
+        let data = cursor.into_inner();
+        let mut sections = Reader { data: &data, position: 0 }.sections(b"r1cs", 1);
+        assert_eq!(vec![1, 2, 3], sections.keys().cloned().collect::<Vec<u32>>());
+
+        let order = gadget.variable_order(&public_wires);
+        let header = sections.get_mut(&1).unwrap();
+        assert_eq!(32, header.u32());
+        assert_eq!(Bn128::order(), header.biguint(32));
+        assert_eq!(order.len() as u32, header.u32());
+        assert_eq!(0, header.u32());
+        assert_eq!(1, header.u32());
+        assert_eq!(0, header.u32());
+        assert_eq!(gadget.free_variable_id(), header.u64());
+        assert_eq!(gadget.size() as u32, header.u32());
+        assert!(header.is_done());
+
+        // The constraints section should match the gadget's sparse constraints.
+        let constraints = sections.get_mut(&2).unwrap();
+        for constraint in gadget.constraints() {
+            for expression in &[&constraint.a, &constraint.b, &constraint.c] {
+                let terms = constraints.u32() as usize;
+                assert_eq!(expression.num_terms(), terms);
+                let mut read = Expression::zero();
+                for _i in 0..terms {
+                    let wire = order[constraints.u32() as usize];
+                    let coefficient = Element::from(constraints.biguint(32));
+                    read += Expression::from(wire) * coefficient;
+                }
+                assert_eq!(**expression, read);
+            }
+        }
+        assert!(constraints.is_done());
+
+        let labels = sections.get_mut(&3).unwrap();
+        for wire in &order {
+            assert_eq!(u64::from(wire.index), labels.u64());
+        }
+        assert!(labels.is_done());
+    }
+
+    #[test]
+    fn wtns() {
+        // Assert that z = x * y - w, with an unused wire, so that the sections encode a negative
+        // coefficient and a gap in the wire indices.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y, _unused, w) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        let xy = builder.product(&Expression::from(x), &Expression::from(y));
+        let z = builder.wire();
+        builder.assert_equal(&Expression::from(z), &(xy - Expression::from(w)));
+        let gadget = builder.build();
+        let public_wires: BTreeSet<Wire> = [z].iter().cloned().collect();
+
+        // Without a full assignment, there is no witness to write.
+        let partial = values!(x => 3u8.into());
+        let mut cursor = Cursor::new(Vec::new());
+        assert!(write_circom_wtns(&gadget, &partial, &public_wires, &mut cursor).is_err());
+
+        let mut values =
+            values!(x => 3u8.into(), y => 4u8.into(), z => 7u8.into(), w => 5u8.into());
+        assert!(gadget.execute(&mut values));
+
+        let mut cursor = Cursor::new(Vec::new());
+        write_circom_wtns(&gadget, &values, &public_wires, &mut cursor).unwrap();
+        let data = cursor.into_inner();
+        let assignment = gadget.assignment_vector(&values, &public_wires).unwrap();
+        assert_eq!(4 + 4 + 4 + (4 + 8 + 4 + 32 + 4) + (4 + 8 + 32 * assignment.len()), data.len());
+
+        let mut sections = Reader { data: &data, position: 0 }.sections(b"wtns", 2);
+        let header = sections.get_mut(&1).unwrap();
+        assert_eq!(32, header.u32());
+        assert_eq!(Bn128::order(), header.biguint(32));
+        assert_eq!(assignment.len() as u32, header.u32());
+        assert!(header.is_done());
+
+        // The constant one and public z should come first, in the same order as the variables.
+        let witness = sections.get_mut(&2).unwrap();
+        let read: Vec<Element<Bn128>> =
+            (0..assignment.len()).map(|_i| Element::from(witness.biguint(32))).collect();
+        assert!(witness.is_done());
+        assert_eq!(assignment, read);
+        assert_eq!(&[Element::one(), Element::from(7u8)], &read[..2]);
+    }
+}
@@ -19,14 +19,16 @@ impl<F: Field> GadgetBuilder<F> {
         items: &[Expression<F>],
         index: &Expression<F>,
     ) -> Expression<F> {
-        // Determine the minimum number of bits needed to encode the index.
-        let mut bits = 0;
-        while 1 << bits < items.len() {
-            bits += 1;
-        }
+        logged!(self, "random_access", [items.len(), index], {
+            // Determine the minimum number of bits needed to encode the index.
+            let mut bits = 0;
+            while 1 << bits < items.len() {
+                bits += 1;
+            }
 
-        let index_binary = self.split_bounded(index, bits);
-        self.random_access_binary(items, index_binary.bits)
+            let index_binary = self.split_bounded(index, bits);
+            self.random_access_binary(items, index_binary.bits)
+        })
     }
 
     /// Like `random_access`, but with a binary index.
@@ -50,7 +52,7 @@ impl<F: Field> GadgetBuilder<F> {
         }
 
         let lsb = index_bits.remove(0);
-        let num_parents = (items.len() + 1) / 2;
+        let num_parents = items.len().div_ceil(2);
         let mut parent_layer = Vec::with_capacity(num_parents);
         for parent_index in 0..num_parents {
             let left_child_index = parent_index * 2;
@@ -75,7 +77,7 @@ mod tests {
     use crate::expression::Expression;
     use crate::field::Element;
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::fields::small::F257;
     use crate::wire_values::WireValues;
 
     #[test]
@@ -90,8 +92,8 @@ mod tests {
         let gadget = builder.build();
 
         let mut wire_values = WireValues::new();
-        for i in 0..n {
-            wire_values.set(item_wires[i], Element::from(i));
+        for (i, &item_wire) in item_wires.iter().enumerate() {
+            wire_values.set(item_wire, Element::from(i));
         }
 
         for i in 0..n {
@@ -0,0 +1,123 @@
+//! This module extends GadgetBuilder with a method for asserting that two vectors are equal by
+//! checking a random linear combination of their differences.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::Expression;
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+use crate::gadget_traits::HashFunction;
+use crate::transcript::Transcript;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Assert that `a[i] = b[i]` for each `i`, by asserting that `sum(r^i * (a[i] - b[i])) = 0`,
+    /// where the challenge `r` is derived from `transcript` after absorbing `a` and `b`. Vectors
+    /// of fewer than two elements are compared directly, leaving the transcript untouched.
+    ///
+    /// This is probabilistically sound: if the vectors differ, the combination is a nonzero
+    /// polynomial in `r` of degree less than `n`, so it has fewer than `n` roots, and a prover
+    /// succeeds with probability at most `(n - 1) / |F|`, assuming the transcript's hash behaves
+    /// like a random oracle. This is negligible for cryptographically sized fields, but not for
+    /// small ones.
+    ///
+    /// Note that this is *not* cheaper than calling `assert_equal` on each pair, which costs `n`
+    /// constraints. Evaluating the combination costs `n - 1` products, since `r` isn't constant,
+    /// and hashing both vectors costs far more. It can only pay off if the challenge is available
+    /// for free, e.g. if it's derived elsewhere for other purposes.
+    pub fn assert_vectors_equal_rlc<H: HashFunction<F>>(
+        &mut self, a: &[Expression<F>], b: &[Expression<F>], transcript: &mut Transcript<F, H>,
+    ) {
+        logged!(self, "assert_vectors_equal_rlc", [a.len(), b.len()], {
+            assert_eq!(a.len(), b.len(), "Vectors must have the same length");
+            let differences: Vec<Expression<F>> =
+                a.iter().zip(b).map(|(a_i, b_i)| a_i - b_i).collect();
+            let (first, rest) = match differences.split_first() {
+                Some(split) => split,
+                None => return,
+            };
+            let (last, middle) = match rest.split_last() {
+                Some(split) => split,
+                None => {
+                    self.assert_equal(first, &Expression::zero());
+                    return;
+                }
+            };
+
+            transcript.absorb("rlc a", a);
+            transcript.absorb("rlc b", b);
+            let challenge = transcript.challenge(self, "rlc challenge");
+
+            // We evaluate the combination using Horner's method. The final multiplication by r is
+            // asserted directly against -d[0], rather than being computed and summed.
+            let mut combination = last.clone();
+            for difference in middle.iter().rev() {
+                combination = self.product(&combination, &challenge) + difference;
+            }
+            self.assert_product(&combination, &challenge, &-first);
+        })
+    }
+}
+
+#[cfg(all(test, feature = "rand"))]
+mod tests {
+    use crate::expression::Expression;
+    use crate::field::{Bn128, Element};
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::poseidon::poseidon_compression;
+    use crate::transcript::Transcript;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+
+    fn values(a_wires: &[Wire], b_wires: &[Wire], changed: Option<usize>) -> WireValues<Bn128> {
+        let mut values = WireValues::new();
+        for (i, (&a, &b)) in a_wires.iter().zip(b_wires).enumerate() {
+            let value = Element::from(i * 1000 + 17);
+            let b_value = if changed == Some(i) { &value + Element::one() } else { value.clone() };
+            values.set(a, value);
+            values.set(b, b_value);
+        }
+        values
+    }
+
+    #[test]
+    fn assert_vectors_equal_rlc() {
+        for &n in &[8, 1] {
+            let mut builder = GadgetBuilder::<Bn128>::new();
+            let (a_wires, b_wires) = (builder.wires(n), builder.wires(n));
+            let a: Vec<Expression<Bn128>> = a_wires.iter().map(Expression::from).collect();
+            let b: Vec<Expression<Bn128>> = b_wires.iter().map(Expression::from).collect();
+            let mut transcript = Transcript::new("rlc test", poseidon_compression());
+            builder.assert_vectors_equal_rlc(&a, &b, &mut transcript);
+            let gadget = builder.build();
+
+            assert!(gadget.execute(&mut values(&a_wires, &b_wires, None)));
+            for &i in &[0, n / 2, n - 1] {
+                assert!(!gadget.execute(&mut values(&a_wires, &b_wires, Some(i))));
+            }
+        }
+    }
+
+    #[test]
+    fn assert_vectors_equal_rlc_empty() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let mut transcript = Transcript::new("rlc test", poseidon_compression());
+        builder.assert_vectors_equal_rlc(&[], &[], &mut transcript);
+        assert_eq!(0, builder.build().size());
+    }
+
+    #[test]
+    fn assert_vectors_equal_rlc_cost() {
+        // The hash dominates, so the combination is more expensive than plain equality checks,
+        // which take one constraint per element, for both short and long vectors.
+        for &n in &[4, 64] {
+            let mut builder = GadgetBuilder::<Bn128>::new();
+            let a: Vec<Expression<Bn128>> = builder.wires(n).iter().map(Expression::from).collect();
+            let b: Vec<Expression<Bn128>> = builder.wires(n).iter().map(Expression::from).collect();
+            let mut transcript = Transcript::new("rlc test", poseidon_compression());
+            builder.assert_vectors_equal_rlc(&a, &b, &mut transcript);
+            let rlc = builder.build().size();
+            assert!(rlc > n, "n = {}: {} constraints with RLC, {} without", n, rlc, n);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! This module extends GadgetBuilder with methods for asserting equalities between products and
+//! between ratios, using cross-multiplication rather than division.
+
+use crate::expression::Expression;
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+
+/// How `assert_ratios_equal` should treat its denominators.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DenominatorPolicy {
+    /// Assert that each denominator is nonzero, unless it is already known to be nonzero. Each
+    /// such assertion costs one constraint.
+    AssertNonzero,
+    /// Trust that each denominator is nonzero, because it is a nonzero constant or was previously
+    /// asserted or marked to be nonzero (see `GadgetBuilder::mark_nonzero`). Panics otherwise.
+    KnownNonzero,
+    /// Place no restriction on the denominators. If a denominator is zero, the cross-multiplied
+    /// equation no longer implies equal ratios; in particular, if both are zero, it holds for any
+    /// numerators.
+    AllowZero,
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Assert that `a * b = c * d`. This costs two constraints, or one if either product involves
+    /// a constant.
+    pub fn assert_products_equal(
+        &mut self, a: &Expression<F>, b: &Expression<F>, c: &Expression<F>, d: &Expression<F>,
+    ) {
+        logged!(self, "assert_products_equal", [a, b, c, d], {
+            // product() requires no constraints when either factor is constant, so we compute the
+            // cheaper product and assert the other one against it.
+            if a.as_constant().is_some() || b.as_constant().is_some() {
+                let ab = self.product(a, b);
+                self.assert_product(c, d, &ab);
+            } else {
+                let cd = self.product(c, d);
+                self.assert_product(a, b, &cd);
+            }
+        })
+    }
+
+    /// Assert that `a / b = c / d`, by asserting that `a * d = c * b`. This avoids computing
+    /// either quotient, so it is cheaper than comparing the results of `quotient`. See
+    /// `DenominatorPolicy` for how zero denominators are handled.
+    pub fn assert_ratios_equal(
+        &mut self,
+        a: &Expression<F>,
+        b: &Expression<F>,
+        c: &Expression<F>,
+        d: &Expression<F>,
+        denominators_nonzero: DenominatorPolicy,
+    ) {
+        logged!(self, "assert_ratios_equal",
+                [a, b, c, d, format!("{:?}", denominators_nonzero)], {
+            match denominators_nonzero {
+                DenominatorPolicy::AssertNonzero => {
+                    for denominator in &[b, d] {
+                        if !self.is_known_nonzero(denominator) {
+                            self.assert_nonzero(denominator);
+                        }
+                    }
+                }
+                DenominatorPolicy::KnownNonzero => {
+                    for denominator in &[b, d] {
+                        assert!(self.is_known_nonzero(denominator),
+                                "Denominator {} is not known to be nonzero", denominator);
+                    }
+                }
+                DenominatorPolicy::AllowZero => {}
+            }
+            self.assert_products_equal(a, d, c, b);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::Expression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::ratio::DenominatorPolicy;
+    use crate::fields::small::F257;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+
+    fn values(wires: [Wire; 4], elements: [u8; 4]) -> WireValues<F257> {
+        let mut values = WireValues::new();
+        for (&wire, &value) in wires.iter().zip(elements.iter()) {
+            values.set(wire, Element::from(value));
+        }
+        values
+    }
+
+    #[test]
+    fn assert_products_equal() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        builder.assert_products_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::from(c), &Expression::from(d));
+        let gadget = builder.build();
+        assert_eq!(2, gadget.size());
+
+        let wires = [a, b, c, d];
+        assert!(gadget.execute(&mut values(wires, [2, 6, 3, 4])));
+        assert!(!gadget.execute(&mut values(wires, [2, 6, 3, 5])));
+    }
+
+    #[test]
+    fn assert_products_equal_constant() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, c, d) = (builder.wire(), builder.wire(), builder.wire());
+        builder.assert_products_equal(
+            &Expression::from(a), &Expression::from(6u8), &Expression::from(c),
+            &Expression::from(d));
+        let gadget = builder.build();
+        assert_eq!(1, gadget.size());
+
+        assert!(gadget.execute(&mut values!(a => 2u8.into(), c => 3u8.into(), d => 4u8.into())));
+        assert!(!gadget.execute(&mut values!(a => 2u8.into(), c => 3u8.into(), d => 5u8.into())));
+    }
+
+    #[test]
+    fn assert_ratios_equal_assert_nonzero() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        builder.assert_ratios_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::from(c), &Expression::from(d),
+            DenominatorPolicy::AssertNonzero);
+        let gadget = builder.build();
+
+        let wires = [a, b, c, d];
+        assert_eq!(4, gadget.size());
+        assert!(gadget.execute(&mut values(wires, [3, 4, 6, 8])));
+        assert!(!gadget.execute(&mut values(wires, [3, 4, 5, 8])));
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_ratios_equal_assert_nonzero_zero_denominators() {
+        // The nonzero assertions' generators panic, since zero has no inverse.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        builder.assert_ratios_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::from(c), &Expression::from(d),
+            DenominatorPolicy::AssertNonzero);
+        let gadget = builder.build();
+
+        let wires = [a, b, c, d];
+        gadget.execute(&mut values(wires, [3, 0, 6, 0]));
+    }
+
+    #[test]
+    fn assert_ratios_equal_known_nonzero() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        builder.mark_nonzero(&Expression::from(b));
+        builder.mark_nonzero(&-Expression::from(d));
+        builder.assert_ratios_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::from(c), &Expression::from(d),
+            DenominatorPolicy::KnownNonzero);
+        let gadget = builder.build();
+
+        let wires = [a, b, c, d];
+        assert_eq!(2, gadget.size());
+        assert!(gadget.execute(&mut values(wires, [3, 4, 6, 8])));
+        assert!(!gadget.execute(&mut values(wires, [3, 4, 5, 8])));
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_ratios_equal_unknown_nonzero() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b) = (builder.wire(), builder.wire());
+        builder.assert_ratios_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::one(), &Expression::from(2u8),
+            DenominatorPolicy::KnownNonzero);
+    }
+
+    #[test]
+    fn assert_ratios_equal_allow_zero() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+        builder.assert_ratios_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::from(c), &Expression::from(d),
+            DenominatorPolicy::AllowZero);
+        let gadget = builder.build();
+
+        let wires = [a, b, c, d];
+        assert_eq!(2, gadget.size());
+        assert!(gadget.execute(&mut values(wires, [3, 4, 6, 8])));
+        assert!(!gadget.execute(&mut values(wires, [3, 4, 5, 8])));
+        // With both denominators zero, any numerators are accepted.
+        assert!(gadget.execute(&mut values(wires, [3, 0, 5, 0])));
+    }
+
+    #[test]
+    fn assert_ratios_equal_reuses_nonzero_assertions() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c) = (builder.wire(), builder.wire(), builder.wire());
+        builder.assert_nonzero(&Expression::from(b));
+        builder.assert_ratios_equal(
+            &Expression::from(a), &Expression::from(b), &Expression::from(c),
+            &Expression::from(2u8), DenominatorPolicy::AssertNonzero);
+        let gadget = builder.build();
+        // One constraint for the earlier nonzero assertion, and one for the cross-multiplication,
+        // since the second denominator is a nonzero constant.
+        assert_eq!(2, gadget.size());
+        assert!(gadget.execute(&mut values!(a => 3u8.into(), b => 2u8.into(), c => 3u8.into())));
+    }
+}
@@ -1,7 +1,11 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-use crate::{Element, Expression, Field, GadgetBuilder, MdsMatrix, MonomialPermutation, MultiPermutation, Permutation};
+use crate::{Element, Expression, Field, GadgetBuilder, MdsMatrix, MonomialPermutation,
+            MultiPermutation, Permutation};
+#[cfg(feature = "rand")]
+use crate::Sponge;
+use crate::constants::derive_round_constants;
 
 const DEFAULT_SECURITY_BITS: usize = 128;
 const SECURITY_MARGIN: usize = 2;
@@ -17,9 +21,54 @@ pub struct Rescue<F: Field> {
     num_rounds: usize,
     /// The MDS matrix to apply after each permutation layer.
     mds_matrix: MdsMatrix<F>,
+    /// The constants to add after each MDS layer, two sets per round.
+    round_constants: Vec<Vec<Element<F>>>,
 }
 
 impl<F: Field> Rescue<F> {
+    /// The size of the permutation, in field elements.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The degree of the permutation monomial.
+    pub fn alpha(&self) -> &Element<F> {
+        &self.alpha
+    }
+
+    /// The number of rounds.
+    pub fn num_rounds(&self) -> usize {
+        self.num_rounds
+    }
+
+    /// The constants added after each MDS layer, two sets per round.
+    pub fn round_constants(&self) -> &[Vec<Element<F>>] {
+        &self.round_constants
+    }
+
+    /// Applies the permutation to the given elements directly. Each round applies `x^alpha`, an MDS
+    /// layer and constants, then the inverse S-box, another MDS layer and constants. This backs
+    /// `permute_evaluate`.
+    pub fn permute_elements(&self, inputs: &[Element<F>]) -> Vec<Element<F>> {
+        let monomial = MonomialPermutation::new(self.alpha.clone());
+        let mut current = inputs.to_vec();
+        for round in 0..self.num_rounds {
+            current = current.iter().map(|x| monomial.permute_evaluate(x)).collect();
+            current = &self.mds_matrix * current.as_slice();
+            Self::add_element_constants(&mut current, &self.round_constants[2 * round]);
+            current = current.iter().map(|x| monomial.inverse_evaluate(x)).collect();
+            current = &self.mds_matrix * current.as_slice();
+            Self::add_element_constants(&mut current, &self.round_constants[2 * round + 1]);
+        }
+        current
+    }
+
+    fn add_element_constants(state: &mut [Element<F>], constants: &[Element<F>]) {
+        for (x, constant) in state.iter_mut().zip(constants) {
+            *x += constant;
+        }
+    }
+
     fn pi_1(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>) -> Expression<F> {
         MonomialPermutation::new(self.alpha.clone()).permute(builder, x)
     }
@@ -27,6 +76,12 @@ impl<F: Field> Rescue<F> {
     fn pi_2(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>) -> Expression<F> {
         MonomialPermutation::new(self.alpha.clone()).inverse(builder, x)
     }
+
+    fn add_constants(&self, state: &mut [Expression<F>], step: usize) {
+        for (exp, constant) in state.iter_mut().zip(self.round_constants[step].iter()) {
+            *exp += Expression::from(constant);
+        }
+    }
 }
 
 impl<F: Field> MultiPermutation<F> for Rescue<F> {
@@ -37,15 +92,21 @@ impl<F: Field> MultiPermutation<F> for Rescue<F> {
     fn permute(&self, builder: &mut GadgetBuilder<F>, inputs: &[Expression<F>])
                -> Vec<Expression<F>> {
         let mut current = inputs.to_vec();
-        for _round in 0..self.num_rounds {
+        for round in 0..self.num_rounds {
             current = current.iter().map(|exp| self.pi_1(builder, exp)).collect();
             current = &self.mds_matrix * current.as_slice();
+            self.add_constants(&mut current, 2 * round);
             current = current.iter().map(|exp| self.pi_2(builder, exp)).collect();
             current = &self.mds_matrix * current.as_slice();
+            self.add_constants(&mut current, 2 * round + 1);
         }
         current
     }
 
+    fn permute_evaluate(&self, inputs: &[Element<F>]) -> Vec<Element<F>> {
+        self.permute_elements(inputs)
+    }
+
     fn inverse(&self, _builder: &mut GadgetBuilder<F>, _outputs: &[Expression<F>])
                -> Vec<Expression<F>> {
         unimplemented!("TODO: implement inverse Rescue")
@@ -64,6 +125,8 @@ pub struct RescueBuilder<F: Field> {
     security_bits: Option<usize>,
     /// The MDS matrix to apply after each permutation layer.
     mds_matrix: Option<MdsMatrix<F>>,
+    /// The constants to add after each MDS layer, two sets per round.
+    round_constants: Option<Vec<Vec<Element<F>>>>,
 }
 
 impl<F: Field> RescueBuilder<F> {
@@ -75,6 +138,7 @@ impl<F: Field> RescueBuilder<F> {
             num_rounds: None,
             security_bits: None,
             mds_matrix: None,
+            round_constants: None,
         }
     }
 
@@ -98,12 +162,20 @@ impl<F: Field> RescueBuilder<F> {
         self
     }
 
+    /// Sets the round constants, which must contain two vectors of `width` constants per round.
+    /// The first set of each round is added after the first MDS layer, and the second set after
+    /// the second.
+    pub fn round_constants(&mut self, round_constants: Vec<Vec<Element<F>>>) -> &mut Self {
+        self.round_constants = Some(round_constants);
+        self
+    }
+
     pub fn build(&self) -> Rescue<F> {
         let width = self.width;
         let alpha = self.alpha.clone().unwrap_or_else(Self::smallest_alpha);
 
-        // TODO: Generate a default MDS matrix instead of making the caller supply one.
-        let mds_matrix = self.mds_matrix.clone().expect("MDS matrix required for now");
+        // If an MDS matrix is not specified, use a Cauchy matrix.
+        let mds_matrix = self.mds_matrix.clone().unwrap_or_else(|| MdsMatrix::cauchy(width));
 
         if self.num_rounds.is_some() && self.security_bits.is_some() {
             panic!("Cannot specify both the number of rounds and the desired security level");
@@ -113,7 +185,17 @@ impl<F: Field> RescueBuilder<F> {
                 self.security_bits.unwrap_or(DEFAULT_SECURITY_BITS),
                 width));
 
-        Rescue { width, alpha, num_rounds, mds_matrix }
+        // If round constants are not specified, derive them as MiMC does, with a Rescue tag. This
+        // requires the rand feature.
+        let round_constants = self.round_constants.clone()
+            .unwrap_or_else(|| derive_round_constants("Rescue", 2 * num_rounds, width));
+        assert_eq!(round_constants.len(), 2 * num_rounds,
+                   "Expected two sets of constants per round");
+        for constants in round_constants.iter() {
+            assert_eq!(constants.len(), width, "Expected one round constant per state element");
+        }
+
+        Rescue { width, alpha, num_rounds, mds_matrix, round_constants }
     }
 
     /// Find the smallest prime `a` such that `x^a` is a permutation in `F`, or equivalently,
@@ -139,15 +221,25 @@ impl<F: Field> RescueBuilder<F> {
     }
 }
 
+/// A hash function built from a Rescue instance with default parameters, used as a sponge with the
+/// given rate and a capacity of one element, so the permutation has a width of `rate + 1`. The
+/// exponent, MDS matrix and number of rounds are chosen by `RescueBuilder` for the default
+/// security level, and can be inspected through `Sponge::permutation`.
+#[cfg(feature = "rand")]
+pub fn rescue_hash<F: Field>(rate: usize) -> Sponge<F, Rescue<F>> {
+    assert!(rate > 0, "Rate must be non-zero");
+    Sponge::new(RescueBuilder::new(rate + 1).build(), rate, 1)
+}
+
 fn integer_division_ceil(n: usize, m: usize) -> usize {
-    (n + m - 1) / m
+    n.div_ceil(m)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "rand"))]
 mod tests {
-    use crate::MdsMatrix;
-    use crate::rescue::RescueBuilder;
-    use crate::test_util::F11;
+    use crate::{Bn128, Element, Expression, GadgetBuilder, HashFunction, MdsMatrix};
+    use crate::rescue::{RescueBuilder, rescue_hash};
+    use crate::fields::small::F11;
 
     #[test]
     fn rescue_permutation_f11() {
@@ -162,4 +254,25 @@ mod tests {
 
         // TODO: Verify execution.
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rescue_hash_bn128() {
+        let hash = rescue_hash::<Bn128>(2);
+        assert_eq!(2, hash.bitrate());
+        assert_eq!(1, hash.capacity());
+        assert_eq!(3, hash.permutation().width());
+        assert_eq!(&Element::from(5u8), hash.permutation().alpha());
+        assert_eq!(22, hash.permutation().num_rounds());
+
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.wire(), builder.wire());
+        let digest = hash.hash(&mut builder, &[Expression::from(x), Expression::from(y)]);
+        let gadget = builder.build();
+
+        let mut values = values!(x => 1u8.into(), y => 2u8.into());
+        assert!(gadget.execute(&mut values));
+        let (one, two) = (Element::from(1u8), Element::from(2u8));
+        assert_eq!(hash.hash_evaluate(&[one.clone(), two.clone()]), digest.evaluate(&values));
+        assert!(hash.hash_evaluate(&[one.clone(), two.clone()]) != hash.hash_evaluate(&[two, one]));
+    }
+}
@@ -0,0 +1,147 @@
+//! This module extends GadgetBuilder with lookups into constant byte tables, such as the S-boxes
+//! of AES-style ciphers.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::{BinaryExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Returns `table[input]`, where `input` is an 8-bit binary expression and `table` is a
+    /// constant 256-entry table, such as the AES S-box.
+    ///
+    /// The table is viewed as a 16 x 16 grid, with the high nibble of the input selecting a row
+    /// and the low nibble selecting a column. Each nibble is converted to a hinted one-hot vector
+    /// of length 16, costing 18 constraints. Since the table is constant, the entries of the
+    /// selected row are linear in the row selector, so selecting a row is free, and selecting a
+    /// column from it costs one product per column. Splitting the result into booleans costs 9
+    /// more constraints, for a total of 61.
+    pub fn sbox_lookup_8bit(
+        &mut self, input: &BinaryExpression<F>, table: &[u8; 256],
+    ) -> BinaryExpression<F> {
+        logged!(self, "sbox_lookup_8bit", [input.join_allowing_overflow(), table.len()], {
+            assert_eq!(input.len(), 8, "Expected an 8-bit input");
+            let low = BinaryExpression { bits: input.bits[..4].to_vec() };
+            let high = BinaryExpression { bits: input.bits[4..].to_vec() };
+            let rows = self.one_hot_from_index(&high.join(), 16);
+            let columns = self.one_hot_from_index(&low.join(), 16);
+
+            let terms: Vec<Expression<F>> = columns.iter().enumerate()
+                .map(|(j, column)| {
+                    let entries: Vec<Expression<F>> = rows.iter().enumerate()
+                        .map(|(i, row)| row.expression() * Element::from(table[i * 16 + j]))
+                        .collect();
+                    let entry = Expression::sum_of_expressions(&entries);
+                    self.product(column.expression(), &entry)
+                })
+                .collect();
+            self.split_bounded(&Expression::sum_of_expressions(&terms), 8)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::expression::BinaryExpression;
+    use crate::field::{Bn128, Element};
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+
+    const AES_SBOX: [u8; 256] = [
+        0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5,
+        0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+        0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0,
+        0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+        0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc,
+        0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+        0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a,
+        0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+        0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0,
+        0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+        0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b,
+        0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+        0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85,
+        0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+        0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5,
+        0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+        0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17,
+        0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+        0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88,
+        0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+        0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c,
+        0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+        0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9,
+        0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+        0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6,
+        0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+        0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e,
+        0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+        0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94,
+        0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+        0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68,
+        0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+    ];
+
+    fn lookup(table: &[u8; 256], input: u8) -> u8 {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let input_wire = builder.binary_wire(8);
+        let output = builder.sbox_lookup_8bit(&BinaryExpression::from(&input_wire), table);
+        let gadget = builder.build();
+        // 8 booleanity constraints for the input, plus the lookup itself.
+        assert_eq!(8 + 61, gadget.size());
+
+        let mut values = binary_unsigned_values!(&input_wire => BigUint::from(input));
+        assert!(gadget.execute(&mut values));
+        output.to_le_bytes_value(&values)[0]
+    }
+
+    #[test]
+    fn aes_sbox() {
+        assert_eq!(0x63, lookup(&AES_SBOX, 0x00));
+        assert_eq!(0x7c, lookup(&AES_SBOX, 0x01));
+        assert_eq!(0xed, lookup(&AES_SBOX, 0x53));
+        assert_eq!(0x8a, lookup(&AES_SBOX, 0xcf));
+        assert_eq!(0x16, lookup(&AES_SBOX, 0xff));
+    }
+
+    #[test]
+    fn identity_table() {
+        let mut identity = [0u8; 256];
+        for (i, entry) in identity.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        for &input in &[0u8, 1, 0x5a, 0xa5, 0xf0, 0x0f, 0xff] {
+            assert_eq!(input, lookup(&identity, input));
+        }
+    }
+
+    #[test]
+    fn forged_row_selector() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let input_wire = builder.binary_wire(8);
+        builder.sbox_lookup_8bit(&BinaryExpression::from(&input_wire), &AES_SBOX);
+        let gadget = builder.build();
+
+        let mut values = binary_unsigned_values!(&input_wire => BigUint::from(0x53u8));
+        assert!(gadget.execute(&mut values));
+
+        // The row selector consists of the 16 wires following the input. Moving its set bit from
+        // row 5 to row 6 must violate some constraint.
+        let row = |i: u32| Wire { index: 9 + i };
+        assert_eq!(&Element::one(), values.get(row(5)));
+        let mut forged = WireValues::new();
+        for (&wire, value) in values.as_map() {
+            if wire != Wire::ONE && wire != row(5) && wire != row(6) {
+                forged.set(wire, value.clone());
+            }
+        }
+        forged.set(row(5), Element::zero());
+        forged.set(row(6), Element::one());
+        assert!(!gadget.constraints.iter().all(|c| c.evaluate(&forged)));
+    }
+}
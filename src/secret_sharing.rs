@@ -0,0 +1,142 @@
+//! This module extends GadgetBuilder with methods for verifying Shamir secret sharings.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::Expression;
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Assert that `secret` is the constant term of the polynomial interpolating the given shares,
+    /// i.e. that the `t = shares.len()` shares reconstruct `secret` in a `t`-of-`n` sharing.
+    ///
+    /// Since the x-coordinates are constants, the Lagrange coefficients at zero are computed
+    /// out-of-circuit, and the check is a single linear constraint.
+    pub fn assert_shamir_consistent(
+        &mut self, shares: &[(Element<F>, Expression<F>)], secret: &Expression<F>,
+    ) {
+        logged!(self, "assert_shamir_consistent", [shares.len(), secret], {
+            assert!(!shares.is_empty(), "At least one share is required");
+            let reconstructed = interpolate_at(shares, &Element::zero());
+            self.assert_equal(&reconstructed, secret);
+        })
+    }
+
+    /// Assert that all of the given shares lie on a common polynomial of at most the given degree.
+    /// The polynomial is defined by the first `degree + 1` shares, and each additional share is
+    /// checked against it.
+    ///
+    /// As with `assert_shamir_consistent`, each check is linear since the x-coordinates are
+    /// constants.
+    pub fn assert_shares_on_common_polynomial(
+        &mut self, shares: &[(Element<F>, Expression<F>)], degree: usize,
+    ) {
+        logged!(self, "assert_shares_on_common_polynomial", [shares.len(), degree], {
+            assert!(shares.len() > degree, "At least degree + 1 shares are required");
+            let (basis, extra) = shares.split_at(degree + 1);
+            for (x, y) in extra {
+                let expected = interpolate_at(basis, x);
+                self.assert_equal(&expected, y);
+            }
+        })
+    }
+}
+
+/// Evaluate, at the constant `at`, the polynomial interpolating the given points. Since the
+/// x-coordinates and the evaluation point are all constants, the result is a linear combination of
+/// the y-coordinates.
+fn interpolate_at<F: Field>(
+    points: &[(Element<F>, Expression<F>)], at: &Element<F>,
+) -> Expression<F> {
+    let terms: Vec<Expression<F>> = points.iter().enumerate().map(|(i, (x_i, y_i))| {
+        let mut numerator = Element::one();
+        let mut denominator = Element::one();
+        for (j, (x_j, _y_j)) in points.iter().enumerate() {
+            if i != j {
+                assert!(x_i != x_j, "Share x-coordinates must be distinct");
+                numerator *= at - x_j;
+                denominator *= x_i - x_j;
+            }
+        }
+        y_i * (numerator / denominator)
+    }).collect();
+    Expression::sum_of_expressions(&terms)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use itertools::Itertools;
+
+    use crate::expression::Expression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
+
+    /// Shares of f(x) = 42 + 5x + 11x^2 at x = 1, ..., 5.
+    fn valid_shares() -> Vec<Element<F257>> {
+        (1u8..=5).map(|x| {
+            let x = Element::from(x);
+            Element::from(42u8) + &x * 5u128 + &x * &x * 11u128
+        }).collect()
+    }
+
+    fn values(share_wires: &[Wire], shares: &[Element<F257>], secret: Wire, secret_value: u8)
+              -> WireValues<F257> {
+        let mut values = values!(secret => secret_value.into());
+        for (&wire, share) in share_wires.iter().zip(shares.iter()) {
+            values.set(wire, share.clone());
+        }
+        values
+    }
+
+    #[test]
+    fn shamir_consistent() {
+        // Reconstruct the secret of a 3-of-5 sharing from the first three shares only.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let share_wires = builder.wires(5);
+        let secret = builder.wire();
+        let shares = share_wires.iter().enumerate()
+            .map(|(i, &w)| (Element::from(i + 1), Expression::from(w)))
+            .collect_vec();
+        builder.assert_shamir_consistent(&shares[..3], &Expression::from(secret));
+        let gadget = builder.build();
+
+        let valid = valid_shares();
+        assert!(gadget.execute(&mut values(&share_wires, &valid, secret, 42)));
+        assert!(!gadget.execute(&mut values(&share_wires, &valid, secret, 43)));
+
+        // The last two shares aren't checked.
+        let mut tampered = valid_shares();
+        tampered[4] += Element::one();
+        assert!(gadget.execute(&mut values(&share_wires, &tampered, secret, 42)));
+    }
+
+    #[test]
+    fn shares_on_common_polynomial() {
+        // Check that all five shares of a 3-of-5 sharing are consistent.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let share_wires = builder.wires(5);
+        let secret = builder.wire();
+        let shares = share_wires.iter().enumerate()
+            .map(|(i, &w)| (Element::from(i + 1), Expression::from(w)))
+            .collect_vec();
+        builder.assert_shares_on_common_polynomial(&shares, 2);
+        let gadget = builder.build();
+
+        let valid = valid_shares();
+        assert!(gadget.execute(&mut values(&share_wires, &valid, secret, 42)));
+
+        let mut tampered = valid_shares();
+        tampered[4] += Element::one();
+        assert!(!gadget.execute(&mut values(&share_wires, &tampered, secret, 42)));
+
+        // The secret isn't involved.
+        assert!(gadget.execute(&mut values(&share_wires, &valid, secret, 43)));
+    }
+}
@@ -0,0 +1,156 @@
+//! This module extends GadgetBuilder with methods for selecting one of several expressions based
+//! on boolean conditions, as in an if/else-if chain.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::expression::{BooleanExpression, Expression};
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+
+/// How `select_chain` should combine its cases.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SelectionMode {
+    /// The first case whose condition holds is selected, exactly as with nested calls to
+    /// `selection`. This costs one product per case, except that the last case is free if both
+    /// its value and the default are constant.
+    Priority,
+    /// Assert that at most one condition holds, with `assert_at_most_one_true`, and select the
+    /// matching case. This costs one constraint for the assertion, plus one product per case whose
+    /// value or the default is non-constant.
+    AssertExclusive,
+    /// Like `AssertExclusive`, but trust that at most one condition holds, because the conditions
+    /// were constrained elsewhere (e.g. they came from `one_hot_from_index`). If several hold, the
+    /// result is an arbitrary linear combination of their values.
+    KnownExclusive,
+}
+
+impl<F: Field> GadgetBuilder<F> {
+    /// Returns the value of the first case whose condition holds, or `default` if none do. In the
+    /// exclusive modes, the caller promises or the gadget asserts that at most one condition holds,
+    /// which allows a flat sum with no nesting: `default + sum(c_i * (x_i - default))`. Each
+    /// product is then of a condition and a fixed expression, so it is free whenever `x_i` and
+    /// `default` are constant.
+    pub fn select_chain(
+        &mut self,
+        cases: &[(BooleanExpression<F>, Expression<F>)],
+        default: &Expression<F>,
+        mode: SelectionMode,
+    ) -> Expression<F> {
+        logged!(self, "select_chain", [cases.len(), default, format!("{:?}", mode)], {
+            match mode {
+                SelectionMode::Priority => cases.iter().rev()
+                    .fold(default.clone(), |rest, (c, x)| self.selection(c, x, &rest)),
+                SelectionMode::AssertExclusive | SelectionMode::KnownExclusive => {
+                    if mode == SelectionMode::AssertExclusive {
+                        let conditions: Vec<BooleanExpression<F>> =
+                            cases.iter().map(|(c, _x)| c.clone()).collect();
+                        self.assert_at_most_one_true(&conditions);
+                    }
+                    let mut terms = vec![default.clone()];
+                    for (c, x) in cases {
+                        terms.push(self.product(c.expression(), &(x - default)));
+                    }
+                    Expression::sum_of_expressions(&terms)
+                }
+            }
+        })
+    }
+
+    /// Assert that at most one of the given booleans is true, by asserting that their sum is
+    /// boolean. This costs a single constraint, since the sum of fewer than `|F|` booleans can't
+    /// wrap around.
+    pub fn assert_at_most_one_true(&mut self, bits: &[BooleanExpression<F>]) {
+        logged!(self, "assert_at_most_one_true", [bits.len()], {
+            let sum = Expression::sum_of_expressions(
+                &bits.iter().map(|bit| bit.expression().clone()).collect::<Vec<_>>());
+            self.assert_boolean(&sum);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::{BooleanExpression, Expression};
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::selection::SelectionMode;
+    use crate::fields::small::F257;
+    use crate::wire::BooleanWire;
+    use crate::wire_values::WireValues;
+
+    /// Builds an 8-case chain selecting the constant `10 * (i + 1)` for case `i`, with a default
+    /// of 1. Returns the gadget size, and the result for the given set conditions if the gadget is
+    /// satisfied.
+    fn constant_chain(mode: SelectionMode, set: &[usize]) -> (usize, Option<Element<F257>>) {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires: Vec<BooleanWire> = (0..8).map(|_i| builder.boolean_wire()).collect();
+        let cases: Vec<(BooleanExpression<F257>, Expression<F257>)> = wires.iter().enumerate()
+            .map(|(i, w)| (BooleanExpression::from(w), Expression::from(10 * (i as u8 + 1))))
+            .collect();
+        let result = builder.select_chain(&cases, &Expression::one(), mode);
+        let gadget = builder.build();
+
+        let mut values = WireValues::new();
+        for (i, &w) in wires.iter().enumerate() {
+            values.set_boolean(w, set.contains(&i));
+        }
+        let satisfied = gadget.execute(&mut values);
+        (gadget.size(), if satisfied { Some(result.evaluate(&values)) } else { None })
+    }
+
+    #[test]
+    fn priority_first_wins() {
+        assert_eq!(Some(20u8.into()), constant_chain(SelectionMode::Priority, &[1, 4]).1);
+        assert_eq!(Some(50u8.into()), constant_chain(SelectionMode::Priority, &[4]).1);
+        assert_eq!(Some(1u8.into()), constant_chain(SelectionMode::Priority, &[]).1);
+    }
+
+    #[test]
+    fn priority_matches_nested_selection() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (c1, c2) = (builder.boolean_wire(), builder.boolean_wire());
+        let (x1, x2, d) = (builder.wire(), builder.wire(), builder.wire());
+        let (c1_exp, c2_exp) = (BooleanExpression::from(c1), BooleanExpression::from(c2));
+        let (x1_exp, x2_exp) = (Expression::from(x1), Expression::from(x2));
+        let d_exp = Expression::from(d);
+        let chain = builder.select_chain(
+            &[(c1_exp.clone(), x1_exp.clone()), (c2_exp.clone(), x2_exp.clone())], &d_exp,
+            SelectionMode::Priority);
+        let inner = builder.selection(&c2_exp, &x2_exp, &d_exp);
+        let nested = builder.selection(&c1_exp, &x1_exp, &inner);
+        let gadget = builder.build();
+
+        for &(c1_value, c2_value) in &[(false, false), (false, true), (true, false), (true, true)] {
+            let mut values = values!(x1 => 3u8.into(), x2 => 5u8.into(), d => 7u8.into());
+            values.set_boolean(c1, c1_value);
+            values.set_boolean(c2, c2_value);
+            assert!(gadget.execute(&mut values));
+            assert_eq!(nested.evaluate(&values), chain.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn exclusive() {
+        for &mode in &[SelectionMode::AssertExclusive, SelectionMode::KnownExclusive] {
+            assert_eq!(Some(50u8.into()), constant_chain(mode, &[4]).1);
+            assert_eq!(Some(80u8.into()), constant_chain(mode, &[7]).1);
+            assert_eq!(Some(1u8.into()), constant_chain(mode, &[]).1);
+        }
+    }
+
+    #[test]
+    fn exclusive_violation() {
+        assert_eq!(None, constant_chain(SelectionMode::AssertExclusive, &[1, 4]).1);
+        // Without the assertion, the result is meaningless, but the gadget is satisfied.
+        assert!(constant_chain(SelectionMode::KnownExclusive, &[1, 4]).1.is_some());
+    }
+
+    #[test]
+    fn constraint_counts() {
+        // 8 booleanity constraints for the conditions, plus the selection itself.
+        assert_eq!(8 + 7, constant_chain(SelectionMode::Priority, &[]).0);
+        assert_eq!(8 + 1, constant_chain(SelectionMode::AssertExclusive, &[]).0);
+        assert_eq!(8, constant_chain(SelectionMode::KnownExclusive, &[]).0);
+    }
+}
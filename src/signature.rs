@@ -1,7 +1,26 @@
 use std::marker::PhantomData;
 
-use crate::{CompressionFunction, Expression, Field, GadgetBuilder, GroupExpression, CyclicGroup};
+use num::BigUint;
 
+use crate::{CompressionFunction, Element, Expression, Field, GadgetBuilder, GroupExpression,
+            CyclicGroup, PrimeOrderGroup, WireValues};
+use crate::coprimality::modular_inverse;
+use crate::expression::BinaryExpression;
+use crate::group::mul_two_scalars;
+
+/// A signature scheme whose verification can be expressed in constraints over `F`.
+///
+/// The group `C` must be a `CyclicGroup<F>` over the same field as the builder, so its elements
+/// are represented by expressions over `F`. Mixing fields is rejected at compile time. For
+/// example, JubJub is only defined over `Bls12_381`, so it can't be used with a `Bn128` builder:
+///
+/// ```compile_fail
+/// use r1cs::{Bn128, EdwardsGroup, JubJub, MiMCBlockCipher, MiyaguchiPreneel, Schnorr};
+///
+/// type Compress = MiyaguchiPreneel<Bn128, MiMCBlockCipher<Bn128>>;
+/// type Mismatched = Schnorr<Bn128, EdwardsGroup<Bn128, JubJub>, Compress>;
+/// let _scheme: Option<Mismatched> = None;
+/// ```
 pub trait SignatureScheme<F: Field, C: CyclicGroup<F>, CF: CompressionFunction<F>> {
     fn verify(
         builder: &mut GadgetBuilder<F>,
@@ -13,9 +32,9 @@ pub trait SignatureScheme<F: Field, C: CyclicGroup<F>, CF: CompressionFunction<F
 }
 
 pub struct Schnorr<F: Field, C: CyclicGroup<F>, CF: CompressionFunction<F>> {
-    phantom_f: PhantomData<*const F>,
-    phantom_c: PhantomData<*const C>,
-    phantom_cf: PhantomData<*const CF>,
+    phantom_f: PhantomData<fn() -> F>,
+    phantom_c: PhantomData<fn() -> C>,
+    phantom_cf: PhantomData<fn() -> CF>,
 }
 
 /// Struct to represent a Schnorr Signature.
@@ -55,62 +74,228 @@ impl<F: Field, C: CyclicGroup<F>, CF: CompressionFunction<F>> SignatureScheme<F,
         let gs_ye = C::add_expressions(builder, &gs, &ye);
 
         // TODO: verify that compressing the Edwards Curve point to the Y-coordinate is valid
-        let hash_check = compress.compress(builder, &gs_ye.compressed(), &message);
+        let hash_check = compress.compress(builder, gs_ye.compressed(), message);
         builder.assert_equal(&hash_check, &signature.e);
     }
 }
 
+/// ECDSA over a prime-order group which is embedded in the native field, such as JubJub over
+/// BLS12-381.
+///
+/// This is not ECDSA over a foreign curve such as secp256k1: group operations use native field
+/// arithmetic, and only the scalar arithmetic modulo the group order needs limb arithmetic. The
+/// x-coordinate of a point is taken to be its first component, which is `x` for affine curve
+/// points such as `EdwardsExpression`.
+pub struct Ecdsa<F: Field, C: PrimeOrderGroup<F>> {
+    phantom_f: PhantomData<fn() -> F>,
+    phantom_c: PhantomData<fn() -> C>,
+}
+
+impl<F: Field, C: PrimeOrderGroup<F>> Ecdsa<F, C> {
+    /// Generates constraints to verify that `(r, s)` is an ECDSA signature of `message_hash`,
+    /// given a public key, which must lie in the subgroup generated by the generator `G`.
+    ///
+    /// With `n` the order of `G`, this asserts that `r` and `s` lie in `[1, n)`, computes
+    /// `u1 = m / s` and `u2 = r / s` modulo `n`, where `m` is the message hash reduced modulo `n`,
+    /// and asserts that the x-coordinate of `u1 G + u2 Q`, reduced modulo `n`, equals `r`.
+    ///
+    /// A naive implementation that has not been optimized or audited.
+    pub fn verify(
+        builder: &mut GadgetBuilder<F>,
+        message_hash: &Expression<F>,
+        r: &Expression<F>,
+        s: &Expression<F>,
+        public_key: &C::GroupExpression,
+    ) {
+        let n = C::generator_order();
+        assert!(n < F::order(), "The group order must be smaller than the field");
+        let n_bits = n.bits() as usize;
+
+        let r_bits = Self::split_scalar(builder, r, &n);
+        let s_bits = Self::split_scalar(builder, s, &n);
+
+        // The prover supplies m mod n, u1 and u2, whose congruences are then checked.
+        let outputs = {
+            let (m, r, s, n) = (message_hash.clone(), r.clone(), s.clone(), n.clone());
+            let mut dependencies = m.dependencies();
+            dependencies.extend(r.dependencies());
+            dependencies.extend(s.dependencies());
+            builder.hint(dependencies, 3, move |values: &WireValues<F>| {
+                let m = m.evaluate(values).to_biguint() % &n;
+                let r = r.evaluate(values).to_biguint().clone();
+                // If s is not invertible, the range check of s will fail regardless.
+                let s_inverse = modular_inverse(s.evaluate(values).to_biguint(), &n)
+                    .unwrap_or_default();
+                let u1 = &m * &s_inverse % &n;
+                let u2 = &r * &s_inverse % &n;
+                vec![Element::from(m), Element::from(u1), Element::from(u2)]
+            })
+        };
+        let m_bits = builder.split(message_hash);
+        let m_reduced_bits = builder.split_bounded(&Expression::from(outputs.wire(0)), n_bits);
+        let u1_bits = builder.split_bounded(&Expression::from(outputs.wire(1)), n_bits);
+        let u2_bits = builder.split_bounded(&Expression::from(outputs.wire(2)), n_bits);
+        builder.assert_congruent_mod_constant(&m_bits, &m_reduced_bits, &n);
+        builder.assert_product_mod_constant(&u1_bits, &s_bits, &m_reduced_bits, &n);
+        builder.assert_product_mod_constant(&u2_bits, &s_bits, &r_bits, &n);
+
+        // Since G and Q have order n, u1 and u2 need not be reduced.
+        let generator = C::generator_expression();
+        let point = mul_two_scalars::<F, C>(builder, &generator, &u1_bits, public_key, &u2_bits);
+
+        // Since r < n, this asserts that r = x mod n. The identity has x = 0, so it is rejected.
+        let x_bits = builder.split(&point.to_components()[0]);
+        builder.assert_congruent_mod_constant(&x_bits, &r_bits, &n);
+    }
+
+    /// Asserts that `x` lies in `[1, n)`, returning its binary representation.
+    fn split_scalar(
+        builder: &mut GadgetBuilder<F>, x: &Expression<F>, n: &BigUint,
+    ) -> BinaryExpression<F> {
+        let n_bits = n.bits() as usize;
+        let bits = builder.split_bounded(x, n_bits);
+        builder.assert_lt_bounded(x, &Expression::from(Element::from(n.clone())), n_bits);
+        builder.assert_nonzero(x);
+        bits
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use crate::{CyclicGenerator, EdwardsExpression, Expression, GadgetBuilder, Group, WireValues, JubJub, JubJubPrimeSubgroup};
+    use crate::{Bls12_381, CyclicGroup, EdwardsExpression, EdwardsGroup, Expression, GadgetBuilder,
+                Group, JubJub, WireValues};
     use crate::CompressionFunction;
-    use crate::field::{Bls12_381, Element, Field};
-    use crate::signature::{Schnorr, SignatureExpression, SignatureScheme};
+    use crate::field::{Element, Field};
+    use crate::coprimality::modular_inverse;
+    use crate::signature::{Ecdsa, Schnorr, SignatureExpression, SignatureScheme};
 
-    #[test]
-    fn verify() {
-        // Generate signature
-        let generator = JubJub::generator_element();
+    type JubJubGroup = EdwardsGroup<Bls12_381, JubJub>;
 
-        let private_key = Element::from_str("4372820819045374670962167435360035096875258").unwrap();
+    /// Signs a message out-of-circuit, returning the public key and the signature.
+    fn sign(message: &Element<Bls12_381>)
+            -> (EdwardsExpression<Bls12_381, JubJub>, SignatureExpression<Bls12_381>) {
+        let generator = JubJubGroup::generator_element();
 
-        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let private_key = Element::from_str("4372820819045374670962167435360035096875258").unwrap();
 
-        let public_key
-            = JubJubPrimeSubgroup::mul_scalar_element(&generator, &private_key);
+        let public_key = JubJubGroup::mul_scalar_element(&generator, &private_key);
 
         let nonce = Element::from_str("5434290453746709621674353600312312").unwrap();
 
-        let r
-            = JubJubPrimeSubgroup::mul_scalar_element(&generator, &nonce);
+        let r = JubJubGroup::mul_scalar_element(&generator, &nonce);
 
-        let compress = TestCompress {};
+        let e = TestCompress.compress_evaluate(r.compressed_element(), message);
 
-        let message = Element::from_str("12345").unwrap();
-
-        let e = compress.compress_evaluate(&r.compressed_element(), &message);
-
-        let s = &nonce - &private_key * &e;
+        // Scalars are integers modulo the order of the subgroup, not elements of the base field,
+        // so s = nonce - private_key * e is computed modulo the subgroup order.
+        let order = JubJub::subgroup_order();
+        let x_e = private_key.to_biguint() * e.to_biguint() % &order;
+        let s = Element::from((nonce.to_biguint() + &order - x_e) % &order);
 
         let signature = SignatureExpression { s: Expression::from(s), e: Expression::from(e) };
+        (EdwardsExpression::from(&public_key), signature)
+    }
 
+    /// Verifies a signature in-circuit, returning whether the constraints were satisfied.
+    fn verify_gadget(
+        public_key: &EdwardsExpression<Bls12_381, JubJub>,
+        signature: &SignatureExpression<Bls12_381>,
+        message: &Element<Bls12_381>,
+    ) -> bool {
         let mut builder = GadgetBuilder::<Bls12_381>::new();
 
-        Schnorr::<Bls12_381, JubJubPrimeSubgroup, TestCompress>::verify(
+        Schnorr::<Bls12_381, JubJubGroup, TestCompress>::verify(
             &mut builder,
-            &signature,
+            signature,
             &Expression::from(message),
-            &EdwardsExpression::from(&public_key),
-            &compress,
+            public_key,
+            &TestCompress,
         );
 
         let gadget = builder.build();
         let mut values = WireValues::new();
-        gadget.execute(&mut values);
+        gadget.execute(&mut values)
+    }
+
+    #[test]
+    fn verify() {
+        let message = Element::from_str("12345").unwrap();
+        let (public_key, signature) = sign(&message);
+        assert!(verify_gadget(&public_key, &signature, &message));
+    }
+
+    #[test]
+    fn verify_wrong_message() {
+        let (public_key, signature) = sign(&Element::from_str("12345").unwrap());
+        let wrong_message = Element::from_str("54321").unwrap();
+        assert!(!verify_gadget(&public_key, &signature, &wrong_message));
+    }
+
+    /// Signs a message hash with ECDSA out-of-circuit, returning the public key and `(r, s)`.
+    fn sign_ecdsa(
+        message_hash: &Element<Bls12_381>,
+    ) -> (EdwardsExpression<Bls12_381, JubJub>, Element<Bls12_381>, Element<Bls12_381>) {
+        let generator = JubJubGroup::generator_element();
+        let order = JubJub::subgroup_order();
+
+        let private_key = Element::from_str("4372820819045374670962167435360035096875258").unwrap();
+        let public_key = JubJubGroup::mul_scalar_element(&generator, &private_key);
+
+        let nonce = Element::from_str("5434290453746709621674353600312312").unwrap();
+        let point = JubJubGroup::mul_scalar_element(&generator, &nonce);
+
+        // r = x(k G) mod n and s = (m + r d) / k mod n.
+        let r = point.x.to_biguint() % &order;
+        let m = message_hash.to_biguint() % &order;
+        let nonce_inverse = modular_inverse(nonce.to_biguint(), &order).unwrap();
+        let s = (m + &r * private_key.to_biguint()) * nonce_inverse % &order;
+        (EdwardsExpression::from(&public_key), Element::from(r), Element::from(s))
+    }
+
+    /// Verifies an ECDSA signature in-circuit, returning whether the constraints were satisfied.
+    fn verify_ecdsa_gadget(
+        public_key: &EdwardsExpression<Bls12_381, JubJub>,
+        r: &Element<Bls12_381>,
+        s: &Element<Bls12_381>,
+        message_hash: &Element<Bls12_381>,
+    ) -> bool {
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        Ecdsa::<Bls12_381, JubJubGroup>::verify(
+            &mut builder,
+            &Expression::from(message_hash),
+            &Expression::from(r),
+            &Expression::from(s),
+            public_key,
+        );
+        let gadget = builder.build();
+        gadget.execute(&mut WireValues::new())
+    }
 
-        //TODO: include test vectors
+    /// Flips the given bit of `x`.
+    fn flip_bit(x: &Element<Bls12_381>, bit: u64) -> Element<Bls12_381> {
+        let mut x = x.to_biguint().clone();
+        x.set_bit(bit, !x.bit(bit));
+        Element::from(x)
+    }
+
+    #[test]
+    fn verify_ecdsa() {
+        let message_hash = Element::from_str("12345").unwrap();
+        let (public_key, r, s) = sign_ecdsa(&message_hash);
+        assert!(verify_ecdsa_gadget(&public_key, &r, &s, &message_hash));
+
+        let wrong_message_hash = Element::from_str("54321").unwrap();
+        assert!(!verify_ecdsa_gadget(&public_key, &r, &s, &wrong_message_hash));
+    }
+
+    #[test]
+    fn verify_ecdsa_flipped_bits() {
+        let message_hash = Element::from_str("12345").unwrap();
+        let (public_key, r, s) = sign_ecdsa(&message_hash);
+        assert!(!verify_ecdsa_gadget(&public_key, &flip_bit(&r, 3), &s, &message_hash));
+        assert!(!verify_ecdsa_gadget(&public_key, &r, &flip_bit(&s, 3), &message_hash));
     }
 
     // A dummy compression function which returns 2x + y.
@@ -122,4 +307,4 @@ mod tests {
             x * 2 + y
         }
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
-//! This module extends GadgetBuilder with a method for sorting lists of field elements.
+//! This module extends GadgetBuilder with methods for sorting lists of field elements, and for
+//! verifying that lists are sorted.
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
@@ -8,76 +9,110 @@ use itertools::enumerate;
 use crate::expression::Expression;
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
-use crate::wire::Wire;
 use crate::wire_values::WireValues;
 
 impl<F: Field> GadgetBuilder<F> {
     /// Sorts field elements in ascending order.
     pub fn sort_ascending(&mut self, inputs: &[Expression<F>]) -> Vec<Expression<F>> {
-        let n = inputs.len();
-
-        let output_wires: Vec<Wire> = self.wires(n);
-        let outputs: Vec<Expression<F>> = output_wires.iter().map(Expression::from).collect();
-
-        // First we assert that the input and output lists are permutations of one another, i.e.,
-        // that they contain the same values.
-        self.assert_permutation(inputs, &outputs);
-
-        // Then, we assert the order of each adjacent pair of output values. Note that assert_le
-        // would internally split each input into its binary form. To avoid splitting intermediate
-        // items twice, we will explicitly split here, and call assert_le_binary instead.
-        // Also note that only the purportedly largest item (i.e. the last one) needs to be split
-        // canonically. If one of the other elements were to be split into their non-canonical
-        // binary encoding, that binary expression would be greater than the last element, rendering
-        // the instance unsatisfiable.
-        let mut outputs_binary = Vec::new();
-        for out in outputs.iter().take(n - 1) {
-            outputs_binary.push(self.split_allowing_ambiguity(out));
-        }
-        outputs_binary.push(self.split(&outputs[n - 1]));
-
-        for i in 0..(n - 1) {
-            let a = &outputs_binary[i];
-            let b = &outputs_binary[i + 1];
-            self.assert_le_binary(a, b);
-        }
-
-        let inputs = inputs.to_vec();
-        self.generator(
-            inputs.iter().flat_map(Expression::dependencies).collect(),
-            move |values: &mut WireValues<F>| {
-                // Evaluate all the inputs, sort that list of field elements, and output that.
-                let mut items: Vec<Element<F>> =
-                    inputs.iter().map(|exp| exp.evaluate(values)).collect();
-                items.sort();
-                for (i, item) in enumerate(items) {
-                    values.set(output_wires[i], item);
-                }
-            });
-
-        outputs
+        logged!(self, "sort_ascending", [inputs.len()], {
+            let n = inputs.len();
+
+            let (output_wires, outputs) = self.expression_wires(n);
+
+            // First we assert that the input and output lists are permutations of one another,
+            // i.e., that they contain the same values. Then we assert that the outputs are in
+            // order.
+            self.assert_permutation(inputs, &outputs);
+            self.assert_ascending(&outputs);
+
+            let inputs = inputs.to_vec();
+            self.generator_with_outputs(
+                inputs.iter().flat_map(Expression::dependencies).collect(),
+                output_wires.clone(),
+                move |values: &mut WireValues<F>| {
+                    // Evaluate all the inputs, sort that list of field elements, and output that.
+                    let mut items: Vec<Element<F>> =
+                        inputs.iter().map(|exp| exp.evaluate(values)).collect();
+                    items.sort();
+                    for (i, item) in enumerate(items) {
+                        values.set(output_wires[i], item);
+                    }
+                });
+
+            outputs
+        })
+    }
+
+    /// Assert that the given field elements are in ascending order, allowing duplicates.
+    pub fn assert_ascending(&mut self, items: &[Expression<F>]) {
+        logged!(self, "assert_ascending", [items.len()], {
+            let n = items.len();
+            if n < 2 {
+                return;
+            }
+
+            // Note that assert_le would internally split each input into its binary form. To avoid
+            // splitting intermediate items twice, we will explicitly split here, and call
+            // assert_le_binary instead.
+            // Also note that only the purportedly largest item (i.e. the last one) needs to be
+            // split canonically. If one of the other elements were to be split into their
+            // non-canonical binary encoding, that binary expression would be greater than the last
+            // element, rendering the instance unsatisfiable.
+            let mut items_binary = Vec::new();
+            for item in items.iter().take(n - 1) {
+                items_binary.push(self.split_allowing_ambiguity(item));
+            }
+            items_binary.push(self.split(&items[n - 1]));
+
+            for i in 0..(n - 1) {
+                let a = &items_binary[i];
+                let b = &items_binary[i + 1];
+                self.assert_le_binary(a, b);
+            }
+        })
+    }
+
+    /// Assert that `c` is the sorted merge of `a` and `b`, i.e. that it is a permutation of their
+    /// concatenation, in ascending order.
+    ///
+    /// This doesn't require `a` or `b` to be sorted, and doesn't assert that they are, since the
+    /// sorted merge of two lists is just their sorted concatenation. It costs a permutation network
+    /// over `c.len()` items plus the checks of `assert_ascending`.
+    pub fn assert_sorted_merge(
+        &mut self, a: &[Expression<F>], b: &[Expression<F>], c: &[Expression<F>],
+    ) {
+        logged!(self, "assert_sorted_merge", [a.len(), b.len(), c.len()], {
+            assert_eq!(a.len() + b.len(), c.len(),
+                       "The merged list must have as many items as the two lists combined");
+            self.assert_permutation(&[a, b].concat(), c);
+            self.assert_ascending(c);
+        })
     }
 
     /// Sorts field elements in descending order.
     pub fn sort_descending(&mut self, inputs: &[Expression<F>]) -> Vec<Expression<F>> {
-        let mut items = self.sort_ascending(inputs);
-        items.reverse();
-        items
+        logged!(self, "sort_descending", [inputs.len()], {
+            let mut items = self.sort_ascending(inputs);
+            items.reverse();
+            items
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::expression::Expression;
-    use crate::field::Element;
+    use crate::field::{Bn128, Element};
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::fields::small::F257;
+    use crate::wire::Wire;
+    use crate::wire_values::WireValues;
 
     #[test]
     fn sort_4_ascending() {
         let mut builder = GadgetBuilder::<F257>::new();
         let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
-        let outputs = builder.sort_ascending(&vec![
+        let outputs = builder.sort_ascending(&[
             Expression::from(a), Expression::from(b), Expression::from(c), Expression::from(d)]);
         let gadget = builder.build();
 
@@ -94,7 +129,7 @@ mod tests {
     fn sort_4_descending() {
         let mut builder = GadgetBuilder::<F257>::new();
         let (a, b, c, d) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
-        let outputs = builder.sort_descending(&vec![
+        let outputs = builder.sort_descending(&[
             Expression::from(a), Expression::from(b), Expression::from(c), Expression::from(d)]);
         let gadget = builder.build();
 
@@ -106,4 +141,63 @@ mod tests {
         assert_eq!(Element::from(1u8), outputs[2].evaluate(&values));
         assert_eq!(Element::from(0u8), outputs[3].evaluate(&values));
     }
-}
\ No newline at end of file
+
+    /// Values for the concatenated wires of a sorted merge gadget's three lists.
+    fn merge_values(wires: &[Wire], a: [u64; 3], b: [u64; 3], c: [u64; 6]) -> WireValues<Bn128> {
+        let elements: Vec<Element<Bn128>> = a.iter().chain(&b).chain(&c)
+            .map(|&x| Element::from(x))
+            .collect();
+        let mut values = WireValues::new();
+        values.set_each(wires, &elements);
+        values
+    }
+
+    #[test]
+    fn sorted_merge() {
+        // Assert that a list of six items is the sorted merge of two lists of three.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (a_wires, a) = builder.expression_wires(3);
+        let (b_wires, b) = builder.expression_wires(3);
+        let (c_wires, c) = builder.expression_wires(6);
+        builder.assert_sorted_merge(&a, &b, &c);
+        let gadget = builder.build();
+        let wires = [a_wires, b_wires, c_wires].concat();
+
+        let mut values = merge_values(&wires, [1, 5, 9], [2, 5, 10], [1, 2, 5, 5, 9, 10]);
+        assert!(gadget.execute(&mut values));
+
+        let big = 1 << 40;
+        let mut values = merge_values(&wires, [3, 3, big], [0, 3, big], [0, 3, 3, 3, big, big]);
+        assert!(gadget.execute(&mut values));
+
+        // c is a permutation of a and b, but isn't sorted.
+        let mut values = merge_values(&wires, [1, 5, 9], [2, 5, 10], [1, 2, 5, 9, 5, 10]);
+        assert!(!gadget.execute(&mut values));
+    }
+
+    #[test]
+    #[should_panic]
+    fn sorted_merge_missing_item() {
+        // Assert that a list of six items is the sorted merge of two lists of three.
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (a_wires, a) = builder.expression_wires(3);
+        let (b_wires, b) = builder.expression_wires(3);
+        let (c_wires, c) = builder.expression_wires(6);
+        builder.assert_sorted_merge(&a, &b, &c);
+        let gadget = builder.build();
+        let wires = [a_wires, b_wires, c_wires].concat();
+
+        // 9 is replaced by a duplicate 5, so c is sorted but not a permutation of a and b. The
+        // permutation network's generator should fail, since there's no possible routing.
+        gadget.execute(&mut merge_values(&wires, [1, 5, 9], [2, 5, 10], [1, 2, 5, 5, 5, 10]));
+    }
+
+    #[test]
+    #[should_panic(expected = "The merged list must have as many items as the two lists combined")]
+    fn sorted_merge_wrong_length() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (_, a) = builder.expression_wires(2);
+        let (_, c) = builder.expression_wires(3);
+        builder.assert_sorted_merge(&a, &[], &c);
+    }
+}
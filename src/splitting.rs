@@ -1,16 +1,22 @@
 //! This module extends GadgetBuilder with methods for splitting field elements into bits.
 
+use num::BigUint;
+use num_traits::{One, Zero};
+
 use crate::expression::{BinaryExpression, Expression};
 use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
+use crate::wire::Wire;
 use crate::wire_values::WireValues;
 
 impl<F: Field> GadgetBuilder<F> {
     /// Split an arbitrary field element `x` into its canonical binary representation.
     pub fn split(&mut self, x: &Expression<F>) -> BinaryExpression<F> {
-        let result = self.split_without_range_check(x, Element::<F>::max_bits());
-        self.assert_lt_binary(&result, &BinaryExpression::from(F::order()));
-        result
+        logged!(self, "split", [x], {
+            let result = self.split_without_range_check(x, Element::<F>::max_bits());
+            self.assert_lt_binary(&result, &BinaryExpression::from(F::order()));
+            result
+        })
     }
 
     /// Split an arbitrary field element `x` into a binary representation. Unlike `split`, this
@@ -19,15 +25,73 @@ impl<F: Field> GadgetBuilder<F> {
     /// constraints, but the ambiguity can be a security problem depending on the context. If in
     /// doubt, use `split` instead.
     pub fn split_allowing_ambiguity(&mut self, x: &Expression<F>) -> BinaryExpression<F> {
-        self.split_without_range_check(x, Element::<F>::max_bits())
+        logged!(self, "split_allowing_ambiguity", [x], {
+            self.split_without_range_check(x, Element::<F>::max_bits())
+        })
     }
 
     /// Split `x` into `bits` bit wires. This method assumes `x < 2^bits < |F|`. Note that only one
     /// binary representation is possible here, since `bits` bits is not enough to overflow the
     /// field size.
+    ///
+    /// If `x` is already known to fit in fewer bits, only that many bit wires are allocated, and
+    /// the result is padded with constant zeros up to `bits` bits. Afterward, `x` is known to fit
+    /// in `bits` bits.
     pub fn split_bounded(&mut self, x: &Expression<F>, bits: usize) -> BinaryExpression<F> {
-        assert!(bits < Element::<F>::max_bits());
-        self.split_without_range_check(x, bits)
+        logged!(self, "split_bounded", [x, bits], {
+            assert!(bits < Element::<F>::max_bits());
+            match self.known_bound(x) {
+                Some(known_bits) if known_bits < bits => {
+                    let mut result = self.split_without_range_check(x, known_bits);
+                    result.pad(bits);
+                    result
+                }
+                _ => self.split_without_range_check(x, bits),
+            }
+        })
+    }
+
+    /// Split `x` into as few bits as possible, based on a static bound on its value. The bound is
+    /// derived from the known bounds of `x` itself and of each wire it depends on, with known
+    /// booleans fitting in one bit. If `x` can't be shown to fit in fewer than `max_bits` bits,
+    /// for example because it depends on a wire with no known bound, or because it has a negative
+    /// coefficient which could make it wrap around, this falls back to `split`.
+    pub fn split_auto(&mut self, x: &Expression<F>) -> BinaryExpression<F> {
+        logged!(self, "split_auto", [x], {
+            let static_bits = self.static_upper_bound(x).map(|bound| bound.bits() as usize);
+            let bits = match (self.known_bound(x), static_bits) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            match bits {
+                Some(bits) if bits < Element::<F>::max_bits() =>
+                    self.split_without_range_check(x, bits),
+                _ => self.split(x),
+            }
+        })
+    }
+
+    /// An upper bound on the value of `x`, as an integer, or `None` if no bound less than `|F|`
+    /// can be shown. This requires every coefficient to be non-negative, i.e. at most `|F| / 2`,
+    /// and every wire other than `Wire::ONE` to have a known bound.
+    fn static_upper_bound(&self, x: &Expression<F>) -> Option<BigUint> {
+        let half_order = F::order() >> 1;
+        let mut bound = BigUint::zero();
+        for (&wire, coefficient) in x.iter() {
+            let coefficient = coefficient.to_biguint();
+            if coefficient > &half_order {
+                // The term could be negative, so the sum could wrap around.
+                return None;
+            }
+            let wire_bound = if wire == Wire::ONE {
+                BigUint::one()
+            } else {
+                let bits = self.known_bound(&Expression::from(wire))?;
+                (BigUint::one() << bits) - BigUint::one()
+            };
+            bound += coefficient * wire_bound;
+        }
+        if bound < F::order() { Some(bound) } else { None }
     }
 
     fn split_without_range_check(&mut self, x: &Expression<F>, bits: usize) -> BinaryExpression<F> {
@@ -36,12 +100,19 @@ impl<F: Field> GadgetBuilder<F> {
         let weighted_sum = binary_exp.join_allowing_overflow();
         self.assert_equal(x, &weighted_sum);
 
+        // If the weighted sum can't overflow, the decomposition also bounds x.
+        if bits < Element::<F>::max_bits() {
+            self.mark_bounded(x, bits);
+        }
+
         let x = x.clone();
-        self.generator(
+        self.generator_with_outputs(
             x.dependencies(),
+            binary_wire.bits.iter().map(|bit| bit.wire()).collect(),
             move |values: &mut WireValues<F>| {
+                // If the value doesn't fit in `bits` bits, we assign only the low bits. Generation
+                // still completes, but the weighted sum constraint will not be satisfied.
                 let value = x.evaluate(values);
-                assert!(value.bits() <= bits);
                 for i in 0..bits {
                     values.set_boolean(binary_wire.bits[i], value.bit(i));
                 }
@@ -54,9 +125,13 @@ impl<F: Field> GadgetBuilder<F> {
 
 #[cfg(test)]
 mod tests {
+    use num::BigUint;
+
     use crate::Bn128;
-    use crate::expression::Expression;
+    use crate::expression::{BooleanExpression, Expression};
+    use crate::field::Element;
     use crate::gadget_builder::GadgetBuilder;
+    use crate::wire_values::WireValues;
 
     #[test]
     fn split_19_32() {
@@ -101,4 +176,121 @@ mod tests {
         assert_eq!(false, bit_wires.bits[30].evaluate(&wire_values));
         assert_eq!(false, bit_wires.bits[31].evaluate(&wire_values));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn split_bounded_value_too_large() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let wire = builder.wire();
+        builder.split_bounded(&Expression::from(wire), 8);
+        let gadget = builder.build();
+
+        // 300 doesn't fit in 8 bits, so the constraints should not be satisfiable, but witness
+        // generation should not panic.
+        let mut values = values!(wire => 300u32.into());
+        assert!(!gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn split_canonical() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let wire = builder.wire();
+        let bits = builder.split(&Expression::from(wire));
+        let gadget = builder.build();
+
+        let mut values = values!(wire => 123_456_789u32.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(123_456_789u32), bits.evaluate(&values));
+    }
+
+    #[test]
+    fn split_bounded_reuses_known_bound() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let wire = builder.wire();
+        let x = Expression::from(wire);
+        builder.split_bounded(&x, 8);
+        assert_eq!(Some(8), builder.known_bound(&x));
+        let bits = builder.split_bounded(&x, 16);
+        assert_eq!(16, bits.len());
+        let gadget = builder.build();
+
+        // Each split allocates 8 bit wires with booleanity constraints, plus a constraint for the
+        // weighted sum.
+        assert_eq!(18, gadget.size());
+
+        let mut values = values!(wire => 200u8.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(200u8), bits.evaluate(&values));
+    }
+
+    #[test]
+    fn split_bounded_unmarked_expression() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = Expression::from(builder.wire());
+        let y = Expression::from(builder.wire());
+        builder.split_bounded(&x, 8);
+        assert_eq!(None, builder.known_bound(&y));
+        let bits = builder.split_bounded(&y, 16);
+        assert_eq!(16, bits.len());
+
+        // The bound on x must not be applied to y, so y gets all 16 bit wires.
+        assert_eq!(9 + 17, builder.build().size());
+    }
+
+    #[test]
+    fn split_auto_sum_of_booleans() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let wires: Vec<_> = (0..5).map(|_| builder.boolean_wire()).collect();
+        let sum = Expression::sum_of_expressions(
+            &wires.iter().map(|w| BooleanExpression::from(w).expression().clone())
+                .collect::<Vec<_>>());
+        let bits = builder.split_auto(&sum);
+        assert_eq!(3, bits.len());
+        assert_eq!(Some(3), builder.known_bound(&sum));
+
+        // Five booleanity constraints on the inputs, three on the result bits, and one for the
+        // weighted sum.
+        assert_eq!(9, builder.build().size());
+    }
+
+    #[test]
+    fn split_auto_matches_split() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let wires: Vec<_> = (0..5).map(|_| builder.boolean_wire()).collect();
+        let sum = Expression::sum_of_expressions(
+            &wires.iter().map(|w| BooleanExpression::from(w).expression().clone())
+                .collect::<Vec<_>>());
+        let auto_bits = builder.split_auto(&sum);
+        let full_bits = builder.split(&sum);
+        let gadget = builder.build();
+
+        for assignment in 0..(1u32 << 5) {
+            let mut values = WireValues::new();
+            for (i, &wire) in wires.iter().enumerate() {
+                values.set_boolean(wire, (assignment >> i) & 1 == 1);
+            }
+            assert!(gadget.execute(&mut values));
+            assert_eq!(BigUint::from(assignment.count_ones()), auto_bits.evaluate(&values));
+            assert_eq!(full_bits.evaluate(&values), auto_bits.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn split_auto_fallback() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let bit = builder.boolean_wire();
+        let wire = builder.wire();
+        let x = Expression::from(bit.wire) + Expression::from(wire);
+        let bits = builder.split_auto(&x);
+        assert_eq!(Element::<Bn128>::max_bits(), bits.len());
+
+        // A negative coefficient could wrap around, so it also falls back, even for booleans.
+        let y = Expression::from(bit.wire) * Element::from(2u8) - Expression::one();
+        assert_eq!(Element::<Bn128>::max_bits(), builder.split_auto(&y).len());
+        let gadget = builder.build();
+
+        let mut values = values!(wire => 123_456u32.into());
+        values.set_boolean(bit, true);
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(123_457u32), bits.evaluate(&values));
+    }
+}
@@ -8,7 +8,8 @@ use std::marker::PhantomData;
 
 use itertools::{enumerate, Itertools};
 
-use crate::{GadgetBuilder, MultiPermutation};
+use crate::{CompressionFunction, Element, GadgetBuilder, HashFunction, MultiPermutation,
+            WideHashFunction};
 use crate::Expression;
 use crate::Field;
 use crate::util::concat;
@@ -22,7 +23,7 @@ pub struct Sponge<F: Field, MP: MultiPermutation<F>> {
     permutation: MP,
     bitrate: usize,
     capacity: usize,
-    phantom: PhantomData<*const F>,
+    phantom: PhantomData<fn() -> F>,
 }
 
 impl<F: Field, MP: MultiPermutation<F>> Sponge<F, MP> {
@@ -38,13 +39,26 @@ impl<F: Field, MP: MultiPermutation<F>> Sponge<F, MP> {
         Sponge { permutation, bitrate, capacity, phantom: PhantomData }
     }
 
+    /// The permutation with which state memory is transformed.
+    pub fn permutation(&self) -> &MP {
+        &self.permutation
+    }
+
+    /// The size of the input section, in field elements.
+    pub fn bitrate(&self) -> usize {
+        self.bitrate
+    }
+
+    /// The size of the capacity section, in field elements.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
     pub fn evaluate(
         &self, builder: &mut GadgetBuilder<F>, inputs: &[Expression<F>], output_len: usize,
     ) -> Vec<Expression<F>> {
-        let mut input_section = iter::repeat(Expression::zero())
-            .take(self.bitrate).collect_vec();
-        let mut capacity_section = iter::repeat(Expression::zero())
-            .take(self.capacity).collect_vec();
+        let mut input_section = iter::repeat_n(Expression::zero(), self.bitrate).collect_vec();
+        let mut capacity_section = iter::repeat_n(Expression::zero(), self.capacity).collect_vec();
 
         let chunks = inputs.chunks(self.bitrate);
         for chunk in chunks {
@@ -81,14 +95,76 @@ impl<F: Field, MP: MultiPermutation<F>> Sponge<F, MP> {
 
         outputs
     }
+
+    /// Like `evaluate`, but computes the outputs directly from the given elements, using the
+    /// permutation's `permute_evaluate`, rather than adding constraints to a `GadgetBuilder`.
+    pub fn evaluate_elements(&self, inputs: &[Element<F>], output_len: usize) -> Vec<Element<F>> {
+        let mut state = vec![Element::zero(); self.bitrate + self.capacity];
+
+        for chunk in inputs.chunks(self.bitrate) {
+            for (i, element) in enumerate(chunk) {
+                state[i] += element;
+            }
+            state = self.permutation.permute_evaluate(&state);
+        }
+
+        let mut outputs = state[..self.bitrate].to_vec();
+        while outputs.len() < output_len {
+            state = self.permutation.permute_evaluate(&state);
+            outputs.extend_from_slice(&state[..self.bitrate]);
+        }
+        outputs.truncate(output_len);
+
+        outputs
+    }
+}
+
+/// Hashes by absorbing all blocks, then squeezing a single element. No padding is applied, so
+/// inputs which differ only in trailing zeros collide; inputs should have a fixed length, or
+/// encode their length.
+impl<F: Field, MP: MultiPermutation<F>> HashFunction<F> for Sponge<F, MP> {
+    fn hash(&self, builder: &mut GadgetBuilder<F>, blocks: &[Expression<F>]) -> Expression<F> {
+        self.evaluate(builder, blocks, 1).remove(0)
+    }
+
+    fn hash_evaluate(&self, blocks: &[Element<F>]) -> Element<F> {
+        self.evaluate_elements(blocks, 1).remove(0)
+    }
+}
+
+/// Hashes by absorbing all blocks, then squeezing `output_len` elements, as with `evaluate`. The
+/// first element is the output of `HashFunction::hash`.
+impl<F: Field, MP: MultiPermutation<F>> WideHashFunction<F> for Sponge<F, MP> {
+    fn hash_wide(
+        &self, builder: &mut GadgetBuilder<F>, blocks: &[Expression<F>], output_len: usize,
+    ) -> Vec<Expression<F>> {
+        self.evaluate(builder, blocks, output_len)
+    }
+
+    fn hash_wide_evaluate(&self, blocks: &[Element<F>], output_len: usize) -> Vec<Element<F>> {
+        self.evaluate_elements(blocks, output_len)
+    }
+}
+
+/// Compresses by hashing the two inputs, as with `HashFunction::hash`.
+impl<F: Field, MP: MultiPermutation<F>> CompressionFunction<F> for Sponge<F, MP> {
+    fn compress(&self, builder: &mut GadgetBuilder<F>, x: &Expression<F>, y: &Expression<F>)
+                -> Expression<F> {
+        self.hash(builder, &[x.clone(), y.clone()])
+    }
+
+    fn compress_evaluate(&self, x: &Element<F>, y: &Element<F>) -> Element<F> {
+        self.hash_evaluate(&[x.clone(), y.clone()])
+    }
 }
 
 #[cfg(test)]
 mod tests {
     #[cfg(not(feature = "std"))]
     use alloc::vec::Vec;
-    use crate::{Element, Expression, Field, GadgetBuilder, MultiPermutation, Sponge};
-    use crate::test_util::F7;
+    use crate::{Element, Expression, Field, FirstOutputHash, GadgetBuilder, HashFunction,
+                MultiPermutation, Sponge, WideHashFunction, WireValues};
+    use crate::fields::small::F7;
 
     #[test]
     fn sponge_1_1_1_f7() {
@@ -143,4 +219,58 @@ mod tests {
         // Output: 4
         assert_eq!(Element::from(4u8), hash.evaluate(&values));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn wide_output() {
+        // The same trivial permutation as above, transforming (x, y) into (2y, 3x).
+        struct TestPermutation;
+
+        impl<F: Field> MultiPermutation<F> for TestPermutation {
+            fn width(&self) -> usize {
+                2
+            }
+
+            fn permute(
+                &self, _builder: &mut GadgetBuilder<F>, inputs: &[Expression<F>],
+            ) -> Vec<Expression<F>> {
+                assert_eq!(inputs.len(), 2);
+                let x = &inputs[0];
+                let y = &inputs[1];
+                vec![y * Element::from(2u8), x * Element::from(3u8)]
+            }
+
+            fn inverse(
+                &self, _builder: &mut GadgetBuilder<F>, outputs: &[Expression<F>],
+            ) -> Vec<Expression<F>> {
+                assert_eq!(outputs.len(), 2);
+                let x = &outputs[0];
+                let y = &outputs[1];
+                vec![y / Element::from(3u8), x / Element::from(2u8)]
+            }
+        }
+
+        let mut builder = GadgetBuilder::<F7>::new();
+        let (wires, blocks) = builder.expression_wires(3);
+        let sponge = Sponge::new(TestPermutation, 1, 1);
+        let wide = sponge.hash_wide(&mut builder, &blocks, 4);
+        let squeezed = sponge.evaluate(&mut builder, &blocks, 4);
+        let gadget = builder.build();
+
+        let inputs = [Element::from(3u8), Element::from(4u8), Element::from(5u8)];
+        let mut values = WireValues::new();
+        values.set_each(&wires, &inputs);
+        assert!(gadget.execute(&mut values));
+        let wide = wide.iter().map(|exp| exp.evaluate(&values)).collect::<Vec<_>>();
+        let squeezed = squeezed.iter().map(|exp| exp.evaluate(&values)).collect::<Vec<_>>();
+        assert_eq!(squeezed, wide);
+        assert_eq!(sponge.evaluate_elements(&inputs, 4), wide);
+        assert_eq!(sponge.hash_wide_evaluate(&inputs, 4), wide);
+
+        // The first output is the sponge's ordinary hash, so adapting it changes nothing.
+        let adapter = FirstOutputHash(Sponge::new(TestPermutation, 1, 1));
+        for inputs in &[vec![3u8, 4], vec![0, 6, 1], vec![]] {
+            let inputs = inputs.iter().map(|&x| Element::<F7>::from(x)).collect::<Vec<_>>();
+            assert_eq!(sponge.hash_evaluate(&inputs), adapter.hash_evaluate(&inputs));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! This module contains prepackaged statements, which assemble complete gadgets for common proofs
+//! of knowledge along with handles to their input wires and the set of public wires.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "std"))]
+use alloc::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
+
+use crate::expression::{BinaryExpression, Expression};
+use crate::factory::GadgetFactory;
+use crate::field::Field;
+use crate::gadget::Gadget;
+use crate::gadget_builder::GadgetBuilder;
+use crate::gadget_traits::{CompressionFunction, HashFunction};
+use crate::merkle_trees::MerklePath;
+use crate::util::expressions_from_wires;
+use crate::wire::{ToWires, Wire};
+
+/// Handles to the wires of a prepackaged statement, used to populate a witness and to export the
+/// statement's R1CS instance.
+#[derive(Clone, Debug)]
+pub struct StatementHandles {
+    /// The input wires of the statement, keyed by name. Each statement documents its inputs.
+    pub inputs: BTreeMap<&'static str, Vec<Wire>>,
+    /// The wires whose values are public, in the form expected by `Gadget::variable_order` and
+    /// `Gadget::assignment_vector`.
+    pub public_wires: BTreeSet<Wire>,
+}
+
+impl StatementHandles {
+    /// The input wires with the given name. Panics if the statement has no such input.
+    pub fn input(&self, name: &str) -> &[Wire] {
+        self.inputs.get(name)
+            .unwrap_or_else(|| panic!("Statement has no input named {}", name))
+    }
+}
+
+/// A statement proving knowledge of a preimage of a digest under the given hash function. The
+/// preimage is a single field element.
+///
+/// The inputs are `preimage` and `digest`, each a single wire. If `digest_public` is set, the
+/// digest wire is public; otherwise all wires are private.
+pub fn preimage_statement<F: Field, H: HashFunction<F>>(
+    hash: &H, digest_public: bool,
+) -> (Gadget<F>, StatementHandles) {
+    let mut builder = GadgetBuilder::new();
+    let preimage = builder.wire();
+    let digest = builder.wire();
+
+    let computed_digest = hash.hash(&mut builder, &[Expression::from(preimage)]);
+    builder.assert_equal(&computed_digest, &Expression::from(digest));
+
+    let mut inputs = BTreeMap::new();
+    inputs.insert("preimage", vec![preimage]);
+    inputs.insert("digest", vec![digest]);
+    let mut public_wires = BTreeSet::new();
+    if digest_public {
+        public_wires.insert(digest);
+    }
+    (builder.build(), StatementHandles { inputs, public_wires })
+}
+
+/// A statement proving that a leaf is a member of a binary Merkle tree of the given depth, with a
+/// public root.
+///
+/// The inputs are `leaf` and `root`, each a single wire; `position`, the `depth` bits of the
+/// leaf's index, from least significant to most significant; and `siblings`, the `depth` sibling
+/// nodes encountered along the path from the leaf to the root. Only the root is public.
+pub fn merkle_membership_statement<F: Field, CF: CompressionFunction<F>>(
+    depth: usize, compress: &CF,
+) -> (Gadget<F>, StatementHandles) {
+    let mut builder = GadgetBuilder::new();
+    let leaf = builder.wire();
+    let root = builder.wire();
+    let position = builder.binary_wire(depth);
+    let siblings = builder.wires(depth);
+
+    let path = MerklePath::new(
+        BinaryExpression::from(&position), expressions_from_wires(&siblings));
+    builder.assert_merkle_tree_membership(
+        &Expression::from(leaf), &Expression::from(root), &path, compress);
+
+    let mut inputs = BTreeMap::new();
+    inputs.insert("leaf", vec![leaf]);
+    inputs.insert("root", vec![root]);
+    inputs.insert("position", position.to_wires());
+    inputs.insert("siblings", siblings);
+    let public_wires = [root].iter().cloned().collect();
+    (builder.build(), StatementHandles { inputs, public_wires })
+}
+
+/// A factory for `merkle_membership_statement`, so that the statement can be built once and shared,
+/// e.g. with `CachedFactory`.
+pub struct MerkleMembershipFactory<CF> {
+    depth: usize,
+    compress: CF,
+}
+
+impl<CF> MerkleMembershipFactory<CF> {
+    pub fn new(depth: usize, compress: CF) -> Self {
+        MerkleMembershipFactory { depth, compress }
+    }
+}
+
+impl<F: Field, CF: CompressionFunction<F>> GadgetFactory<F> for MerkleMembershipFactory<CF> {
+    type IoHandles = StatementHandles;
+
+    fn build_gadget(&self) -> (Gadget<F>, StatementHandles) {
+        merkle_membership_statement(self.depth, &self.compress)
+    }
+}
@@ -1,13 +1,32 @@
-//! This module contains test helper functions.
+//! This module contains test helper functions. Besides the crate's own tests, it is available to
+//! downstream crates through the `test-utils` feature. The small fields F7, F11 and F257 are
+//! re-exported from `fields::small` for compatibility.
 
 use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::panic::{AssertUnwindSafe, catch_unwind};
 
-use num::BigUint;
+use num::ToPrimitive;
 
-use crate::expression::BooleanExpression;
-use crate::field::Field;
+use crate::expression::{BooleanExpression, Expression};
+use crate::field::{Element, Field};
+use crate::gadget::Gadget;
+use crate::gadget_builder::GadgetBuilder;
+pub use crate::fields::small::{F11, F257, F7};
+use crate::fields::small::SmallField;
+use crate::util::join;
+use crate::wire::Wire;
 use crate::wire_values::WireValues;
 
+/// The maximum number of input assignments `exhaustive_check` will enumerate.
+const MAX_ASSIGNMENTS: u64 = 1 << 20;
+
+/// The number of accepted witnesses which `exhaustive_check` transplants onto each rejected input
+/// assignment, in search of a forged witness.
+const MAX_TRANSPLANTS: usize = 8;
+
 pub fn assert_eq_true<F, T>(x: T, values: &WireValues<F>)
     where F: Field, T: Borrow<BooleanExpression<F>> {
     assert_eq!(true, x.borrow().evaluate(values));
@@ -18,29 +37,256 @@ pub fn assert_eq_false<F, T>(x: T, values: &WireValues<F>)
     assert_eq!(false, x.borrow().evaluate(values));
 }
 
+/// The seed of the stream drawn from by `deterministic_elements`.
+#[cfg(feature = "rand")]
+const TEST_SEED: u64 = 0;
+
+/// Returns `n` pseudorandom elements, which are the same on every run. Tests should use this rather
+/// than ad-hoc generators, so that their inputs are reproducible.
+#[cfg(feature = "rand")]
+pub fn deterministic_elements<F: Field>(n: usize) -> Vec<Element<F>> {
+    (0..n).map(|i| Element::random_from_seed(TEST_SEED, i)).collect()
+}
+
+/// Builds a gadget asserting `z = x * y + w`, with an unused wire between `y` and `w`, returning
+/// the gadget and the wires `[x, y, z, w]`. Useful for tests of whole-gadget transformations.
+pub fn multiply_add<F: Field>() -> (Gadget<F>, [Wire; 4]) {
+    let mut builder = GadgetBuilder::new();
+    let (x, y, _unused, w) = (builder.wire(), builder.wire(), builder.wire(), builder.wire());
+    let xy = builder.product(&Expression::from(x), &Expression::from(y));
+    let z = builder.wire();
+    builder.assert_equal(&Expression::from(z), &(xy + Expression::from(w)));
+    (builder.build(), [x, y, z, w])
+}
+
+/// The ways in which a gadget can disagree with a reference predicate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CounterexampleKind {
+    /// The reference accepts the inputs, but witness generation either failed or produced a witness
+    /// which violates some constraint.
+    Incomplete,
+    /// The reference rejects the inputs, but the generated witness satisfies every constraint.
+    AcceptedInvalid,
+    /// The reference rejects the inputs, and the generated witness violates some constraint, but a
+    /// witness with other values for non-input wires satisfies every constraint.
+    Forged,
+}
+
+/// An input assignment on which a gadget disagrees with a reference predicate, as found by
+/// `exhaustive_check`.
 #[derive(Debug)]
-pub struct F7 {}
+pub struct CounterexampleReport<F: Field> {
+    /// How the gadget disagrees with the reference.
+    pub kind: CounterexampleKind,
+    /// The violating assignment of input wires.
+    pub inputs: Vec<(Wire, Element<F>)>,
+    /// For soundness failures, a complete witness which satisfies every constraint.
+    pub witness: Option<WireValues<F>>,
+}
 
-impl Field for F7 {
-    fn order() -> BigUint {
-        BigUint::from(7u8)
+impl<F: Field> fmt::Display for CounterexampleReport<F> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let description = match self.kind {
+            CounterexampleKind::Incomplete =>
+                "reference accepts, but the gadget rejects the generated witness",
+            CounterexampleKind::AcceptedInvalid =>
+                "reference rejects, but the gadget accepts the generated witness",
+            CounterexampleKind::Forged =>
+                "reference rejects, but a forged witness satisfies all constraints",
+        };
+        let inputs: Vec<String> = self.inputs.iter()
+            .map(|(wire, value)| format!("{} = {}", wire, value))
+            .collect();
+        write!(f, "{}: {}", join(", ", &inputs), description)?;
+        if let Some(witness) = &self.witness {
+            write!(f, "; witness {:.*}", witness.as_map().len(), witness)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-pub struct F11 {}
+impl<F: Field + fmt::Debug> std::error::Error for CounterexampleReport<F> {}
+
+/// Check a gadget against a reference predicate over a small field, by enumerating every assignment
+/// of `input_wires`. For each assignment, witness generation should succeed and satisfy all
+/// constraints exactly when `reference` holds.
+///
+/// When `reference` rejects an assignment, this also plays the part of a malicious prover, looking
+/// for a witness which satisfies every constraint anyway. It tries overriding each non-input wire
+/// of the generated witness with every field element, and transplanting the non-input wires of
+/// witnesses generated for accepted assignments. This search is not exhaustive, so a gadget which
+/// passes may still be under-constrained, but it catches the common mistake of a generated wire
+/// which no constraint pins down.
+///
+/// Witness generation which panics counts as rejection.
+pub fn exhaustive_check<F, R>(
+    gadget: &Gadget<F>, input_wires: &[Wire], reference: R,
+) -> Result<(), CounterexampleReport<F>>
+    where F: SmallField, R: Fn(&[Element<F>]) -> bool {
+    let order = F::order().to_u64().expect("Field is too large for an exhaustive check");
+    let num_assignments = input_wires.iter()
+        .try_fold(1u64, |total, _| total.checked_mul(order).filter(|&n| n <= MAX_ASSIGNMENTS))
+        .expect("Too many input assignments for an exhaustive check");
+
+    let assignments: Vec<Vec<Element<F>>> = (0..num_assignments)
+        .map(|index| {
+            let mut index = index;
+            input_wires.iter().map(|_| {
+                let digit = index % order;
+                index /= order;
+                Element::from(digit)
+            }).collect()
+        })
+        .collect();
+
+    let mut rejected = Vec::new();
+    let mut accepted_witnesses = Vec::new();
+    for inputs in assignments {
+        let (witness, accepted) = generate(gadget, input_wires, &inputs);
+        match (reference(&inputs), accepted) {
+            (true, true) => accepted_witnesses.push(witness),
+            (false, false) => rejected.push((inputs, witness)),
+            (true, false) => return Err(
+                report(CounterexampleKind::Incomplete, input_wires, &inputs, None)),
+            (false, true) => return Err(
+                report(CounterexampleKind::AcceptedInvalid, input_wires, &inputs, Some(witness))),
+        }
+    }
+
+    let wires = gadget.wires();
+    let non_input_wires: Vec<Wire> = wires.iter().cloned()
+        .filter(|wire| !input_wires.contains(wire))
+        .collect();
+
+    for (inputs, witness) in rejected {
+        let mut candidate = witness.as_map().clone();
+        for &wire in &wires {
+            candidate.entry(wire).or_insert_with(Element::zero);
+        }
+
+        for &wire in &non_input_wires {
+            let original = candidate[&wire].clone();
+            for value in 0..order {
+                let value = Element::from(value);
+                if value == original {
+                    continue;
+                }
+                candidate.insert(wire, value);
+                if let Some(forged) = satisfying(gadget, &candidate) {
+                    return Err(report(
+                        CounterexampleKind::Forged, input_wires, &inputs, Some(forged)));
+                }
+            }
+            candidate.insert(wire, original);
+        }
 
-impl Field for F11 {
-    fn order() -> BigUint {
-        BigUint::from(11u8)
+        for accepted_witness in accepted_witnesses.iter().take(MAX_TRANSPLANTS) {
+            let mut candidate = accepted_witness.as_map().clone();
+            for (&wire, value) in input_wires.iter().zip(&inputs) {
+                candidate.insert(wire, value.clone());
+            }
+            if let Some(forged) = satisfying(gadget, &candidate) {
+                return Err(report(
+                    CounterexampleKind::Forged, input_wires, &inputs, Some(forged)));
+            }
+        }
     }
+
+    Ok(())
 }
 
-#[derive(Debug)]
-pub struct F257 {}
+/// Run witness generation for the given inputs, returning the (possibly partial) witness and
+/// whether it satisfies all constraints.
+fn generate<F: Field>(
+    gadget: &Gadget<F>, input_wires: &[Wire], inputs: &[Element<F>],
+) -> (WireValues<F>, bool) {
+    let mut values = WireValues::new();
+    for (&wire, value) in input_wires.iter().zip(inputs) {
+        values.set(wire, value.clone());
+    }
+    let accepted = catch_unwind(AssertUnwindSafe(|| gadget.execute(&mut values)))
+        .unwrap_or(false);
+    (values, accepted)
+}
+
+/// If the given assignment satisfies every constraint, convert it to a `WireValues`.
+fn satisfying<F: Field>(
+    gadget: &Gadget<F>, assignment: &BTreeMap<Wire, Element<F>>,
+) -> Option<WireValues<F>> {
+    let mut values = WireValues::new();
+    for (&wire, value) in assignment {
+        if wire != Wire::ONE {
+            values.set(wire, value.clone());
+        }
+    }
+    if gadget.constraints.iter().all(|constraint| constraint.evaluate(&values)) {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+fn report<F: Field>(
+    kind: CounterexampleKind, input_wires: &[Wire], inputs: &[Element<F>],
+    witness: Option<WireValues<F>>,
+) -> CounterexampleReport<F> {
+    let inputs = input_wires.iter().cloned().zip(inputs.iter().cloned()).collect();
+    CounterexampleReport { kind, inputs, witness }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::Expression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F7;
+    use crate::test_util::{CounterexampleKind, exhaustive_check};
+
+    #[test]
+    fn exhaustive_check_product() {
+        let mut builder = GadgetBuilder::<F7>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let product = builder.product(&Expression::from(x), &Expression::from(y));
+        builder.assert_equal(&product, &Expression::from(z));
+        let gadget = builder.build();
+
+        let result = exhaustive_check(&gadget, &[x, y, z], |v| &v[0] * &v[1] == v[2]);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn exhaustive_check_incomplete() {
+        let mut builder = GadgetBuilder::<F7>::new();
+        let x = builder.wire();
+        builder.assert_zero(&Expression::from(x));
+        let gadget = builder.build();
+
+        let report = exhaustive_check(&gadget, &[x], |_| true).unwrap_err();
+        assert_eq!(CounterexampleKind::Incomplete, report.kind);
+        assert_eq!(vec![(x, Element::from(1u8))], report.inputs);
+    }
+
+    #[test]
+    fn exhaustive_check_under_constrained() {
+        // Computes the product of x and y with a generator, but never constrains it.
+        let mut builder = GadgetBuilder::<F7>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let product = builder.wire();
+        builder.generator_with_outputs(vec![x, y], vec![product], move |values| {
+            let value = values.get(x) * values.get(y);
+            values.set(product, value);
+        });
+        builder.assert_equal(&Expression::from(product), &Expression::from(z));
+        let gadget = builder.build();
 
-impl Field for F257 {
-    fn order() -> BigUint {
-        BigUint::from(257u16)
+        let report = exhaustive_check(&gadget, &[x, y, z], |v| &v[0] * &v[1] == v[2])
+            .unwrap_err();
+        assert_eq!(CounterexampleKind::Forged, report.kind);
+        let one = Element::from(1u8);
+        assert_eq!(vec![(x, one.clone()), (y, one), (z, Element::zero())], report.inputs);
+        assert_eq!(format!("{} = 1, {} = 1, {} = 0: reference rejects, but a forged witness \
+                            satisfies all constraints; witness {{{}: 1, {}: 1, {}: 0, {}: 0}}",
+                           x, y, z, x, y, z, product),
+                   report.to_string());
     }
 }
@@ -0,0 +1,217 @@
+//! This module contains a Fiat-Shamir transcript, which derives in-circuit challenges from the
+//! values which precede them.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::byte_encoding::encode_bytes_to_elements;
+use crate::expression::Expression;
+use crate::field::{Element, Field};
+use crate::gadget_builder::GadgetBuilder;
+use crate::gadget_traits::{HashFunction, WideHashFunction};
+
+/// A Fiat-Shamir transcript, from which probabilistic gadgets derive challenges.
+///
+/// Each challenge is a hash of the transcript's domain, every label and value absorbed so far, and
+/// the challenge's own label. Labels are encoded as constants, so they add no constraints, but they
+/// do affect every later challenge, which separates the challenges of different gadgets. Absorbing
+/// is free; the hash is only evaluated when a challenge is requested, after which the challenge
+/// itself stands in for everything before it.
+pub struct Transcript<F: Field, H: HashFunction<F>> {
+    hash: H,
+    /// The inputs to the next challenge's hash.
+    pending: Vec<Expression<F>>,
+}
+
+impl<F: Field, H: HashFunction<F>> Transcript<F, H> {
+    /// Creates a transcript for the given domain, which should be unique to the protocol.
+    pub fn new(domain: &str, hash: H) -> Self {
+        Transcript { hash, pending: expressions_from_label(domain) }
+    }
+
+    /// Absorbs the given values, tagged with a label describing them. This adds no constraints.
+    pub fn absorb(&mut self, label: &str, values: &[Expression<F>]) {
+        self.pending.extend(expressions_from_label(label));
+        self.pending.push(Expression::from(values.len()));
+        self.pending.extend(values.iter().cloned());
+    }
+
+    /// Derives a challenge from everything absorbed so far, tagged with a label describing it.
+    /// Later challenges depend on this one, and hence on everything before it.
+    pub fn challenge(&mut self, builder: &mut GadgetBuilder<F>, label: &str) -> Expression<F> {
+        self.pending.extend(expressions_from_label(label));
+        let challenge = self.hash.hash(builder, &self.pending);
+        self.pending = vec![challenge.clone()];
+        challenge
+    }
+}
+
+impl<F: Field, H: HashFunction<F> + WideHashFunction<F>> Transcript<F, H> {
+    /// Derives `n` challenges from everything absorbed so far, tagged with a label describing them,
+    /// with a single call to `hash_wide`. Later challenges depend on all of these ones.
+    pub fn challenges(
+        &mut self, builder: &mut GadgetBuilder<F>, label: &str, n: usize,
+    ) -> Vec<Expression<F>> {
+        self.pending.extend(expressions_from_label(label));
+        let challenges = self.hash.hash_wide(builder, &self.pending, n);
+        self.pending = challenges.clone();
+        challenges
+    }
+}
+
+/// Encodes a label as field elements with `encode_bytes_to_elements`, whose length prefix ensures
+/// that no two sequences of labels have the same encoding.
+fn label_elements<F: Field>(label: &str) -> Vec<Element<F>> {
+    encode_bytes_to_elements(label.as_bytes())
+}
+
+fn expressions_from_label<F: Field>(label: &str) -> Vec<Expression<F>> {
+    label_elements(label).iter().map(Expression::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Element, Expression, GadgetBuilder, HashFunction, Transcript, WideHashFunction,
+                WireValues};
+    use crate::fields::small::F257;
+    use crate::transcript::label_elements;
+
+    /// A simple, non-cryptographic hash, which folds blocks with `h' = 3 h^2 + b + 1`.
+    struct TestHash;
+
+    impl HashFunction<F257> for TestHash {
+        fn hash(
+            &self, builder: &mut GadgetBuilder<F257>, blocks: &[Expression<F257>],
+        ) -> Expression<F257> {
+            blocks.iter().fold(Expression::zero(), |h, block| {
+                builder.product(&h, &h) * Element::from(3u8) + block + Expression::one()
+            })
+        }
+    }
+
+    /// Outputs the hash of the blocks with a counter appended, for each counter below `output_len`.
+    impl WideHashFunction<F257> for TestHash {
+        fn hash_wide(
+            &self, builder: &mut GadgetBuilder<F257>, blocks: &[Expression<F257>],
+            output_len: usize,
+        ) -> Vec<Expression<F257>> {
+            (0..output_len).map(|i| {
+                let mut counted = blocks.to_vec();
+                counted.push(Expression::from(i));
+                self.hash(builder, &counted)
+            }).collect()
+        }
+    }
+
+    /// Absorbs two values, then derives two challenges, absorbing a third value in between.
+    fn challenges(
+        domain: &str, label: &str, values: &[Element<F257>; 3],
+    ) -> (Element<F257>, Element<F257>) {
+        let mut builder = GadgetBuilder::new();
+        let (wires, exps) = builder.expression_wires(3);
+        let mut transcript = Transcript::new(domain, TestHash);
+        transcript.absorb(label, &exps[..2]);
+        let first = transcript.challenge(&mut builder, "first");
+        transcript.absorb("third", &exps[2..]);
+        let second = transcript.challenge(&mut builder, "second");
+        let gadget = builder.build();
+
+        let mut wire_values = WireValues::new();
+        wire_values.set_each(&wires, values);
+        assert!(gadget.execute(&mut wire_values));
+        (first.evaluate(&wire_values), second.evaluate(&wire_values))
+    }
+
+    /// An out-of-circuit implementation of the same transcript as `challenges`.
+    fn reference_challenges(
+        domain: &str, label: &str, values: &[Element<F257>; 3],
+    ) -> (Element<F257>, Element<F257>) {
+        let mut inputs = label_elements(domain);
+        inputs.extend(label_elements(label));
+        inputs.push(Element::from(2u8));
+        inputs.extend(values[..2].iter().cloned());
+        inputs.extend(label_elements("first"));
+        let first = TestHash.hash_evaluate(&inputs);
+
+        let mut inputs = vec![first.clone()];
+        inputs.extend(label_elements("third"));
+        inputs.push(Element::from(1u8));
+        inputs.push(values[2].clone());
+        inputs.extend(label_elements("second"));
+        (first, TestHash.hash_evaluate(&inputs))
+    }
+
+    fn values(x: u8, y: u8, z: u8) -> [Element<F257>; 3] {
+        [Element::from(x), Element::from(y), Element::from(z)]
+    }
+
+    #[test]
+    fn deterministic() {
+        let values = values(3, 4, 5);
+        assert_eq!(challenges("test", "values", &values), challenges("test", "values", &values));
+    }
+
+    #[test]
+    fn matches_reference() {
+        for values in &[values(3, 4, 5), values(0, 0, 0), values(200, 1, 100)] {
+            assert_eq!(reference_challenges("test", "values", values),
+                       challenges("test", "values", values));
+        }
+    }
+
+    #[test]
+    fn domain_separation() {
+        let values = values(3, 4, 5);
+        let base = challenges("test", "values", &values);
+        let other_domain = challenges("other", "values", &values);
+        let other_label = challenges("test", "other", &values);
+        assert!(base.0 != other_domain.0 && base.1 != other_domain.1);
+        assert!(base.0 != other_label.0 && base.1 != other_label.1);
+    }
+
+    #[test]
+    fn later_challenges_depend_on_earlier_absorbs() {
+        let (first_a, second_a) = challenges("test", "values", &values(3, 4, 5));
+        let (first_b, second_b) = challenges("test", "values", &values(3, 6, 5));
+        assert!(first_a != first_b);
+        assert!(second_a != second_b);
+    }
+
+    #[test]
+    fn multiple_challenges() {
+        let mut builder = GadgetBuilder::new();
+        let (wires, exps) = builder.expression_wires(2);
+        let mut transcript = Transcript::new("test", TestHash);
+        transcript.absorb("values", &exps);
+        let batch = transcript.challenges(&mut builder, "batch", 3);
+        let last = transcript.challenge(&mut builder, "last");
+        let gadget = builder.build();
+
+        let values = [Element::from(3u8), Element::from(4u8)];
+        let mut wire_values = WireValues::new();
+        wire_values.set_each(&wires, &values);
+        assert!(gadget.execute(&mut wire_values));
+        let batch = batch.iter().map(|c| c.evaluate(&wire_values)).collect::<Vec<_>>();
+        assert!(batch[0] != batch[1] && batch[1] != batch[2] && batch[0] != batch[2]);
+
+        let mut inputs = label_elements("test");
+        inputs.extend(label_elements("values"));
+        inputs.push(Element::from(2u8));
+        inputs.extend(values.iter().cloned());
+        inputs.extend(label_elements("batch"));
+        assert_eq!(TestHash.hash_wide_evaluate(&inputs, 3), batch);
+
+        // The next challenge depends on the whole batch.
+        let mut inputs = batch;
+        inputs.extend(label_elements("last"));
+        assert_eq!(TestHash.hash_evaluate(&inputs), last.evaluate(&wire_values));
+    }
+
+    #[test]
+    fn labels_are_prefix_free() {
+        let a = label_elements::<F257>("ab");
+        let b = label_elements::<F257>("a");
+        assert!(!a.starts_with(&b));
+        assert!(a != [b, label_elements("b")].concat());
+    }
+}
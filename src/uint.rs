@@ -0,0 +1,268 @@
+//! This module provides `UInt`, an unsigned integer of fixed bit width, with arithmetic methods
+//! which mirror the wrapping, checked and saturating methods of Rust's native integer types.
+
+use num::BigUint;
+
+use crate::expression::{BinaryExpression, BooleanExpression, Expression};
+use crate::field::Field;
+use crate::gadget_builder::GadgetBuilder;
+use crate::wire::BinaryWire;
+use crate::wire_values::WireValues;
+
+/// An unsigned integer of a fixed number of bits, such as a `u64`, represented by a
+/// `BinaryExpression` of exactly that many bits.
+///
+/// Operations between two `UInt`s require them to have the same width. Arithmetic which can exceed
+/// the width is available in wrapping, checked and saturating variants.
+pub struct UInt<F: Field> {
+    value: BinaryExpression<F>,
+}
+
+impl<F: Field> UInt<F> {
+    /// Interpret a binary expression as an unsigned integer, with the same width as the expression.
+    pub fn new(value: BinaryExpression<F>) -> Self {
+        UInt { value }
+    }
+
+    /// A constant integer of the given width.
+    pub fn constant(value: u128, bits: usize) -> Self {
+        let value = BigUint::from(value);
+        assert!(value.bits() as usize <= bits, "{} does not fit in {} bits", value, bits);
+        UInt { value: BinaryExpression::from(value).padded(bits) }
+    }
+
+    /// The largest integer of the given width, i.e. `2^bits - 1`.
+    pub fn max_value(bits: usize) -> Self {
+        let bits = (0..bits).map(|_i| BooleanExpression::_true()).collect();
+        UInt { value: BinaryExpression { bits } }
+    }
+
+    /// The width of this integer, in bits.
+    pub fn bits(&self) -> usize {
+        self.value.len()
+    }
+
+    /// The binary representation of this integer.
+    pub fn binary(&self) -> &BinaryExpression<F> {
+        &self.value
+    }
+
+    /// This integer as a field element. This requires that `2^bits < |F|`.
+    pub fn join(&self) -> Expression<F> {
+        self.value.join()
+    }
+
+    pub fn evaluate(&self, values: &WireValues<F>) -> BigUint {
+        self.value.evaluate(values)
+    }
+
+    /// `self + rhs`, wrapping around at `2^bits`.
+    pub fn wrapping_add(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> Self {
+        self.assert_same_width("wrapping_add", rhs);
+        UInt::new(builder.binary_sum_ignoring_overflow(&self.value, &rhs.value))
+    }
+
+    /// `self + rhs`, wrapping around at `2^bits`, along with a flag indicating whether overflow
+    /// occurred.
+    pub fn checked_add(
+        &self, builder: &mut GadgetBuilder<F>, rhs: &Self,
+    ) -> (Self, BooleanExpression<F>) {
+        self.assert_same_width("checked_add", rhs);
+        let mut sum = builder.binary_sum(&self.value, &rhs.value);
+        let overflow = sum.bits[self.bits()].clone();
+        sum.truncate(self.bits());
+        (UInt::new(sum), overflow)
+    }
+
+    /// `self + rhs`, saturating at `2^bits - 1`.
+    pub fn saturating_add(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> Self {
+        let (sum, overflow) = self.checked_add(builder, rhs);
+        let max = Self::max_value(self.bits());
+        let bits = sum.value.bits.iter().zip(max.value.bits.iter())
+            .map(|(sum_bit, max_bit)| BooleanExpression::new_unsafe(
+                builder.selection(&overflow, max_bit.expression(), sum_bit.expression())))
+            .collect();
+        UInt::new(BinaryExpression { bits })
+    }
+
+    /// `self * rhs`, wrapping around at `2^bits`.
+    pub fn wrapping_mul(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> Self {
+        self.assert_same_width("wrapping_mul", rhs);
+        UInt::new(builder.binary_product_ignoring_overflow(&self.value, &rhs.value))
+    }
+
+    /// `self < rhs`.
+    pub fn lt(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> BooleanExpression<F> {
+        self.assert_same_width("lt", rhs);
+        builder.lt_binary(&self.value, &rhs.value)
+    }
+
+    /// `self <= rhs`.
+    pub fn le(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> BooleanExpression<F> {
+        self.assert_same_width("le", rhs);
+        builder.le_binary(&self.value, &rhs.value)
+    }
+
+    /// `self > rhs`.
+    pub fn gt(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> BooleanExpression<F> {
+        self.assert_same_width("gt", rhs);
+        builder.gt_binary(&self.value, &rhs.value)
+    }
+
+    /// `self >= rhs`.
+    pub fn ge(&self, builder: &mut GadgetBuilder<F>, rhs: &Self) -> BooleanExpression<F> {
+        self.assert_same_width("ge", rhs);
+        builder.ge_binary(&self.value, &rhs.value)
+    }
+
+    fn assert_same_width(&self, operation: &str, rhs: &Self) {
+        assert_eq!(self.bits(), rhs.bits(), "{}: mismatched widths, {} bits and {} bits",
+                   operation, self.bits(), rhs.bits());
+    }
+}
+
+impl<F: Field> Clone for UInt<F> {
+    fn clone(&self) -> Self {
+        UInt { value: self.value.clone() }
+    }
+}
+
+impl<F: Field> From<&BinaryWire> for UInt<F> {
+    fn from(wire: &BinaryWire) -> Self {
+        UInt::new(BinaryExpression::from(wire))
+    }
+}
+
+impl<F: Field> From<BinaryWire> for UInt<F> {
+    fn from(wire: BinaryWire) -> Self {
+        UInt::from(&wire)
+    }
+}
+
+impl<F: Field> WireValues<F> {
+    /// Set the value of a binary wire which represents an unsigned integer.
+    pub fn set_uint(&mut self, wire: &BinaryWire, value: u128) {
+        self.set_binary_unsigned(wire, &BigUint::from(value));
+    }
+
+    /// Get the value of an unsigned integer with at most 128 bits.
+    pub fn get_uint(&self, x: &UInt<F>) -> u128 {
+        assert!(x.bits() <= 128, "A {}-bit integer does not fit in a u128", x.bits());
+        x.evaluate(self).iter_u64_digits().enumerate()
+            .fold(0u128, |sum, (i, digit)| sum | (u128::from(digit) << (64 * i)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Bn128;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::test_util::{assert_eq_false, assert_eq_true};
+    use crate::uint::UInt;
+    use crate::wire_values::WireValues;
+
+    const MAX: u128 = u64::MAX as u128;
+
+    #[test]
+    fn wrapping_add() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.binary_wire(64), builder.binary_wire(64));
+        let sum = UInt::from(&x).wrapping_add(&mut builder, &UInt::from(&y));
+        let gadget = builder.build();
+
+        let mut values = WireValues::new();
+        values.set_uint(&x, MAX);
+        values.set_uint(&y, 3);
+        assert!(gadget.execute(&mut values));
+        assert_eq!(2, values.get_uint(&sum));
+    }
+
+    #[test]
+    fn checked_add() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.binary_wire(64), builder.binary_wire(64));
+        let (sum, overflow) = UInt::from(&x).checked_add(&mut builder, &UInt::from(&y));
+        let gadget = builder.build();
+
+        let mut values_no_overflow = WireValues::new();
+        values_no_overflow.set_uint(&x, MAX - 3);
+        values_no_overflow.set_uint(&y, 3);
+        assert!(gadget.execute(&mut values_no_overflow));
+        assert_eq!(MAX, values_no_overflow.get_uint(&sum));
+        assert_eq_false(&overflow, &values_no_overflow);
+
+        let mut values_overflow = WireValues::new();
+        values_overflow.set_uint(&x, MAX - 3);
+        values_overflow.set_uint(&y, 4);
+        assert!(gadget.execute(&mut values_overflow));
+        assert_eq!(0, values_overflow.get_uint(&sum));
+        assert_eq_true(&overflow, &values_overflow);
+    }
+
+    #[test]
+    fn saturating_add() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (x, y) = (builder.binary_wire(64), builder.binary_wire(64));
+        let sum = UInt::from(&x).saturating_add(&mut builder, &UInt::from(&y));
+        let gadget = builder.build();
+
+        let mut values_small = WireValues::new();
+        values_small.set_uint(&x, 1 << 40);
+        values_small.set_uint(&y, 12345);
+        assert!(gadget.execute(&mut values_small));
+        assert_eq!((1 << 40) + 12345, values_small.get_uint(&sum));
+
+        let mut values_saturated = WireValues::new();
+        values_saturated.set_uint(&x, MAX - 10);
+        values_saturated.set_uint(&y, 1 << 63);
+        assert!(gadget.execute(&mut values_saturated));
+        assert_eq!(MAX, values_saturated.get_uint(&sum));
+    }
+
+    #[test]
+    fn mul_add_matches_native() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let (a, b, c) = (builder.binary_wire(64), builder.binary_wire(64), builder.binary_wire(64));
+        let (a_uint, b_uint, c_uint) = (UInt::from(&a), UInt::from(&b), UInt::from(&c));
+        let ab = a_uint.wrapping_mul(&mut builder, &b_uint);
+        let result = ab.wrapping_add(&mut builder, &c_uint);
+        let lt = a_uint.lt(&mut builder, &b_uint);
+        let ge = a_uint.ge(&mut builder, &b_uint);
+        let gadget = builder.build();
+
+        let test_cases = [
+            (3u128, 5u128, 7u128),
+            (MAX, MAX, MAX),
+            (0xDEAD_BEEF_CAFE_BABE, 0x1234_5678_9ABC_DEF0, 0xFFFF_0000_FFFF_0000),
+        ];
+        for &(a_value, b_value, c_value) in test_cases.iter() {
+            let mut values = WireValues::new();
+            values.set_uint(&a, a_value);
+            values.set_uint(&b, b_value);
+            values.set_uint(&c, c_value);
+            assert!(gadget.execute(&mut values));
+
+            let expected = (a_value.wrapping_mul(b_value) & MAX).wrapping_add(c_value) & MAX;
+            assert_eq!(expected, values.get_uint(&result));
+            assert_eq!(a_value < b_value, lt.evaluate(&values));
+            assert_eq!(a_value >= b_value, ge.evaluate(&values));
+        }
+    }
+
+    #[test]
+    fn constants() {
+        let values = WireValues::<Bn128>::new();
+        assert_eq!(42, values.get_uint(&UInt::constant(42, 64)));
+        assert_eq!(MAX, values.get_uint(&UInt::max_value(64)));
+        assert_eq!(64, UInt::<Bn128>::constant(0, 64).bits());
+    }
+
+    #[test]
+    #[should_panic(expected = "wrapping_add: mismatched widths, 64 bits and 32 bits")]
+    fn mismatched_widths() {
+        let mut builder = GadgetBuilder::<Bn128>::new();
+        let x = UInt::from(builder.binary_wire(64));
+        let y = UInt::from(builder.binary_wire(32));
+        x.wrapping_add(&mut builder, &y);
+    }
+}
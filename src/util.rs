@@ -5,6 +5,15 @@ use alloc::vec::Vec;
 use alloc::string::String;
 
 use core::borrow::Borrow;
+use core::fmt;
+use core::hash::Hasher;
+
+use num::BigUint;
+
+use crate::expression::Expression;
+use crate::field::{Element, Field};
+use crate::wire::Wire;
+use crate::wire_values::WireValues;
 
 /// Like SliceConcatExt::concat, but works in stable with no_std.
 /// See https://github.com/rust-lang/rust/issues/27747
@@ -28,4 +37,100 @@ pub fn join<S: Borrow<str>>(sep: &str, strings: &[S]) -> String {
         builder += s.borrow();
     }
     builder
-}
\ No newline at end of file
+}
+
+/// Converts each wire to an expression.
+pub fn expressions_from_wires<F: Field>(wires: &[Wire]) -> Vec<Expression<F>> {
+    wires.iter().map(Expression::from).collect()
+}
+
+/// Converts each element to a constant expression.
+pub fn expressions_from_elements<F: Field>(elements: &[Element<F>]) -> Vec<Expression<F>> {
+    elements.iter().map(Expression::from).collect()
+}
+
+/// Evaluates each expression with the given wire values.
+pub fn evaluate_all<F: Field>(
+    expressions: &[Expression<F>], values: &WireValues<F>,
+) -> Vec<Element<F>> {
+    expressions.iter().map(|expression| expression.evaluate(values)).collect()
+}
+
+/// Converts each integer to a field element, failing if any is not less than the field order.
+/// Unlike `Element::from`, this doesn't panic on out-of-range values.
+pub fn elements_from_biguints<F: Field>(
+    values: &[BigUint],
+) -> Result<Vec<Element<F>>, ElementRangeError> {
+    let order = F::order();
+    values.iter().enumerate()
+        .map(|(index, value)| if value < &order {
+            Ok(Element::from(value.clone()))
+        } else {
+            Err(ElementRangeError { index })
+        })
+        .collect()
+}
+
+/// An error indicating that an integer was too large to convert to a field element.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ElementRangeError {
+    /// The index of the first out-of-range integer.
+    pub index: usize,
+}
+
+impl fmt::Display for ElementRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Value at index {} is not less than the field order", self.index)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ElementRangeError {}
+
+/// A 64-bit FNV-1a hasher. Unlike `DefaultHasher`, it works with no_std, and it isn't randomly
+/// seeded, so equal values hash the same way in every run.
+pub struct FnvHasher {
+    state: u64,
+}
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher { state: 0xcbf2_9ce4_8422_2325 }
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= u64::from(byte);
+            self.state = self.state.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::field::Element;
+    use crate::fields::small::F257;
+    use crate::util::{elements_from_biguints, ElementRangeError};
+
+    #[test]
+    fn elements_from_biguints_in_range() {
+        let values = [BigUint::from(0u8), BigUint::from(7u8), BigUint::from(256u16)];
+        let elements = elements_from_biguints::<F257>(&values).unwrap();
+        assert_eq!(vec![Element::zero(), Element::from(7u8), Element::from(256u16)], elements);
+        assert_eq!(Ok(vec![]), elements_from_biguints::<F257>(&[]));
+    }
+
+    #[test]
+    fn elements_from_biguints_out_of_range() {
+        let values = [BigUint::from(1u8), BigUint::from(257u16), BigUint::from(1000u16)];
+        assert_eq!(Err(ElementRangeError { index: 1 }), elements_from_biguints::<F257>(&values));
+    }
+}
@@ -2,6 +2,8 @@
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
 
 #[cfg(feature = "std")]
 use std::collections::BTreeMap;
@@ -14,133 +16,352 @@ use crate::field::{Element, Field};
 use crate::gadget_builder::GadgetBuilder;
 use crate::wire::{BooleanWire, Wire};
 use crate::wire_values::WireValues;
-use crate::util::concat;
+use crate::util::{concat, join};
 
 impl<F: Field> GadgetBuilder<F> {
     /// Assert that two lists of expressions evaluate to permutations of one another.
     ///
     /// This is currently implemented using an AS-Waksman permutation network, although that could
     /// change in the future. See "On Arbitrary Waksman Networks and their Vulnerability".
+    ///
+    /// If both lists are entirely constant, they are compared immediately, without adding any
+    /// constraints, and this panics if they are not permutations of one another. If the
+    /// permutation is known in advance, `assert_permutation_fixed` is much cheaper.
     pub fn assert_permutation(&mut self, a: &[Expression<F>], b: &[Expression<F>]) {
-        assert_eq!(a.len(), b.len(), "Permutation must have same number of inputs and outputs");
-
-        match a.len() {
-            // Two empty lists are permutations of one another, trivially.
-            0 => (),
-            // Two singleton lists are permutations of one another as long as their items are equal.
-            1 => self.assert_equal(&a[0], &b[0]),
-            // For the 2x2 case, we're implementing a switch gadget. The switch will be controlled
-            // with a binary wire. It will swap swap the order of its inputs iff that wire is true.
-            2 => self.assert_permutation_2x2(&a[0], &a[1], &b[0], &b[1]),
-            // For larger lists, we recursively use two smaller permutation networks.
-            _ => self.assert_permutation_recursive(a, b)
-        }
+        logged!(self, "assert_permutation", [a.len(), b.len()], {
+            let a_constants: Option<Vec<Element<F>>> =
+                a.iter().map(Expression::as_constant).collect();
+            let b_constants: Option<Vec<Element<F>>> =
+                b.iter().map(Expression::as_constant).collect();
+            if let (Some(mut a_constants), Some(mut b_constants)) = (a_constants, b_constants) {
+                a_constants.sort();
+                b_constants.sort();
+                assert!(a_constants == b_constants,
+                        "Constant lists are not permutations of one another");
+                return;
+            }
+
+            let a_rows: Vec<Vec<Expression<F>>> = a.iter().map(|x| vec![x.clone()]).collect();
+            let b_rows: Vec<Vec<Expression<F>>> = b.iter().map(|x| vec![x.clone()]).collect();
+            self.assert_permutation_rows(&a_rows, &b_rows, 1);
+        })
     }
 
-    /// Assert that [a, b] is a permutation of [c, d].
-    fn assert_permutation_2x2(&mut self,
-                              a: &Expression<F>, b: &Expression<F>,
-                              c: &Expression<F>, d: &Expression<F>) {
-        let (switch, c_target, d_target) = self.create_switch(a, b);
-        self.assert_equal(c, &c_target);
-        self.assert_equal(d, &d_target);
-        let a = a.clone();
-        let b = b.clone();
-        let c = c.clone();
-        let d = d.clone();
-        self.generator(
-            concat(&[a.dependencies(), b.dependencies(), c.dependencies(), d.dependencies()]),
+    /// Assert that two lists of rows evaluate to permutations of one another, where each row is a
+    /// tuple of `row_width` expressions. Rows are treated atomically, so this is stronger than
+    /// asserting that each column is a permutation.
+    ///
+    /// This uses the same AS-Waksman network as `assert_permutation`, except that each switch
+    /// swaps entire rows. A single boolean switch wire is shared by all columns of a switch, so
+    /// wider rows cost one additional constraint per column per switch.
+    pub fn assert_permutation_rows(
+        &mut self, a: &[Vec<Expression<F>>], b: &[Vec<Expression<F>>], row_width: usize,
+    ) {
+        logged!(self, "assert_permutation_rows", [a.len(), b.len(), row_width], {
+            assert_eq!(a.len(), b.len(), "Permutation must have same number of inputs and outputs");
+            for row in a.iter().chain(b.iter()) {
+                assert_eq!(row.len(), row_width, "Rows must have uniform width");
+            }
+            self.assert_row_permutation(a, b);
+        })
+    }
+
+    /// Assert that `b[perm[i]] = a[i]` for each `i`, where `perm` is a permutation known when the
+    /// gadget is built, such as a bit reversal. This costs one constraint per element, with no
+    /// switches. Panics if `perm` is not a permutation of `0..a.len()`.
+    ///
+    /// If `b` doesn't need to be supplied separately, `apply_fixed_permutation` computes it with
+    /// no constraints at all.
+    pub fn assert_permutation_fixed(
+        &mut self, a: &[Expression<F>], b: &[Expression<F>], perm: &[usize],
+    ) {
+        logged!(self, "assert_permutation_fixed", [a.len(), b.len(), perm.len()], {
+            assert_eq!(a.len(), b.len(), "Permutation must have same number of inputs and outputs");
+            assert_eq!(a.len(), perm.len(), "Permutation must have one index per input");
+            inverse_permutation(perm);
+            for (a_i, &j) in a.iter().zip(perm) {
+                self.assert_equal(&b[j], a_i);
+            }
+        })
+    }
+
+    fn assert_row_permutation(&mut self, a: &[Vec<Expression<F>>], b: &[Vec<Expression<F>>]) {
+        let network = self.create_permutation_network(a, b);
+
+        // Networks with fewer than two inputs have no switches, so there is nothing to route.
+        if a.len() < 2 {
+            return;
+        }
+
+        // A single generator routes the entire network. The inputs and outputs are evaluated once
+        // here, and inner subnetworks are routed on the resulting values.
+        let a = a.to_vec();
+        let b = b.to_vec();
+        let switches = network.iter()
+            .flat_map(|subnetwork| match subnetwork {
+                Subnetwork::Trivial => vec![],
+                Subnetwork::Switch(switch) => vec![switch.wire()],
+                Subnetwork::Recursive { a_switches, b_switches, .. } =>
+                    a_switches.iter().chain(b_switches).map(|switch| switch.wire()).collect(),
+            })
+            .collect();
+        self.generator_with_outputs(
+            concat(&[rows_dependencies(&a), rows_dependencies(&b)]),
+            switches,
             move |values: &mut WireValues<F>| {
-                let a_value = a.evaluate(values);
-                let b_value = b.evaluate(values);
-                let c_value = c.evaluate(values);
-                let d_value = d.evaluate(values);
-                if a_value == c_value && b_value == d_value {
-                    values.set_boolean(switch, false);
-                } else if a_value == d_value && b_value == c_value {
-                    values.set_boolean(switch, true);
-                } else {
-                    panic!("No permutation from [{}, {}] to [{}, {}]",
-                           a_value, b_value, c_value, d_value);
-                }
+                let a_values: Vec<Vec<Element<F>>> =
+                    a.iter().map(|row| evaluate_row(row, values)).collect();
+                let b_values: Vec<Vec<Element<F>>> =
+                    b.iter().map(|row| evaluate_row(row, values)).collect();
+                route_network(&network, a_values, b_values, values);
             });
     }
 
-    /// Creates a 2x2 switch given the two input expressions. Returns three things: the (boolean)
-    /// switch wire and the two output expressions. The order of the outputs will match that of the
-    /// inputs if the switch wire is set to false, otherwise the order will be swapped.
-    fn create_switch(&mut self, a: &Expression<F>, b: &Expression<F>)
-                     -> (BooleanWire, Expression<F>, Expression<F>) {
+    /// Creates the switches of an AS-Waksman network from `a` to `b`, returning a description of
+    /// the network which can later be used to route it. Subnetworks are built with an explicit
+    /// stack rather than recursion, in the same order that a recursive construction would use.
+    fn create_permutation_network(
+        &mut self, a: &[Vec<Expression<F>>], b: &[Vec<Expression<F>>],
+    ) -> Vec<Subnetwork> {
+        let mut network = Vec::new();
+
+        // Each pending entry holds the inputs and outputs of a subnetwork, along with its parent's
+        // index and its position among the parent's children, if it has a parent.
+        let mut pending = vec![(a.to_vec(), b.to_vec(), None)];
+
+        while let Some((a, b, parent)) = pending.pop() {
+            let index = network.len();
+            if let Some((parent_index, child)) = parent {
+                if let Subnetwork::Recursive { children, .. } = &mut network[parent_index] {
+                    children[child] = index;
+                }
+            }
+
+            let n = a.len();
+            let subnetwork = match n {
+                // Two empty lists are permutations of one another, trivially.
+                0 => Subnetwork::Trivial,
+                // Two singleton lists are permutations of one another as long as their rows are
+                // equal.
+                1 => {
+                    self.assert_rows_equal(&a[0], &b[0]);
+                    Subnetwork::Trivial
+                }
+                // For the 2x2 case, we're implementing a switch gadget. The switch will be
+                // controlled with a binary wire. It will swap the order of its inputs iff that wire
+                // is true.
+                2 => {
+                    let (switch, c_target, d_target) = self.create_switch(&a[0], &a[1]);
+                    self.assert_rows_equal(&b[0], &c_target);
+                    self.assert_rows_equal(&b[1], &d_target);
+                    Subnetwork::Switch(switch)
+                }
+                // For larger lists, we use two smaller permutation networks.
+                _ => {
+                    let even = n.is_multiple_of(2);
+
+                    let mut child_1_a = Vec::new();
+                    let mut child_1_b = Vec::new();
+                    let mut child_2_a = Vec::new();
+                    let mut child_2_b = Vec::new();
+
+                    // See Figure 8 in the AS-Waksman paper.
+                    let a_num_switches = n / 2;
+                    let b_num_switches = if even { a_num_switches - 1 } else { a_num_switches };
+
+                    let mut a_switches = Vec::new();
+                    let mut b_switches = Vec::new();
+                    for i in 0..a_num_switches {
+                        let (switch, out_1, out_2) = self.create_switch(&a[i * 2], &a[i * 2 + 1]);
+                        a_switches.push(switch);
+                        child_1_a.push(out_1);
+                        child_2_a.push(out_2);
+                    }
+                    for i in 0..b_num_switches {
+                        let (switch, out_1, out_2) = self.create_switch(&b[i * 2], &b[i * 2 + 1]);
+                        b_switches.push(switch);
+                        child_1_b.push(out_1);
+                        child_2_b.push(out_2);
+                    }
+
+                    // See Figure 8 in the AS-Waksman paper.
+                    if even {
+                        child_1_b.push(b[n - 2].clone());
+                        child_2_b.push(b[n - 1].clone());
+                    } else {
+                        child_2_a.push(a[n - 1].clone());
+                        child_2_b.push(b[n - 1].clone());
+                    }
+
+                    // Push the second child first, so that the first child is built first.
+                    pending.push((child_2_a, child_2_b, Some((index, 1))));
+                    pending.push((child_1_a, child_1_b, Some((index, 0))));
+                    Subnetwork::Recursive { a_switches, b_switches, children: [0, 0] }
+                }
+            };
+            network.push(subnetwork);
+        }
+
+        network
+    }
+
+    fn assert_rows_equal(&mut self, x: &[Expression<F>], y: &[Expression<F>]) {
+        for (x_i, y_i) in x.iter().zip(y.iter()) {
+            self.assert_equal(x_i, y_i);
+        }
+    }
+
+    /// Creates a 2x2 switch given the two input rows. Returns three things: the (boolean) switch
+    /// wire and the two output rows. The order of the outputs will match that of the inputs if the
+    /// switch wire is set to false, otherwise the order will be swapped. The same switch wire
+    /// controls every column.
+    fn create_switch(&mut self, a: &[Expression<F>], b: &[Expression<F>])
+                     -> (BooleanWire, Vec<Expression<F>>, Vec<Expression<F>>) {
         let switch = self.boolean_wire();
-        let c = self.selection(&BooleanExpression::from(switch), b, a);
-        let d = a + b - &c;
+        let switch_exp = BooleanExpression::from(switch);
+        let mut c = Vec::with_capacity(a.len());
+        let mut d = Vec::with_capacity(a.len());
+        for (a_i, b_i) in a.iter().zip(b.iter()) {
+            let c_i = self.selection(&switch_exp, b_i, a_i);
+            d.push(a_i + b_i - &c_i);
+            c.push(c_i);
+        }
         (switch, c, d)
     }
+}
 
-    fn assert_permutation_recursive(&mut self, a: &[Expression<F>], b: &[Expression<F>]) {
-        let n = a.len();
-        let even = n % 2 == 0;
-
-        let mut child_1_a = Vec::new();
-        let mut child_1_b = Vec::new();
-        let mut child_2_a = Vec::new();
-        let mut child_2_b = Vec::new();
-
-        // See Figure 8 in the AS-Waksman paper.
-        let a_num_switches = n / 2;
-        let b_num_switches = if even { a_num_switches - 1 } else { a_num_switches };
-
-        let mut a_switches = Vec::new();
-        let mut b_switches = Vec::new();
-        for i in 0..a_num_switches {
-            let (switch, out_1, out_2) = self.create_switch(&a[i * 2], &a[i * 2 + 1]);
-            a_switches.push(switch);
-            child_1_a.push(out_1);
-            child_2_a.push(out_2);
-        }
-        for i in 0..b_num_switches {
-            let (switch, out_1, out_2) = self.create_switch(&b[i * 2], &b[i * 2 + 1]);
-            b_switches.push(switch);
-            child_1_b.push(out_1);
-            child_2_b.push(out_2);
+/// Reorders `a` by the permutation `perm`, which must be known when the gadget is built, so that
+/// `a[i]` moves to index `perm[i]` of the result. Since this just rearranges expressions, it adds
+/// no constraints. Panics if `perm` is not a permutation of `0..a.len()`.
+pub fn apply_fixed_permutation<F: Field>(
+    a: &[Expression<F>], perm: &[usize],
+) -> Vec<Expression<F>> {
+    assert_eq!(a.len(), perm.len(), "Permutation must have one index per input");
+    inverse_permutation(perm).iter().map(|&i| a[i].clone()).collect()
+}
+
+/// The inverse of `perm`, i.e. the permutation mapping `perm[i]` to `i`. Panics if `perm` is not a
+/// permutation of `0..perm.len()`.
+fn inverse_permutation(perm: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![None; perm.len()];
+    for (i, &j) in perm.iter().enumerate() {
+        assert!(j < perm.len(), "Permutation index {} is out of range", j);
+        assert!(inverse[j].is_none(), "Permutation maps several indices to {}", j);
+        inverse[j] = Some(i);
+    }
+    inverse.into_iter().map(Option::unwrap).collect()
+}
+
+/// A subnetwork of an AS-Waksman network. A network is stored as a list of subnetworks in the
+/// order they were built, with the outermost subnetwork first.
+enum Subnetwork {
+    /// A subnetwork with fewer than two inputs, which has no switches.
+    Trivial,
+    /// A single 2x2 switch.
+    Switch(BooleanWire),
+    /// A layer of input switches and a layer of output switches, connected by two smaller
+    /// subnetworks whose indices are given by `children`.
+    Recursive { a_switches: Vec<BooleanWire>, b_switches: Vec<BooleanWire>, children: [usize; 2] },
+}
+
+/// Sets every switch in `network` so that it maps `a_values` to `b_values`. Inner subnetworks are
+/// routed with an explicit stack, on the values produced by applying the outer switch settings.
+fn route_network<F: Field>(network: &[Subnetwork],
+                           a_values: Vec<Vec<Element<F>>>, b_values: Vec<Vec<Element<F>>>,
+                           values: &mut WireValues<F>) {
+    let mut pending = vec![(0, a_values, b_values)];
+
+    while let Some((index, a, b)) = pending.pop() {
+        match &network[index] {
+            Subnetwork::Trivial => (),
+            Subnetwork::Switch(switch) => {
+                if a[0] == b[0] && a[1] == b[1] {
+                    values.set_boolean(*switch, false);
+                } else if a[0] == b[1] && a[1] == b[0] {
+                    values.set_boolean(*switch, true);
+                } else {
+                    panic!("No permutation from [{}, {}] to [{}, {}]",
+                           row_to_string(&a[0]), row_to_string(&a[1]),
+                           row_to_string(&b[0]), row_to_string(&b[1]));
+                }
+            }
+            Subnetwork::Recursive { a_switches, b_switches, children } => {
+                let n = a.len();
+                let [a_swaps, b_swaps] = route(&a, &b, a_switches.len(), b_switches.len());
+                for (&switch, &swap) in a_switches.iter().zip(a_swaps.iter())
+                    .chain(b_switches.iter().zip(b_swaps.iter())) {
+                    values.set_boolean(switch, swap);
+                }
+
+                let (child_1_a, mut child_2_a) = apply_switches(&a, &a_swaps);
+                let (mut child_1_b, mut child_2_b) = apply_switches(&b, &b_swaps);
+
+                // See Figure 8 in the AS-Waksman paper.
+                if n.is_multiple_of(2) {
+                    child_1_b.push(b[n - 2].clone());
+                    child_2_b.push(b[n - 1].clone());
+                } else {
+                    child_2_a.push(a[n - 1].clone());
+                    child_2_b.push(b[n - 1].clone());
+                }
+
+                pending.push((children[1], child_2_a, child_2_b));
+                pending.push((children[0], child_1_a, child_1_b));
+            }
         }
+    }
+}
 
-        // See Figure 8 in the AS-Waksman paper.
-        if even {
-            child_1_b.push(b[n - 2].clone());
-            child_2_b.push(b[n - 1].clone());
+/// Applies a layer of switches to the given rows, returning the rows sent to the first and second
+/// subnetworks. Rows which don't pass through a switch are omitted.
+fn apply_switches<T: Clone>(rows: &[T], swaps: &[bool]) -> (Vec<T>, Vec<T>) {
+    let mut out_1 = Vec::with_capacity(swaps.len());
+    let mut out_2 = Vec::with_capacity(swaps.len());
+    for (i, &swap) in swaps.iter().enumerate() {
+        let (first, second) = (&rows[i * 2], &rows[i * 2 + 1]);
+        if swap {
+            out_1.push(second.clone());
+            out_2.push(first.clone());
         } else {
-            child_2_a.push(a[n - 1].clone());
-            child_2_b.push(b[n - 1].clone());
+            out_1.push(first.clone());
+            out_2.push(second.clone());
         }
+    }
+    (out_1, out_2)
+}
 
-        self.assert_permutation(&child_1_a, &child_1_b);
-        self.assert_permutation(&child_2_a, &child_2_b);
+fn rows_dependencies<F: Field>(rows: &[Vec<Expression<F>>]) -> Vec<Wire> {
+    rows.iter().flat_map(|row| row_dependencies(row)).collect()
+}
 
-        let a_deps: Vec<Wire> = a.iter().flat_map(Expression::dependencies).collect();
-        let b_deps: Vec<Wire> = b.iter().flat_map(Expression::dependencies).collect();
+fn row_dependencies<F: Field>(row: &[Expression<F>]) -> Vec<Wire> {
+    row.iter().flat_map(Expression::dependencies).collect()
+}
 
-        let a = a.to_vec();
-        let b = b.to_vec();
-        self.generator(
-            concat(&[a_deps, b_deps]),
-            move |values: &mut WireValues<F>| {
-                let a_values: Vec<Element<F>> = a.iter().map(|exp| exp.evaluate(values)).collect();
-                let b_values: Vec<Element<F>> = b.iter().map(|exp| exp.evaluate(values)).collect();
-                route(a_values, b_values, &a_switches, &b_switches, values);
-            });
+fn evaluate_row<F: Field>(row: &[Expression<F>], values: &WireValues<F>) -> Vec<Element<F>> {
+    row.iter().map(|exp| exp.evaluate(values)).collect()
+}
+
+/// Formats a row of values for error messages. Rows of width 1 are shown as a bare value.
+fn row_to_string<F: Field>(row: &[Element<F>]) -> String {
+    let strings: Vec<String> = row.iter().map(|x| format!("{}", x)).collect();
+    if strings.len() == 1 {
+        strings[0].clone()
+    } else {
+        format!("({})", join(", ", &strings))
     }
 }
 
-/// Generates switch settings for a single layer of the recursive network.
-fn route<F: Field>(a_values: Vec<Element<F>>, b_values: Vec<Element<F>>,
-                   a_switches: &[BooleanWire], b_switches: &[BooleanWire],
-                   values: &mut WireValues<F>) {
+/// Generates switch settings for a single layer of the recursive network, given the number of
+/// switches on each side. Returns the settings of the `a` switches and the `b` switches.
+fn route<T: Clone + Ord>(a_values: &[T], b_values: &[T],
+                         a_num_switches: usize, b_num_switches: usize)
+                         -> [Vec<bool>; 2] {
     assert_eq!(a_values.len(), b_values.len());
     let n = a_values.len();
-    let even = n % 2 == 0;
-    let ab_map = bimap_from_lists(a_values, b_values);
-    let switches = [a_switches, b_switches];
+    let even = n.is_multiple_of(2);
+    let ab_map = bimap_from_lists(a_values.to_vec(), b_values.to_vec());
+    let mut settings = [vec![None; a_num_switches], vec![None; b_num_switches]];
 
     let ab_map_by_side = |side: usize, index: usize| -> usize {
         *match side {
@@ -158,18 +379,18 @@ fn route<F: Field>(a_values: Vec<Element<F>>, b_values: Vec<Element<F>>,
     // After we route a wire on one side, we find the corresponding wire on the other side and check
     // if it still needs to be routed. If so, we add it to partial_routes.
     let enqueue_other_side = |partial_routes: &mut [BTreeMap<usize, bool>],
-                              values: &mut WireValues<F>,
+                              settings: &[Vec<Option<bool>>],
                               side: usize, this_i: usize, subnet: bool| {
         let other_side = 1 - side;
         let other_i = ab_map_by_side(side, this_i);
         let other_switch_i = other_i / 2;
 
-        if other_switch_i >= switches[other_side].len() {
+        if other_switch_i >= settings[other_side].len() {
             // The other wire doesn't go through a switch, so there's no routing to be done.
             return;
         }
 
-        if values.contains_boolean(switches[other_side][other_switch_i]) {
+        if settings[other_side][other_switch_i].is_some() {
             // The other switch has already been routed.
             return;
         }
@@ -188,24 +409,25 @@ fn route<F: Field>(a_values: Vec<Element<F>>, b_values: Vec<Element<F>>,
 
     // See Figure 8 in the AS-Waksman paper.
     if even {
-        enqueue_other_side(&mut partial_routes, values, 1, n - 2, false);
-        enqueue_other_side(&mut partial_routes, values, 1, n - 1, true);
+        enqueue_other_side(&mut partial_routes, &settings, 1, n - 2, false);
+        enqueue_other_side(&mut partial_routes, &settings, 1, n - 1, true);
     } else {
-        enqueue_other_side(&mut partial_routes, values, 0, n - 1, true);
-        enqueue_other_side(&mut partial_routes, values, 1, n - 1, true);
+        enqueue_other_side(&mut partial_routes, &settings, 0, n - 1, true);
+        enqueue_other_side(&mut partial_routes, &settings, 1, n - 1, true);
     }
 
-    let route_switch = |partial_routes: &mut [BTreeMap<usize, bool>], values: &mut WireValues<F>,
+    let route_switch = |partial_routes: &mut [BTreeMap<usize, bool>],
+                        settings: &mut [Vec<Option<bool>>],
                         side: usize, switch_index: usize, swap: bool| {
         // First, we actually set the switch configuration.
-        values.set_boolean(switches[side][switch_index], swap);
+        settings[side][switch_index] = Some(swap);
 
         // Then, we enqueue the two corresponding wires on the other side of the network, to ensure
         // that they get routed in the next step.
         let this_i_1 = switch_index * 2;
         let this_i_2 = this_i_1 + 1;
-        enqueue_other_side(partial_routes, values, side, this_i_1, swap);
-        enqueue_other_side(partial_routes, values, side, this_i_2, !swap);
+        enqueue_other_side(partial_routes, settings, side, this_i_1, swap);
+        enqueue_other_side(partial_routes, settings, side, this_i_2, !swap);
     };
 
     // If {a,b}_only_routes is empty, then we can route any switch next. For efficiency, we will
@@ -216,30 +438,32 @@ fn route<F: Field>(a_values: Vec<Element<F>>, b_values: Vec<Element<F>>,
     // Until both scans complete, we alternate back and worth between the left and right switch
     // layers. We process any partially routed wires for that side, or if there aren't any, we route
     // the next switch in our scan.
-    while scan_index[0] < switches[0].len() || scan_index[1] < switches[1].len() {
+    while scan_index[0] < settings[0].len() || scan_index[1] < settings[1].len() {
         for side in 0..=1 {
             if !partial_routes[side].is_empty() {
                 for (this_i, subnet) in partial_routes[side].clone().into_iter() {
                     let this_first_switch_input = this_i % 2 == 0;
                     let swap = this_first_switch_input == subnet;
                     let this_switch_i = this_i / 2;
-                    route_switch(&mut partial_routes, values, side, this_switch_i, swap);
+                    route_switch(&mut partial_routes, &mut settings, side, this_switch_i, swap);
                 }
                 partial_routes[side].clear();
             } else {
                 // We can route any switch next. Continue our scan for pending switches.
-                while scan_index[side] < switches[side].len()
-                    && values.contains_boolean(switches[side][scan_index[side]]) {
+                while scan_index[side] < settings[side].len()
+                    && settings[side][scan_index[side]].is_some() {
                     scan_index[side] += 1;
                 }
-                if scan_index[side] < switches[side].len() {
+                if scan_index[side] < settings[side].len() {
                     // Either switch configuration would work; we arbitrarily choose to not swap.
-                    route_switch(&mut partial_routes, values, side, scan_index[side], false);
+                    route_switch(&mut partial_routes, &mut settings, side, scan_index[side], false);
                     scan_index[side] += 1;
                 }
             }
         }
     }
+
+    settings.map(|side| side.into_iter().map(|setting| setting.unwrap()).collect())
 }
 
 #[cfg(test)]
@@ -247,8 +471,10 @@ mod tests {
     use itertools::Itertools;
 
     use crate::expression::Expression;
+    use crate::field::Element;
     use crate::gadget_builder::GadgetBuilder;
-    use crate::test_util::F257;
+    use crate::fields::small::F257;
+    use crate::verify_permutation::apply_fixed_permutation;
     use crate::wire_values::WireValues;
 
     #[test]
@@ -284,10 +510,8 @@ mod tests {
     #[test]
     fn route_5x5() {
         let mut builder = GadgetBuilder::<F257>::new();
-        let a = builder.wires(5);
-        let b = builder.wires(5);
-        let a_exp = a.iter().map(Expression::from).collect_vec();
-        let b_exp = b.iter().map(Expression::from).collect_vec();
+        let (a, a_exp) = builder.expression_wires(5);
+        let (b, b_exp) = builder.expression_wires(5);
         builder.assert_permutation(&a_exp, &b_exp);
         let gadget = builder.build();
 
@@ -306,14 +530,205 @@ mod tests {
     #[should_panic]
     fn not_a_permutation() {
         let mut builder = GadgetBuilder::<F257>::new();
-        builder.assert_permutation(
-            &[1u8.into(), 2u8.into(), 2u8.into()],
-            &[1u8.into(), 2u8.into(), 1u8.into()]);
+        let (a, a_exp) = builder.expression_wires(3);
+        builder.assert_permutation(&a_exp, &[1u8.into(), 2u8.into(), 1u8.into()]);
+        let gadget = builder.build();
+        // The generator should fail, since there's no possible routing.
+        gadget.execute(&mut values!(a[0] => 1u8.into(), a[1] => 2u8.into(), a[2] => 2u8.into()));
+    }
+
+    #[test]
+    fn route_1024() {
+        let n = 1024;
+        let mut builder = GadgetBuilder::<F257>::new();
+        let a = builder.wires(n);
+        let b = builder.wires(n);
+        let a_exp = a.iter().map(Expression::from).collect_vec();
+        let b_exp = b.iter().map(Expression::from).collect_vec();
+        builder.assert_permutation(&a_exp, &b_exp);
+        let gadget = builder.build();
+
+        // Pseudorandom values from a linear congruential generator, and a Fisher-Yates shuffle of
+        // them driven by the same generator. With 1024 values in F257, there are many duplicates.
+        let mut state = 12345u64;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 16
+        };
+        let a_values = (0..n).map(|_| next() % 257).collect_vec();
+        let mut b_values = a_values.clone();
+        for i in (1..n).rev() {
+            b_values.swap(i, next() as usize % (i + 1));
+        }
+
+        let mut values = WireValues::new();
+        for (&wire, &value) in a.iter().zip(a_values.iter()).chain(b.iter().zip(b_values.iter())) {
+            values.set(wire, Element::from(value));
+        }
+        assert!(gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn route_rows_4x3() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let a = (0..4).map(|_i| builder.wires(3)).collect_vec();
+        let b = (0..4).map(|_i| builder.wires(3)).collect_vec();
+        let a_exp = a.iter()
+            .map(|row| row.iter().map(Expression::from).collect_vec())
+            .collect_vec();
+        let b_exp = b.iter()
+            .map(|row| row.iter().map(Expression::from).collect_vec())
+            .collect_vec();
+        builder.assert_permutation_rows(&a_exp, &b_exp, 3);
+        let gadget = builder.build();
+
+        let a_rows = [[1u8, 2, 3], [4, 5, 6], [7, 8, 9], [10, 11, 12]];
+        let b_rows = [[7u8, 8, 9], [1, 2, 3], [10, 11, 12], [4, 5, 6]];
+        let mut values = WireValues::new();
+        for (wires, row) in a.iter().zip(a_rows.iter()).chain(b.iter().zip(b_rows.iter())) {
+            for (&wire, &value) in wires.iter().zip(row.iter()) {
+                values.set(wire, value.into());
+            }
+        }
+        assert!(gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn route_rows_sharing_first_column() {
+        // Every row has the same first component, so routing must match on the full row.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let row = |x: u8, y: u8| vec![Expression::from(x), Expression::from(y)];
+        builder.assert_permutation_rows(
+            &[row(1, 2), row(1, 3), row(1, 4)],
+            &[row(1, 4), row(1, 2), row(1, 3)],
+            2);
+        let gadget = builder.build();
+        assert!(gadget.execute(&mut WireValues::new()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rows_not_a_permutation() {
+        // Each column is a permutation of the corresponding column, but the rows are not.
+        let mut builder = GadgetBuilder::<F257>::new();
+        let row = |x: u8, y: u8| vec![Expression::from(x), Expression::from(y)];
+        builder.assert_permutation_rows(
+            &[row(1, 2), row(3, 4), row(5, 6)],
+            &[row(1, 4), row(3, 2), row(5, 6)],
+            2);
         let gadget = builder.build();
         // The generator should fail, since there's no possible routing.
         gadget.execute(&mut WireValues::new());
     }
 
+    /// Rotation by 3, and bit reversal of 3-bit indices.
+    fn fixed_permutations() -> [Vec<usize>; 2] {
+        let rotation = (0..8).map(|i| (i + 3) % 8).collect();
+        let bit_reversal = (0..8usize).map(|i| i.reverse_bits() >> (usize::BITS - 3)).collect();
+        [rotation, bit_reversal]
+    }
+
+    #[test]
+    fn apply_fixed_permutation_reorders() {
+        for perm in &fixed_permutations() {
+            let a: Vec<Expression<F257>> = (0..8u8).map(Expression::from).collect();
+            let b = apply_fixed_permutation(&a, perm);
+            for (i, &j) in perm.iter().enumerate() {
+                assert_eq!(a[i], b[j]);
+            }
+        }
+    }
+
+    #[test]
+    fn assert_permutation_fixed() {
+        for perm in &fixed_permutations() {
+            let mut builder = GadgetBuilder::<F257>::new();
+            let (a, a_exp) = builder.expression_wires(8);
+            let (b, b_exp) = builder.expression_wires(8);
+            builder.assert_permutation_fixed(&a_exp, &b_exp, perm);
+            let gadget = builder.build();
+            assert_eq!(8, gadget.size());
+
+            let values = |b_values: &[usize]| {
+                let mut values = WireValues::new();
+                for i in 0..8 {
+                    values.set(a[i], Element::from(10 * i));
+                    values.set(b[i], Element::from(10 * b_values[i]));
+                }
+                values
+            };
+            let mut permuted = vec![0; 8];
+            for (i, &j) in perm.iter().enumerate() {
+                permuted[j] = i;
+            }
+            assert!(gadget.execute(&mut values(&permuted)));
+            // Another permutation of the same values is rejected.
+            permuted.swap(0, 1);
+            assert!(!gadget.execute(&mut values(&permuted)));
+        }
+    }
+
+    #[test]
+    fn fixed_matches_network() {
+        let perm = &fixed_permutations()[1];
+        let mut fixed_builder = GadgetBuilder::<F257>::new();
+        let mut network_builder = GadgetBuilder::<F257>::new();
+        let (a, a_exp) = fixed_builder.expression_wires(8);
+        let (b, b_exp) = fixed_builder.expression_wires(8);
+        network_builder.expression_wires(16);
+        fixed_builder.assert_permutation_fixed(&a_exp, &b_exp, perm);
+        network_builder.assert_permutation(&a_exp, &b_exp);
+        let fixed = fixed_builder.build();
+        let network = network_builder.build();
+        assert!(fixed.size() < network.size());
+
+        // Both accept the fixed permutation, including with duplicate values.
+        for a_values in &[[5u8, 1, 4, 1, 5, 9, 2, 6], [3u8; 8]] {
+            let mut values = WireValues::new();
+            for (i, &j) in perm.iter().enumerate() {
+                values.set(a[i], Element::from(a_values[i]));
+                values.set(b[j], Element::from(a_values[i]));
+            }
+            let mut network_values = values.clone();
+            assert!(fixed.execute(&mut values));
+            assert!(network.execute(&mut network_values));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Permutation maps several indices to 2")]
+    fn assert_permutation_fixed_not_bijective() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (_a, a_exp) = builder.expression_wires(3);
+        let (_b, b_exp) = builder.expression_wires(3);
+        builder.assert_permutation_fixed(&a_exp, &b_exp, &[2, 0, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Permutation index 3 is out of range")]
+    fn apply_fixed_permutation_out_of_range() {
+        let a: Vec<Expression<F257>> = (0..3u8).map(Expression::from).collect();
+        apply_fixed_permutation(&a, &[0, 3, 1]);
+    }
+
+    #[test]
+    fn constant_lists() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.assert_permutation(
+            &[1u8.into(), 2u8.into(), 2u8.into(), 3u8.into()],
+            &[2u8.into(), 3u8.into(), 1u8.into(), 2u8.into()]);
+        assert_eq!(0, builder.build().size());
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant lists are not permutations of one another")]
+    fn constant_lists_not_a_permutation() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        builder.assert_permutation(
+            &[1u8.into(), 2u8.into(), 2u8.into()],
+            &[1u8.into(), 2u8.into(), 1u8.into()]);
+    }
+
     #[test]
     #[should_panic]
     fn lengths_differ() {
@@ -322,4 +737,4 @@ mod tests {
             &[1u8.into(), 2u8.into(), 3u8.into()],
             &[1u8.into(), 2u8.into()]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,197 @@
+//! This module provides a verifiable random function (VRF) gadget over an embedded curve.
+
+use std::marker::PhantomData;
+
+use crate::{Expression, Field, GadgetBuilder, GroupExpression, HashFunction, HashToCurve};
+
+/// An EC-VRF proof: the point `gamma = x H`, where `x` is the prover's private key and `H` is the
+/// input hashed to the curve, along with the challenge `c` and response `s`.
+pub struct EcVrfProof<F: Field, C: HashToCurve<F>> {
+    pub gamma: C::GroupExpression,
+    pub c: Expression<F>,
+    pub s: Expression<F>,
+}
+
+/// An elliptic curve VRF, following the structure of ECVRF from RFC 9381, adapted to a curve
+/// embedded in the constraint field.
+///
+/// A prover with private key `x` and public key `Y = x G` evaluates the VRF on an input `alpha` by
+/// computing `H = hash_to_curve(alpha)` and `gamma = x H`. To prove that `gamma` is correct, it
+/// picks a nonce `k` and computes
+///
+/// ```text
+/// c = hash(G, H, Y, gamma, k G, k H)
+/// s = k - c x (mod the subgroup order)
+/// ```
+///
+/// The output of the VRF is `hash(cofactor * gamma)`.
+///
+/// This deviates from RFC 9381 in several ways, since all hashing happens within the field:
+/// - Points are hashed by their compressed representations, which are single field elements, and
+///   the hash function operates on field elements rather than octet strings. There are no suite
+///   or domain separation strings.
+/// - The challenge `c` is a full field element, rather than being truncated to 128 bits.
+/// - Following `Schnorr`, `s = k - c x` rather than `k + c x`, so that the verifier computes
+///   `U = s G + c Y` and `V = s H + c gamma` without negating points.
+/// - The input is hashed to the curve with `C::hash_to_curve`, rather than with one of the
+///   RFC's encodings.
+///
+/// A naive implementation that has not been optimized or audited.
+pub struct EcVrf<F: Field, C: HashToCurve<F>, HF: HashFunction<F>> {
+    phantom_f: PhantomData<fn() -> F>,
+    phantom_c: PhantomData<fn() -> C>,
+    phantom_hf: PhantomData<fn() -> HF>,
+}
+
+impl<F: Field, C: HashToCurve<F>, HF: HashFunction<F>> EcVrf<F, C, HF> {
+    /// Generates constraints to verify an EC-VRF proof that `proof.gamma` is the VRF evaluation
+    /// of `alpha` under `public_key`, returning the VRF output.
+    ///
+    /// The public key and `proof.gamma` are assumed to be valid group elements; for example, an
+    /// `EdwardsExpression` taken from the witness should be created with `EdwardsExpression::new`,
+    /// which asserts that it lies on the curve. The public key should also be known to lie in the
+    /// subgroup generated by the generator.
+    pub fn verify(
+        builder: &mut GadgetBuilder<F>,
+        public_key: &C::GroupExpression,
+        alpha: &Expression<F>,
+        proof: &EcVrfProof<F, C>,
+        hash: &HF,
+    ) -> Expression<F> {
+        let generator = C::generator_expression();
+        let h = C::hash_to_curve(builder, alpha, hash);
+
+        // U = s G + c Y
+        let gs = C::mul_scalar_expression(builder, &generator, &proof.s);
+        let yc = C::mul_scalar_expression(builder, public_key, &proof.c);
+        let u = C::add_expressions(builder, &gs, &yc);
+
+        // V = s H + c gamma
+        let hs = C::mul_scalar_expression(builder, &h, &proof.s);
+        let gamma_c = C::mul_scalar_expression(builder, &proof.gamma, &proof.c);
+        let v = C::add_expressions(builder, &hs, &gamma_c);
+
+        let challenge = hash.hash(builder, &[
+            generator.compressed().clone(),
+            h.compressed().clone(),
+            public_key.compressed().clone(),
+            proof.gamma.compressed().clone(),
+            u.compressed().clone(),
+            v.compressed().clone(),
+        ]);
+        builder.assert_equal(&challenge, &proof.c);
+
+        let cofactor_gamma = C::clear_cofactor(builder, &proof.gamma);
+        hash.hash(builder, &[cofactor_gamma.compressed().clone()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{Bls12_381, CyclicGroup, EdwardsExpression, EdwardsGroup, EdwardsPoint, Element,
+                Evaluable, Expression, GadgetBuilder, Group, HashFunction, HashToCurve, JubJub,
+                WireValues};
+    use crate::vrf::{EcVrf, EcVrfProof};
+
+    type JubJubGroup = EdwardsGroup<Bls12_381, JubJub>;
+    type JubJubVrf = EcVrf<Bls12_381, JubJubGroup, TestHash>;
+
+    /// Out-of-circuit values for a VRF evaluation.
+    struct Evaluation {
+        public_key: EdwardsPoint<Bls12_381, JubJub>,
+        alpha: Element<Bls12_381>,
+        gamma: EdwardsPoint<Bls12_381, JubJub>,
+        c: Element<Bls12_381>,
+        s: Element<Bls12_381>,
+        output: Element<Bls12_381>,
+    }
+
+    /// Evaluates the VRF and generates a proof out-of-circuit.
+    fn prove(alpha: &Element<Bls12_381>) -> Evaluation {
+        let generator = JubJubGroup::generator_element();
+        let private_key = Element::from_str("4372820819045374670962167435360035096875258").unwrap();
+        let public_key = JubJubGroup::mul_scalar_element(&generator, &private_key);
+
+        let mut builder = GadgetBuilder::new();
+        let h = JubJubGroup::hash_to_curve(&mut builder, &Expression::from(alpha), &TestHash);
+        let cofactor_h = JubJubGroup::clear_cofactor(&mut builder, &h);
+        let mut values = WireValues::new();
+        assert!(builder.build().execute(&mut values));
+        let h = h.evaluate(&values);
+        let gamma = JubJubGroup::mul_scalar_element(&h, &private_key);
+        let cofactor_gamma = JubJubGroup::mul_scalar_element(
+            &cofactor_h.evaluate(&values), &private_key);
+
+        let nonce = Element::from_str("5434290453746709621674353600312312").unwrap();
+        let u = JubJubGroup::mul_scalar_element(&generator, &nonce);
+        let v = JubJubGroup::mul_scalar_element(&h, &nonce);
+        let c = TestHash.hash_evaluate(&[
+            generator.y.clone(), h.y.clone(), public_key.y.clone(), gamma.y.clone(), u.y, v.y]);
+
+        let order = JubJub::subgroup_order();
+        let x_c = private_key.to_biguint() * c.to_biguint() % &order;
+        let s = Element::from((nonce.to_biguint() + &order - x_c) % &order);
+
+        let output = TestHash.hash_evaluate(&[cofactor_gamma.y]);
+        Evaluation { public_key, alpha: alpha.clone(), gamma, c, s, output }
+    }
+
+    /// Verifies a proof in-circuit, returning the VRF output if the constraints were satisfied.
+    fn verify_gadget(
+        evaluation: &Evaluation, c: &Element<Bls12_381>, s: &Element<Bls12_381>,
+    ) -> Option<Element<Bls12_381>> {
+        let mut builder = GadgetBuilder::<Bls12_381>::new();
+        let proof = EcVrfProof {
+            gamma: EdwardsExpression::from(&evaluation.gamma),
+            c: Expression::from(c),
+            s: Expression::from(s),
+        };
+        let output = JubJubVrf::verify(
+            &mut builder,
+            &EdwardsExpression::from(&evaluation.public_key),
+            &Expression::from(&evaluation.alpha),
+            &proof,
+            &TestHash,
+        );
+
+        let gadget = builder.build();
+        let mut values = WireValues::new();
+        if gadget.execute(&mut values) {
+            Some(output.evaluate(&values))
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn verify() {
+        let evaluation = prove(&Element::from(12345u32));
+        let output = verify_gadget(&evaluation, &evaluation.c, &evaluation.s);
+        assert_eq!(Some(evaluation.output), output);
+    }
+
+    #[test]
+    fn verify_tampered_proof() {
+        let evaluation = prove(&Element::from(12345u32));
+        let tampered_c = &evaluation.c + Element::one();
+        let tampered_s = &evaluation.s + Element::one();
+        assert_eq!(None, verify_gadget(&evaluation, &tampered_c, &evaluation.s));
+        assert_eq!(None, verify_gadget(&evaluation, &evaluation.c, &tampered_s));
+    }
+
+    // A dummy hash function which returns a linear combination of its inputs.
+    struct TestHash;
+
+    impl HashFunction<Bls12_381> for TestHash {
+        fn hash(&self, _builder: &mut GadgetBuilder<Bls12_381>, blocks: &[Expression<Bls12_381>])
+                -> Expression<Bls12_381> {
+            let mut sum = Expression::from(7u8);
+            for (i, block) in blocks.iter().enumerate() {
+                sum += block * Element::from(i as u64 + 3);
+            }
+            sum
+        }
+    }
+}
@@ -6,6 +6,10 @@ use std::fmt;
 use std::fmt::Formatter;
 
 /// A wire represents a witness element.
+///
+/// Wires are ordered by ascending index, except that `Wire::ONE` comes last. They are displayed as
+/// `w<index>`, except that `Wire::ONE` is displayed as `1`. Both of these are stable, so they can
+/// be relied on for deterministic output.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Wire {
     pub index: u32,
@@ -40,10 +44,10 @@ impl PartialOrd for Wire {
 
 impl fmt::Display for Wire {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        if self.index == 0 {
+        if *self == Wire::ONE {
             write!(f, "1")
         } else {
-            write!(f, "wire_{}", self.index)
+            write!(f, "w{}", self.index)
         }
     }
 }
@@ -84,3 +88,73 @@ impl BinaryWire {
         self.bits.len()
     }
 }
+
+/// The kind of a wire allocated with a typed method such as `GadgetBuilder::boolean_wire`, which
+/// `Gadget::typed_values` uses to reject values the wire's constraints would reject. Every kind is
+/// constrained to hold either 0 or 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WireKind {
+    /// A wire allocated by `GadgetBuilder::boolean_wire`.
+    Boolean,
+    /// A bit of a wire allocated by `GadgetBuilder::binary_wire`.
+    BinaryBit {
+        /// The bit's index, with 0 being the least significant bit.
+        index: usize,
+        /// The number of bits in the binary wire.
+        bits: usize,
+    },
+}
+
+impl fmt::Display for WireKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WireKind::Boolean => write!(f, "a boolean wire"),
+            WireKind::BinaryBit { index, bits } =>
+                write!(f, "bit {} of a {}-bit binary wire", index, bits),
+        }
+    }
+}
+
+/// A wire or collection of wires, which can be expanded into its constituent `Wire`s. This lets
+/// `values_with_defaults!` and similar helpers accept any mix of wire types.
+pub trait ToWires {
+    /// The constituent wires, in order. For a `BinaryWire`, this is its bits, from least
+    /// significant to most significant.
+    fn to_wires(&self) -> Vec<Wire>;
+}
+
+impl ToWires for Wire {
+    fn to_wires(&self) -> Vec<Wire> {
+        vec![*self]
+    }
+}
+
+impl ToWires for BooleanWire {
+    fn to_wires(&self) -> Vec<Wire> {
+        vec![self.wire]
+    }
+}
+
+impl ToWires for BinaryWire {
+    fn to_wires(&self) -> Vec<Wire> {
+        self.bits.iter().map(|bit| bit.wire).collect()
+    }
+}
+
+impl<T: ToWires> ToWires for [T] {
+    fn to_wires(&self) -> Vec<Wire> {
+        self.iter().flat_map(ToWires::to_wires).collect()
+    }
+}
+
+impl<T: ToWires> ToWires for Vec<T> {
+    fn to_wires(&self) -> Vec<Wire> {
+        self.as_slice().to_wires()
+    }
+}
+
+impl<T: ToWires + ?Sized> ToWires for &T {
+    fn to_wires(&self) -> Vec<Wire> {
+        (**self).to_wires()
+    }
+}
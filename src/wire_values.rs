@@ -1,26 +1,48 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
 #[cfg(feature = "std")]
 use std::collections::BTreeMap;
 #[cfg(not(feature = "std"))]
 use alloc::collections::btree_map::BTreeMap;
 
+use std::fmt;
+use std::fmt::Formatter;
+
 use num::BigUint;
 use num_traits::One;
 
 use crate::expression::{BooleanExpression};
 use crate::field::{Element, Field};
-use crate::wire::{BinaryWire, BooleanWire, Wire};
+use crate::gadget::Gadget;
+use crate::wire::{BinaryWire, BooleanWire, Wire, WireKind};
+use crate::util::join;
+
+/// The number of wire values shown by `WireValues`'s `Display` implementation, unless a precision
+/// is given.
+const DEFAULT_DISPLAY_LIMIT: usize = 16;
 
 /// An assignment of wire values, where each value is an element of the field `F`.
 #[derive(Default, Debug)]
 pub struct WireValues<F: Field> {
     values: BTreeMap<Wire, Element<F>>,
+    /// Every wire which was assigned a value, in order, used to roll back to snapshots. Since
+    /// values are never overwritten, rolling back a change just means removing the wire.
+    journal: Vec<Wire>,
+}
+
+/// A point to which a `WireValues` can be rolled back, created by `WireValues::snapshot`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Snapshot {
+    journal_len: usize,
 }
 
 impl<F: Field> WireValues<F> {
     pub fn new() -> Self {
         let mut values = BTreeMap::new();
         values.insert(Wire::ONE, Element::one());
-        WireValues { values }
+        WireValues { values, journal: Vec::new() }
     }
 
     pub fn as_map(&self) -> &BTreeMap<Wire, Element<F>> {
@@ -39,6 +61,54 @@ impl<F: Field> WireValues<F> {
     pub fn set(&mut self, wire: Wire, value: Element<F>) {
         let old_value = self.values.insert(wire, value);
         assert!(old_value.is_none());
+        self.journal.push(wire);
+    }
+
+    /// Like `set`, but first checks `value` against the kind of `wire` in `gadget`, if it has one,
+    /// returning an error rather than setting a value which the wire's constraints would reject.
+    pub fn set_checked(
+        &mut self, gadget: &Gadget<F>, wire: Wire, value: Element<F>,
+    ) -> Result<(), TypeError<F>> {
+        if let Some(kind) = gadget.wire_kind(wire) {
+            if !value.is_zero() && !value.is_one() {
+                return Err(TypeError { wire, kind, value });
+            }
+        }
+        self.set(wire, value);
+        Ok(())
+    }
+
+    /// Set each of the given wires to `value`. Panics if any of them already has a value.
+    pub fn set_all(&mut self, wires: &[Wire], value: Element<F>) {
+        for &wire in wires {
+            self.set(wire, value.clone());
+        }
+    }
+
+    /// Set each of the given wires to the corresponding value, e.g. to populate the sibling wires
+    /// of a Merkle path from a tree computed outside the circuit. Panics if the lengths differ, or
+    /// if any of the wires already has a value.
+    pub fn set_each(&mut self, wires: &[Wire], values: &[Element<F>]) {
+        assert_eq!(wires.len(), values.len(), "Expected one value per wire");
+        for (&wire, value) in wires.iter().zip(values) {
+            self.set(wire, value.clone());
+        }
+    }
+
+    /// Set each of the given wires which doesn't already have a value to `value`, leaving any
+    /// existing values in place.
+    pub fn set_missing(&mut self, wires: &[Wire], value: Element<F>) {
+        for &wire in wires {
+            if !self.contains(wire) {
+                self.set(wire, value.clone());
+            }
+        }
+    }
+
+    /// Set each of the given wires which doesn't already have a value to zero. This is convenient
+    /// for optional inputs, which can be set explicitly when present and defaulted otherwise.
+    pub fn ensure_defaults(&mut self, wires: &[Wire]) {
+        self.set_missing(wires, Element::zero());
     }
 
     pub fn set_boolean(&mut self, wire: BooleanWire, value: bool) {
@@ -55,6 +125,36 @@ impl<F: Field> WireValues<F> {
         }
     }
 
+    /// Set the bits of `wire` to the given big-endian bytes, i.e. with the first byte being the
+    /// most significant. The wire must have exactly `8 * bytes.len()` bits.
+    pub fn set_binary_be_bytes(&mut self, wire: &BinaryWire, bytes: &[u8]) {
+        assert_eq!(wire.len(), bytes.len() * 8, "Expected one wire per bit");
+        self.set_binary_unsigned(wire, &BigUint::from_bytes_be(bytes));
+    }
+
+    /// Set the bits of `wire` to the given little-endian bytes, i.e. with the first byte being the
+    /// least significant. The wire must have exactly `8 * bytes.len()` bits.
+    pub fn set_binary_le_bytes(&mut self, wire: &BinaryWire, bytes: &[u8]) {
+        assert_eq!(wire.len(), bytes.len() * 8, "Expected one wire per bit");
+        self.set_binary_unsigned(wire, &BigUint::from_bytes_le(bytes));
+    }
+
+    /// Records the current state, so that it can later be restored with `rollback`. This is cheap,
+    /// since changes are journaled as they're made.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { journal_len: self.journal.len() }
+    }
+
+    /// Restores the state recorded by `snapshot`, removing any values which were set since then.
+    /// Snapshots taken after the given one become invalid. Panics if the snapshot is already
+    /// invalid, i.e. if this was rolled back to an earlier snapshot.
+    pub fn rollback(&mut self, snapshot: Snapshot) {
+        assert!(snapshot.journal_len <= self.journal.len(), "Snapshot is no longer valid");
+        for wire in self.journal.drain(snapshot.journal_len..) {
+            self.values.remove(&wire);
+        }
+    }
+
     pub fn contains(&self, wire: Wire) -> bool {
         self.values.contains_key(&wire)
     }
@@ -66,56 +166,574 @@ impl<F: Field> WireValues<F> {
     pub fn contains_all(&self, wires: &[Wire]) -> bool {
         wires.iter().all(|&wire| self.contains(wire))
     }
+
+    /// Translates these values to a renumbered set of wires, such as the map returned by
+    /// `Gadget::compact_wires`. Wires which aren't in the map are dropped.
+    pub fn remap(&self, map: &BTreeMap<Wire, Wire>) -> WireValues<F> {
+        let mut values = WireValues { values: BTreeMap::new(), journal: Vec::new() };
+        for (wire, value) in &self.values {
+            if let Some(&new_wire) = map.get(wire) {
+                values.set(new_wire, value.clone());
+            }
+        }
+        values
+    }
+
+    /// Lists the wires whose values differ between `self` and `other`, one per line, in wire
+    /// order. Wires with a value in only one of the two are included, with the missing value shown
+    /// as `unset`. Returns an empty string if the values are identical.
+    ///
+    /// This is useful for comparing a failing witness against a known-good one.
+    pub fn debug_diff(&self, other: &WireValues<F>) -> String {
+        let mut wires: Vec<Wire> = self.values.keys().chain(other.values.keys()).cloned().collect();
+        wires.sort();
+        wires.dedup();
+
+        let value_string = |values: &WireValues<F>, wire: Wire| match values.values.get(&wire) {
+            Some(value) => format!("{}", value),
+            None => String::from("unset"),
+        };
+        let lines: Vec<String> = wires.into_iter()
+            .filter(|wire| self.values.get(wire) != other.values.get(wire))
+            .map(|wire| format!("{}: {} != {}",
+                                wire, value_string(self, wire), value_string(other, wire)))
+            .collect();
+        join("\n", &lines)
+    }
+}
+
+/// Displays wire values in wire order, like `{w1: 5, w2: 0}`, omitting the constant `1` wire. At
+/// most 16 values are shown, or as many as the formatter's precision if one is given, e.g.
+/// `{:.4}`; any others are summarized with a suffix like `+3 more`.
+impl<F: Field> fmt::Display for WireValues<F> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let limit = f.precision().unwrap_or(DEFAULT_DISPLAY_LIMIT);
+        let entries: Vec<(&Wire, &Element<F>)> = self.values.iter()
+            .filter(|(&wire, _value)| wire != Wire::ONE)
+            .collect();
+
+        let mut strings: Vec<String> = entries.iter().take(limit)
+            .map(|(wire, value)| format!("{}: {}", wire, value))
+            .collect();
+        if entries.len() > limit {
+            strings.push(format!("+{} more", entries.len() - limit));
+        }
+        write!(f, "{{{}}}", join(", ", &strings))
+    }
 }
 
 impl<F: Field> Clone for WireValues<F> {
     fn clone(&self) -> Self {
-        WireValues { values: self.values.clone() }
+        WireValues { values: self.values.clone(), journal: self.journal.clone() }
+    }
+}
+
+/// A `WireValues` which checks every value against the kind of its wire in a gadget, created by
+/// `Gadget::typed_values`. Once populated, the values can be taken with `into_values` and passed
+/// to `Gadget::execute` as usual.
+pub struct TypedWireValues<'a, F: Field> {
+    gadget: &'a Gadget<F>,
+    values: WireValues<F>,
+}
+
+impl<'a, F: Field> TypedWireValues<'a, F> {
+    pub fn new(gadget: &'a Gadget<F>) -> Self {
+        TypedWireValues { gadget, values: WireValues::new() }
+    }
+
+    /// Sets the value of `wire`, as by `WireValues::set_checked`.
+    pub fn set(&mut self, wire: Wire, value: Element<F>) -> Result<(), TypeError<F>> {
+        self.values.set_checked(self.gadget, wire, value)
+    }
+
+    /// Sets each bit of `wire` to the corresponding bit of `value`, checking each one.
+    pub fn set_binary_unsigned(
+        &mut self, wire: &BinaryWire, value: &BigUint,
+    ) -> Result<(), TypeError<F>> {
+        assert!(value.bits() <= wire.len() as u64, "Value does not fit");
+        for (i, bit) in wire.bits.iter().enumerate() {
+            self.set(bit.wire, Element::from(((value >> i) & BigUint::one()).is_one()))?;
+        }
+        Ok(())
+    }
+
+    /// The values set so far.
+    pub fn values(&self) -> &WireValues<F> {
+        &self.values
+    }
+
+    pub fn into_values(self) -> WireValues<F> {
+        self.values
+    }
+}
+
+/// An error indicating that a value was rejected by `WireValues::set_checked`, because it isn't
+/// valid for the kind of wire it was assigned to.
+pub struct TypeError<F: Field> {
+    /// The wire whose value was rejected.
+    pub wire: Wire,
+    /// The kind of the wire, as recorded when it was allocated.
+    pub kind: WireKind,
+    /// The rejected value.
+    pub value: Element<F>,
+}
+
+impl<F: Field> fmt::Display for TypeError<F> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Cannot set {}, {}, to non-boolean value {}", self.wire, self.kind, self.value)
+    }
+}
+
+impl<F: Field> fmt::Debug for TypeError<F> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("TypeError")
+            .field("wire", &self.wire)
+            .field("kind", &self.kind)
+            .field("value", &format_args!("{}", self.value))
+            .finish()
+    }
+}
+
+impl<F: Field> Clone for TypeError<F> {
+    fn clone(&self) -> Self {
+        TypeError { wire: self.wire, kind: self.kind, value: self.value.clone() }
     }
 }
 
+#[cfg(feature = "std")]
+impl<F: Field> std::error::Error for TypeError<F> {}
+
 pub trait Evaluable<F: Field, R> {
     fn evaluate(&self, wire_values: &WireValues<F>) -> R;
 }
 
 /// Creates an instance of `WireValues` from the given wires and field element values.
+///
+/// Wires may be any expression, such as a struct field or a method call, and may be given by value
+/// or by reference. Values must be owned `Element`s, so that conversions like `5u8.into()` can be
+/// inferred. A trailing comma is allowed.
+///
+/// ```
+/// # use r1cs::*;
+/// # let mut builder = GadgetBuilder::<Bn128>::new();
+/// # let (x, y) = (builder.wire(), builder.wire());
+/// let five = Element::from(5u8);
+/// let values: WireValues<Bn128> = values!(x => 3u8.into(), &y => five.clone(),);
+/// assert_eq!(&five, values.get(y));
+/// ```
 #[macro_export]
 macro_rules! values {
-    ( $( $wire:expr => $value:expr ),* ) => {
+    (@munch $values:ident; $wire:expr => $value:expr $(, $( $rest:tt )* )? ) => {
+        $values.set(*::core::borrow::Borrow::<$crate::Wire>::borrow(&$wire), $value);
+        $crate::values!(@munch $values; $( $( $rest )* )?);
+    };
+    (@munch $values:ident; ) => {};
+    ( $( $tokens:tt )* ) => {
         {
             let mut values = $crate::WireValues::new();
-            $(
-                values.set($wire, $value);
-            )*
+            $crate::values!(@munch values; $( $tokens )*);
             values
         }
-    }
+    };
 }
 
-/// Creates an instance of `WireValues` from the given boolean wires and boolean values.
+/// Like `values!`, but with a trailing clause of the form `default (wires...) => value`, which sets
+/// each listed wire that wasn't given an explicit value to `value`. The listed wires can be any
+/// `ToWires` type, so a `BinaryWire` is expanded to its bits. Explicit values take precedence.
+///
+/// ```
+/// # use r1cs::*;
+/// # let mut builder = GadgetBuilder::<Bn128>::new();
+/// # let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+/// let values: WireValues<Bn128> =
+///     values_with_defaults!(x => 5u8.into(), default (x, y, z) => Element::zero());
+/// assert_eq!(&Element::from(5u8), values.get(x));
+/// assert_eq!(&Element::zero(), values.get(y));
+/// ```
+#[macro_export]
+macro_rules! values_with_defaults {
+    (@munch $values:ident; default ( $( $default_wire:expr ),* $(,)? ) => $default:expr $(,)? ) => {
+        let default: $crate::Element<_> = $default;
+        $(
+            $values.set_missing(&$crate::ToWires::to_wires(&$default_wire), default.clone());
+        )*
+    };
+    (@munch $values:ident; $wire:expr => $value:expr $(, $( $rest:tt )* )? ) => {
+        $values.set(*::core::borrow::Borrow::<$crate::Wire>::borrow(&$wire), $value);
+        $crate::values_with_defaults!(@munch $values; $( $( $rest )* )?);
+    };
+    (@munch $values:ident; ) => {};
+    ( $( $tokens:tt )* ) => {
+        {
+            let mut values = $crate::WireValues::new();
+            $crate::values_with_defaults!(@munch values; $( $tokens )*);
+            values
+        }
+    };
+}
+
+/// Creates an instance of `WireValues` from the given boolean wires and boolean values. Wires may
+/// be given by value or by reference, and a trailing comma is allowed.
 #[macro_export]
 macro_rules! boolean_values {
-    ( $( $wire:expr => $value:expr ),* ) => {
+    ( $( $wire:expr => $value:expr ),* $(,)? ) => {
         {
             let mut values = $crate::WireValues::new();
             $(
-                values.set_boolean($wire, $value);
+                values.set_boolean(
+                    *::core::borrow::Borrow::<$crate::BooleanWire>::borrow(&$wire), $value);
             )*
             values
         }
     }
 }
 
-/// Creates an instance of `WireValues` from the given binary wires and `BigUint` values.
+/// Creates an instance of `WireValues` from the given binary wires and `BigUint` values. Both wires
+/// and values may be given by value or by reference, and a trailing comma is allowed.
 #[macro_export]
 macro_rules! binary_unsigned_values {
-    ( $( $wire:expr => $value:expr ),* ) => {
+    ( $( $wire:expr => $value:expr ),* $(,)? ) => {
         {
             let mut values = $crate::WireValues::new();
             $(
-                values.set_binary_unsigned($wire, $value);
+                values.set_binary_unsigned(
+                    ::core::borrow::Borrow::<$crate::BinaryWire>::borrow(&$wire),
+                    ::core::borrow::Borrow::<$crate::num::BigUint>::borrow(&$value));
             )*
             values
         }
     }
 }
+
+/// Like `binary_unsigned_values!`, but with a trailing clause of the form
+/// `default (wires...) => value`, which sets each listed binary wire that wasn't given an explicit
+/// value to the `BigUint` `value`. Explicit values take precedence.
+#[macro_export]
+macro_rules! binary_unsigned_values_with_defaults {
+    (@munch $values:ident; default ( $( $default_wire:expr ),* $(,)? ) => $default:expr $(,)? ) => {
+        let default = &$default;
+        let default: &$crate::num::BigUint = ::core::borrow::Borrow::borrow(default);
+        $(
+            let wire = &$default_wire;
+            let wire: &$crate::BinaryWire = ::core::borrow::Borrow::borrow(wire);
+            if !$values.contains_all(&$crate::ToWires::to_wires(wire)) {
+                $values.set_binary_unsigned(wire, default);
+            }
+        )*
+    };
+    (@munch $values:ident; $wire:expr => $value:expr $(, $( $rest:tt )* )? ) => {
+        $values.set_binary_unsigned(
+            ::core::borrow::Borrow::<$crate::BinaryWire>::borrow(&$wire),
+            ::core::borrow::Borrow::<$crate::num::BigUint>::borrow(&$value));
+        $crate::binary_unsigned_values_with_defaults!(@munch $values; $( $( $rest )* )?);
+    };
+    (@munch $values:ident; ) => {};
+    ( $( $tokens:tt )* ) => {
+        {
+            let mut values = $crate::WireValues::new();
+            $crate::binary_unsigned_values_with_defaults!(@munch values; $( $tokens )*);
+            values
+        }
+    };
+}
+
+/// Creates an instance of `WireValues` from a mix of field element, boolean and binary
+/// assignments. Entries prefixed with `bool` are set as by `boolean_values!`, entries prefixed with
+/// `binary` as by `binary_unsigned_values!`, and other entries as by `values!`.
+///
+/// ```
+/// # use r1cs::*;
+/// # use r1cs::num::BigUint;
+/// # let mut builder = GadgetBuilder::<Bn128>::new();
+/// # let (x, b, n) = (builder.wire(), builder.boolean_wire(), builder.binary_wire(4));
+/// let values: WireValues<Bn128> = mixed_values!(
+///     x => 5u8.into(),
+///     bool b => true,
+///     binary &n => BigUint::from(9u8),
+/// );
+/// assert!(values.get_boolean(b));
+/// ```
+#[macro_export]
+macro_rules! mixed_values {
+    (@munch $values:ident; bool $wire:expr => $value:expr $(, $( $rest:tt )* )? ) => {
+        $values.set_boolean(*::core::borrow::Borrow::<$crate::BooleanWire>::borrow(&$wire), $value);
+        $crate::mixed_values!(@munch $values; $( $( $rest )* )?);
+    };
+    (@munch $values:ident; binary $wire:expr => $value:expr $(, $( $rest:tt )* )? ) => {
+        $values.set_binary_unsigned(
+            ::core::borrow::Borrow::<$crate::BinaryWire>::borrow(&$wire),
+            ::core::borrow::Borrow::<$crate::num::BigUint>::borrow(&$value));
+        $crate::mixed_values!(@munch $values; $( $( $rest )* )?);
+    };
+    (@munch $values:ident; $wire:expr => $value:expr $(, $( $rest:tt )* )? ) => {
+        $values.set(*::core::borrow::Borrow::<$crate::Wire>::borrow(&$wire), $value);
+        $crate::mixed_values!(@munch $values; $( $( $rest )* )?);
+    };
+    (@munch $values:ident; ) => {};
+    ( $( $tokens:tt )* ) => {
+        {
+            let mut values = $crate::WireValues::new();
+            $crate::mixed_values!(@munch values; $( $tokens )*);
+            values
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::expression::BinaryExpression;
+    use crate::field::Element;
+    use crate::gadget_builder::GadgetBuilder;
+    use crate::fields::small::F257;
+    use crate::wire::{BinaryWire, BooleanWire, Wire, WireKind};
+    use crate::wire_values::WireValues;
+
+    #[test]
+    fn display_wire() {
+        assert_eq!("w5", format!("{}", Wire { index: 5 }));
+        assert_eq!("1", format!("{}", Wire::ONE));
+    }
+
+    #[test]
+    fn display() {
+        let values: WireValues<F257> = values!(
+            Wire { index: 2 } => 0u8.into(), Wire { index: 1 } => 5u8.into());
+        assert_eq!("{w1: 5, w2: 0}", format!("{}", values));
+        assert_eq!("{}", format!("{}", WireValues::<F257>::new()));
+    }
+
+    #[test]
+    fn display_truncated() {
+        let mut values = WireValues::<F257>::new();
+        for i in 1u8..=20 {
+            values.set(Wire { index: i as u32 }, i.into());
+        }
+        assert_eq!("{w1: 1, w2: 2, +18 more}", format!("{:.2}", values));
+        assert!(format!("{}", values).ends_with(", w16: 16, +4 more}"));
+    }
+
+    #[test]
+    fn debug_diff() {
+        let (x, y, z) = (Wire { index: 1 }, Wire { index: 2 }, Wire { index: 3 });
+        let good: WireValues<F257> = values!(x => 1u8.into(), y => 2u8.into(), z => 3u8.into());
+        let bad: WireValues<F257> = values!(x => 1u8.into(), y => 7u8.into(), z => 3u8.into());
+        assert_eq!("w2: 2 != 7", good.debug_diff(&bad));
+        assert_eq!("", good.debug_diff(&good.clone()));
+
+        let partial: WireValues<F257> = values!(x => 1u8.into(), y => 2u8.into());
+        assert_eq!("w3: 3 != unset", good.debug_diff(&partial));
+    }
+
+    #[test]
+    fn values_with_defaults() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (x, y, z) = (builder.wire(), builder.wire(), builder.wire());
+        let values: WireValues<F257> = values_with_defaults!(
+            x => 3u8.into(), default (y, z) => 7u8.into());
+        assert_eq!(&Element::from(3u8), values.get(x));
+        assert_eq!(&Element::from(7u8), values.get(y));
+        assert_eq!(&Element::from(7u8), values.get(z));
+
+        // Explicit values take precedence over defaults.
+        let values: WireValues<F257> = values_with_defaults!(
+            x => 3u8.into(), z => 4u8.into(), default (x, y, z) => Element::zero());
+        assert_eq!(&Element::from(3u8), values.get(x));
+        assert_eq!(&Element::zero(), values.get(y));
+        assert_eq!(&Element::from(4u8), values.get(z));
+
+        // The default clause is optional, as are explicit values.
+        let values: WireValues<F257> = values_with_defaults!(x => 1u8.into());
+        assert!(!values.contains(y));
+        let values: WireValues<F257> = values_with_defaults!(default (x) => 2u8.into());
+        assert_eq!(&Element::from(2u8), values.get(x));
+    }
+
+    #[test]
+    fn values_with_defaults_binary_wire() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let x = builder.wire();
+        let bits = builder.binary_wire(4);
+        let values: WireValues<F257> = values_with_defaults!(
+            bits.bits[1].wire() => 1u8.into(), default (x, bits) => Element::zero());
+        assert_eq!(&Element::zero(), values.get(x));
+        assert_eq!(BigUint::from(2u8), BinaryExpression::from(&bits).evaluate(&values));
+    }
+
+    #[test]
+    fn binary_unsigned_values_with_defaults() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (a, b, c) = (builder.binary_wire(4), builder.binary_wire(4), builder.binary_wire(2));
+        let values: WireValues<F257> = binary_unsigned_values_with_defaults!(
+            &a => &BigUint::from(9u8), default (&a, &b, &c) => &BigUint::from(3u8));
+        assert_eq!(BigUint::from(9u8), BinaryExpression::from(&a).evaluate(&values));
+        assert_eq!(BigUint::from(3u8), BinaryExpression::from(&b).evaluate(&values));
+        assert_eq!(BigUint::from(3u8), BinaryExpression::from(&c).evaluate(&values));
+    }
+
+    /// Wires of a test gadget, to check that macros accept struct fields and method calls.
+    struct TestWires {
+        x: Wire,
+        flag: BooleanWire,
+        n: BinaryWire,
+    }
+
+    impl TestWires {
+        fn new(builder: &mut GadgetBuilder<F257>) -> Self {
+            TestWires { x: builder.wire(), flag: builder.boolean_wire(), n: builder.binary_wire(4) }
+        }
+
+        fn x(&self) -> Wire {
+            self.x
+        }
+
+        fn n(&self) -> &BinaryWire {
+            &self.n
+        }
+    }
+
+    #[test]
+    fn values_expression_wires() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires = TestWires::new(&mut builder);
+        let y = builder.wire();
+
+        let values: WireValues<F257> = values!(wires.x => 3u8.into(), &y => 4u8.into(),);
+        assert_eq!(&Element::from(3u8), values.get(wires.x));
+        assert_eq!(&Element::from(4u8), values.get(y));
+
+        let values: WireValues<F257> = values!(wires.x() => 5u8.into());
+        assert_eq!(&Element::from(5u8), values.get(wires.x));
+
+        let values: WireValues<F257> = boolean_values!(wires.flag => true,);
+        assert!(values.get_boolean(wires.flag));
+    }
+
+    #[test]
+    fn binary_unsigned_values_by_value_or_reference() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires = TestWires::new(&mut builder);
+        let nine = BigUint::from(9u8);
+        let n = BinaryExpression::from(&wires.n);
+
+        let values: WireValues<F257> = binary_unsigned_values!(&wires.n => &nine);
+        assert_eq!(nine, n.evaluate(&values));
+        let values: WireValues<F257> = binary_unsigned_values!(wires.n() => nine.clone(),);
+        assert_eq!(nine, n.evaluate(&values));
+        let values: WireValues<F257> =
+            binary_unsigned_values!(wires.n.clone() => BigUint::from(9u8));
+        assert_eq!(nine, n.evaluate(&values));
+    }
+
+    #[test]
+    fn mixed_values() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let wires = TestWires::new(&mut builder);
+        let values: WireValues<F257> = mixed_values!(
+            wires.x() => 7u8.into(),
+            bool wires.flag => true,
+            binary wires.n() => BigUint::from(6u8),
+        );
+        assert_eq!(&Element::from(7u8), values.get(wires.x));
+        assert!(values.get_boolean(wires.flag));
+        assert_eq!(BigUint::from(6u8), BinaryExpression::from(&wires.n).evaluate(&values));
+    }
+
+    #[test]
+    fn set_all_and_ensure_defaults() {
+        let wires: Vec<Wire> = (1..=3).map(|index| Wire { index }).collect();
+        let mut values = WireValues::<F257>::new();
+        values.set(wires[0], 5u8.into());
+        values.ensure_defaults(&wires);
+        assert_eq!(&Element::from(5u8), values.get(wires[0]));
+        assert_eq!(&Element::zero(), values.get(wires[2]));
+
+        let mut values = WireValues::<F257>::new();
+        values.set_all(&wires, 6u8.into());
+        assert!(wires.iter().all(|&wire| values.get(wire) == &Element::from(6u8)));
+    }
+
+    #[test]
+    fn snapshot_and_rollback() {
+        let wires: Vec<Wire> = (1..=4).map(|index| Wire { index }).collect();
+        let mut values = WireValues::<F257>::new();
+        values.set(wires[0], 1u8.into());
+        let original = values.as_map().clone();
+        let outer = values.snapshot();
+
+        values.set(wires[1], 2u8.into());
+        let inner = values.snapshot();
+        values.set_missing(&wires, 3u8.into());
+        assert_eq!(&Element::from(3u8), values.get(wires[3]));
+
+        values.rollback(inner);
+        assert!(values.contains(wires[1]));
+        assert!(!values.contains(wires[2]) && !values.contains(wires[3]));
+        assert_eq!(inner, values.snapshot());
+
+        values.rollback(outer);
+        assert_eq!(&original, values.as_map());
+        assert_eq!(outer, values.snapshot());
+
+        // Wires can be set again after being rolled back.
+        values.set(wires[1], 4u8.into());
+        assert_eq!(&Element::from(4u8), values.get(wires[1]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rollback_invalid_snapshot() {
+        let mut values = WireValues::<F257>::new();
+        let outer = values.snapshot();
+        values.set(Wire { index: 1 }, 1u8.into());
+        let inner = values.snapshot();
+        values.rollback(outer);
+        values.rollback(inner);
+    }
+
+    #[test]
+    fn set_checked_boolean() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let (b, x) = (builder.boolean_wire(), builder.wire());
+        let gadget = builder.build();
+        assert_eq!(Some(WireKind::Boolean), gadget.wire_kind(b.wire));
+        assert_eq!(None, gadget.wire_kind(x));
+
+        let mut values = WireValues::new();
+        let error = values.set_checked(&gadget, b.wire, Element::from(2u8)).unwrap_err();
+        assert_eq!(b.wire, error.wire);
+        assert_eq!("Cannot set w1, a boolean wire, to non-boolean value 2", format!("{}", error));
+        assert!(!values.contains(b.wire));
+
+        // Untyped wires accept any value.
+        values.set_checked(&gadget, b.wire, Element::one()).unwrap();
+        values.set_checked(&gadget, x, Element::from(2u8)).unwrap();
+        assert!(gadget.execute(&mut values));
+    }
+
+    #[test]
+    fn typed_values_binary() {
+        let mut builder = GadgetBuilder::<F257>::new();
+        let n = builder.binary_wire(4);
+        let gadget = builder.build();
+
+        let mut values = gadget.typed_values();
+        for (i, bit) in n.bits.iter().enumerate() {
+            if i == 2 {
+                let error = values.set(bit.wire, Element::from(3u8)).unwrap_err();
+                assert_eq!(WireKind::BinaryBit { index: 2, bits: 4 }, error.kind);
+                assert_eq!("Cannot set w3, bit 2 of a 4-bit binary wire, to non-boolean value 3",
+                           format!("{}", error));
+            }
+            values.set(bit.wire, Element::from(i % 2 == 0)).unwrap();
+        }
+        let mut values = values.into_values();
+        assert!(gadget.execute(&mut values));
+        assert_eq!(BigUint::from(5u8), BinaryExpression::from(&n).evaluate(&values));
+
+        let mut values = gadget.typed_values();
+        values.set_binary_unsigned(&n, &BigUint::from(9u8)).unwrap();
+        assert!(gadget.execute(&mut values.into_values()));
+    }
+}
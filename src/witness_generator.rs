@@ -1,16 +1,40 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "std"))]
-use alloc::boxed::Box;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
 use crate::field::Field;
 use crate::wire::Wire;
 use crate::wire_values::WireValues;
 
+/// A shared function which generates some elements of the witness, given the number of previous
+/// attempts. It is reference counted so that gadgets can be cloned cheaply, and thread-safe so
+/// that a gadget can be shared between threads.
+type GeneratorFn<F> = Arc<dyn Fn(&mut WireValues<F>, usize) -> Result<(), Retry> + Send + Sync>;
+
+/// Returned by a retryable generator to indicate that its attempt failed. Any values it set are
+/// rolled back before it is run again; see `Gadget::execute_with_backtracking`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Retry;
+
 /// Generates some elements of the witness.
 pub struct WitnessGenerator<F: Field> {
     inputs: Vec<Wire>,
-    generator: Box<dyn Fn(&mut WireValues<F>)>,
+    outputs: Vec<Wire>,
+    generator: GeneratorFn<F>,
+}
+
+impl<F: Field> Clone for WitnessGenerator<F> {
+    /// Clones the wire lists, while sharing the generator function.
+    fn clone(&self) -> Self {
+        WitnessGenerator {
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            generator: self.generator.clone(),
+        }
+    }
 }
 
 impl<F: Field> WitnessGenerator<F> {
@@ -20,10 +44,26 @@ impl<F: Field> WitnessGenerator<F> {
     /// * `inputs` - the wires whose values must be set before this generator can run
     /// * `generate` - a function which generates some elements of the witness
     pub fn new<T>(inputs: Vec<Wire>, generate: T) -> Self
-        where T: Fn(&mut WireValues<F>) + 'static {
+        where T: Fn(&mut WireValues<F>) + Send + Sync + 'static {
         WitnessGenerator {
             inputs,
-            generator: Box::new(generate),
+            outputs: Vec::new(),
+            generator: Arc::new(move |values, _attempt| {
+                generate(values);
+                Ok(())
+            }),
+        }
+    }
+
+    /// Creates a new `WitnessGenerator` which may fail, by returning `Retry`. The generator is
+    /// passed the number of previous attempts, so that it can try something different each time.
+    /// Such generators require `Gadget::execute_with_backtracking`.
+    pub fn new_retryable<T>(inputs: Vec<Wire>, generate: T) -> Self
+        where T: Fn(&mut WireValues<F>, usize) -> Result<(), Retry> + Send + Sync + 'static {
+        WitnessGenerator {
+            inputs,
+            outputs: Vec::new(),
+            generator: Arc::new(generate),
         }
     }
 
@@ -32,8 +72,28 @@ impl<F: Field> WitnessGenerator<F> {
         &self.inputs
     }
 
-    /// Run the generator.
+    /// Declare the wires which this generator sets. Declaring outputs is optional, but it lets
+    /// `Gadget::unconstrained_hint_wires` detect outputs which no constraint verifies.
+    pub fn with_outputs(mut self, outputs: Vec<Wire>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// The wires which this generator was declared to set. This may be empty, even if the
+    /// generator sets some wires, since declaring outputs is optional.
+    pub fn outputs(&self) -> &[Wire] {
+        &self.outputs
+    }
+
+    /// Run the generator. Panics if it requests a retry.
     pub fn generate(&self, values: &mut WireValues<F>) {
-        (*self.generator)(values)
+        if self.try_generate(values, 0).is_err() {
+            panic!("Generator requested a retry, which requires execute_with_backtracking");
+        }
+    }
+
+    /// Run the generator, given the number of previous attempts.
+    pub fn try_generate(&self, values: &mut WireValues<F>, attempt: usize) -> Result<(), Retry> {
+        (*self.generator)(values, attempt)
     }
 }
\ No newline at end of file
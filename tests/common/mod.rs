@@ -0,0 +1,37 @@
+//! Fixtures shared by the integration tests which use MiMC, and so need the rand feature.
+
+use std::str::FromStr;
+
+use num::BigUint;
+
+use r1cs::{CompressionFunction, DaviesMeyer, Element, Field, MiMCBlockCipher};
+
+/// The prime field of order `2^127 + 45`. We can't use `Bn128` or `Bls12_381` here, since cubing
+/// (which MiMC relies on) is not a permutation in those fields.
+#[derive(Debug)]
+pub struct F127 {}
+
+impl Field for F127 {
+    fn order() -> BigUint {
+        BigUint::from_str("170141183460469231731687303715884105773").unwrap()
+    }
+}
+
+/// MiMC turned into a one-way compression function with Davies-Meyer.
+pub fn compress() -> DaviesMeyer<F127, MiMCBlockCipher<F127>> {
+    DaviesMeyer::new(MiMCBlockCipher::default())
+}
+
+/// Builds every layer of a Merkle tree out-of-circuit with `compress`, starting with the leaves
+/// and ending with a layer containing only the root.
+pub fn merkle_layers(leaves: Vec<Element<F127>>) -> Vec<Vec<Element<F127>>> {
+    let compress = compress();
+    let mut layers = vec![leaves];
+    while layers.last().unwrap().len() > 1 {
+        let parents = layers.last().unwrap().chunks(2)
+            .map(|pair| compress.compress_evaluate(&pair[0], &pair[1]))
+            .collect();
+        layers.push(parents);
+    }
+    layers
+}
@@ -0,0 +1,57 @@
+//! Checks that building, executing and exporting the same gadget repeatedly gives identical
+//! results, so that fingerprints and exported artifacts are reproducible.
+
+// The circom exporters need std.
+#![cfg(feature = "std")]
+
+use std::collections::BTreeSet;
+use std::io::Cursor;
+
+use r1cs::{Bn128, Element, Gadget, GadgetBuilder, Wire, WireValues, write_circom_r1cs,
+           write_circom_wtns};
+
+/// Sorts some inputs, which routes a permutation network, then compares the extremes.
+fn build() -> (Gadget<Bn128>, Vec<Wire>) {
+    let mut builder = GadgetBuilder::new();
+    let (wires, inputs) = builder.expression_wires(6);
+    let sorted = builder.sort_ascending(&inputs);
+    builder.assert_lt(&sorted[0], &sorted[5]);
+    builder.assert_within(&sorted[1], &sorted[2], &Element::from(1000u16));
+    (builder.build(), wires)
+}
+
+/// Builds and executes the gadget, then exports its constraints and witness.
+fn export() -> (u64, Vec<u8>, Vec<u8>) {
+    let (gadget, wires) = build();
+    let mut values = WireValues::new();
+    // The duplicates give the permutation network a choice of routes.
+    for (&wire, &x) in wires.iter().zip(&[500u16, 7, 300, 7, 900, 300]) {
+        values.set(wire, Element::from(x));
+    }
+    assert!(gadget.execute(&mut values));
+
+    let public_wires: BTreeSet<Wire> = wires.iter().cloned().collect();
+    let mut r1cs = Cursor::new(Vec::new());
+    write_circom_r1cs(&gadget, &public_wires, &mut r1cs).unwrap();
+    let mut wtns = Cursor::new(Vec::new());
+    write_circom_wtns(&gadget, &values, &public_wires, &mut wtns).unwrap();
+    (gadget.fingerprint(), r1cs.into_inner(), wtns.into_inner())
+}
+
+#[test]
+fn repeated_builds_are_identical() {
+    let (first, _wires) = build();
+    for _i in 0..3 {
+        let (gadget, _wires) = build();
+        assert_eq!(first.constraints(), gadget.constraints());
+        assert_eq!(first.fingerprint(), gadget.fingerprint());
+    }
+}
+
+#[test]
+fn repeated_exports_are_identical() {
+    let first = export();
+    for _i in 0..3 {
+        assert_eq!(first, export());
+    }
+}
@@ -0,0 +1,29 @@
+//! Inspects a gadget's constraints using only the public API, as downstream tools such as exporters
+//! and verifiers do.
+
+use r1cs::{Bn128, Expression, GadgetBuilder, values};
+
+#[test]
+fn sum_terms_across_constraints() {
+    let mut builder = GadgetBuilder::<Bn128>::new();
+    let (x, y) = (builder.wire(), builder.wire());
+    let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+    let product = builder.product(&x_exp, &y_exp);
+    builder.assert_equal(&(&product + Expression::from(3u8)), &(&x_exp + &y_exp));
+    let gadget = builder.build();
+
+    // x * y = product has one term in each expression. The equality is expressed as
+    // (product + 3 - x - y) * 1 = 0, with four terms, then one, then none.
+    let terms: usize = gadget.constraints().iter()
+        .map(|constraint| constraint.a().num_terms() + constraint.b().num_terms()
+            + constraint.c().num_terms())
+        .sum();
+    assert_eq!(3 + 5, terms);
+    let total: usize = gadget.constraints().iter().map(|constraint| constraint.terms_total()).sum();
+    assert_eq!(terms, total);
+
+    // 3 * 0 + 3 = 3 + 0.
+    let mut values = values!(x => 3u8.into(), y => 0u8.into());
+    assert!(gadget.execute(&mut values));
+    assert!(gadget.clone_constraints_only().check(&values));
+}
@@ -0,0 +1,63 @@
+//! Builds and executes gadgets from a `no_std` crate which has only `alloc`, to check that the
+//! public API doesn't require std of its users. CI runs this with `--no-default-features`, so the
+//! library itself is built without std or rand, which is the configuration embedded users rely on.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use r1cs::{Element, Expression, F257, GadgetBuilder, values, Wire};
+
+fn expressions(wires: &[Wire]) -> Vec<Expression<F257>> {
+    wires.iter().map(Expression::from).collect()
+}
+
+#[test]
+fn field_arithmetic() {
+    let mut builder = GadgetBuilder::<F257>::new();
+    let (x, y) = (builder.wire(), builder.wire());
+    let (x_exp, y_exp) = (Expression::from(x), Expression::from(y));
+    let quotient = builder.quotient(&x_exp, &y_exp);
+    let cube = builder.exponentiation(&x_exp, &Element::from(3u8));
+    let gadget = builder.build();
+
+    let mut values = values!(x => 12u8.into(), y => 5u8.into());
+    assert!(gadget.execute(&mut values));
+    assert_eq!(Element::from(12u8), quotient.evaluate(&values) * Element::from(5u8));
+    assert_eq!(Element::from(1728u16 % 257), cube.evaluate(&values));
+}
+
+#[test]
+fn comparisons() {
+    let mut builder = GadgetBuilder::<F257>::new();
+    let (x, y) = (builder.wire(), builder.wire());
+    let lt = builder.lt(&Expression::from(x), &Expression::from(y));
+    let gadget = builder.build();
+
+    for &(x_value, y_value) in &[(3u8, 200u8), (200, 3), (42, 42)] {
+        let mut values = values!(x => x_value.into(), y => y_value.into());
+        assert!(gadget.execute(&mut values));
+        assert_eq!(x_value < y_value, lt.evaluate(&values));
+    }
+}
+
+#[test]
+fn permutations() {
+    let mut builder = GadgetBuilder::<F257>::new();
+    let (a, b) = (builder.wires(4), builder.wires(4));
+    builder.assert_permutation(&expressions(&a), &expressions(&b));
+    let gadget = builder.build();
+
+    let assign = |a_values: [u8; 4], b_values: [u8; 4]| {
+        let mut values = values!();
+        for i in 0..4 {
+            values.set(a[i], a_values[i].into());
+            values.set(b[i], b_values[i].into());
+        }
+        values
+    };
+    assert!(gadget.execute(&mut assign([1, 2, 3, 4], [3, 1, 4, 2])));
+    assert!(gadget.execute(&mut assign([5, 5, 7, 9], [9, 5, 7, 5])));
+}
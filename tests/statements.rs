@@ -0,0 +1,170 @@
+//! End-to-end tests of the prepackaged statements, which populate each statement's inputs, execute
+//! it, and check the public portion of the resulting assignment.
+
+// These use `CachedFactory`, which needs std, and MiMC's default round constants, which are derived
+// with the rand feature.
+#![cfg(all(feature = "std", feature = "rand"))]
+
+mod common;
+
+use r1cs::{CachedFactory, DaviesMeyer, Element, GadgetFactory, HashFunction, MerkleDamgard,
+           merkle_membership_statement, MerkleMembershipFactory, MiMCBlockCipher,
+           preimage_statement, values, Wire, WireValues};
+
+use common::{compress, F127, merkle_layers};
+
+const DEPTH: usize = 3;
+
+fn hash() -> MerkleDamgard<F127, DaviesMeyer<F127, MiMCBlockCipher<F127>>> {
+    MerkleDamgard::new_defaults(compress())
+}
+
+#[test]
+fn preimage_public_digest() {
+    let (gadget, handles) = preimage_statement(&hash(), true);
+    let preimage_wire = handles.input("preimage")[0];
+    let digest_wire = handles.input("digest")[0];
+    assert_eq!(vec![digest_wire], handles.public_wires.iter().cloned().collect::<Vec<Wire>>());
+
+    let preimage = Element::from(123_456_789u64);
+    let digest = hash().hash_evaluate(std::slice::from_ref(&preimage));
+    let mut values = values!(preimage_wire => preimage, digest_wire => digest.clone());
+    assert!(gadget.execute(&mut values));
+
+    // The digest should be the first variable after the constant one.
+    let order = gadget.variable_order(&handles.public_wires);
+    assert_eq!(&[Wire::ONE, digest_wire], &order[..2]);
+    let assignment = gadget.assignment_vector(&values, &handles.public_wires).unwrap();
+    assert_eq!(order.len(), assignment.len());
+    assert_eq!(&[Element::one(), digest], &assignment[..2]);
+}
+
+#[test]
+fn preimage_wrong_digest() {
+    let (gadget, handles) = preimage_statement(&hash(), true);
+    let preimage = Element::from(123_456_789u64);
+    let wrong_digest = hash().hash_evaluate(std::slice::from_ref(&preimage)) + Element::one();
+    let mut values = values!(
+        handles.input("preimage")[0] => preimage,
+        handles.input("digest")[0] => wrong_digest);
+    assert!(!gadget.execute(&mut values));
+}
+
+#[test]
+fn preimage_private_digest() {
+    let (gadget, handles) = preimage_statement(&hash(), false);
+    assert!(handles.public_wires.is_empty());
+
+    let preimage = Element::from(42u8);
+    let mut values = values!(
+        handles.input("preimage")[0] => preimage.clone(),
+        handles.input("digest")[0] => hash().hash_evaluate(&[preimage]));
+    assert!(gadget.execute(&mut values));
+    assert_eq!(Wire::ONE, gadget.variable_order(&handles.public_wires)[0]);
+}
+
+#[test]
+fn statement_missing_inputs() {
+    let (gadget, handles) = preimage_statement(&hash(), true);
+    let mut expected: Vec<Wire> = handles.inputs.values().flatten().cloned().collect();
+    expected.sort();
+    assert_eq!(expected, gadget.missing_inputs(&WireValues::new()));
+
+    let (gadget, handles) = merkle_membership_statement(3, &compress());
+    let mut expected: Vec<Wire> = handles.inputs.values().flatten().cloned().collect();
+    expected.sort();
+    assert_eq!(expected, gadget.missing_inputs(&WireValues::new()));
+}
+
+/// Computes a complete set of input values for proving membership of the leaf at `position`, but
+/// with the position bits set to `claimed_position`.
+fn merkle_witness(
+    handles: &r1cs::StatementHandles, layers: &[Vec<Element<F127>>], position: usize,
+    claimed_position: usize,
+) -> WireValues<F127> {
+    let mut values = values!(
+        handles.input("leaf")[0] => layers[0][position].clone(),
+        handles.input("root")[0] => layers[DEPTH][0].clone());
+    for (i, &bit) in handles.input("position").iter().enumerate() {
+        values.set(bit, Element::from((claimed_position >> i) & 1));
+    }
+    for (layer, &sibling) in handles.input("siblings").iter().enumerate() {
+        values.set(sibling, layers[layer][(position >> layer) ^ 1].clone());
+    }
+    values
+}
+
+#[test]
+fn merkle_membership() {
+    let (gadget, handles) = merkle_membership_statement(DEPTH, &compress());
+    let root_wire = handles.input("root")[0];
+    assert_eq!(DEPTH, handles.input("position").len());
+    assert_eq!(DEPTH, handles.input("siblings").len());
+
+    let leaves = (0..1usize << DEPTH).map(|i| Element::from(i * 1000 + 7)).collect();
+    let layers = merkle_layers(leaves);
+    let position = 6;
+    let mut values = merkle_witness(&handles, &layers, position, position);
+    assert!(gadget.execute(&mut values));
+
+    let order = gadget.variable_order(&handles.public_wires);
+    assert_eq!(&[Wire::ONE, root_wire], &order[..2]);
+    let assignment = gadget.assignment_vector(&values, &handles.public_wires).unwrap();
+    assert_eq!(order.len(), assignment.len());
+    assert_eq!(layers[DEPTH][0], assignment[1]);
+}
+
+#[test]
+fn merkle_membership_wrong_position() {
+    let (gadget, handles) = merkle_membership_statement(DEPTH, &compress());
+    let leaves = (0..1usize << DEPTH).map(|i| Element::from(i * 1000 + 7)).collect();
+    let layers = merkle_layers(leaves);
+
+    // Claim the leaf is at position 7 instead, without changing the siblings.
+    let mut values = merkle_witness(&handles, &layers, 6, 7);
+    assert!(!gadget.execute(&mut values));
+}
+
+#[test]
+fn cached_merkle_membership() {
+    let factory = CachedFactory::new(MerkleMembershipFactory::new(DEPTH, compress()));
+    assert!(!factory.is_built());
+    let (first, _handles, _values) = factory.instance();
+    let (second, handles, _values) = factory.instance();
+    assert!(factory.is_built());
+    assert_eq!(first.fingerprint(), second.fingerprint());
+    let (fresh, _handles) = factory.factory().build_gadget();
+    assert_eq!(first.fingerprint(), fresh.fingerprint());
+
+    // Witness generation works on the shared instance.
+    let leaves = (0..1usize << DEPTH).map(|i| Element::from(i * 1000 + 7)).collect();
+    let layers = merkle_layers(leaves);
+    let mut witness = merkle_witness(&handles, &layers, 5, 5);
+    assert!(second.execute(&mut witness));
+    assert!(!second.execute(&mut merkle_witness(&handles, &layers, 5, 4)));
+}
+
+#[test]
+fn cached_merkle_membership_concurrent() {
+    let factory = CachedFactory::new(MerkleMembershipFactory::new(DEPTH, compress()));
+    let leaves = (0..1usize << DEPTH).map(|i| Element::from(i * 1000 + 7)).collect();
+    let layers = merkle_layers(leaves);
+
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..4)
+            .map(|position| {
+                let (factory, layers) = (&factory, &layers);
+                scope.spawn(move || {
+                    let (gadget, handles, _values) = factory.instance();
+                    let mut values = merkle_witness(&handles, layers, position, position);
+                    (gadget.fingerprint(), gadget.execute(&mut values))
+                })
+            })
+            .collect();
+        let results: Vec<(u64, bool)> =
+            threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        assert!(results.iter().all(|&(fingerprint, satisfied)| {
+            fingerprint == results[0].0 && satisfied
+        }));
+    });
+}
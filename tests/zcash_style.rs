@@ -0,0 +1,149 @@
+//! An end-to-end example which assembles a minimal shielded-transfer statement, in the style of
+//! Zcash, from the primitives in this crate.
+//!
+//! The prover demonstrates knowledge of a `value`, some commitment `randomness`, a `secret_key`,
+//! and a Merkle path with leaf `position`, such that:
+//!
+//! - `commitment = H(value, randomness)` is a leaf of the Merkle tree with the public `root`,
+//! - `nullifier = H(secret_key, position)` matches the public nullifier, and
+//! - `value < 2^64`.
+//!
+//! Here `H` is MiMC turned into a one-way compression function with Davies-Meyer. The same
+//! compression function is used for the Merkle tree. Witnesses are computed out-of-circuit with
+//! `CompressionFunction::compress_evaluate`, then fed to the gadget as inputs.
+
+// MiMC's default round constants are derived with the rand feature.
+#![cfg(feature = "rand")]
+
+mod common;
+
+use num::BigUint;
+
+use r1cs::{BinaryExpression, BinaryWire, CompressionFunction, Element, Expression, Field, Gadget,
+           GadgetBuilder, MerklePath, values, Wire, WireValues};
+
+use common::{compress, F127, merkle_layers};
+
+/// The number of bits used to encode a note's value.
+const VALUE_BITS: usize = 64;
+
+/// The depth of our example Merkle tree, which therefore has `2^DEPTH` leaves.
+const DEPTH: usize = 4;
+
+/// Handles to the input wires of a shielded-transfer gadget.
+struct ShieldedTransferWires {
+    /// The public Merkle root of the note commitment tree.
+    root: Wire,
+    /// The public nullifier, which prevents the note from being spent twice.
+    nullifier: Wire,
+    /// The (private) value of the note being spent.
+    value: Wire,
+    /// The (private) randomness used to hide the note's value in its commitment.
+    randomness: Wire,
+    /// The (private) key of the note's owner.
+    secret_key: Wire,
+    /// The (private) position of the note commitment in the tree, least significant bit first.
+    position: BinaryWire,
+    /// The (private) siblings encountered along the path from the commitment to the root.
+    siblings: Vec<Wire>,
+}
+
+/// Builds the shielded-transfer statement for a tree of the given depth.
+fn shielded_transfer_gadget<F: Field, CF: CompressionFunction<F>>(
+    depth: usize, compress: &CF,
+) -> (Gadget<F>, ShieldedTransferWires) {
+    let mut builder = GadgetBuilder::<F>::new();
+    let root = builder.wire();
+    let nullifier = builder.wire();
+    let value = builder.wire();
+    let randomness = builder.wire();
+    let secret_key = builder.wire();
+    let position = builder.binary_wire(depth);
+    let siblings = builder.wires(depth);
+
+    // Range check the value. Splitting it into exactly VALUE_BITS bits implies value < 2^64.
+    builder.split_bounded(&Expression::from(value), VALUE_BITS);
+
+    // Recompute the note commitment, and check that it is a member of the tree.
+    let commitment = compress.compress(
+        &mut builder, &Expression::from(value), &Expression::from(randomness));
+    let position_exp = BinaryExpression::from(&position);
+    let path = MerklePath::new(
+        position_exp.clone(), siblings.iter().map(Expression::from).collect());
+    builder.assert_merkle_tree_membership(
+        &commitment, &Expression::from(root), &path, compress);
+
+    // Recompute the nullifier, and check that it matches the public one.
+    let computed_nullifier = compress.compress(
+        &mut builder, &Expression::from(secret_key), &position_exp.join());
+    builder.assert_equal(&computed_nullifier, &Expression::from(nullifier));
+
+    let wires = ShieldedTransferWires {
+        root, nullifier, value, randomness, secret_key, position, siblings,
+    };
+    (builder.build(), wires)
+}
+
+/// Computes a complete set of input values for spending the note at `position`.
+fn witness(
+    wires: &ShieldedTransferWires, layers: &[Vec<Element<F127>>], position: usize,
+    value: u64, randomness: &Element<F127>, secret_key: &Element<F127>,
+) -> WireValues<F127> {
+    let compress = compress();
+    let root = layers.last().unwrap()[0].clone();
+    let nullifier = compress.compress_evaluate(secret_key, &Element::from(position));
+
+    let mut values = values!(
+        wires.root => root,
+        wires.nullifier => nullifier,
+        wires.value => Element::from(value),
+        wires.randomness => randomness.clone(),
+        wires.secret_key => secret_key.clone());
+    values.set_binary_unsigned(&wires.position, &BigUint::from(position));
+    for (layer, &sibling) in wires.siblings.iter().enumerate() {
+        let index = position >> layer;
+        values.set(sibling, layers[layer][index ^ 1].clone());
+    }
+    values
+}
+
+/// A tree containing our note at `position`, with arbitrary filler leaves elsewhere.
+fn tree_with_note(commitment: Element<F127>, position: usize) -> Vec<Vec<Element<F127>>> {
+    let mut leaves: Vec<Element<F127>> = (0..1usize << DEPTH).map(Element::from).collect();
+    leaves[position] = commitment;
+    merkle_layers(leaves)
+}
+
+#[test]
+fn shielded_transfer() {
+    let (gadget, wires) = shielded_transfer_gadget::<F127, _>(DEPTH, &compress());
+
+    let value = 1_000_000u64;
+    let randomness = Element::from(123_456_789u64);
+    let secret_key = Element::from(987_654_321u64);
+    let position = 5;
+    let commitment = compress().compress_evaluate(&Element::from(value), &randomness);
+    let layers = tree_with_note(commitment, position);
+
+    let mut values = witness(&wires, &layers, position, value, &randomness, &secret_key);
+    assert!(gadget.execute(&mut values));
+}
+
+#[test]
+fn shielded_transfer_wrong_root() {
+    let (gadget, wires) = shielded_transfer_gadget::<F127, _>(DEPTH, &compress());
+
+    let value = 1_000_000u64;
+    let randomness = Element::from(123_456_789u64);
+    let secret_key = Element::from(987_654_321u64);
+    let position = 5;
+    let commitment = compress().compress_evaluate(&Element::from(value), &randomness);
+    let mut layers = tree_with_note(commitment, position);
+
+    // Replace the true root with some other value.
+    let wrong_root = &layers[DEPTH][0] + Element::one();
+    layers[DEPTH][0] = wrong_root;
+
+    let mut values = witness(&wires, &layers, position, value, &randomness, &secret_key);
+    assert!(!gadget.execute(&mut values));
+}